@@ -0,0 +1,266 @@
+use anyhow::{anyhow, Result};
+use crossbeam_channel::{bounded, Receiver, Sender};
+use gst::prelude::*;
+use gst_audio::{AudioFormat, AudioInfo};
+use gstreamer as gst;
+use gstreamer_app as gst_app;
+use gstreamer_audio as gst_audio;
+
+use scribl_curves::{Cursor, Time};
+
+use super::{create_gst_elt, Limiter, OutputData, SAMPLE_RATE};
+
+/// When exporting, audio gets mixed in chunks of this many samples (a quarter of a second).
+const EXPORT_CHUNK: usize = SAMPLE_RATE as usize / 4;
+
+/// The number of mixed chunks that can be waiting for the export pipeline to take them.
+const EXPORT_QUEUE_LEN: usize = 16;
+
+/// Converts samples into a gstreamer buffer that starts at sample index `idx`.
+fn make_buffer(samples: &[i16], idx: usize) -> Result<gst::Buffer> {
+    let mut gst_buffer = gst::Buffer::with_size(samples.len() * 2)?;
+    {
+        let gst_buffer_ref = gst_buffer
+            .get_mut()
+            .ok_or(anyhow!("couldn't get mut buffer"))?;
+        let start = Time::from_audio_idx(idx, SAMPLE_RATE);
+        let end = Time::from_audio_idx(idx + samples.len(), SAMPLE_RATE);
+        gst_buffer_ref.set_pts(start.as_gst_clock_time());
+        gst_buffer_ref.set_duration(gst::ClockTime::from_useconds(
+            (end - start).as_micros() as u64
+        ));
+        let mut gst_buf = gst_buffer_ref.map_writable()?;
+        for (sample, bytes) in samples.iter().zip(gst_buf.as_mut_slice().chunks_mut(2)) {
+            bytes.copy_from_slice(&sample.to_le_bytes());
+        }
+    }
+    Ok(gst_buffer)
+}
+
+/// Mixes all of the audio, from the beginning, and sends it in chunks to `tx`. This blocks
+/// whenever the channel is full, and stops when the audio is finished or the receiver goes away.
+fn mix_for_export(data: OutputData, mut limiter: Option<Limiter>, tx: Sender<gst::Buffer>) {
+    let end = data.end_idx();
+    let mut cursor = Cursor::new(data.snips.snippet_spans(), 0, 0);
+    let mut buf = vec![0i16; EXPORT_CHUNK];
+    // Even if there's no audio, we send one chunk of silence: ending the stream immediately
+    // causes corrupted files.
+    loop {
+        let prev_end = cursor.current().1;
+        cursor.advance_to(prev_end, prev_end + EXPORT_CHUNK);
+        buf.iter_mut().for_each(|x| *x = 0);
+        data.mix_to(&cursor, &mut buf);
+        if let Some(limiter) = &mut limiter {
+            limiter.process(&mut buf);
+        }
+        let len = EXPORT_CHUNK.min(end.saturating_sub(prev_end)).max(1);
+        match make_buffer(&buf[..len], prev_end) {
+            Ok(gst_buffer) => {
+                if tx.send(gst_buffer).is_err() {
+                    return;
+                }
+            }
+            Err(e) => {
+                log::error!("error synthesizing audio: {}", e);
+                return;
+            }
+        }
+        if cursor.current().1 >= end {
+            return;
+        }
+    }
+}
+
+/// Creates a gstreamer AppSrc element that provides the audio for an export.
+///
+/// Unlike the one from [`create_appsrc`], which mixes audio whenever gstreamer asks for more, this
+/// one mixes audio ahead of time on a separate thread. The pipeline only needs to take mixed
+/// audio from a queue, so slow mixing (with lots of overlapping snippets, say) doesn't hold up the
+/// video.
+///
+/// If there's a limiter, it gets applied to the mixed audio.
+pub fn create_export_appsrc(
+    data: OutputData,
+    limiter: Option<Limiter>,
+    name: &str,
+) -> Result<gst::Element> {
+    let src = create_gst_elt("appsrc", name)?;
+    let src = src
+        .dynamic_cast::<gst_app::AppSrc>()
+        .map_err(|_| anyhow!("bug: couldn't cast src to an AppSrc"))?;
+    let audio_info = AudioInfo::builder(AudioFormat::S16le, SAMPLE_RATE as u32, 1).build()?;
+    src.set_caps(Some(&audio_info.to_caps()?));
+    src.set_format(gst::Format::Time);
+    src.set_stream_type(gst_app::AppStreamType::Stream);
+
+    let (tx, rx) = bounded(EXPORT_QUEUE_LEN);
+    std::thread::Builder::new()
+        .name("export-audio".to_owned())
+        .spawn(move || mix_for_export(data, limiter, tx))?;
+
+    // The receiver only gets disconnected after the mixer has sent everything.
+    let need_data = move |src: &gst_app::AppSrc, _size_hint: u32| match rx.recv() {
+        Ok(buf) => {
+            let _ = src.push_buffer(buf);
+        }
+        Err(_) => {
+            let _ = src.end_of_stream();
+        }
+    };
+    src.set_callbacks(
+        gst_app::AppSrcCallbacks::builder()
+            .need_data(need_data)
+            .build(),
+    );
+    Ok(src.upcast::<gst::Element>())
+}
+
+/// Creates a gstreamer AppSrc element that mixes our audio and provides it to a gstreamer
+/// pipeline.
+pub fn create_appsrc(rx: Receiver<OutputData>, name: &str) -> Result<gst::Element> {
+    let src = create_gst_elt("appsrc", name)?;
+    let src = src
+        .dynamic_cast::<gst_app::AppSrc>()
+        .map_err(|_| anyhow!("bug: couldn't cast src to an AppSrc"))?;
+    let audio_info = AudioInfo::builder(AudioFormat::S16le, SAMPLE_RATE as u32, 1).build()?;
+    src.set_caps(Some(&audio_info.to_caps()?));
+    src.set_format(gst::Format::Time);
+    src.set_stream_type(gst_app::AppStreamType::RandomAccess);
+
+    let mut data = OutputData::new();
+    let mut cursor = Cursor::empty(0);
+    let mut need_audio_data_inner =
+        move |src: &gst_app::AppSrc, size_hint: u32| -> anyhow::Result<()> {
+            for new_data in rx.try_iter() {
+                data = new_data;
+                let idx = data.start_time.as_audio_idx(SAMPLE_RATE);
+                cursor = Cursor::new(data.snips.snippet_spans(), idx, idx);
+            }
+            // When playing forwards, if there's no audio then don't end the stream immediately:
+            // it causes corrupted files when encoding.
+            if (data.forwards()
+                && cursor.is_finished()
+                && cursor.current() != (0, 0)
+                && cursor.current().1 >= data.end_idx())
+                || (!data.forwards() && cursor.current().1 == 0)
+            {
+                let _ = src.end_of_stream();
+                return Ok(());
+            }
+
+            let size = size_hint as usize / 2;
+
+            // gstreamer buffers seem to only ever hand out [u8], but we prefer to work with
+            // [i16]s. Here, we're doing an extra copy to handle endian-ness and avoid unsafe.
+            let mut buf = vec![0i16; size];
+            if data.forwards() {
+                let prev_end = cursor.current().1;
+                cursor.advance_to(prev_end, prev_end + buf.len());
+            } else {
+                let prev_start = cursor.current().0;
+                cursor.advance_to(prev_start.saturating_sub(buf.len()), prev_start);
+            }
+            data.mix_to(&cursor, &mut buf[..]);
+            let time = Time::from_audio_idx(cursor.current().0, SAMPLE_RATE);
+
+            let mut gst_buffer = gst::Buffer::with_size(size * 2)?;
+            {
+                let gst_buffer_ref = gst_buffer
+                    .get_mut()
+                    .ok_or(anyhow!("couldn't get mut buffer"))?;
+
+                let time = if data.forwards() {
+                    time
+                } else {
+                    data.start_time + (data.start_time - time)
+                };
+                gst_buffer_ref.set_pts(gst::ClockTime::from_useconds(time.as_micros() as u64));
+                let mut gst_buf = gst_buffer_ref.map_writable()?;
+                if data.forwards() {
+                    for (idx, bytes) in gst_buf.as_mut_slice().chunks_mut(2).enumerate() {
+                        bytes.copy_from_slice(&buf[idx].to_le_bytes());
+                    }
+                } else {
+                    for (idx, bytes) in gst_buf.as_mut_slice().chunks_mut(2).rev().enumerate() {
+                        bytes.copy_from_slice(&buf[idx].to_le_bytes());
+                    }
+                }
+            }
+            let _ = src.push_buffer(gst_buffer);
+            Ok(())
+        };
+
+    let need_audio_data = move |src: &gst_app::AppSrc, size_hint: u32| {
+        if let Err(e) = need_audio_data_inner(src, size_hint) {
+            log::error!("error synthesizing audio: {}", e);
+        }
+    };
+
+    // The seek callback doesn't actually do anything. That's because we reset the cursor position
+    // in `start_playing` anyway, and that's the only meaningful seek that ever happens.
+    let seek = move |_src: &gst_app::AppSrc, _arg: u64| -> bool { true };
+
+    src.set_callbacks(
+        gst_app::AppSrcCallbacks::builder()
+            .need_data(need_audio_data)
+            .seek_data(seek)
+            .build(),
+    );
+    Ok(src.upcast::<gst::Element>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{TalkSnippet, TalkSnippets};
+
+    // Runs `mix_for_export` to completion, and returns the lengths of the chunks it sent, along
+    // with all of their samples.
+    fn export(data: OutputData) -> (Vec<usize>, Vec<i16>) {
+        gst::init().unwrap();
+        let (tx, rx) = crossbeam_channel::unbounded();
+        mix_for_export(data, None, tx);
+
+        let mut lens = Vec::new();
+        let mut samples = Vec::new();
+        for buf in rx.iter() {
+            let map = buf.map_readable().unwrap();
+            lens.push(map.as_slice().len() / 2);
+            samples.extend(
+                map.as_slice()
+                    .chunks(2)
+                    .map(|b| i16::from_le_bytes([b[0], b[1]])),
+            );
+        }
+        (lens, samples)
+    }
+
+    #[test]
+    fn export_in_chunks() {
+        let len = 2 * EXPORT_CHUNK + 100;
+        let buf: Vec<i16> = (0..len).map(|i| (i % 1000) as i16).collect();
+        let snips = TalkSnippets::default()
+            .with_new_snippet(TalkSnippet::new(buf, Time::ZERO, 1.0))
+            .0;
+        let data = OutputData {
+            crossfades: snips.crossfades(),
+            snips,
+            ..OutputData::new()
+        };
+
+        let cursor = Cursor::new(data.snips.snippet_spans(), 0, len);
+        let mut expected = vec![0; len];
+        data.mix_to(&cursor, &mut expected);
+
+        let (lens, samples) = export(data);
+        assert_eq!(lens, vec![EXPORT_CHUNK, EXPORT_CHUNK, 100]);
+        assert_eq!(samples, expected);
+    }
+
+    #[test]
+    fn export_silence() {
+        let (lens, samples) = export(OutputData::new());
+        assert_eq!(lens, vec![1]);
+        assert_eq!(samples, vec![0]);
+    }
+}
@@ -0,0 +1,278 @@
+use memmap2::{Mmap, MmapOptions};
+use serde::de::{Deserializer, Error as _, SeqAccess, Visitor};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use super::SAMPLE_RATE;
+
+/// Buffers with at least this many samples (five minutes of audio) get moved out of memory and
+/// into a memory-mapped temporary file.
+const MAP_THRESHOLD: usize = SAMPLE_RATE as usize * 300;
+
+// The directory for the memory-mapped files, or `None` for the system's temporary directory.
+static MAP_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Sets the directory where long audio buffers get memory-mapped.
+///
+/// By default, they go in the system's temporary directory. But that's often a RAM-backed tmpfs,
+/// in which case mapping the samples doesn't save any memory.
+pub fn set_map_dir(dir: Option<PathBuf>) {
+    *MAP_DIR.lock().unwrap() = dir;
+}
+
+enum Storage {
+    Memory(Box<[i16]>),
+    // The file behind the map is an anonymous temporary file that nobody else can see, so its
+    // contents never change.
+    Mapped { map: Mmap, len: usize },
+}
+
+/// A buffer of audio samples.
+///
+/// The samples are behind a reference-counted pointer, so this is cheap to clone (and undo states
+/// share their audio with the current state). Long buffers are stored in a memory-mapped file
+/// instead of in memory, so that the operating system can page them out when they aren't being
+/// used; that way, hour-long narrations don't need an hour's worth of RAM.
+#[derive(Clone)]
+pub struct AudioBuf(Arc<Storage>);
+
+impl AudioBuf {
+    /// Do these buffers share their samples?
+    pub fn ptr_eq(&self, other: &AudioBuf) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Is this buffer stored in a memory-mapped file?
+    pub fn is_mapped(&self) -> bool {
+        matches!(*self.0, Storage::Mapped { .. })
+    }
+
+    /// The number of bytes of memory used by this buffer. Memory-mapped buffers don't count,
+    /// because the operating system can page them out whenever it wants.
+    pub fn memory_size(&self) -> usize {
+        match *self.0 {
+            Storage::Memory(ref buf) => buf.len() * std::mem::size_of::<i16>(),
+            Storage::Mapped { .. } => 0,
+        }
+    }
+
+    fn mapped(samples: &[i16]) -> std::io::Result<AudioBuf> {
+        let mut writer = MapWriter::new()?;
+        for &sample in samples {
+            writer.push(sample)?;
+        }
+        writer.finish()
+    }
+
+    fn in_memory(samples: Vec<i16>) -> AudioBuf {
+        AudioBuf(Arc::new(Storage::Memory(samples.into_boxed_slice())))
+    }
+}
+
+// Writes samples to a temporary file, which gets memory-mapped once they're all written.
+struct MapWriter {
+    writer: BufWriter<File>,
+    len: usize,
+}
+
+impl MapWriter {
+    fn new() -> std::io::Result<MapWriter> {
+        let dir = MAP_DIR.lock().unwrap().clone();
+        // The file doesn't need a name: unlike the things in the cache directory, it can't be
+        // deleted to make space, because it holds audio that's in use.
+        let file = match dir {
+            Some(dir) => {
+                std::fs::create_dir_all(&dir)?;
+                tempfile::tempfile_in(dir)?
+            }
+            None => tempfile::tempfile()?,
+        };
+        Ok(MapWriter {
+            writer: BufWriter::new(file),
+            len: 0,
+        })
+    }
+
+    fn push(&mut self, sample: i16) -> std::io::Result<()> {
+        self.len += 1;
+        self.writer.write_all(&sample.to_ne_bytes())
+    }
+
+    fn finish(self) -> std::io::Result<AudioBuf> {
+        let file = self.writer.into_inner().map_err(|e| e.into_error())?;
+        // Safety: nobody else has access to the file, so it can't change underneath us.
+        let map = unsafe { MmapOptions::new().map(&file)? };
+        Ok(AudioBuf(Arc::new(Storage::Mapped { map, len: self.len })))
+    }
+}
+
+impl Deref for AudioBuf {
+    type Target = [i16];
+
+    fn deref(&self) -> &[i16] {
+        match *self.0 {
+            Storage::Memory(ref buf) => buf,
+            // Safety: the map is page-aligned (so it's aligned for i16), it contains `len`
+            // samples, and it lives as long as `self`.
+            Storage::Mapped { ref map, len } => unsafe {
+                std::slice::from_raw_parts(map.as_ptr() as *const i16, len)
+            },
+        }
+    }
+}
+
+impl From<Vec<i16>> for AudioBuf {
+    fn from(samples: Vec<i16>) -> AudioBuf {
+        if samples.len() >= MAP_THRESHOLD {
+            match AudioBuf::mapped(&samples) {
+                Ok(buf) => return buf,
+                Err(e) => log::warn!("failed to map audio, keeping it in memory: {}", e),
+            }
+        }
+        AudioBuf::in_memory(samples)
+    }
+}
+
+impl PartialEq for AudioBuf {
+    fn eq(&self, other: &AudioBuf) -> bool {
+        self.ptr_eq(other) || self.deref() == other.deref()
+    }
+}
+
+#[cfg(feature = "druid")]
+impl druid::Data for AudioBuf {
+    fn same(&self, other: &AudioBuf) -> bool {
+        self.ptr_eq(other)
+    }
+}
+
+// This has the same format as a plain list of samples, so that save files don't depend on how
+// the samples are stored.
+impl Serialize for AudioBuf {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        self.deref().serialize(ser)
+    }
+}
+
+impl<'de> Deserialize<'de> for AudioBuf {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<AudioBuf, D::Error> {
+        de.deserialize_seq(SamplesVisitor {
+            threshold: MAP_THRESHOLD,
+        })
+    }
+}
+
+// Reads a list of samples. Once there are `threshold` of them, the rest get written straight to a
+// memory-mapped file, so that loading a long narration doesn't need all of it in memory at once.
+struct SamplesVisitor {
+    threshold: usize,
+}
+
+impl<'de> Visitor<'de> for SamplesVisitor {
+    type Value = AudioBuf;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a list of audio samples")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<AudioBuf, A::Error> {
+        let capacity = seq.size_hint().unwrap_or(0).min(self.threshold);
+        let mut samples = Vec::with_capacity(capacity);
+        while samples.len() < self.threshold {
+            match seq.next_element()? {
+                Some(sample) => samples.push(sample),
+                None => return Ok(AudioBuf::in_memory(samples)),
+            }
+        }
+
+        let mut writer = match MapWriter::new() {
+            Ok(w) => w,
+            Err(e) => {
+                log::warn!("failed to map audio, keeping it in memory: {}", e);
+                while let Some(sample) = seq.next_element()? {
+                    samples.push(sample);
+                }
+                return Ok(AudioBuf::in_memory(samples));
+            }
+        };
+        let write_err = |e: std::io::Error| A::Error::custom(format!("failed to map audio: {}", e));
+        for sample in samples {
+            writer.push(sample).map_err(write_err)?;
+        }
+        while let Some(sample) = seq.next_element()? {
+            writer.push(sample).map_err(write_err)?;
+        }
+        writer.finish().map_err(write_err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapped() {
+        let samples: Vec<i16> = (0..10_000).map(|x| (x * 7) as i16).collect();
+        let buf = AudioBuf::mapped(&samples).unwrap();
+        assert!(buf.is_mapped());
+        assert_eq!(&*buf, &samples[..]);
+        assert_eq!(buf.memory_size(), 0);
+        assert!(buf == AudioBuf::from(samples));
+    }
+
+    #[test]
+    fn small_buffers_stay_in_memory() {
+        let buf = AudioBuf::from(vec![1, 2, 3]);
+        assert!(!buf.is_mapped());
+        assert_eq!(buf.memory_size(), 6);
+        assert!(buf.ptr_eq(&buf.clone()));
+        assert!(!buf.ptr_eq(&AudioBuf::from(vec![1, 2, 3])));
+    }
+
+    #[test]
+    fn serialization() {
+        let samples = vec![1i16, -2, 3];
+        let arc: Arc<[i16]> = samples.clone().into();
+        let bytes = serde_cbor::to_vec(&AudioBuf::mapped(&samples).unwrap()).unwrap();
+        assert_eq!(bytes, serde_cbor::to_vec(&arc).unwrap());
+        let buf: AudioBuf = serde_cbor::from_slice(&bytes).unwrap();
+        assert_eq!(&*buf, &samples[..]);
+    }
+
+    #[test]
+    fn deserialize_long() {
+        let samples: Vec<i16> = (0..1000).map(|x| (x * 3) as i16).collect();
+        let bytes = serde_cbor::to_vec(&samples).unwrap();
+        let visit = |threshold| {
+            let mut de = serde_cbor::Deserializer::from_slice(&bytes);
+            de.deserialize_seq(SamplesVisitor { threshold }).unwrap()
+        };
+
+        // Long buffers get mapped without collecting them in memory first.
+        for &threshold in &[1, 999, 1000] {
+            let buf = visit(threshold);
+            assert!(buf.is_mapped());
+            assert_eq!(&*buf, &samples[..]);
+        }
+        let buf = visit(1001);
+        assert!(!buf.is_mapped());
+        assert_eq!(&*buf, &samples[..]);
+    }
+
+    #[test]
+    fn map_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let map_dir = dir.path().join("audio");
+        set_map_dir(Some(map_dir.clone()));
+        let buf = AudioBuf::mapped(&[1, 2, 3]).unwrap();
+        set_map_dir(None);
+        assert_eq!(&*buf, &[1, 2, 3]);
+        // The directory gets created, but the file in it doesn't have a name.
+        assert!(map_dir.is_dir());
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::{anyhow, Result};
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use gstreamer_app as gst_app;
+use gstreamer_audio::{AudioFormat, AudioInfo};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use super::{create_gst_elt, SAMPLE_RATE};
+
+/// Decodes an audio file (in any format that gstreamer understands) into a buffer of mono audio at
+/// our internal sample rate.
+///
+/// Audio files can have any sample rate, and they get resampled to ours. This blocks until the
+/// whole file is decoded, so it should be called from a separate thread.
+pub fn decode_file(path: &Path) -> Result<Vec<i16>> {
+    let pipeline = gst::Pipeline::new(None);
+    let src = create_gst_elt("filesrc", "decode-source")?;
+    let decode = create_gst_elt("decodebin", "decode-decode")?;
+    let convert = create_gst_elt("audioconvert", "decode-convert")?;
+    let resample = create_gst_elt("audioresample", "decode-resample")?;
+    let sink = create_gst_elt("appsink", "decode-sink")?;
+
+    // Decoding doesn't happen in real time, so we can afford the best resampling quality.
+    resample.set_property("quality", 10i32);
+    src.set_property(
+        "location",
+        path.to_str()
+            .ok_or_else(|| anyhow!("this filename is too weird"))?,
+    );
+    pipeline.add_many(&[&src, &decode, &convert, &resample, &sink])?;
+    src.link(&decode)?;
+    gst::Element::link_many(&[&convert, &resample, &sink])?;
+
+    // The decoder only creates its output pads once it figures out what's in the file. If the
+    // file also contains video, we ignore it.
+    let convert_weak = convert.downgrade();
+    decode.connect_pad_added(move |_, pad| {
+        let is_audio = pad
+            .current_caps()
+            .and_then(|caps| caps.structure(0).map(|s| s.name().starts_with("audio/")))
+            .unwrap_or(false);
+        if let (true, Some(convert)) = (is_audio, convert_weak.upgrade()) {
+            if let Some(sink_pad) = convert.static_pad("sink") {
+                if !sink_pad.is_linked() {
+                    if let Err(e) = pad.link(&sink_pad) {
+                        log::error!("failed to link audio decoder: {:?}", e);
+                    }
+                }
+            }
+        }
+    });
+
+    let sink = sink
+        .dynamic_cast::<gst_app::AppSink>()
+        .map_err(|_| anyhow!("bug: couldn't cast sink to an AppSink"))?;
+    let audio_info = AudioInfo::builder(AudioFormat::S16le, SAMPLE_RATE as u32, 1).build()?;
+    sink.set_caps(Some(&audio_info.to_caps()?));
+    // We want to decode as fast as possible, not in real time.
+    sink.set_sync(false);
+
+    let data = Arc::new(Mutex::new(Vec::new()));
+    let data_clone = Arc::clone(&data);
+    let new_sample = move |sink: &gst_app::AppSink| -> Result<gst::FlowSuccess, gst::FlowError> {
+        let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+        let buffer = sample.buffer().ok_or(gst::FlowError::Error)?;
+        let buffer = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
+        data_clone.lock().unwrap().extend(
+            buffer
+                .as_slice()
+                .chunks_exact(2)
+                .map(|s| i16::from_le_bytes([s[0], s[1]])),
+        );
+        Ok(gst::FlowSuccess::Ok)
+    };
+    sink.set_callbacks(
+        gst_app::AppSinkCallbacks::builder()
+            .new_sample(new_sample)
+            .build(),
+    );
+
+    pipeline.set_state(gst::State::Playing)?;
+    let bus = pipeline
+        .bus()
+        .ok_or_else(|| anyhow!("couldn't get pipeline bus"))?;
+    for msg in bus.iter_timed(gst::ClockTime::NONE) {
+        use gst::MessageView::*;
+
+        match msg.view() {
+            Eos(..) => break,
+            Error(err) => {
+                pipeline.set_state(gst::State::Null)?;
+                return Err(anyhow!(
+                    "failed to decode {}: {}",
+                    path.display(),
+                    err.error()
+                ));
+            }
+            _ => {}
+        }
+    }
+    pipeline.set_state(gst::State::Null)?;
+
+    let buf = std::mem::take(&mut *data.lock().unwrap());
+    if buf.is_empty() {
+        return Err(anyhow!("{} doesn't contain any audio", path.display()));
+    }
+    Ok(buf)
+}
@@ -0,0 +1,180 @@
+//! Finding audio that was accidentally recorded twice.
+//!
+//! This happens when (for example) the microphone gets recorded both as a microphone and as part
+//! of the system audio, or when a snippet gets pasted on top of itself. The two copies play at the
+//! same time, which sounds like an echo.
+
+use scribl_curves::Time;
+
+use super::{TalkSnippet, TalkSnippetId, TalkSnippets, SAMPLE_RATE};
+
+/// We compare the loudness envelopes of the snippets, with one point for every this many samples
+/// (5ms). The envelopes don't care about phase, so they still match if one copy went through a
+/// different audio path.
+const ENVELOPE_WINDOW: usize = SAMPLE_RATE as usize / 200;
+/// Snippets only count as duplicates if they overlap by at least this many envelope points (1s).
+const MIN_OVERLAP: usize = 200;
+/// We look for matches with the copies shifted by up to this many envelope points (250ms).
+const MAX_LAG: usize = 50;
+/// The correlation between the envelopes that counts as being a duplicate.
+const MIN_SIMILARITY: f64 = 0.9;
+
+/// Two audio snippets that overlap and sound the same.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Duplicate {
+    pub original: TalkSnippetId,
+    /// The copy, which is the one that was added to the animation last.
+    pub copy: TalkSnippetId,
+    /// How similar the overlapping parts are, between 0.0 and 1.0.
+    pub similarity: f64,
+}
+
+/// The average volume of each window of samples.
+fn envelope(buf: &[i16]) -> Vec<f64> {
+    buf.chunks(ENVELOPE_WINDOW)
+        .map(|w| w.iter().map(|&x| (x as f64).abs()).sum::<f64>() / w.len() as f64)
+        .collect()
+}
+
+/// The correlation coefficient between `x` and `y`, which must have the same length. If either one
+/// is constant, they're treated as uncorrelated.
+fn correlation(x: &[f64], y: &[f64]) -> f64 {
+    let n = x.len() as f64;
+    let mean_x = x.iter().sum::<f64>() / n;
+    let mean_y = y.iter().sum::<f64>() / n;
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (a, b) in x.iter().zip(y) {
+        cov += (a - mean_x) * (b - mean_y);
+        var_x += (a - mean_x) * (a - mean_x);
+        var_y += (b - mean_y) * (b - mean_y);
+    }
+    if var_x <= f64::EPSILON || var_y <= f64::EPSILON {
+        0.0
+    } else {
+        cov / (var_x * var_y).sqrt()
+    }
+}
+
+/// The loudness envelope of the part of `snip` between `start` and `end`.
+fn envelope_between(snip: &TalkSnippet, start: Time, end: Time) -> Vec<f64> {
+    let idx = |t: Time| {
+        ((t - snip.start_time()).as_audio_idx(SAMPLE_RATE).max(0) as usize).min(snip.buf().len())
+    };
+    envelope(&snip.samples()[idx(start)..idx(end)])
+}
+
+/// The best correlation between the envelopes `a` and `b`, allowing either one to be shifted a
+/// little.
+fn best_correlation(a: &[f64], b: &[f64]) -> f64 {
+    let n = a.len().min(b.len());
+    let max_lag = MAX_LAG.min(n.saturating_sub(MIN_OVERLAP));
+    let mut best = f64::NEG_INFINITY;
+    for lag in 0..=max_lag {
+        best = best
+            .max(correlation(&a[lag..n], &b[..n - lag]))
+            .max(correlation(&a[..n - lag], &b[lag..n]));
+    }
+    best
+}
+
+/// Compares two snippets, returning their similarity if they overlap enough to tell.
+fn similarity(a: &TalkSnippet, b: &TalkSnippet) -> Option<f64> {
+    let start = a.start_time().max(b.start_time());
+    let end = a.end_time().min(b.end_time());
+    if end <= start {
+        return None;
+    }
+    let env_a = envelope_between(a, start, end);
+    let env_b = envelope_between(b, start, end);
+    if env_a.len().min(env_b.len()) < MIN_OVERLAP {
+        return None;
+    }
+    Some(best_correlation(&env_a, &env_b))
+}
+
+/// Finds all the pairs of audio snippets that overlap and have near-identical audio in the
+/// overlapping part. Muted snippets are ignored, since they can't be heard anyway.
+///
+/// This looks at all the audio, so it should be done in the background.
+pub fn find_duplicates(snips: &TalkSnippets) -> Vec<Duplicate> {
+    let snips: Vec<_> = snips.snippets().filter(|(_, s)| !s.muted()).collect();
+    let mut ret = Vec::new();
+    for (i, &(id_a, a)) in snips.iter().enumerate() {
+        for &(id_b, b) in &snips[(i + 1)..] {
+            if let Some(similarity) = similarity(a, b) {
+                if similarity >= MIN_SIMILARITY {
+                    // Snippet ids are assigned in increasing order, so the copy has the bigger one.
+                    ret.push(Duplicate {
+                        original: id_a.min(id_b),
+                        copy: id_a.max(id_b),
+                        similarity,
+                    });
+                }
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Some noise, with the volume going up and down `freq` times per second, like syllables.
+    fn speech(secs: f64, freq: f64, seed: u32) -> Vec<i16> {
+        let mut state = seed;
+        (0..(secs * SAMPLE_RATE as f64) as usize)
+            .map(|i| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let noise = (state >> 16) as f64 / 32768.0 - 1.0;
+                let t = i as f64 / SAMPLE_RATE as f64;
+                let volume = 8000.0 * (std::f64::consts::PI * freq * t).sin().abs();
+                (noise * volume) as i16
+            })
+            .collect()
+    }
+
+    fn snippets(snips: Vec<TalkSnippet>) -> (TalkSnippets, Vec<TalkSnippetId>) {
+        let mut ret = TalkSnippets::default();
+        let mut ids = Vec::new();
+        for s in snips {
+            let (new, id) = ret.with_new_snippet(s);
+            ret = new;
+            ids.push(id);
+        }
+        (ret, ids)
+    }
+
+    #[test]
+    fn duplicates() {
+        let audio = speech(3.0, 3.0, 1);
+        let (snips, ids) = snippets(vec![
+            TalkSnippet::new(audio.clone(), Time::ZERO, 1.0),
+            // A quieter copy, recorded a little bit later.
+            TalkSnippet::new(audio.clone(), Time::from_micros(20_000), 0.5),
+        ]);
+        let dups = find_duplicates(&snips);
+        assert_eq!(dups.len(), 1);
+        assert_eq!(dups[0].original, ids[0]);
+        assert_eq!(dups[0].copy, ids[1]);
+        assert!(dups[0].similarity > 0.95);
+
+        // Muting the copy fixes the problem.
+        let muted = snips.with_muted_snippet(ids[1], true);
+        assert!(find_duplicates(&muted).is_empty());
+    }
+
+    #[test]
+    fn not_duplicates() {
+        let audio = speech(3.0, 3.0, 1);
+        let (snips, _) = snippets(vec![
+            TalkSnippet::new(audio.clone(), Time::ZERO, 1.0),
+            TalkSnippet::new(speech(3.0, 5.0, 2), Time::ZERO, 1.0),
+            // This one is the same, but it doesn't overlap.
+            TalkSnippet::new(audio, Time::from_micros(10_000_000), 1.0),
+            // Silence isn't a duplicate of anything.
+            TalkSnippet::new(vec![0; 3 * SAMPLE_RATE as usize], Time::ZERO, 1.0),
+        ]);
+        assert!(find_duplicates(&snips).is_empty());
+    }
+}
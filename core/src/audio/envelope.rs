@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use scribl_curves::TimeDiff;
+
+/// The largest gain that a point in a gain envelope can have.
+pub const MAX_GAIN: f32 = 2.0;
+
+/// A single keyframe in a [`GainEnvelope`].
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+pub struct GainPoint {
+    /// The time of this point, relative to the beginning of the snippet.
+    pub offset: TimeDiff,
+    /// The volume multiplier at this point.
+    pub gain: f32,
+}
+
+/// A piecewise-linear volume envelope for an audio snippet.
+///
+/// Between two points the gain is interpolated linearly; before the first point and after the
+/// last one it's constant. An envelope with no points has a gain of 1.0 everywhere.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+#[serde(transparent)]
+pub struct GainEnvelope {
+    // Sorted by offset, with no two points having the same offset.
+    points: Arc<[GainPoint]>,
+}
+
+impl Default for GainEnvelope {
+    fn default() -> GainEnvelope {
+        GainEnvelope {
+            points: Vec::new().into(),
+        }
+    }
+}
+
+impl GainEnvelope {
+    fn from_points(points: Vec<GainPoint>) -> GainEnvelope {
+        GainEnvelope {
+            points: points.into(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn points(&self) -> &[GainPoint] {
+        &self.points
+    }
+
+    /// Returns the gain at the given offset from the beginning of the snippet.
+    pub fn gain_at(&self, offset: TimeDiff) -> f32 {
+        let next = self.points.partition_point(|p| p.offset <= offset);
+        self.interpolate(next, offset)
+    }
+
+    /// Like [`GainEnvelope::gain_at`], but faster when it gets called with increasing offsets
+    /// (like when mixing): `cursor` remembers where in the envelope the last offset was. It
+    /// should start out as zero.
+    pub fn gain_at_cursor(&self, offset: TimeDiff, cursor: &mut usize) -> f32 {
+        if *cursor > self.points.len() || (*cursor > 0 && self.points[*cursor - 1].offset > offset)
+        {
+            *cursor = 0;
+        }
+        while *cursor < self.points.len() && self.points[*cursor].offset <= offset {
+            *cursor += 1;
+        }
+        self.interpolate(*cursor, offset)
+    }
+
+    // Returns the gain at `offset`, where `next` is the index of the first point after it.
+    fn interpolate(&self, next: usize, offset: TimeDiff) -> f32 {
+        if next == self.points.len() {
+            self.points.last().map(|p| p.gain).unwrap_or(1.0)
+        } else if next == 0 {
+            self.points[0].gain
+        } else {
+            let a = &self.points[next - 1];
+            let b = &self.points[next];
+            let t =
+                (offset - a.offset).as_micros() as f32 / (b.offset - a.offset).as_micros() as f32;
+            a.gain + (b.gain - a.gain) * t
+        }
+    }
+
+    /// Returns a new envelope with an extra point. If there was already a point at this offset,
+    /// it gets replaced.
+    pub fn with_point(&self, offset: TimeDiff, gain: f32) -> GainEnvelope {
+        let gain = gain.max(0.0).min(MAX_GAIN);
+        let mut points = self.points.to_vec();
+        match points.binary_search_by_key(&offset, |p| p.offset) {
+            Ok(i) => points[i].gain = gain,
+            Err(i) => points.insert(i, GainPoint { offset, gain }),
+        }
+        GainEnvelope::from_points(points)
+    }
+
+    /// Returns a new envelope with the point at index `idx` moved. The point isn't allowed to move
+    /// past its neighbors, so it keeps the same index.
+    pub fn with_moved_point(&self, idx: usize, offset: TimeDiff, gain: f32) -> GainEnvelope {
+        let mut points = self.points.to_vec();
+        let min = if idx > 0 {
+            points[idx - 1].offset + TimeDiff::from_micros(1)
+        } else {
+            TimeDiff::ZERO
+        };
+        let max = points
+            .get(idx + 1)
+            .map(|p| p.offset - TimeDiff::from_micros(1))
+            .unwrap_or(offset.max(min));
+        points[idx] = GainPoint {
+            offset: offset.max(min).min(max),
+            gain: gain.max(0.0).min(MAX_GAIN),
+        };
+        GainEnvelope::from_points(points)
+    }
+
+    /// Returns a new envelope with the point at index `idx` removed.
+    pub fn without_point(&self, idx: usize) -> GainEnvelope {
+        let mut points = self.points.to_vec();
+        points.remove(idx);
+        GainEnvelope::from_points(points)
+    }
+
+    /// Returns a new envelope with all the points shifted by `by`. Points that would end up
+    /// before the beginning of the snippet are removed.
+    pub fn shifted(&self, by: TimeDiff) -> GainEnvelope {
+        let points = self
+            .points
+            .iter()
+            .map(|p| GainPoint {
+                offset: p.offset + by,
+                gain: p.gain,
+            })
+            .filter(|p| p.offset >= TimeDiff::ZERO)
+            .collect();
+        GainEnvelope::from_points(points)
+    }
+
+    /// Returns a new envelope with all the points' offsets multiplied by `factor`, for when the
+    /// snippet gets stretched.
+    pub fn scaled(&self, factor: f64) -> GainEnvelope {
+        let points = self
+            .points
+            .iter()
+            .map(|p| {
+                let micros = p.offset.as_micros() as f64 * factor;
+                GainPoint {
+                    offset: TimeDiff::from_micros(micros.round() as i64),
+                    gain: p.gain,
+                }
+            })
+            .collect();
+        GainEnvelope::from_points(points)
+    }
+
+    /// Returns a new envelope with the range between `from` and `to` deleted, and the points
+    /// after it moved earlier to fill the gap.
+    pub fn snipped(&self, from: TimeDiff, to: TimeDiff) -> GainEnvelope {
+        let points = self
+            .points
+            .iter()
+            .filter(|p| p.offset < from || p.offset >= to)
+            .map(|p| {
+                if p.offset >= to {
+                    GainPoint {
+                        offset: p.offset - (to - from),
+                        gain: p.gain,
+                    }
+                } else {
+                    *p
+                }
+            })
+            .collect();
+        GainEnvelope::from_points(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(x: i64) -> TimeDiff {
+        TimeDiff::from_micros(x * 1000)
+    }
+
+    #[test]
+    fn empty() {
+        let env = GainEnvelope::default();
+        assert_eq!(env.gain_at(ms(0)), 1.0);
+        assert_eq!(env.gain_at(ms(100)), 1.0);
+    }
+
+    #[test]
+    fn interpolate() {
+        let env = GainEnvelope::default()
+            .with_point(ms(100), 0.0)
+            .with_point(ms(200), 1.0);
+        assert_eq!(env.gain_at(ms(0)), 0.0);
+        assert_eq!(env.gain_at(ms(150)), 0.5);
+        assert_eq!(env.gain_at(ms(300)), 1.0);
+    }
+
+    #[test]
+    fn cursor() {
+        let env = GainEnvelope::default()
+            .with_point(ms(100), 0.0)
+            .with_point(ms(200), 1.0)
+            .with_point(ms(300), 0.5);
+        let mut cursor = 0;
+        for x in (0..400).step_by(7) {
+            assert_eq!(env.gain_at_cursor(ms(x), &mut cursor), env.gain_at(ms(x)));
+        }
+        // Going backwards works too, it just isn't any faster.
+        for x in (0..400).rev().step_by(13) {
+            assert_eq!(env.gain_at_cursor(ms(x), &mut cursor), env.gain_at(ms(x)));
+        }
+        assert_eq!(
+            GainEnvelope::default().gain_at_cursor(ms(5), &mut cursor),
+            1.0
+        );
+    }
+
+    #[test]
+    fn move_point() {
+        let env = GainEnvelope::default()
+            .with_point(ms(100), 0.0)
+            .with_point(ms(200), 1.0);
+        // The first point can't move past the second one.
+        let moved = env.with_moved_point(0, ms(300), 0.5);
+        assert_eq!(moved.points()[0].offset, ms(200) - TimeDiff::from_micros(1));
+        assert_eq!(moved.points()[0].gain, 0.5);
+        assert_eq!(moved.points()[1].offset, ms(200));
+    }
+
+    #[test]
+    fn snip() {
+        let env = GainEnvelope::default()
+            .with_point(ms(100), 0.0)
+            .with_point(ms(200), 1.0)
+            .with_point(ms(300), 0.5);
+        let snipped = env.snipped(ms(150), ms(250));
+        assert_eq!(snipped.points().len(), 2);
+        assert_eq!(snipped.points()[1].offset, ms(200));
+        assert_eq!(snipped.points()[1].gain, 0.5);
+    }
+
+    #[test]
+    fn scale() {
+        let env = GainEnvelope::default()
+            .with_point(ms(100), 0.0)
+            .with_point(ms(200), 1.0);
+        let scaled = env.scaled(1.5);
+        assert_eq!(scaled.points()[0].offset, ms(150));
+        assert_eq!(scaled.points()[1].offset, ms(300));
+        assert_eq!(scaled.gain_at(ms(225)), 0.5);
+    }
+}
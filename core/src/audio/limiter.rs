@@ -0,0 +1,96 @@
+use super::SAMPLE_RATE;
+
+/// The limiter keeps the audio below this level (about -1 dBFS), leaving a little room for the
+/// encoder, which can overshoot slightly.
+const CEILING: f32 = 0.89 * i16::MAX as f32;
+/// After a loud peak, the limiter takes about this long (in seconds) to go back to full volume.
+const RELEASE_SECS: f32 = 0.1;
+
+/// A limiter for exported audio: it applies a gain to the audio, and then turns down the loud
+/// parts so that they don't clip.
+///
+/// It reacts to a loud sample immediately, so nothing ever goes over the ceiling. It recovers
+/// slowly, so that the volume doesn't jump around from one sample to the next.
+#[derive(Clone, Debug)]
+pub struct Limiter {
+    gain: f32,
+    /// The current reduction in volume, as a multiplier in `(0.0, 1.0]`.
+    reduction: f32,
+    /// How much of the remaining distance to 1.0 the reduction recovers in each sample.
+    release: f32,
+}
+
+impl Limiter {
+    /// Creates a limiter that multiplies the audio by `gain` before limiting it.
+    pub fn new(gain: f64) -> Limiter {
+        Limiter {
+            gain: gain as f32,
+            reduction: 1.0,
+            release: 1.0 - (-1.0 / (RELEASE_SECS * SAMPLE_RATE as f32)).exp(),
+        }
+    }
+
+    /// Amplifies and limits a buffer of audio. Consecutive buffers should be passed in order,
+    /// because the limiter remembers how loud the previous one was.
+    pub fn process(&mut self, buf: &mut [i16]) {
+        for sample in buf {
+            let x = *sample as f32 * self.gain;
+            let needed = if x.abs() > CEILING {
+                CEILING / x.abs()
+            } else {
+                1.0
+            };
+            if needed < self.reduction {
+                self.reduction = needed;
+            } else {
+                self.reduction += (needed - self.reduction) * self.release;
+            }
+            *sample = (x * self.reduction).round() as i16;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f32, len: usize) -> Vec<i16> {
+        (0..len)
+            .map(|i| (amplitude * (i as f32 * 0.05).sin()) as i16)
+            .collect()
+    }
+
+    #[test]
+    fn quiet_audio_is_only_amplified() {
+        let mut buf = sine(1000.0, 1000);
+        let orig = buf.clone();
+        Limiter::new(2.0).process(&mut buf);
+        for (x, y) in orig.iter().zip(&buf) {
+            assert_eq!(*x as i32 * 2, *y as i32);
+        }
+    }
+
+    #[test]
+    fn loud_audio_stays_below_ceiling() {
+        let mut buf = sine(20000.0, 10000);
+        Limiter::new(4.0).process(&mut buf);
+        assert!(buf.iter().all(|&x| (x as f32).abs() <= CEILING.ceil()));
+        assert!(buf.iter().any(|&x| (x as f32).abs() >= CEILING - 100.0));
+    }
+
+    #[test]
+    fn recovers_after_peak() {
+        let mut limiter = Limiter::new(1.0);
+        let mut loud = vec![i16::MAX; 100];
+        limiter.process(&mut loud);
+        assert!(limiter.reduction < 1.0);
+
+        // A second of quiet audio is plenty of time to recover. Processing it in chunks is the
+        // same as processing it all at once.
+        let mut quiet = vec![100i16; SAMPLE_RATE as usize];
+        for chunk in quiet.chunks_mut(1000) {
+            limiter.process(chunk);
+        }
+        assert_eq!(*quiet.last().unwrap(), 100);
+    }
+}
@@ -0,0 +1,142 @@
+//! The audio data in an animation, and how it gets mixed together for playback and export.
+
+use anyhow::{Context, Result};
+use ebur128::EbuR128;
+use gstreamer as gst;
+
+use scribl_curves::{Cursor, Time};
+
+mod appsrc;
+mod buf;
+mod decode;
+mod duplicates;
+mod envelope;
+mod limiter;
+mod music;
+mod pencil;
+mod snippets;
+mod spectrogram;
+mod stretch;
+
+pub use appsrc::{create_appsrc, create_export_appsrc};
+pub use buf::{set_map_dir, AudioBuf};
+pub use decode::decode_file;
+pub use duplicates::{find_duplicates, Duplicate};
+pub use envelope::{GainEnvelope, GainPoint, MAX_GAIN};
+pub use limiter::Limiter;
+pub use music::{MusicOutput, MusicTrack};
+pub use pencil::{PencilOutput, DEFAULT_PENCIL_VOLUME};
+pub use snippets::{Crossfades, TalkSnippet, TalkSnippetId, TalkSnippets};
+pub use spectrogram::Spectrogram;
+pub use stretch::time_stretch;
+
+/// We do all of our internal audio processing at 48kHz.
+pub const SAMPLE_RATE: u32 = 48000;
+
+/// The volume multiplier that changes audio with integrated loudness `loudness` (in LUFS) to have
+/// integrated loudness `target`.
+pub fn loudness_gain(loudness: f64, target: f64) -> f64 {
+    // Multiplying a signal by x has the effect of adding 20 * log_10(x) to the loudness.
+    10.0f64.powf((target - loudness) / 20.0)
+}
+
+/// All the information needed to specify some audio for playback (or encoding).
+#[derive(Clone)]
+pub struct OutputData {
+    /// The collection of audio snippets. They will be mixed into the final audio output.
+    pub snips: TalkSnippets,
+    /// Where the snippets overlap. This should be computed from `snips` (by
+    /// [`TalkSnippets::crossfades`]) so that it doesn't need to be recomputed every time we mix.
+    pub crossfades: Crossfades,
+    /// Background music, to be mixed in underneath the snippets.
+    pub music: Option<MusicOutput>,
+    /// A pencil sound, to be mixed in while strokes are being drawn.
+    pub pencil: Option<PencilOutput>,
+    /// The time at which to start playing.
+    pub start_time: Time,
+    /// The velocity at which to play back the audio. (1.0 is normal, forwards, playback)
+    pub velocity: f64,
+}
+
+impl OutputData {
+    pub fn new() -> OutputData {
+        OutputData {
+            snips: TalkSnippets::default(),
+            crossfades: Crossfades::default(),
+            music: None,
+            pencil: None,
+            start_time: Time::ZERO,
+            velocity: 1.0,
+        }
+    }
+
+    pub fn forwards(&self) -> bool {
+        self.velocity > 0.0
+    }
+
+    /// Mixes the snippets, the music and the pencil sound into `buf`.
+    pub fn mix_to(&self, cursor: &Cursor<usize, TalkSnippetId>, buf: &mut [i16]) {
+        self.snips
+            .mix_with_crossfades(&self.crossfades, cursor, &mut *buf);
+        if let Some(music) = &self.music {
+            music.mix_to(&self.snips, cursor.current().0, &mut *buf);
+        }
+        if let Some(pencil) = &self.pencil {
+            pencil.mix_to(cursor.current().0, buf);
+        }
+    }
+
+    /// Measures the integrated loudness (in LUFS) of all the audio. If there's no audio, this is
+    /// negative infinity.
+    pub fn integrated_loudness(&self) -> Result<f64> {
+        // We mix one second at a time.
+        let chunk = SAMPLE_RATE as usize;
+        let end = self.end_idx();
+        let mut meter = EbuR128::new(1, SAMPLE_RATE, ebur128::Mode::I)?;
+        let mut cursor = Cursor::new(self.snips.snippet_spans(), 0, 0);
+        let mut buf = vec![0i16; chunk];
+        while cursor.current().1 < end {
+            let prev_end = cursor.current().1;
+            cursor.advance_to(prev_end, prev_end + chunk);
+            buf.iter_mut().for_each(|x| *x = 0);
+            self.mix_to(&cursor, &mut buf);
+            meter.add_frames_i16(&buf)?;
+        }
+        Ok(meter.loudness_global()?)
+    }
+
+    /// The index (in samples) after which there is definitely no more audio.
+    fn end_idx(&self) -> usize {
+        let snips_end = self.snips.end_time().as_audio_idx(SAMPLE_RATE);
+        let music_end = self.music.as_ref().map(|m| m.end_idx()).unwrap_or(0);
+        let pencil_end = self.pencil.as_ref().map(|p| p.end_idx()).unwrap_or(0);
+        snips_end.max(music_end).max(pencil_end)
+    }
+}
+
+/// The context of the error returned by [`create_gst_elt`], for finding out which element was
+/// missing (with `anyhow::Error::downcast_ref`).
+#[derive(Clone, Debug)]
+pub struct MissingElement {
+    /// The element's type, like "audioconvert".
+    pub kind: String,
+    /// The name that we tried to give the element.
+    pub name: String,
+}
+
+impl std::fmt::Display for MissingElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "tried to create {}, of type {}. You are probably missing a gstreamer plugin",
+            self.name, self.kind
+        )
+    }
+}
+
+pub fn create_gst_elt(kind: &str, name: &str) -> Result<gst::Element> {
+    gst::ElementFactory::make(kind, Some(name)).with_context(|| MissingElement {
+        kind: kind.to_owned(),
+        name: name.to_owned(),
+    })
+}
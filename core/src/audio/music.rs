@@ -0,0 +1,224 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+use scribl_curves::Time;
+
+use super::{decode_file, TalkSnippets, SAMPLE_RATE};
+
+/// The default volume of newly imported music.
+const DEFAULT_VOLUME: f32 = 0.5;
+/// The default amount by which the music is attenuated while someone is talking.
+const DEFAULT_DUCK: f32 = 0.25;
+/// The music starts getting quieter this many samples before someone starts talking.
+const DUCK_ATTACK: usize = SAMPLE_RATE as usize / 10;
+/// After someone stops talking, it takes this many samples for the music to get back to its full
+/// volume.
+const DUCK_RELEASE: usize = SAMPLE_RATE as usize / 2;
+
+/// A background music track, which plays underneath the recorded speech.
+///
+/// Like a [`TalkSnippet`](struct.TalkSnippet.html), the actual data is behind a pointer, so this
+/// is cheap to clone.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+pub struct MusicTrack {
+    /// The name of the file that the music was imported from. This is only for display; we store
+    /// the decoded audio, so the original file isn't needed after importing.
+    name: Arc<str>,
+    buf: Arc<[i16]>,
+    /// The volume of the music while nobody is talking.
+    volume: f32,
+    /// While someone is talking, the volume of the music gets multiplied by this.
+    duck: f32,
+    /// If true, the music repeats until the end of the animation. Otherwise it plays once (but it
+    /// still gets cut off at the end of the animation).
+    looping: bool,
+}
+
+/// Everything that the audio thread needs to know in order to play some background music.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+pub struct MusicOutput {
+    pub track: MusicTrack,
+    /// The music is cut off at this time, which is normally the end of the animation.
+    pub end_time: Time,
+}
+
+impl MusicTrack {
+    pub fn new(name: &str, buf: Vec<i16>) -> MusicTrack {
+        MusicTrack {
+            name: name.into(),
+            buf: buf.into(),
+            volume: DEFAULT_VOLUME,
+            duck: DEFAULT_DUCK,
+            looping: true,
+        }
+    }
+
+    /// Decodes an audio file (in any format that gstreamer understands) into a music track.
+    ///
+    /// This blocks until the whole file is decoded, so it should be called from a separate thread.
+    pub fn from_path(path: &Path) -> Result<MusicTrack> {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("music");
+        Ok(MusicTrack::new(name, decode_file(path)?))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn looping(&self) -> bool {
+        self.looping
+    }
+
+    pub fn with_looping(&self, looping: bool) -> MusicTrack {
+        MusicTrack {
+            looping,
+            ..self.clone()
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn with_volume(&self, volume: f32) -> MusicTrack {
+        MusicTrack {
+            volume: volume.max(0.0).min(1.0),
+            ..self.clone()
+        }
+    }
+}
+
+impl MusicOutput {
+    /// The index (in samples) at which the music stops.
+    pub fn end_idx(&self) -> usize {
+        let len = self.track.buf.len();
+        let end = self.end_time.as_audio_idx(SAMPLE_RATE);
+        if self.track.looping {
+            end
+        } else {
+            end.min(len)
+        }
+    }
+
+    /// Returns the music that plays between `from` and `to`, moved earlier so that `from` becomes
+    /// the beginning.
+    pub fn excerpt(&self, from: Time, to: Time) -> MusicOutput {
+        let buf = &self.track.buf;
+        let offset = (from - Time::ZERO).as_audio_idx(SAMPLE_RATE).max(0) as usize;
+        let buf: Vec<i16> = if self.track.looping && !buf.is_empty() {
+            let offset = offset % buf.len();
+            buf[offset..]
+                .iter()
+                .chain(&buf[..offset])
+                .copied()
+                .collect()
+        } else {
+            buf[offset.min(buf.len())..].to_owned()
+        };
+        MusicOutput {
+            track: MusicTrack {
+                buf: buf.into(),
+                ..self.track.clone()
+            },
+            end_time: Time::ZERO + (self.end_time.min(to) - from),
+        }
+    }
+
+    /// Adds the music to a buffer, ducking it wherever one of the talk snippets is active.
+    ///
+    /// `start` is the index (in samples) of the first element of `buf`.
+    pub fn mix_to(&self, talk: &TalkSnippets, start: usize, buf: &mut [i16]) {
+        let len = self.track.buf.len();
+        let end = self.end_idx();
+        if len == 0 || start >= end {
+            return;
+        }
+
+        // The talk snippets that are close enough to the current chunk to affect its volume.
+        let buf_end = start + buf.len();
+        let speech: Vec<(usize, usize)> = talk
+            .snippet_spans()
+            .map(|sp| (sp.start, sp.end.unwrap_or(usize::MAX)))
+            .filter(|&(s, e)| {
+                s < buf_end.saturating_add(DUCK_ATTACK) && e.saturating_add(DUCK_RELEASE) > start
+            })
+            .collect();
+
+        for (idx, out) in buf.iter_mut().enumerate().take(end - start) {
+            let pos = start + idx;
+            let sample = self.track.buf[pos % len];
+            let ducking = speech
+                .iter()
+                .map(|&(s, e)| speech_proximity(s, e, pos))
+                .fold(0.0, f32::max);
+            let gain = self.track.volume * (1.0 - ducking * (1.0 - self.track.duck));
+            *out = out.saturating_add((sample as f32 * gain) as i16);
+        }
+    }
+}
+
+/// Returns 1.0 if `pos` is inside the range from `start` to `end`, 0.0 if it's far from it, and
+/// something in between if it's close to the beginning or end of the range.
+fn speech_proximity(start: usize, end: usize, pos: usize) -> f32 {
+    if pos < start {
+        1.0 - ((start - pos) as f32 / DUCK_ATTACK as f32).min(1.0)
+    } else if pos >= end {
+        1.0 - ((pos - end) as f32 / DUCK_RELEASE as f32).min(1.0)
+    } else {
+        1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::TalkSnippet;
+
+    fn output(buf: &[i16], looping: bool, end: usize) -> MusicOutput {
+        MusicOutput {
+            track: MusicTrack::new("test", buf.to_owned())
+                .with_volume(1.0)
+                .with_looping(looping),
+            end_time: Time::from_audio_idx(end, SAMPLE_RATE),
+        }
+    }
+
+    #[test]
+    fn looping() {
+        let music = output(&[1, 2, 3], true, 6);
+        let mut out = vec![0; 9];
+        music.mix_to(&TalkSnippets::default(), 0, &mut out[..]);
+        assert_eq!(out, vec![1, 2, 3, 1, 2, 3, 0, 0, 0]);
+
+        let music = output(&[1, 2, 3], false, 6);
+        let mut out = vec![0; 9];
+        music.mix_to(&TalkSnippets::default(), 0, &mut out[..]);
+        assert_eq!(out, vec![1, 2, 3, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn ducking() {
+        let len = DUCK_ATTACK * 4;
+        let music = output(&vec![100; len], false, len);
+        let speech = TalkSnippet::new(
+            vec![0; DUCK_ATTACK],
+            Time::from_audio_idx(DUCK_ATTACK * 2, SAMPLE_RATE),
+            1.0,
+        );
+        let (talk, _) = TalkSnippets::default().with_new_snippet(speech);
+        let mut out = vec![0; len];
+        music.mix_to(&talk, 0, &mut out[..]);
+
+        // Full volume well before the speech, ducked during the speech, and something in between
+        // while fading.
+        assert_eq!(out[0], 100);
+        assert_eq!(out[DUCK_ATTACK * 5 / 2], (100.0 * DEFAULT_DUCK) as i16);
+        assert!(out[DUCK_ATTACK * 3 / 2] > 25 && out[DUCK_ATTACK * 3 / 2] < 100);
+    }
+}
@@ -0,0 +1,202 @@
+use std::sync::Arc;
+
+use scribl_curves::{DrawSnippets, Time, TimeDiff};
+
+use super::SAMPLE_RATE;
+
+/// The default volume of the pencil sound.
+pub const DEFAULT_PENCIL_VOLUME: f32 = 0.3;
+/// If the pen stays still for longer than this, the pencil sound stops.
+const MAX_GAP: TimeDiff = TimeDiff::from_micros(100_000);
+/// The pencil sound fades in and out over this many samples, so that it doesn't click.
+const FADE: usize = SAMPLE_RATE as usize / 100;
+/// The roughness of the sound changes every this many samples.
+const GRAIN: usize = SAMPLE_RATE as usize / 200;
+/// At full volume, the pencil sound has roughly this amplitude.
+const AMPLITUDE: f32 = 8000.0;
+
+/// A synthetic pencil-on-paper sound, which plays whenever a stroke is being drawn.
+///
+/// The sound is made up on the fly from filtered noise, so this only needs to remember when
+/// something is being drawn. It's cheap to clone.
+#[derive(Clone, PartialEq)]
+pub struct PencilOutput {
+    /// The ranges of samples during which something is being drawn. They're sorted, and they
+    /// don't overlap.
+    activity: Arc<[(usize, usize)]>,
+    volume: f32,
+}
+
+impl PencilOutput {
+    /// Makes a pencil sound that follows the strokes in `draw`, at the given volume (between
+    /// 0.0 and 1.0).
+    pub fn new(draw: &DrawSnippets, volume: f32) -> PencilOutput {
+        let idx = |t: Time| t.as_audio_idx(SAMPLE_RATE);
+        let mut ranges = Vec::new();
+        for (_, snip) in draw.snippets() {
+            let end = snip.end_time();
+            for stroke in snip.strokes() {
+                for w in stroke.times.windows(2) {
+                    let a = snip.playback_time(w[0]);
+                    let b = snip.playback_time(w[1]);
+                    let (a, b) = (a.min(b), a.max(b));
+                    if a < b && b - a <= MAX_GAP && end.map_or(true, |e| a < e) {
+                        ranges.push((idx(a), idx(b)));
+                    }
+                }
+            }
+        }
+        ranges.sort_unstable();
+
+        let mut activity: Vec<(usize, usize)> = Vec::new();
+        for (start, end) in ranges {
+            match activity.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => activity.push((start, end)),
+            }
+        }
+        PencilOutput {
+            activity: activity.into(),
+            volume: volume.max(0.0).min(1.0),
+        }
+    }
+
+    /// The index (in samples) after which the pencil is silent.
+    pub fn end_idx(&self) -> usize {
+        self.activity.last().map_or(0, |r| r.1)
+    }
+
+    /// Returns the pencil sound that plays between `from` and `to`, moved earlier so that `from`
+    /// becomes the beginning.
+    pub fn excerpt(&self, from: Time, to: Time) -> PencilOutput {
+        let from = from.as_audio_idx(SAMPLE_RATE);
+        let to = to.as_audio_idx(SAMPLE_RATE);
+        let activity: Vec<_> = self
+            .activity
+            .iter()
+            .filter(|&&(s, e)| e > from && s < to)
+            .map(|&(s, e)| (s.max(from) - from, e.min(to) - from))
+            .collect();
+        PencilOutput {
+            activity: activity.into(),
+            volume: self.volume,
+        }
+    }
+
+    /// Adds the pencil sound to a buffer.
+    ///
+    /// `start` is the index (in samples) of the first element of `buf`. The sound at any given
+    /// index doesn't depend on how the buffers are split up.
+    pub fn mix_to(&self, start: usize, buf: &mut [i16]) {
+        if self.volume <= 0.0 {
+            return;
+        }
+        let end = start + buf.len();
+        let first = self.activity.partition_point(|&(_, e)| e <= start);
+        for &(s, e) in self.activity[first..].iter().take_while(|r| r.0 < end) {
+            for pos in s.max(start)..e.min(end) {
+                let fade = ((pos - s).min(e - pos) as f32 / FADE as f32).min(1.0);
+                let sample = scratch(pos) * fade * self.volume * AMPLITUDE;
+                let out = &mut buf[pos - start];
+                *out = out.saturating_add(sample as i16);
+            }
+        }
+    }
+}
+
+/// Deterministic noise in the range [-1, 1), using the "splitmix" hash.
+fn noise(x: u64) -> f32 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+}
+
+/// The pencil sound at sample `pos`: noise with the highest frequencies taken out (because they
+/// sound more like hissing than scratching), and with a slowly varying roughness.
+fn scratch(pos: usize) -> f32 {
+    let pos = pos as u64;
+    let smooth = (0..4).map(|i| noise(pos.wrapping_sub(i))).sum::<f32>() / 4.0;
+    // The roughness gets its own noise, so offset it from the samples' noise.
+    let grain = pos / GRAIN as u64;
+    let frac = (pos % GRAIN as u64) as f32 / GRAIN as f32;
+    let rough = |g: u64| 0.75 + 0.25 * noise(g ^ 0x5eed_0000_0000_0000);
+    smooth * (rough(grain) * (1.0 - frac) + rough(grain + 1) * frac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurbo::Point;
+    use scribl_curves::{Brush, DrawSnippet, Effects, StrokeInProgress, StrokeSeq, StrokeStyle};
+
+    fn t(ms: i64) -> Time {
+        Time::from_micros(ms * 1000)
+    }
+
+    fn draw() -> DrawSnippets {
+        let style = StrokeStyle {
+            color: piet::Color::BLACK,
+            thickness: 0.01,
+            effects: Effects::default(),
+            brush: Brush::Round,
+        };
+        let mut seq = StrokeSeq::new();
+        for &(start, end) in &[(100, 200), (1000, 1100)] {
+            let mut stroke = StrokeInProgress::new();
+            stroke.add_point(Point::new(0.0, 0.0), t(start));
+            stroke.add_point(Point::new(0.5, 0.5), t((start + end) / 2));
+            stroke.add_point(Point::new(1.0, 0.0), t(end));
+            seq.append_stroke(stroke, style.clone(), false, 0.0001);
+        }
+        DrawSnippets::default()
+            .with_new_snippet(DrawSnippet::new(seq))
+            .0
+    }
+
+    #[test]
+    fn activity() {
+        let pencil = PencilOutput::new(&draw(), 1.0);
+        let idx = |ms: i64| t(ms).as_audio_idx(SAMPLE_RATE);
+        assert_eq!(
+            &pencil.activity[..],
+            &[(idx(100), idx(200)), (idx(1000), idx(1100))]
+        );
+        assert_eq!(pencil.end_idx(), idx(1100));
+
+        let excerpt = pencil.excerpt(t(150), t(1050));
+        assert_eq!(&excerpt.activity[..], &[(0, idx(50)), (idx(850), idx(900))]);
+    }
+
+    #[test]
+    fn mix() {
+        let pencil = PencilOutput::new(&draw(), 1.0);
+        let len = t(1200).as_audio_idx(SAMPLE_RATE);
+        let mut whole = vec![0i16; len];
+        pencil.mix_to(0, &mut whole);
+
+        // It's silent when nothing is being drawn.
+        assert!(whole[..t(100).as_audio_idx(SAMPLE_RATE)]
+            .iter()
+            .all(|&x| x == 0));
+        assert!(
+            whole[t(200).as_audio_idx(SAMPLE_RATE)..t(1000).as_audio_idx(SAMPLE_RATE)]
+                .iter()
+                .all(|&x| x == 0)
+        );
+        assert!(whole.iter().any(|&x| x != 0));
+
+        // Mixing in pieces gives the same result.
+        let mut pieces = vec![0i16; len];
+        for (i, chunk) in pieces.chunks_mut(1000).enumerate() {
+            pencil.mix_to(i * 1000, chunk);
+        }
+        assert_eq!(whole, pieces);
+
+        // Silence is silent.
+        let mut quiet = vec![0i16; len];
+        PencilOutput::new(&draw(), 0.0).mix_to(0, &mut quiet);
+        assert!(quiet.iter().all(|&x| x == 0));
+    }
+}
@@ -0,0 +1,1455 @@
+use ebur128::EbuR128;
+use im::OrdMap;
+use serde::de::Deserializer;
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+
+use scribl_curves::{Cursor, GroupId, LabelColor, Span, Time, TimeDiff};
+
+use super::{decode_file, time_stretch, AudioBuf, GainEnvelope, SAMPLE_RATE};
+
+/// When talk snippets overlap, we crossfade between them. This is the maximum length (in samples)
+/// of the crossfade.
+const CROSSFADE_LEN: usize = SAMPLE_RATE as usize / 4;
+
+/// When looking for quiet parts of a snippet, we look at windows of this many samples (10ms).
+const QUIET_WINDOW: usize = SAMPLE_RATE as usize / 100;
+
+/// Each audio snippet is uniquely identified by one of these ids.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+#[serde(transparent)]
+pub struct TalkSnippetId(u64);
+
+/// A buffer of audio data, starting at a particular time.
+///
+/// The actual data is in an [`AudioBuf`], so this is cheap to clone.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+pub struct TalkSnippet {
+    buf: AudioBuf,
+    multiplier: f32,
+    start_time: Time,
+    // This was added after the original file format, so old files don't have it.
+    #[serde(default, skip_serializing_if = "GainEnvelope::is_empty")]
+    gain: GainEnvelope,
+    // These were also added after the original file format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    muted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<LabelColor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    group: Option<GroupId>,
+    // How far (in samples) the audio has been slid back within the snippet. See `offset`.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    offset: i64,
+    // The other version of the audio, if we kept one: the raw recording if `buf` is the processed
+    // audio, and the processed audio if `buf` is raw. It has the same length as `buf`, and every
+    // edit to `buf` gets applied to it too. See `is_raw`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alternate: Option<AudioBuf>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    raw: bool,
+    // The lengths (in samples) of the fades at the beginning and end of the snippet.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    fade_in: i64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    fade_out: i64,
+}
+
+fn is_zero(x: &i64) -> bool {
+    *x == 0
+}
+
+/// The sample at index `idx` of `buf`, or silence if `idx` is out of range.
+fn sample_at(buf: &[i16], idx: i64) -> i16 {
+    if idx >= 0 {
+        buf.get(idx as usize).copied().unwrap_or(0)
+    } else {
+        0
+    }
+}
+
+/// `buf`, slid back by `offset` samples (see [`TalkSnippet::offset`]).
+fn apply_offset(buf: &[i16], offset: i64) -> Vec<i16> {
+    (0..buf.len() as i64)
+        .map(|i| sample_at(buf, i + offset))
+        .collect()
+}
+
+/// A collection of [`TalkSnippet`](struct.TalkSnippet.html), each one
+/// identified by an [`TalkSnippetId`](struct.TalkSnippetId.html).
+#[derive(Clone, Default, PartialEq)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+pub struct TalkSnippets {
+    last_id: u64,
+    snippets: OrdMap<TalkSnippetId, TalkSnippet>,
+}
+
+impl TalkSnippet {
+    pub fn new(buf: Vec<i16>, start_time: Time, multiplier: f32) -> TalkSnippet {
+        TalkSnippet {
+            buf: buf.into(),
+            multiplier,
+            start_time,
+            gain: GainEnvelope::default(),
+            name: None,
+            muted: false,
+            label: None,
+            group: None,
+            offset: 0,
+            alternate: None,
+            raw: false,
+            fade_in: 0,
+            fade_out: 0,
+        }
+    }
+
+    /// Decodes an audio file into a snippet that starts at `start_time`, and that's named after
+    /// the file. The audio gets resampled to our internal sample rate.
+    ///
+    /// This blocks until the whole file is decoded, so it should be called from a separate thread.
+    pub fn from_path(path: &Path, start_time: Time) -> anyhow::Result<TalkSnippet> {
+        let name = path
+            .file_stem()
+            .and_then(|n| n.to_str())
+            .map(|n| n.to_owned());
+        Ok(TalkSnippet::new(decode_file(path)?, start_time, 1.0).with_name(name))
+    }
+
+    /// The recorded audio, without taking the [`offset`](TalkSnippet::offset) into account. Use
+    /// [`samples`](TalkSnippet::samples) for the audio as it actually plays.
+    pub fn buf(&self) -> &[i16] {
+        &self.buf
+    }
+
+    /// The audio as it plays, with the [`offset`](TalkSnippet::offset) applied. This has the same
+    /// length as [`buf`](TalkSnippet::buf), and the parts that have been slid out of the snippet
+    /// are replaced by silence.
+    pub fn samples(&self) -> Cow<'_, [i16]> {
+        if self.offset == 0 {
+            Cow::Borrowed(self.buf())
+        } else {
+            Cow::Owned(apply_offset(&self.buf, self.offset))
+        }
+    }
+
+    /// The sample that plays at index `idx` of this snippet, with the offset applied.
+    fn sample(&self, idx: usize) -> i16 {
+        sample_at(&self.buf, idx as i64 + self.offset)
+    }
+
+    /// How far the audio has been slid back, relative to the start of the snippet. Sliding the
+    /// audio doesn't change when the snippet starts or how long it is: if the offset is positive,
+    /// the beginning of the recording gets cut off and there's silence at the end.
+    pub fn offset(&self) -> TimeDiff {
+        TimeDiff::from_audio_idx(self.offset, SAMPLE_RATE)
+    }
+
+    /// Returns a new snippet with the audio slid back by `offset`. The offset gets clamped so that
+    /// at least some of the audio is still in the snippet.
+    pub fn with_offset(&self, offset: TimeDiff) -> TalkSnippet {
+        let max = (self.buf.len() as i64 - 1).max(0);
+        // Round to the nearest sample, because `as_audio_idx` rounds down and we want offsets to
+        // survive a round-trip through `offset`.
+        let offset = (offset.as_micros() as f64 * SAMPLE_RATE as f64 / 1e6).round() as i64;
+        TalkSnippet {
+            offset: offset.max(-max).min(max),
+            ..self.clone()
+        }
+    }
+
+    /// Returns an equivalent snippet with an offset of zero. The buffer-editing operations call
+    /// this first, so that they only need to deal with unshifted audio.
+    fn baked(&self) -> TalkSnippet {
+        let offset = self.offset;
+        TalkSnippet {
+            offset: 0,
+            ..self.with_edited_audio(|buf| apply_offset(buf, offset))
+        }
+    }
+
+    /// Returns a new snippet in which both versions of the audio (see
+    /// [`is_raw`](TalkSnippet::is_raw)) have been edited by `edit`. The edit must not change
+    /// the relationship between the lengths of the versions, because the versions need to stay
+    /// aligned.
+    fn with_edited_audio(&self, edit: impl Fn(&[i16]) -> Vec<i16>) -> TalkSnippet {
+        TalkSnippet {
+            buf: edit(&self.buf).into(),
+            alternate: self.alternate.as_ref().map(|alt| edit(alt).into()),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a new snippet that plays `samples` instead of this snippet's audio, but is
+    /// otherwise the same. The new audio replaces the audio as it plays (see
+    /// [`samples`](TalkSnippet::samples)), so the new snippet has no offset, and it no longer
+    /// has another version of its audio.
+    pub fn with_samples(&self, samples: Vec<i16>) -> TalkSnippet {
+        TalkSnippet {
+            buf: samples.into(),
+            offset: 0,
+            alternate: None,
+            raw: false,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a new snippet that keeps `raw` (the unprocessed recording that this snippet's
+    /// audio was made from) alongside its audio, so that they can switch to it later with
+    /// [`with_raw`](TalkSnippet::with_raw). The raw audio gets padded or truncated to the
+    /// length of this snippet.
+    pub fn with_raw_recording(&self, mut raw: Vec<i16>) -> TalkSnippet {
+        if self.offset != 0 {
+            return self.baked().with_raw_recording(raw);
+        }
+        raw.resize(self.buf.len(), 0);
+        TalkSnippet {
+            alternate: Some(raw.into()),
+            raw: false,
+            ..self.clone()
+        }
+    }
+
+    /// Does this snippet have both a processed (noise-removed) and a raw version of its audio?
+    pub fn has_raw(&self) -> bool {
+        self.alternate.is_some()
+    }
+
+    /// Is this snippet playing the raw version of its audio? Snippets that were recorded with
+    /// noise removal or voice detection turned on can also keep the raw recording (see
+    /// [`has_raw`](TalkSnippet::has_raw)), and they can switch between the two versions.
+    ///
+    /// Everything (including [`buf`](TalkSnippet::buf) and
+    /// [`samples`](TalkSnippet::samples)) looks at the version that is playing.
+    pub fn is_raw(&self) -> bool {
+        self.raw
+    }
+
+    /// Returns a new snippet that plays the raw version of its audio (if `raw` is true) or the
+    /// processed version. If the snippet doesn't have that version, it gets returned unchanged.
+    pub fn with_raw(&self, raw: bool) -> TalkSnippet {
+        match &self.alternate {
+            Some(alt) if raw != self.raw => TalkSnippet {
+                buf: alt.clone(),
+                alternate: Some(self.buf.clone()),
+                raw,
+                ..self.clone()
+            },
+            _ => self.clone(),
+        }
+    }
+
+    /// The raw version of the audio as it plays (with the [`offset`](TalkSnippet::offset)
+    /// applied). If this snippet didn't keep its raw recording, its audio counts as the raw
+    /// audio.
+    pub fn raw_samples(&self) -> Cow<'_, [i16]> {
+        let raw = self.version(true).unwrap_or(&self.buf);
+        if self.offset == 0 {
+            Cow::Borrowed(raw)
+        } else {
+            Cow::Owned(apply_offset(raw, self.offset))
+        }
+    }
+
+    /// Returns a new snippet that plays `processed` (which should be a processed version of
+    /// [`raw_samples`](TalkSnippet::raw_samples)) instead of its current audio. The raw audio is
+    /// kept, so that they can switch back to it.
+    pub fn with_processed_audio(&self, mut processed: Vec<i16>) -> TalkSnippet {
+        if self.offset != 0 {
+            return self.baked().with_processed_audio(processed);
+        }
+        processed.resize(self.buf.len(), 0);
+        TalkSnippet {
+            buf: processed.into(),
+            alternate: Some(self.version(true).unwrap_or(&self.buf).clone()),
+            raw: false,
+            ..self.clone()
+        }
+    }
+
+    /// The raw (if `raw` is true) or processed version of this snippet's audio, if it has one.
+    fn version(&self, raw: bool) -> Option<&AudioBuf> {
+        if raw == self.raw {
+            Some(&self.buf)
+        } else {
+            self.alternate.as_ref()
+        }
+    }
+
+    /// Is this snippet an unmodified copy of `other`? Unlike comparing with `==`, this doesn't
+    /// look at the audio samples: copies share their audio buffer.
+    pub fn is_copy_of(&self, other: &TalkSnippet) -> bool {
+        self.buf.ptr_eq(&other.buf)
+            && self.multiplier == other.multiplier
+            && self.start_time == other.start_time
+            && self.gain == other.gain
+            && self.name == other.name
+            && self.muted == other.muted
+            && self.label == other.label
+            && self.group == other.group
+            && self.offset == other.offset
+            && self.raw == other.raw
+    }
+
+    pub fn start_time(&self) -> Time {
+        self.start_time
+    }
+
+    /// Estimates the number of bytes of memory used by this snippet's audio, unless it shares its
+    /// audio with a snippet that's already in `seen`. Audio that's stored in a memory-mapped
+    /// file doesn't count.
+    pub fn memory_size(&self, seen: &mut HashSet<usize>) -> usize {
+        let mut size = |buf: &AudioBuf| {
+            if seen.insert(buf.as_ptr() as usize) {
+                buf.memory_size()
+            } else {
+                0
+            }
+        };
+        size(&self.buf) + self.alternate.as_ref().map_or(0, size)
+    }
+
+    pub fn end_time(&self) -> Time {
+        let length = TimeDiff::from_audio_idx(self.buf().len() as i64, SAMPLE_RATE);
+        self.start_time() + length
+    }
+
+    pub fn shifted(&self, shift: TimeDiff) -> TalkSnippet {
+        TalkSnippet {
+            start_time: self.start_time + shift,
+            ..self.clone()
+        }
+    }
+
+    pub fn multiplier(&self) -> f32 {
+        self.multiplier
+    }
+
+    /// Measures the integrated loudness (in LUFS) of this snippet, taking its multiplier into
+    /// account (but not its volume envelope). If the snippet is silent, this is negative infinity.
+    ///
+    /// This looks at every sample, so it can take a while for long snippets.
+    pub fn loudness(&self) -> anyhow::Result<f64> {
+        let mut meter = EbuR128::new(1, SAMPLE_RATE, ebur128::Mode::I)?;
+        meter.add_frames_i16(&self.samples())?;
+        let loudness = meter.loudness_global()?;
+        // Multiplying a signal by x has the effect of adding 20 * log_10(x) to the loudness.
+        Ok(loudness + 20.0 * (self.multiplier as f64).log10())
+    }
+
+    pub fn multiplied(&self, factor: f32) -> TalkSnippet {
+        TalkSnippet {
+            multiplier: self.multiplier * factor,
+            ..self.clone()
+        }
+    }
+
+    pub fn with_multiplier(&self, multiplier: f32) -> TalkSnippet {
+        TalkSnippet {
+            multiplier,
+            ..self.clone()
+        }
+    }
+
+    /// The volume envelope of this snippet.
+    pub fn gain(&self) -> &GainEnvelope {
+        &self.gain
+    }
+
+    pub fn with_gain(&self, gain: GainEnvelope) -> TalkSnippet {
+        TalkSnippet {
+            gain,
+            ..self.clone()
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn with_name(&self, name: Option<String>) -> TalkSnippet {
+        TalkSnippet {
+            name,
+            ..self.clone()
+        }
+    }
+
+    /// The color that this snippet is shown with in the timeline, if they chose one.
+    pub fn label(&self) -> Option<LabelColor> {
+        self.label
+    }
+
+    pub fn with_label(&self, label: Option<LabelColor>) -> TalkSnippet {
+        TalkSnippet {
+            label,
+            ..self.clone()
+        }
+    }
+
+    /// The group of linked snippets that this snippet belongs to, if any.
+    pub fn group(&self) -> Option<GroupId> {
+        self.group
+    }
+
+    pub fn with_group(&self, group: Option<GroupId>) -> TalkSnippet {
+        TalkSnippet {
+            group,
+            ..self.clone()
+        }
+    }
+
+    /// How long it takes this snippet to fade in from silence at the beginning.
+    pub fn fade_in(&self) -> TimeDiff {
+        TimeDiff::from_audio_idx(self.fade_in, SAMPLE_RATE)
+    }
+
+    /// How long it takes this snippet to fade out to silence at the end.
+    pub fn fade_out(&self) -> TimeDiff {
+        TimeDiff::from_audio_idx(self.fade_out, SAMPLE_RATE)
+    }
+
+    /// Returns a new snippet with the given fade lengths. Negative lengths count as zero, and
+    /// each fade gets clamped to the length of the snippet.
+    pub fn with_fades(&self, fade_in: TimeDiff, fade_out: TimeDiff) -> TalkSnippet {
+        let len = self.buf.len() as i64;
+        // Round to the nearest sample, like `with_offset` does.
+        let samples =
+            |d: TimeDiff| ((d.as_micros() as f64 * SAMPLE_RATE as f64 / 1e6).round() as i64);
+        TalkSnippet {
+            fade_in: samples(fade_in).max(0).min(len),
+            fade_out: samples(fade_out).max(0).min(len),
+            ..self.clone()
+        }
+    }
+
+    /// The volume of the fades at index `idx` of this snippet, between 0.0 and 1.0.
+    fn fade_at(&self, idx: usize) -> f32 {
+        let idx = idx as i64;
+        let from_end = self.buf.len() as i64 - 1 - idx;
+        let mut ret = 1.0;
+        if idx < self.fade_in {
+            ret *= idx as f32 / self.fade_in as f32;
+        }
+        if from_end < self.fade_out {
+            ret *= from_end as f32 / self.fade_out as f32;
+        }
+        ret
+    }
+
+    /// A muted snippet stays in the timeline, but it doesn't make any sound.
+    pub fn muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn with_muted(&self, muted: bool) -> TalkSnippet {
+        TalkSnippet {
+            muted,
+            ..self.clone()
+        }
+    }
+
+    /// Splits this snippet in two at `time`. Returns `None` if `time` isn't strictly inside the
+    /// snippet.
+    pub fn split(&self, time: Time) -> Option<(TalkSnippet, TalkSnippet)> {
+        let first = self.excerpt(self.start_time(), time)?;
+        let second = self.excerpt(time, self.end_time())?;
+        Some((first, second))
+    }
+
+    fn idx(&self, time: Time) -> usize {
+        (time - self.start_time())
+            .as_audio_idx(SAMPLE_RATE)
+            .max(0)
+            .min(self.buf.len() as isize) as usize
+    }
+
+    /// Returns a new snippet, with all audio between `from` and `to` silenced.
+    pub fn silenced(&self, from: Time, to: Time) -> TalkSnippet {
+        if self.offset != 0 {
+            return self.baked().silenced(from, to);
+        }
+        let from_idx = self.idx(from);
+        let to_idx = self.idx(to);
+        let (from_idx, to_idx) = (from_idx.min(to_idx), from_idx.max(to_idx));
+
+        if from_idx < to_idx {
+            self.with_edited_audio(|buf| {
+                let mut buf = buf.to_owned();
+                for x in &mut buf[from_idx..to_idx] {
+                    *x = 0;
+                }
+                buf
+            })
+        } else {
+            self.clone()
+        }
+    }
+
+    /// Returns a new (shorter) snippet, with all audio between `from` and `to` deleted.
+    pub fn snipped(&self, from: Time, to: Time) -> TalkSnippet {
+        let from_idx = self.idx(from);
+        let to_idx = self.idx(to);
+        self.snipped_idx(from_idx.min(to_idx), from_idx.max(to_idx))
+    }
+
+    fn snipped_idx(&self, from_idx: usize, to_idx: usize) -> TalkSnippet {
+        if self.offset != 0 {
+            return self.baked().snipped_idx(from_idx, to_idx);
+        }
+        if from_idx < to_idx {
+            let gain = self.gain.snipped(
+                TimeDiff::from_audio_idx(from_idx as i64, SAMPLE_RATE),
+                TimeDiff::from_audio_idx(to_idx as i64, SAMPLE_RATE),
+            );
+            let len = (self.buf.len() - (to_idx - from_idx)) as i64;
+            TalkSnippet {
+                gain,
+                fade_in: self.fade_in.min(len),
+                fade_out: self.fade_out.min(len),
+                ..self.with_edited_audio(|buf| {
+                    let mut buf = buf.to_owned();
+                    buf.drain(from_idx..to_idx);
+                    buf
+                })
+            }
+        } else {
+            self.clone()
+        }
+    }
+
+    /// Returns a new snippet, in which the audio between `from` and `to` is replaced by the audio
+    /// from `take`. We crossfade between the old and new audio for a duration of `fade` on either
+    /// side of the replaced range.
+    ///
+    /// The audio from `take` is aligned according to its start time, and its volume is scaled to
+    /// account for the difference in multipliers. Any part of the range that `take` doesn't cover
+    /// becomes silent.
+    pub fn punched_in(
+        &self,
+        take: &TalkSnippet,
+        from: Time,
+        to: Time,
+        fade: TimeDiff,
+    ) -> TalkSnippet {
+        if self.offset != 0 || take.offset != 0 {
+            return self.baked().punched_in(&take.baked(), from, to, fade);
+        }
+        let from_idx = self.idx(from.min(to));
+        let to_idx = self.idx(from.max(to));
+        if from_idx >= to_idx {
+            return self.clone();
+        }
+
+        let fade_len = fade.as_audio_idx(SAMPLE_RATE).max(0) as usize;
+        let lo = from_idx.saturating_sub(fade_len);
+        let hi = (to_idx + fade_len).min(self.buf.len());
+        // The index in `take` that corresponds to index zero in `self`.
+        let offset = (self.start_time() - take.start_time()).as_audio_idx(SAMPLE_RATE);
+        let scale = take.multiplier / self.multiplier;
+
+        let mix = |buf: &[i16], take_buf: &[i16]| -> AudioBuf {
+            let mut buf = buf.to_owned();
+            for (i, sample) in buf.iter_mut().enumerate().take(hi).skip(lo) {
+                let weight = if i < from_idx {
+                    (i - lo) as f32 / (from_idx - lo) as f32
+                } else if i >= to_idx {
+                    (hi - 1 - i) as f32 / (hi - to_idx) as f32
+                } else {
+                    1.0
+                };
+                let take_sample = sample_at(take_buf, i as i64 + offset as i64);
+                let mixed = *sample as f32 * (1.0 - weight) + take_sample as f32 * scale * weight;
+                *sample = mixed.round() as i16;
+            }
+            buf.into()
+        };
+        // The new take gets mixed into both versions of the audio. If the take doesn't have the
+        // other version, this snippet can't keep it either.
+        let take_buf = take.version(self.raw).unwrap_or(&take.buf);
+        let alternate = match (&self.alternate, take.version(!self.raw)) {
+            (Some(alt), Some(take_alt)) => Some(mix(alt, take_alt)),
+            _ => None,
+        };
+        TalkSnippet {
+            buf: mix(&self.buf, take_buf),
+            alternate,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the part of this snippet between `from` and `to`, or `None` if the snippet doesn't
+    /// overlap that range.
+    pub fn excerpt(&self, from: Time, to: Time) -> Option<TalkSnippet> {
+        if self.offset != 0 {
+            return self.baked().excerpt(from, to);
+        }
+        let from_idx = self.idx(from);
+        let to_idx = self.idx(to);
+        if from_idx >= to_idx {
+            return None;
+        }
+
+        let shift = TimeDiff::from_audio_idx(from_idx as i64, SAMPLE_RATE);
+        // The fades belong to the ends of the snippet, so they only survive in an excerpt that
+        // includes those ends.
+        let fade_in = if from_idx == 0 { self.fade_in } else { 0 };
+        let fade_out = if to_idx == self.buf.len() {
+            self.fade_out
+        } else {
+            0
+        };
+        Some(TalkSnippet {
+            start_time: self.start_time() + shift,
+            gain: self.gain.shifted(TimeDiff::ZERO - shift),
+            fade_in,
+            fade_out,
+            ..self.with_edited_audio(|buf| buf[from_idx..to_idx].to_owned())
+        })
+    }
+
+    /// Returns a new snippet that's `ratio` times as long as this one, but with the same pitch.
+    /// The new snippet starts at the same time as this one.
+    pub fn stretched(&self, ratio: f64) -> TalkSnippet {
+        if self.offset != 0 {
+            return self.baked().stretched(ratio);
+        }
+        TalkSnippet {
+            gain: self.gain.scaled(ratio),
+            fade_in: (self.fade_in as f64 * ratio).round() as i64,
+            fade_out: (self.fade_out as f64 * ratio).round() as i64,
+            ..self.with_edited_audio(|buf| time_stretch(buf, ratio))
+        }
+    }
+
+    /// Returns a new snippet, with silence at the beginning and end deleted.
+    ///
+    /// If this snippet has only silence, returns `None`.
+    pub fn trimmed(&self) -> Option<TalkSnippet> {
+        if self.offset != 0 {
+            return self.baked().trimmed();
+        }
+        let buf = self.buf.deref();
+        let first_nonzero = buf.iter().position(|&x| x != 0);
+        let last_nonzero = buf.iter().rposition(|&x| x != 0);
+
+        if let (Some(first), Some(last)) = (first_nonzero, last_nonzero) {
+            let shift = TimeDiff::from_audio_idx(first as i64, SAMPLE_RATE);
+            Some(TalkSnippet {
+                start_time: self.start_time() + shift,
+                gain: self.gain.shifted(TimeDiff::ZERO - shift),
+                ..self.with_edited_audio(|buf| buf[first..=last].to_owned())
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl TalkSnippet {
+    /// For each window of `QUIET_WINDOW` samples, is the audio in that window quiet? Audio counts
+    /// as quiet if its volume (after applying the multiplier) stays below `threshold`, as a
+    /// fraction of full scale.
+    fn quiet_windows(&self, threshold: f32) -> Vec<bool> {
+        let limit = threshold * i16::MAX as f32;
+        self.buf
+            .chunks(QUIET_WINDOW)
+            .map(|w| {
+                let peak = w.iter().map(|&x| (x as i32).abs()).max().unwrap_or(0);
+                (peak as f32) * self.multiplier < limit
+            })
+            .collect()
+    }
+
+    /// Returns a new snippet, with the quiet audio (see `quiet_windows`) at the beginning and end
+    /// deleted. We keep `padding` of the quiet audio on either side, so that the beginning and
+    /// end of the speech don't get cut off.
+    ///
+    /// If this snippet is quiet all the way through, returns `None`.
+    pub fn trimmed_quiet(&self, threshold: f32, padding: TimeDiff) -> Option<TalkSnippet> {
+        if self.offset != 0 {
+            return self.baked().trimmed_quiet(threshold, padding);
+        }
+        let quiet = self.quiet_windows(threshold);
+        let first = quiet.iter().position(|&q| !q)?;
+        let last = quiet.iter().rposition(|&q| !q)?;
+        let padding = padding.as_audio_idx(SAMPLE_RATE).max(0) as usize;
+        let from_idx = (first * QUIET_WINDOW).saturating_sub(padding);
+        let to_idx = ((last + 1) * QUIET_WINDOW + padding).min(self.buf.len());
+
+        let shift = TimeDiff::from_audio_idx(from_idx as i64, SAMPLE_RATE);
+        Some(TalkSnippet {
+            start_time: self.start_time() + shift,
+            gain: self.gain.shifted(TimeDiff::ZERO - shift),
+            ..self.with_edited_audio(|buf| buf[from_idx..to_idx].to_owned())
+        })
+    }
+
+    /// Returns a new snippet in which the quiet pauses (see `quiet_windows`) that are longer than
+    /// `max_pause` are shortened to `factor` times their length, but not to less than
+    /// `max_pause`. The audio gets cut from the middle of each pause, and the later audio moves
+    /// back to fill the gap.
+    ///
+    /// Quiet audio at the beginning or end of the snippet doesn't count as a pause; use
+    /// `trimmed_quiet` for that.
+    pub fn tightened(&self, threshold: f32, max_pause: TimeDiff, factor: f64) -> TalkSnippet {
+        if self.offset != 0 {
+            return self.baked().tightened(threshold, max_pause, factor);
+        }
+        let quiet = self.quiet_windows(threshold);
+        let max_len = max_pause.as_audio_idx(SAMPLE_RATE).max(0) as usize;
+        let factor = factor.max(0.0).min(1.0);
+
+        // The ranges of samples to cut out, in increasing order.
+        let mut cuts = Vec::new();
+        let mut i = quiet.iter().position(|&q| !q).unwrap_or(quiet.len());
+        while let Some(start) = quiet[i..].iter().position(|&q| q).map(|p| i + p) {
+            let end = match quiet[start..].iter().position(|&q| !q) {
+                Some(p) => start + p,
+                // The snippet ends quietly, so this isn't a pause.
+                None => break,
+            };
+            let len = (end - start) * QUIET_WINDOW;
+            if len > max_len {
+                let new_len = ((len as f64 * factor) as usize).max(max_len);
+                let from = start * QUIET_WINDOW + new_len / 2;
+                cuts.push((from, from + len - new_len));
+            }
+            i = end;
+        }
+
+        // Cut from the back, so that the earlier indices stay valid.
+        let mut ret = self.clone();
+        for &(from, to) in cuts.iter().rev() {
+            ret = ret.snipped_idx(from, to);
+        }
+        ret
+    }
+}
+
+impl TalkSnippets {
+    pub fn with_new_snippet(&self, snip: TalkSnippet) -> (TalkSnippets, TalkSnippetId) {
+        let mut ret = self.clone();
+        ret.last_id += 1;
+        let id = TalkSnippetId(ret.last_id);
+        ret.snippets.insert(id, snip);
+        (ret, id)
+    }
+
+    pub fn has_snippet(&self, snip: TalkSnippetId) -> bool {
+        self.snippets.contains_key(&snip)
+    }
+
+    fn with_modified_snippet(
+        &self,
+        id: TalkSnippetId,
+        f: impl FnOnce(&TalkSnippet) -> TalkSnippet,
+    ) -> TalkSnippets {
+        let mut ret = self.clone();
+        let snip = f(ret.snippet(id));
+        ret.snippets.insert(id, snip);
+        ret
+    }
+
+    pub fn with_shifted_snippet(&self, id: TalkSnippetId, shift: TimeDiff) -> TalkSnippets {
+        self.with_modified_snippet(id, |s| s.shifted(shift))
+    }
+
+    pub fn with_multiplied_snippet(&self, id: TalkSnippetId, factor: f64) -> TalkSnippets {
+        self.with_modified_snippet(id, |s| s.multiplied(factor as f32))
+    }
+
+    /// Multiplies the volume of every snippet by `factor`.
+    pub fn with_all_multiplied(&self, factor: f64) -> TalkSnippets {
+        let mut ret = self.clone();
+        for (id, snip) in self.snippets() {
+            ret.snippets.insert(id, snip.multiplied(factor as f32));
+        }
+        ret
+    }
+
+    pub fn with_snippet_name(&self, id: TalkSnippetId, name: Option<String>) -> TalkSnippets {
+        self.with_modified_snippet(id, |s| s.with_name(name))
+    }
+
+    pub fn with_snippet_label(&self, id: TalkSnippetId, label: Option<LabelColor>) -> TalkSnippets {
+        self.with_modified_snippet(id, |s| s.with_label(label))
+    }
+
+    pub fn with_snippet_group(&self, id: TalkSnippetId, group: Option<GroupId>) -> TalkSnippets {
+        self.with_modified_snippet(id, |s| s.with_group(group))
+    }
+
+    pub fn with_muted_snippet(&self, id: TalkSnippetId, muted: bool) -> TalkSnippets {
+        self.with_modified_snippet(id, |s| s.with_muted(muted))
+    }
+
+    pub fn with_raw_snippet(&self, id: TalkSnippetId, raw: bool) -> TalkSnippets {
+        self.with_modified_snippet(id, |s| s.with_raw(raw))
+    }
+
+    pub fn with_replacement_snippet(&self, id: TalkSnippetId, snip: TalkSnippet) -> TalkSnippets {
+        self.with_modified_snippet(id, |_| snip)
+    }
+
+    pub fn with_silenced_snippet(&self, id: TalkSnippetId, start: Time, end: Time) -> TalkSnippets {
+        self.with_modified_snippet(id, |s| s.silenced(start, end))
+    }
+
+    pub fn with_punched_in_snippet(
+        &self,
+        id: TalkSnippetId,
+        take: &TalkSnippet,
+        start: Time,
+        end: Time,
+        fade: TimeDiff,
+    ) -> TalkSnippets {
+        self.with_modified_snippet(id, |s| s.punched_in(take, start, end, fade))
+    }
+
+    pub fn with_snippet_offset(&self, id: TalkSnippetId, offset: TimeDiff) -> TalkSnippets {
+        self.with_modified_snippet(id, |s| s.with_offset(offset))
+    }
+
+    pub fn with_gain_envelope(&self, id: TalkSnippetId, gain: GainEnvelope) -> TalkSnippets {
+        self.with_modified_snippet(id, |s| s.with_gain(gain))
+    }
+
+    pub fn with_snipped_snippet(&self, id: TalkSnippetId, start: Time, end: Time) -> TalkSnippets {
+        let ret = self.with_modified_snippet(id, |s| s.snipped(start, end));
+        if ret.snippet(id).buf.is_empty() {
+            self.without_snippet(id)
+        } else {
+            ret
+        }
+    }
+
+    /// Returns the audio between `from` and `to`, moved earlier so that `from` becomes the
+    /// beginning.
+    pub fn excerpt(&self, from: Time, to: Time) -> TalkSnippets {
+        let shift = Time::ZERO - from;
+        TalkSnippets {
+            last_id: self.last_id,
+            snippets: self
+                .snippets
+                .iter()
+                .filter_map(|(&id, snip)| Some((id, snip.excerpt(from, to)?.shifted(shift))))
+                .collect(),
+        }
+    }
+
+    pub fn without_snippet(&self, id: TalkSnippetId) -> TalkSnippets {
+        let mut ret = self.clone();
+        ret.snippets.remove(&id);
+        ret
+    }
+
+    pub fn snippet(&self, id: TalkSnippetId) -> &TalkSnippet {
+        self.snippets.get(&id).unwrap()
+    }
+
+    pub fn snippets(&self) -> impl Iterator<Item = (TalkSnippetId, &TalkSnippet)> {
+        self.snippets.iter().map(|(k, v)| (*k, v))
+    }
+
+    /// Estimates the number of bytes of memory used by these snippets, not counting any audio
+    /// that is shared with snippets already in `seen`.
+    pub fn memory_size(&self, seen: &mut HashSet<usize>) -> usize {
+        self.snippets.values().map(|s| s.memory_size(seen)).sum()
+    }
+
+    pub fn end_time(&self) -> Time {
+        self.snippets
+            .values()
+            .map(|snip| snip.end_time())
+            .max()
+            .unwrap_or(Time::ZERO)
+    }
+
+    /// Returns the time intervals in which the given snippet overlaps with some other snippet.
+    pub fn overlaps(&self, id: TalkSnippetId) -> Vec<(Time, Time)> {
+        let snip = self.snippet(id);
+        self.snippets
+            .iter()
+            .filter(|(&other_id, _)| other_id != id)
+            .filter_map(|(_, other)| {
+                let start = snip.start_time().max(other.start_time());
+                let end = snip.end_time().min(other.end_time());
+                if start < end {
+                    Some((start, end))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Fills the provided buffer with samples from the cursor, and advance the cursor.
+    ///
+    /// Where snippets overlap, the later one fades in while the earlier one fades out, so that
+    /// overlapping snippets don't clip. This recomputes the crossfades every time, so when mixing
+    /// lots of buffers prefer [`TalkSnippets::mix_with_crossfades`].
+    pub fn mix_to<B: DerefMut<Target = [i16]>>(
+        &self,
+        cursor: &Cursor<usize, TalkSnippetId>,
+        buf: B,
+    ) {
+        self.mix_with_crossfades(&self.crossfades(), cursor, buf);
+    }
+
+    /// Like [`TalkSnippets::mix_to`], but with crossfades that were computed (by
+    /// [`TalkSnippets::crossfades`]) ahead of time.
+    pub fn mix_with_crossfades<B: DerefMut<Target = [i16]>>(
+        &self,
+        crossfades: &Crossfades,
+        cursor: &Cursor<usize, TalkSnippetId>,
+        mut buf: B,
+    ) {
+        struct MixSpan<'a> {
+            id: TalkSnippetId,
+            snip: &'a TalkSnippet,
+            start: usize,
+            end: usize,
+            gain_cursor: usize,
+        }
+
+        let buf: &mut [i16] = &mut buf;
+        let (curs_start, curs_end) = cursor.current();
+        let mut spans: Vec<_> = cursor
+            .active_spans()
+            .map(|sp| {
+                let snip = self.snippet(sp.id);
+                MixSpan {
+                    id: sp.id,
+                    snip,
+                    start: sp.start,
+                    end: sp.start + snip.buf.len(),
+                    gain_cursor: 0,
+                }
+            })
+            .collect();
+        spans.sort_by_key(|sp| (sp.start, sp.id));
+
+        for pos in curs_start..curs_end {
+            // Each span is attenuated by all the spans that start after it.
+            let mut remaining = 1.0;
+            for sp in spans.iter_mut().rev() {
+                if pos < sp.start || pos >= sp.end {
+                    continue;
+                }
+                let fade = crossfades.fade_at(sp.id, sp.start, sp.end, pos);
+                let weight = fade * remaining;
+                remaining *= 1.0 - fade;
+                if sp.snip.muted {
+                    continue;
+                }
+
+                let idx = pos - sp.start;
+                let mut factor = sp.snip.multiplier * sp.snip.fade_at(idx) * weight;
+                if !sp.snip.gain.is_empty() {
+                    let offset = TimeDiff::from_audio_idx(idx as i64, SAMPLE_RATE);
+                    factor *= sp.snip.gain.gain_at_cursor(offset, &mut sp.gain_cursor);
+                }
+                buf[pos - curs_start] += (sp.snip.sample(idx) as f32 * factor) as i16;
+            }
+        }
+    }
+
+    /// Computes where the snippets need to be crossfaded. This only depends on the snippets'
+    /// positions, so it only needs to be recomputed when they change.
+    pub fn crossfades(&self) -> Crossfades {
+        let mut spans: Vec<_> = self.snippet_spans().collect();
+        spans.sort_by_key(|sp| (sp.start, sp.id));
+
+        let mut overlap_ends = HashMap::new();
+        let mut max_end = 0;
+        for sp in spans {
+            if max_end > sp.start {
+                overlap_ends.insert(sp.id, max_end);
+            }
+            max_end = max_end.max(sp.end.unwrap_or(usize::MAX));
+        }
+        Crossfades { overlap_ends }
+    }
+
+    pub fn snippet_spans<'a>(&'a self) -> impl Iterator<Item = Span<usize, TalkSnippetId>> + 'a {
+        self.snippets.iter().map(|(&id, snip)| {
+            let start = snip.start_time().as_audio_idx(SAMPLE_RATE);
+            let end = start + snip.buf.len();
+            Span {
+                start,
+                end: Some(end),
+                id,
+            }
+        })
+    }
+}
+
+/// Where the talk snippets overlap one another, as computed by [`TalkSnippets::crossfades`].
+///
+/// Each snippet fades in over the part where it overlaps an earlier snippet (and fades out again if
+/// it ends before the earlier one does). The earlier snippets are attenuated correspondingly, so
+/// the weights at each position add up to at most one.
+#[derive(Clone, Debug, Default)]
+pub struct Crossfades {
+    // For each snippet that starts before some earlier snippet ends, the (audio index of the) last
+    // end of an earlier snippet.
+    overlap_ends: HashMap<TalkSnippetId, usize>,
+}
+
+impl Crossfades {
+    // The fade of the snippet `id`, which spans from `start` to `end`, at position `pos`.
+    fn fade_at(&self, id: TalkSnippetId, start: usize, end: usize, pos: usize) -> f32 {
+        match self.overlap_ends.get(&id) {
+            Some(&overlap_end) if overlap_end > pos => {
+                let len = (overlap_end.min(end) - start).min(CROSSFADE_LEN).max(1);
+                let fade_in = (pos - start + 1) as f32 / len as f32;
+                let fade_out = if end <= overlap_end {
+                    (end - pos) as f32 / len as f32
+                } else {
+                    1.0
+                };
+                fade_in.min(fade_out).min(1.0)
+            }
+            _ => 1.0,
+        }
+    }
+}
+
+impl std::iter::FromIterator<(TalkSnippetId, TalkSnippet)> for TalkSnippets {
+    fn from_iter<I: IntoIterator<Item = (TalkSnippetId, TalkSnippet)>>(iter: I) -> TalkSnippets {
+        let snippets: OrdMap<TalkSnippetId, TalkSnippet> = iter.into_iter().collect();
+        let last_id = snippets.keys().max().unwrap_or(&TalkSnippetId(0)).0;
+        TalkSnippets { last_id, snippets }
+    }
+}
+
+// Here is the serialization for audio. Note that the serialization format needs to remain
+// stable, because it is used for file saving.
+//
+// Specifically, we serialize the audio state as a map id -> snippet data. Any other fields
+// on `TalkSnippets` are ignored, and must be reconstituted from the snippet map on
+// deserialization.
+impl Serialize for TalkSnippets {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        self.snippets.serialize(ser)
+    }
+}
+
+impl<'de> Deserialize<'de> for TalkSnippets {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<TalkSnippets, D::Error> {
+        let snips: OrdMap<TalkSnippetId, TalkSnippet> = Deserialize::deserialize(de)?;
+        let max_id = snips.keys().max().unwrap_or(&TalkSnippetId(0)).0;
+        Ok(TalkSnippets {
+            snippets: snips,
+            last_id: max_id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! snips {
+        ($($time:expr => $buf:expr),*) => {
+            {
+                let mut ret = TalkSnippets::default();
+                $(
+                    let buf: &[i16] = $buf;
+                    let time = Time::from_audio_idx($time, SAMPLE_RATE);
+                    ret = ret.with_new_snippet(TalkSnippet::new(buf.to_owned(), time, 1.0)).0;
+                )*
+
+                ret
+            }
+        }
+    }
+
+    #[test]
+    fn trimmed_quiet() {
+        let mut buf = vec![10; 3 * QUIET_WINDOW];
+        buf.extend(vec![1000; QUIET_WINDOW]);
+        buf.extend(vec![0; 2 * QUIET_WINDOW]);
+        let snip = TalkSnippet::new(buf, Time::ZERO, 1.0);
+        let padding = TimeDiff::from_audio_idx(QUIET_WINDOW as i64, SAMPLE_RATE);
+
+        let trimmed = snip.trimmed_quiet(0.01, padding).unwrap();
+        assert_eq!(trimmed.buf().len(), 3 * QUIET_WINDOW);
+        assert_eq!(
+            trimmed.start_time(),
+            Time::from_audio_idx(2 * QUIET_WINDOW, SAMPLE_RATE)
+        );
+        assert_eq!(trimmed.buf()[QUIET_WINDOW], 1000);
+
+        // Increasing the multiplier makes the quiet parts louder.
+        let louder = snip.multiplied(200.0);
+        assert_eq!(
+            louder.trimmed_quiet(0.01, padding).unwrap().buf().len(),
+            5 * QUIET_WINDOW
+        );
+
+        assert!(snip.trimmed_quiet(0.5, padding).is_none());
+    }
+
+    #[test]
+    fn loudness() {
+        let buf: Vec<i16> = (0..SAMPLE_RATE)
+            .map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                (10000.0 * (2.0 * std::f64::consts::PI * 1000.0 * t).sin()) as i16
+            })
+            .collect();
+        let snip = TalkSnippet::new(buf, Time::ZERO, 1.0);
+        let loudness = snip.loudness().unwrap();
+        assert!(loudness.is_finite());
+        let louder = snip.multiplied(2.0).loudness().unwrap();
+        assert!((louder - loudness - 6.02).abs() < 0.01);
+
+        let silent = TalkSnippet::new(vec![0; SAMPLE_RATE as usize], Time::ZERO, 1.0);
+        assert_eq!(silent.loudness().unwrap(), -f64::INFINITY);
+    }
+
+    #[test]
+    fn tightened() {
+        let loud = vec![1000; QUIET_WINDOW];
+        let mut buf = vec![0; 10 * QUIET_WINDOW];
+        buf.extend(&loud);
+        buf.extend(vec![0; 10 * QUIET_WINDOW]);
+        buf.extend(&loud);
+        buf.extend(vec![0; 2 * QUIET_WINDOW]);
+        buf.extend(&loud);
+        buf.extend(vec![0; 10 * QUIET_WINDOW]);
+        let snip = TalkSnippet::new(buf, Time::ZERO, 1.0);
+        let max_pause = TimeDiff::from_audio_idx(4 * QUIET_WINDOW as i64, SAMPLE_RATE);
+
+        // Only the long pause in the middle gets shorter; the quiet parts at the beginning and end
+        // stay.
+        let tight = snip.tightened(0.01, max_pause, 0.5);
+        assert_eq!(tight.buf().len(), snip.buf().len() - 5 * QUIET_WINDOW);
+        assert_eq!(tight.start_time(), snip.start_time());
+        let loud_windows = tight.quiet_windows(0.01).iter().filter(|q| !**q).count();
+        assert_eq!(loud_windows, 3);
+
+        // Pauses don't get shorter than `max_pause`.
+        let tight = snip.tightened(0.01, max_pause, 0.0);
+        assert_eq!(tight.buf().len(), snip.buf().len() - 6 * QUIET_WINDOW);
+    }
+
+    #[test]
+    fn punch_in() {
+        let snip = TalkSnippet::new(vec![1; 18], Time::ZERO, 1.0);
+        let take = TalkSnippet::new(vec![10; 24], Time::ZERO, 1.0);
+        let from = Time::from_audio_idx(6, SAMPLE_RATE);
+        let to = Time::from_audio_idx(12, SAMPLE_RATE);
+        let out = snip.punched_in(&take, from, to, TimeDiff::ZERO);
+        let mut expected = vec![1; 6];
+        expected.extend_from_slice(&[10; 6]);
+        expected.extend_from_slice(&[1; 6]);
+        assert_eq!(out.buf(), &expected[..]);
+    }
+
+    #[test]
+    fn punch_in_crossfade() {
+        let snip = TalkSnippet::new(vec![0; 30], Time::ZERO, 1.0);
+        let take = TalkSnippet::new(vec![60; 30], Time::ZERO, 1.0);
+        let from = Time::from_audio_idx(12, SAMPLE_RATE);
+        let to = Time::from_audio_idx(18, SAMPLE_RATE);
+        let fade = TimeDiff::from_audio_idx(6, SAMPLE_RATE);
+        let out = snip.punched_in(&take, from, to, fade);
+        let mut expected = vec![0; 6];
+        expected.extend_from_slice(&[0, 10, 20, 30, 40, 50]);
+        expected.extend_from_slice(&[60; 6]);
+        expected.extend_from_slice(&[50, 40, 30, 20, 10, 0]);
+        expected.extend_from_slice(&[0; 6]);
+        assert_eq!(out.buf(), &expected[..]);
+    }
+
+    #[test]
+    fn split() {
+        let snip = TalkSnippet::new(vec![1, 2, 3, 4], Time::from_audio_idx(2, SAMPLE_RATE), 1.0)
+            .with_name(Some("hello".to_owned()))
+            .with_label(Some(LabelColor::Blue));
+        let (a, b) = snip.split(Time::from_audio_idx(3, SAMPLE_RATE)).unwrap();
+        assert_eq!(a.buf(), &[1]);
+        assert_eq!(b.buf(), &[2, 3, 4]);
+        assert_eq!(b.start_time(), Time::from_audio_idx(3, SAMPLE_RATE));
+        assert_eq!(b.name(), Some("hello"));
+        assert_eq!(b.label(), Some(LabelColor::Blue));
+
+        assert!(snip.split(snip.start_time()).is_none());
+        assert!(snip.split(snip.end_time()).is_none());
+    }
+
+    #[test]
+    fn all_multiplied() {
+        let (snips, id1) = TalkSnippets::default().with_new_snippet(TalkSnippet::new(
+            vec![100; 10],
+            Time::ZERO,
+            1.0,
+        ));
+        let (snips, id2) = snips.with_new_snippet(TalkSnippet::new(vec![100; 10], Time::ZERO, 0.5));
+        let snips = snips.with_all_multiplied(2.0);
+        assert_eq!(snips.snippet(id1).multiplier(), 2.0);
+        assert_eq!(snips.snippet(id2).multiplier(), 1.0);
+    }
+
+    #[test]
+    fn muted() {
+        let snips = snips!(0 => &[1, 2, 3]);
+        let id = snips.snippets().next().unwrap().0;
+        let snips = snips.with_muted_snippet(id, true);
+        let mut c = Cursor::new(snips.snippet_spans(), 0, 0);
+        let mut out = vec![0; 3];
+        c.advance_to(0, 3);
+        snips.mix_to(&c, &mut out[..]);
+        assert_eq!(out, vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn offset() {
+        let snips = snips!(0 => &[1, 2, 3, 4]);
+        let id = snips.snippets().next().unwrap().0;
+        let mix = |snips: &TalkSnippets| {
+            let mut c = Cursor::new(snips.snippet_spans(), 0, 0);
+            let mut out = vec![0; 4];
+            c.advance_to(0, 4);
+            snips.mix_to(&c, &mut out[..]);
+            out
+        };
+
+        let back = snips.with_snippet_offset(id, TimeDiff::from_audio_idx(1, SAMPLE_RATE));
+        assert_eq!(mix(&back), vec![2, 3, 4, 0]);
+        assert_eq!(back.snippet(id).end_time(), snips.snippet(id).end_time());
+        let forward = snips.with_snippet_offset(id, TimeDiff::from_audio_idx(-2, SAMPLE_RATE));
+        assert_eq!(mix(&forward), vec![0, 0, 1, 2]);
+        assert_eq!(&forward.snippet(id).samples()[..], &[0, 0, 1, 2]);
+
+        // Editing the audio keeps the offset.
+        let snip = forward.snippet(id);
+        let (a, b) = snip.split(Time::from_micros(63)).unwrap();
+        assert_eq!(a.buf(), &[0, 0, 1]);
+        assert_eq!(b.buf(), &[2]);
+        assert_eq!(b.offset(), TimeDiff::ZERO);
+
+        assert!(snip.with_offset(snip.offset()) == *snip);
+
+        // The offset can't slide all the audio out of the snippet.
+        let far = snip.with_offset(TimeDiff::from_audio_idx(100, SAMPLE_RATE));
+        assert_eq!(&far.samples()[..], &[4, 0, 0, 0]);
+    }
+
+    #[test]
+    fn fades() {
+        let snips = snips!(0 => &[100; 8]);
+        let id = snips.snippets().next().unwrap().0;
+        let samples = |n| TimeDiff::from_audio_idx(n, SAMPLE_RATE);
+        let snip = snips.snippet(id).with_fades(samples(4), samples(2));
+        assert_eq!(snip.fade_in(), samples(4));
+        assert_eq!(snip.fade_out(), samples(2));
+
+        let snips = snips.with_replacement_snippet(id, snip.clone());
+        let mut c = Cursor::new(snips.snippet_spans(), 0, 0);
+        let mut out = vec![0; 8];
+        c.advance_to(0, 8);
+        snips.mix_to(&c, &mut out[..]);
+        assert_eq!(out, vec![0, 25, 50, 75, 100, 100, 50, 0]);
+
+        // Splitting keeps each fade on the part with its end of the snippet.
+        let (a, b) = snip.split(Time::from_micros(100)).unwrap();
+        assert_eq!((a.fade_in(), a.fade_out()), (samples(4), TimeDiff::ZERO));
+        assert_eq!((b.fade_in(), b.fade_out()), (TimeDiff::ZERO, samples(2)));
+
+        // Fades can't be longer than the snippet.
+        let long = snip.with_fades(samples(100), samples(-3));
+        assert_eq!(long.fade_in(), samples(8));
+        assert_eq!(long.fade_out(), TimeDiff::ZERO);
+    }
+
+    #[test]
+    fn raw() {
+        let snip = TalkSnippet::new(vec![0, 2, 3, 0], Time::ZERO, 1.0)
+            .with_raw_recording(vec![5, 6, 7, 8]);
+        assert!(snip.has_raw());
+        assert!(!snip.is_raw());
+
+        // Edits apply to both versions of the audio.
+        let trimmed = snip.trimmed().unwrap();
+        assert_eq!(trimmed.buf(), &[2, 3]);
+        let raw = trimmed.with_raw(true);
+        assert!(raw.is_raw());
+        assert_eq!(raw.buf(), &[6, 7]);
+        let silenced = raw.silenced(Time::ZERO, Time::from_micros(50));
+        assert_eq!(silenced.buf(), &[0, 7]);
+        assert_eq!(silenced.with_raw(false).buf(), &[0, 3]);
+
+        // Processing the audio again replaces the processed version.
+        let processed = raw.with_processed_audio(vec![1, 1]);
+        assert!(!processed.is_raw());
+        assert_eq!(processed.buf(), &[1, 1]);
+        assert_eq!(&processed.raw_samples()[..], &[6, 7]);
+        // If there was no raw version, the original audio becomes the raw version.
+        let plain = TalkSnippet::new(vec![1, 2], Time::ZERO, 1.0);
+        let processed = plain.with_processed_audio(vec![3, 4]);
+        assert_eq!(processed.with_raw(true).buf(), &[1, 2]);
+
+        // Replacing the audio forgets the raw version.
+        assert!(!snip.with_samples(vec![1, 2, 3, 4]).has_raw());
+        // Snippets without raw audio can't switch to it.
+        assert!(!plain.with_raw(true).is_raw());
+    }
+
+    #[test]
+    fn excerpt() {
+        let snips = snips!(0 => &[1, 2, 3, 4, 5], 10 => &[6, 7, 8]);
+        let from = Time::from_audio_idx(3, SAMPLE_RATE);
+        let to = Time::from_audio_idx(11, SAMPLE_RATE);
+        let ex = snips.excerpt(from, to);
+        let ex: Vec<_> = ex.snippets().map(|(_, s)| s).collect();
+        assert_eq!(ex.len(), 2);
+        assert_eq!(ex[0].buf(), &[4, 5]);
+        assert_eq!(ex[0].start_time(), Time::ZERO);
+        assert_eq!(ex[1].buf(), &[6]);
+        assert_eq!(ex[1].start_time(), Time::from_audio_idx(7, SAMPLE_RATE));
+
+        assert!(snips.excerpt(to, to).snippets().next().is_none());
+    }
+
+    #[test]
+    fn forward() {
+        let snips = snips!(0 => &[1, 2, 3, 4, 5]);
+        let mut c = Cursor::new(snips.snippet_spans(), 0, 0);
+        let mut out = vec![0; 5];
+        c.advance_to(0, 5);
+        snips.mix_to(&c, &mut out[..]);
+        assert_eq!(out, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn forward_offset() {
+        let snips = snips!(5 => &[1, 2, 3, 4, 5]);
+        let c = Cursor::new(snips.snippet_spans(), 0, 15);
+        let mut out = vec![0; 15];
+        snips.mix_to(&c, &mut out[..]);
+        assert_eq!(out, vec![0, 0, 0, 0, 1, 2, 3, 4, 5, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn backward() {
+        let snips = snips!(3 => &[1, 2, 3, 4, 5]);
+        let mut c = Cursor::new(snips.snippet_spans(), 9, 9);
+        let mut out = vec![0; 10];
+        c.advance_to(0, 10);
+        snips.mix_to(&c, &mut out[..]);
+        // 2 leading zeros, not 3, because of rounding in audio/index conversion.
+        assert_eq!(out, vec![0, 0, 1, 2, 3, 4, 5, 0, 0, 0]);
+    }
+
+    #[test]
+    fn multiple_snippets() {
+        let snips = snips!(
+            0 => &[1, 2, 3],
+            3 => &[1, 2, 3]
+        );
+        let c = Cursor::new(snips.snippet_spans(), 0, 10);
+        let mut out = vec![0; 10];
+        snips.mix_to(&c, &mut out[..]);
+        // The overlap is only one sample long, so the second snippet takes over immediately.
+        assert_eq!(out, vec![1, 2, 1, 2, 3, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn multiple_snippets_backwards() {
+        let snips = snips!(
+            0 => &[1, 2, 3],
+            3 => &[1, 2, 3]
+        );
+        let mut c = Cursor::new(snips.snippet_spans(), 10, 20);
+        let mut out = vec![0; 10];
+        c.advance_to(0, 10);
+        snips.mix_to(&c, &mut out[..]);
+        assert_eq!(out, vec![1, 2, 1, 2, 3, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn crossfade() {
+        let snips = snips!(
+            0 => &[100; 8],
+            6 => &[100; 6]
+        );
+        let c = Cursor::new(snips.snippet_spans(), 0, 12);
+        let mut out = vec![0; 12];
+        snips.mix_to(&c, &mut out[..]);
+        // The overlap is two samples long; over those two samples, the first snippet fades out
+        // and the second one fades in.
+        assert_eq!(out, vec![100; 12]);
+    }
+
+    #[test]
+    fn mix_in_chunks() {
+        let gain = GainEnvelope::default()
+            .with_point(TimeDiff::from_audio_idx(100, SAMPLE_RATE), 0.5)
+            .with_point(TimeDiff::from_audio_idx(600, SAMPLE_RATE), 2.0);
+        let first = TalkSnippet::new(vec![100; 1000], Time::ZERO, 1.0).with_gain(gain);
+        let snips = TalkSnippets::default().with_new_snippet(first).0;
+        // The second snippet ends before the first one, and the third overlaps both of them.
+        let snips = snips
+            .with_new_snippet(TalkSnippet::new(
+                vec![200; 300],
+                Time::from_audio_idx(400, SAMPLE_RATE),
+                1.0,
+            ))
+            .0
+            .with_new_snippet(TalkSnippet::new(
+                vec![300; 1000],
+                Time::from_audio_idx(650, SAMPLE_RATE),
+                1.0,
+            ))
+            .0;
+
+        let c = Cursor::new(snips.snippet_spans(), 0, 2000);
+        let mut expected = vec![0; 2000];
+        snips.mix_to(&c, &mut expected[..]);
+
+        let crossfades = snips.crossfades();
+        let mut c = Cursor::new(snips.snippet_spans(), 0, 0);
+        let mut out = Vec::new();
+        for start in (0..2000).step_by(37) {
+            let end = (start + 37).min(2000);
+            c.advance_to(start, end);
+            let mut chunk = vec![0; end - start];
+            snips.mix_with_crossfades(&crossfades, &c, &mut chunk[..]);
+            out.extend(chunk);
+        }
+        assert_eq!(out, expected);
+        assert!(expected[500..700].iter().all(|&x| x > 0));
+    }
+
+    #[test]
+    fn non_overlapping_snippets() {
+        let snips = snips!(
+            0 => &[1, 2, 3],
+            12 => &[1, 2, 3]
+        );
+        let mut c = Cursor::new(snips.snippet_spans(), 0, 10);
+        let mut out = vec![0; 10];
+        snips.mix_to(&c, &mut out[..]);
+        assert_eq!(out, vec![1, 2, 3, 0, 0, 0, 0, 0, 0, 0]);
+
+        let mut out = vec![0; 10];
+        c.advance_to(10, 20);
+        snips.mix_to(&c, &mut out[..]);
+        assert_eq!(out, vec![0, 0, 1, 2, 3, 0, 0, 0, 0, 0]);
+    }
+}
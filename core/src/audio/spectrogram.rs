@@ -0,0 +1,180 @@
+//! Spectrograms of audio snippets, for showing in the timeline.
+
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+
+use super::SAMPLE_RATE;
+
+/// The number of samples in each FFT window.
+const FFT_SIZE: usize = 1024;
+/// The number of frequency bands in a spectrogram.
+const BANDS: usize = 64;
+/// The lowest frequency (in Hz) that we show.
+const MIN_FREQ: f64 = 60.0;
+/// Anything quieter than this (in dB, relative to a full-scale sine wave) gets shown as silence.
+const MIN_DB: f32 = -90.0;
+
+/// A grid of loudness values, one column for every step in time and one row for every frequency
+/// band.
+///
+/// The frequency bands are spaced logarithmically (which is how we hear them), and row 0 is the
+/// highest frequency, so that the rows can be drawn from top to bottom.
+#[derive(Clone, Debug)]
+pub struct Spectrogram {
+    columns: usize,
+    // The loudness values, between 0.0 (silent) and 1.0 (full scale), column by column.
+    values: Vec<f32>,
+}
+
+impl Spectrogram {
+    /// Computes the spectrogram of some audio (at our usual sample rate), with a column for every
+    /// `step_secs` seconds.
+    ///
+    /// This is quite a bit of work for a long snippet, so it shouldn't be done on the UI thread.
+    pub fn compute(buf: &[i16], step_secs: f64) -> Spectrogram {
+        let step = ((step_secs * SAMPLE_RATE as f64).round() as usize).max(1);
+        let columns = (buf.len() + step - 1) / step;
+        let fft = FftPlanner::<f32>::new().plan_fft_forward(FFT_SIZE);
+        let window: Vec<f32> = (0..FFT_SIZE)
+            .map(|i| {
+                let x = i as f32 / FFT_SIZE as f32;
+                0.5 - 0.5 * (2.0 * std::f32::consts::PI * x).cos()
+            })
+            .collect();
+        let bands = band_edges();
+        // A full-scale sine wave should come out at 0 dB. The factor of 0.5 is the average value
+        // of the window.
+        let norm = 2.0 / (FFT_SIZE as f32 * 0.5 * i16::MAX as f32);
+
+        let mut values = Vec::with_capacity(columns * BANDS);
+        let mut fft_buf = vec![Complex::new(0.0, 0.0); FFT_SIZE];
+        for col in 0..columns {
+            // Center the window on the time of the column.
+            let center = col * step + step / 2;
+            for (i, x) in fft_buf.iter_mut().enumerate() {
+                let sample = (center + i)
+                    .checked_sub(FFT_SIZE / 2)
+                    .and_then(|idx| buf.get(idx))
+                    .copied()
+                    .unwrap_or(0);
+                *x = Complex::new(sample as f32 * window[i], 0.0);
+            }
+            fft.process(&mut fft_buf);
+
+            for band in (0..BANDS).rev() {
+                let (lo, hi) = bands[band];
+                let mag = fft_buf[lo..hi]
+                    .iter()
+                    .map(|c| c.norm())
+                    .fold(0.0f32, f32::max);
+                let db = 20.0 * (mag * norm).max(1e-10).log10();
+                values.push(((db - MIN_DB) / -MIN_DB).max(0.0).min(1.0));
+            }
+        }
+        Spectrogram { columns, values }
+    }
+
+    /// The number of time steps.
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// The number of frequency bands.
+    pub fn rows(&self) -> usize {
+        BANDS
+    }
+
+    /// The loudness (between 0.0 and 1.0) in a column and a row.
+    pub fn value(&self, column: usize, row: usize) -> f32 {
+        self.values[column * BANDS + row]
+    }
+
+    /// Returns the spectrogram as an image, with one pixel for each column and row, in RGBA
+    /// format (without premultiplied alpha).
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let mut ret = vec![0; self.columns * BANDS * 4];
+        for col in 0..self.columns {
+            for row in 0..BANDS {
+                let idx = (row * self.columns + col) * 4;
+                ret[idx..(idx + 4)].copy_from_slice(&color(self.value(col, row)));
+            }
+        }
+        ret
+    }
+}
+
+/// The ranges of FFT bins that go into each frequency band, from lowest to highest.
+fn band_edges() -> Vec<(usize, usize)> {
+    let nyquist = SAMPLE_RATE as f64 / 2.0;
+    let bin = |freq: f64| ((freq / nyquist * (FFT_SIZE / 2) as f64) as usize).min(FFT_SIZE / 2);
+    (0..BANDS)
+        .map(|b| {
+            let lo = MIN_FREQ * (nyquist / MIN_FREQ).powf(b as f64 / BANDS as f64);
+            let hi = MIN_FREQ * (nyquist / MIN_FREQ).powf((b + 1) as f64 / BANDS as f64);
+            let lo = bin(lo);
+            // Every band needs at least one bin.
+            (lo, bin(hi).max(lo + 1))
+        })
+        .collect()
+}
+
+/// Maps a loudness between 0.0 and 1.0 to a color: dark blue for quiet, through purple and
+/// orange, to pale yellow for loud.
+fn color(value: f32) -> [u8; 4] {
+    const STOPS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.1],
+        [0.45, 0.1, 0.5],
+        [0.95, 0.45, 0.1],
+        [1.0, 1.0, 0.75],
+    ];
+    let x = value * (STOPS.len() - 1) as f32;
+    let i = (x.floor() as usize).min(STOPS.len() - 2);
+    let t = x - i as f32;
+    let mut ret = [255; 4];
+    for c in 0..3 {
+        let v = STOPS[i][c] + t * (STOPS[i + 1][c] - STOPS[i][c]);
+        ret[c] = (v * 255.0).round() as u8;
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f64, secs: f64) -> Vec<i16> {
+        let len = (secs * SAMPLE_RATE as f64) as usize;
+        (0..len)
+            .map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                ((2.0 * std::f64::consts::PI * freq * t).sin() * i16::MAX as f64) as i16
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sine_wave() {
+        let spec = Spectrogram::compute(&sine(1000.0, 1.0), 0.1);
+        assert_eq!(spec.columns(), 10);
+
+        // The loudest band should be the one containing 1kHz, and it should be close to full
+        // scale.
+        let col = 5;
+        let loudest = (0..spec.rows())
+            .max_by(|&a, &b| spec.value(col, a).partial_cmp(&spec.value(col, b)).unwrap())
+            .unwrap();
+        let (lo, hi) = band_edges()[spec.rows() - 1 - loudest];
+        let bin_freq = SAMPLE_RATE as f64 / FFT_SIZE as f64;
+        assert!(lo as f64 * bin_freq <= 1000.0 + bin_freq);
+        assert!(hi as f64 * bin_freq >= 1000.0 - bin_freq);
+        assert!(spec.value(col, loudest) > 0.95);
+    }
+
+    #[test]
+    fn silence() {
+        let spec = Spectrogram::compute(&[0; 4800], 0.025);
+        assert_eq!(spec.columns(), 4);
+        assert!((0..spec.rows()).all(|r| spec.value(2, r) == 0.0));
+        assert_eq!(spec.to_rgba().len(), 4 * 64 * 4);
+    }
+}
@@ -0,0 +1,149 @@
+//! Changing the speed of speech without changing its pitch.
+//!
+//! We use WSOLA ("waveform similarity overlap-add"): the output is built out of short, overlapping
+//! windows of the input, taken from roughly the right places. Each window is nudged a little
+//! earlier or later so that it lines up with the one before it, which avoids the warbling that
+//! plain overlap-add produces.
+
+use super::SAMPLE_RATE;
+
+/// The length of each window (40ms). This is long enough to contain a few pitch periods of even
+/// a low voice.
+const FRAME: usize = SAMPLE_RATE as usize / 25;
+/// Consecutive output windows overlap by half.
+const HOP: usize = FRAME / 2;
+/// How far (in samples) we're willing to nudge a window to line it up with the previous one.
+const TOLERANCE: isize = SAMPLE_RATE as isize / 200;
+/// When comparing windows, we only look at every few samples. Speech doesn't have much energy at
+/// high frequencies, so this barely affects the result, and it's a lot faster.
+const CORRELATION_STRIDE: usize = 4;
+
+/// Stretches (or squashes) some audio (at our usual sample rate) so that it becomes `ratio` times
+/// as long, without changing its pitch.
+pub fn time_stretch(buf: &[i16], ratio: f64) -> Vec<i16> {
+    let out_len = (buf.len() as f64 * ratio).round().max(0.0) as usize;
+    if (ratio - 1.0).abs() < 1e-6 {
+        return buf.to_vec();
+    } else if buf.is_empty() {
+        return vec![0; out_len];
+    }
+
+    // Reading past the ends of the input gives silence.
+    let input = |i: isize| -> f32 {
+        if i < 0 {
+            0.0
+        } else {
+            buf.get(i as usize).copied().unwrap_or(0) as f32
+        }
+    };
+    // A (periodic) Hann window: overlapping copies of it, spaced by `HOP`, add up to one.
+    let window: Vec<f32> = (0..FRAME)
+        .map(|i| {
+            let x = i as f32 / FRAME as f32;
+            0.5 - 0.5 * (2.0 * std::f32::consts::PI * x).cos()
+        })
+        .collect();
+
+    let mut out = vec![0.0f32; out_len];
+    // The first window starts before the beginning, so that every output sample is covered by
+    // two windows.
+    let mut out_start = -(HOP as isize);
+    // Where the previous window came from in the input.
+    let mut prev: Option<isize> = None;
+    while out_start < out_len as isize {
+        let nominal = (out_start as f64 / ratio).round() as isize;
+        let pos = match prev {
+            None => nominal,
+            Some(prev) => best_match(&input, prev + HOP as isize, nominal),
+        };
+
+        for (i, w) in window.iter().enumerate() {
+            let out_idx = out_start + i as isize;
+            if out_idx >= 0 && (out_idx as usize) < out_len {
+                out[out_idx as usize] += w * input(pos + i as isize);
+            }
+        }
+        prev = Some(pos);
+        out_start += HOP as isize;
+    }
+
+    out.into_iter()
+        .map(|x| x.round().max(i16::MIN as f32).min(i16::MAX as f32) as i16)
+        .collect()
+}
+
+/// Finds the position near `nominal` whose audio best continues the audio at `target`.
+///
+/// The first half of the new window overlaps with the second half of the previous one, and
+/// `target` is where the input would have continued after the previous window's first half. So
+/// we look for the window that looks the most like the input at `target`.
+fn best_match(input: &impl Fn(isize) -> f32, target: isize, nominal: isize) -> isize {
+    let mut best = nominal;
+    let mut best_score = f32::NEG_INFINITY;
+    for pos in (nominal - TOLERANCE)..=(nominal + TOLERANCE) {
+        let mut corr = 0.0;
+        let mut energy = 0.0;
+        for i in (0..HOP as isize).step_by(CORRELATION_STRIDE) {
+            let x = input(pos + i);
+            corr += x * input(target + i);
+            energy += x * x;
+        }
+        // Normalize by the energy, so that we don't just pick the loudest window.
+        let score = if energy > 0.0 {
+            corr / energy.sqrt()
+        } else {
+            0.0
+        };
+        if score > best_score {
+            best = pos;
+            best_score = score;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f64, secs: f64) -> Vec<i16> {
+        let len = (secs * SAMPLE_RATE as f64) as usize;
+        (0..len)
+            .map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                ((2.0 * std::f64::consts::PI * freq * t).sin() * 10000.0) as i16
+            })
+            .collect()
+    }
+
+    // The number of times that the audio crosses zero, per second.
+    fn zero_crossings(buf: &[i16]) -> f64 {
+        let crossings = buf.windows(2).filter(|w| (w[0] < 0) != (w[1] < 0)).count();
+        crossings as f64 * SAMPLE_RATE as f64 / buf.len() as f64
+    }
+
+    #[test]
+    fn lengths() {
+        let buf = sine(200.0, 1.0);
+        assert_eq!(time_stretch(&buf, 1.0), buf);
+        assert_eq!(time_stretch(&buf, 1.5).len(), 72000);
+        assert_eq!(time_stretch(&buf, 0.5).len(), 24000);
+        assert_eq!(time_stretch(&[], 2.0), Vec::<i16>::new());
+    }
+
+    #[test]
+    fn pitch_stays_the_same() {
+        let buf = sine(220.0, 1.0);
+        for &ratio in &[0.7, 1.3, 2.0] {
+            let stretched = time_stretch(&buf, ratio);
+            // Ignore the very beginning and end, which fade in and out.
+            let middle = &stretched[FRAME..(stretched.len() - FRAME)];
+            let rate = zero_crossings(middle);
+            assert!((rate - 440.0).abs() < 440.0 * 0.03, "{} {}", ratio, rate);
+
+            // The volume should stay about the same, too.
+            let peak = middle.iter().map(|&x| (x as i32).abs()).max().unwrap();
+            assert!(peak > 9000 && peak < 11000, "{} {}", ratio, peak);
+        }
+    }
+}
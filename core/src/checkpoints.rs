@@ -0,0 +1,152 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use scribl_curves::{DrawSnippetId, DrawSnippets};
+
+use crate::audio::{TalkSnippetId, TalkSnippets};
+
+/// A named snapshot of the drawing and audio snippets.
+///
+/// Unlike undo states, checkpoints are only created when they ask for one, and they get saved
+/// along with the animation. Since the snippets are behind shared pointers, a checkpoint is cheap
+/// to keep around as long as it shares most of its snippets with the current animation.
+#[derive(Clone)]
+#[cfg_attr(feature = "druid", derive(druid::Data, druid::Lens))]
+pub struct Checkpoint {
+    pub name: String,
+    pub draw: DrawSnippets,
+    pub talk: TalkSnippets,
+}
+
+/// The saved form of a [`Checkpoint`].
+///
+/// Typically, most of the snippets in a checkpoint are also in the main animation, so we don't
+/// save those twice: we only save their ids.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Deserialize, Serialize)]
+pub struct CheckpointSave {
+    name: String,
+    /// The ids of drawing snippets that are identical to the ones in the main animation.
+    shared_draw: Vec<DrawSnippetId>,
+    /// The drawing snippets that aren't in the main animation.
+    draw: DrawSnippets,
+    /// The ids of audio snippets that are identical to the ones in the main animation.
+    shared_talk: Vec<TalkSnippetId>,
+    /// The audio snippets that aren't in the main animation.
+    talk: TalkSnippets,
+}
+
+impl CheckpointSave {
+    pub fn new(checkpoint: &Checkpoint, draw: &DrawSnippets, talk: &TalkSnippets) -> Self {
+        let draw_shared = |id: DrawSnippetId, snip: &scribl_curves::DrawSnippet| {
+            draw.has_snippet(id) && draw.snippet(id).is_copy_of(snip)
+        };
+        let talk_shared = |id: TalkSnippetId, snip: &crate::audio::TalkSnippet| {
+            talk.has_snippet(id) && talk.snippet(id).is_copy_of(snip)
+        };
+
+        CheckpointSave {
+            name: checkpoint.name.clone(),
+            shared_draw: checkpoint
+                .draw
+                .snippets()
+                .filter(|&(id, s)| draw_shared(id, s))
+                .map(|(id, _)| id)
+                .collect(),
+            draw: checkpoint
+                .draw
+                .snippets()
+                .filter(|&(id, s)| !draw_shared(id, s))
+                .map(|(id, s)| (id, s.clone()))
+                .collect(),
+            shared_talk: checkpoint
+                .talk
+                .snippets()
+                .filter(|&(id, s)| talk_shared(id, s))
+                .map(|(id, _)| id)
+                .collect(),
+            talk: checkpoint
+                .talk
+                .snippets()
+                .filter(|&(id, s)| !talk_shared(id, s))
+                .map(|(id, s)| (id, s.clone()))
+                .collect(),
+        }
+    }
+
+    /// Reconstitutes the checkpoint, taking the shared snippets from the main animation.
+    pub fn load(self, draw: &DrawSnippets, talk: &TalkSnippets) -> anyhow::Result<Checkpoint> {
+        let shared_draw = self.shared_draw.iter().map(|&id| {
+            if draw.has_snippet(id) {
+                Ok((id, draw.snippet(id).clone()))
+            } else {
+                Err(anyhow!("checkpoint refers to a missing drawing snippet"))
+            }
+        });
+        let shared_talk = self.shared_talk.iter().map(|&id| {
+            if talk.has_snippet(id) {
+                Ok((id, talk.snippet(id).clone()))
+            } else {
+                Err(anyhow!("checkpoint refers to a missing audio snippet"))
+            }
+        });
+
+        Ok(Checkpoint {
+            name: self.name,
+            draw: shared_draw
+                .collect::<anyhow::Result<Vec<_>>>()?
+                .into_iter()
+                .chain(self.draw.snippets().map(|(id, s)| (id, s.clone())))
+                .collect(),
+            talk: shared_talk
+                .collect::<anyhow::Result<Vec<_>>>()?
+                .into_iter()
+                .chain(self.talk.snippets().map(|(id, s)| (id, s.clone())))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::TalkSnippet;
+    use scribl_curves::Time;
+
+    fn talk_snippet(start: i64) -> TalkSnippet {
+        TalkSnippet::new(vec![1, 2, 3], Time::from_micros(start), 1.0)
+    }
+
+    #[test]
+    fn shared_snippets() {
+        let (talk, id1) = TalkSnippets::default().with_new_snippet(talk_snippet(0));
+        let checkpoint = Checkpoint {
+            name: "take 1".to_owned(),
+            draw: DrawSnippets::default(),
+            talk: talk.clone(),
+        };
+        let talk = talk.with_multiplied_snippet(id1, 2.0);
+        let (talk, id2) = talk.with_new_snippet(talk_snippet(1000));
+
+        // The snippet changed after making the checkpoint, so it gets saved again.
+        let save = CheckpointSave::new(&checkpoint, &DrawSnippets::default(), &talk);
+        assert!(save.shared_talk.is_empty());
+        assert_eq!(save.talk.snippets().count(), 1);
+
+        // Now everything in the checkpoint is shared.
+        let checkpoint = Checkpoint {
+            name: "take 2".to_owned(),
+            draw: DrawSnippets::default(),
+            talk: talk.clone(),
+        };
+        let save = CheckpointSave::new(&checkpoint, &DrawSnippets::default(), &talk);
+        assert_eq!(save.shared_talk, vec![id1, id2]);
+        assert_eq!(save.talk.snippets().count(), 0);
+
+        let loaded = save.load(&DrawSnippets::default(), &talk).unwrap();
+        assert_eq!(loaded.name, "take 2");
+        assert!(loaded.talk.snippet(id2).is_copy_of(talk.snippet(id2)));
+        assert_eq!(loaded.talk.snippets().count(), 2);
+    }
+}
@@ -0,0 +1,256 @@
+//! Exporting animations as videos.
+//!
+//! Rendering the video needs piet's native backend (which is cairo on linux), so it's behind the
+//! `piet-render` feature. The settings and the status messages are always available.
+
+use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use scribl_curves::Time;
+
+use crate::ScriblState;
+
+#[cfg(feature = "piet-render")]
+mod pipeline;
+
+#[cfg(feature = "piet-render")]
+pub use pipeline::{do_encode_blocking, encode_blocking};
+
+fn default_video_height() -> u32 {
+    1080
+}
+
+fn default_video_fps() -> f64 {
+    30.0
+}
+
+fn default_video_bitrate() -> u32 {
+    4096
+}
+
+fn default_verify_export() -> bool {
+    true
+}
+
+/// Settings for exported videos. In scribl, these come from the `[export]` section of the config
+/// file, or from an export preset.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Export {
+    /// Height of the exported video, in pixels.
+    #[serde(default = "default_video_height")]
+    pub height: u32,
+
+    /// Frames per second in the exported video.
+    #[serde(default = "default_video_fps")]
+    pub fps: f64,
+
+    /// Bitrate of the exported video.
+    #[serde(default = "default_video_bitrate")]
+    pub bitrate: u32,
+
+    #[serde(default)]
+    pub codec: VideoCodec,
+
+    /// If this is set, the exported audio is made louder or quieter so that its integrated
+    /// loudness (in LUFS) is this much.
+    #[serde(default)]
+    pub loudness_lufs: Option<f64>,
+
+    /// If true, the exported audio goes through a limiter (after being made louder or quieter),
+    /// so that loud parts don't clip.
+    #[serde(default)]
+    pub limiter: bool,
+
+    /// If true, we re-open the video after exporting it, to check that it was written correctly.
+    #[serde(default = "default_verify_export")]
+    pub verify: bool,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    H264,
+    /// Smaller files than H264 at the same quality, but slower to encode and not supported by as
+    /// many players.
+    H265,
+}
+
+impl Default for VideoCodec {
+    fn default() -> VideoCodec {
+        VideoCodec::H264
+    }
+}
+
+impl Default for Export {
+    fn default() -> Export {
+        Export {
+            height: default_video_height(),
+            fps: default_video_fps(),
+            bitrate: default_video_bitrate(),
+            codec: VideoCodec::default(),
+            loudness_lufs: None,
+            limiter: false,
+            verify: default_verify_export(),
+        }
+    }
+}
+
+/// Everything needed to export an animation.
+#[derive(Clone)]
+pub struct ExportCmd {
+    pub scribl: ScriblState,
+    pub filename: PathBuf,
+    pub config: Export,
+    /// If this is set, only the part of the animation between these two times gets exported.
+    pub range: Option<(Time, Time)>,
+}
+
+// Note that the aspect ratio here needs to match the aspect ratio
+// of the drawing, which is currently fixed at 4:3 in scribl/src/widgets/drawing_pane.rs.
+pub(crate) const ASPECT_RATIO: f64 = 4.0 / 3.0;
+
+/// Summary statistics of how long some video frames took to render.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameStats {
+    pub frames: usize,
+    /// The total time spent rendering.
+    pub total: Duration,
+    pub min: Duration,
+    pub mean: Duration,
+    pub median: Duration,
+    /// 95% of the frames were at least this fast.
+    pub p95: Duration,
+    pub max: Duration,
+    /// The index of the frame that took the longest.
+    pub slowest: usize,
+    /// The number of frames that took longer than the time they're shown for (so that rendering
+    /// is slower than real time).
+    pub slow: usize,
+}
+
+impl FrameStats {
+    /// Computes the statistics of a bunch of frame times, where `budget` is the time that each
+    /// frame gets shown for. Returns `None` if there aren't any frames.
+    pub fn new(times: &[Duration], budget: Duration) -> Option<FrameStats> {
+        let slowest = (0..times.len()).max_by_key(|&i| times[i])?;
+        let mut sorted = times.to_vec();
+        sorted.sort_unstable();
+        let total: Duration = sorted.iter().sum();
+        let percentile = |p: usize| sorted[((sorted.len() - 1) * p + 50) / 100];
+        Some(FrameStats {
+            frames: sorted.len(),
+            total,
+            min: sorted[0],
+            mean: total / sorted.len() as u32,
+            median: percentile(50),
+            p95: percentile(95),
+            max: sorted[sorted.len() - 1],
+            slowest,
+            slow: sorted.iter().filter(|&&t| t > budget).count(),
+        })
+    }
+}
+
+/// What happened during a successful export.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EncodingSummary {
+    /// How long the whole export took.
+    pub elapsed: Duration,
+    /// How long the video frames took to render, or `None` if there weren't any.
+    pub frames: Option<FrameStats>,
+}
+
+impl EncodingSummary {
+    /// The average number of frames encoded per second.
+    pub fn fps(&self) -> f64 {
+        let frames = self.frames.as_ref().map_or(0, |f| f.frames);
+        frames as f64 / self.elapsed.as_secs_f64().max(1e-3)
+    }
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+impl fmt::Display for EncodingSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let frames = match &self.frames {
+            Some(frames) => frames,
+            None => return write!(f, "no frames in {:.0}s", self.elapsed.as_secs_f64()),
+        };
+        write!(
+            f,
+            "{} frames in {:.0}s ({:.1} fps), frame time min/median/max {:.1}/{:.1}/{:.1}ms",
+            frames.frames,
+            self.elapsed.as_secs_f64(),
+            self.fps(),
+            ms(frames.min),
+            ms(frames.median),
+            ms(frames.max),
+        )?;
+        match frames.slow {
+            0 => {}
+            1 => write!(f, ", 1 frame slower than real time")?,
+            n => write!(f, ", {} frames slower than real time", n)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+pub enum EncodingStatus {
+    /// We are still encoding, and the parameter is the progress (0.0 at the beginning, 1.0 at the
+    /// end).
+    Encoding { frame: u64, out_of: u64 },
+
+    /// We finished encoding successfully.
+    Finished(
+        #[cfg_attr(feature = "druid", data(same_fn = "PartialEq::eq"))] PathBuf,
+        #[cfg_attr(feature = "druid", data(same_fn = "PartialEq::eq"))] EncodingSummary,
+    ),
+
+    /// Encoding aborted with an error.
+    Error(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_stats() {
+        assert_eq!(FrameStats::new(&[], Duration::from_millis(33)), None);
+
+        let times: Vec<_> = (1..=100).rev().map(Duration::from_millis).collect();
+        let stats = FrameStats::new(&times, Duration::from_millis(90)).unwrap();
+        assert_eq!(stats.frames, 100);
+        assert_eq!(stats.total, Duration::from_millis(5050));
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.mean, Duration::from_micros(50_500));
+        assert_eq!(stats.median, Duration::from_millis(51));
+        assert_eq!(stats.p95, Duration::from_millis(95));
+        assert_eq!(stats.max, Duration::from_millis(100));
+        assert_eq!(stats.slowest, 0);
+        assert_eq!(stats.slow, 10);
+    }
+
+    #[test]
+    fn summary() {
+        let times: Vec<_> = [10, 20, 40]
+            .iter()
+            .map(|&ms| Duration::from_millis(ms))
+            .collect();
+        let summary = EncodingSummary {
+            elapsed: Duration::from_secs(2),
+            frames: FrameStats::new(&times, Duration::from_millis(33)),
+        };
+        assert_eq!(
+            summary.to_string(),
+            "3 frames in 2s (1.5 fps), frame time min/median/max 10.0/20.0/40.0ms, \
+             1 frame slower than real time"
+        );
+    }
+}
@@ -0,0 +1,656 @@
+//! The gstreamer pipeline that renders and encodes the video.
+
+use anyhow::{anyhow, Error};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use gst::prelude::*;
+use gst_video::{VideoFormat, VideoInfo};
+use gstreamer as gst;
+use gstreamer_app as gst_app;
+use gstreamer_pbutils as gst_pbutils;
+use gstreamer_video as gst_video;
+use kurbo::{Rect, TranslateScale};
+use piet_common::{Color, Device, ImageFormat, RenderContext};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use scribl_curves::{DrawSnippets, Time, TimeDiff};
+
+use super::{
+    EncodingStatus, EncodingSummary, Export, ExportCmd, FrameStats, VideoCodec, ASPECT_RATIO,
+};
+use crate::audio::{Limiter, MusicOutput, PencilOutput, TalkSnippets};
+use crate::underlay::{Frame, Pip, Underlay, UnderlayReader};
+use crate::Pages;
+
+// The largest amplification that gstreamer's volume element supports.
+const MAX_VOLUME: f64 = 10.0;
+
+// How long to wait for gstreamer to read an exported file when verifying it.
+const VERIFY_TIMEOUT_SECS: u64 = 30;
+
+// When verifying an exported file, we allow it to be this much shorter than expected (because
+// the audio and video streams might not end at exactly the same time).
+const VERIFY_DURATION_TOLERANCE: TimeDiff = TimeDiff::from_micros(1_000_000);
+
+// We make a custom error here because the default display for gst::message::Error isn't very
+// helpful in narrowing down the problem.
+#[derive(Debug, thiserror::Error)]
+#[error("error from {src}: {error} ({debug})")]
+struct PipelineError {
+    src: String,
+    error: String,
+    debug: String,
+}
+
+impl<'a> From<gst::message::Error<'a>> for PipelineError {
+    fn from(e: gst::message::Error<'a>) -> PipelineError {
+        PipelineError {
+            src: e
+                .src()
+                .map(|s| String::from(s.path_string()))
+                .unwrap_or_else(|| "None".to_owned()),
+            error: e.error().to_string(),
+            debug: e.debug().unwrap_or_else(|| "No debug info".to_owned()),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("failed to create gstreamer element \"{ty}\"; probably you are missing a gstreamer plugin")]
+struct ElementCreationError {
+    ty: String,
+    msg: String,
+}
+
+fn make_elt(ty: &str, name: &str) -> Result<gst::Element, ElementCreationError> {
+    gst::ElementFactory::make(ty, Some(name)).map_err(|e| ElementCreationError {
+        ty: ty.to_owned(),
+        msg: e.message.to_string(),
+    })
+}
+
+/// Adds chapter metadata to the exported video.
+///
+/// If the muxer supports tables of contents, the chapters go into the video file itself.
+/// Otherwise, we write them next to the video in ffmpeg's metadata format, so that they can be
+/// added with `ffmpeg -i video.mp4 -i video.chapters.txt -map_metadata 1 -codec copy out.mp4`.
+fn add_chapters(
+    mux: &gst::Element,
+    chapters: &[(Time, Time, &str)],
+    path: &Path,
+) -> Result<(), anyhow::Error> {
+    if let Some(setter) = mux.dynamic_cast_ref::<gst::TocSetter>() {
+        let mut toc = gst::Toc::new(gst::TocScope::Global);
+        let mut edition = gst::TocEntry::new(gst::TocEntryType::Edition, "chapters");
+        for (i, &(start, end, name)) in chapters.iter().enumerate() {
+            let mut chapter =
+                gst::TocEntry::new(gst::TocEntryType::Chapter, &format!("chapter{}", i));
+            let mut tags = gst::TagList::new();
+            // The unwraps are ok because we just created these, so nobody else has a reference.
+            tags.get_mut()
+                .unwrap()
+                .add::<gst::tags::Title>(&name, gst::TagMergeMode::Replace);
+            let chapter_ref = chapter.get_mut().unwrap();
+            chapter_ref.set_start_stop_times(start.as_micros() * 1000, end.as_micros() * 1000);
+            chapter_ref.set_tags(tags);
+            edition.get_mut().unwrap().append_sub_entry(chapter);
+        }
+        toc.get_mut().unwrap().append_entry(edition);
+        setter.set_toc(Some(&toc));
+        return Ok(());
+    }
+
+    fn escape(s: &str) -> String {
+        let mut ret = String::with_capacity(s.len());
+        for c in s.chars() {
+            if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+                ret.push('\\');
+            }
+            ret.push(c);
+        }
+        ret
+    }
+
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for &(start, end, name) in chapters {
+        metadata.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            start.as_micros() / 1000,
+            end.as_micros() / 1000,
+            escape(name)
+        ));
+    }
+    let chapter_path = path.with_extension("chapters.txt");
+    log::info!(
+        "the video muxer doesn't support chapters, writing them to {}",
+        chapter_path.display()
+    );
+    std::fs::write(&chapter_path, metadata)?;
+    Ok(())
+}
+
+fn create_pipeline(
+    anim: DrawSnippets,
+    pages: Pages,
+    underlay: Option<Underlay>,
+    webcam: Option<Pip>,
+    audio: TalkSnippets,
+    music: Option<MusicOutput>,
+    pencil: Option<PencilOutput>,
+    chapters: &[(Time, Time, &str)],
+    start: Time,
+    frame_count: u32,
+    path: &Path,
+    temp_path: &Path,
+    config: Export,
+    progress: Sender<EncodingStatus>,
+) -> Result<(gst::Pipeline, Receiver<Option<FrameStats>>), anyhow::Error> {
+    let pipeline = gst::Pipeline::new(None);
+    let v_src = make_elt("appsrc", "encode-vsource")?;
+    let v_convert = make_elt("videoconvert", "encode-vconvert")?;
+    let v_encode = match config.codec {
+        VideoCodec::H264 => make_elt("x264enc", "encode-vencode")?,
+        VideoCodec::H265 => make_elt("x265enc", "encode-vencode")?,
+    };
+    // mp4mux can't take H265 straight from the encoder, because it needs to be split up
+    // differently.
+    let v_parse = match config.codec {
+        VideoCodec::H264 => make_elt("identity", "encode-vparse")?,
+        VideoCodec::H265 => make_elt("h265parse", "encode-vparse")?,
+    };
+    let v_queue1 = make_elt("queue", "encode-vqueue1")?;
+    let v_queue2 = make_elt("queue", "encode-vqueue2")?;
+    let audio_output_data = crate::audio::OutputData {
+        start_time: Time::ZERO,
+        crossfades: audio.crossfades(),
+        snips: audio,
+        music,
+        pencil,
+        velocity: 1.0,
+    };
+    let volume = match config.loudness_lufs {
+        Some(target) => match audio_output_data.integrated_loudness() {
+            Ok(loudness) if loudness.is_finite() => {
+                crate::audio::loudness_gain(loudness, target).min(MAX_VOLUME)
+            }
+            Ok(_) => 1.0,
+            Err(e) => {
+                log::error!("failed to measure loudness, not normalizing audio: {}", e);
+                1.0
+            }
+        },
+        None => 1.0,
+    };
+    // If there's a limiter, it needs to come after the change in volume, so it takes care of the
+    // volume itself.
+    let (volume, limiter) = if config.limiter {
+        (1.0, Some(Limiter::new(volume)))
+    } else {
+        (volume, None)
+    };
+    let a_src = crate::audio::create_export_appsrc(audio_output_data, limiter, "encode-asrc")?;
+    let a_convert = make_elt("audioconvert", "encode-aconvert")?;
+    let a_volume = make_elt("volume", "encode-avolume")?;
+    let a_encode = make_elt("lamemp3enc", "encode-aencode")?;
+    let a_queue1 = make_elt("queue", "encode-aqueue1")?;
+    let a_queue2 = make_elt("queue", "encode-aqueue2")?;
+    let mux = make_elt("mp4mux", "encode-mux")?;
+    let sink = make_elt("filesink", "encode-sink")?;
+
+    v_encode.set_property("bitrate", &config.bitrate);
+    a_volume.set_property("volume", volume);
+    if !chapters.is_empty() {
+        add_chapters(&mux, chapters, path)?;
+    }
+
+    pipeline.add_many(&[
+        &v_src, &v_convert, &v_encode, &v_parse, &v_queue1, &v_queue2,
+    ])?;
+    pipeline.add_many(&[
+        &a_src, &a_convert, &a_volume, &a_encode, &a_queue1, &a_queue2,
+    ])?;
+    pipeline.add_many(&[&mux, &sink])?;
+    gst::Element::link_many(&[
+        &v_src, &v_queue1, &v_convert, &v_encode, &v_parse, &v_queue2, &mux,
+    ])?;
+    gst::Element::link_many(&[
+        &a_src, &a_queue1, &a_convert, &a_volume, &a_encode, &a_queue2, &mux,
+    ])?;
+    gst::Element::link(&mux, &sink)?;
+
+    // TODO: allow weirder filenames
+    sink.set_property(
+        "location",
+        &temp_path
+            .to_str()
+            .ok_or(anyhow!("this filename is too weird"))?
+            .to_value(),
+    );
+
+    let height = config.height;
+    let width = (height as f64 * ASPECT_RATIO).round() as u32;
+    let (fps_frac, fps) = if let Some(f) = gst::Fraction::approximate_f64(config.fps) {
+        (f, config.fps)
+    } else {
+        log::warn!("invalid fps value {}, defaulting to 30.0", config.fps);
+        (gst::Fraction::new(30, 1), 30.0)
+    };
+    let video_info = VideoInfo::builder(VideoFormat::Rgba, width, height)
+        .fps(fps_frac)
+        .build()?;
+
+    let v_src = v_src
+        .dynamic_cast::<gst_app::AppSrc>()
+        .map_err(|_| anyhow!("bug: couldn't cast v_src to an AppSrc"))?;
+    v_src.set_caps(Some(&video_info.to_caps()?));
+    v_src.set_format(gst::Format::Time);
+
+    let (tx, rx) = unbounded();
+    // gstreamer's callbacks need Sync, not just Send.
+    let tx = Arc::new(std::sync::Mutex::new(tx));
+    let tx_clone = Arc::clone(&tx);
+    v_src.set_callbacks(
+        gst_app::AppSrcCallbacks::builder()
+            .need_data(move |_, _| {
+                let _ = tx.lock().unwrap().send(RenderLoopCmd::NeedsData);
+            })
+            .enough_data(move |_| {
+                let _ = tx_clone.lock().unwrap().send(RenderLoopCmd::EnoughData);
+            })
+            .build(),
+    );
+    let (stats_tx, stats_rx) = crossbeam_channel::bounded(1);
+    std::thread::spawn(move || {
+        render_loop(
+            rx,
+            progress,
+            stats_tx,
+            v_src,
+            anim,
+            pages,
+            underlay,
+            webcam,
+            width,
+            height,
+            fps,
+            start,
+            frame_count,
+            video_info,
+        )
+    });
+
+    Ok((pipeline, stats_rx))
+}
+
+// Runs the pipeline (blocking) until it exits or errors.
+fn main_loop(pipeline: gst::Pipeline) -> Result<(), anyhow::Error> {
+    pipeline.set_state(gst::State::Playing)?;
+    let bus = pipeline
+        .bus()
+        .ok_or_else(|| anyhow!("couldn't get pipeline bus"))?;
+
+    for msg in bus.iter_timed(gst::ClockTime::NONE) {
+        use gst::MessageView::*;
+
+        match msg.view() {
+            Eos(..) => break,
+            Error(err) => {
+                pipeline.set_state(gst::State::Null)?;
+
+                return Err(PipelineError::from(err).into());
+            }
+            _ => {}
+        }
+    }
+
+    pipeline.set_state(gst::State::Null)?;
+    Ok(())
+}
+
+enum RenderLoopCmd {
+    EnoughData,
+    NeedsData,
+}
+
+fn render_loop(
+    cmd: Receiver<RenderLoopCmd>,
+    progress: Sender<EncodingStatus>,
+    stats: Sender<Option<FrameStats>>,
+    app_src: gst_app::AppSrc,
+    snippets: DrawSnippets,
+    pages: Pages,
+    underlay: Option<Underlay>,
+    webcam: Option<Pip>,
+    width: u32,
+    height: u32,
+    fps: f64,
+    start: Time,
+    frame_count: u32,
+    video_info: VideoInfo,
+) -> Result<(), Error> {
+    let mut device = Device::new().map_err(|e| anyhow!("failed to get device: {}", e))?;
+    let mut bitmap = device
+        .bitmap_target(width as usize, height as usize, 1.0)
+        .map_err(|e| anyhow!("failed to get bitmap: {}", e))?;
+    let mut cursor = snippets.create_cursor(start);
+    let transform = TranslateScale::scale(width as f64);
+    let full_frame = Rect::new(0.0, 0.0, width as f64, height as f64);
+    // If a video can't be read, we export the drawing without it.
+    let open = |u: &Underlay| match UnderlayReader::new(u) {
+        Ok(reader) => Some(reader),
+        Err(e) => {
+            log::error!("failed to read video {}: {}", u.path.display(), e);
+            None
+        }
+    };
+    let mut underlay = underlay.as_ref().and_then(open);
+    let mut webcam_reader = webcam.as_ref().and_then(|w| open(&w.video));
+    let mut last_underlay_frame = None;
+    let mut last_webcam_frame = None;
+    let mut render_times = Vec::with_capacity(frame_count as usize);
+    let frame_budget = Duration::from_secs_f64(1.0 / fps);
+
+    {
+        let mut ctx = bitmap.render_context();
+        ctx.clear(None, Color::WHITE);
+        ctx.finish()
+            .map_err(|e| anyhow!("failed to finish context: {}", e))?;
+    }
+
+    for frame_counter in 0..frame_count {
+        while let Ok(msg) = cmd.try_recv() {
+            match msg {
+                RenderLoopCmd::EnoughData => while let RenderLoopCmd::EnoughData = cmd.recv()? {},
+                RenderLoopCmd::NeedsData => {}
+            }
+        }
+
+        // We track encoding progress by the fraction of video frames that we've rendered.  This
+        // isn't perfect (what with gstreamer's buffering, etc.), but it's probably good enough.
+        let _ = progress.send(EncodingStatus::Encoding {
+            frame: frame_counter as u64,
+            out_of: frame_count as u64,
+        });
+
+        // The time in the video, which is offset from the time in the animation if we're only
+        // exporting part of it.
+        let video_time = Time::from_video_frame(frame_counter, fps);
+        let time = start + (video_time - Time::ZERO);
+        let last_time = cursor.current().0;
+        let render_start = Instant::now();
+
+        // TODO: we have a cursor for visible snippets, but we could also have a cursor for
+        // snippets that might potentially cause a change in the visibility. There should be less
+        // of these.
+        cursor.advance_to(time.min(last_time), time.max(last_time));
+        let mut bbox = Rect::ZERO;
+        for b in cursor.bboxes(&snippets) {
+            if bbox.area() == 0.0 {
+                bbox = b;
+            } else {
+                // TODO: could be more efficient about redrawing.
+                bbox = bbox.union(b);
+            }
+        }
+
+        bbox = (transform * bbox).expand();
+        // Switching pages replaces the whole frame.
+        if !pages.same_page(last_time, time) {
+            bbox = full_frame;
+        }
+
+        let underlay_frame = video_frame(&mut underlay, time);
+        let webcam_frame = video_frame(&mut webcam_reader, time);
+        // The underlay is behind everything, so if it changed then we redraw everything. The
+        // webcam doesn't cover much, but it's simpler to do the same for it.
+        let underlay_time = underlay_frame.as_ref().map(|f| f.time);
+        let webcam_time = webcam_frame.as_ref().map(|f| f.time);
+        if underlay_time != last_underlay_frame || webcam_time != last_webcam_frame {
+            bbox = full_frame;
+        }
+        last_underlay_frame = underlay_time;
+        last_webcam_frame = webcam_time;
+
+        cursor.advance_to(time, time);
+        {
+            let mut ctx = bitmap.render_context();
+            ctx.with_save(|ctx| {
+                ctx.clip(bbox);
+                ctx.fill(bbox, &Color::WHITE);
+                if let Some(frame) = &underlay_frame {
+                    frame.render(ctx, full_frame);
+                }
+                ctx.transform(transform.into());
+                for id in cursor.active_ids_in_drawing_order(&snippets) {
+                    let snip = snippets.snippet(id);
+                    if pages.shows_snippet(snip, time) {
+                        snip.render(ctx, time);
+                    }
+                }
+                if let (Some(webcam), Some(frame)) = (&webcam, &webcam_frame) {
+                    webcam.render(ctx, frame);
+                }
+                Ok(())
+            })
+            .map_err(|e| anyhow!("failed to render: {}", e))?;
+            ctx.finish()
+                .map_err(|e| anyhow!("failed to finish context: {}", e))?;
+        }
+
+        // Create a gst buffer and copy our data into it (it would be nice to render directly
+        // into this buffer, but druid doesn't seem to support rendering into borrowed buffers).
+        let mut gst_buffer = gst::Buffer::with_size(video_info.size())?;
+        {
+            let gst_buffer_ref = gst_buffer
+                .get_mut()
+                .ok_or(anyhow!("failed to get mutable buffer"))?;
+            // Presentation time stamp (i.e. when should this frame be displayed).
+            gst_buffer_ref.set_pts(video_time.as_gst_clock_time());
+
+            let mut data = gst_buffer_ref.map_writable()?;
+            bitmap
+                .copy_raw_pixels(ImageFormat::RgbaPremul, &mut data)
+                .map_err(|e| anyhow!("failed to get raw pixels: {}", e))?;
+        }
+        let render_time = render_start.elapsed();
+        if render_time > frame_budget {
+            log::debug!(
+                "frame {} took {:.1}ms to render",
+                frame_counter,
+                render_time.as_secs_f64() * 1000.0
+            );
+        }
+        render_times.push(render_time);
+
+        // Ignore the error, since appsrc is supposed to handle it.
+        let _ = app_src.push_buffer(gst_buffer);
+        // Note that piet-cairo (and probably other backends too) currently only supports
+        // RgbaPremul.
+    }
+
+    // The stats need to be sent before the end of the stream, because the encoder stops waiting
+    // for them once the pipeline finishes.
+    let _ = stats.send(FrameStats::new(&render_times, frame_budget));
+    let _ = app_src.end_of_stream();
+    Ok(())
+}
+
+/// Gets the frame to show at `time`, logging any errors.
+fn video_frame(reader: &mut Option<UnderlayReader>, time: Time) -> Option<Frame> {
+    match reader.as_mut()?.frame_at(time) {
+        Ok(frame) => frame,
+        Err(e) => {
+            log::error!("failed to decode video: {}", e);
+            None
+        }
+    }
+}
+
+pub fn do_encode_blocking(
+    mut cmd: ExportCmd,
+    progress: Sender<EncodingStatus>,
+) -> Result<EncodingSummary, anyhow::Error> {
+    let encode_start = Instant::now();
+    // The animation's own frame rate takes precedence over the one in the export settings.
+    if let Some(fps) = cmd.scribl.fps {
+        cmd.config.fps = fps;
+    }
+    let (start, end_time) = match cmd.range {
+        Some(range) => range,
+        None => match cmd.scribl.project_end {
+            Some(end) => (Time::ZERO, end),
+            // Without an explicit end, leave a little bit of space after the last snippet.
+            None => (
+                Time::ZERO,
+                cmd.scribl.content_end_time() + TimeDiff::from_micros(200000),
+            ),
+        },
+    };
+    let num_frames = (Time::ZERO + (end_time - start)).as_video_frame(cmd.config.fps);
+    let talk = cmd.scribl.talk.excerpt(start, end_time);
+    let music = cmd
+        .scribl
+        .music_output()
+        .map(|m| m.excerpt(start, end_time));
+    let pencil = cmd
+        .scribl
+        .pencil_output()
+        .map(|p| p.excerpt(start, end_time));
+    // Chapters are relative to the start of the video, so we need to clip them to the exported
+    // range and shift them.
+    let chapters: Vec<_> = cmd
+        .scribl
+        .markers
+        .chapters(end_time)
+        .into_iter()
+        .filter(|&(_, chapter_end, _)| chapter_end > start)
+        .map(|(chapter_start, chapter_end, name)| {
+            (
+                Time::ZERO + (chapter_start.max(start) - start),
+                Time::ZERO + (chapter_end - start),
+                name,
+            )
+        })
+        .collect();
+    let verify = cmd.config.verify;
+    let codec = cmd.config.codec;
+    let filename = &cmd.filename;
+    let temp_path = crate::partial_exports::temp_path(filename);
+    crate::partial_exports::register(&temp_path);
+    let result = create_pipeline(
+        cmd.scribl.draw,
+        cmd.scribl.pages.clone(),
+        cmd.scribl.underlay.clone(),
+        cmd.scribl.webcam.clone(),
+        talk,
+        music,
+        pencil,
+        &chapters,
+        start,
+        num_frames as u32,
+        filename,
+        &temp_path,
+        cmd.config,
+        progress,
+    )
+    .and_then(|(pipeline, stats)| {
+        main_loop(pipeline)?;
+        Ok(EncodingSummary {
+            elapsed: encode_start.elapsed(),
+            // If rendering failed, the render loop quit without sending the stats.
+            frames: stats.recv().ok().flatten(),
+        })
+    });
+
+    let result = result.and_then(|summary| {
+        if verify {
+            verify_export(&temp_path, codec, end_time - start)?;
+        }
+        std::fs::rename(&temp_path, filename)?;
+        Ok(summary)
+    });
+    if result.is_err() {
+        // The video is probably broken, so don't leave it lying around.
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    crate::partial_exports::forget(&[temp_path]);
+    result
+}
+
+/// Re-opens an exported video, and checks that it has the streams and the duration that we
+/// expect.
+///
+/// Sometimes the encoding pipeline finishes without errors, but writes a file that can't be
+/// played (or that only has part of the video). This lets us tell them about it, instead of
+/// letting them find out later.
+fn verify_export(path: &Path, codec: VideoCodec, duration: TimeDiff) -> Result<(), Error> {
+    use gst_pbutils::prelude::*;
+
+    let path = path.canonicalize()?;
+    let uri = gst::glib::filename_to_uri(&path, None)?;
+    let discoverer =
+        gst_pbutils::Discoverer::new(gst::ClockTime::from_seconds(VERIFY_TIMEOUT_SECS))?;
+    let info = discoverer
+        .discover_uri(&uri)
+        .map_err(|e| anyhow!("the exported video couldn't be read back: {}", e))?;
+
+    let video = info.video_streams();
+    let audio = info.audio_streams();
+    if video.len() != 1 || audio.len() != 1 {
+        return Err(anyhow!(
+            "the exported video has {} video streams and {} audio streams (expected one of each)",
+            video.len(),
+            audio.len()
+        ));
+    }
+
+    let format = |caps: Option<gst::Caps>| {
+        caps.and_then(|c| c.structure(0).map(|s| s.name().to_owned()))
+            .unwrap_or_else(|| "unknown".to_owned())
+    };
+    let expected_video = match codec {
+        VideoCodec::H264 => "video/x-h264",
+        VideoCodec::H265 => "video/x-h265",
+    };
+    let video_format = format(video[0].caps());
+    if video_format != expected_video {
+        return Err(anyhow!(
+            "the exported video has the wrong format (expected {}, found {})",
+            expected_video,
+            video_format
+        ));
+    }
+    let audio_format = format(audio[0].caps());
+    if audio_format != "audio/mpeg" {
+        return Err(anyhow!(
+            "the exported audio has the wrong format (expected audio/mpeg, found {})",
+            audio_format
+        ));
+    }
+
+    let expected_micros = (duration - VERIFY_DURATION_TOLERANCE).as_micros();
+    let actual_micros = info.duration().map(|d| d.useconds() as i64).unwrap_or(0);
+    if actual_micros < expected_micros {
+        return Err(anyhow!(
+            "the exported video is too short ({:.1}s, expected {:.1}s)",
+            actual_micros as f64 / 1e6,
+            duration.as_micros() as f64 / 1e6
+        ));
+    }
+    Ok(())
+}
+
+pub fn encode_blocking(cmd: ExportCmd, progress: Sender<EncodingStatus>) {
+    let path = cmd.filename.clone();
+    match do_encode_blocking(cmd, progress.clone()) {
+        Ok(summary) => {
+            log::info!("encoded {}", summary);
+            let _ = progress.send(EncodingStatus::Finished(path, summary));
+        }
+        Err(e) => {
+            log::error!("error {}", e);
+            let _ = progress.send(EncodingStatus::Error(e.to_string()));
+        }
+    }
+}
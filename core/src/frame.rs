@@ -0,0 +1,330 @@
+//! Rendering single frames of an animation as images.
+//!
+//! Frames are rendered deterministically: the same animation, time and size always give the same
+//! pixels (for a given piet backend), with a fixed pixel scale and a white background. This makes
+//! them suitable for golden-image tests of the renderer, and for making visual bug reports
+//! reproducible. Unlike exported videos, frames don't include the video underlay or the webcam,
+//! because decoded video frames depend on the installed gstreamer plugins.
+//!
+//! There is more than one way to draw a frame (see [`FrameRenderer`]). The default one uses piet,
+//! which is what the editor and the video export use; it needs the `piet-render` feature. With
+//! the `tiny-skia` feature, frames can also be drawn with tiny-skia, which is written in pure rust
+//! and so works on machines that don't have cairo. The two renderers only differ in their
+//! antialiasing.
+
+use anyhow::{anyhow, Result};
+#[cfg(feature = "piet-render")]
+use kurbo::{Rect, TranslateScale};
+#[cfg(feature = "piet-render")]
+use piet_common::{Color, Device, ImageFormat, RenderContext};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use scribl_curves::Time;
+
+use crate::encode::ASPECT_RATIO;
+use crate::ScriblState;
+
+#[cfg(feature = "tiny-skia")]
+mod skia;
+
+#[cfg(feature = "tiny-skia")]
+pub use skia::SkiaRenderer;
+
+/// A rendered frame, as 8-bit RGBA pixels in row-major order. Frames are always opaque.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Something that knows how to draw a frame.
+pub trait FrameRenderer {
+    /// Renders the frame that is showing at `time`, scaled so that the animation fills the width
+    /// of the image.
+    fn render(
+        &mut self,
+        scribl: &ScriblState,
+        time: Time,
+        width: u32,
+        height: u32,
+    ) -> Result<Image>;
+}
+
+/// Renders frames with piet, the same way that the editor does.
+#[cfg(feature = "piet-render")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PietRenderer;
+
+/// The names of the renderers that [`renderer`] knows about.
+pub fn renderer_names() -> Vec<&'static str> {
+    let mut ret = Vec::new();
+    if cfg!(feature = "piet-render") {
+        ret.push("piet");
+    }
+    if cfg!(feature = "tiny-skia") {
+        ret.push("tiny-skia");
+    }
+    ret
+}
+
+/// Finds a renderer by name (one of the ones in [`renderer_names`]).
+pub fn renderer(name: &str) -> Result<Box<dyn FrameRenderer>> {
+    match name {
+        #[cfg(feature = "piet-render")]
+        "piet" => Ok(Box::new(PietRenderer)),
+        #[cfg(feature = "tiny-skia")]
+        "tiny-skia" => Ok(Box::new(SkiaRenderer)),
+        _ => Err(anyhow!(
+            "unknown renderer \"{}\" (the options are {})",
+            name,
+            renderer_names().join(", ")
+        )),
+    }
+}
+
+/// Renders the frame that is showing at `time`, using piet. The frame has the same aspect ratio as
+/// exported videos, and is `height` pixels high.
+#[cfg(feature = "piet-render")]
+pub fn render(scribl: &ScriblState, time: Time, height: u32) -> Result<Image> {
+    render_with(&mut PietRenderer, scribl, time, height)
+}
+
+/// Like [`render`], but with a different renderer.
+pub fn render_with(
+    renderer: &mut dyn FrameRenderer,
+    scribl: &ScriblState,
+    time: Time,
+    height: u32,
+) -> Result<Image> {
+    let width = (height as f64 * ASPECT_RATIO).round() as u32;
+    renderer.render(scribl, time, width, height)
+}
+
+#[cfg(feature = "piet-render")]
+impl FrameRenderer for PietRenderer {
+    fn render(
+        &mut self,
+        scribl: &ScriblState,
+        time: Time,
+        width: u32,
+        height: u32,
+    ) -> Result<Image> {
+        let mut device = Device::new().map_err(|e| anyhow!("failed to get device: {}", e))?;
+        // The pixel scale is always 1, so that the output doesn't depend on the display.
+        let mut bitmap = device
+            .bitmap_target(width as usize, height as usize, 1.0)
+            .map_err(|e| anyhow!("failed to get bitmap: {}", e))?;
+
+        {
+            let mut ctx = bitmap.render_context();
+            let full_frame = Rect::new(0.0, 0.0, width as f64, height as f64);
+            ctx.fill(full_frame, &Color::WHITE);
+            ctx.with_save(|ctx| {
+                ctx.transform(TranslateScale::scale(width as f64).into());
+                for (_, snip) in scribl.draw.snippets_in_drawing_order() {
+                    if scribl.pages.shows_snippet(snip, time) {
+                        snip.render(ctx, time);
+                    }
+                }
+                Ok(())
+            })
+            .map_err(|e| anyhow!("failed to render: {}", e))?;
+            ctx.finish()
+                .map_err(|e| anyhow!("failed to finish context: {}", e))?;
+        }
+
+        // Since the background is opaque, premultiplied alpha is the same as separate alpha.
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        bitmap
+            .copy_raw_pixels(ImageFormat::RgbaPremul, &mut pixels)
+            .map_err(|e| anyhow!("failed to get raw pixels: {}", e))?;
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+impl Image {
+    /// Writes this frame to a PNG file.
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+
+    /// Reads a frame from a PNG file, like one written by [`Image::save_png`].
+    pub fn load_png(path: impl AsRef<Path>) -> Result<Image> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+        buf.truncate(info.buffer_size());
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => buf,
+            png::ColorType::Rgb => buf
+                .chunks(3)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            other => return Err(anyhow!("unsupported PNG color type {:?}", other)),
+        };
+        Ok(Image {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    /// The largest difference between corresponding color components of the two frames, or
+    /// `None` if they have different sizes.
+    ///
+    /// Golden-image tests should allow a small difference, because antialiasing can change
+    /// slightly between versions of the rendering backend.
+    pub fn max_difference(&self, other: &Image) -> Option<u8> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return None;
+        }
+        Some(
+            self.pixels
+                .iter()
+                .zip(&other.pixels)
+                .map(|(a, b)| if a > b { a - b } else { b - a })
+                .max()
+                .unwrap_or(0),
+        )
+    }
+
+    /// The number of pixels where some color component differs by more than `tolerance` between
+    /// the two frames, or `None` if they have different sizes.
+    ///
+    /// This is for comparing frames from different renderers, which antialias the edges of
+    /// strokes differently.
+    pub fn count_differences(&self, other: &Image, tolerance: u8) -> Option<usize> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return None;
+        }
+        Some(
+            self.pixels
+                .chunks(4)
+                .zip(other.pixels.chunks(4))
+                .filter(|(a, b)| {
+                    a.iter()
+                        .zip(b.iter())
+                        .any(|(x, y)| if x > y { x - y } else { y - x } > tolerance)
+                })
+                .count(),
+        )
+    }
+}
+
+// These compare against the piet renderer, so they need it.
+#[cfg(all(test, feature = "piet-render"))]
+mod tests {
+    use super::*;
+    use kurbo::BezPath;
+    use scribl_curves::{Brush, DrawSnippet, Effects, StrokeStyle, TimeDiff};
+
+    fn scribl() -> ScriblState {
+        scribl_with_brush(Brush::Round)
+    }
+
+    fn scribl_with_brush(brush: Brush) -> ScriblState {
+        let mut path = BezPath::new();
+        path.move_to((0.1, 0.1));
+        path.line_to((0.9, 0.6));
+        let style = StrokeStyle {
+            color: Color::BLACK,
+            thickness: 0.02,
+            effects: Effects::default(),
+            brush,
+        };
+        let seq = crate::svg_import::to_strokes(&[path], &style, Time::ZERO, TimeDiff::ZERO);
+        let mut ret = ScriblState::default();
+        ret.add_draw_snippet(DrawSnippet::new(seq));
+        ret
+    }
+
+    #[test]
+    fn blank() {
+        let image = render(&ScriblState::default(), Time::ZERO, 30).unwrap();
+        assert_eq!((image.width, image.height), (40, 30));
+        assert!(image.pixels.iter().all(|&p| p == 255));
+    }
+
+    #[test]
+    fn deterministic() {
+        let time = Time::from_micros(1_000_000);
+        let image = render(&scribl(), time, 60).unwrap();
+        assert!(image.pixels.iter().any(|&p| p != 255));
+        let same = render(&scribl(), time, 60).unwrap();
+        let smaller = render(&scribl(), time, 30).unwrap();
+        assert_eq!(image.max_difference(&same), Some(0));
+        assert_eq!(image.max_difference(&smaller), None);
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("frame.png");
+        let image = render(&scribl(), Time::from_micros(1_000_000), 60).unwrap();
+        image.save_png(&path).unwrap();
+        assert_eq!(Image::load_png(&path).unwrap(), image);
+    }
+
+    #[test]
+    fn renderers() {
+        assert!(renderer("piet").is_ok());
+        assert!(renderer("povray").is_err());
+    }
+
+    #[test]
+    fn differences() {
+        let image = render(&scribl(), Time::from_micros(1_000_000), 60).unwrap();
+        let blank = render(&ScriblState::default(), Time::ZERO, 60).unwrap();
+        let ink = blank.count_differences(&image, 0).unwrap();
+        assert!(ink > 0);
+        assert_eq!(image.count_differences(&image, 0), Some(0));
+        assert!(blank.count_differences(&image, 128).unwrap() <= ink);
+    }
+
+    // The tiny-skia renderer should draw the same thing as the piet one, up to antialiasing.
+    #[cfg(feature = "tiny-skia")]
+    #[test]
+    fn skia_matches_piet() {
+        // How much we allow the antialiasing to differ.
+        const TOLERANCE: u8 = 96;
+
+        let height = 240;
+        for brush in Brush::ALL.iter().copied() {
+            let scribl = scribl_with_brush(brush);
+            for &micros in &[0, 500_000, 1_000_000] {
+                let time = Time::from_micros(micros);
+                let piet = render(&scribl, time, height).unwrap();
+                let skia = render_with(&mut SkiaRenderer, &scribl, time, height).unwrap();
+                assert_eq!((skia.width, skia.height), (piet.width, piet.height));
+
+                // Only the pixels on the edges of strokes are allowed to be different.
+                let blank = render(&ScriblState::default(), time, height).unwrap();
+                let ink = blank.count_differences(&piet, 0).unwrap();
+                let diff = skia.count_differences(&piet, TOLERANCE).unwrap();
+                assert!(
+                    diff * 10 <= ink.max(10),
+                    "{:?} at {}us: {} of {} pixels differ",
+                    brush,
+                    micros,
+                    diff,
+                    ink
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Rendering frames with tiny-skia.
+
+use anyhow::{anyhow, Result};
+use kurbo::{BezPath, PathEl};
+use piet::{Color, LineCap, LineJoin};
+use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, Stroke, Transform};
+
+use scribl_curves::{StrokePaint, Time};
+
+use super::{FrameRenderer, Image};
+use crate::ScriblState;
+
+/// Renders frames with tiny-skia, which doesn't need a display or any system graphics libraries.
+///
+/// Everything is drawn with the same paths, colors and stroke styles as [`super::PietRenderer`],
+/// so the frames only differ in their antialiasing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SkiaRenderer;
+
+impl FrameRenderer for SkiaRenderer {
+    fn render(
+        &mut self,
+        scribl: &ScriblState,
+        time: Time,
+        width: u32,
+        height: u32,
+    ) -> Result<Image> {
+        let mut pixmap = Pixmap::new(width, height)
+            .ok_or_else(|| anyhow!("invalid frame size {}x{}", width, height))?;
+        pixmap.fill(tiny_skia::Color::WHITE);
+        let transform = Transform::from_scale(width as f32, width as f32);
+        for (_, snip) in scribl.draw.snippets_in_drawing_order() {
+            if scribl.pages.shows_snippet(snip, time) {
+                for stroke in snip.visible_strokes(time) {
+                    draw(&mut pixmap, &stroke.paint(), transform);
+                }
+            }
+        }
+
+        // tiny-skia uses premultiplied alpha, but the background is opaque.
+        Ok(Image {
+            width,
+            height,
+            pixels: pixmap.take(),
+        })
+    }
+}
+
+fn draw(pixmap: &mut Pixmap, op: &StrokePaint, transform: Transform) {
+    match op {
+        StrokePaint::Stroke {
+            path,
+            color,
+            width,
+            style,
+        } => {
+            if let Some(path) = convert_path(path) {
+                let mut stroke = Stroke {
+                    width: *width as f32,
+                    line_cap: match style.line_cap {
+                        LineCap::Butt => tiny_skia::LineCap::Butt,
+                        LineCap::Round => tiny_skia::LineCap::Round,
+                        LineCap::Square => tiny_skia::LineCap::Square,
+                    },
+                    ..Stroke::default()
+                };
+                // None of the brushes use dashes, so we don't bother with them.
+                match style.line_join {
+                    LineJoin::Miter { limit } => {
+                        stroke.line_join = tiny_skia::LineJoin::Miter;
+                        stroke.miter_limit = limit as f32;
+                    }
+                    LineJoin::Round => stroke.line_join = tiny_skia::LineJoin::Round,
+                    LineJoin::Bevel => stroke.line_join = tiny_skia::LineJoin::Bevel,
+                }
+                pixmap.stroke_path(&path, &paint(color), &stroke, transform, None);
+            }
+        }
+        StrokePaint::Fill { path, color } => {
+            if let Some(path) = convert_path(path) {
+                pixmap.fill_path(&path, &paint(color), FillRule::Winding, transform, None);
+            }
+        }
+    }
+}
+
+fn paint(color: &Color) -> Paint<'static> {
+    let (r, g, b, a) = color.as_rgba8();
+    let mut ret = Paint::default();
+    ret.set_color_rgba8(r, g, b, a);
+    ret.anti_alias = true;
+    ret
+}
+
+/// Converts a path to tiny-skia's format, returning `None` if it's empty.
+fn convert_path(path: &BezPath) -> Option<tiny_skia::Path> {
+    let mut pb = PathBuilder::new();
+    for el in path.elements() {
+        match *el {
+            PathEl::MoveTo(p) => pb.move_to(p.x as f32, p.y as f32),
+            PathEl::LineTo(p) => pb.line_to(p.x as f32, p.y as f32),
+            PathEl::QuadTo(p1, p2) => {
+                pb.quad_to(p1.x as f32, p1.y as f32, p2.x as f32, p2.y as f32)
+            }
+            PathEl::CurveTo(p1, p2, p3) => pb.cubic_to(
+                p1.x as f32,
+                p1.y as f32,
+                p2.x as f32,
+                p2.y as f32,
+                p3.x as f32,
+                p3.y as f32,
+            ),
+            PathEl::ClosePath => pb.close(),
+        }
+    }
+    pb.finish()
+}
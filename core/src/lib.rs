@@ -0,0 +1,56 @@
+//! The parts of [scribl](https://github.com/jneem/scribl) that don't need a GUI: the contents of
+//! an animation, reading and writing `.scb` files, and exporting videos (or, with
+//! [`web_export`], animations for the web).
+//!
+//! For example, this converts a saved animation into a video:
+//!
+//! ```no_run
+//! use scribl_core::encode::{encode_blocking, Export, ExportCmd};
+//! use scribl_core::{SaveFileData, ScriblState};
+//!
+//! gstreamer::init().unwrap();
+//! let data = SaveFileData::load_from_path("talk.scb").unwrap();
+//! let (tx, rx) = crossbeam_channel::unbounded();
+//! let cmd = ExportCmd {
+//!     scribl: ScriblState::from_save_file(&data),
+//!     filename: "talk.mp4".into(),
+//!     config: Export::default(),
+//!     range: None,
+//! };
+//! std::thread::spawn(move || encode_blocking(cmd, tx));
+//! for status in rx {
+//!     println!("{:?}", status);
+//! }
+//! ```
+//!
+//! With the `druid` feature, the data types here implement `druid::Data`, so that the GUI can use
+//! them directly.
+//!
+//! Exporting videos, streaming [`live`], and rendering frames with piet all use piet's native
+//! backend, which needs system graphics libraries (cairo, on linux). They're behind the
+//! `piet-render` feature, which is on by default; without it, this crate builds on headless
+//! machines that only have gstreamer.
+
+pub mod audio;
+mod checkpoints;
+pub mod encode;
+pub mod frame;
+#[cfg(feature = "piet-render")]
+pub mod live;
+mod markers;
+pub mod otio;
+mod pages;
+pub mod partial_exports;
+mod rows;
+mod save;
+mod scribl;
+pub mod svg_import;
+pub mod underlay;
+pub mod web_export;
+
+pub use checkpoints::{Checkpoint, CheckpointSave};
+pub use markers::Markers;
+pub use pages::Pages;
+pub use rows::RowHints;
+pub use save::SaveFileData;
+pub use scribl::ScriblState;
@@ -0,0 +1,247 @@
+//! Streaming the drawing live, while it's being drawn.
+//!
+//! This is an alternative to exporting: instead of rendering the whole animation into a file, we
+//! render whatever is currently on the canvas, a fixed number of times per second, and push it
+//! into a virtual camera (so that scribl can be used as a whiteboard in video calls) or to an RTMP
+//! server (for streaming).
+
+use anyhow::{anyhow, Result};
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use gst::prelude::*;
+use gst_video::{VideoFormat, VideoInfo};
+use gstreamer as gst;
+use gstreamer_app as gst_app;
+use gstreamer_video as gst_video;
+use kurbo::TranslateScale;
+use piet_common::{Color, Device, ImageFormat, RenderContext};
+use std::time::{Duration, Instant};
+
+use scribl_curves::{render::VisibleStroke, DrawSnippets, StrokeSeq, Time};
+
+use crate::audio::create_gst_elt;
+use crate::Pages;
+
+// Note that the aspect ratio here needs to match the aspect ratio of the drawing.
+const ASPECT_RATIO: f64 = 4.0 / 3.0;
+
+/// Where to send the live video.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LiveTarget {
+    /// A virtual camera. On linux, this is a v4l2loopback device (like `/dev/video10`).
+    VirtualCamera(String),
+    /// An RTMP server, like `rtmp://localhost/live/scribl`.
+    Rtmp(String),
+}
+
+/// Settings for the live video.
+#[derive(Clone, Debug)]
+pub struct LiveConfig {
+    pub height: u32,
+    pub fps: f64,
+    /// The bitrate (in kbit/s) of the RTMP stream. This is ignored for virtual cameras, which
+    /// aren't compressed.
+    pub bitrate: u32,
+}
+
+/// Everything that is on the canvas at some moment.
+#[derive(Clone)]
+pub struct LiveFrame {
+    pub snippets: DrawSnippets,
+    pub pages: Pages,
+    /// The strokes that are being drawn, but aren't part of a snippet yet.
+    pub new_strokes: Option<StrokeSeq>,
+    /// The stroke that the pen is drawing right now.
+    pub current_stroke: Option<VisibleStroke>,
+    pub time: Time,
+}
+
+impl LiveFrame {
+    fn render(&self, ctx: &mut impl RenderContext) {
+        for (_, snip) in self.snippets.snippets_in_drawing_order() {
+            if self.pages.shows_snippet(snip, self.time) {
+                snip.render(ctx, self.time);
+            }
+        }
+        if let Some(strokes) = &self.new_strokes {
+            strokes.render(ctx, self.time);
+        }
+        if let Some(stroke) = &self.current_stroke {
+            stroke.render(ctx);
+        }
+    }
+}
+
+/// Sends video to a [`LiveTarget`] until it's dropped.
+pub struct LiveOutput {
+    frames: Sender<LiveFrame>,
+}
+
+impl LiveOutput {
+    /// Starts sending video.
+    ///
+    /// If something goes wrong after the video has started, `on_error` gets called (on a
+    /// different thread) and the video stops.
+    pub fn start(
+        target: &LiveTarget,
+        config: &LiveConfig,
+        on_error: impl FnOnce(String) + Send + 'static,
+    ) -> Result<LiveOutput> {
+        let height = config.height;
+        let width = (height as f64 * ASPECT_RATIO).round() as u32;
+        let fps = if config.fps > 0.0 {
+            config.fps
+        } else {
+            log::warn!("invalid fps value {}, defaulting to 30.0", config.fps);
+            30.0
+        };
+        let video_info = VideoInfo::builder(VideoFormat::Rgba, width, height)
+            .fps(gst::Fraction::approximate_f64(fps).unwrap_or_else(|| gst::Fraction::new(30, 1)))
+            .build()?;
+        let (pipeline, app_src) = create_pipeline(target, config, &video_info)?;
+        pipeline.set_state(gst::State::Playing)?;
+
+        let (tx, rx) = unbounded();
+        std::thread::spawn(move || {
+            let result = render_loop(rx, &pipeline, &app_src, &video_info, fps);
+            let _ = pipeline.set_state(gst::State::Null);
+            if let Err(e) = result {
+                log::error!("live output failed: {}", e);
+                on_error(e.to_string());
+            }
+        });
+        Ok(LiveOutput { frames: tx })
+    }
+
+    /// Updates the contents of the canvas. They'll be sent out with the next video frame.
+    pub fn update(&self, frame: LiveFrame) {
+        let _ = self.frames.send(frame);
+    }
+}
+
+fn create_pipeline(
+    target: &LiveTarget,
+    config: &LiveConfig,
+    video_info: &VideoInfo,
+) -> Result<(gst::Pipeline, gst_app::AppSrc)> {
+    let pipeline = gst::Pipeline::new(None);
+    let src = create_gst_elt("appsrc", "live-source")?;
+    let convert = create_gst_elt("videoconvert", "live-convert")?;
+    let queue = create_gst_elt("queue", "live-queue")?;
+    pipeline.add_many(&[&src, &convert, &queue])?;
+    gst::Element::link_many(&[&src, &convert, &queue])?;
+
+    match target {
+        LiveTarget::VirtualCamera(device) => {
+            // Most programs that read from cameras don't understand RGBA.
+            let filter = create_gst_elt("capsfilter", "live-filter")?;
+            let caps = gst::Caps::builder("video/x-raw")
+                .field("format", VideoFormat::Yuy2.to_str())
+                .build();
+            filter.set_property("caps", &caps);
+            let sink = create_gst_elt("v4l2sink", "live-sink")?;
+            sink.set_property("device", device.as_str());
+            sink.set_property("sync", false);
+            pipeline.add_many(&[&filter, &sink])?;
+            gst::Element::link_many(&[&queue, &filter, &sink])?;
+        }
+        LiveTarget::Rtmp(url) => {
+            let encode = create_gst_elt("x264enc", "live-encode")?;
+            encode.set_property("bitrate", config.bitrate);
+            encode.set_property_from_str("tune", "zerolatency");
+            encode.set_property_from_str("speed-preset", "veryfast");
+            let mux = create_gst_elt("flvmux", "live-mux")?;
+            mux.set_property("streamable", true);
+            let sink = create_gst_elt("rtmpsink", "live-sink")?;
+            sink.set_property("location", url.as_str());
+            pipeline.add_many(&[&encode, &mux, &sink])?;
+            gst::Element::link_many(&[&queue, &encode, &mux, &sink])?;
+        }
+    }
+
+    let src = src
+        .dynamic_cast::<gst_app::AppSrc>()
+        .map_err(|_| anyhow!("bug: couldn't cast src to an AppSrc"))?;
+    src.set_caps(Some(&video_info.to_caps()?));
+    src.set_format(gst::Format::Time);
+    src.set_is_live(true);
+    src.set_do_timestamp(true);
+    Ok((pipeline, src))
+}
+
+// Renders the most recent frame, `fps` times per second, until the `LiveOutput` is dropped.
+fn render_loop(
+    frames: Receiver<LiveFrame>,
+    pipeline: &gst::Pipeline,
+    app_src: &gst_app::AppSrc,
+    video_info: &VideoInfo,
+    fps: f64,
+) -> Result<()> {
+    let width = video_info.width();
+    let height = video_info.height();
+    let mut device = Device::new().map_err(|e| anyhow!("failed to get device: {}", e))?;
+    let mut bitmap = device
+        .bitmap_target(width as usize, height as usize, 1.0)
+        .map_err(|e| anyhow!("failed to get bitmap: {}", e))?;
+    let transform = TranslateScale::scale(width as f64);
+    let bus = pipeline
+        .bus()
+        .ok_or_else(|| anyhow!("couldn't get pipeline bus"))?;
+
+    let frame_duration = Duration::from_secs_f64(1.0 / fps);
+    let mut next_frame_time = Instant::now();
+    let mut frame: Option<LiveFrame> = None;
+    loop {
+        // Wait until it's time for the next frame, and then take the most recent canvas.
+        let timeout = next_frame_time.saturating_duration_since(Instant::now());
+        match frames.recv_timeout(timeout) {
+            Ok(f) => frame = Some(f),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if Instant::now() < next_frame_time {
+            continue;
+        }
+        if let Some(f) = frames.try_iter().last() {
+            frame = Some(f);
+        }
+        // If we fell behind, don't try to catch up by sending lots of frames at once.
+        next_frame_time = (next_frame_time + frame_duration).max(Instant::now());
+
+        if let Some(msg) = bus.pop_filtered(&[gst::MessageType::Error]) {
+            if let gst::MessageView::Error(err) = msg.view() {
+                return Err(anyhow!("{}", err.error()));
+            }
+        }
+
+        {
+            let mut ctx = bitmap.render_context();
+            ctx.clear(None, Color::WHITE);
+            ctx.with_save(|ctx| {
+                ctx.transform(transform.into());
+                if let Some(frame) = &frame {
+                    frame.render(ctx);
+                }
+                Ok(())
+            })
+            .map_err(|e| anyhow!("failed to render: {}", e))?;
+            ctx.finish()
+                .map_err(|e| anyhow!("failed to finish context: {}", e))?;
+        }
+
+        let mut gst_buffer = gst::Buffer::with_size(video_info.size())?;
+        {
+            let gst_buffer_ref = gst_buffer
+                .get_mut()
+                .ok_or(anyhow!("failed to get mutable buffer"))?;
+            let mut data = gst_buffer_ref.map_writable()?;
+            bitmap
+                .copy_raw_pixels(ImageFormat::RgbaPremul, &mut data)
+                .map_err(|e| anyhow!("failed to get raw pixels: {}", e))?;
+        }
+        // If the pipeline is falling behind, this will block, which is what we want.
+        app_src.push_buffer(gst_buffer)?;
+    }
+
+    let _ = app_src.end_of_stream();
+    Ok(())
+}
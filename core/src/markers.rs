@@ -0,0 +1,155 @@
+use im::OrdMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use scribl_curves::Time;
+
+/// A collection of named markers (or chapters) on the timeline.
+///
+/// There is at most one marker at any given time.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+#[serde(transparent)]
+pub struct Markers {
+    markers: OrdMap<Time, Arc<str>>,
+}
+
+impl Markers {
+    pub fn is_empty(&self) -> bool {
+        self.markers.is_empty()
+    }
+
+    /// Iterates over the markers in order of time.
+    pub fn iter(&self) -> impl Iterator<Item = (Time, &str)> {
+        self.markers.iter().map(|(t, name)| (*t, name.as_ref()))
+    }
+
+    /// Returns the name of the marker at exactly this time, if there is one.
+    pub fn get(&self, time: Time) -> Option<&str> {
+        self.markers.get(&time).map(|name| name.as_ref())
+    }
+
+    /// Returns a new collection with an extra marker. If there was already a marker at this time,
+    /// it gets renamed.
+    pub fn with_marker(&self, time: Time, name: &str) -> Markers {
+        Markers {
+            markers: self.markers.update(time, name.into()),
+        }
+    }
+
+    /// Returns a new collection without the marker at this time.
+    pub fn without_marker(&self, time: Time) -> Markers {
+        Markers {
+            markers: self.markers.without(&time),
+        }
+    }
+
+    /// Returns a name for a new marker that doesn't clash with any of the existing ones.
+    pub fn fresh_name(&self) -> String {
+        (1..)
+            .map(|i| format!("Chapter {}", i))
+            .find(|name| self.markers.values().all(|n| n.as_ref() != name))
+            .unwrap()
+    }
+
+    /// The time of the first marker strictly after `time`.
+    pub fn next_after(&self, time: Time) -> Option<Time> {
+        self.markers
+            .range((std::ops::Bound::Excluded(time), std::ops::Bound::Unbounded))
+            .next()
+            .map(|(t, _)| *t)
+    }
+
+    /// The time of the last marker strictly before `time`.
+    pub fn prev_before(&self, time: Time) -> Option<Time> {
+        self.markers.range(..time).next_back().map(|(t, _)| *t)
+    }
+
+    /// The name of the chapter that `time` is in: the name of the last marker at or before `time`.
+    pub fn chapter_at(&self, time: Time) -> Option<&str> {
+        self.markers
+            .range(..=time)
+            .next_back()
+            .map(|(_, name)| name.as_ref())
+    }
+
+    /// Divides the range from the beginning to `end` into chapters, one for each marker. Each
+    /// chapter runs from its marker until the next one (or until `end`, for the last one).
+    ///
+    /// If the first marker isn't at the beginning, the part before it isn't in any chapter.
+    /// Markers at or after `end` are ignored.
+    pub fn chapters(&self, end: Time) -> Vec<(Time, Time, &str)> {
+        let starts: Vec<_> = self.iter().filter(|(t, _)| *t < end).collect();
+        starts
+            .iter()
+            .enumerate()
+            .map(|(i, &(start, name))| {
+                let stop = starts.get(i + 1).map(|(t, _)| *t).unwrap_or(end);
+                (start, stop, name)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(x: i64) -> Time {
+        Time::from_micros(x * 1000)
+    }
+
+    fn markers() -> Markers {
+        Markers::default()
+            .with_marker(ms(100), "a")
+            .with_marker(ms(200), "b")
+            .with_marker(ms(300), "c")
+    }
+
+    #[test]
+    fn next_and_prev() {
+        let m = markers();
+        assert_eq!(m.next_after(ms(0)), Some(ms(100)));
+        assert_eq!(m.next_after(ms(100)), Some(ms(200)));
+        assert_eq!(m.next_after(ms(300)), None);
+        assert_eq!(m.prev_before(ms(300)), Some(ms(200)));
+        assert_eq!(m.prev_before(ms(250)), Some(ms(200)));
+        assert_eq!(m.prev_before(ms(100)), None);
+    }
+
+    #[test]
+    fn chapter_at() {
+        let m = markers();
+        assert_eq!(m.chapter_at(ms(50)), None);
+        assert_eq!(m.chapter_at(ms(100)), Some("a"));
+        assert_eq!(m.chapter_at(ms(250)), Some("b"));
+        assert_eq!(m.chapter_at(ms(1000)), Some("c"));
+    }
+
+    #[test]
+    fn rename_and_remove() {
+        let m = markers().with_marker(ms(200), "renamed");
+        assert_eq!(m.iter().count(), 3);
+        assert_eq!(m.get(ms(200)), Some("renamed"));
+        let m = m.without_marker(ms(200));
+        assert_eq!(m.iter().count(), 2);
+        assert_eq!(m.get(ms(200)), None);
+    }
+
+    #[test]
+    fn fresh_name() {
+        let m = Markers::default().with_marker(ms(100), "Chapter 1");
+        assert_eq!(m.fresh_name(), "Chapter 2");
+    }
+
+    #[test]
+    fn chapters() {
+        let m = markers();
+        assert_eq!(
+            m.chapters(ms(250)),
+            vec![(ms(100), ms(200), "a"), (ms(200), ms(250), "b")]
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Exporting the structure of an animation as an [OpenTimelineIO](https://opentimeline.io) file.
+//!
+//! This is for finishing an animation in a dedicated video editor: every snippet becomes a clip
+//! (drawings on video tracks, speech on audio tracks) and every marker becomes a marker. Snippets
+//! that overlap in time go on different tracks. The clips don't point at any media, but their
+//! source ranges are the same as their positions in the timeline, so they line up with the
+//! exported video if they get relinked to it.
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::path::Path;
+
+use scribl_curves::{LabelColor, Time};
+
+use crate::ScriblState;
+
+/// A snippet, as it will appear in the exported timeline.
+#[derive(Clone, Debug, PartialEq)]
+struct Clip {
+    name: String,
+    kind: &'static str,
+    label: Option<LabelColor>,
+    /// The first frame of the clip.
+    start: u32,
+    /// The frame just after the clip ends.
+    end: u32,
+}
+
+fn rational_time(frame: u32, fps: f64) -> Value {
+    json!({
+        "OTIO_SCHEMA": "RationalTime.1",
+        "rate": fps,
+        "value": frame as f64,
+    })
+}
+
+fn time_range(start: u32, duration: u32, fps: f64) -> Value {
+    json!({
+        "OTIO_SCHEMA": "TimeRange.1",
+        "start_time": rational_time(start, fps),
+        "duration": rational_time(duration, fps),
+    })
+}
+
+fn gap(duration: u32, fps: f64) -> Value {
+    json!({
+        "OTIO_SCHEMA": "Gap.1",
+        "name": "",
+        "metadata": {},
+        "source_range": time_range(0, duration, fps),
+        "effects": [],
+        "markers": [],
+    })
+}
+
+impl Clip {
+    fn to_json(&self, fps: f64) -> Value {
+        json!({
+            "OTIO_SCHEMA": "Clip.1",
+            "name": self.name,
+            "metadata": {
+                "scribl": {
+                    "kind": self.kind,
+                    "label": self.label.map(|l| l.name()),
+                },
+            },
+            "source_range": time_range(self.start, self.end - self.start, fps),
+            "media_reference": {
+                "OTIO_SCHEMA": "MissingReference.1",
+                "name": "",
+                "metadata": {},
+                "available_range": null,
+            },
+            "effects": [],
+            "markers": [],
+        })
+    }
+}
+
+/// Splits the clips into tracks, so that the clips in each track don't overlap. The clips in
+/// each track are sorted by time.
+fn tracks(mut clips: Vec<Clip>) -> Vec<Vec<Clip>> {
+    clips.sort_by_key(|c| (c.start, c.end));
+    let mut ret: Vec<Vec<Clip>> = Vec::new();
+    for clip in clips {
+        match ret
+            .iter_mut()
+            .find(|t| t.last().map_or(true, |last| last.end <= clip.start))
+        {
+            Some(track) => track.push(clip),
+            None => ret.push(vec![clip]),
+        }
+    }
+    ret
+}
+
+fn track_json(name: String, kind: &str, clips: &[Clip], fps: f64) -> Value {
+    let mut children = Vec::new();
+    let mut frame = 0;
+    for clip in clips {
+        if clip.start > frame {
+            children.push(gap(clip.start - frame, fps));
+        }
+        children.push(clip.to_json(fps));
+        frame = clip.end;
+    }
+    json!({
+        "OTIO_SCHEMA": "Track.1",
+        "name": name,
+        "kind": kind,
+        "metadata": {},
+        "source_range": null,
+        "effects": [],
+        "markers": [],
+        "children": children,
+    })
+}
+
+/// The clips for all the snippets. Every clip is at least one frame long.
+fn clips(scribl: &ScriblState, fps: f64) -> (Vec<Clip>, Vec<Clip>) {
+    let frame = |t: Time| t.as_video_frame(fps);
+    let clip = |name: Option<&str>, kind, label, start: Time, end: Time| Clip {
+        name: name.unwrap_or_default().to_owned(),
+        kind,
+        label,
+        start: frame(start),
+        end: frame(end).max(frame(start) + 1),
+    };
+
+    // Drawings without an end time stay until the end of the animation.
+    let end = scribl.end_time();
+    let draw = scribl
+        .draw
+        .snippets()
+        .map(|(_, s)| {
+            let snip_end = s.end_time().unwrap_or(end).max(s.last_draw_time());
+            clip(s.name(), "drawing", s.label(), s.start_time(), snip_end)
+        })
+        .collect();
+    let talk = scribl
+        .talk
+        .snippets()
+        .filter(|(_, s)| !s.muted())
+        .map(|(_, s)| clip(s.name(), "speech", s.label(), s.start_time(), s.end_time()))
+        .collect();
+    (draw, talk)
+}
+
+/// Converts an animation into an OpenTimelineIO timeline, with times measured in frames at the
+/// rate `fps`.
+pub fn timeline(scribl: &ScriblState, name: &str, fps: f64) -> Value {
+    let (draw, talk) = clips(scribl, fps);
+    let mut children = Vec::new();
+    for (i, track) in tracks(draw).iter().enumerate() {
+        children.push(track_json(
+            format!("Drawing {}", i + 1),
+            "Video",
+            track,
+            fps,
+        ));
+    }
+    for (i, track) in tracks(talk).iter().enumerate() {
+        children.push(track_json(format!("Speech {}", i + 1), "Audio", track, fps));
+    }
+
+    let markers: Vec<_> = scribl
+        .markers
+        .iter()
+        .map(|(time, name)| {
+            json!({
+                "OTIO_SCHEMA": "Marker.2",
+                "name": name,
+                "metadata": {},
+                "color": "RED",
+                "comment": "",
+                "marked_range": time_range(time.as_video_frame(fps), 0, fps),
+            })
+        })
+        .collect();
+
+    json!({
+        "OTIO_SCHEMA": "Timeline.1",
+        "name": name,
+        "metadata": {},
+        "global_start_time": null,
+        "tracks": {
+            "OTIO_SCHEMA": "Stack.1",
+            "name": "tracks",
+            "metadata": {},
+            "source_range": null,
+            "effects": [],
+            "markers": markers,
+            "children": children,
+        },
+    })
+}
+
+/// Writes an animation's timeline to an `.otio` file.
+pub fn export(scribl: &ScriblState, path: &Path, fps: f64) -> Result<()> {
+    let name = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let json = serde_json::to_string_pretty(&timeline(scribl, &name, fps))?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(start: u32, end: u32) -> Clip {
+        Clip {
+            name: String::new(),
+            kind: "drawing",
+            label: None,
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn split_tracks() {
+        let t = tracks(vec![clip(5, 10), clip(0, 6), clip(6, 8), clip(10, 12)]);
+        assert_eq!(
+            t,
+            vec![
+                vec![clip(0, 6), clip(6, 8), clip(10, 12)],
+                vec![clip(5, 10)]
+            ]
+        );
+    }
+
+    #[test]
+    fn gaps() {
+        let track = track_json("t".to_owned(), "Video", &[clip(3, 5), clip(5, 9)], 30.0);
+        let children = track["children"].as_array().unwrap();
+        assert_eq!(children.len(), 3);
+        assert_eq!(children[0]["OTIO_SCHEMA"], "Gap.1");
+        assert_eq!(children[0]["source_range"]["duration"]["value"], 3.0);
+        assert_eq!(children[2]["source_range"]["start_time"]["value"], 5.0);
+        assert_eq!(children[2]["source_range"]["duration"]["value"], 4.0);
+    }
+
+    #[test]
+    fn markers() {
+        let mut scribl = ScriblState::default();
+        scribl.markers = scribl
+            .markers
+            .with_marker(Time::from_micros(2_000_000), "Intro");
+        let json = timeline(&scribl, "test", 25.0);
+        let markers = json["tracks"]["markers"].as_array().unwrap();
+        assert_eq!(markers.len(), 1);
+        assert_eq!(markers[0]["name"], "Intro");
+        assert_eq!(markers[0]["marked_range"]["start_time"]["value"], 50.0);
+        assert!(json["tracks"]["children"].as_array().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+use im::OrdSet;
+use serde::{Deserialize, Serialize};
+
+use scribl_curves::{DrawSnippet, Time};
+
+/// The division of the animation into pages (like the slides of a presentation).
+///
+/// Each page starts at a "page break," and lasts until the next one. When the animation reaches a
+/// page break, everything that was drawn on the previous page disappears, so each drawing snippet
+/// belongs to the page on which it started.
+///
+/// The first page always starts at the beginning, and it doesn't need a page break.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+#[serde(transparent)]
+pub struct Pages {
+    breaks: OrdSet<Time>,
+}
+
+impl Pages {
+    /// Returns true if there's only one page.
+    pub fn is_empty(&self) -> bool {
+        self.breaks.is_empty()
+    }
+
+    /// The number of pages.
+    pub fn page_count(&self) -> usize {
+        self.breaks.len() + 1
+    }
+
+    /// Iterates over the times at which the pages start, in order (starting with zero for the
+    /// first page).
+    pub fn starts(&self) -> impl Iterator<Item = Time> + '_ {
+        std::iter::once(Time::ZERO).chain(self.breaks.iter().copied().filter(|&t| t > Time::ZERO))
+    }
+
+    /// The index of the page that is showing at `time`.
+    pub fn page_at(&self, time: Time) -> usize {
+        self.breaks
+            .range(..=time)
+            .filter(|&&t| t > Time::ZERO)
+            .count()
+    }
+
+    /// The time at which the page showing at `time` started.
+    pub fn page_start(&self, time: Time) -> Time {
+        self.breaks
+            .range(..=time)
+            .next_back()
+            .copied()
+            .unwrap_or(Time::ZERO)
+    }
+
+    /// Is there a page break at exactly this time?
+    pub fn has_break(&self, time: Time) -> bool {
+        self.breaks.contains(&time)
+    }
+
+    /// Returns a new set of pages with an extra page break. Breaking at time zero does nothing,
+    /// because the first page always starts there.
+    pub fn with_break(&self, time: Time) -> Pages {
+        if time == Time::ZERO {
+            self.clone()
+        } else {
+            Pages {
+                breaks: self.breaks.update(time),
+            }
+        }
+    }
+
+    /// Returns a new set of pages without the page break at this time.
+    pub fn without_break(&self, time: Time) -> Pages {
+        Pages {
+            breaks: self.breaks.without(&time),
+        }
+    }
+
+    /// The time at which the next page starts, if there is a page after the one showing at
+    /// `time`.
+    pub fn next_start(&self, time: Time) -> Option<Time> {
+        self.breaks
+            .range((std::ops::Bound::Excluded(time), std::ops::Bound::Unbounded))
+            .next()
+            .copied()
+    }
+
+    /// The time at which the previous page starts, if there is a page before the one showing at
+    /// `time`.
+    pub fn prev_start(&self, time: Time) -> Option<Time> {
+        let start = self.page_start(time);
+        if start == Time::ZERO {
+            None
+        } else {
+            Some(self.page_start(start - scribl_curves::TimeDiff::from_micros(1)))
+        }
+    }
+
+    /// Do these two times show the same page?
+    pub fn same_page(&self, a: Time, b: Time) -> bool {
+        self.page_at(a) == self.page_at(b)
+    }
+
+    /// Is this drawing snippet visible at `time`? This takes into account both the snippet's own
+    /// visibility and whether it's on the page that's showing.
+    pub fn shows_snippet(&self, snip: &DrawSnippet, time: Time) -> bool {
+        snip.visible_at(time) && self.same_page(snip.start_time(), time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(x: i64) -> Time {
+        Time::from_micros(x * 1000)
+    }
+
+    fn pages() -> Pages {
+        Pages::default().with_break(ms(100)).with_break(ms(200))
+    }
+
+    #[test]
+    fn page_at() {
+        let p = pages();
+        assert_eq!(p.page_count(), 3);
+        assert_eq!(p.page_at(ms(0)), 0);
+        assert_eq!(p.page_at(ms(99)), 0);
+        assert_eq!(p.page_at(ms(100)), 1);
+        assert_eq!(p.page_at(ms(250)), 2);
+        assert_eq!(p.page_start(ms(150)), ms(100));
+        assert_eq!(
+            p.starts().collect::<Vec<_>>(),
+            vec![ms(0), ms(100), ms(200)]
+        );
+    }
+
+    #[test]
+    fn next_and_prev() {
+        let p = pages();
+        assert_eq!(p.next_start(ms(0)), Some(ms(100)));
+        assert_eq!(p.next_start(ms(100)), Some(ms(200)));
+        assert_eq!(p.next_start(ms(200)), None);
+        assert_eq!(p.prev_start(ms(250)), Some(ms(100)));
+        assert_eq!(p.prev_start(ms(100)), Some(ms(0)));
+        assert_eq!(p.prev_start(ms(50)), None);
+    }
+
+    #[test]
+    fn break_at_zero() {
+        let p = Pages::default().with_break(Time::ZERO);
+        assert!(p.is_empty());
+        assert_eq!(p.page_count(), 1);
+    }
+}
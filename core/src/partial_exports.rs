@@ -0,0 +1,156 @@
+//! Exports get written to a temporary file, which is renamed once the export succeeds. That way,
+//! if scribl crashes (or gets killed) in the middle of an export, it doesn't leave behind a broken
+//! video with the name they asked for.
+//!
+//! It does leave behind the temporary file, though, so we keep a list of the temporary files that
+//! are being written. On startup, any of them that are still around (and that aren't being written
+//! by some other instance of scribl) were orphaned, and we offer to delete them.
+
+use anyhow::{anyhow, Result};
+use directories_next::ProjectDirs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// If a temporary file hasn't been written to for this long, we assume that nobody is writing to
+/// it anymore.
+const STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// Protects the list of temporary files from concurrent exports.
+static LIST_LOCK: Mutex<()> = Mutex::new(());
+
+/// The temporary file that gets written while exporting to `path`. It's in the same directory as
+/// `path`, so that it can be renamed atomically.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".partial");
+    path.with_file_name(name)
+}
+
+/// The list gets used by other processes, which might have a different working directory.
+fn absolute(path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .map(|d| d.join(path))
+        .unwrap_or_else(|_| path.to_owned())
+}
+
+fn list_path() -> Result<PathBuf> {
+    let proj_dirs = ProjectDirs::from("ink", "scribl", "scribl")
+        .ok_or_else(|| anyhow!("couldn't determine the data directory"))?;
+    Ok(proj_dirs.data_local_dir().join("partial-exports.txt"))
+}
+
+fn read_list(list: &Path) -> Vec<PathBuf> {
+    std::fs::read_to_string(list)
+        .unwrap_or_default()
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+fn write_list(list: &Path, paths: &[PathBuf]) -> Result<()> {
+    if let Some(dir) = list.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut contents = String::new();
+    for p in paths {
+        // We can't store paths that aren't valid UTF-8, but it doesn't matter much: the worst
+        // that can happen is that we fail to clean up after a crash.
+        if let Some(s) = p.to_str() {
+            contents.push_str(s);
+            contents.push('\n');
+        }
+    }
+    std::fs::write(list, contents)?;
+    Ok(())
+}
+
+fn modify_list(f: impl FnOnce(&mut Vec<PathBuf>)) -> Result<()> {
+    let _guard = LIST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let list = list_path()?;
+    let mut paths = read_list(&list);
+    f(&mut paths);
+    write_list(&list, &paths)
+}
+
+/// Records that we're about to start writing to a temporary file.
+pub fn register(temp: &Path) {
+    let temp = absolute(temp);
+    let result = modify_list(|paths| {
+        if !paths.contains(&temp) {
+            paths.push(temp);
+        }
+    });
+    if let Err(e) = result {
+        log::warn!("failed to record temporary export file: {}", e);
+    }
+}
+
+/// Records that we're done with some temporary files (either because they were renamed, or
+/// because they were deleted).
+pub fn forget(temps: &[PathBuf]) {
+    let temps: Vec<_> = temps.iter().map(|p| absolute(p)).collect();
+    if let Err(e) = modify_list(|paths| paths.retain(|p| !temps.contains(p))) {
+        log::warn!("failed to update the list of temporary export files: {}", e);
+    }
+}
+
+fn is_stale(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .map(|age| age >= STALE_AFTER)
+        .unwrap_or(false)
+}
+
+/// Returns the temporary files that were left behind by exports that didn't finish.
+///
+/// Files that no longer exist are removed from the list, but everything else stays in the list
+/// until it gets passed to `remove` or `forget`.
+pub fn orphans() -> Vec<PathBuf> {
+    let mut ret = Vec::new();
+    let result = modify_list(|paths| {
+        paths.retain(|p| p.exists());
+        ret = paths.iter().filter(|p| is_stale(p)).cloned().collect();
+    });
+    if let Err(e) = result {
+        log::warn!("failed to check for temporary export files: {}", e);
+    }
+    ret
+}
+
+/// Deletes some temporary files.
+pub fn remove(temps: &[PathBuf]) {
+    for p in temps {
+        if let Err(e) = std::fs::remove_file(p) {
+            log::warn!("failed to delete {}: {}", p.display(), e);
+        }
+    }
+    forget(temps);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_path_is_hidden_sibling() {
+        assert_eq!(
+            temp_path(Path::new("videos/talk.mp4")),
+            Path::new("videos/.talk.mp4.partial")
+        );
+    }
+
+    #[test]
+    fn list_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let list = dir.path().join("list.txt");
+        let paths = vec![PathBuf::from("/a/.b.mp4.partial"), PathBuf::from("c")];
+        write_list(&list, &paths).unwrap();
+        assert_eq!(read_list(&list), paths);
+        assert!(read_list(&dir.path().join("missing.txt")).is_empty());
+    }
+}
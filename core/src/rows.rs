@@ -0,0 +1,49 @@
+use im::OrdMap;
+use serde::{Deserialize, Serialize};
+
+use scribl_curves::DrawSnippetId;
+
+use crate::audio::TalkSnippetId;
+
+/// The rows that the snippets occupied the last time the timeline was laid out.
+///
+/// When the timeline is in its "stable" layout mode, it tries to put each snippet back in the
+/// same row that it was in before, so that editing one snippet doesn't move all the others
+/// around. These are only hints: if a snippet doesn't fit in its old row any more, it moves.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+pub struct RowHints {
+    #[serde(default)]
+    draw: OrdMap<DrawSnippetId, usize>,
+    #[serde(default)]
+    talk: OrdMap<TalkSnippetId, usize>,
+}
+
+impl RowHints {
+    /// Collects the rows of the drawing and audio snippets.
+    pub fn new(
+        draw: impl IntoIterator<Item = (DrawSnippetId, usize)>,
+        talk: impl IntoIterator<Item = (TalkSnippetId, usize)>,
+    ) -> RowHints {
+        RowHints {
+            draw: draw.into_iter().collect(),
+            talk: talk.into_iter().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draw.is_empty() && self.talk.is_empty()
+    }
+
+    /// The row that the drawing snippet was in, if we know it.
+    pub fn draw_row(&self, id: DrawSnippetId) -> Option<usize> {
+        self.draw.get(&id).copied()
+    }
+
+    /// The row that the audio snippet was in, if we know it.
+    pub fn talk_row(&self, id: TalkSnippetId) -> Option<usize> {
+        self.talk.get(&id).copied()
+    }
+}
@@ -0,0 +1,374 @@
+use anyhow::anyhow;
+use im::Vector;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read};
+use std::path::Path;
+
+use scribl_curves::{DrawSnippets, Time};
+
+use crate::audio::{MusicTrack, TalkSnippets};
+use crate::underlay::{Pip, Underlay};
+use crate::{Checkpoint, CheckpointSave, Markers, Pages, RowHints, ScriblState};
+
+/// This is the data that we put into the saved files.
+// TODO: how disruptive would it be to move to bincode? It's apparently much faster than CBOR.
+#[derive(Clone)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+pub struct SaveFileData {
+    /// This is the version of the save file format. Every time we change the format, this gets
+    /// incremented. We retain support for reading (but not writing) old versions.
+    ///
+    /// The current version is 1.
+    pub version: u8,
+
+    pub snippets: DrawSnippets,
+    pub audio_snippets: TalkSnippets,
+    /// The background music. This was added in version 1 without bumping the version number, so
+    /// it's optional.
+    pub music: Option<MusicTrack>,
+    /// A screen recording that plays behind the drawing. This was also added in version 1 without
+    /// bumping the version number.
+    pub underlay: Option<Underlay>,
+    /// A webcam recording that plays in a corner of the drawing. This was also added in version 1
+    /// without bumping the version number.
+    pub webcam: Option<Pip>,
+    /// Named markers on the timeline. These were also added in version 1 without bumping the
+    /// version number.
+    pub markers: Markers,
+    /// Page breaks. These were also added in version 1 without bumping the version number.
+    pub pages: Pages,
+    /// Named checkpoints. These were also added in version 1 without bumping the version number.
+    pub checkpoints: Vector<Checkpoint>,
+    /// The timeline rows of the snippets. These were also added in version 1 without bumping the
+    /// version number.
+    pub row_hints: RowHints,
+    /// The frame rate of the animation, if it has one of its own. Otherwise, it uses the frame
+    /// rate from the export settings. This was also added in version 1 without bumping the
+    /// version number.
+    pub fps: Option<f64>,
+    /// The volume of the pencil sound, if the animation has one. This was also added in version 1
+    /// without bumping the version number.
+    pub pencil_volume: Option<f32>,
+    /// The explicitly set end of the animation, if it has one. This was also added in version 1
+    /// without bumping the version number.
+    pub project_end: Option<Time>,
+
+    /// The aspect ratio of the animation. Currently this is fixed at 4:3, but eventually we'll
+    /// want to support other values, so let's put it in the save file format.
+    pub aspect_ratio: (u32, u32),
+    /// The "width" of the animation, in some arbitrary and meaningless unit. Currently this is
+    /// fixed at 1.0 but eventually we may want to allow them to resize the canvas, so let's put it
+    /// in the save file format.
+    pub width: f64,
+}
+
+/// The serialized form of [`SaveFileData`]. The only difference is that checkpoints don't store
+/// the snippets that they share with the main animation.
+#[derive(Deserialize, Serialize)]
+struct SaveFileRepr {
+    // The version needs to come first; see `SaveFileData::load_from`.
+    version: u8,
+    snippets: DrawSnippets,
+    audio_snippets: TalkSnippets,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    music: Option<MusicTrack>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    underlay: Option<Underlay>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    webcam: Option<Pip>,
+    #[serde(default, skip_serializing_if = "Markers::is_empty")]
+    markers: Markers,
+    #[serde(default, skip_serializing_if = "Pages::is_empty")]
+    pages: Pages,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    checkpoints: Vec<CheckpointSave>,
+    #[serde(default, skip_serializing_if = "RowHints::is_empty")]
+    row_hints: RowHints,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fps: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pencil_volume: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project_end: Option<Time>,
+    aspect_ratio: (u32, u32),
+    width: f64,
+}
+
+impl Serialize for SaveFileData {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let checkpoints = self
+            .checkpoints
+            .iter()
+            .map(|c| CheckpointSave::new(c, &self.snippets, &self.audio_snippets))
+            .collect();
+        SaveFileRepr {
+            version: self.version,
+            snippets: self.snippets.clone(),
+            audio_snippets: self.audio_snippets.clone(),
+            music: self.music.clone(),
+            underlay: self.underlay.clone(),
+            webcam: self.webcam.clone(),
+            markers: self.markers.clone(),
+            pages: self.pages.clone(),
+            checkpoints,
+            row_hints: self.row_hints.clone(),
+            fps: self.fps,
+            pencil_volume: self.pencil_volume,
+            project_end: self.project_end,
+            aspect_ratio: self.aspect_ratio,
+            width: self.width,
+        }
+        .serialize(ser)
+    }
+}
+
+impl<'de> Deserialize<'de> for SaveFileData {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<SaveFileData, D::Error> {
+        let repr = SaveFileRepr::deserialize(de)?;
+        let (snippets, audio_snippets) = (&repr.snippets, &repr.audio_snippets);
+        let checkpoints = repr
+            .checkpoints
+            .into_iter()
+            .map(|c| c.load(snippets, audio_snippets))
+            .collect::<anyhow::Result<Vector<_>>>()
+            .map_err(serde::de::Error::custom)?;
+        Ok(SaveFileData {
+            version: repr.version,
+            snippets: repr.snippets,
+            audio_snippets: repr.audio_snippets,
+            music: repr.music,
+            underlay: repr.underlay,
+            webcam: repr.webcam,
+            markers: repr.markers,
+            pages: repr.pages,
+            checkpoints,
+            row_hints: repr.row_hints,
+            fps: repr.fps,
+            pencil_volume: repr.pencil_volume,
+            project_end: repr.project_end,
+            aspect_ratio: repr.aspect_ratio,
+            width: repr.width,
+        })
+    }
+}
+
+pub mod v0 {
+    #[derive(serde::Deserialize)]
+    pub struct SaveFileData {
+        pub version: u8,
+        pub snippets: scribl_curves::save::v0::DrawSnippets,
+        pub audio_snippets: crate::audio::TalkSnippets,
+    }
+
+    impl From<SaveFileData> for super::SaveFileData {
+        fn from(d: SaveFileData) -> super::SaveFileData {
+            super::SaveFileData {
+                version: 1,
+                snippets: d.snippets.into(),
+                audio_snippets: d.audio_snippets,
+                music: None,
+                underlay: None,
+                webcam: None,
+                markers: Default::default(),
+                pages: Default::default(),
+                checkpoints: Default::default(),
+                row_hints: Default::default(),
+                fps: None,
+                pencil_volume: None,
+                project_end: None,
+                aspect_ratio: (4, 3),
+                width: 1.0,
+            }
+        }
+    }
+}
+
+impl SaveFileData {
+    pub fn from_scribl(scribl: &ScriblState) -> SaveFileData {
+        SaveFileData {
+            version: 1,
+            snippets: scribl.draw.clone(),
+            audio_snippets: scribl.talk.clone(),
+            music: scribl.music.clone(),
+            underlay: scribl.underlay.clone(),
+            webcam: scribl.webcam.clone(),
+            markers: scribl.markers.clone(),
+            pages: scribl.pages.clone(),
+            checkpoints: scribl.checkpoints.clone(),
+            row_hints: scribl.row_hints.clone(),
+            fps: scribl.fps,
+            pencil_volume: scribl.pencil_volume,
+            project_end: scribl.project_end,
+            aspect_ratio: (4, 3),
+            width: 1.0,
+        }
+    }
+
+    pub fn load_from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<SaveFileData> {
+        SaveFileData::load_from_path_with_progress(path, |_| {})
+    }
+
+    /// Loads a file, periodically calling `progress` with the fraction of the file that has been
+    /// read so far.
+    pub fn load_from_path_with_progress<P: AsRef<Path>>(
+        path: P,
+        mut progress: impl FnMut(f64),
+    ) -> anyhow::Result<SaveFileData> {
+        let file = File::open(path.as_ref())?;
+        let len = file.metadata()?.len().max(1);
+        let mut bytes_read = 0;
+        let file = ProgressReader {
+            inner: file,
+            callback: move |n| {
+                bytes_read += n as u64;
+                progress(bytes_read as f64 / len as f64);
+            },
+        };
+        SaveFileData::load_from(BufReader::new(file))
+    }
+
+    /// Loads a file from a reader.
+    ///
+    /// The file gets parsed as it's read, instead of being read into memory first, so that
+    /// progress reporting (see `load_from_path_with_progress`) reflects the parsing progress.
+    pub fn load_from<R: Read>(mut read: R) -> anyhow::Result<SaveFileData> {
+        // The version number is at byte 9 (the first two bytes are some CBOR tags, followed by the
+        // string "version", followed by the version number.
+        let mut header = [0; 10];
+        read.read_exact(&mut header).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => anyhow!("file too short!"),
+            _ => e.into(),
+        })?;
+        let version = header[9];
+        log::info!("Found file format version {}", version);
+
+        let read = (&header[..]).chain(read);
+        match version {
+            0 => {
+                let data: v0::SaveFileData = serde_cbor::from_reader(read)?;
+                Ok(data.into())
+            }
+            1 => Ok(serde_cbor::from_reader(read)?),
+            n => Err(anyhow!("unsupported file format version: {}", n)),
+        }
+    }
+
+    pub fn save_to_path<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let tmp_file_name = format!(
+            "{}.savefile",
+            path.file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("untitled")
+        );
+        let tmp_path = path.with_file_name(tmp_file_name);
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let write = BufWriter::new(File::create(&tmp_path)?);
+        self.save_to(write)?;
+        std::fs::rename(tmp_path, path)?;
+
+        Ok(())
+    }
+
+    pub fn save_to<W: std::io::Write>(&self, write: W) -> anyhow::Result<()> {
+        serde_cbor::to_writer(write, self)?;
+        Ok(())
+    }
+}
+
+/// A reader that calls a callback with the number of bytes in every chunk that it reads.
+struct ProgressReader<R, F> {
+    inner: R,
+    callback: F,
+}
+
+impl<R: Read, F: FnMut(usize)> Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        (self.callback)(n);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_round_trip(data: &[u8]) {
+        // Check that we can read our sample file.
+        let save_data = SaveFileData::load_from(&data[..]).unwrap();
+
+        let mut written = Vec::new();
+        save_data.save_to(&mut written).unwrap();
+
+        // We don't check that save -> load is the identity, because it's too
+        // fragile (e.g., compression settings could change). We also don't check
+        // that load -> save is the identity (for now), because implementing
+        // PartialEq is a pain.
+        let read_again = SaveFileData::load_from(&written[..]).unwrap();
+
+        // We do check that if something was written using the current version
+        // of scribl, then save -> load is the identity.
+        let mut written_again = Vec::new();
+        read_again.save_to(&mut written_again).unwrap();
+        assert_eq!(written, written_again);
+    }
+
+    #[test]
+    fn save_load() {
+        check_round_trip(&include_bytes!("../../scribl/sample/intro.scb")[..]);
+    }
+
+    #[test]
+    fn load_progress() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../scribl/sample/intro.scb");
+        let mut fractions = Vec::new();
+        SaveFileData::load_from_path_with_progress(path, |f| fractions.push(f)).unwrap();
+
+        assert!(!fractions.is_empty());
+        assert!(fractions.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(*fractions.last().unwrap(), 1.0);
+    }
+
+    #[test]
+    fn save_load_fields() {
+        // The sample file is older than these fields, so it doesn't have any of them.
+        let data = include_bytes!("../../scribl/sample/intro.scb");
+        let mut save_data = SaveFileData::load_from(&data[..]).unwrap();
+        assert_eq!(save_data.underlay, None);
+        assert_eq!(save_data.fps, None);
+        assert_eq!(save_data.pencil_volume, None);
+        assert_eq!(save_data.project_end, None);
+
+        let (draw_id, _) = save_data.snippets.snippets().next().unwrap();
+        save_data.underlay = Some(Underlay {
+            path: "capture.mp4".into(),
+            start: Time::from_micros(1_000_000),
+            duration: scribl_curves::TimeDiff::from_micros(2_000_000),
+        });
+        save_data.row_hints = RowHints::new(vec![(draw_id, 3)], vec![]);
+        save_data.fps = Some(24.0);
+        save_data.pencil_volume = Some(0.5);
+        save_data.project_end = Some(Time::from_micros(12_345_000));
+
+        let mut written = Vec::new();
+        save_data.save_to(&mut written).unwrap();
+        let read_again = SaveFileData::load_from(&written[..]).unwrap();
+        assert_eq!(read_again.underlay, save_data.underlay);
+        assert_eq!(read_again.row_hints, save_data.row_hints);
+        assert_eq!(read_again.row_hints.draw_row(draw_id), Some(3));
+        assert_eq!(read_again.fps, save_data.fps);
+        assert_eq!(read_again.pencil_volume, save_data.pencil_volume);
+        assert_eq!(read_again.project_end, save_data.project_end);
+    }
+
+    #[test]
+    fn save_load_v0() {
+        check_round_trip(&include_bytes!("../../scribl/sample/intro_v0.scb")[..]);
+    }
+}
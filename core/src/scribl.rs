@@ -0,0 +1,131 @@
+use im::Vector;
+use scribl_curves::{DrawSnippet, DrawSnippetId, DrawSnippets, Time};
+
+use crate::audio::{
+    MusicOutput, MusicTrack, OutputData, PencilOutput, TalkSnippet, TalkSnippetId, TalkSnippets,
+};
+use crate::underlay::{Pip, Underlay};
+use crate::{Checkpoint, Markers, Pages, RowHints, SaveFileData};
+
+/// This data contains the state of the current scribl. That means, just the parts that get saved
+/// if we save the file.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "druid", derive(druid::Data, druid::Lens))]
+pub struct ScriblState {
+    pub draw: DrawSnippets,
+    pub talk: TalkSnippets,
+    pub music: Option<MusicTrack>,
+    pub underlay: Option<Underlay>,
+    pub webcam: Option<Pip>,
+    pub markers: Markers,
+    pub pages: Pages,
+    pub checkpoints: Vector<Checkpoint>,
+    pub row_hints: RowHints,
+    /// The frame rate for exporting and for stepping through frames. If this is `None`, the
+    /// frame rate comes from the export settings.
+    pub fps: Option<f64>,
+    /// The volume of the pencil sound that plays while strokes are being drawn, or `None` if
+    /// there is no pencil sound.
+    pub pencil_volume: Option<f32>,
+    /// The time at which the animation ends, if it was set explicitly. If this is `None`, the
+    /// animation ends when the last snippet does.
+    pub project_end: Option<Time>,
+}
+
+impl ScriblState {
+    pub fn new(draw: DrawSnippets, talk: TalkSnippets) -> ScriblState {
+        ScriblState {
+            draw,
+            talk,
+            music: None,
+            underlay: None,
+            webcam: None,
+            markers: Markers::default(),
+            pages: Pages::default(),
+            checkpoints: Vector::new(),
+            row_hints: RowHints::default(),
+            fps: None,
+            pencil_volume: None,
+            project_end: None,
+        }
+    }
+
+    pub fn from_save_file(data: &SaveFileData) -> ScriblState {
+        ScriblState {
+            draw: data.snippets.clone(),
+            talk: data.audio_snippets.clone(),
+            music: data.music.clone(),
+            underlay: data.underlay.clone(),
+            webcam: data.webcam.clone(),
+            markers: data.markers.clone(),
+            pages: data.pages.clone(),
+            checkpoints: data.checkpoints.clone(),
+            row_hints: data.row_hints.clone(),
+            fps: data.fps,
+            pencil_volume: data.pencil_volume,
+            project_end: data.project_end,
+        }
+    }
+
+    /// The time at which the animation ends. This is the explicitly set end time if there is one,
+    /// and otherwise the end of the content (see [`ScriblState::content_end_time`]).
+    pub fn end_time(&self) -> Time {
+        self.project_end.unwrap_or_else(|| self.content_end_time())
+    }
+
+    /// The last time at which something is drawn or said.
+    pub fn content_end_time(&self) -> Time {
+        self.draw.last_draw_time().max(self.talk.end_time())
+    }
+
+    /// Returns the music that should be played along with the animation, if there is any.
+    pub fn music_output(&self) -> Option<MusicOutput> {
+        self.music.as_ref().map(|track| MusicOutput {
+            track: track.clone(),
+            end_time: self.end_time(),
+        })
+    }
+
+    /// Returns the pencil sound that should be played along with the animation, if there is one.
+    ///
+    /// This has to look at every stroke in the animation, so it shouldn't be called too often.
+    pub fn pencil_output(&self) -> Option<PencilOutput> {
+        self.pencil_volume
+            .map(|volume| PencilOutput::new(&self.draw, volume))
+    }
+
+    /// Measures the integrated loudness (in LUFS) of all the audio, mixed together the way it
+    /// would be exported. If there's no audio, this is negative infinity.
+    ///
+    /// This mixes the whole animation, so it can take a while.
+    pub fn loudness(&self) -> anyhow::Result<f64> {
+        OutputData {
+            snips: self.talk.clone(),
+            crossfades: self.talk.crossfades(),
+            music: self.music_output(),
+            pencil: self.pencil_output(),
+            ..OutputData::new()
+        }
+        .integrated_loudness()
+    }
+
+    pub fn add_draw_snippet(&mut self, snip: DrawSnippet) -> DrawSnippetId {
+        let (new_snippets, new_id) = self.draw.with_new_snippet(snip);
+        self.draw = new_snippets;
+        new_id
+    }
+
+    pub fn add_talk_snippet(&mut self, snip: TalkSnippet) -> TalkSnippetId {
+        let (new_snippets, new_id) = self.talk.with_new_snippet(snip);
+        self.talk = new_snippets;
+        new_id
+    }
+
+    pub fn delete_draw_snippet(&mut self, id: DrawSnippetId) {
+        self.draw = self.draw.without_snippet(id);
+    }
+
+    pub fn delete_talk_snippet(&mut self, id: TalkSnippetId) {
+        self.talk = self.talk.without_snippet(id);
+    }
+}
@@ -0,0 +1,315 @@
+//! Importing drawings from SVG files.
+//!
+//! We only look at the geometry: every path and basic shape in the file becomes one or more
+//! strokes, drawn with whatever style the caller asks for. Fills, colors, text and images are
+//! ignored. The whole image is scaled to fit in the middle of the drawing.
+
+use anyhow::{anyhow, Context, Result};
+use kurbo::{Affine, BezPath, Circle, Ellipse, PathEl, Point, Rect, Shape, Vec2};
+use std::path::Path;
+
+use scribl_curves::{StrokeInProgress, StrokeSeq, StrokeStyle, Time, TimeDiff};
+
+// The drawing coordinates go from 0.0 to 1.0 horizontally, and from 0.0 to 0.75 vertically.
+const DRAWING_WIDTH: f64 = 1.0;
+const DRAWING_HEIGHT: f64 = 0.75;
+
+// The imported image is scaled to leave this much of the drawing empty on each side.
+const MARGIN: f64 = 0.05;
+
+// How accurately (in drawing coordinates) we approximate curves by line segments.
+const TOLERANCE: f64 = 0.0001;
+
+// Passed to `StrokeSeq::append_stroke`. This is a little finer than what we use for strokes drawn
+// by hand, because imported drawings are usually more precise.
+const SIMPLIFY_THRESHOLD: f64 = 0.0002;
+
+// Elements whose contents don't get drawn directly.
+const HIDDEN_ELEMENTS: &[&str] = &["defs", "clipPath", "mask", "marker", "pattern", "symbol"];
+
+/// Reads the paths from an SVG file. See [`parse`].
+pub fn load(path: &Path) -> Result<Vec<BezPath>> {
+    let svg = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    parse(&svg)
+}
+
+/// Reads the paths from an SVG image, scaled and moved to fit in the middle of the drawing.
+pub fn parse(svg: &str) -> Result<Vec<BezPath>> {
+    let doc = roxmltree::Document::parse(svg)?;
+    let mut paths = Vec::new();
+    for node in doc.descendants().filter(|n| n.is_element()) {
+        if node.ancestors().any(|a| {
+            HIDDEN_ELEMENTS.contains(&a.tag_name().name()) || a.attribute("display") == Some("none")
+        }) {
+            continue;
+        }
+        if let Some(path) = element_path(&node)? {
+            paths.push(node_transform(&node) * path);
+        }
+    }
+
+    let bbox = paths
+        .iter()
+        .map(|p| p.bounding_box())
+        .reduce(|a, b| a.union(b))
+        .ok_or_else(|| anyhow!("the image doesn't contain any paths"))?;
+    let fit = fit_to_drawing(bbox);
+    Ok(paths.into_iter().map(|p| fit * p).collect())
+}
+
+/// Turns paths into a stroke sequence.
+///
+/// The strokes get drawn one after the other, starting at `start`. They all appear immediately if
+/// `duration` is zero; otherwise they're drawn at a constant speed, so that the last one finishes
+/// after `duration`.
+pub fn to_strokes(
+    paths: &[BezPath],
+    style: &StrokeStyle,
+    start: Time,
+    duration: TimeDiff,
+) -> StrokeSeq {
+    let lines: Vec<Vec<Point>> = paths.iter().flat_map(polylines).collect();
+    // This adds up the lengths in the same order as the loop below, so that the last stroke ends
+    // at exactly the right time.
+    let total_len = lines
+        .iter()
+        .flat_map(|l| l.windows(2))
+        .fold(0.0, |acc, w| acc + (w[1] - w[0]).hypot());
+    let time_at = |len: f64| {
+        if total_len > 0.0 {
+            start + TimeDiff::from_micros((duration.as_micros() as f64 * len / total_len) as i64)
+        } else {
+            start
+        }
+    };
+
+    let mut ret = StrokeSeq::new();
+    let mut len = 0.0;
+    for line in lines {
+        let mut stroke = StrokeInProgress::new();
+        stroke.add_point(line[0], time_at(len));
+        for w in line.windows(2) {
+            len += (w[1] - w[0]).hypot();
+            stroke.add_point(w[1], time_at(len));
+        }
+        ret.append_stroke(stroke, style.clone(), false, SIMPLIFY_THRESHOLD);
+    }
+    ret
+}
+
+/// Approximates a path by polylines, one for each of its subpaths. Closed subpaths end where they
+/// started, and subpaths with only one point are dropped.
+fn polylines(path: &BezPath) -> Vec<Vec<Point>> {
+    let mut ret: Vec<Vec<Point>> = Vec::new();
+    kurbo::flatten(path, TOLERANCE, |el| match el {
+        PathEl::MoveTo(p) => ret.push(vec![p]),
+        PathEl::LineTo(p) => {
+            if let Some(line) = ret.last_mut() {
+                line.push(p);
+            }
+        }
+        PathEl::ClosePath => {
+            if let Some(line) = ret.last_mut() {
+                line.push(line[0]);
+            }
+        }
+        // `flatten` doesn't produce curves.
+        _ => {}
+    });
+    ret.retain(|line| line.len() > 1);
+    ret
+}
+
+/// Returns the transformation that scales `bbox` to fit in the drawing, and centers it.
+fn fit_to_drawing(bbox: Rect) -> Affine {
+    let avail_width = DRAWING_WIDTH * (1.0 - 2.0 * MARGIN);
+    let avail_height = DRAWING_HEIGHT * (1.0 - 2.0 * MARGIN);
+    let scale = if bbox.width() > 0.0 && bbox.height() > 0.0 {
+        (avail_width / bbox.width()).min(avail_height / bbox.height())
+    } else if bbox.width() > 0.0 {
+        avail_width / bbox.width()
+    } else if bbox.height() > 0.0 {
+        avail_height / bbox.height()
+    } else {
+        1.0
+    };
+    let center = Vec2::new(DRAWING_WIDTH / 2.0, DRAWING_HEIGHT / 2.0);
+    Affine::translate(center) * Affine::scale(scale) * Affine::translate(-bbox.center().to_vec2())
+}
+
+/// The combined transformation of a node and all of its ancestors.
+fn node_transform(node: &roxmltree::Node) -> Affine {
+    node.ancestors()
+        .filter_map(|n| n.attribute("transform"))
+        .fold(Affine::IDENTITY, |acc, t| parse_transform(t) * acc)
+}
+
+/// Parses a list of numbers, separated by commas or whitespace.
+fn numbers(s: &str) -> Vec<f64> {
+    s.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|x| !x.is_empty())
+        .filter_map(|x| x.parse().ok())
+        .collect()
+}
+
+/// Parses an SVG `transform` attribute.
+fn parse_transform(s: &str) -> Affine {
+    let mut ret = Affine::IDENTITY;
+    for part in s.split(')') {
+        let (name, args) = match part.split_once('(') {
+            Some(x) => x,
+            None => continue,
+        };
+        let name = name.trim_matches(|c: char| c == ',' || c.is_whitespace());
+        let t = match (name, &numbers(args)[..]) {
+            ("matrix", &[a, b, c, d, e, f]) => Affine::new([a, b, c, d, e, f]),
+            ("translate", &[x]) => Affine::translate((x, 0.0)),
+            ("translate", &[x, y]) => Affine::translate((x, y)),
+            ("scale", &[s]) => Affine::scale(s),
+            ("scale", &[x, y]) => Affine::scale_non_uniform(x, y),
+            ("rotate", &[a]) => Affine::rotate(a.to_radians()),
+            ("rotate", &[a, x, y]) => {
+                Affine::translate((x, y))
+                    * Affine::rotate(a.to_radians())
+                    * Affine::translate((-x, -y))
+            }
+            ("skewX", &[a]) => Affine::new([1.0, 0.0, a.to_radians().tan(), 1.0, 0.0, 0.0]),
+            ("skewY", &[a]) => Affine::new([1.0, a.to_radians().tan(), 0.0, 1.0, 0.0, 0.0]),
+            _ => {
+                log::warn!("ignoring invalid transform {:?}", part);
+                Affine::IDENTITY
+            }
+        };
+        ret = ret * t;
+    }
+    ret
+}
+
+/// Reads a length attribute. We only understand lengths in user units (with an optional "px").
+fn length(node: &roxmltree::Node, attr: &str) -> f64 {
+    node.attribute(attr)
+        .and_then(|s| s.trim().trim_end_matches("px").parse().ok())
+        .unwrap_or(0.0)
+}
+
+/// Returns the path described by an element (without applying its transform), or `None` if the
+/// element isn't a shape.
+fn element_path(node: &roxmltree::Node) -> Result<Option<BezPath>> {
+    let len = |attr| length(node, attr);
+    let points = || {
+        let nums = numbers(node.attribute("points").unwrap_or(""));
+        nums.chunks_exact(2)
+            .map(|p| Point::new(p[0], p[1]))
+            .collect::<Vec<_>>()
+    };
+    let polyline = |points: Vec<Point>, close: bool| {
+        let mut path = BezPath::new();
+        if let Some((first, rest)) = points.split_first() {
+            path.move_to(*first);
+            for p in rest {
+                path.line_to(*p);
+            }
+            if close {
+                path.close_path();
+            }
+        }
+        path
+    };
+
+    let path = match node.tag_name().name() {
+        "path" => {
+            let d = node.attribute("d").unwrap_or("");
+            BezPath::from_svg(d).map_err(|e| anyhow!("invalid path {:?}: {}", d, e))?
+        }
+        "line" => polyline(
+            vec![
+                Point::new(len("x1"), len("y1")),
+                Point::new(len("x2"), len("y2")),
+            ],
+            false,
+        ),
+        "polyline" => polyline(points(), false),
+        "polygon" => polyline(points(), true),
+        "rect" => {
+            let (x, y) = (len("x"), len("y"));
+            Rect::new(x, y, x + len("width"), y + len("height")).to_path(TOLERANCE)
+        }
+        "circle" => Circle::new((len("cx"), len("cy")), len("r")).to_path(TOLERANCE),
+        "ellipse" => {
+            Ellipse::new((len("cx"), len("cy")), (len("rx"), len("ry")), 0.0).to_path(TOLERANCE)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use piet::Color;
+    use scribl_curves::{Brush, Effects};
+
+    const SVG: &str = r#"
+        <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 200 100">
+          <defs><path d="M 0 0 L 1000 1000"/></defs>
+          <g transform="translate(100, 0)">
+            <path d="M 0 0 L 100 0 L 100 100 Z"/>
+          </g>
+          <rect x="0" y="0" width="50px" height="50"/>
+        </svg>
+    "#;
+
+    fn style() -> StrokeStyle {
+        StrokeStyle {
+            color: Color::BLACK,
+            thickness: 0.01,
+            effects: Effects::default(),
+            brush: Brush::Round,
+        }
+    }
+
+    #[test]
+    fn transforms() {
+        let p = Point::new(1.0, 2.0);
+        assert_eq!(parse_transform("translate(1 2)") * p, Point::new(2.0, 4.0));
+        assert_eq!(
+            parse_transform("translate(1,2) scale(2)") * p,
+            Point::new(3.0, 6.0)
+        );
+        assert_eq!(parse_transform("bogus(1)") * p, p);
+    }
+
+    #[test]
+    fn parsing() {
+        let paths = parse(SVG).unwrap();
+        assert_eq!(paths.len(), 2);
+
+        // The image is 200 by 100, so its width determines the scale.
+        let bbox = paths
+            .iter()
+            .map(|p| p.bounding_box())
+            .reduce(|a, b| a.union(b))
+            .unwrap();
+        assert!((bbox.width() - 0.9).abs() < 1e-9);
+        assert!((bbox.center().x - 0.5).abs() < 1e-9);
+        assert!((bbox.center().y - 0.375).abs() < 1e-9);
+
+        assert!(parse(r#"<svg xmlns="http://www.w3.org/2000/svg"/>"#).is_err());
+    }
+
+    #[test]
+    fn timing() {
+        let paths = parse(SVG).unwrap();
+        let start = Time::from_micros(1_000_000);
+
+        let seq = to_strokes(&paths, &style(), start, TimeDiff::ZERO);
+        assert_eq!(seq.len(), 2);
+        assert_eq!(seq.first_time(), start);
+        assert_eq!(seq.last_time(), start);
+
+        let seq = to_strokes(&paths, &style(), start, TimeDiff::from_micros(2_000_000));
+        assert_eq!(seq.first_time(), start);
+        assert_eq!(seq.last_time(), Time::from_micros(3_000_000));
+    }
+}
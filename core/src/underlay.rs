@@ -0,0 +1,630 @@
+//! Videos that play along with the drawing.
+//!
+//! While they're talking, scribl can record (part of) the screen into a video file. That video
+//! becomes the animation's underlay: it plays behind the strokes, starting at the time that the
+//! recording started, both in the editor and in exported videos.
+//!
+//! It can also record from a webcam. The webcam video plays as a small picture-in-picture in one
+//! corner of the drawing, on top of the strokes.
+
+use anyhow::{anyhow, Context, Result};
+use crossbeam_channel::{unbounded, Sender};
+use directories_next::ProjectDirs;
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use gstreamer_app as gst_app;
+use gstreamer_video::{VideoFormat, VideoInfo};
+use kurbo::{Point, Rect, Size};
+use piet::{ImageFormat, InterpolationMode, RenderContext};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+use scribl_curves::{Time, TimeDiff};
+
+use crate::audio::create_gst_elt;
+
+// The drawing coordinates go from 0.0 to 1.0 horizontally, and from 0.0 to 0.75 vertically.
+const DRAWING_WIDTH: f64 = 1.0;
+const DRAWING_HEIGHT: f64 = 0.75;
+
+// The distance between the picture-in-picture and the edges of the drawing.
+const PIP_MARGIN: f64 = 0.02;
+
+// How long to wait for the recording to finish writing its file.
+const FINISH_TIMEOUT_SECS: u64 = 10;
+
+// How long to wait for the decoder to produce a frame.
+const PULL_TIMEOUT_SECS: u64 = 5;
+
+// If we're asked for a frame this far ahead of the last one we decoded, it's faster to seek than
+// to decode everything in between.
+const MAX_SKIP: TimeDiff = TimeDiff::from_micros(2_000_000);
+
+/// A video that plays behind the drawing.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+pub struct Underlay {
+    /// The video file. Unlike music, we don't store the video in the save file, so this needs to
+    /// stay where it is.
+    #[cfg_attr(feature = "druid", data(same_fn = "PartialEq::eq"))]
+    pub path: PathBuf,
+    /// The animation time at which the video starts.
+    pub start: Time,
+    /// The length of the video.
+    pub duration: TimeDiff,
+}
+
+impl Underlay {
+    /// The time in the video that should be shown at the animation time `time`, or `None` if the
+    /// video isn't showing then.
+    pub fn offset(&self, time: Time) -> Option<TimeDiff> {
+        let offset = time - self.start;
+        if offset >= TimeDiff::ZERO && offset < self.duration {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+
+    pub fn end_time(&self) -> Time {
+        self.start + self.duration
+    }
+}
+
+/// A corner of the drawing.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Default for Corner {
+    fn default() -> Corner {
+        Corner::BottomRight
+    }
+}
+
+/// A video that plays in a corner of the drawing, on top of the strokes.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+pub struct Pip {
+    /// The video, which is stored in the same way as an underlay.
+    pub video: Underlay,
+    pub corner: Corner,
+    /// The width of the video, as a fraction of the width of the drawing.
+    pub size: f64,
+}
+
+impl Pip {
+    /// The rectangle (in drawing coordinates) that the video covers, if its frames have the given
+    /// size.
+    pub fn rect(&self, frame_size: Size) -> Rect {
+        let width = self.size * DRAWING_WIDTH;
+        let height = if frame_size.width > 0.0 {
+            width * frame_size.height / frame_size.width
+        } else {
+            width
+        };
+        let x = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => PIP_MARGIN,
+            Corner::TopRight | Corner::BottomRight => DRAWING_WIDTH - PIP_MARGIN - width,
+        };
+        let y = match self.corner {
+            Corner::TopLeft | Corner::TopRight => PIP_MARGIN,
+            Corner::BottomLeft | Corner::BottomRight => DRAWING_HEIGHT - PIP_MARGIN - height,
+        };
+        Rect::from_origin_size(Point::new(x, y), (width, height))
+    }
+
+    /// Draws a frame of the video (in drawing coordinates).
+    pub fn render(&self, ctx: &mut impl RenderContext, frame: &Frame) {
+        let rect = self.rect(Size::new(frame.width as f64, frame.height as f64));
+        frame.render(ctx, rect);
+    }
+}
+
+/// Where to record video from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VideoSource {
+    /// Record the screen, or part of it.
+    Screen(Option<CaptureRegion>),
+    /// Record from a webcam. On linux, this is the path of the device (like `/dev/video0`); on
+    /// other platforms, it's ignored. If it's `None`, we use the default webcam.
+    Webcam(Option<String>),
+}
+
+/// The part of the screen to record, in pixels.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub struct CaptureRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Where to put a new recording.
+///
+/// If the animation has been saved at `project`, the recording goes next to it (so that it's easy
+/// to move them together). Otherwise, it goes in scribl's data directory. `kind` is included in
+/// the file name, to tell different kinds of recordings apart.
+pub fn capture_path(kind: &str, project: Option<&Path>) -> Result<PathBuf> {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    if let Some(project) = project {
+        let dir = project.parent().unwrap_or_else(|| Path::new(""));
+        let stem = project
+            .file_stem()
+            .map(|s| s.to_string_lossy())
+            .unwrap_or_default();
+        return Ok(dir.join(format!("{}-{}-{}.mp4", stem, kind, secs)));
+    }
+
+    let proj_dirs = ProjectDirs::from("ink", "scribl", "scribl")
+        .ok_or_else(|| anyhow!("couldn't determine the data directory"))?;
+    let dir = proj_dirs.data_local_dir().join("captures");
+    std::fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    Ok(dir.join(format!("{}-{}.mp4", kind, secs)))
+}
+
+/// Creates the gstreamer element that captures from a webcam on this platform.
+fn webcam_source(device: Option<&str>) -> Result<gst::Element> {
+    if cfg!(target_os = "windows") {
+        create_gst_elt("mfvideosrc", "capture-source")
+    } else if cfg!(target_os = "macos") {
+        create_gst_elt("avfvideosrc", "capture-source")
+    } else {
+        let src = create_gst_elt("v4l2src", "capture-source")?;
+        if let Some(device) = device {
+            src.set_property("device", device);
+        }
+        Ok(src)
+    }
+}
+
+/// Creates the gstreamer element that captures the screen on this platform.
+fn screen_source(region: Option<CaptureRegion>) -> Result<gst::Element> {
+    if cfg!(target_os = "windows") {
+        if region.is_some() {
+            log::warn!("recording part of the screen isn't supported on windows");
+        }
+        create_gst_elt("d3d11screencapturesrc", "capture-source")
+    } else if cfg!(target_os = "macos") {
+        if region.is_some() {
+            log::warn!("recording part of the screen isn't supported on macOS");
+        }
+        let src = create_gst_elt("avfvideosrc", "capture-source")?;
+        src.set_property("capture-screen", true);
+        Ok(src)
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        // On wayland, the screen can only be recorded through pipewire (and the desktop will ask
+        // which part of the screen to share).
+        if region.is_some() {
+            log::warn!("recording part of the screen isn't supported on wayland");
+        }
+        create_gst_elt("pipewiresrc", "capture-source")
+    } else {
+        let src = create_gst_elt("ximagesrc", "capture-source")?;
+        src.set_property("use-damage", false);
+        if let Some(r) = region {
+            // x264 only accepts even dimensions.
+            let width = r.width & !1;
+            let height = r.height & !1;
+            if width == 0 || height == 0 {
+                return Err(anyhow!("the screen capture region is empty"));
+            }
+            src.set_property("startx", r.x);
+            src.set_property("starty", r.y);
+            src.set_property("endx", r.x + width - 1);
+            src.set_property("endy", r.y + height - 1);
+        }
+        Ok(src)
+    }
+}
+
+/// Records video into a file, until it's finished.
+pub struct VideoRecorder {
+    pipeline: gst::Pipeline,
+    path: PathBuf,
+    started: Instant,
+}
+
+impl VideoRecorder {
+    /// Starts recording into the file at `path`.
+    pub fn start(path: &Path, source: &VideoSource, fps: f64) -> Result<VideoRecorder> {
+        let pipeline = gst::Pipeline::new(None);
+        let src = match source {
+            VideoSource::Screen(region) => screen_source(*region)?,
+            VideoSource::Webcam(device) => webcam_source(device.as_deref())?,
+        };
+        let convert = create_gst_elt("videoconvert", "capture-convert")?;
+        let rate = create_gst_elt("videorate", "capture-rate")?;
+        let filter = create_gst_elt("capsfilter", "capture-filter")?;
+        let queue = create_gst_elt("queue", "capture-queue")?;
+        let encode = create_gst_elt("x264enc", "capture-encode")?;
+        let mux = create_gst_elt("mp4mux", "capture-mux")?;
+        let sink = create_gst_elt("filesink", "capture-sink")?;
+
+        let fps = gst::Fraction::approximate_f64(fps).unwrap_or_else(|| {
+            log::warn!("invalid fps value {}, defaulting to 30.0", fps);
+            gst::Fraction::new(30, 1)
+        });
+        let caps = gst::Caps::builder("video/x-raw")
+            .field("framerate", fps)
+            .build();
+        filter.set_property("caps", &caps);
+        // We're recording live, and we don't want the recording to fall behind.
+        encode.set_property_from_str("tune", "zerolatency");
+        encode.set_property_from_str("speed-preset", "ultrafast");
+        sink.set_property(
+            "location",
+            path.to_str()
+                .ok_or_else(|| anyhow!("this filename is too weird"))?,
+        );
+
+        pipeline.add_many(&[&src, &convert, &rate, &filter, &queue, &encode, &mux, &sink])?;
+        gst::Element::link_many(&[&src, &convert, &rate, &filter, &queue, &encode, &mux, &sink])?;
+        pipeline.set_state(gst::State::Playing)?;
+
+        Ok(VideoRecorder {
+            pipeline,
+            path: path.to_owned(),
+            started: Instant::now(),
+        })
+    }
+
+    /// Stops recording, and waits for the video file to be written.
+    ///
+    /// The returned underlay starts at time `start`; its duration is the time that passed since
+    /// the recording started.
+    pub fn finish(self, start: Time) -> Result<Underlay> {
+        let duration = TimeDiff::from_micros(self.started.elapsed().as_micros() as i64);
+        // The muxer only writes a playable file after it sees the end of the stream.
+        self.pipeline.send_event(gst::event::Eos::new());
+        let bus = self
+            .pipeline
+            .bus()
+            .ok_or_else(|| anyhow!("couldn't get pipeline bus"))?;
+        let mut result = Err(anyhow!("timed out while finishing the recording"));
+        for msg in bus.iter_timed(gst::ClockTime::from_seconds(FINISH_TIMEOUT_SECS)) {
+            use gst::MessageView::*;
+
+            match msg.view() {
+                Eos(..) => {
+                    result = Ok(());
+                    break;
+                }
+                Error(err) => {
+                    result = Err(anyhow!("failed to record video: {}", err.error()));
+                    break;
+                }
+                _ => {}
+            }
+        }
+        self.pipeline.set_state(gst::State::Null)?;
+        result.map(|_| Underlay {
+            path: self.path,
+            start,
+            duration,
+        })
+    }
+}
+
+/// A decoded frame of video.
+#[derive(Clone)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    /// The pixels, in RGBA format, without any padding between the rows.
+    pub data: Arc<[u8]>,
+    /// The time of this frame, relative to the start of the video.
+    pub time: TimeDiff,
+}
+
+impl Frame {
+    fn from_sample(sample: &gst::Sample) -> Result<Frame> {
+        let caps = sample
+            .caps()
+            .ok_or_else(|| anyhow!("decoded frame has no format"))?;
+        let info = VideoInfo::from_caps(caps)?;
+        let buffer = sample
+            .buffer()
+            .ok_or_else(|| anyhow!("decoded frame has no data"))?;
+        let time = buffer
+            .pts()
+            .map(|t| TimeDiff::from_micros(t.useconds() as i64))
+            .unwrap_or(TimeDiff::ZERO);
+        let map = buffer.map_readable()?;
+
+        let row_len = info.width() as usize * 4;
+        let stride = info.stride()[0] as usize;
+        let mut data = Vec::with_capacity(row_len * info.height() as usize);
+        for row in map.as_slice().chunks(stride).take(info.height() as usize) {
+            data.extend_from_slice(&row[..row_len]);
+        }
+        Ok(Frame {
+            width: info.width(),
+            height: info.height(),
+            data: data.into(),
+            time,
+        })
+    }
+
+    /// The largest rectangle with the aspect ratio of this frame that fits in the middle of
+    /// `area`.
+    pub fn fit_rect(&self, area: Rect) -> Rect {
+        fit_rect(Size::new(self.width as f64, self.height as f64), area)
+    }
+
+    /// Draws this frame, scaled to fit in `area`.
+    pub fn render(&self, ctx: &mut impl RenderContext, area: Rect) {
+        let image = ctx.make_image(
+            self.width as usize,
+            self.height as usize,
+            &self.data,
+            ImageFormat::RgbaSeparate,
+        );
+        match image {
+            Ok(image) => ctx.draw_image(&image, self.fit_rect(area), InterpolationMode::Bilinear),
+            Err(e) => log::error!("failed to make underlay image: {}", e),
+        }
+    }
+}
+
+fn fit_rect(size: Size, area: Rect) -> Rect {
+    if size.width <= 0.0 || size.height <= 0.0 {
+        return area;
+    }
+    let scale = (area.width() / size.width).min(area.height() / size.height);
+    Rect::from_center_size(area.center(), size * scale)
+}
+
+/// Decodes frames from an underlay.
+///
+/// This is optimized for asking for frames in order (like when playing or exporting), but it
+/// also supports jumping around.
+pub struct UnderlayReader {
+    underlay: Underlay,
+    pipeline: gst::Pipeline,
+    sink: gst_app::AppSink,
+    /// The most recently decoded frame that was at or before the last requested time.
+    current: Option<Frame>,
+    /// A decoded frame that is after the last requested time.
+    pending: Option<Frame>,
+    /// The video time that we're decoding from.
+    position: TimeDiff,
+}
+
+impl UnderlayReader {
+    pub fn new(underlay: &Underlay) -> Result<UnderlayReader> {
+        let pipeline = gst::Pipeline::new(None);
+        let src = create_gst_elt("filesrc", "underlay-source")?;
+        let decode = create_gst_elt("decodebin", "underlay-decode")?;
+        let convert = create_gst_elt("videoconvert", "underlay-convert")?;
+        let sink = create_gst_elt("appsink", "underlay-sink")?;
+
+        src.set_property(
+            "location",
+            underlay
+                .path
+                .to_str()
+                .ok_or_else(|| anyhow!("this filename is too weird"))?,
+        );
+        pipeline.add_many(&[&src, &decode, &convert, &sink])?;
+        src.link(&decode)?;
+        convert.link(&sink)?;
+
+        // The decoder only creates its output pads once it figures out what's in the file.
+        let convert_weak = convert.downgrade();
+        decode.connect_pad_added(move |_, pad| {
+            let is_video = pad
+                .current_caps()
+                .and_then(|caps| caps.structure(0).map(|s| s.name().starts_with("video/")))
+                .unwrap_or(false);
+            if let (true, Some(convert)) = (is_video, convert_weak.upgrade()) {
+                if let Some(sink_pad) = convert.static_pad("sink") {
+                    if !sink_pad.is_linked() {
+                        if let Err(e) = pad.link(&sink_pad) {
+                            log::error!("failed to link underlay decoder: {:?}", e);
+                        }
+                    }
+                }
+            }
+        });
+
+        let sink = sink
+            .dynamic_cast::<gst_app::AppSink>()
+            .map_err(|_| anyhow!("bug: couldn't cast sink to an AppSink"))?;
+        let caps = gst::Caps::builder("video/x-raw")
+            .field("format", VideoFormat::Rgba.to_str())
+            .build();
+        sink.set_caps(Some(&caps));
+        // We decode on demand, so there's no point in decoding far ahead.
+        sink.set_max_buffers(2);
+        sink.set_sync(false);
+
+        pipeline.set_state(gst::State::Playing)?;
+        Ok(UnderlayReader {
+            underlay: underlay.clone(),
+            pipeline,
+            sink,
+            current: None,
+            pending: None,
+            position: TimeDiff::ZERO,
+        })
+    }
+
+    /// The frame that should be shown at the animation time `time`, or `None` if the underlay
+    /// isn't showing then.
+    pub fn frame_at(&mut self, time: Time) -> Result<Option<Frame>> {
+        let offset = match self.underlay.offset(time) {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+        if offset < self.position || offset > self.position + MAX_SKIP {
+            self.seek(offset)?;
+        }
+
+        loop {
+            if let Some(frame) = self.pending.take() {
+                // Right after seeking, we take the first frame even if it's a little late.
+                if frame.time > offset && self.current.is_some() {
+                    self.pending = Some(frame);
+                    break;
+                }
+                self.position = frame.time.min(offset);
+                self.current = Some(frame);
+            }
+            let timeout = gst::ClockTime::from_seconds(PULL_TIMEOUT_SECS);
+            match self.sink.try_pull_sample(timeout) {
+                Some(sample) => self.pending = Some(Frame::from_sample(&sample)?),
+                // We reached the end of the video (or the decoder is stuck), so keep showing the
+                // last frame.
+                None => break,
+            }
+        }
+        Ok(self.current.clone())
+    }
+
+    fn seek(&mut self, offset: TimeDiff) -> Result<()> {
+        let time = Time::ZERO + offset;
+        self.pipeline.seek_simple(
+            gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+            time.as_gst_clock_time(),
+        )?;
+        self.current = None;
+        self.pending = None;
+        self.position = offset;
+        Ok(())
+    }
+}
+
+impl Drop for UnderlayReader {
+    fn drop(&mut self) {
+        let _ = self.pipeline.set_state(gst::State::Null);
+    }
+}
+
+/// Decodes underlay frames on a background thread.
+///
+/// Every time that the time changes, call [`UnderlayPlayer::seek`]. The callback will be called
+/// (on the background thread) whenever the frame that should be displayed changes. If the frames
+/// are requested faster than they can be decoded, some of the requests are skipped.
+pub struct UnderlayPlayer {
+    tx: Sender<Time>,
+}
+
+impl UnderlayPlayer {
+    pub fn new(
+        underlay: &Underlay,
+        callback: impl Fn(Option<Frame>) + Send + 'static,
+    ) -> UnderlayPlayer {
+        let (tx, rx) = unbounded::<Time>();
+        let underlay = underlay.clone();
+        std::thread::spawn(move || {
+            let mut reader = match UnderlayReader::new(&underlay) {
+                Ok(r) => r,
+                Err(e) => {
+                    log::error!("failed to open underlay {}: {}", underlay.path.display(), e);
+                    return;
+                }
+            };
+            let mut last_frame_time = None;
+            // This ends when the `UnderlayPlayer` gets dropped.
+            while let Ok(time) = rx.recv() {
+                let time = rx.try_iter().last().unwrap_or(time);
+                match reader.frame_at(time) {
+                    Ok(frame) => {
+                        let frame_time = frame.as_ref().map(|f| f.time);
+                        if frame_time != last_frame_time {
+                            last_frame_time = frame_time;
+                            callback(frame);
+                        }
+                    }
+                    Err(e) => log::error!("failed to decode underlay: {}", e),
+                }
+            }
+        });
+        UnderlayPlayer { tx }
+    }
+
+    /// Asks for the frame at the animation time `time`.
+    pub fn seek(&self, time: Time) {
+        let _ = self.tx.send(time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset() {
+        let underlay = Underlay {
+            path: "capture.mp4".into(),
+            start: Time::from_micros(1_000),
+            duration: TimeDiff::from_micros(2_000),
+        };
+        assert_eq!(underlay.offset(Time::from_micros(0)), None);
+        assert_eq!(
+            underlay.offset(Time::from_micros(1_500)),
+            Some(TimeDiff::from_micros(500))
+        );
+        assert_eq!(underlay.offset(underlay.end_time()), None);
+    }
+
+    #[test]
+    fn capture_paths() {
+        let path = capture_path("webcam", Some(Path::new("/talks/intro.scb"))).unwrap();
+        assert_eq!(path.parent(), Some(Path::new("/talks")));
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("intro-webcam-"));
+        assert!(name.ends_with(".mp4"));
+    }
+
+    #[test]
+    fn pip_rect() {
+        let mut pip = Pip {
+            video: Underlay {
+                path: "webcam.mp4".into(),
+                start: Time::ZERO,
+                duration: TimeDiff::from_micros(1_000),
+            },
+            corner: Corner::BottomRight,
+            size: 0.2,
+        };
+        let frame = Size::new(640.0, 480.0);
+        let rect = pip.rect(frame);
+        assert!((rect.width() - 0.2).abs() < 1e-9);
+        assert!((rect.height() - 0.15).abs() < 1e-9);
+        assert!((rect.x1 - 0.98).abs() < 1e-9);
+        assert!((rect.y1 - 0.73).abs() < 1e-9);
+
+        pip.corner = Corner::TopLeft;
+        let rect = pip.rect(frame);
+        assert!((rect.x0 - 0.02).abs() < 1e-9);
+        assert!((rect.y0 - 0.02).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fitting() {
+        let area = Rect::new(0.0, 0.0, 400.0, 300.0);
+        assert_eq!(
+            fit_rect(Size::new(1600.0, 900.0), area),
+            Rect::new(0.0, 37.5, 400.0, 262.5)
+        );
+        assert_eq!(fit_rect(Size::new(40.0, 30.0), area), area);
+        assert_eq!(fit_rect(Size::ZERO, area), area);
+    }
+}
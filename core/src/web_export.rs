@@ -0,0 +1,478 @@
+//! Exporting the drawings of an animation (but not its audio) for the web.
+//!
+//! There are two formats: animated SVG (using SMIL animations, which all the major browsers
+//! support) and [Lottie](https://airbnb.io/lottie/) JSON. In both of them, each stroke gets drawn
+//! on at the same pace that it was drawn in scribl. Then it fades out (if it has a fade effect)
+//! and disappears when its snippet ends or its page is over.
+
+use anyhow::{anyhow, Result};
+use kurbo::{BezPath, ParamCurveArclen, PathEl, Point};
+use piet::Color;
+use serde_json::{json, Value};
+use std::fmt::Write;
+use std::path::Path;
+
+use scribl_curves::{Time, TimeDiff};
+
+use crate::encode::Export;
+use crate::ScriblState;
+
+// The drawing coordinates go from 0.0 to 1.0 horizontally, and from 0.0 to 0.75 vertically.
+const DRAWING_HEIGHT: f64 = 0.75;
+
+// Like the video export, we keep showing the final frame for a little while.
+const END_PADDING: TimeDiff = TimeDiff::from_micros(200000);
+
+// How accurately we measure the lengths of strokes.
+const ARCLEN_ACCURACY: f64 = 1e-6;
+
+/// The formats that we can export to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebFormat {
+    /// An SVG file, animated using SMIL.
+    Svg,
+    /// A Lottie animation, in JSON.
+    Lottie,
+}
+
+impl WebFormat {
+    /// Guesses the format from a file's extension: `.svg` for SVG and `.json` for Lottie.
+    pub fn from_path(path: &Path) -> Option<WebFormat> {
+        match path.extension()?.to_str()? {
+            "svg" => Some(WebFormat::Svg),
+            "json" => Some(WebFormat::Lottie),
+            _ => None,
+        }
+    }
+}
+
+/// A value that changes over time, given as a list of (time, value) pairs with linear
+/// interpolation in between. Before the first time and after the last one, the value stays
+/// constant.
+///
+/// The times are non-decreasing. If two consecutive entries have the same time, the value jumps
+/// at that time.
+type Keyframes = Vec<(Time, f64)>;
+
+/// Everything we need to know for animating one stroke.
+struct AnimatedStroke {
+    path: BezPath,
+    color: Color,
+    thickness: f64,
+    length: f64,
+    /// How much of the stroke has been drawn: 0.0 at the beginning, 1.0 once it's complete.
+    progress: Keyframes,
+    /// The stroke's opacity.
+    opacity: Keyframes,
+}
+
+/// Truncates `keys` at `time`, and then makes the value jump to `value`.
+fn jump_at(keys: &mut Keyframes, time: Time, value: f64) {
+    let before = value_at(keys, time);
+    keys.retain(|&(t, _)| t <= time);
+    keys.push((time, before));
+    keys.push((time, value));
+}
+
+fn value_at(keys: &[(Time, f64)], time: Time) -> f64 {
+    let idx = keys.iter().position(|&(t, _)| t > time);
+    match idx {
+        Some(0) => keys[0].1,
+        Some(i) => {
+            let (t0, v0) = keys[i - 1];
+            let (t1, v1) = keys[i];
+            let ratio = (time - t0).as_micros() as f64 / (t1 - t0).as_micros() as f64;
+            v0 + (v1 - v0) * ratio
+        }
+        None => keys.last().map(|k| k.1).unwrap_or(0.0),
+    }
+}
+
+fn animated_strokes(scribl: &ScriblState) -> Vec<AnimatedStroke> {
+    let mut ret = Vec::new();
+    for (_, snip) in scribl.draw.snippets() {
+        // The snippet disappears either when it ends or when the page that it's on ends.
+        let hide = match (snip.end_time(), scribl.pages.next_start(snip.start_time())) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        for stroke in snip.strokes() {
+            let path = BezPath::from_vec(stroke.elements.to_owned());
+            let mut lengths = vec![0.0];
+            for seg in path.segments() {
+                lengths.push(lengths.last().unwrap() + seg.arclen(ARCLEN_ACCURACY));
+            }
+            let length = *lengths.last().unwrap();
+            let start = stroke.times[0];
+            let end = *stroke.times.last().unwrap();
+
+            let mut progress: Keyframes = stroke
+                .times
+                .iter()
+                .zip(&lengths)
+                .map(|(&t, &len)| {
+                    let frac = if length > 0.0 { len / length } else { 1.0 };
+                    (snip.playback_time(t), frac)
+                })
+                .collect();
+
+            // Reversed strokes are there from the start of the snippet, and they disappear once
+            // they're undrawn. (Fading doesn't make sense when playing backwards.)
+            let shown = if snip.reversed() {
+                snip.start_time()
+            } else {
+                start
+            };
+            let mut opacity = vec![(shown, 0.0), (shown, 1.0)];
+            if snip.reversed() {
+                progress.reverse();
+                jump_at(&mut opacity, snip.playback_time(start), 0.0);
+            } else if let Some(fade) = stroke.style.effects.fade() {
+                opacity.push((end + fade.pause, 1.0));
+                opacity.push((end + fade.pause + fade.fade, 0.0));
+            }
+            if let Some(hide) = hide {
+                jump_at(&mut opacity, hide, 0.0);
+            }
+
+            ret.push(AnimatedStroke {
+                path,
+                color: stroke.style.color.clone(),
+                thickness: stroke.style.thickness,
+                length,
+                progress,
+                opacity,
+            });
+        }
+    }
+    ret
+}
+
+/// The length of the exported animation. Everything happens before this.
+fn duration(scribl: &ScriblState, strokes: &[AnimatedStroke]) -> Time {
+    strokes
+        .iter()
+        .flat_map(|s| s.progress.iter().chain(&s.opacity))
+        .map(|&(t, _)| t)
+        .fold(scribl.end_time() + END_PADDING, Time::max)
+}
+
+fn seconds(t: Time) -> f64 {
+    t.as_micros() as f64 / 1e6
+}
+
+/// Writes a SMIL animation element that animates `attr` according to `keys`.
+fn smil_animate(out: &mut String, attr: &str, keys: &[(Time, f64)], duration: Time) {
+    let dur = duration.as_micros().max(1) as f64;
+    let first = keys.first().map(|k| k.1).unwrap_or(0.0);
+    let last = keys.last().map(|k| k.1).unwrap_or(0.0);
+    // SMIL needs the key times to start at 0 and end at 1.
+    let keys = std::iter::once((0.0, first))
+        .chain(keys.iter().map(|&(t, v)| (t.as_micros() as f64 / dur, v)))
+        .chain(std::iter::once((1.0, last)));
+    let (times, values): (Vec<String>, Vec<String>) = keys
+        .map(|(t, v)| (format!("{:.6}", t), format!("{:.6}", v)))
+        .unzip();
+    let _ = write!(
+        out,
+        r#"<animate attributeName="{}" dur="{:.6}s" fill="freeze" calcMode="linear" keyTimes="{}" values="{}"/>"#,
+        attr,
+        seconds(duration),
+        times.join(";"),
+        values.join(";")
+    );
+}
+
+/// Converts the drawings of an animation into an animated SVG image that is `height` pixels high.
+pub fn to_svg(scribl: &ScriblState, height: u32) -> String {
+    let strokes = animated_strokes(scribl);
+    let duration = duration(scribl, &strokes);
+    let width = (height as f64 / DRAWING_HEIGHT).round() as u32;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 1 {}">"#,
+        width, height, DRAWING_HEIGHT
+    );
+    let _ = writeln!(
+        out,
+        r#"<rect width="1" height="{}" fill="white"/>"#,
+        DRAWING_HEIGHT
+    );
+    for s in &strokes {
+        let (r, g, b, a) = s.color.as_rgba8();
+        // The stroke is drawn on by making it one long dash, and sliding the dash along the path.
+        let dash: Keyframes = s
+            .progress
+            .iter()
+            .map(|&(t, p)| (t, s.length * (1.0 - p)))
+            .collect();
+        let _ = write!(
+            out,
+            r#"<path d="{}" fill="none" stroke="rgb({},{},{})" stroke-opacity="{:.3}" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round" stroke-dasharray="{:.6} {:.6}" stroke-dashoffset="{:.6}" opacity="0">"#,
+            s.path.to_svg(),
+            r,
+            g,
+            b,
+            a as f64 / 255.0,
+            s.thickness,
+            s.length,
+            s.length,
+            s.length,
+        );
+        smil_animate(&mut out, "stroke-dashoffset", &dash, duration);
+        smil_animate(&mut out, "opacity", &s.opacity, duration);
+        let _ = writeln!(out, "</path>");
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+/// Converts keyframes into an animated Lottie property.
+fn lottie_keyframes(keys: &[(Time, f64)], fps: f64, scale: f64) -> Value {
+    let mut prev_frame = f64::NEG_INFINITY;
+    let frames: Vec<Value> = keys
+        .iter()
+        .map(|&(t, v)| {
+            // Lottie doesn't allow two keyframes at the same time, so we make jumps very fast
+            // instead of instantaneous.
+            let frame = (seconds(t) * fps).max(prev_frame + 0.01);
+            prev_frame = frame;
+            json!({
+                "t": frame,
+                "s": [v * scale],
+                "i": { "x": [1.0], "y": [1.0] },
+                "o": { "x": [0.0], "y": [0.0] },
+            })
+        })
+        .collect();
+    json!({ "a": 1, "k": frames })
+}
+
+/// Converts a path into a Lottie shape, scaling it by `scale`.
+fn lottie_path(path: &BezPath, scale: f64) -> Value {
+    let mut vertices = Vec::new();
+    let mut in_tangents = Vec::new();
+    let mut out_tangents: Vec<[f64; 2]> = Vec::new();
+    let pt = |p: Point| [p.x * scale, p.y * scale];
+    let rel = |p: Point, base: Point| [(p.x - base.x) * scale, (p.y - base.y) * scale];
+
+    let mut prev = Point::ZERO;
+    for el in path.elements() {
+        match *el {
+            PathEl::MoveTo(p) => {
+                vertices.push(pt(p));
+                in_tangents.push([0.0, 0.0]);
+                out_tangents.push([0.0, 0.0]);
+                prev = p;
+            }
+            PathEl::CurveTo(c1, c2, p) => {
+                if let Some(last) = out_tangents.last_mut() {
+                    *last = rel(c1, prev);
+                }
+                vertices.push(pt(p));
+                in_tangents.push(rel(c2, p));
+                out_tangents.push([0.0, 0.0]);
+                prev = p;
+            }
+            // Our strokes only contain moves and cubic curves.
+            _ => log::error!("unexpected path element {:?}", el),
+        }
+    }
+    json!({ "c": false, "v": vertices, "i": in_tangents, "o": out_tangents })
+}
+
+/// Converts the drawings of an animation into a Lottie animation that is `height` pixels high
+/// and runs at `fps` frames per second.
+pub fn to_lottie(scribl: &ScriblState, height: u32, fps: f64) -> Value {
+    let strokes = animated_strokes(scribl);
+    let duration = duration(scribl, &strokes);
+    let width = (height as f64 / DRAWING_HEIGHT).round();
+    let end_frame = (seconds(duration) * fps).ceil();
+    // Lottie uses pixel coordinates.
+    let scale = width;
+
+    // In Lottie, the first layer goes on top. In scribl, the last stroke goes on top.
+    let layers: Vec<Value> = strokes
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, s)| {
+            let (r, g, b, a) = s.color.as_rgba();
+            json!({
+                "ddd": 0,
+                "ind": i + 1,
+                "ty": 4,
+                "nm": format!("stroke {}", i + 1),
+                "sr": 1,
+                "ks": {
+                    "o": { "a": 0, "k": 100 },
+                    "r": { "a": 0, "k": 0 },
+                    "p": { "a": 0, "k": [0, 0, 0] },
+                    "a": { "a": 0, "k": [0, 0, 0] },
+                    "s": { "a": 0, "k": [100, 100, 100] },
+                },
+                "ao": 0,
+                "ip": 0,
+                "op": end_frame,
+                "st": 0,
+                "bm": 0,
+                "shapes": [
+                    { "ty": "sh", "ks": { "a": 0, "k": lottie_path(&s.path, scale) } },
+                    {
+                        "ty": "tm",
+                        "s": { "a": 0, "k": 0 },
+                        "e": lottie_keyframes(&s.progress, fps, 100.0),
+                        "o": { "a": 0, "k": 0 },
+                        "m": 1,
+                    },
+                    {
+                        "ty": "st",
+                        "c": { "a": 0, "k": [r, g, b, 1.0] },
+                        "o": lottie_keyframes(&s.opacity, fps, 100.0 * a),
+                        "w": { "a": 0, "k": s.thickness * scale },
+                        "lc": 2,
+                        "lj": 2,
+                    },
+                ],
+            })
+        })
+        .collect();
+
+    json!({
+        "v": "5.7.4",
+        "fr": fps,
+        "ip": 0,
+        "op": end_frame,
+        "w": width,
+        "h": height,
+        "nm": "scribl",
+        "ddd": 0,
+        "assets": [],
+        "layers": layers,
+    })
+}
+
+/// Exports the drawings of an animation to `path`, choosing the format based on its extension.
+///
+/// The size comes from `config`. For Lottie, so does the frame rate, unless the animation has its
+/// own.
+pub fn export(scribl: &ScriblState, path: &Path, config: &Export) -> Result<()> {
+    let fps = scribl.fps.unwrap_or(config.fps);
+    let contents = match WebFormat::from_path(path) {
+        Some(WebFormat::Svg) => to_svg(scribl, config.height),
+        Some(WebFormat::Lottie) => to_lottie(scribl, config.height, fps).to_string(),
+        None => {
+            return Err(anyhow!(
+                "don't know how to export to {} (expected .svg or .json)",
+                path.display()
+            ))
+        }
+    };
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scribl_curves::StrokeStyle;
+    use scribl_curves::{
+        Brush, DrawSnippet, Effect, Effects, FadeEffect, StrokeInProgress, StrokeSeq,
+    };
+
+    fn t(ms: i64) -> Time {
+        Time::from_micros(ms * 1000)
+    }
+
+    fn scribl(effects: Effects) -> ScriblState {
+        let mut stroke = StrokeInProgress::new();
+        stroke.add_point(Point::new(0.0, 0.0), t(0));
+        stroke.add_point(Point::new(0.5, 0.5), t(100));
+        stroke.add_point(Point::new(1.0, 0.0), t(200));
+        let style = StrokeStyle {
+            color: Color::BLACK,
+            thickness: 0.01,
+            effects,
+            brush: Brush::Round,
+        };
+        let mut seq = StrokeSeq::new();
+        seq.append_stroke(stroke, style, false, 0.0001);
+        let mut ret = ScriblState::default();
+        ret.add_draw_snippet(DrawSnippet::new(seq));
+        ret
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(
+            WebFormat::from_path(Path::new("a/b.svg")),
+            Some(WebFormat::Svg)
+        );
+        assert_eq!(
+            WebFormat::from_path(Path::new("b.json")),
+            Some(WebFormat::Lottie)
+        );
+        assert_eq!(WebFormat::from_path(Path::new("b.mp4")), None);
+    }
+
+    #[test]
+    fn keyframes() {
+        let mut keys = vec![(t(0), 0.0), (t(100), 1.0), (t(200), 0.0)];
+        assert_eq!(value_at(&keys, t(50)), 0.5);
+        jump_at(&mut keys, t(150), 0.0);
+        assert_eq!(
+            keys,
+            vec![(t(0), 0.0), (t(100), 1.0), (t(150), 0.5), (t(150), 0.0)]
+        );
+    }
+
+    #[test]
+    fn hiding() {
+        let mut effects = Effects::default();
+        effects.add(Effect::Fade(FadeEffect {
+            pause: TimeDiff::from_micros(100000),
+            fade: TimeDiff::from_micros(100000),
+        }));
+        let scribl = scribl(effects);
+        let strokes = animated_strokes(&scribl);
+        assert_eq!(strokes.len(), 1);
+        let s = &strokes[0];
+        assert_eq!(s.progress.first(), Some(&(t(0), 0.0)));
+        assert_eq!(s.progress.last(), Some(&(t(200), 1.0)));
+        assert_eq!(value_at(&s.opacity, t(100)), 1.0);
+        // Fading makes the snippet end, and then the stroke is hidden.
+        let (_, snip) = scribl.draw.snippets().next().unwrap();
+        let end = snip.end_time().unwrap();
+        assert_eq!(s.opacity.last(), Some(&(end, 0.0)));
+    }
+
+    #[test]
+    fn reversed() {
+        let mut scribl = scribl(Effects::default());
+        let (id, snip) = scribl.draw.snippets().next().unwrap();
+        let snip = snip.with_reversed(true);
+        scribl.draw = scribl.draw.with_replacement_snippet(id, snip);
+        let strokes = animated_strokes(&scribl);
+        let s = &strokes[0];
+        assert_eq!(s.progress.first(), Some(&(t(0), 1.0)));
+        assert_eq!(s.progress.last(), Some(&(t(200), 0.0)));
+        assert_eq!(value_at(&s.opacity, t(0)), 1.0);
+        assert_eq!(s.opacity.last(), Some(&(t(200), 0.0)));
+    }
+
+    #[test]
+    fn outputs() {
+        let scribl = scribl(Effects::default());
+        let svg = to_svg(&scribl, 300);
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<path").count(), 1);
+        assert_eq!(svg.matches("<animate").count(), 2);
+
+        let lottie = to_lottie(&scribl, 300, 30.0);
+        assert_eq!(lottie["w"], 400.0);
+        assert_eq!(lottie["layers"].as_array().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,211 @@
+//! Brushes, which determine the texture of a stroke.
+//!
+//! All of the brushes are drawn with ordinary piet operations, and any randomness in them is
+//! determined by the stroke itself. That way, a stroke looks exactly the same every time that it's
+//! drawn, whether that's on the screen or in an exported video.
+
+use kurbo::{BezPath, Circle, PathEl, Point, Shape, Vec2};
+use piet::{LineCap, LineJoin};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+use crate::render::{StrokePaint, VisibleStroke};
+
+// How accurately (relative to the thickness of the stroke) we flatten paths before stamping them.
+const FLATTEN_TOLERANCE: f64 = 0.1;
+
+// The distance between chalk stamps, relative to the thickness of the stroke.
+const CHALK_SPACING: f64 = 0.35;
+// How many grains each chalk stamp makes.
+const CHALK_GRAINS: u64 = 3;
+// The radius of a grain of chalk, relative to the thickness of the stroke.
+const CHALK_GRAIN_RADIUS: f64 = 0.25;
+// Chalk is a little bit see-through.
+const CHALK_OPACITY: f64 = 0.85;
+
+// Markers are see-through, so you can see where strokes cross.
+const MARKER_OPACITY: f64 = 0.7;
+
+/// The kind of pen that draws a stroke.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+#[serde(rename_all = "snake_case")]
+pub enum Brush {
+    /// A smooth, solid line with round ends. This is the original (and default) brush.
+    Round,
+    /// A grainy line, like chalk on a blackboard.
+    Chalk,
+    /// A flat-tipped, slightly see-through line, like a felt-tip marker.
+    Marker,
+}
+
+impl Default for Brush {
+    fn default() -> Brush {
+        Brush::Round
+    }
+}
+
+impl Brush {
+    /// All the brushes, in the order that they should be shown to people.
+    pub const ALL: [Brush; 3] = [Brush::Round, Brush::Chalk, Brush::Marker];
+
+    pub fn is_round(&self) -> bool {
+        *self == Brush::Round
+    }
+
+    /// The name of this brush, for showing to people.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Brush::Round => "Round",
+            Brush::Chalk => "Chalk",
+            Brush::Marker => "Marker",
+        }
+    }
+
+    pub(crate) fn paint<'a>(&self, stroke: &'a VisibleStroke) -> StrokePaint<'a> {
+        match self {
+            Brush::Round => StrokePaint::Stroke {
+                path: Cow::Borrowed(&stroke.path),
+                color: stroke.paint_color(),
+                width: stroke.thickness,
+                style: crate::render::stroke_style(),
+            },
+            Brush::Chalk => StrokePaint::Fill {
+                path: Cow::Owned(chalk_path(stroke)),
+                color: stroke
+                    .color
+                    .clone()
+                    .with_alpha(stroke.alpha * CHALK_OPACITY),
+            },
+            Brush::Marker => StrokePaint::Stroke {
+                path: Cow::Borrowed(&stroke.path),
+                color: stroke
+                    .color
+                    .clone()
+                    .with_alpha(stroke.alpha * MARKER_OPACITY),
+                width: stroke.thickness,
+                style: piet::StrokeStyle {
+                    line_join: LineJoin::Bevel,
+                    line_cap: LineCap::Butt,
+                    ..piet::StrokeStyle::new()
+                },
+            },
+        }
+    }
+}
+
+/// Returns the grains of chalk that make up a stroke, as a single path.
+///
+/// We walk along the stroke, and every little while we put down a few grains at random positions
+/// near the stroke. The randomness only depends on the position of the stamp along the stroke, so
+/// that the part of the stroke that's already been drawn doesn't change as the stroke gets longer.
+fn chalk_path(stroke: &VisibleStroke) -> BezPath {
+    let mut ret = BezPath::new();
+    let spacing = (stroke.thickness * CHALK_SPACING).max(1e-6);
+    let grain_radius = stroke.thickness * CHALK_GRAIN_RADIUS;
+    // The grains should stay inside the stroke.
+    let scatter = (stroke.thickness / 2.0 - grain_radius).max(0.0);
+    let tolerance = stroke.thickness * FLATTEN_TOLERANCE;
+
+    let mut stamp_idx: u64 = 0;
+    // How far along the current segment we put the next stamp.
+    let mut next_stamp = 0.0;
+    let mut prev: Option<Point> = None;
+    let mut stamp = |p: Point, ret: &mut BezPath| {
+        for grain in 0..CHALK_GRAINS {
+            let offset = scatter * unit_disk(hash(stamp_idx, grain));
+            let circle = Circle::new(p + offset, grain_radius);
+            ret.extend(circle.path_elements(tolerance));
+        }
+        stamp_idx += 1;
+    };
+
+    kurbo::flatten(&stroke.path, tolerance, |el| match el {
+        PathEl::MoveTo(p) => {
+            stamp(p, &mut ret);
+            next_stamp = spacing;
+            prev = Some(p);
+        }
+        PathEl::LineTo(p) => {
+            if let Some(q) = prev {
+                let len = (p - q).hypot();
+                if len > 0.0 {
+                    let dir = (p - q) / len;
+                    while next_stamp <= len {
+                        stamp(q + dir * next_stamp, &mut ret);
+                        next_stamp += spacing;
+                    }
+                    next_stamp -= len;
+                }
+            }
+            prev = Some(p);
+        }
+        _ => {}
+    });
+    ret
+}
+
+/// A pseudo-random point in the unit disk, depending only on `h`.
+fn unit_disk(h: u64) -> Vec2 {
+    let angle = (h & 0xffff_ffff) as f64 / u32::MAX as f64 * std::f64::consts::TAU;
+    // Taking the square root makes the points uniformly distributed over the disk.
+    let radius = ((h >> 32) as f64 / u32::MAX as f64).sqrt();
+    Vec2::from_angle(angle) * radius
+}
+
+// The "splitmix64" finalizer, applied to a combination of the two inputs.
+fn hash(a: u64, b: u64) -> u64 {
+    let mut z = a
+        .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        .wrapping_add(b.wrapping_mul(0xbf58_476d_1ce4_e5b9));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use piet::Color;
+
+    fn stroke(len: f64) -> VisibleStroke {
+        let mut path = BezPath::new();
+        path.move_to((0.0, 0.0));
+        path.line_to((len, 0.0));
+        VisibleStroke {
+            path,
+            color: Color::BLACK,
+            alpha: 1.0,
+            thickness: 0.01,
+            brush: Brush::Chalk,
+        }
+    }
+
+    #[test]
+    fn chalk() {
+        let short = chalk_path(&stroke(0.1));
+        let long = chalk_path(&stroke(0.2));
+
+        // Chalk stays inside the stroke.
+        let bbox = long.bounding_box();
+        // (The circles are approximated by curves, so they might be a tiny bit too big.)
+        assert!(bbox.y0 >= -0.005 - 1e-5 && bbox.y1 <= 0.005 + 1e-5);
+        assert!(bbox.x0 >= -0.005 - 1e-5 && bbox.x1 <= 0.205 + 1e-5);
+
+        // Making a stroke longer doesn't change the part that was already there.
+        let short_els = short.elements();
+        assert_eq!(short_els, &long.elements()[..short_els.len()]);
+
+        // Drawing the same stroke twice gives the same result.
+        assert_eq!(long.elements(), chalk_path(&stroke(0.2)).elements());
+    }
+
+    #[test]
+    fn serde() {
+        assert_eq!(serde_json::to_string(&Brush::Chalk).unwrap(), "\"chalk\"");
+        assert_eq!(
+            serde_json::from_str::<Brush>("\"marker\"").unwrap(),
+            Brush::Marker
+        );
+    }
+}
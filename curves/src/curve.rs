@@ -1,13 +1,14 @@
-use druid::im::Vector;
-use druid::kurbo::{BezPath, ParamCurve, PathEl, PathSeg, Point, Shape};
-use druid::piet::{self, LineCap, LineJoin};
-use druid::{Color, Data, Rect, RenderContext};
+use im::Vector;
+use kurbo::{BezPath, ParamCurve, PathEl, PathSeg, Point, Rect, Shape};
+use piet::{Color, RenderContext};
 use serde::ser::SerializeSeq;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cell::RefCell;
 use std::sync::Arc;
 
+use crate::brush::Brush;
 use crate::effect::Effects;
+use crate::render::VisibleStroke;
 use crate::time::Time;
 
 mod serde_color {
@@ -25,12 +26,13 @@ mod serde_color {
 /// While drawing, this stores one continuous poly-line (from pen-down to
 /// pen-up). Because we expect lots of fast changes to this, it uses interior
 /// mutability to avoid repeated allocations.
-#[derive(Clone, Data, Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
 pub struct StrokeInProgress {
-    #[data(ignore)]
+    #[cfg_attr(feature = "druid", data(ignore))]
     pub(crate) points: Arc<RefCell<Vec<Point>>>,
 
-    #[data(ignore)]
+    #[cfg_attr(feature = "druid", data(ignore))]
     pub(crate) times: Arc<RefCell<Vec<Time>>>,
 
     // Data comparison is done using the number of points, which grows with every modification.
@@ -75,30 +77,38 @@ impl StrokeInProgress {
         self.times.borrow().first().copied()
     }
 
-    /// Renders the part of this stroke that is visible at the time `time`.
-    pub fn render(&self, ctx: &mut impl RenderContext, style: StrokeStyle, time: Time) {
-        let stroke_style = piet::StrokeStyle {
-            line_join: LineJoin::Round,
-            line_cap: LineCap::Round,
-            ..piet::StrokeStyle::new()
-        };
-
+    /// Returns this stroke as it should be drawn at time `time`, or `None` if it's empty.
+    ///
+    /// Unlike a `StrokeInProgress`, the returned stroke can be sent to other threads.
+    pub fn visible_stroke(&self, style: StrokeStyle, time: Time) -> Option<VisibleStroke> {
         let ps = self.points.borrow();
-        if ps.is_empty() {
-            return;
-        }
+        let (first, rest) = ps.split_first()?;
         let mut path = BezPath::new();
-        path.move_to(ps[0]);
-        for p in &ps[1..] {
+        path.move_to(*first);
+        for p in rest {
             path.line_to(*p);
         }
         let last = *self.times.borrow().last().unwrap();
-        let color = if let Some(fade) = style.effects.fade() {
-            style.color.with_alpha(fade.opacity_at_time(time - last))
-        } else {
-            style.color
+        let alpha = style
+            .effects
+            .fade()
+            .map_or(1.0, |fade| fade.opacity_at_time(time - last));
+        let mut stroke = VisibleStroke {
+            path,
+            color: style.color,
+            alpha,
+            thickness: style.thickness,
+            brush: style.brush,
         };
-        ctx.stroke_styled(&path, &color, style.thickness, &stroke_style);
+        style.effects.apply(&mut stroke, time);
+        Some(stroke)
+    }
+
+    /// Renders the part of this stroke that is visible at the time `time`.
+    pub fn render(&self, ctx: &mut impl RenderContext, style: StrokeStyle, time: Time) {
+        if let Some(stroke) = self.visible_stroke(style, time) {
+            stroke.render(ctx);
+        }
     }
 
     fn to_path(&self, shape_detect: bool, distance_threshold: f64) -> Option<(BezPath, Vec<Time>)> {
@@ -140,6 +150,9 @@ pub struct StrokeStyle {
     pub color: Color,
     pub thickness: f64,
     pub effects: Effects,
+    // This was added after the original file format, so old files don't have it.
+    #[serde(default, skip_serializing_if = "Brush::is_round")]
+    pub brush: Brush,
 }
 
 // piet::Color doesn't implement PartialEq, so we can't derive this.
@@ -148,13 +161,15 @@ impl PartialEq for StrokeStyle {
         self.thickness == other.thickness
             && self.color.as_rgba_u32() == other.color.as_rgba_u32()
             && self.effects == other.effects
+            && self.brush == other.brush
     }
 }
 
 /// A `StrokeSeq` is a sequence of strokes, each of which is a continuous curve. Each stroke can
 /// have its own style (thickness, color, effects). The strokes in a `StrokeSeq` are non-decreasing
 /// in time: one stroke ends before another begins.
-#[derive(Clone, Data, Debug, Default)]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
 pub struct StrokeSeq {
     strokes: Vector<Arc<Stroke>>,
 }
@@ -163,7 +178,7 @@ pub struct StrokeSeq {
 /// segment is annotated with the time at which it was drawn.
 #[derive(Clone, Debug)]
 pub struct Stroke {
-    path: BezPath,
+    pub(crate) path: BezPath,
     pub(crate) times: Vec<Time>,
     style: StrokeStyle,
 }
@@ -220,6 +235,12 @@ impl StrokeSeq {
         self.strokes.iter().map(|x| x.as_ref())
     }
 
+    /// Splits this sequence in two: the first `idx` strokes, and all the rest.
+    pub(crate) fn split_at(&self, idx: usize) -> (StrokeSeq, StrokeSeq) {
+        let (first, second) = self.strokes.clone().split_at(idx);
+        (StrokeSeq { strokes: first }, StrokeSeq { strokes: second })
+    }
+
     pub(crate) fn append_path(&mut self, path: BezPath, times: Vec<Time>, style: StrokeStyle) {
         self.strokes
             .push_back(Arc::new(Stroke { path, times, style }));
@@ -264,79 +285,87 @@ impl StrokeSeq {
         })
     }
 
+    /// Returns the strokes (and parts of strokes) that are visible at time `time`, in the order
+    /// that they were drawn.
+    ///
+    /// Strokes that have completely faded out are skipped.
+    pub fn visible_strokes<'a>(&'a self, time: Time) -> impl Iterator<Item = VisibleStroke> + 'a {
+        // The strokes are in order, so everything after the first stroke that hasn't started yet
+        // is also invisible.
+        self.strokes()
+            .map_while(move |stroke| {
+                let (path, alpha) = visible_part(&stroke, time)?;
+                let mut visible = VisibleStroke {
+                    path,
+                    color: stroke.style.color.clone(),
+                    alpha: alpha.unwrap_or(1.0),
+                    thickness: stroke.style.thickness,
+                    brush: stroke.style.brush,
+                };
+                stroke.style.effects.apply(&mut visible, time);
+                Some(visible)
+            })
+            .filter(|s| s.alpha > 0.0)
+    }
+
     /// Renders the part of this stroke sequence that is visible at time `time`.
     pub fn render(&self, ctx: &mut impl RenderContext, time: Time) {
-        let stroke_style = piet::StrokeStyle {
-            line_join: LineJoin::Round,
-            line_cap: LineCap::Round,
-            ..piet::StrokeStyle::new()
+        for stroke in self.visible_strokes(time) {
+            stroke.render(ctx);
+        }
+    }
+}
+
+/// Returns the part of `stroke` that is visible at time `time`.
+///
+/// If the stroke was finished by `time`, the second return value is its opacity. If it's still
+/// being drawn, the path only goes up until `time` and the second return value is `None`. If it
+/// hasn't started yet, returns `None`.
+fn visible_part(stroke: &StrokeRef, time: Time) -> Option<(BezPath, Option<f64>)> {
+    let last = *stroke.times.last()?;
+    if last <= time {
+        let alpha = if let Some(fade) = stroke.style.effects.fade() {
+            fade.opacity_at_time(time - last)
+        } else {
+            1.0
         };
+        return Some((BezPath::from_vec(stroke.elements.to_owned()), Some(alpha)));
+    }
+
+    // For the last stroke, we construct a new stroke whose end time is interpolated up until
+    // the current time.
+    // Note: we're doing some unnecessary cloning, just for the convenience of being able to use
+    // BezPath::get_seg.
+    let c = BezPath::from_vec(stroke.elements.to_owned());
+    let t_idx = stroke.times.binary_search(&time).unwrap_or_else(|i| i);
+
+    if t_idx == 0 {
+        // If we only contain the first element, it's a MoveTo and doesn't need to be drawn
+        // anyway.
+        return None;
+    }
+
+    // We already checked that time > stroke.times.last().
+    assert!(t_idx < stroke.times.len());
+    assert_eq!(stroke.times.len(), stroke.elements.len());
+    let last_stroke = c.get_seg(t_idx).unwrap();
+    // The indexing is ok, because we already checked t_idx > 0.
+    let prev_t = stroke.times[t_idx - 1].as_micros() as f64;
+    let next_t = stroke.times[t_idx].as_micros() as f64;
+    let t_ratio = if prev_t == next_t {
+        1.0
+    } else {
+        (time.as_micros() as f64 - prev_t) / (next_t - prev_t)
+    };
+    let last_stroke = last_stroke.subsegment(0.0..t_ratio);
 
-        for stroke in self.strokes() {
-            if let Some(last) = stroke.times.last() {
-                if *last <= time {
-                    let color = if let Some(fade) = stroke.style.effects.fade() {
-                        stroke
-                            .style
-                            .color
-                            .with_alpha(fade.opacity_at_time(time - *last))
-                    } else {
-                        stroke.style.color
-                    };
-                    ctx.stroke_styled(
-                        &stroke.elements,
-                        &color,
-                        stroke.style.thickness,
-                        &stroke_style,
-                    );
-                } else {
-                    // For the last stroke, we construct a new stroke whose end time is
-                    // interpolated up until the current time.
-                    // Note: we're doing some unnecessary cloning, just for the convenience of
-                    // being able to use BezPath::get_seg.
-                    let c = BezPath::from_vec(stroke.elements.to_owned());
-                    let t_idx = stroke.times.binary_search(&time).unwrap_or_else(|i| i);
-
-                    if t_idx == 0 {
-                        // If we only contain the first element, it's a MoveTo and
-                        // doesn't need to be drawn anyway.
-                        break;
-                    }
-
-                    // We already checked that time > stroke.times.last().
-                    assert!(t_idx < stroke.times.len());
-                    assert_eq!(stroke.times.len(), stroke.elements.len());
-                    let last_stroke = c.get_seg(t_idx).unwrap();
-                    // The indexing is ok, because we already checked t_idx > 0.
-                    let prev_t = stroke.times[t_idx - 1].as_micros() as f64;
-                    let next_t = stroke.times[t_idx].as_micros() as f64;
-                    let t_ratio = if prev_t == next_t {
-                        1.0
-                    } else {
-                        (time.as_micros() as f64 - prev_t) / (next_t - prev_t)
-                    };
-                    let last_stroke = last_stroke.subsegment(0.0..t_ratio);
-
-                    let mut c: BezPath = c.iter().take(t_idx).collect();
-                    match last_stroke {
-                        PathSeg::Cubic(x) => c.curve_to(x.p1, x.p2, x.p3),
-                        PathSeg::Quad(x) => c.quad_to(x.p1, x.p2),
-                        PathSeg::Line(x) => c.line_to(x.p1),
-                    }
-
-                    ctx.stroke_styled(
-                        &c,
-                        &stroke.style.color,
-                        stroke.style.thickness,
-                        &stroke_style,
-                    );
-
-                    // We've already rendered the stroke spanning the ending time, so we're done.
-                    break;
-                }
-            }
-        }
+    let mut c: BezPath = c.iter().take(t_idx).collect();
+    match last_stroke {
+        PathSeg::Cubic(x) => c.curve_to(x.p1, x.p2, x.p3),
+        PathSeg::Quad(x) => c.quad_to(x.p1, x.p2),
+        PathSeg::Line(x) => c.line_to(x.p1),
     }
+    Some((c, None))
 }
 
 // A curve gets serialized as a sequence of strokes.
@@ -418,9 +447,12 @@ pub struct StrokeRef<'a> {
 impl<'a> StrokeRef<'a> {
     /// Returns a bounding box of the entire stroke.
     pub fn bbox(&self) -> Rect {
-        self.elements
-            .bounding_box()
-            .inset(self.style.thickness / 2.0)
+        self.elements.bounding_box().inset(self.padding())
+    }
+
+    // How far the drawn stroke can extend beyond its path.
+    fn padding(&self) -> f64 {
+        self.style.thickness / 2.0 + self.style.effects.padding()
     }
 
     /// Returns a bounding box of everything that is drawn in the interval
@@ -461,7 +493,11 @@ impl<'a> StrokeRef<'a> {
             }
         };
 
-        let active_elts = if let Some(fade) = self.style.effects.fade() {
+        let started = self.times.first().map_or(false, |&t| t < end_time);
+        let active_elts = if started && self.style.effects.is_animated() {
+            // Animated effects change the whole stroke all the time.
+            &self.elements[..]
+        } else if let Some(fade) = self.style.effects.fade() {
             // If a fade is active between start_time and end_time, the whole stroke needs to be
             // repainted.
             let fade_start = *self.times.last().unwrap_or(&Time::ZERO) + fade.pause;
@@ -477,7 +513,7 @@ impl<'a> StrokeRef<'a> {
 
         let bbox = active_elts.bounding_box();
         if !active_elts.is_empty() {
-            bbox.inset(self.style.thickness / 2.0)
+            bbox.inset(self.padding())
         } else {
             Rect::ZERO
         }
@@ -541,6 +577,7 @@ pub mod tests {
             color: Color::WHITE,
             thickness: 1.0,
             effects: Effects::default(),
+            brush: Brush::Round,
         };
         let mut s = StrokeInProgress::new();
         s.add_point(p(0.0, 0.0), t(1));
@@ -1,47 +1,73 @@
-use druid::im::OrdMap;
-use druid::kurbo::Shape;
-use druid::{Data, Rect, RenderContext};
+use im::OrdMap;
+use kurbo::{Point, Rect};
+use piet::RenderContext;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashSet;
+use std::mem::size_of;
 use std::sync::Arc;
 
-use crate::{span_cursor, Lerp, StrokeSeq, Time, TimeDiff};
+use crate::lerp::IntervalEasing;
+use crate::render::VisibleStroke;
+use crate::{span_cursor, GroupId, LabelColor, Lerp, StrokeSeq, Time, TimeDiff};
 
 /// Snippets are identified by unique ids.
-#[derive(
-    Deserialize, Serialize, Clone, Copy, Data, Debug, Eq, Hash, Ord, PartialEq, PartialOrd,
-)]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
 #[serde(transparent)]
 pub struct DrawSnippetId(pub(crate) u64);
 
 /// A snippet is a sequence of strokes, possibly modified by a time distortion.
 ///
-/// This struct implements [`druid::Data`]. In particular, it is cheap to clone: most of the actual
-/// data lives behind shared references.
+/// This struct is cheap to clone: most of the actual data lives behind shared references. With
+/// the `druid` feature, it implements [`druid::Data`].
 ///
 /// [`Lerp`]: struct.Lerp.html
 /// [`druid::Data`]: ../druid/trait.Data.html
-#[derive(Data, Debug, Clone)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
 pub struct DrawSnippet {
     pub(crate) strokes: Arc<StrokeSeq>,
     /// The time-distortion applied to the strokes.
     pub(crate) lerp: Arc<Lerp>,
     /// The times of the strokes, with distortion applied.
-    #[data(ignore)]
+    #[cfg_attr(feature = "druid", data(ignore))]
     times: Arc<Vec<Vec<Time>>>,
 
     /// Controls whether the snippet ever ends. If `None`, it means that the snippet will remain
     /// forever; if `Some(t)` it means that the snippet will disappear at time `t`.
     pub(crate) end: Option<Time>,
+
+    /// A name that they gave to this snippet, if any.
+    pub(crate) name: Option<String>,
+
+    /// The color that this snippet is shown with in the timeline. If `None`, the timeline shows
+    /// the colors of the strokes instead.
+    pub(crate) label: Option<LabelColor>,
+
+    /// The group of linked snippets that this snippet belongs to, if any.
+    pub(crate) group: Option<GroupId>,
+
+    /// If true, this snippet plays backwards: it starts out completely drawn, and then gets
+    /// undrawn.
+    pub(crate) reversed: bool,
+
+    /// If true, this snippet stays in the timeline but never gets drawn.
+    pub(crate) hidden: bool,
+
+    /// Snippets with a higher layer are drawn on top of snippets with a lower one. Snippets in
+    /// the same layer are drawn in the order that they were added.
+    pub(crate) layer: i32,
 }
 
 /// A collection of `DrawSnippet`s, which can be accessed using their [id].
 ///
-/// This struct implements [`druid::Data`]. In particular, it is cheap to clone: most of the actual
-/// data lives behind shared references.
+/// This struct is cheap to clone: most of the actual data lives behind shared references. With
+/// the `druid` feature, it implements [`druid::Data`].
 ///
 /// [id]: struct.DrawSnippetId.html
 /// [`druid::Data`]: ../druid/trait.Data.html
-#[derive(Clone, Data, Default)]
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
 pub struct DrawSnippets {
     pub(crate) last_id: u64,
     pub(crate) snippets: OrdMap<DrawSnippetId, DrawSnippet>,
@@ -71,6 +97,12 @@ impl DrawSnippet {
             lerp: Arc::new(lerp),
             times: Arc::new(times),
             end,
+            name: None,
+            label: None,
+            group: None,
+            reversed: false,
+            hidden: false,
+            layer: 0,
         }
     }
 
@@ -81,6 +113,12 @@ impl DrawSnippet {
             lerp: Arc::new(lerp),
             times: Arc::new(times),
             end,
+            name: None,
+            label: None,
+            group: None,
+            reversed: false,
+            hidden: false,
+            layer: 0,
         }
     }
 
@@ -95,14 +133,65 @@ impl DrawSnippet {
     }
 
     pub fn with_new_lerp(&self, lerp_from: Time, lerp_to: Time) -> DrawSnippet {
-        let mut lerp = (*self.lerp).clone();
-        lerp.add_lerp(lerp_from, lerp_to);
+        self.with_lerp(self.lerp.with_new_lerp(lerp_from, lerp_to))
+    }
+
+    /// The time-distortion that is applied to the strokes.
+    pub fn lerp(&self) -> &Lerp {
+        &self.lerp
+    }
+
+    /// Returns a copy of this snippet with a different time-distortion.
+    pub fn with_lerp(&self, lerp: Lerp) -> DrawSnippet {
         let times = lerp_times(&self.strokes, &lerp);
         DrawSnippet {
             strokes: Arc::clone(&self.strokes),
             lerp: Arc::new(lerp),
             times: Arc::new(times),
             end: self.end,
+            name: self.name.clone(),
+            label: self.label,
+            group: self.group,
+            reversed: self.reversed,
+            hidden: self.hidden,
+            layer: self.layer,
+        }
+    }
+
+    /// The times during which this snippet holds still, as (start, end) pairs.
+    pub fn holds<'a>(&'a self) -> impl Iterator<Item = (Time, Time)> + 'a {
+        self.lerp.holds()
+    }
+
+    /// Returns a copy of this snippet that holds still from `start` to `end`. Everything that
+    /// this snippet used to draw after `start` gets delayed (and so does its end time).
+    pub fn with_hold(&self, start: Time, end: Time) -> DrawSnippet {
+        let len = end - start;
+        let mut ret = self.with_lerp(self.lerp.with_hold(start, len));
+        if ret.lerp.last() != self.lerp.last() {
+            ret.end = self.end.map(|e| if e > start { e + len } else { e });
+        }
+        ret
+    }
+
+    /// Returns a copy of this snippet without the hold that contains `time`. Everything after the
+    /// hold gets moved earlier.
+    pub fn without_hold(&self, time: Time) -> DrawSnippet {
+        let hold = self
+            .holds()
+            .find(|&(start, end)| start <= time && time <= end);
+        if let Some((start, end)) = hold {
+            let mut ret = self.with_lerp(self.lerp.without_hold(time));
+            ret.end = self.end.map(|e| {
+                if e > start {
+                    (e - (end - start)).max(start)
+                } else {
+                    e
+                }
+            });
+            ret
+        } else {
+            self.clone()
         }
     }
 
@@ -127,9 +216,85 @@ impl DrawSnippet {
             lerp: Arc::new(lerp),
             times: Arc::new(times),
             end: self.end.map(|x| x + shift),
+            name: self.name.clone(),
+            label: self.label,
+            group: self.group,
+            reversed: self.reversed,
+            hidden: self.hidden,
+            layer: self.layer,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn with_name(&self, name: Option<String>) -> DrawSnippet {
+        DrawSnippet {
+            name,
+            ..self.clone()
+        }
+    }
+
+    pub fn label(&self) -> Option<LabelColor> {
+        self.label
+    }
+
+    /// Is this snippet an unmodified copy of `other`? Copies share their strokes, so this is
+    /// cheap.
+    pub fn is_copy_of(&self, other: &DrawSnippet) -> bool {
+        Arc::ptr_eq(&self.strokes, &other.strokes)
+            && Arc::ptr_eq(&self.lerp, &other.lerp)
+            && self.end == other.end
+            && self.name == other.name
+            && self.label == other.label
+            && self.group == other.group
+            && self.reversed == other.reversed
+            && self.hidden == other.hidden
+            && self.layer == other.layer
+    }
+
+    pub fn with_label(&self, label: Option<LabelColor>) -> DrawSnippet {
+        DrawSnippet {
+            label,
+            ..self.clone()
         }
     }
 
+    pub fn group(&self) -> Option<GroupId> {
+        self.group
+    }
+
+    pub fn with_group(&self, group: Option<GroupId>) -> DrawSnippet {
+        DrawSnippet {
+            group,
+            ..self.clone()
+        }
+    }
+
+    /// Splits this snippet in two, with the strokes that start before `time` in the first part
+    /// and the rest in the second part. Both parts keep this snippet's time distortion and end
+    /// time.
+    ///
+    /// Returns `None` if one of the parts would be empty.
+    pub fn split(&self, time: Time) -> Option<(DrawSnippet, DrawSnippet)> {
+        let idx = self.times.iter().position(|t| t[0] >= time)?;
+        if idx == 0 {
+            return None;
+        }
+        let (first, second) = self.strokes.split_at(idx);
+        let part = |strokes: StrokeSeq| DrawSnippet {
+            name: self.name.clone(),
+            label: self.label,
+            group: self.group,
+            reversed: self.reversed,
+            hidden: self.hidden,
+            layer: self.layer,
+            ..DrawSnippet::new_complete(strokes, (*self.lerp).clone(), self.end)
+        };
+        Some((part(first), part(second)))
+    }
+
     pub fn start_time(&self) -> Time {
         self.times[0][0]
     }
@@ -139,12 +304,147 @@ impl DrawSnippet {
         *self.times.last().unwrap().last().unwrap()
     }
 
+    /// Does this snippet play backwards?
+    pub fn reversed(&self) -> bool {
+        self.reversed
+    }
+
+    /// Returns a copy of this snippet that plays backwards (if `reversed` is true) or forwards.
+    pub fn with_reversed(&self, reversed: bool) -> DrawSnippet {
+        DrawSnippet {
+            reversed,
+            ..self.clone()
+        }
+    }
+
+    /// If this snippet plays backwards, returns the time at which the forwards version of this
+    /// snippet looks the way that this one does at `time`. Otherwise, returns `time`.
+    ///
+    /// Reversing a snippet keeps its start time and its last draw time, so those two times get
+    /// swapped by this function.
+    pub fn playback_time(&self, time: Time) -> Time {
+        if self.reversed {
+            self.start_time() + (self.last_draw_time() - time)
+        } else {
+            time
+        }
+    }
+
+    /// How fast this snippet plays, compared to how fast it was drawn. This includes any time
+    /// distortion and holds, so (for example) a snippet that was retimed to take twice as long has
+    /// a speed of 0.5.
+    pub fn speed(&self) -> f64 {
+        let original = *self.lerp.original_values.last().unwrap()
+            - *self.lerp.original_values.first().unwrap();
+        let lerped = self.lerp.last() - self.lerp.first();
+        if original <= TimeDiff::ZERO || lerped <= TimeDiff::ZERO {
+            1.0
+        } else {
+            original.as_micros() as f64 / lerped.as_micros() as f64
+        }
+    }
+
+    /// Returns a copy of this snippet that plays at `speed` (see [`speed`](DrawSnippet::speed)).
+    /// The snippet still starts at the same time, and if it has an end time then it stays for
+    /// just as long after it finishes drawing.
+    pub fn with_speed(&self, speed: f64) -> DrawSnippet {
+        if speed <= 0.0 || !speed.is_finite() {
+            return self.clone();
+        }
+        let lerp = self.lerp.scaled(self.speed() / speed);
+        let end = self.end.map(|end| end + (lerp.last() - self.lerp.last()));
+        DrawSnippet {
+            end,
+            ..self.with_lerp(lerp)
+        }
+    }
+
+    /// Returns a copy of this snippet that disappears at `end` (or never, if `end` is `None`). The
+    /// end time can't be before the start time.
+    pub fn with_end_time(&self, end: Option<Time>) -> DrawSnippet {
+        DrawSnippet {
+            end: end.map(|end| end.max(self.start_time())),
+            ..self.clone()
+        }
+    }
+
+    /// A hidden snippet stays in the timeline, but it doesn't get drawn.
+    pub fn hidden(&self) -> bool {
+        self.hidden
+    }
+
+    pub fn with_hidden(&self, hidden: bool) -> DrawSnippet {
+        DrawSnippet {
+            hidden,
+            ..self.clone()
+        }
+    }
+
+    /// The layer that this snippet is drawn in. Higher layers are drawn on top.
+    pub fn layer(&self) -> i32 {
+        self.layer
+    }
+
+    pub fn with_layer(&self, layer: i32) -> DrawSnippet {
+        DrawSnippet {
+            layer,
+            ..self.clone()
+        }
+    }
+
+    /// Estimates the number of bytes of memory used by this snippet.
+    ///
+    /// Snippets share a lot of their data with their clones (and with snippets derived from them,
+    /// like time-shifted ones), and the shared parts are only counted the first time they're seen.
+    /// `seen` keeps track of the data that was already counted, so that the memory used by a
+    /// collection of snippets can be estimated by calling this on each of them in turn.
+    pub fn memory_size(&self, seen: &mut HashSet<usize>) -> usize {
+        let mut size = 0;
+        if seen.insert(Arc::as_ptr(&self.strokes) as usize) {
+            size += self
+                .strokes
+                .elts()
+                .map(|s| {
+                    s.path.elements().len() * size_of::<kurbo::PathEl>()
+                        + s.times.len() * size_of::<Time>()
+                })
+                .sum::<usize>();
+        }
+        if seen.insert(Arc::as_ptr(&self.lerp) as usize) {
+            size += (self.lerp.original_values.len() + self.lerp.lerped_values.len())
+                * size_of::<Time>()
+                + self.lerp.easings.len() * size_of::<(usize, IntervalEasing)>();
+        }
+        if seen.insert(Arc::as_ptr(&self.times) as usize) {
+            size += self.times.iter().map(|t| t.len()).sum::<usize>() * size_of::<Time>();
+        }
+        size
+    }
+
+    /// Returns the strokes of this snippet that are visible at time `time`, in the order that they
+    /// were drawn.
+    pub fn visible_strokes<'a>(&'a self, time: Time) -> impl Iterator<Item = VisibleStroke> + 'a {
+        let local_time = self.lerp.unlerp_extended(self.playback_time(time));
+        // Once a reversed snippet is completely undrawn, it stays that way.
+        let undrawn = self.reversed && time > self.last_draw_time();
+        (self.visible_at(time) && !undrawn && !self.hidden)
+            .then(|| self.strokes.visible_strokes(local_time))
+            .into_iter()
+            .flatten()
+    }
+
+    /// Is the point `p` on (or within `tolerance` of) one of the strokes that this snippet shows
+    /// at time `time`?
+    pub fn hit_test(&self, p: Point, time: Time, tolerance: f64) -> bool {
+        self.visible_strokes(time)
+            .any(|stroke| stroke.hit_test(p, tolerance))
+    }
+
+    /// Renders the part of this snippet that is visible at time `time`.
     pub fn render(&self, ctx: &mut impl RenderContext, time: Time) {
-        if !self.visible_at(time) {
-            return;
+        for stroke in self.visible_strokes(time) {
+            stroke.render(ctx);
         }
-        let local_time = self.lerp.unlerp_extended(time);
-        self.strokes.render(ctx, local_time);
     }
 }
 
@@ -177,6 +477,21 @@ impl DrawSnippets {
         self.with_replacement_snippet(id, snip)
     }
 
+    pub fn with_lerp(&self, id: DrawSnippetId, lerp: Lerp) -> DrawSnippets {
+        let snip = self.snippet(id).with_lerp(lerp);
+        self.with_replacement_snippet(id, snip)
+    }
+
+    pub fn with_hold(&self, id: DrawSnippetId, start: Time, end: Time) -> DrawSnippets {
+        let snip = self.snippet(id).with_hold(start, end);
+        self.with_replacement_snippet(id, snip)
+    }
+
+    pub fn without_hold(&self, id: DrawSnippetId, time: Time) -> DrawSnippets {
+        let snip = self.snippet(id).without_hold(time);
+        self.with_replacement_snippet(id, snip)
+    }
+
     pub fn with_truncated_snippet(&self, id: DrawSnippetId, time: Time) -> DrawSnippets {
         let mut snip = self.snippet(id).clone();
         snip.end = Some(time);
@@ -188,14 +503,43 @@ impl DrawSnippets {
         self.with_replacement_snippet(id, snip)
     }
 
+    pub fn has_snippet(&self, id: DrawSnippetId) -> bool {
+        self.snippets.contains_key(&id)
+    }
+
     pub fn snippet(&self, id: DrawSnippetId) -> &DrawSnippet {
         self.snippets.get(&id).unwrap()
     }
 
+    /// Estimates the number of bytes of memory used by these snippets, not counting anything that
+    /// is already in `seen`. See [`DrawSnippet::memory_size`] for details.
+    pub fn memory_size(&self, seen: &mut HashSet<usize>) -> usize {
+        self.snippets.values().map(|s| s.memory_size(seen)).sum()
+    }
+
     pub fn snippets(&self) -> impl Iterator<Item = (DrawSnippetId, &DrawSnippet)> {
         self.snippets.iter().map(|(k, v)| (*k, v))
     }
 
+    /// All the snippets, in the order that they should be drawn: by layer, and then in the order
+    /// that they were added.
+    pub fn snippets_in_drawing_order(&self) -> Vec<(DrawSnippetId, &DrawSnippet)> {
+        let mut ret: Vec<_> = self.snippets().collect();
+        // The sort is stable, so snippets in the same layer stay in order of id.
+        ret.sort_by_key(|(_, snip)| snip.layer);
+        ret
+    }
+
+    /// Returns all the strokes that are visible at time `time`.
+    ///
+    /// The strokes are grouped by snippet, with the snippets in drawing order (see
+    /// [`snippets_in_drawing_order`](DrawSnippets::snippets_in_drawing_order)).
+    pub fn visible_strokes<'a>(&'a self, time: Time) -> impl Iterator<Item = VisibleStroke> + 'a {
+        self.snippets_in_drawing_order()
+            .into_iter()
+            .flat_map(move |(_, snip)| snip.visible_strokes(time))
+    }
+
     pub fn last_draw_time(&self) -> Time {
         self.snippets
             .values()
@@ -215,6 +559,14 @@ impl DrawSnippets {
 }
 
 impl DrawCursor {
+    /// The ids of the active snippets, in drawing order (see
+    /// [`DrawSnippets::snippets_in_drawing_order`]).
+    pub fn active_ids_in_drawing_order(&self, snippets: &DrawSnippets) -> Vec<DrawSnippetId> {
+        let mut ret: Vec<_> = self.active_ids().collect();
+        ret.sort_by_key(|&id| (snippets.snippet(id).layer, id));
+        ret
+    }
+
     pub fn bboxes<'a, 'b: 'a, 'c: 'a>(
         &'b self,
         snippets: &'c DrawSnippets,
@@ -226,18 +578,18 @@ impl DrawCursor {
             // bboxes, but we could be more efficient.
             .flat_map(move |snip| {
                 let (start, end) = self.current();
+                let (start, end) = if snip.reversed() {
+                    (snip.playback_time(end), snip.playback_time(start))
+                } else {
+                    (start, end)
+                };
                 // TODO: this is linear in the number of strokes, but probably most strokes will be
                 // uninteresting. Using some extra cached computations in DrawSnippet, this could
                 // be made (linear in useful strokes + logarithmic in total strokes).
                 snip.strokes().filter_map(move |stroke| {
                     if let Some(snip_end) = snip.end_time() {
                         if self.current().0 < snip_end && self.current().1 >= snip_end {
-                            return Some(
-                                stroke
-                                    .elements
-                                    .bounding_box()
-                                    .inset(stroke.style.thickness / 2.0),
-                            );
+                            return Some(stroke.bbox());
                         }
                     }
                     let bbox = stroke.changes_bbox(start, end);
@@ -258,6 +610,23 @@ struct DrawSnippetSave {
     strokes: Arc<StrokeSeq>,
     lerp: Arc<Lerp>,
     end: Option<Time>,
+    // This was added after the original file format, so old files don't have it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<LabelColor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    group: Option<GroupId>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    reversed: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    hidden: bool,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    layer: i32,
+}
+
+fn is_zero(x: &i32) -> bool {
+    *x == 0
 }
 
 impl From<DrawSnippetSave> for DrawSnippet {
@@ -268,6 +637,12 @@ impl From<DrawSnippetSave> for DrawSnippet {
             lerp: save.lerp,
             times: Arc::new(times),
             end: save.end,
+            name: save.name,
+            label: save.label,
+            group: save.group,
+            reversed: save.reversed,
+            hidden: save.hidden,
+            layer: save.layer,
         }
     }
 }
@@ -278,6 +653,12 @@ impl From<DrawSnippet> for DrawSnippetSave {
             strokes: snip.strokes,
             lerp: snip.lerp,
             end: snip.end,
+            name: snip.name,
+            label: snip.label,
+            group: snip.group,
+            reversed: snip.reversed,
+            hidden: snip.hidden,
+            layer: snip.layer,
         }
     }
 }
@@ -295,6 +676,14 @@ impl<'de> Deserialize<'de> for DrawSnippet {
     }
 }
 
+impl std::iter::FromIterator<(DrawSnippetId, DrawSnippet)> for DrawSnippets {
+    fn from_iter<I: IntoIterator<Item = (DrawSnippetId, DrawSnippet)>>(iter: I) -> DrawSnippets {
+        let snippets: OrdMap<DrawSnippetId, DrawSnippet> = iter.into_iter().collect();
+        let last_id = snippets.keys().max().unwrap_or(&DrawSnippetId(0)).0;
+        DrawSnippets { last_id, snippets }
+    }
+}
+
 // The serialization of DrawSnippets is part of our save file format, and so it needs
 // to remain stable. Here, we serialize DrawSnippets as an id -> DrawSnippet map.
 impl Serialize for DrawSnippets {
@@ -325,5 +714,123 @@ mod tests {
         let written = serde_cbor::to_vec(&snip).unwrap();
         let read: DrawSnippet = serde_cbor::from_slice(&written[..]).unwrap();
         assert_eq!(snip.lerp, read.lerp);
+        assert_eq!(read.name(), None);
+
+        let snip = snip.with_name(Some("intro".to_owned()));
+        let written = serde_cbor::to_vec(&snip).unwrap();
+        let read: DrawSnippet = serde_cbor::from_slice(&written[..]).unwrap();
+        assert_eq!(read.name(), Some("intro"));
+        assert_eq!(read.label(), None);
+
+        let snip = snip.with_label(Some(LabelColor::Green));
+        let written = serde_cbor::to_vec(&snip).unwrap();
+        let read: DrawSnippet = serde_cbor::from_slice(&written[..]).unwrap();
+        assert_eq!(read.label(), Some(LabelColor::Green));
+        assert_eq!(read.group(), None);
+
+        let group = GroupId::unused(vec![]);
+        let snip = snip.with_group(Some(group));
+        let written = serde_cbor::to_vec(&snip).unwrap();
+        let read: DrawSnippet = serde_cbor::from_slice(&written[..]).unwrap();
+        assert_eq!(read.group(), Some(group));
+    }
+
+    #[test]
+    fn holds() {
+        let t = Time::from_micros;
+        let snip = DrawSnippet::new(crate::curve::tests::basic_curve());
+        let snip = DrawSnippet {
+            end: Some(t(20)),
+            ..snip
+        };
+        let held = snip.with_hold(t(5), t(15));
+        assert_eq!(held.holds().collect::<Vec<_>>(), vec![(t(5), t(15))]);
+        assert_eq!(held.end_time(), Some(t(30)));
+        let last_stroke = held.strokes().last().unwrap();
+        assert_eq!(last_stroke.times.last(), Some(&t(18)));
+
+        let written = serde_cbor::to_vec(&held).unwrap();
+        let read: DrawSnippet = serde_cbor::from_slice(&written[..]).unwrap();
+        assert_eq!(read.holds().collect::<Vec<_>>(), vec![(t(5), t(15))]);
+
+        let unheld = held.without_hold(t(10));
+        assert_eq!(unheld.holds().count(), 0);
+        assert_eq!(unheld.end_time(), Some(t(20)));
+    }
+
+    #[test]
+    fn reversed() {
+        let t = Time::from_micros;
+        let snip = DrawSnippet::new(crate::curve::tests::basic_curve()).with_reversed(true);
+        assert_eq!(snip.playback_time(t(1)), t(8));
+        assert_eq!(snip.playback_time(t(8)), t(1));
+        assert_eq!(snip.visible_strokes(t(0)).count(), 0);
+        assert_eq!(snip.visible_strokes(t(1)).count(), 2);
+        assert_eq!(snip.visible_strokes(t(4)).count(), 1);
+        assert_eq!(snip.visible_strokes(t(9)).count(), 0);
+
+        let written = serde_cbor::to_vec(&snip).unwrap();
+        let read: DrawSnippet = serde_cbor::from_slice(&written[..]).unwrap();
+        assert!(read.reversed());
+        let written = serde_cbor::to_vec(&snip.with_reversed(false)).unwrap();
+        let read: DrawSnippet = serde_cbor::from_slice(&written[..]).unwrap();
+        assert!(!read.reversed());
+    }
+
+    #[test]
+    fn speed() {
+        let t = Time::from_micros;
+        let snip = DrawSnippet::new(crate::curve::tests::basic_curve()).with_end_time(Some(t(20)));
+        assert_eq!(snip.speed(), 1.0);
+
+        let slow = snip.with_speed(0.5);
+        assert_eq!(slow.speed(), 0.5);
+        assert_eq!(slow.start_time(), t(1));
+        assert_eq!(slow.last_draw_time(), t(15));
+        // It stays around for just as long after it finishes drawing.
+        assert_eq!(slow.end_time(), Some(t(27)));
+
+        // Nonsense speeds are ignored.
+        assert_eq!(snip.with_speed(0.0).speed(), 1.0);
+        assert_eq!(snip.with_speed(-1.0).speed(), 1.0);
+    }
+
+    #[test]
+    fn hidden_and_layers() {
+        let t = Time::from_micros;
+        let snip = DrawSnippet::new(crate::curve::tests::basic_curve());
+        let hidden = snip.with_hidden(true);
+        assert_eq!(hidden.visible_strokes(t(4)).count(), 0);
+        assert!(!hidden.is_copy_of(&snip));
+
+        let written = serde_cbor::to_vec(&hidden.with_layer(-2)).unwrap();
+        let read: DrawSnippet = serde_cbor::from_slice(&written[..]).unwrap();
+        assert!(read.hidden());
+        assert_eq!(read.layer(), -2);
+
+        let (snips, a) = DrawSnippets::default().with_new_snippet(snip.with_layer(1));
+        let (snips, b) = snips.with_new_snippet(snip.clone());
+        let (snips, c) = snips.with_new_snippet(snip.with_layer(1));
+        let order: Vec<_> = snips
+            .snippets_in_drawing_order()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        // The one in the lower layer is drawn first, even though it was added later.
+        assert_eq!(order, vec![b, a, c]);
+    }
+
+    #[test]
+    fn split() {
+        let snip = DrawSnippet::new(crate::curve::tests::basic_curve());
+        let (first, second) = snip.split(Time::from_micros(5)).unwrap();
+        assert_eq!(first.strokes().count(), 1);
+        assert_eq!(second.strokes().count(), 1);
+        assert_eq!(first.start_time(), Time::from_micros(1));
+        assert_eq!(second.start_time(), Time::from_micros(6));
+
+        // There's nothing to split off before the first stroke or after the last one.
+        assert!(snip.split(Time::from_micros(1)).is_none());
+        assert!(snip.split(Time::from_micros(7)).is_none());
     }
 }
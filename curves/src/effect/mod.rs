@@ -0,0 +1,294 @@
+//! This module contains visual effects that can be applied to snippets.
+//!
+//! The fade effect is built into the file format. Other effects are implementations of
+//! [`StrokeEffect`], each in its own module, and they are listed in the [registry](registry). In
+//! saved files, they're stored by name along with their parameters (see [`NamedEffect`]), so files
+//! containing effects that we don't know about can still be loaded (and saved again without losing
+//! anything); the unknown effects just don't get drawn.
+//!
+//! To add a new effect, implement [`StrokeEffect`] in a new module and add it to the registry.
+
+use im::{OrdMap, Vector};
+use serde::de::{Deserializer, SeqAccess, Visitor};
+use serde::ser::{SerializeSeq, Serializer};
+use serde::{Deserialize, Serialize};
+
+use crate::render::VisibleStroke;
+use crate::time::{Time, TimeDiff};
+
+mod wiggle;
+
+pub use wiggle::Wiggle;
+
+// All the effects that we know how to draw.
+static REGISTRY: &[&dyn StrokeEffect] = &[&Wiggle];
+
+/// Returns all the effects that we know how to draw.
+pub fn registry() -> &'static [&'static dyn StrokeEffect] {
+    REGISTRY
+}
+
+/// Finds an effect in the registry by name.
+pub fn find_effect(name: &str) -> Option<&'static dyn StrokeEffect> {
+    REGISTRY.iter().copied().find(|e| e.name() == name)
+}
+
+/// A visual effect that changes the way strokes are drawn.
+///
+/// Effects must be deterministic: the way a stroke looks can only depend on the stroke, the
+/// effect's parameters and the time. That way, the drawing looks the same when playing back and
+/// when exporting.
+pub trait StrokeEffect: Send + Sync {
+    /// The name of the effect. This is what gets saved in files, so it can never change.
+    fn name(&self) -> &'static str;
+
+    /// The name of the effect, for showing to people.
+    fn title(&self) -> &'static str;
+
+    /// Changes `stroke` to the way it should look at time `time`.
+    fn apply(&self, params: &NamedEffect, stroke: &mut VisibleStroke, time: Time);
+
+    /// How far this effect can move any part of a stroke.
+    fn padding(&self, _params: &NamedEffect) -> f64 {
+        0.0
+    }
+
+    /// Returns true if this effect keeps changing the stroke after it has been drawn. If so, the
+    /// whole stroke gets redrawn on every frame.
+    fn is_animated(&self, _params: &NamedEffect) -> bool {
+        false
+    }
+}
+
+/// An effect from the [registry](registry), along with its parameters.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+pub struct NamedEffect {
+    /// The name of the effect (see [`StrokeEffect::name`]).
+    pub name: String,
+
+    /// The parameters of the effect. Any parameter that's missing gets a default value chosen by
+    /// the effect.
+    #[serde(default)]
+    pub params: OrdMap<String, f64>,
+}
+
+impl NamedEffect {
+    /// Creates an effect that uses the default values for all its parameters.
+    pub fn new(name: &str) -> NamedEffect {
+        NamedEffect {
+            name: name.to_owned(),
+            params: OrdMap::new(),
+        }
+    }
+
+    /// Sets the value of a parameter.
+    pub fn with_param(mut self, name: &str, value: f64) -> NamedEffect {
+        self.params.insert(name.to_owned(), value);
+        self
+    }
+
+    /// Returns the value of a parameter, or `default` if it wasn't set.
+    pub fn param(&self, name: &str, default: f64) -> f64 {
+        self.params.get(name).copied().unwrap_or(default)
+    }
+
+    /// The implementation of this effect, if we have one.
+    pub fn effect(&self) -> Option<&'static dyn StrokeEffect> {
+        find_effect(&self.name)
+    }
+}
+
+/// A fade effect.
+///
+/// When a segment is finished, it will start fading out.
+#[derive(Clone, Debug, Eq, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+pub struct FadeEffect {
+    /// After the segment finishes, it will remain at full opacity for this duration.
+    /// Then it will start fading out.
+    pub pause: TimeDiff,
+
+    /// The segment will fade out (linearly interpolated) for this length of time.
+    pub fade: TimeDiff,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+pub enum Effect {
+    Fade(FadeEffect),
+    Named(NamedEffect),
+}
+
+/// A collection of effects.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+pub struct Effects {
+    fade: Option<FadeEffect>,
+    // There's at most one effect with any given name.
+    named: Vector<NamedEffect>,
+}
+
+impl FadeEffect {
+    /// `t` is the time that has elapsed since the end of a segment. By how much should we fade the
+    /// segment in response?
+    pub fn opacity_at_time(&self, t: TimeDiff) -> f64 {
+        if t >= self.pause + self.fade {
+            0.0
+        } else if t <= self.pause {
+            1.0
+        } else {
+            let ratio = (t - self.pause).as_micros() as f64 / self.fade.as_micros() as f64;
+            1.0 - ratio
+        }
+    }
+}
+
+impl Effects {
+    /// Adds an effect, replacing any existing effect of the same kind.
+    pub fn add(&mut self, effect: Effect) {
+        match effect {
+            Effect::Fade(fade) => self.fade = Some(fade),
+            Effect::Named(named) => {
+                if let Some(idx) = self.named.iter().position(|e| e.name == named.name) {
+                    self.named.set(idx, named);
+                } else {
+                    self.named.push_back(named);
+                }
+            }
+        }
+    }
+
+    pub fn fade(&self) -> Option<&FadeEffect> {
+        self.fade.as_ref()
+    }
+
+    /// All the effects other than the fade, including ones that aren't in the registry.
+    pub fn named(&self) -> impl Iterator<Item = &NamedEffect> {
+        self.named.iter()
+    }
+
+    fn known(&self) -> impl Iterator<Item = (&'static dyn StrokeEffect, &NamedEffect)> {
+        self.named
+            .iter()
+            .filter_map(|params| params.effect().map(|e| (e, params)))
+    }
+
+    /// Applies all of our effects (other than the fade, which is handled separately) to a stroke.
+    pub fn apply(&self, stroke: &mut VisibleStroke, time: Time) {
+        for (effect, params) in self.known() {
+            effect.apply(params, stroke, time);
+        }
+    }
+
+    /// How far our effects can move any part of a stroke.
+    pub fn padding(&self) -> f64 {
+        self.known()
+            .map(|(effect, params)| effect.padding(params))
+            .sum()
+    }
+
+    /// Returns true if any of our effects keep changing strokes after they've been drawn.
+    pub fn is_animated(&self) -> bool {
+        self.known()
+            .any(|(effect, params)| effect.is_animated(params))
+    }
+}
+
+// We serialize effects as a sequence, so that we can implement more effects
+// without breaking the file format.
+impl Serialize for Effects {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let len = if self.fade.is_some() { 1 } else { 0 } + self.named.len();
+        let mut seq = ser.serialize_seq(Some(len))?;
+
+        if let Some(fade) = &self.fade {
+            seq.serialize_element(&Effect::Fade(fade.clone()))?;
+        }
+        for named in &self.named {
+            seq.serialize_element(&Effect::Named(named.clone()))?;
+        }
+
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Effects {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Effects, D::Error> {
+        de.deserialize_seq(EffectsVisitor)
+    }
+}
+
+struct EffectsVisitor;
+
+impl<'de> Visitor<'de> for EffectsVisitor {
+    type Value = Effects;
+    fn expecting(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str("a list of effects")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut access: A) -> Result<Effects, A::Error> {
+        let mut ret = Effects::default();
+
+        while let Some(effect) = access.next_element()? {
+            ret.add(effect);
+        }
+
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serde() {
+        let empty = Effects::default();
+        let written = serde_cbor::to_vec(&empty).unwrap();
+        let read = serde_cbor::from_slice(&written[..]).unwrap();
+        assert_eq!(empty, read);
+
+        let mut fade = Effects::default();
+        fade.add(Effect::Fade(FadeEffect {
+            pause: TimeDiff::from_micros(100),
+            fade: TimeDiff::from_micros(100),
+        }));
+        let written = serde_cbor::to_vec(&fade).unwrap();
+        let read = serde_cbor::from_slice(&written[..]).unwrap();
+        assert_eq!(fade, read);
+
+        let mut both = fade.clone();
+        both.add(Effect::Named(
+            NamedEffect::new("wiggle").with_param("amplitude", 0.01),
+        ));
+        // Effects that we don't know about should survive a round-trip.
+        both.add(Effect::Named(NamedEffect::new("sparkle")));
+        let written = serde_cbor::to_vec(&both).unwrap();
+        let read: Effects = serde_cbor::from_slice(&written[..]).unwrap();
+        assert_eq!(both, read);
+        assert_eq!(read.named().count(), 2);
+        assert!(read.fade().is_some());
+    }
+
+    #[test]
+    fn registry() {
+        let mut effects = Effects::default();
+        assert!(!effects.is_animated());
+        assert_eq!(effects.padding(), 0.0);
+
+        effects.add(Effect::Named(NamedEffect::new("sparkle")));
+        assert!(!effects.is_animated());
+
+        effects.add(Effect::Named(NamedEffect::new("wiggle")));
+        assert!(effects.is_animated());
+        assert!(effects.padding() > 0.0);
+
+        // Adding an effect with the same name replaces the old one.
+        effects.add(Effect::Named(
+            NamedEffect::new("wiggle").with_param("amplitude", 0.0),
+        ));
+        assert_eq!(effects.named().count(), 2);
+        assert_eq!(effects.padding(), 0.0);
+    }
+}
@@ -0,0 +1,138 @@
+//! A "line boil" effect: strokes keep wobbling a little, as though each frame was drawn again by
+//! hand.
+
+use kurbo::{BezPath, PathEl, Point, Vec2};
+
+use super::{NamedEffect, StrokeEffect};
+use crate::render::VisibleStroke;
+use crate::time::Time;
+
+// The default amount (in drawing coordinates) by which points are moved.
+const DEFAULT_AMPLITUDE: f64 = 0.002;
+// The default number of times per second that the wiggle changes.
+const DEFAULT_RATE: f64 = 8.0;
+
+/// Moves the points of a stroke by small random amounts, which change a few times per second.
+///
+/// Parameters:
+///
+/// - `amplitude`: the maximum distance (in drawing coordinates) that a point moves
+/// - `rate`: the number of times per second that the points move
+pub struct Wiggle;
+
+impl Wiggle {
+    fn amplitude(params: &NamedEffect) -> f64 {
+        params.param("amplitude", DEFAULT_AMPLITUDE).max(0.0)
+    }
+}
+
+impl StrokeEffect for Wiggle {
+    fn name(&self) -> &'static str {
+        "wiggle"
+    }
+
+    fn title(&self) -> &'static str {
+        "Wiggle"
+    }
+
+    fn apply(&self, params: &NamedEffect, stroke: &mut VisibleStroke, time: Time) {
+        let amplitude = Wiggle::amplitude(params);
+        let rate = params.param("rate", DEFAULT_RATE);
+        if amplitude == 0.0 || rate <= 0.0 {
+            return;
+        }
+
+        let frame = (time.as_micros() as f64 * rate / 1e6).floor() as i64;
+        let mut idx = 0;
+        let mut offset = |p: Point| {
+            idx += 1;
+            p + amplitude * jitter(frame, idx)
+        };
+        let els: Vec<_> = stroke
+            .path
+            .elements()
+            .iter()
+            .map(|el| match *el {
+                PathEl::MoveTo(p) => PathEl::MoveTo(offset(p)),
+                PathEl::LineTo(p) => PathEl::LineTo(offset(p)),
+                PathEl::QuadTo(p1, p2) => PathEl::QuadTo(offset(p1), offset(p2)),
+                PathEl::CurveTo(p1, p2, p3) => PathEl::CurveTo(offset(p1), offset(p2), offset(p3)),
+                PathEl::ClosePath => PathEl::ClosePath,
+            })
+            .collect();
+        stroke.path = BezPath::from_vec(els);
+    }
+
+    fn padding(&self, params: &NamedEffect) -> f64 {
+        // `jitter` has length at most sqrt(2).
+        Wiggle::amplitude(params) * std::f64::consts::SQRT_2
+    }
+
+    fn is_animated(&self, params: &NamedEffect) -> bool {
+        Wiggle::amplitude(params) > 0.0
+    }
+}
+
+/// A pseudo-random vector with coordinates between -1 and 1, depending only on its inputs.
+fn jitter(frame: i64, idx: u64) -> Vec2 {
+    let h = hash(frame as u64, idx);
+    let x = (h & 0xffff_ffff) as f64 / u32::MAX as f64;
+    let y = (h >> 32) as f64 / u32::MAX as f64;
+    Vec2::new(2.0 * x - 1.0, 2.0 * y - 1.0)
+}
+
+// The "splitmix64" finalizer, applied to a combination of the two inputs.
+fn hash(a: u64, b: u64) -> u64 {
+    let mut z = a
+        .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        .wrapping_add(b.wrapping_mul(0xbf58_476d_1ce4_e5b9));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Brush;
+    use kurbo::BezPath;
+    use piet::Color;
+
+    fn stroke() -> VisibleStroke {
+        let mut path = BezPath::new();
+        path.move_to((0.0, 0.0));
+        path.line_to((0.5, 0.5));
+        VisibleStroke {
+            path,
+            color: Color::BLACK,
+            alpha: 1.0,
+            thickness: 0.01,
+            brush: Brush::Round,
+        }
+    }
+
+    fn wiggled(params: &NamedEffect, micros: i64) -> Vec<PathEl> {
+        let mut s = stroke();
+        Wiggle.apply(params, &mut s, Time::from_micros(micros));
+        s.path.elements().to_vec()
+    }
+
+    #[test]
+    fn wiggle() {
+        let params = NamedEffect::new("wiggle").with_param("rate", 10.0);
+        // The wiggle stays the same for a tenth of a second.
+        assert_eq!(wiggled(&params, 0), wiggled(&params, 99_999));
+        assert_ne!(wiggled(&params, 0), wiggled(&params, 100_000));
+
+        let orig = stroke().path.elements().to_vec();
+        assert_ne!(wiggled(&params, 0), orig);
+        for (el, orig) in wiggled(&params, 0).iter().zip(&orig) {
+            if let (PathEl::LineTo(p), PathEl::LineTo(q)) = (el, orig) {
+                assert!((*p - *q).hypot() <= Wiggle.padding(&params));
+            }
+        }
+
+        let still = NamedEffect::new("wiggle").with_param("amplitude", 0.0);
+        assert_eq!(wiggled(&still, 0), orig);
+    }
+}
@@ -0,0 +1,41 @@
+use piet::Color;
+use serde::{Deserialize, Serialize};
+
+/// Identifies a group of linked snippets.
+///
+/// Snippets in the same group (for example, a drawing and the narration that goes with it) get
+/// selected, moved and deleted together. Both drawing and audio snippets can belong to a group,
+/// and each snippet belongs to at most one.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+#[serde(transparent)]
+pub struct GroupId(u64);
+
+impl GroupId {
+    /// Returns a group id that is different from all of the `existing` ones.
+    pub fn unused(existing: impl IntoIterator<Item = GroupId>) -> GroupId {
+        GroupId(existing.into_iter().map(|g| g.0 + 1).max().unwrap_or(0))
+    }
+
+    /// The color that this group's snippets are outlined with in the timeline.
+    ///
+    /// The hues are spaced by the golden angle, so that groups with nearby ids get colors that
+    /// are easy to tell apart.
+    pub fn color(self) -> Color {
+        let hue = (self.0 as f64 * 137.508) % 360.0;
+        Color::hlc(hue, 70.0, 60.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unused() {
+        assert_eq!(GroupId::unused(vec![]), GroupId(0));
+        assert_eq!(GroupId::unused(vec![GroupId(3), GroupId(1)]), GroupId(4));
+    }
+}
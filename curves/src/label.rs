@@ -0,0 +1,59 @@
+use piet::Color;
+use serde::{Deserialize, Serialize};
+
+/// A color that they can use to tag snippets, to make them easier to tell apart in the timeline.
+///
+/// There's a small fixed set of these (rather than allowing arbitrary colors) so that they can be
+/// chosen to be readable against the timeline's other colors.
+// This is serialized as part of saving files, so its serialization format needs to remain
+// stable.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
+#[serde(rename_all = "snake_case")]
+pub enum LabelColor {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+    Gray,
+}
+
+impl LabelColor {
+    /// All the label colors, in the order that they should be offered.
+    pub const ALL: [LabelColor; 7] = [
+        LabelColor::Red,
+        LabelColor::Orange,
+        LabelColor::Yellow,
+        LabelColor::Green,
+        LabelColor::Blue,
+        LabelColor::Purple,
+        LabelColor::Gray,
+    ];
+
+    /// A human-readable name for this color.
+    pub fn name(self) -> &'static str {
+        match self {
+            LabelColor::Red => "Red",
+            LabelColor::Orange => "Orange",
+            LabelColor::Yellow => "Yellow",
+            LabelColor::Green => "Green",
+            LabelColor::Blue => "Blue",
+            LabelColor::Purple => "Purple",
+            LabelColor::Gray => "Gray",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            LabelColor::Red => Color::rgb8(0xe0, 0x5a, 0x4f),
+            LabelColor::Orange => Color::rgb8(0xf0, 0x98, 0x3e),
+            LabelColor::Yellow => Color::rgb8(0xf2, 0xd0, 0x4b),
+            LabelColor::Green => Color::rgb8(0x6c, 0xc0, 0x5a),
+            LabelColor::Blue => Color::rgb8(0x4f, 0x8f, 0xe0),
+            LabelColor::Purple => Color::rgb8(0xa0, 0x6c, 0xd5),
+            LabelColor::Gray => Color::rgb8(0x9a, 0x9a, 0x9a),
+        }
+    }
+}
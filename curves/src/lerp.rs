@@ -1,14 +1,160 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-use crate::time::{Time, TimeDiff, TimeSpan};
+use crate::time::{Time, TimeDiff};
+
+/// How the interpolation between two key-frames speeds up and slows down.
+///
+/// An easing is a map from `[0, 1]` to `[0, 1]`: it takes the fraction of the (lerped) time
+/// between two key-frames that has passed, and returns the fraction of the original time. So with
+/// [`Easing::EaseIn`], a drawing starts out being drawn slowly and then speeds up.
+///
+/// # Examples
+///
+/// ```rust
+/// # use scribl_curves::Easing;
+/// assert_eq!(Easing::Linear.apply(0.5), 0.5);
+/// assert!(Easing::EaseIn.apply(0.5) < 0.5);
+/// assert!(Easing::EaseOut.apply(0.5) > 0.5);
+/// assert_eq!(Easing::Cubic.apply(0.5), 0.5);
+/// assert!((Easing::Cubic.invert(Easing::Cubic.apply(0.3)) - 0.3).abs() < 1e-9);
+/// ```
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    /// Starts slowly and speeds up.
+    EaseIn,
+    /// Starts quickly and slows down.
+    EaseOut,
+    /// Starts slowly, speeds up in the middle, and slows down at the end.
+    Cubic,
+}
+
+impl Default for Easing {
+    fn default() -> Easing {
+        Easing::Linear
+    }
+}
+
+impl Easing {
+    /// All the easings, in the order that they should be offered to the user.
+    pub const ALL: [Easing; 4] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::Cubic,
+    ];
+
+    /// A human-readable name for this easing.
+    pub fn name(self) -> &'static str {
+        match self {
+            Easing::Linear => "Linear",
+            Easing::EaseIn => "Ease in",
+            Easing::EaseOut => "Ease out",
+            Easing::Cubic => "Ease in and out",
+        }
+    }
+
+    /// Maps the fraction `x` of lerped time to a fraction of original time.
+    pub fn apply(self, x: f64) -> f64 {
+        let x = x.max(0.0).min(1.0);
+        match self {
+            Easing::Linear => x,
+            Easing::EaseIn => x * x,
+            Easing::EaseOut => 1.0 - (1.0 - x) * (1.0 - x),
+            Easing::Cubic => {
+                if x < 0.5 {
+                    4.0 * x * x * x
+                } else {
+                    1.0 - (2.0 - 2.0 * x).powi(3) / 2.0
+                }
+            }
+        }
+    }
+
+    /// The inverse of [`Easing::apply`]: maps the fraction `y` of original time to a fraction of
+    /// lerped time.
+    pub fn invert(self, y: f64) -> f64 {
+        let y = y.max(0.0).min(1.0);
+        match self {
+            Easing::Linear => y,
+            Easing::EaseIn => y.sqrt(),
+            Easing::EaseOut => 1.0 - (1.0 - y).sqrt(),
+            Easing::Cubic => {
+                if y < 0.5 {
+                    (y / 4.0).cbrt()
+                } else {
+                    1.0 - (2.0 * (1.0 - y)).cbrt() / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// The easing of one interval between key-frames.
+///
+/// When a key-frame is added in the middle of an eased interval, each half gets the part of the
+/// easing curve that it covers: `from` and `to` are the fractions of the split interval's lerped
+/// time where the half starts and ends. This way, adding a key-frame doesn't change the mapping.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub(crate) struct IntervalEasing {
+    easing: Easing,
+    from: f64,
+    to: f64,
+}
+
+impl IntervalEasing {
+    fn new(easing: Easing) -> IntervalEasing {
+        IntervalEasing {
+            easing,
+            from: 0.0,
+            to: 1.0,
+        }
+    }
+
+    // Like `Easing::apply`, but for the part of the curve between `from` and `to`, rescaled to
+    // `[0, 1]`.
+    fn apply(&self, x: f64) -> f64 {
+        let x = x.max(0.0).min(1.0);
+        if self.to <= self.from {
+            return x;
+        }
+        let start = self.easing.apply(self.from);
+        let end = self.easing.apply(self.to);
+        let y = self.easing.apply(self.from + x * (self.to - self.from));
+        ((y - start) / (end - start)).max(0.0).min(1.0)
+    }
+
+    // The inverse of `IntervalEasing::apply`.
+    fn invert(&self, y: f64) -> f64 {
+        let y = y.max(0.0).min(1.0);
+        if self.to <= self.from {
+            return y;
+        }
+        let start = self.easing.apply(self.from);
+        let end = self.easing.apply(self.to);
+        let x = self.easing.invert(start + y * (end - start));
+        ((x - self.from) / (self.to - self.from)).max(0.0).min(1.0)
+    }
+
+    // Splits this easing at the point where the fraction `y` of the original time has passed.
+    fn split(&self, y: f64) -> (IntervalEasing, IntervalEasing) {
+        let mid = self.from + self.invert(y) * (self.to - self.from);
+        (
+            IntervalEasing { to: mid, ..*self },
+            IntervalEasing { from: mid, ..*self },
+        )
+    }
+}
 
 /// Specifies interpolations between two sets of times.
 ///
 /// This struct maintains two lists of "key-frame" times, which it uses for mapping times from one
 /// scale to another.  The two lists of key-frames must have the same length; the `i`th key-frame
 /// of one list is mapped to the `i`th key-frame of the other list, and times that fall in between
-/// key-frames are mapped using linear interpolation. There are various different ways that you can
-/// handle times that lie outside the range of key-frames, as illustrated in the examples below.
+/// key-frames are mapped using linear interpolation (or with an [`Easing`], if one was set using
+/// [`Lerp::with_easing`]). There are various different ways that you can handle times that lie
+/// outside the range of key-frames, as illustrated in the examples below.
 ///
 /// # Examples
 ///
@@ -55,6 +201,11 @@ use crate::time::{Time, TimeDiff, TimeSpan};
 pub struct Lerp {
     pub(crate) original_values: Vec<Time>,
     pub(crate) lerped_values: Vec<Time>,
+    // The easing between each key-frame and the next one, indexed by the index of the first
+    // key-frame. Linear easings aren't stored. This was added after the original file format, so
+    // old files don't have it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) easings: BTreeMap<usize, IntervalEasing>,
 }
 
 impl Lerp {
@@ -71,6 +222,7 @@ impl Lerp {
         Lerp {
             original_values: original,
             lerped_values: lerped,
+            easings: BTreeMap::new(),
         }
     }
 
@@ -153,12 +305,53 @@ impl Lerp {
         Lerp {
             original_values: self.original_values.clone(),
             lerped_values,
+            easings: self.easings.clone(),
         }
     }
 
+    /// Returns a clone of this `Lerp` that is stretched by a factor of `factor`, keeping the
+    /// first time in the range fixed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scribl_curves::{Lerp, Time};
+    /// let t = |x| Time::from_micros(x);
+    /// let lerp = Lerp::identity(t(10), t(20)).with_new_lerp(t(15), t(18));
+    /// assert_eq!(lerp.scaled(2.0).times(), &[t(10), t(26), t(30)]);
+    /// ```
+    pub fn scaled(&self, factor: f64) -> Lerp {
+        let first = self.first();
+        let lerped_values = self
+            .lerped_values
+            .iter()
+            .map(|&v| {
+                let diff = (v - first).as_micros() as f64 * factor;
+                first + TimeDiff::from_micros(diff.round() as i64)
+            })
+            .collect();
+        Lerp {
+            original_values: self.original_values.clone(),
+            lerped_values,
+            easings: self.easings.clone(),
+        }
+    }
+
+    fn lerp_interval(&self, t: Time) -> LerpResult {
+        lerp_interval(t, &self.original_values, &self.lerped_values, |i, x| {
+            self.easings.get(&i).map_or(x, |e| e.invert(x))
+        })
+    }
+
+    fn unlerp_interval(&self, t: Time) -> LerpResult {
+        lerp_interval(t, &self.lerped_values, &self.original_values, |i, x| {
+            self.eased_fraction(i, x)
+        })
+    }
+
     pub fn lerp(&self, t: Time) -> Option<Time> {
         use LerpResult::*;
-        match lerp_interval(t, &self.original_values, &self.lerped_values) {
+        match self.lerp_interval(t) {
             AfterEnd(_) => None,
             BeforeStart(_) => None,
             SingleTime(t) => Some(t),
@@ -168,7 +361,7 @@ impl Lerp {
 
     pub fn lerp_clamped(&self, t: Time) -> Time {
         use LerpResult::*;
-        match lerp_interval(t, &self.original_values, &self.lerped_values) {
+        match self.lerp_interval(t) {
             AfterEnd(_) => self.last(),
             BeforeStart(_) => self.first(),
             SingleTime(t) => t,
@@ -178,7 +371,7 @@ impl Lerp {
 
     pub fn lerp_extended(&self, t: Time) -> Time {
         use LerpResult::*;
-        match lerp_interval(t, &self.original_values, &self.lerped_values) {
+        match self.lerp_interval(t) {
             AfterEnd(t) => *self.lerped_values.last().unwrap() + t,
             BeforeStart(t) => *self.lerped_values.first().unwrap() + t,
             SingleTime(t) => t,
@@ -188,7 +381,7 @@ impl Lerp {
 
     pub fn unlerp(&self, t: Time) -> Option<Time> {
         use LerpResult::*;
-        match lerp_interval(t, &self.lerped_values, &self.original_values) {
+        match self.unlerp_interval(t) {
             AfterEnd(_) => None,
             BeforeStart(_) => None,
             SingleTime(t) => Some(t),
@@ -198,7 +391,7 @@ impl Lerp {
 
     pub fn unlerp_clamped(&self, t: Time) -> Time {
         use LerpResult::*;
-        match lerp_interval(t, &self.lerped_values, &self.original_values) {
+        match self.unlerp_interval(t) {
             AfterEnd(_) => *self.original_values.last().unwrap(),
             BeforeStart(_) => *self.original_values.first().unwrap(),
             SingleTime(t) => t,
@@ -208,7 +401,7 @@ impl Lerp {
 
     pub fn unlerp_extended(&self, t: Time) -> Time {
         use LerpResult::*;
-        match lerp_interval(t, &self.lerped_values, &self.original_values) {
+        match self.unlerp_interval(t) {
             AfterEnd(t) => *self.original_values.last().unwrap() + t,
             BeforeStart(t) => *self.original_values.first().unwrap() + t,
             SingleTime(t) => t,
@@ -221,8 +414,7 @@ impl Lerp {
         let idx = match self.original_values.binary_search(&local_time_from) {
             Ok(idx) => idx,
             Err(idx) => {
-                self.original_values.insert(idx, local_time_from);
-                self.lerped_values.insert(idx, time_from);
+                self.insert_keyframe(idx, local_time_from, time_from);
                 idx
             }
         };
@@ -244,6 +436,285 @@ impl Lerp {
         ret.add_lerp(time_from, time_to);
         ret
     }
+
+    /// The key-frames of the mapping, as pairs of (original time, lerped time).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scribl_curves::{Lerp, Time};
+    /// let t = |x| Time::from_micros(x);
+    /// let lerp = Lerp::identity(t(10), t(20)).with_new_lerp(t(15), t(18));
+    /// assert_eq!(
+    ///     lerp.keyframes().collect::<Vec<_>>(),
+    ///     vec![(t(10), t(10)), (t(15), t(18)), (t(20), t(20))]
+    /// );
+    /// ```
+    pub fn keyframes<'a>(&'a self) -> impl Iterator<Item = (Time, Time)> + 'a {
+        self.original_values
+            .iter()
+            .cloned()
+            .zip(self.lerped_values.iter().cloned())
+    }
+
+    /// Returns a clone of this `Lerp` with an extra key-frame at the original time `original`.
+    /// The new key-frame is mapped to wherever `original` was already mapped, so this doesn't
+    /// change the mapping (if it splits an interval with an easing, each half gets its part of the
+    /// easing curve); it just gives another key-frame that can be moved. Nothing changes if
+    /// `original` is outside the domain, or if it already has a key-frame.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scribl_curves::{Lerp, Time};
+    /// let t = |x| Time::from_micros(x);
+    /// let lerp = Lerp::identity(t(10), t(20)).with_new_lerp(t(20), t(30));
+    /// let with_key = lerp.with_keyframe(t(15));
+    /// assert_eq!(with_key.times(), &[t(10), t(20), t(30)]);
+    /// assert_eq!(with_key.lerp(t(12)), lerp.lerp(t(12)));
+    /// ```
+    pub fn with_keyframe(&self, original: Time) -> Lerp {
+        let mut ret = self.clone();
+        if original < *self.original_values.first().unwrap()
+            || original > *self.original_values.last().unwrap()
+        {
+            return ret;
+        }
+        if let Err(idx) = self.original_values.binary_search(&original) {
+            ret.insert_keyframe(idx, original, self.lerp_clamped(original));
+        }
+        ret
+    }
+
+    /// Returns a clone of this `Lerp` in which the `idx`th key-frame is mapped to `time`. The
+    /// key-frames have to stay in order, so `time` gets clamped between the lerped times of the
+    /// key-frames on either side.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scribl_curves::{Lerp, Time};
+    /// let t = |x| Time::from_micros(x);
+    /// let lerp = Lerp::identity(t(10), t(20)).with_keyframe(t(15));
+    /// assert_eq!(lerp.with_moved_keyframe(1, t(12)).times(), &[t(10), t(12), t(20)]);
+    /// assert_eq!(lerp.with_moved_keyframe(1, t(25)).times(), &[t(10), t(20), t(20)]);
+    /// assert_eq!(lerp.with_moved_keyframe(2, t(25)).times(), &[t(10), t(15), t(25)]);
+    /// ```
+    pub fn with_moved_keyframe(&self, idx: usize, time: Time) -> Lerp {
+        let mut ret = self.clone();
+        let mut time = time;
+        if idx > 0 {
+            time = time.max(self.lerped_values[idx - 1]);
+        }
+        if let Some(&next) = self.lerped_values.get(idx + 1) {
+            time = time.min(next);
+        }
+        ret.lerped_values[idx] = time;
+        ret
+    }
+
+    /// Returns a clone of this `Lerp` without its `idx`th key-frame. The first and last
+    /// key-frames can't be removed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scribl_curves::{Lerp, Time};
+    /// let t = |x| Time::from_micros(x);
+    /// let lerp = Lerp::identity(t(10), t(20)).with_new_lerp(t(15), t(18));
+    /// assert_eq!(lerp.without_keyframe(1), Lerp::identity(t(10), t(20)));
+    /// assert_eq!(lerp.without_keyframe(2), lerp);
+    /// ```
+    pub fn without_keyframe(&self, idx: usize) -> Lerp {
+        let mut ret = self.clone();
+        if 0 < idx && idx + 1 < self.original_values.len() {
+            ret.original_values.remove(idx);
+            ret.lerped_values.remove(idx);
+
+            // The merged interval keeps the easing of the first half. If the two halves came from
+            // splitting a single interval, they get put back together.
+            let before = ret.easings.remove(&(idx - 1));
+            let after = ret.easings.remove(&idx);
+            let merged = match (before, after) {
+                (Some(b), Some(a)) if b.easing == a.easing && b.to == a.from => {
+                    Some(IntervalEasing { to: a.to, ..b })
+                }
+                (b, _) => b.map(|b| IntervalEasing::new(b.easing)),
+            };
+            if let Some(merged) = merged {
+                ret.easings.insert(idx - 1, merged);
+            }
+            ret.renumber_easings(idx + 1, false);
+        }
+        ret
+    }
+
+    /// The easing between the `idx`th key-frame and the next one. The last key-frame doesn't have
+    /// a next one, so its easing is always linear.
+    pub fn easing(&self, idx: usize) -> Easing {
+        self.easings.get(&idx).map(|e| e.easing).unwrap_or_default()
+    }
+
+    /// The fraction of the original time between the `idx`th key-frame and the next one that has
+    /// passed when the fraction `x` of the lerped time has passed.
+    ///
+    /// This is usually just `self.easing(idx).apply(x)`, but if the interval was split from a
+    /// bigger eased interval then it only covers part of the easing curve.
+    pub fn eased_fraction(&self, idx: usize, x: f64) -> f64 {
+        self.easings
+            .get(&idx)
+            .map_or(x.max(0.0).min(1.0), |e| e.apply(x))
+    }
+
+    /// Returns a clone of this `Lerp` in which the times between the `idx`th key-frame and the
+    /// next one are interpolated using `easing`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scribl_curves::{Easing, Lerp, Time};
+    /// let t = |x| Time::from_micros(x);
+    /// let lerp = Lerp::identity(t(0), t(100)).with_easing(0, Easing::EaseIn);
+    /// assert_eq!(lerp.easing(0), Easing::EaseIn);
+    /// // Halfway through the lerped times, only a quarter of the original time has passed.
+    /// assert_eq!(lerp.unlerp(t(50)), Some(t(25)));
+    /// assert_eq!(lerp.lerp(t(25)), Some(t(50)));
+    /// // The end points don't move.
+    /// assert_eq!(lerp.lerp(t(100)), Some(t(100)));
+    /// assert_eq!(lerp.with_easing(0, Easing::Linear), Lerp::identity(t(0), t(100)));
+    /// ```
+    pub fn with_easing(&self, idx: usize, easing: Easing) -> Lerp {
+        let mut ret = self.clone();
+        if idx + 1 < self.original_values.len() {
+            if easing == Easing::Linear {
+                ret.easings.remove(&idx);
+            } else {
+                ret.easings.insert(idx, IntervalEasing::new(easing));
+            }
+        }
+        ret
+    }
+
+    /// The holds in this mapping, as (start, end) pairs of lerped times. A hold is a pair of
+    /// consecutive key-frames with the same original time, so the drawing doesn't advance between
+    /// them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scribl_curves::{Lerp, Time, TimeDiff};
+    /// let t = |x| Time::from_micros(x);
+    /// let lerp = Lerp::identity(t(10), t(20)).with_hold(t(15), TimeDiff::from_micros(5));
+    /// assert_eq!(lerp.holds().collect::<Vec<_>>(), vec![(t(15), t(20))]);
+    /// assert_eq!(lerp.unlerp(t(17)), Some(t(15)));
+    /// assert_eq!(lerp.unlerp(t(22)), Some(t(17)));
+    /// assert_eq!(lerp.last(), t(25));
+    /// ```
+    pub fn holds<'a>(&'a self) -> impl Iterator<Item = (Time, Time)> + 'a {
+        self.keyframes()
+            .zip(self.keyframes().skip(1))
+            .filter(|((orig0, lerped0), (orig1, lerped1))| orig0 == orig1 && lerped0 < lerped1)
+            .map(|((_, lerped0), (_, lerped1))| (lerped0, lerped1))
+    }
+
+    /// Returns a clone of this `Lerp` with a hold of length `len` starting at the lerped time
+    /// `at`. Everything that used to happen after `at` gets delayed by `len`. Nothing changes if
+    /// `at` is at (or after) the end of the range, because then there's nothing left to delay.
+    pub fn with_hold(&self, at: Time, len: TimeDiff) -> Lerp {
+        let mut ret = self.clone();
+        if len <= TimeDiff::ZERO || at >= self.last() {
+            return ret;
+        }
+        let at = at.max(self.first());
+        let mut idx = self.lerped_values.partition_point(|&t| t <= at);
+        // If there's already a key-frame at `at`, the hold starts from that one. (Because the
+        // lerped times are sorted, it's the last of the key-frames at `at`.)
+        let existing = self.lerped_values[idx - 1] == at;
+        let orig = if existing {
+            self.original_values[idx - 1]
+        } else {
+            self.unlerp_clamped(at)
+        };
+        for t in &mut ret.lerped_values[idx..] {
+            *t += len;
+        }
+        if !existing {
+            ret.insert_keyframe(idx, orig, at);
+            idx += 1;
+        }
+        ret.original_values.insert(idx, orig);
+        ret.lerped_values.insert(idx, at + len);
+        // The hold is the interval before the new key-frame, and the interval that used to be
+        // there comes after it.
+        ret.renumber_easings(idx - 1, true);
+        ret
+    }
+
+    /// Returns a clone of this `Lerp` without the hold that contains the lerped time `time`.
+    /// Everything after the hold happens earlier, to fill the gap.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use scribl_curves::{Lerp, Time, TimeDiff};
+    /// let t = |x| Time::from_micros(x);
+    /// let lerp = Lerp::identity(t(10), t(20));
+    /// let held = lerp.with_hold(t(15), TimeDiff::from_micros(5));
+    /// assert_eq!(held.without_hold(t(12)), held);
+    /// assert_eq!(held.without_hold(t(17)), lerp.with_keyframe(t(15)));
+    /// ```
+    pub fn without_hold(&self, time: Time) -> Lerp {
+        let mut ret = self.clone();
+        let idx = (1..self.original_values.len()).find(|&i| {
+            self.original_values[i - 1] == self.original_values[i]
+                && self.lerped_values[i - 1] <= time
+                && time <= self.lerped_values[i]
+                && self.lerped_values[i - 1] < self.lerped_values[i]
+        });
+        if let Some(idx) = idx {
+            let len = self.lerped_values[idx] - self.lerped_values[idx - 1];
+            ret.original_values.remove(idx);
+            ret.lerped_values.remove(idx);
+            ret.easings.remove(&(idx - 1));
+            ret.renumber_easings(idx, false);
+            for t in &mut ret.lerped_values[idx..] {
+                *t -= len;
+            }
+        }
+        ret
+    }
+
+    // Inserts a new key-frame at position `idx`, splitting the interval between the key-frames on
+    // either side. Each half gets the part of the split interval's easing curve that it covers.
+    fn insert_keyframe(&mut self, idx: usize, original: Time, lerped: Time) {
+        let split = if idx > 0 {
+            self.easings.remove(&(idx - 1)).map(|e| {
+                let start = self.original_values[idx - 1];
+                let len = (self.original_values[idx] - start).as_micros() as f64;
+                e.split((original - start).as_micros() as f64 / len)
+            })
+        } else {
+            None
+        };
+        self.original_values.insert(idx, original);
+        self.lerped_values.insert(idx, lerped);
+        self.renumber_easings(idx, true);
+        if let Some((first, second)) = split {
+            self.easings.insert(idx - 1, first);
+            self.easings.insert(idx, second);
+        }
+    }
+
+    // Updates the indices of the easings starting from the `idx`th interval, after a key-frame
+    // was inserted (if `inserted` is true) or removed before them.
+    fn renumber_easings(&mut self, idx: usize, inserted: bool) {
+        let later = self.easings.split_off(&idx);
+        self.easings.extend(
+            later
+                .into_iter()
+                .map(|(i, e)| (if inserted { i + 1 } else { i - 1 }, e)),
+        );
+    }
 }
 
 enum LerpResult {
@@ -253,7 +724,15 @@ enum LerpResult {
     Interval(Time, Time),
 }
 
-fn lerp_interval(t: Time, orig: &[Time], new: &[Time]) -> LerpResult {
+// Maps `t` from the times in `orig` to the times in `new`. Between key-frames `i` and `i + 1`, the
+// fraction `x` of the way from `orig[i]` to `orig[i + 1]` gets mapped to `ease(i, x)` of the way
+// from `new[i]` to `new[i + 1]`.
+fn lerp_interval(
+    t: Time,
+    orig: &[Time],
+    new: &[Time],
+    ease: impl Fn(usize, f64) -> f64,
+) -> LerpResult {
     debug_assert!(orig.len() == new.len());
 
     if t > *orig.last().unwrap() {
@@ -268,9 +747,11 @@ fn lerp_interval(t: Time, orig: &[Time], new: &[Time]) -> LerpResult {
             LerpResult::Interval(new[begin], new[end])
         } else {
             debug_assert!(end == begin + 1);
-            let orig_span = TimeSpan::new(orig[begin], orig[end]);
-            let new_span = TimeSpan::new(new[begin], new[end]);
-            LerpResult::SingleTime(orig_span.interpolate_to(t, new_span))
+            let ratio =
+                (t - orig[begin]).as_micros() as f64 / (orig[end] - orig[begin]).as_micros() as f64;
+            let len = (new[end] - new[begin]).as_micros() as f64;
+            let diff = TimeDiff::from_micros((len * ease(begin, ratio)) as i64);
+            LerpResult::SingleTime(new[begin] + diff)
         }
     }
 }
@@ -360,6 +841,110 @@ mod tests {
         assert_eq!(out.lerped_values, tvec![0, 150, 200]);
     }
 
+    #[test]
+    fn easing_round_trip() {
+        for &easing in &Easing::ALL {
+            for i in 0..=100 {
+                let x = i as f64 / 100.0;
+                assert!((easing.invert(easing.apply(x)) - x).abs() < 1e-9);
+            }
+        }
+
+        let lerp = Lerp::new(tvec![0, 1000, 2000], tvec![0, 3000, 4000])
+            .with_easing(0, Easing::Cubic)
+            .with_easing(1, Easing::EaseOut);
+        for i in 0..=40 {
+            let lerped = lerp.lerp(t(i * 50)).unwrap();
+            let back = lerp.unlerp(lerped).unwrap().as_micros();
+            assert!((back - i * 50).abs() <= 3);
+        }
+    }
+
+    #[test]
+    fn keyframes_keep_easing() {
+        let lerp = Lerp::new(tvec![0, 100], tvec![0, 100]).with_easing(0, Easing::EaseIn);
+        let out = lerp.with_keyframe(t(50));
+        assert_eq!(out.easing(0), Easing::EaseIn);
+        assert_eq!(out.easing(1), Easing::EaseIn);
+        assert_eq!(out.lerp(t(50)), lerp.lerp(t(50)));
+
+        // Removing the key-frame again puts the two halves back together.
+        assert_eq!(out.without_keyframe(1), lerp);
+
+        let out = out.with_easing(1, Easing::Linear).without_keyframe(1);
+        assert_eq!(out, lerp);
+    }
+
+    #[test]
+    fn keyframes_keep_mapping() {
+        for &easing in &Easing::ALL {
+            let lerp = Lerp::new(tvec![0, 1000, 2000], tvec![0, 3000, 6000])
+                .with_easing(0, easing)
+                .with_easing(1, Easing::Cubic);
+            let out = lerp
+                .with_keyframe(t(300))
+                .with_keyframe(t(700))
+                .with_keyframe(t(1500));
+            assert_eq!(out.original_values.len(), 6);
+            for i in 0..=200 {
+                let time = t(i * 10);
+                let diff = (out.lerp(time).unwrap() - lerp.lerp(time).unwrap()).as_micros();
+                assert!(diff.abs() <= 1, "{:?} at {:?}", easing, time);
+                let time = t(i * 30);
+                let diff = (out.unlerp(time).unwrap() - lerp.unlerp(time).unwrap()).as_micros();
+                assert!(diff.abs() <= 1, "{:?} at {:?}", easing, time);
+            }
+        }
+    }
+
+    #[test]
+    fn holds_keep_easing() {
+        let lerp = Lerp::new(tvec![0, 100, 200], tvec![0, 100, 200])
+            .with_easing(0, Easing::EaseIn)
+            .with_easing(1, Easing::EaseOut);
+
+        // A hold at a key-frame shifts the easings after it.
+        let out = lerp.with_hold(t(100), TimeDiff::from_micros(50));
+        assert_eq!(out.easing(0), Easing::EaseIn);
+        assert_eq!(out.easing(1), Easing::Linear);
+        assert_eq!(out.easing(2), Easing::EaseOut);
+        assert_eq!(
+            out.lerp(t(150)),
+            lerp.lerp(t(150)).map(|t| t + TimeDiff::from_micros(50))
+        );
+        assert_eq!(out.without_hold(t(120)), lerp);
+
+        // Adding a key-frame after the hold only affects the interval that it splits.
+        let keyed = out.with_keyframe(t(150));
+        assert_eq!(keyed.easing(2), Easing::EaseOut);
+        assert_eq!(keyed.easing(3), Easing::EaseOut);
+        assert_eq!(keyed.without_keyframe(3), out);
+    }
+
+    #[test]
+    fn holds() {
+        let lerp = Lerp::new(tvec![0, 50, 100], tvec![0, 100, 200]);
+
+        // A hold in the middle of an interval adds two key-frames.
+        let out = lerp.with_hold(t(50), TimeDiff::from_micros(30));
+        assert_eq!(out.original_values, tvec![0, 25, 25, 50, 100]);
+        assert_eq!(out.lerped_values, tvec![0, 50, 80, 130, 230]);
+        assert_eq!(out.lerp(t(25)), Some(t(50)));
+        assert_eq!(out.unlerp(t(70)), Some(t(25)));
+
+        // A hold at an existing key-frame only adds one.
+        let out = lerp.with_hold(t(100), TimeDiff::from_micros(30));
+        assert_eq!(out.original_values, tvec![0, 50, 50, 100]);
+        assert_eq!(out.lerped_values, tvec![0, 100, 130, 230]);
+        assert_eq!(out.without_hold(t(100)), lerp);
+
+        // Holds before the start delay everything, and holds after the end do nothing.
+        let out = lerp.with_hold(t(0), TimeDiff::from_micros(30));
+        assert_eq!(out.holds().collect::<Vec<_>>(), vec![(t(0), t(30))]);
+        assert_eq!(out.lerp(t(50)), Some(t(130)));
+        assert_eq!(lerp.with_hold(t(200), TimeDiff::from_micros(30)), lerp);
+    }
+
     #[test]
     fn unlerp() {
         let lerp = Lerp::new(tvec![1, 101], tvec![201, 301]);
@@ -1,7 +1,16 @@
+//! The data structures behind scribl animations: strokes, the snippets that they're grouped into,
+//! and the times at which everything happens.
+//!
+//! To show an animation in a program other than scribl, see the [`render`] module.
+
+mod brush;
 mod curve;
 mod draw_snippet;
 mod effect;
+mod group;
+mod label;
 mod lerp;
+pub mod render;
 pub mod save;
 mod shape_detect;
 mod simplify;
@@ -9,11 +18,17 @@ mod smooth;
 mod span_cursor;
 mod time;
 
+pub use crate::brush::Brush;
 pub use crate::curve::{StrokeInProgress, StrokeRef, StrokeSeq, StrokeStyle};
 pub use crate::draw_snippet::{DrawCursor, DrawSnippet, DrawSnippetId, DrawSnippets};
-pub use crate::effect::{Effect, Effects, FadeEffect};
-pub use crate::lerp::Lerp;
+pub use crate::effect::{
+    find_effect, registry, Effect, Effects, FadeEffect, NamedEffect, StrokeEffect, Wiggle,
+};
+pub use crate::group::GroupId;
+pub use crate::label::LabelColor;
+pub use crate::lerp::{Easing, Lerp};
+pub use crate::render::{render_frame, StrokePaint, VisibleStroke};
 pub use crate::simplify::simplify;
 pub use crate::smooth::smooth;
 pub use crate::span_cursor::{Cursor, Span};
-pub use crate::time::{Time, TimeDiff, TimeSpan};
+pub use crate::time::{ParseTimeError, Time, TimeDiff, TimeSpan};
@@ -0,0 +1,302 @@
+//! Rendering animations without the rest of scribl.
+//!
+//! This module is for programs (like a web viewer) that want to show frames of a scribl animation
+//! but don't need any of the editor. There are two levels of API:
+//!
+//! - [`render_frame`] draws everything that is visible at a given time onto a
+//!   [`piet::RenderContext`]. This is exactly what scribl itself does when exporting a video.
+//! - [`DrawSnippets::visible_strokes`] (and the corresponding methods on [`DrawSnippet`] and
+//!   [`StrokeSeq`]) return the visible strokes one by one, for renderers that aren't based on
+//!   piet. Each [`VisibleStroke`] can be turned into polylines with
+//!   [`VisibleStroke::to_polylines`], or (to draw the brushes exactly the way that scribl does)
+//!   into a [`StrokePaint`] with [`VisibleStroke::paint`].
+//!
+//! Note that pages (which hide snippets that belong to other pages) are handled by scribl and not
+//! by this crate, so anything that a saved file shows on a page other than the current one will
+//! also be included here.
+//!
+//! # Example
+//!
+//! ```
+//! use scribl_curves::{render::render_frame, DrawSnippets, Time};
+//!
+//! fn draw(ctx: &mut impl piet::RenderContext, snippets: &DrawSnippets, time: Time) {
+//!     // The animation's coordinates range from 0.0 to 1.0 horizontally (and from 0.0 to 0.75
+//!     // vertically), so scale them up to the size of the canvas.
+//!     ctx.transform(kurbo::Affine::scale(1920.0));
+//!     render_frame(ctx, snippets, time);
+//! }
+//! ```
+//!
+//! [`StrokeSeq`]: crate::StrokeSeq
+//! [`DrawSnippet`]: crate::DrawSnippet
+//! [`DrawSnippets::visible_strokes`]: crate::DrawSnippets::visible_strokes
+
+use kurbo::{BezPath, PathEl, Point, Shape};
+use piet::{Color, LineCap, LineJoin, RenderContext};
+use std::borrow::Cow;
+
+use crate::{Brush, DrawSnippets, Time};
+
+/// A stroke (or the part of a stroke) that is visible at some particular time.
+#[derive(Clone, Debug)]
+pub struct VisibleStroke {
+    /// The path of the stroke. If the stroke is still being drawn, this only contains the part
+    /// that has been drawn so far.
+    pub path: BezPath,
+
+    /// The color of the stroke, ignoring any fading.
+    pub color: Color,
+
+    /// The opacity of the stroke, between 0.0 and 1.0. This comes from the stroke's effects: it's
+    /// 1.0 unless the stroke is fading out.
+    pub alpha: f64,
+
+    /// The width of the pen.
+    pub thickness: f64,
+
+    /// The kind of pen. Renderers that don't support textured brushes can just treat every
+    /// stroke as [`Brush::Round`].
+    pub brush: Brush,
+}
+
+impl VisibleStroke {
+    /// The color that the stroke should be painted with, taking the fade into account.
+    pub fn paint_color(&self) -> Color {
+        if self.alpha < 1.0 {
+            self.color.clone().with_alpha(self.alpha)
+        } else {
+            self.color.clone()
+        }
+    }
+
+    /// Draws this stroke onto a render context.
+    pub fn render(&self, ctx: &mut impl RenderContext) {
+        self.paint().render(ctx);
+    }
+
+    /// Describes how this stroke gets drawn, taking its brush into account.
+    pub fn paint(&self) -> StrokePaint<'_> {
+        self.brush.paint(self)
+    }
+
+    /// Approximates the stroke's path by polylines, where every point is within `tolerance` of
+    /// the true path.
+    ///
+    /// There is one polyline for every connected piece of the path, so in practice there is
+    /// exactly one. The polylines describe the center of the stroke: to get its outline, draw
+    /// them with a width of [`thickness`](VisibleStroke::thickness) and with round caps and joins.
+    pub fn to_polylines(&self, tolerance: f64) -> Vec<Vec<Point>> {
+        let mut ret: Vec<Vec<Point>> = Vec::new();
+        kurbo::flatten(&self.path, tolerance, |el| match el {
+            PathEl::MoveTo(p) => ret.push(vec![p]),
+            PathEl::LineTo(p) => {
+                if let Some(line) = ret.last_mut() {
+                    line.push(p);
+                }
+            }
+            // `flatten` only produces moves and lines, and our paths are never closed.
+            _ => {}
+        });
+        ret
+    }
+
+    /// Is the point `p` on this stroke (or within `tolerance` of it)?
+    ///
+    /// This is for hit-testing, so it treats every stroke as being drawn with a round pen of
+    /// width [`thickness`](VisibleStroke::thickness). It ignores the fade, so a nearly-transparent
+    /// stroke can still be hit.
+    pub fn hit_test(&self, p: Point, tolerance: f64) -> bool {
+        let max_dist = self.thickness / 2.0 + tolerance;
+        let bbox = self.path.bounding_box().inflate(max_dist, max_dist);
+        if !bbox.contains(p) {
+            return false;
+        }
+        // The flattening error is small compared to the tolerance that the UI uses.
+        let max_dist2 = max_dist * max_dist;
+        self.to_polylines(tolerance.max(1e-6) / 4.0)
+            .iter()
+            .any(|line| polyline_dist2(line, p) <= max_dist2)
+    }
+}
+
+/// A single drawing operation, which is how a [`VisibleStroke`] gets drawn.
+///
+/// Every brush is drawn with one of these, so a renderer that isn't based on piet only needs to
+/// support these two operations in order to draw exactly what scribl draws.
+#[derive(Clone, Debug)]
+pub enum StrokePaint<'a> {
+    /// Draw along `path` with a pen that is `width` wide.
+    Stroke {
+        path: Cow<'a, BezPath>,
+        color: Color,
+        width: f64,
+        style: piet::StrokeStyle,
+    },
+    /// Fill in the inside of `path`, using the non-zero winding rule.
+    Fill {
+        path: Cow<'a, BezPath>,
+        color: Color,
+    },
+}
+
+impl<'a> StrokePaint<'a> {
+    /// Draws this onto a render context.
+    pub fn render(&self, ctx: &mut impl RenderContext) {
+        match self {
+            StrokePaint::Stroke {
+                path,
+                color,
+                width,
+                style,
+            } => ctx.stroke_styled(path.as_ref(), color, *width, style),
+            StrokePaint::Fill { path, color } => ctx.fill(path.as_ref(), color),
+        }
+    }
+}
+
+/// The squared distance from `p` to the closest point on a line segment.
+fn segment_dist2(a: Point, b: Point, p: Point) -> f64 {
+    let ab = b - a;
+    let len2 = ab.hypot2();
+    let t = if len2 > 0.0 {
+        ((p - a).dot(ab) / len2).max(0.0).min(1.0)
+    } else {
+        0.0
+    };
+    (a + ab * t - p).hypot2()
+}
+
+/// The squared distance from `p` to the closest point on a polyline.
+fn polyline_dist2(line: &[Point], p: Point) -> f64 {
+    match line {
+        [] => f64::INFINITY,
+        [q] => (*q - p).hypot2(),
+        _ => line
+            .windows(2)
+            .map(|w| segment_dist2(w[0], w[1], p))
+            .fold(f64::INFINITY, f64::min),
+    }
+}
+
+/// The stroke style that scribl uses for drawing strokes with the round brush.
+pub fn stroke_style() -> piet::StrokeStyle {
+    piet::StrokeStyle {
+        line_join: LineJoin::Round,
+        line_cap: LineCap::Round,
+        ..piet::StrokeStyle::new()
+    }
+}
+
+/// Draws all the snippets that are visible at time `time`.
+///
+/// This doesn't clear the background first.
+pub fn render_frame(ctx: &mut impl RenderContext, snippets: &DrawSnippets, time: Time) {
+    for stroke in snippets.visible_strokes(time) {
+        stroke.render(ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::tests::basic_curve;
+    use crate::{DrawSnippet, Effect, Effects, FadeEffect, StrokeInProgress, StrokeSeq};
+    use crate::{StrokeStyle, TimeDiff};
+
+    fn t(us: i64) -> Time {
+        Time::from_micros(us)
+    }
+
+    #[test]
+    fn visible_strokes() {
+        let curve = basic_curve();
+        assert_eq!(curve.visible_strokes(t(0)).count(), 0);
+        assert_eq!(curve.visible_strokes(t(4)).count(), 1);
+        assert_eq!(curve.visible_strokes(t(7)).count(), 2);
+        assert_eq!(curve.visible_strokes(t(100)).count(), 2);
+        assert!(curve.visible_strokes(t(100)).all(|s| s.alpha == 1.0));
+    }
+
+    #[test]
+    fn snippets() {
+        let snip = DrawSnippet::new(basic_curve());
+        let (snips, _) = DrawSnippets::default().with_new_snippet(snip.clone());
+        let (snips, _) = snips.with_new_snippet(snip.shifted(TimeDiff::from_micros(10)));
+        assert_eq!(snips.visible_strokes(t(9)).count(), 2);
+        assert_eq!(snips.visible_strokes(t(100)).count(), 4);
+    }
+
+    #[test]
+    fn fading() {
+        let mut effects = Effects::default();
+        effects.add(Effect::Fade(FadeEffect {
+            pause: TimeDiff::from_micros(10),
+            fade: TimeDiff::from_micros(10),
+        }));
+        let style = StrokeStyle {
+            color: Color::WHITE,
+            thickness: 1.0,
+            effects,
+            brush: Brush::Round,
+        };
+        let mut stroke = StrokeInProgress::new();
+        stroke.add_point(Point::new(0.0, 0.0), t(0));
+        stroke.add_point(Point::new(1.0, 0.0), t(10));
+        let mut curve = StrokeSeq::new();
+        curve.append_stroke(stroke, style, false, 0.01);
+
+        let alpha = |time| curve.visible_strokes(t(time)).next().map(|s| s.alpha);
+        assert_eq!(alpha(15), Some(1.0));
+        assert_eq!(alpha(25), Some(0.5));
+        // Strokes that have completely faded out aren't visible.
+        assert_eq!(alpha(30), None);
+    }
+
+    #[test]
+    fn polylines() {
+        let curve = basic_curve();
+        let strokes: Vec<_> = curve.visible_strokes(t(100)).collect();
+        let lines = strokes[1].to_polylines(0.01);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0][0], Point::new(4.0, 4.0));
+        assert_eq!(*lines[0].last().unwrap(), Point::new(2.0, 2.0));
+    }
+
+    #[test]
+    fn paint() {
+        let curve = basic_curve();
+        let stroke = curve.visible_strokes(t(100)).next().unwrap();
+        match stroke.paint() {
+            StrokePaint::Stroke {
+                path, width, style, ..
+            } => {
+                assert_eq!(path.elements(), stroke.path.elements());
+                assert_eq!(width, stroke.thickness);
+                assert_eq!(style.line_cap, LineCap::Round);
+            }
+            StrokePaint::Fill { .. } => panic!("round brushes should stroke"),
+        }
+
+        let chalk = VisibleStroke {
+            brush: Brush::Chalk,
+            ..stroke
+        };
+        assert!(matches!(chalk.paint(), StrokePaint::Fill { .. }));
+    }
+
+    #[test]
+    fn hit_test() {
+        let curve = basic_curve();
+        let strokes: Vec<_> = curve.visible_strokes(t(100)).collect();
+        // The first stroke is a diagonal line from (0, 0) to (2, 2), with thickness 1.
+        assert!(strokes[0].hit_test(Point::new(1.0, 1.0), 0.0));
+        assert!(strokes[0].hit_test(Point::new(1.2, 0.9), 0.0));
+        assert!(!strokes[0].hit_test(Point::new(1.0, 0.0), 0.0));
+        assert!(strokes[0].hit_test(Point::new(1.0, 0.0), 0.3));
+        // The ends are round.
+        assert!(strokes[0].hit_test(Point::new(0.0, -0.4), 0.0));
+        assert!(!strokes[0].hit_test(Point::new(3.0, 3.0), 0.1));
+        assert!(strokes[1].hit_test(Point::new(3.0, 3.0), 0.1));
+    }
+}
@@ -1,6 +1,5 @@
-use druid::im::OrdMap;
-use druid::kurbo::BezPath;
-use druid::Point;
+use im::OrdMap;
+use kurbo::{BezPath, Point};
 use serde::Deserialize;
 use std::collections::BTreeMap;
 
@@ -24,6 +23,7 @@ pub mod v0 {
             crate::Lerp {
                 original_values: lerp.original_values,
                 lerped_values: lerp.lerped_values,
+                easings: BTreeMap::new(),
             }
         }
     }
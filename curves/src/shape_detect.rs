@@ -1,4 +1,4 @@
-use druid::kurbo::{BezPath, Point, Vec2};
+use kurbo::{BezPath, Point, Vec2};
 use std::f64::consts::PI;
 
 use crate::{StrokeInProgress, Time};
@@ -1,4 +1,4 @@
-use druid::kurbo::{Line, Point, Rect};
+use kurbo::{Line, Point, Rect};
 
 // Squared distance from the point `p` to the line *segment* `line`.
 fn sq_distance(p: Point, line: Line) -> f64 {
@@ -1,7 +1,4 @@
-use druid::{
-    kurbo::{BezPath, Point},
-    Vec2,
-};
+use kurbo::{BezPath, Point, Vec2};
 
 /// Turns a polyline into a (mostly) smooth curve through the same points.
 /// The returned curve will consist only of cubic segments.
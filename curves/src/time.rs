@@ -1,28 +1,30 @@
-use druid::Data;
 use serde::{Deserialize, Serialize};
 use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
 
 /// The clock of a scribl.
 // This is measured in microseconds from the beginning. We enforce that the value is non-negative,
 // but arithmetic is more convenient with signed types.
 #[derive(
-    Copy, Clone, Data, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize,
+    Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize,
 )]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
 #[serde(transparent)]
 pub struct Time(i64);
 
 /// The difference between two [`Time`]s. Unlike `std::time::Duration`, this
 /// can be negative.
 #[derive(
-    Copy, Clone, Data, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize,
+    Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize,
 )]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
 #[serde(transparent)]
 pub struct TimeDiff(i64);
 
 /// An interval of times.
-#[derive(
-    Copy, Clone, Data, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize,
-)]
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize)]
+#[cfg_attr(feature = "druid", derive(druid::Data))]
 pub struct TimeSpan {
     start: Time,
     end: Time,
@@ -44,14 +46,39 @@ impl Time {
         gstreamer::ClockTime::from_useconds(self.as_micros().try_into().unwrap())
     }
 
+    /// The time at which a video frame starts.
+    ///
+    /// We round up to the nearest microsecond, so that the time really does belong to the frame.
+    ///
+    /// # Example
+    /// ```rust
+    /// use scribl_curves::Time;
+    /// let t = Time::from_video_frame(1, 30.0);
+    /// assert_eq!(t, Time::from_micros(33_334));
+    /// assert_eq!(t.as_video_frame(30.0), 1);
+    /// ```
     pub fn from_video_frame(frame: u32, fps: f64) -> Time {
-        Time::from_micros((frame as f64 / fps * 1e6) as i64)
+        Time::from_micros((frame as f64 / fps * 1e6).ceil() as i64)
     }
 
+    /// The video frame that this time belongs to.
     pub fn as_video_frame(&self, fps: f64) -> u32 {
         (self.0 as f64 * fps / 1e6) as u32
     }
 
+    /// Rounds this time down to the beginning of its video frame.
+    ///
+    /// # Example
+    /// ```rust
+    /// use scribl_curves::Time;
+    /// let t = Time::from_micros(50_000).quantized(30.0);
+    /// assert_eq!(t, Time::from_micros(33_334));
+    /// assert_eq!(t.quantized(30.0), t);
+    /// ```
+    pub fn quantized(&self, fps: f64) -> Time {
+        Time::from_video_frame(self.as_video_frame(fps), fps)
+    }
+
     pub fn as_audio_idx(&self, sample_rate: u32) -> usize {
         (self.0 as f64 / 1e6 * sample_rate as f64) as usize
     }
@@ -140,6 +167,182 @@ impl std::ops::Sub<Time> for Time {
     }
 }
 
+/// The error returned when text can't be parsed as a [`Time`] or a [`TimeDiff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseTimeError {
+    input: String,
+}
+
+impl fmt::Display for ParseTimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "expected a time like 1:23.5, 83.5s, or 120@30 (frame 120 at 30 fps), not \"{}\"",
+            self.input
+        )
+    }
+}
+
+impl std::error::Error for ParseTimeError {}
+
+/// Parses a non-negative decimal number of seconds, like "83" or "83.45", into microseconds. We
+/// do this by hand (instead of going through `f64`) so that the result is exactly rounded to
+/// the nearest microsecond.
+fn parse_decimal_micros(s: &str) -> Option<i64> {
+    let (int, frac) = match s.split_once('.') {
+        Some((int, frac)) => (int, frac),
+        None => (s, ""),
+    };
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if (int.is_empty() && frac.is_empty()) || !all_digits(int) || !all_digits(frac) {
+        return None;
+    }
+    let int: i64 = if int.is_empty() { 0 } else { int.parse().ok()? };
+    let mut micros = 0;
+    for (i, digit) in frac.bytes().take(6).enumerate() {
+        micros += (digit - b'0') as i64 * 10i64.pow(5 - i as u32);
+    }
+    // Round half up, based on the first digit that doesn't fit.
+    if frac.len() > 6 && frac.as_bytes()[6] >= b'5' {
+        micros += 1;
+    }
+    int.checked_mul(1_000_000)?.checked_add(micros)
+}
+
+/// Parses a non-negative time into microseconds. See [`Time::from_str`] for the formats.
+fn parse_micros(s: &str) -> Option<i64> {
+    if let Some((frame, fps)) = s.split_once('@') {
+        if !frame.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let frame: u32 = frame.parse().ok()?;
+        let fps = parse_decimal_micros(fps)? as f64 / 1e6;
+        if fps <= 0.0 {
+            return None;
+        }
+        return Some(Time::from_video_frame(frame, fps).as_micros());
+    }
+
+    if let Some(secs) = s.strip_suffix('s') {
+        return parse_decimal_micros(secs);
+    }
+
+    let parts: Vec<_> = s.split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    let (last, init) = parts.split_last()?;
+    let mut micros: i64 = 0;
+    for (i, part) in init.iter().enumerate() {
+        if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let x: i64 = part.parse().ok()?;
+        // Only the first part (the hours or the minutes) is allowed to be 60 or more.
+        if i > 0 && x >= 60 {
+            return None;
+        }
+        micros = micros.checked_add(x)?.checked_mul(60)?;
+    }
+    // Check the whole seconds, not the rounded result, so that "0:59.9999999" is allowed.
+    let whole_secs = last.split('.').next().unwrap_or("");
+    if !init.is_empty() && whole_secs.parse::<u64>().map_or(false, |secs| secs >= 60) {
+        return None;
+    }
+    micros
+        .checked_mul(1_000_000)?
+        .checked_add(parse_decimal_micros(last)?)
+}
+
+/// Formats a non-negative number of microseconds like "1:23.450", rounding to the nearest
+/// millisecond.
+fn format_micros(f: &mut fmt::Formatter, micros: i64) -> fmt::Result {
+    let millis = (micros + 500) / 1000;
+    let mins = millis / 60_000;
+    let secs = (millis / 1000) % 60;
+    write!(f, "{}:{:02}.{:03}", mins, secs, millis % 1000)
+}
+
+impl FromStr for Time {
+    type Err = ParseTimeError;
+
+    /// Parses a time. Leading and trailing whitespace is ignored, and the supported formats are:
+    ///
+    /// - minutes and seconds, like "1:23.45", or hours, minutes and seconds, like "1:01:23";
+    /// - seconds, like "83.45" or "83.45s";
+    /// - a video frame and a frame rate, like "120@30" for the beginning of frame 120 at 30
+    ///   frames per second (see [`Time::from_video_frame`]).
+    ///
+    /// Seconds get rounded to the nearest microsecond.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use scribl_curves::Time;
+    /// let t = |s: &str| s.parse::<Time>().unwrap();
+    /// assert_eq!(t("1:23.45"), Time::from_micros(83_450_000));
+    /// assert_eq!(t("83.45s"), Time::from_micros(83_450_000));
+    /// assert_eq!(t("1:00:00"), Time::from_micros(3_600_000_000));
+    /// assert_eq!(t("1@30"), Time::from_micros(33_334));
+    /// assert!("1:75".parse::<Time>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Time, ParseTimeError> {
+        parse_micros(s.trim())
+            .map(Time)
+            .ok_or_else(|| ParseTimeError {
+                input: s.to_owned(),
+            })
+    }
+}
+
+impl FromStr for TimeDiff {
+    type Err = ParseTimeError;
+
+    /// Parses a time difference. This supports the same formats as [`Time::from_str`], but with
+    /// an optional "-" in front.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use scribl_curves::TimeDiff;
+    /// let d = |s: &str| s.parse::<TimeDiff>().unwrap();
+    /// assert_eq!(d("1.5s"), TimeDiff::from_micros(1_500_000));
+    /// assert_eq!(d("-0:01.5"), TimeDiff::from_micros(-1_500_000));
+    /// ```
+    fn from_str(s: &str) -> Result<TimeDiff, ParseTimeError> {
+        let trimmed = s.trim();
+        let (sign, rest) = match trimmed.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, trimmed),
+        };
+        parse_micros(rest)
+            .map(|us| TimeDiff(sign * us))
+            .ok_or_else(|| ParseTimeError {
+                input: s.to_owned(),
+            })
+    }
+}
+
+/// Formats the time like "1:23.450" (minutes, seconds and milliseconds), rounding to the nearest
+/// millisecond. The minutes aren't split into hours, so an hour and a half is "90:00.000".
+///
+/// Parsing the formatted text gives back the original time, rounded to the nearest millisecond.
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        format_micros(f, self.0)
+    }
+}
+
+/// Formats the time difference like a [`Time`], but with a "-" in front if it's negative.
+impl fmt::Display for TimeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 < 0 {
+            write!(f, "-")?;
+        }
+        format_micros(f, self.0.saturating_abs())
+    }
+}
+
 impl TimeSpan {
     /// Creates a new `TimeSpan` representing the closed interval `[start, end]`.
     ///
@@ -190,3 +393,123 @@ impl TimeSpan {
         Time(other.start.0 + ((other.end - other.start).0 as f64 * ratio) as i64)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn t(us: i64) -> Time {
+        Time::from_micros(us)
+    }
+
+    fn parse(s: &str) -> Option<Time> {
+        s.parse().ok()
+    }
+
+    #[test]
+    fn parse_formats() {
+        assert_eq!(parse("83"), Some(t(83_000_000)));
+        assert_eq!(parse("83.45"), Some(t(83_450_000)));
+        assert_eq!(parse("83.45s"), Some(t(83_450_000)));
+        assert_eq!(parse(".5"), Some(t(500_000)));
+        assert_eq!(parse("5."), Some(t(5_000_000)));
+        assert_eq!(parse("1:23.450"), Some(t(83_450_000)));
+        assert_eq!(parse("0:05"), Some(t(5_000_000)));
+        assert_eq!(parse("90:00"), Some(t(5_400_000_000)));
+        assert_eq!(parse("1:01:23"), Some(t(3_683_000_000)));
+        assert_eq!(parse("  1:23.5 "), Some(t(83_500_000)));
+        assert_eq!(parse("0@30"), Some(Time::ZERO));
+        assert_eq!(parse("30@30"), Some(t(1_000_000)));
+        assert_eq!(parse("1@29.97"), Some(Time::from_video_frame(1, 29.97)));
+    }
+
+    #[test]
+    fn parse_errors() {
+        for s in &[
+            "",
+            ".",
+            "s",
+            "-1",
+            "1e3",
+            "inf",
+            "NaN",
+            "1:",
+            ":1",
+            "1::2",
+            "1:2:3:4",
+            "1:60",
+            "1:60:00",
+            "1:2.5:3",
+            "1.5:00",
+            "+1",
+            "1 2",
+            "1:23s",
+            "1@",
+            "@30",
+            "1@0",
+            "-1@30",
+            "1.5@30",
+            "99999999999999999999",
+        ] {
+            assert_eq!(parse(s), None, "{:?} shouldn't parse", s);
+        }
+        let err = "nope".parse::<Time>().unwrap_err();
+        assert!(err.to_string().contains("\"nope\""));
+    }
+
+    #[test]
+    fn parse_rounding() {
+        // Seconds get rounded to the nearest microsecond, without any floating-point error.
+        assert_eq!(parse("0.0000014"), Some(t(1)));
+        assert_eq!(parse("0.0000015"), Some(t(2)));
+        assert_eq!(parse("0.1234565"), Some(t(123_457)));
+        assert_eq!(parse("0.3"), Some(t(300_000)));
+        assert_eq!(parse("1:00.9999999"), Some(t(61_000_000)));
+        assert_eq!(parse("0:59.9999999"), Some(t(60_000_000)));
+    }
+
+    #[test]
+    fn diffs() {
+        let d = |s: &str| s.parse::<TimeDiff>().ok();
+        assert_eq!(d("1.5"), Some(TimeDiff::from_micros(1_500_000)));
+        assert_eq!(d("-1.5"), Some(TimeDiff::from_micros(-1_500_000)));
+        assert_eq!(d("-0:01.5"), Some(TimeDiff::from_micros(-1_500_000)));
+        assert_eq!(d("--1"), None);
+        assert_eq!(d("- 1"), None);
+
+        assert_eq!(TimeDiff::from_micros(-1_500_000).to_string(), "-0:01.500");
+        assert_eq!(TimeDiff::from_micros(61_000_000).to_string(), "1:01.000");
+        assert_eq!(TimeDiff::ZERO.to_string(), "0:00.000");
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(Time::ZERO.to_string(), "0:00.000");
+        assert_eq!(t(83_450_000).to_string(), "1:23.450");
+        assert_eq!(t(5_400_000_000).to_string(), "90:00.000");
+        // Rounding to the nearest millisecond can carry all the way to the minutes.
+        assert_eq!(t(59_999_499).to_string(), "0:59.999");
+        assert_eq!(t(59_999_500).to_string(), "1:00.000");
+    }
+
+    #[test]
+    fn round_trip() {
+        for &us in &[
+            0,
+            1,
+            499,
+            500,
+            999_999,
+            83_450_000,
+            83_450_499,
+            3_599_999_999,
+        ] {
+            let time = t(us);
+            let parsed = parse(&time.to_string()).unwrap();
+            // The round trip is exact up to rounding to the nearest millisecond.
+            assert!((parsed - time).as_micros().abs() <= 500, "{}", us);
+            assert_eq!(parsed.as_micros() % 1000, 0);
+            assert_eq!(parsed.to_string(), time.to_string());
+        }
+    }
+}
@@ -1,16 +1,30 @@
 use druid::{AppDelegate, Command, DelegateCtx, Env, Handled, Target, WindowId};
 
 use crate::app_state::AppState;
+use crate::cmd;
+use crate::data::FinishedStatus;
+use crate::global_hotkeys::GlobalHotkeys;
+use crate::template::Template;
 use crate::EditorState;
 
 #[derive(Default)]
-pub struct Delegate {}
+pub struct Delegate {
+    // The system-wide hotkeys, if they're turned on.
+    hotkeys: Option<GlobalHotkeys>,
+}
+
+fn target_window(target: Target) -> Option<WindowId> {
+    match target {
+        Target::Window(id) => Some(id),
+        _ => None,
+    }
+}
 
 impl AppDelegate<AppState> for Delegate {
     fn command(
         &mut self,
         ctx: &mut DelegateCtx,
-        _target: Target,
+        target: Target,
         cmd: &Command,
         data: &mut AppState,
         _env: &Env,
@@ -20,6 +34,52 @@ impl AppDelegate<AppState> for Delegate {
             let window_desc = data.add_editor(EditorState::new(crate::config::load_config()));
             ctx.new_window(window_desc);
             Handled::Yes
+        } else if let Some(path) = cmd.get(cmd::NEW_FROM_TEMPLATE) {
+            match Template::load_from_path(path) {
+                Ok(template) => {
+                    let config = crate::config::load_config();
+                    let window_desc = data.add_editor(EditorState::from_template(template, config));
+                    ctx.new_window(window_desc);
+                }
+                Err(e) => {
+                    log::error!("failed to load template {}: {}", path.display(), e);
+                    if let Some(editor) = target_window(target).and_then(|id| data.editor_mut(id)) {
+                        editor.status.last_finished = Some(FinishedStatus::Error(format!(
+                            "Failed to load template: {}",
+                            e
+                        )));
+                    }
+                }
+            }
+            Handled::Yes
+        } else if let (Target::Global, Some(req)) = (target, cmd.get(cmd::REMOTE_REQUEST)) {
+            // Remote-control requests aren't meant for any window in particular.
+            match data.first_window() {
+                Some(id) => ctx.submit_command(cmd::REMOTE_REQUEST.with(req.clone()).to(id)),
+                None => req.reply(Err("there are no open windows".to_owned())),
+            }
+            Handled::Yes
+        } else if let Some(&enabled) = cmd.get(cmd::SET_GLOBAL_HOTKEYS) {
+            // Drop the old hotkeys before registering new ones, so they don't conflict.
+            self.hotkeys = None;
+            if enabled {
+                let config = crate::config::load_config().global_hotkeys;
+                match GlobalHotkeys::register(&config, ctx.get_external_handle()) {
+                    Ok(hotkeys) => self.hotkeys = Some(hotkeys),
+                    Err(e) => {
+                        log::error!("failed to register global hotkeys: {}", e);
+                        if let Some(editor) = data.first_window().and_then(|id| data.editor_mut(id))
+                        {
+                            editor.status.last_finished = Some(FinishedStatus::Error(format!(
+                                "Failed to register global hotkeys: {}",
+                                e
+                            )));
+                        }
+                    }
+                }
+            }
+            data.global_hotkeys = self.hotkeys.is_some();
+            Handled::Yes
         } else {
             Handled::No
         }
@@ -15,6 +15,9 @@ pub struct AppState {
     // for the editor. So we do it in two steps.
     editors: HashMap<u32, EditorState>,
     windows: HashMap<WindowId, u32>,
+
+    /// Are the system-wide hotkeys turned on?
+    pub global_hotkeys: bool,
 }
 
 // We can't use LensExt::Index here, because maps expect borrowed indices.
@@ -55,6 +58,14 @@ impl AppState {
         self.windows.get(&id).and_then(move |w| editors.get_mut(w))
     }
 
+    /// The window of the oldest editor that's still open.
+    pub fn first_window(&self) -> Option<WindowId> {
+        self.windows
+            .iter()
+            .min_by_key(|(_, editor_id)| **editor_id)
+            .map(|(window_id, _)| *window_id)
+    }
+
     pub fn remove_editor(&mut self, id: WindowId) {
         if let Some(editor_id) = self.windows.remove(&id) {
             self.editors.remove(&editor_id);
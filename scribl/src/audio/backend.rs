@@ -0,0 +1,310 @@
+use crossbeam_channel::{Receiver, Sender};
+use ebur128::EbuR128;
+use nnnoiseless::DenoiseState;
+use serde::Deserialize;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use scribl_curves::Time;
+
+use super::{AudioRecordingStatus, InputConfig, MissingElement, OutputData, SAMPLE_RATE};
+
+/// Which audio backend should we use?
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioBackendKind {
+    /// Use gstreamer if it works, and fall back to cpal if it doesn't.
+    Auto,
+    Gstreamer,
+    Cpal,
+}
+
+impl Default for AudioBackendKind {
+    fn default() -> AudioBackendKind {
+        AudioBackendKind::Auto
+    }
+}
+
+/// Something that went wrong while setting up the audio, with enough detail to track down the
+/// problem.
+#[derive(Clone, Debug)]
+pub struct AudioError {
+    /// What we were trying to do, like "initializing audio output".
+    pub action: String,
+    /// The gstreamer element (or audio device) that failed, if we know which one it was.
+    pub element: Option<String>,
+    /// The error message.
+    pub message: String,
+    /// Extra debugging information, if there is any.
+    pub debug: Option<String>,
+}
+
+impl AudioError {
+    pub fn new(action: &str, err: &anyhow::Error) -> AudioError {
+        AudioError {
+            action: action.to_owned(),
+            element: err
+                .downcast_ref::<MissingElement>()
+                .map(|m| format!("{} ({})", m.name, m.kind)),
+            message: format!("{:#}", err),
+            debug: None,
+        }
+    }
+
+    /// A plain-text description of the error.
+    pub fn diagnostics(&self) -> String {
+        let mut ret = format!("Error {}\n", self.action);
+        if let Some(element) = &self.element {
+            ret.push_str(&format!("Element: {}\n", element));
+        }
+        ret.push_str(&format!("Message: {}\n", self.message));
+        if let Some(debug) = &self.debug {
+            ret.push_str(&format!("Debug info: {}\n", debug));
+        }
+        ret
+    }
+
+    /// A plain-text description of some errors (and of the system they happened on), for pasting
+    /// into a bug report.
+    pub fn report(errors: &[AudioError]) -> String {
+        let mut ret = format!(
+            "scribl version: {}\nOS: {} ({})\n",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+        );
+        for e in errors {
+            ret.push('\n');
+            ret.push_str(&e.diagnostics());
+        }
+        ret
+    }
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "error {}: {}", self.action, self.message)
+    }
+}
+
+/// The things that an audio backend needs to be able to do. The audio thread owns a backend, and
+/// translates the commands that it receives into calls to these methods.
+///
+/// The backend is only responsible for getting samples in and out; all the post-processing of
+/// recorded audio happens in the audio thread.
+pub trait AudioBackend {
+    /// Returns true if the backend is able to play audio.
+    fn can_play(&self) -> bool;
+
+    /// Starts playing the given audio.
+    fn start_playing(&mut self, data: OutputData);
+
+    /// Stops playing audio.
+    fn stop_playing(&mut self);
+
+    /// Moves the playback position, and possibly changes the playback speed.
+    fn seek(&mut self, time: Time, velocity: f64);
+
+    /// Starts storing the input audio, processed according to `config`.
+    fn start_recording(&mut self, config: InputConfig);
+
+    /// Stops storing the input audio, and returns everything that was stored since
+    /// `start_recording`.
+    fn take_input_data(&mut self) -> InputData;
+
+    /// A channel on which the backend regularly sends information (like input levels) about the
+    /// audio that it's recording.
+    fn input_status(&self) -> Receiver<AudioRecordingStatus>;
+
+    /// Shuts down and restarts all the audio streams, without losing any recorded audio. This
+    /// might change the channel returned by `input_status`.
+    fn rebuild(&mut self);
+
+    /// Checks whether anything happened to the audio devices (for example, a device was unplugged)
+    /// since the last call, and tries to recover if necessary. Returns a description of what
+    /// happened, if there's something that the user should know about.
+    ///
+    /// If this returns `Some`, the channel returned by `input_status` might have changed.
+    fn check_devices(&mut self) -> Option<String>;
+
+    /// Returns all the errors that left the audio (partly) broken since the last call. These
+    /// are the ones that the user should be told about in detail, as opposed to the ones that
+    /// we recovered from.
+    fn take_errors(&mut self) -> Vec<AudioError>;
+}
+
+/// The audio that was recorded so far, along with some metadata.
+pub struct InputData {
+    pub buf: Vec<i16>,
+    /// For every frame (of size `DenoiseState::FRAME_SIZE`) in `buf`, we store an estimate of how
+    /// likely that frame was to contain speech.
+    pub vad: Vec<f32>,
+    /// The input audio from before noise removal, if the input config asks for it to be kept.
+    /// It's either empty or aligned with `buf`.
+    pub raw: Vec<i16>,
+    pub loudness: EbuR128,
+}
+
+impl InputData {
+    pub fn new() -> InputData {
+        InputData {
+            buf: Vec::new(),
+            vad: Vec::new(),
+            raw: Vec::new(),
+            // TODO: what are the failure cases for Ebur128::new?
+            loudness: EbuR128::new(
+                1,
+                SAMPLE_RATE,
+                ebur128::Mode::I | ebur128::Mode::M | ebur128::Mode::SAMPLE_PEAK,
+            )
+            .unwrap(),
+        }
+    }
+
+    fn append_buffer(&mut self, buf: &[i16], raw: &[i16], vad: &[f32]) -> AudioRecordingStatus {
+        // What are the error cases here?
+        if let Err(e) = self.loudness.add_frames_i16(buf) {
+            log::error!("failed to calculate loudness: {}", e);
+        }
+        self.vad.extend_from_slice(vad);
+        self.buf.extend_from_slice(buf);
+        self.raw.extend_from_slice(raw);
+
+        let peak = self.loudness.prev_sample_peak(0).ok();
+        AudioRecordingStatus {
+            vad: *self.vad.last().unwrap_or(&0.0),
+            loudness: peak
+                .map(|x| (x.log10() * 20.0) as f32)
+                .unwrap_or(-f32::INFINITY),
+            peak: peak.unwrap_or(0.0) as f32,
+        }
+    }
+}
+
+/// Processes the raw input audio (denoising it, if requested) and appends it to the shared
+/// `InputData`. This lives in the backend's input callback, and it's shared between backends.
+pub struct InputProcessor {
+    // This is how the input callback communicates the received audio back to the audio thread: it
+    // unlocks this mutex and appends its audio to the buffer. We're careful to only hold the mutex
+    // for as long as we need to copy the data in or out.
+    data: Arc<Mutex<InputData>>,
+    // We receive input configs on this channel when the input settings change, and `None` when we
+    // should stop storing the input audio.
+    config_rx: Receiver<Option<InputConfig>>,
+    // We regularly send messages about things like input levels on this channel.
+    status_tx: Sender<AudioRecordingStatus>,
+    config: Option<InputConfig>,
+
+    denoise_state: Box<DenoiseState<'static>>,
+    denoise_in_buf: Vec<f32>,
+    denoise_out_buf: Vec<f32>,
+    i16_buf: Vec<i16>,
+    raw_buf: Vec<i16>,
+    vad_buf: Vec<f32>,
+}
+
+impl InputProcessor {
+    pub fn new(
+        data: Arc<Mutex<InputData>>,
+        config_rx: Receiver<Option<InputConfig>>,
+        status_tx: Sender<AudioRecordingStatus>,
+    ) -> InputProcessor {
+        InputProcessor {
+            data,
+            config_rx,
+            status_tx,
+            config: None,
+            denoise_state: DenoiseState::new(),
+            denoise_in_buf: Vec::with_capacity(DenoiseState::FRAME_SIZE),
+            denoise_out_buf: vec![0.0; DenoiseState::FRAME_SIZE],
+            i16_buf: Vec::with_capacity(DenoiseState::FRAME_SIZE),
+            raw_buf: Vec::new(),
+            vad_buf: Vec::new(),
+        }
+    }
+
+    /// Processes a chunk of input audio. The samples should be mono, at our internal sample rate.
+    pub fn process(&mut self, samples: impl IntoIterator<Item = i16>) {
+        for c in self.config_rx.try_iter() {
+            self.config = c;
+        }
+        let (remove_noise, keep_raw) = match self.config.as_ref() {
+            Some(c) => (c.remove_noise, c.remove_noise && c.keep_raw),
+            None => {
+                // We aren't recording, but we keep the partial frame empty so that the next
+                // recording starts cleanly.
+                self.denoise_in_buf.clear();
+                return;
+            }
+        };
+
+        for sample in samples {
+            self.denoise_in_buf.push(sample as f32);
+            if self.denoise_in_buf.len() < DenoiseState::FRAME_SIZE {
+                continue;
+            }
+
+            let vad = if remove_noise {
+                self.denoise_state
+                    .process_frame(&mut self.denoise_out_buf, &self.denoise_in_buf)
+            } else {
+                self.denoise_out_buf.copy_from_slice(&self.denoise_in_buf);
+                1.0
+            };
+            if keep_raw {
+                self.raw_buf
+                    .extend(self.denoise_in_buf.iter().map(|x| x.round() as i16));
+            }
+            self.denoise_in_buf.clear();
+
+            self.vad_buf.push(vad);
+            for sample in &self.denoise_out_buf {
+                self.i16_buf.push(sample.round() as i16);
+            }
+        }
+
+        if !self.i16_buf.is_empty() {
+            let status = self.data.lock().unwrap().append_buffer(
+                &self.i16_buf,
+                &self.raw_buf,
+                &self.vad_buf,
+            );
+            let _ = self.status_tx.send(status);
+            self.i16_buf.clear();
+            self.raw_buf.clear();
+            self.vad_buf.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn missing_element() {
+        let missing = MissingElement {
+            kind: "scaletempo".to_owned(),
+            name: "playback-scale".to_owned(),
+        };
+        let err = AudioError::new(
+            "initializing audio output",
+            &anyhow!("no such element").context(missing),
+        );
+        assert_eq!(err.element.as_deref(), Some("playback-scale (scaletempo)"));
+        assert!(err.message.contains("missing a gstreamer plugin"));
+        assert!(err.message.contains("no such element"));
+
+        let report = AudioError::report(&[err]);
+        assert!(report.contains("Error initializing audio output"));
+        assert!(report.contains("Element: playback-scale (scaletempo)"));
+    }
+
+    #[test]
+    fn unknown_element() {
+        let err = AudioError::new("restarting audio", &anyhow!("no devices"));
+        assert_eq!(err.element, None);
+        assert_eq!(err.message, "no devices");
+    }
+}
@@ -0,0 +1,377 @@
+use anyhow::{anyhow, Result};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{
+    Sample, SampleFormat, SampleRate, Stream, StreamConfig, StreamError,
+    SupportedStreamConfigRange,
+};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::ops::DerefMut;
+use std::sync::{Arc, Mutex};
+
+use scribl_curves::{Cursor, Time};
+
+use super::backend::{AudioBackend, AudioError, InputData, InputProcessor};
+use super::{AudioRecordingStatus, InputConfig, OutputData, TalkSnippetId, SAMPLE_RATE};
+use crate::config::AudioSource;
+
+/// A fallback audio backend, for when gstreamer isn't working.
+///
+/// This is a lot less sophisticated than the gstreamer backend: we require the audio devices to
+/// support our internal sample rate (so we don't need to resample), and when playing at a speed
+/// other than 1.0 we don't preserve the pitch.
+pub struct CpalBackend {
+    streams: Streams,
+
+    // This is shared with the output callback.
+    playback: Arc<Mutex<PlaybackState>>,
+    // This is shared with the input callback.
+    input_data: Arc<Mutex<InputData>>,
+    // If we're currently recording, this is the config that we're recording with.
+    recording: Option<InputConfig>,
+    // The source that the input stream records from.
+    source: AudioSource,
+
+    // The stream callbacks send their errors along here.
+    error_tx: Sender<StreamError>,
+    error_rx: Receiver<StreamError>,
+    // The errors that we couldn't recover from, and that we haven't told anyone about yet.
+    errors: Vec<AudioError>,
+}
+
+/// The parts of the backend that get recreated if the audio devices change.
+struct Streams {
+    // We need to keep the streams alive, but we never need to touch them after creating them.
+    _output: Stream,
+    _input: Stream,
+
+    input_tx: Sender<Option<InputConfig>>,
+    input_status_rx: Receiver<AudioRecordingStatus>,
+}
+
+/// The state of the audio that's currently playing.
+struct PlaybackState {
+    data: OutputData,
+    playing: bool,
+    cursor: Cursor<usize, TalkSnippetId>,
+    // The current playback position (as an index into the audio buffer). This is fractional
+    // because we might be playing at a speed other than 1.0.
+    position: f64,
+    // The audio gets mixed into here before being copied to the output.
+    scratch: Vec<i16>,
+}
+
+impl PlaybackState {
+    fn new() -> PlaybackState {
+        PlaybackState {
+            data: OutputData::new(),
+            playing: false,
+            cursor: Cursor::empty(0),
+            position: 0.0,
+            scratch: Vec::new(),
+        }
+    }
+
+    fn reset(&mut self, data: OutputData) {
+        let idx = data.start_time.as_audio_idx(SAMPLE_RATE);
+        self.cursor = Cursor::new(data.snips.snippet_spans(), idx, idx);
+        self.position = idx as f64;
+        self.data = data;
+    }
+
+    /// Fills `buf` (which has `channels` interleaved channels) with the next chunk of audio.
+    fn fill<T: Sample>(&mut self, buf: &mut [T], channels: usize) {
+        let frames = buf.len() / channels;
+        if !self.playing || frames == 0 {
+            for x in buf.iter_mut() {
+                *x = T::from(&0i16);
+            }
+            return;
+        }
+
+        let velocity = self.data.velocity;
+        let end = self.position + velocity * frames as f64;
+        let lo = self.position.min(end).max(0.0).floor() as usize;
+        let hi = self.position.max(end).max(0.0).ceil() as usize + 1;
+        self.cursor.advance_to(lo, hi);
+        self.scratch.clear();
+        self.scratch.resize(hi - lo, 0);
+        self.data.mix_to(&self.cursor, &mut self.scratch[..]);
+
+        for frame in buf.chunks_mut(channels) {
+            let sample = if self.position >= 0.0 {
+                let idx = (self.position as usize).saturating_sub(lo);
+                self.scratch.get(idx).cloned().unwrap_or(0)
+            } else {
+                0
+            };
+            for x in frame {
+                *x = T::from(&sample);
+            }
+            self.position += velocity;
+        }
+    }
+}
+
+/// Finds a stream configuration that supports our internal sample rate.
+fn find_config(
+    configs: impl Iterator<Item = SupportedStreamConfigRange>,
+) -> Result<(StreamConfig, SampleFormat)> {
+    let supported = configs
+        .filter(|c| c.min_sample_rate().0 <= SAMPLE_RATE && SAMPLE_RATE <= c.max_sample_rate().0)
+        .min_by_key(|c| c.channels())
+        .ok_or_else(|| anyhow!("the audio device doesn't support a {}Hz sample rate", SAMPLE_RATE))?
+        .with_sample_rate(SampleRate(SAMPLE_RATE));
+    Ok((supported.config(), supported.sample_format()))
+}
+
+fn build_output_stream(
+    playback: Arc<Mutex<PlaybackState>>,
+    error_tx: Sender<StreamError>,
+) -> Result<Stream> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| anyhow!("no audio output device"))?;
+    let (config, format) = find_config(device.supported_output_configs()?)?;
+    let channels = config.channels as usize;
+    let err_fn = move |e| {
+        log::error!("error in audio output: {}", e);
+        let _ = error_tx.send(e);
+    };
+
+    let stream = match format {
+        SampleFormat::I16 => device.build_output_stream(
+            &config,
+            move |buf: &mut [i16], _| playback.lock().unwrap().fill(buf, channels),
+            err_fn,
+        )?,
+        SampleFormat::U16 => device.build_output_stream(
+            &config,
+            move |buf: &mut [u16], _| playback.lock().unwrap().fill(buf, channels),
+            err_fn,
+        )?,
+        SampleFormat::F32 => device.build_output_stream(
+            &config,
+            move |buf: &mut [f32], _| playback.lock().unwrap().fill(buf, channels),
+            err_fn,
+        )?,
+    };
+    stream.play()?;
+    Ok(stream)
+}
+
+/// Finds the device to record from.
+fn input_device(source: AudioSource) -> Result<cpal::Device> {
+    let host = cpal::default_host();
+    match source {
+        AudioSource::Microphone => host
+            .default_input_device()
+            .ok_or_else(|| anyhow!("no audio input device")),
+        AudioSource::System => find_loopback_device(&host),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn find_loopback_device(host: &cpal::Host) -> Result<cpal::Device> {
+    // On Windows, recording from an output device records whatever is playing on it.
+    host.default_output_device()
+        .ok_or_else(|| anyhow!("no audio output device"))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn find_loopback_device(host: &cpal::Host) -> Result<cpal::Device> {
+    host.input_devices()?
+        .find(|d| d.name().map_or(false, |n| super::is_loopback_device(&n)))
+        .ok_or_else(|| anyhow!("couldn't find a loopback audio device"))
+}
+
+fn build_input_stream(
+    mut processor: InputProcessor,
+    error_tx: Sender<StreamError>,
+    source: AudioSource,
+) -> Result<Stream> {
+    let device = input_device(source)?;
+    // When recording the system audio on Windows, the "input" device is really an output device.
+    let (config, format) = if source == AudioSource::System && cfg!(target_os = "windows") {
+        find_config(device.supported_output_configs()?)?
+    } else {
+        find_config(device.supported_input_configs()?)?
+    };
+    let channels = config.channels as usize;
+    let err_fn = move |e| {
+        log::error!("error in audio input: {}", e);
+        let _ = error_tx.send(e);
+    };
+
+    // We only record the first channel.
+    let stream = match format {
+        SampleFormat::I16 => device.build_input_stream(
+            &config,
+            move |buf: &[i16], _| processor.process(buf.iter().step_by(channels).cloned()),
+            err_fn,
+        )?,
+        SampleFormat::U16 => device.build_input_stream(
+            &config,
+            move |buf: &[u16], _| {
+                processor.process(buf.iter().step_by(channels).map(|x| x.to_i16()))
+            },
+            err_fn,
+        )?,
+        SampleFormat::F32 => device.build_input_stream(
+            &config,
+            move |buf: &[f32], _| {
+                processor.process(buf.iter().step_by(channels).map(|x| x.to_i16()))
+            },
+            err_fn,
+        )?,
+    };
+    // As with the gstreamer backend, we keep the input running all the time to avoid pops.
+    stream.play()?;
+    Ok(stream)
+}
+
+impl Streams {
+    fn new(
+        playback: &Arc<Mutex<PlaybackState>>,
+        input_data: &Arc<Mutex<InputData>>,
+        error_tx: &Sender<StreamError>,
+        source: AudioSource,
+    ) -> Result<Streams> {
+        let output = build_output_stream(Arc::clone(playback), error_tx.clone())?;
+
+        let (input_tx, input_rx) = unbounded();
+        let (status_tx, status_rx) = unbounded();
+        let processor = InputProcessor::new(Arc::clone(input_data), input_rx, status_tx);
+        let input = build_input_stream(processor, error_tx.clone(), source)?;
+
+        Ok(Streams {
+            _output: output,
+            _input: input,
+            input_tx,
+            input_status_rx: status_rx,
+        })
+    }
+}
+
+impl CpalBackend {
+    pub fn new() -> Result<CpalBackend> {
+        let playback = Arc::new(Mutex::new(PlaybackState::new()));
+        let input_data = Arc::new(Mutex::new(InputData::new()));
+        let (error_tx, error_rx) = unbounded();
+        let source = AudioSource::default();
+        let streams = Streams::new(&playback, &input_data, &error_tx, source)?;
+
+        Ok(CpalBackend {
+            streams,
+            playback,
+            input_data,
+            recording: None,
+            source,
+            error_tx,
+            error_rx,
+            errors: Vec::new(),
+        })
+    }
+
+    fn try_rebuild(&mut self) -> Result<()> {
+        self.streams = Streams::new(
+            &self.playback,
+            &self.input_data,
+            &self.error_tx,
+            self.source,
+        )?;
+        // Errors from the old streams are no longer relevant.
+        for _ in self.error_rx.try_iter() {}
+        if let Some(config) = self.recording.clone() {
+            let _ = self.streams.input_tx.send(Some(config));
+        }
+        Ok(())
+    }
+}
+
+impl AudioBackend for CpalBackend {
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    fn start_playing(&mut self, data: OutputData) {
+        let mut playback = self.playback.lock().unwrap();
+        playback.reset(data);
+        playback.playing = true;
+    }
+
+    fn stop_playing(&mut self) {
+        self.playback.lock().unwrap().playing = false;
+    }
+
+    fn seek(&mut self, time: Time, velocity: f64) {
+        let mut playback = self.playback.lock().unwrap();
+        let mut data = playback.data.clone();
+        data.start_time = time;
+        data.velocity = velocity;
+        playback.reset(data);
+    }
+
+    fn start_recording(&mut self, config: InputConfig) {
+        if config.monitor {
+            log::warn!("input monitoring isn't supported with the cpal audio backend");
+        }
+        self.recording = Some(config.clone());
+        // Drop anything that arrived after the previous recording stopped.
+        *self.input_data.lock().unwrap() = InputData::new();
+        if config.source != self.source {
+            // Rebuilding the streams also sends the config to the new input stream.
+            self.source = config.source;
+            self.rebuild();
+            return;
+        }
+        if self.streams.input_tx.send(Some(config)).is_err() {
+            log::error!("audio input stream died, no audio will be recorded");
+        }
+    }
+
+    fn take_input_data(&mut self) -> InputData {
+        self.recording = None;
+        let data = std::mem::replace(
+            self.input_data.lock().unwrap().deref_mut(),
+            InputData::new(),
+        );
+        if self.streams.input_tx.send(None).is_err() {
+            log::error!("audio input stream died, no audio will be recorded");
+        }
+        data
+    }
+
+    fn input_status(&self) -> Receiver<AudioRecordingStatus> {
+        self.streams.input_status_rx.clone()
+    }
+
+    fn rebuild(&mut self) {
+        if let Err(e) = self.try_rebuild() {
+            log::error!("failed to restart audio: {}", e);
+            self.errors.push(AudioError::new("restarting audio", &e));
+        }
+    }
+
+    fn check_devices(&mut self) -> Option<String> {
+        let errors: Vec<_> = self.error_rx.try_iter().collect();
+        if errors.is_empty() {
+            return None;
+        }
+
+        let mut msg = errors
+            .iter()
+            .map(|e| format!("Audio error: {}", e))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if let Err(e) = self.try_rebuild() {
+            log::error!("failed to restart audio: {}", e);
+            self.errors.push(AudioError::new("restarting audio", &e));
+            msg.push_str("; audio isn't working");
+        }
+        Some(msg)
+    }
+
+    fn take_errors(&mut self) -> Vec<AudioError> {
+        std::mem::take(&mut self.errors)
+    }
+}
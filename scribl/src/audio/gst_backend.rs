@@ -0,0 +1,490 @@
+use anyhow::{anyhow, Result};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use gstreamer_app as gst_app;
+use gstreamer_audio::{AudioFormat, AudioInfo};
+use std::ops::DerefMut;
+use std::sync::{Arc, Mutex};
+
+use scribl_curves::Time;
+
+use super::backend::{AudioBackend, AudioError, InputData, InputProcessor};
+use super::{
+    create_appsrc, create_gst_elt, AudioRecordingStatus, InputConfig, OutputData, SAMPLE_RATE,
+};
+use crate::config::AudioSource;
+
+/// An audio backend based on gstreamer pipelines.
+pub struct GstBackend {
+    // The other end of this lives in the app_src callback. We send new output data along here when
+    // we want to update the audio data that's playing.
+    output_tx: Sender<OutputData>,
+    // The pipeline will be `None` if there was an error while creating it. In that case, we
+    // already printed an error message so we'll just silently (heh) not play any audio.
+    output_pipeline: Option<gst::Pipeline>,
+    // The current output data (i.e., a copy of the last thing we sent along output_tx).
+    output_data: OutputData,
+    // Are we currently playing? (We need to know this if we have to rebuild the pipelines.)
+    playing: bool,
+
+    // The receiver of this lives in the app_sink callback. We send input configs to it when we
+    // want to change the input settings. We send `None` when we want to stop storing the input
+    // audio.
+    input_tx: Sender<Option<InputConfig>>,
+    // The sender of this lives in the app_sink callback. It regularly sends us messages about
+    // things like input levels.
+    input_status_rx: Receiver<AudioRecordingStatus>,
+    // As with the output pipeline, this is `None` if there was an error creating it.
+    input_pipeline: Option<gst::Pipeline>,
+    // The app_sink callback appends the recorded audio to this. This seems to work ok so far, but
+    // the strategy could do with more testing (TODO). E.g., does gstreamer glitch if we block in
+    // appsink? Or does it have enough buffers of its own?
+    input_data: Arc<Mutex<InputData>>,
+    // If we're currently recording, this is the config that we're recording with.
+    recording: Option<InputConfig>,
+    // The source that the input pipeline records from.
+    source: AudioSource,
+
+    // Watches for audio devices being added or removed. This is `None` if the platform doesn't
+    // support device monitoring.
+    device_monitor: Option<gst::DeviceMonitor>,
+
+    // The errors that broke the pipelines, and that we haven't told anyone about yet.
+    errors: Vec<AudioError>,
+}
+
+impl GstBackend {
+    /// Initializes the audio input and output pipelines.
+    pub fn new() -> GstBackend {
+        let mut ret = GstBackend {
+            output_tx: unbounded().0,
+            output_pipeline: None,
+            output_data: OutputData::new(),
+            playing: false,
+            input_tx: unbounded().0,
+            input_status_rx: crossbeam_channel::never(),
+            input_pipeline: None,
+            input_data: Arc::new(Mutex::new(InputData::new())),
+            recording: None,
+            source: AudioSource::default(),
+            device_monitor: None,
+            errors: Vec::new(),
+        };
+        ret.build_pipelines();
+
+        match create_device_monitor() {
+            Ok(monitor) => ret.device_monitor = Some(monitor),
+            Err(e) => log::warn!("failed to watch for audio device changes: {}", e),
+        }
+        ret
+    }
+
+    /// (Re)creates the input and output pipelines.
+    ///
+    /// If there were pipelines already, they are shut down first. Audio that was already recorded
+    /// is preserved, and if we were recording or playing then we continue doing so.
+    fn build_pipelines(&mut self) {
+        // If we're playing, figure out where we're up to so that we can resume from there.
+        let position = self
+            .output_pipeline
+            .as_ref()
+            .filter(|_| self.playing)
+            .and_then(|pipe| pipe.query_position::<gst::ClockTime>())
+            .map(|t| Time::from_micros(t.useconds() as i64));
+        for pipe in self.input_pipeline.iter().chain(self.output_pipeline.iter()) {
+            if let Err(e) = pipe.set_state(gst::State::Null) {
+                log::error!("failed to shut down audio pipeline: {}", e);
+            }
+        }
+
+        let (output_tx, output_rx) = unbounded();
+        let output_pipeline = create_output_pipeline(output_rx);
+        if let Err(e) = &output_pipeline {
+            log::error!(
+                "Error initializing audio output, there will be no sound: {}",
+                e
+            );
+            self.errors
+                .push(AudioError::new("initializing audio output", e));
+        }
+
+        let (input_tx, input_rx) = unbounded();
+        let (status_tx, status_rx) = unbounded();
+        let processor = InputProcessor::new(Arc::clone(&self.input_data), input_rx, status_tx);
+        let input_pipeline = create_input_pipeline(processor, self.source);
+        // We keep the input pipeline running, even if we aren't recording audio. This is because
+        // starting and starting the input pipeline tends to lead to "pops" in the recording.
+        match &input_pipeline {
+            Err(e) => {
+                log::error!(
+                    "Error initializing audio input, there will be no audio recording: {}",
+                    e
+                );
+                self.errors
+                    .push(AudioError::new("initializing audio input", e));
+            }
+            Ok(pipe) => {
+                if let Err(e) = pipe.set_state(gst::State::Playing) {
+                    log::error!("failed to start recording audio: {}", e);
+                    // The bus usually has a more useful description of what went wrong.
+                    let mut errors = bus_errors(pipe, "starting audio input");
+                    if errors.is_empty() {
+                        let e = anyhow::Error::from(e);
+                        errors.push(AudioError::new("starting audio input", &e));
+                    }
+                    self.errors.extend(errors);
+                }
+            }
+        };
+
+        self.output_tx = output_tx;
+        self.output_pipeline = output_pipeline.ok();
+        self.input_tx = input_tx;
+        self.input_status_rx = status_rx;
+        self.input_pipeline = input_pipeline.ok();
+
+        if let Some(config) = self.recording.clone() {
+            self.set_monitoring(config.monitor);
+            let _ = self.input_tx.send(Some(config));
+        }
+        if self.playing {
+            let mut data = self.output_data.clone();
+            data.start_time = position.unwrap_or(data.start_time);
+            self.start_playing(data);
+        }
+    }
+
+    /// Returns true if both the input and the output pipelines were successfully created.
+    pub fn is_working(&self) -> bool {
+        self.input_pipeline.is_some() && self.output_pipeline.is_some()
+    }
+
+    /// Turns on or off the playback of the input audio.
+    fn set_monitoring(&self, monitor: bool) {
+        if let Some(vol) = self
+            .input_pipeline
+            .as_ref()
+            .and_then(|pipe| pipe.by_name("monitor-volume"))
+        {
+            vol.set_property("mute", !monitor);
+        }
+    }
+
+    /// Returns descriptions of all the device changes that happened since the last call.
+    fn device_changes(&self) -> Vec<String> {
+        let mut ret = Vec::new();
+        if let Some(monitor) = &self.device_monitor {
+            while let Some(msg) = monitor.bus().pop() {
+                match msg.view() {
+                    gst::MessageView::DeviceAdded(m) => {
+                        ret.push(format!("Audio device added: {}", m.device().display_name()));
+                    }
+                    gst::MessageView::DeviceRemoved(m) => {
+                        ret.push(format!("Audio device removed: {}", m.device().display_name()));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        ret
+    }
+
+    /// Returns all the errors that the pipelines reported since the last call.
+    fn pipeline_errors(&self) -> Vec<AudioError> {
+        let mut ret = Vec::new();
+        for pipe in self.input_pipeline.iter().chain(self.output_pipeline.iter()) {
+            ret.extend(bus_errors(pipe, "playing or recording audio"));
+        }
+        ret
+    }
+}
+
+/// Takes all the error messages that are waiting on a pipeline's bus.
+fn bus_errors(pipe: &gst::Pipeline, action: &str) -> Vec<AudioError> {
+    let mut ret = Vec::new();
+    if let Some(bus) = pipe.bus() {
+        while let Some(msg) = bus.pop() {
+            if let gst::MessageView::Error(e) = msg.view() {
+                log::error!("audio pipeline error: {} ({:?})", e.error(), e.debug());
+                ret.push(AudioError {
+                    action: action.to_owned(),
+                    element: msg.src().map(|src| src.path_string().to_string()),
+                    message: e.error().to_string(),
+                    debug: e.debug().map(|d| d.to_string()),
+                });
+            }
+        }
+    }
+    ret
+}
+
+fn create_device_monitor() -> Result<gst::DeviceMonitor> {
+    let monitor = gst::DeviceMonitor::new();
+    monitor.add_filter(Some("Audio/Source"), None);
+    monitor.add_filter(Some("Audio/Sink"), None);
+    monitor.start()?;
+    // Some device providers announce all the existing devices when they start up; we only care
+    // about changes after that.
+    while monitor.bus().pop().is_some() {}
+    Ok(monitor)
+}
+
+impl AudioBackend for GstBackend {
+    fn can_play(&self) -> bool {
+        self.output_pipeline.is_some()
+    }
+
+    fn rebuild(&mut self) {
+        self.build_pipelines();
+    }
+
+    fn check_devices(&mut self) -> Option<String> {
+        let mut changes = self.device_changes();
+        let errors = self.pipeline_errors();
+        changes.extend(errors.iter().map(|e| format!("Audio error: {}", e.message)));
+        if changes.is_empty() {
+            return None;
+        }
+
+        // Whatever happened, the simplest way to recover is to start again from scratch. In
+        // particular, the "auto" sources and sinks will pick up the new default devices.
+        self.build_pipelines();
+        if !self.is_working() {
+            // The errors that broke the old pipelines might explain why the new ones failed.
+            self.errors.extend(errors);
+            changes.push("audio isn't working".to_owned());
+        }
+        Some(changes.join("; "))
+    }
+
+    fn take_errors(&mut self) -> Vec<AudioError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    fn seek(&mut self, time: Time, velocity: f64) {
+        self.output_data.velocity = velocity;
+        self.output_data.start_time = time;
+        let result = || -> Result<()> {
+            if let Some(pipe) = self.output_pipeline.as_ref() {
+                if let Some(sink) = pipe.by_name("playback-sink") {
+                    // The "scaletempo" gstreamer plugin has some issues with playing backwards. We
+                    // avoid them by always playing forwards, but adapting our appsrc to produce
+                    // the samples backwards.
+                    sink.seek(
+                        velocity.abs(),
+                        gst::SeekFlags::FLUSH,
+                        gst::SeekType::Set,
+                        Some(gst::ClockTime::from_useconds(time.as_micros() as u64)),
+                        gst::SeekType::Set,
+                        None,
+                    )?;
+                }
+            }
+            Ok(())
+        }();
+        if let Err(e) = result {
+            log::error!(
+                "failed to seek (time {}, velocity {}): {}",
+                e,
+                time.as_micros(),
+                velocity
+            );
+        }
+    }
+
+    fn start_recording(&mut self, config: InputConfig) {
+        self.recording = Some(config.clone());
+        // Drop anything that arrived after the previous recording stopped.
+        *self.input_data.lock().unwrap() = InputData::new();
+        if config.source != self.source {
+            // Rebuilding the pipelines also sends the config to the new input pipeline.
+            self.source = config.source;
+            self.build_pipelines();
+            return;
+        }
+        self.set_monitoring(config.monitor);
+        if self.input_tx.send(Some(config)).is_err() {
+            log::error!("audio input thread died, no audio will be recorded");
+        }
+    }
+
+    fn take_input_data(&mut self) -> InputData {
+        self.recording = None;
+        self.set_monitoring(false);
+        let data = std::mem::replace(
+            self.input_data.lock().unwrap().deref_mut(),
+            InputData::new(),
+        );
+        if self.input_tx.send(None).is_err() {
+            log::error!("audio input thread died, no audio will be recorded");
+        }
+        data
+    }
+
+    fn input_status(&self) -> Receiver<AudioRecordingStatus> {
+        self.input_status_rx.clone()
+    }
+
+    fn start_playing(&mut self, data: OutputData) {
+        self.playing = true;
+        self.output_data = data;
+        if self.output_tx.send(self.output_data.clone()).is_err() {
+            log::error!("audio thread not present");
+        }
+
+        if let Some(pipe) = self.output_pipeline.as_ref() {
+            if let Err(e) = pipe.set_state(gst::State::Playing) {
+                log::error!("failed to start playing audio: {}", e);
+                return;
+            }
+        }
+        self.seek(self.output_data.start_time, self.output_data.velocity);
+    }
+
+    fn stop_playing(&mut self) {
+        self.playing = false;
+        if let Some(pipe) = self.output_pipeline.as_ref() {
+            if let Err(e) = pipe.set_state(gst::State::Paused) {
+                log::error!("failed to stop audio: {}", e);
+            }
+        }
+    }
+}
+
+/// Creates the element that produces the audio to be recorded.
+fn create_record_source(source: AudioSource) -> Result<gst::Element> {
+    match source {
+        AudioSource::Microphone => create_gst_elt("autoaudiosrc", "record-source"),
+        AudioSource::System => create_loopback_source(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn create_loopback_source() -> Result<gst::Element> {
+    // PulseAudio (and PipeWire, through its PulseAudio compatibility layer) has a "monitor" source
+    // for every output device, and this special name refers to the monitor of the default one.
+    let src = create_gst_elt("pulsesrc", "record-source")?;
+    src.set_property("device", "@DEFAULT_MONITOR@");
+    Ok(src)
+}
+
+#[cfg(target_os = "windows")]
+fn create_loopback_source() -> Result<gst::Element> {
+    // In loopback mode, WASAPI records from an output device (the default one, since we don't
+    // specify a device).
+    let src = create_gst_elt("wasapisrc", "record-source")?;
+    src.set_property("loopback", true);
+    Ok(src)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn create_loopback_source() -> Result<gst::Element> {
+    // There's no built-in way to record the system audio, so we look for a virtual device that
+    // loops the output back to an input.
+    let monitor = gst::DeviceMonitor::new();
+    monitor.add_filter(Some("Audio/Source"), None);
+    let device = monitor
+        .devices()
+        .into_iter()
+        .find(|d| super::is_loopback_device(&d.display_name()))
+        .ok_or_else(|| anyhow!("couldn't find a loopback audio device (like BlackHole)"))?;
+    Ok(device.create_element(Some("record-source"))?)
+}
+
+fn create_input_pipeline(
+    mut processor: InputProcessor,
+    source: AudioSource,
+) -> Result<gst::Pipeline> {
+    let pipeline = gst::Pipeline::new(None);
+    let src = create_record_source(source)?;
+    let resample = create_gst_elt("audioresample", "record-resample")?;
+    let convert = create_gst_elt("audioconvert", "record-convert")?;
+    let tee = create_gst_elt("tee", "record-tee")?;
+    let queue = create_gst_elt("queue", "record-queue")?;
+    let sink = create_gst_elt("appsink", "record-sink")?;
+    pipeline.add_many(&[&src, &resample, &convert, &tee, &queue, &sink])?;
+    gst::Element::link_many(&[&src, &resample, &convert, &tee, &queue, &sink])?;
+
+    // The monitoring branch is optional: if we can't create it, we can still record.
+    if let Err(e) = add_monitor_branch(&pipeline, &tee) {
+        log::error!("failed to set up input monitoring: {}", e);
+    }
+
+    let sink = sink
+        .dynamic_cast::<gst_app::AppSink>()
+        .map_err(|_| anyhow!("bug: couldn't cast sink to an AppSink"))?;
+    let audio_info = AudioInfo::builder(AudioFormat::S16le, SAMPLE_RATE as u32, 1).build()?;
+    sink.set_caps(Some(&audio_info.to_caps()?));
+
+    let new_sample = move |sink: &gst_app::AppSink| -> Result<gst::FlowSuccess, gst::FlowError> {
+        let sample = match sink.pull_sample() {
+            Ok(s) => s,
+            Err(e) => {
+                log::error!("Failed to pull sample: {}", e);
+                return Err(gst::FlowError::CustomError);
+            }
+        };
+
+        let buffer = match sample.buffer() {
+            Some(b) => b,
+            None => {
+                log::error!("Failed to get sample buffer");
+                return Err(gst::FlowError::CustomError);
+            }
+        };
+
+        let buffer = match buffer.map_readable() {
+            Ok(b) => b,
+            Err(e) => {
+                log::error!("Failed to map buffer as readable: {}", e);
+                return Err(gst::FlowError::CustomError);
+            }
+        };
+
+        // The buffer is in bytes; each sample is two bytes.
+        processor.process(
+            buffer
+                .as_slice()
+                .chunks_exact(2)
+                .map(|s| i16::from_le_bytes([s[0], s[1]])),
+        );
+        Ok(gst::FlowSuccess::Ok)
+    };
+    sink.set_callbacks(
+        gst_app::AppSinkCallbacks::builder()
+            .new_sample(new_sample)
+            .build(),
+    );
+    Ok(pipeline)
+}
+
+/// Adds a branch to the input pipeline that plays the input audio through the speakers. It starts
+/// off muted.
+fn add_monitor_branch(pipeline: &gst::Pipeline, tee: &gst::Element) -> Result<()> {
+    let queue = create_gst_elt("queue", "monitor-queue")?;
+    let volume = create_gst_elt("volume", "monitor-volume")?;
+    let sink = create_gst_elt("autoaudiosink", "monitor-sink")?;
+    volume.set_property("mute", true);
+    // We want to hear the input as soon as possible, not when the clock says we should.
+    sink.set_property("sync", false);
+
+    pipeline.add_many(&[&queue, &volume, &sink])?;
+    gst::Element::link_many(&[tee, &queue, &volume, &sink])?;
+    Ok(())
+}
+
+fn create_output_pipeline(rx: Receiver<OutputData>) -> Result<gst::Pipeline> {
+    let pipeline = gst::Pipeline::new(None);
+    let src = create_appsrc(rx, "playback-source")?;
+    let scale = create_gst_elt("scaletempo", "playback-scale")?;
+    let resample = create_gst_elt("audioresample", "playback-resample")?;
+    let convert = create_gst_elt("audioconvert", "playback-convert")?;
+    let queue = create_gst_elt("queue", "playback-queue")?;
+    let sink = create_gst_elt("autoaudiosink", "playback-sink")?;
+
+    pipeline.add_many(&[&src, &scale, &resample, &convert, &queue, &sink])?;
+    gst::Element::link_many(&[&src, &scale, &resample, &convert, &queue, &sink])?;
+
+    Ok(pipeline)
+}
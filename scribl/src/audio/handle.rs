@@ -4,7 +4,7 @@ use druid::{ExtEventSink, Target};
 use scribl_curves::Time;
 
 use super::thread::{audio_loop, Cmd};
-use super::{OutputData, TalkSnippets};
+use super::{AudioBackendKind, MusicOutput, OutputData, PencilSource, PunchIn, TalkSnippets};
 use crate::config::AudioInput as InputConfig;
 use crate::data::AudioState as State;
 
@@ -22,9 +22,13 @@ impl AudioHandle {
     ///
     /// TODO: figure out, and describe here, the conditions under which the audio thread shuts
     /// down.
-    pub fn initialize_audio(sink: ExtEventSink, target: Target) -> AudioHandle {
+    pub fn initialize_audio(
+        sink: ExtEventSink,
+        target: Target,
+        backend: AudioBackendKind,
+    ) -> AudioHandle {
         let (tx, rx) = unbounded();
-        std::thread::spawn(move || audio_loop(rx, sink, target));
+        std::thread::spawn(move || audio_loop(rx, sink, target, backend));
         AudioHandle { cmd_tx: tx }
     }
 
@@ -42,16 +46,21 @@ impl AudioHandle {
         // A special case if we're keeping playing but just changing the speed.
         if let (
             Playing {
-                snips: old_snips, ..
+                snips: old_snips,
+                music: old_music,
+                pencil: old_pencil,
+                ..
             },
             Playing {
                 start_time,
                 velocity,
                 snips,
+                music,
+                pencil,
             },
         ) = (&old_state, &new_state)
         {
-            if snips == old_snips {
+            if snips == old_snips && music == old_music && pencil == old_pencil {
                 self.seek(*start_time, *velocity);
                 return;
             }
@@ -60,24 +69,42 @@ impl AudioHandle {
         match old_state {
             Playing { .. } => self.stop_playing(),
             Recording { start_time, .. } => self.stop_recording(start_time),
+            PunchingIn { punch, .. } => self.stop_punch_in(punch),
             Idle => {}
         }
 
         match new_state {
             Playing {
                 snips,
+                music,
+                pencil,
                 start_time,
                 velocity,
-            } => self.play(snips, start_time, velocity),
+            } => self.play(snips, music, pencil, start_time, velocity),
             Recording { config, .. } => self.start_recording(config),
+            PunchingIn {
+                snips,
+                config,
+                punch,
+            } => self.punch_in(snips, config, punch),
             Idle => {}
         }
     }
 
     /// Start playing audio.
-    fn play(&self, snips: TalkSnippets, start_time: Time, velocity: f64) {
+    fn play(
+        &self,
+        snips: TalkSnippets,
+        music: Option<MusicOutput>,
+        pencil: Option<PencilSource>,
+        start_time: Time,
+        velocity: f64,
+    ) {
         if let Err(e) = self.cmd_tx.send(Cmd::Play(OutputData {
+            crossfades: snips.crossfades(),
             snips,
+            music,
+            pencil: pencil.map(|p| p.output()),
             start_time,
             velocity,
         })) {
@@ -111,6 +138,41 @@ impl AudioHandle {
         }
     }
 
+    /// Start playing and recording audio at the same time, for re-recording part of a snippet.
+    fn punch_in(&self, snips: TalkSnippets, config: InputConfig, punch: PunchIn) {
+        let output = OutputData {
+            crossfades: snips.crossfades(),
+            snips,
+            // The music would get picked up by the microphone.
+            music: None,
+            pencil: None,
+            start_time: punch.record_start,
+            velocity: 1.0,
+        };
+        if let Err(e) = self.cmd_tx.send(Cmd::StartPunchIn(output, config)) {
+            log::error!("audio thread exited unexpectedly: {}", e);
+        }
+    }
+
+    /// Stop re-recording part of a snippet.
+    ///
+    /// The new audio will be sent as a `PUNCH_IN_RECORDED` command.
+    fn stop_punch_in(&self, punch: PunchIn) {
+        if let Err(e) = self.cmd_tx.send(Cmd::StopPunchIn(punch)) {
+            log::error!("audio thread exited unexpectedly: {}", e);
+        }
+    }
+
+    /// Measures the audio latency by playing a beep and listening for it.
+    ///
+    /// This should only be called when the audio is idle. The result will be sent as a
+    /// `LATENCY_CALIBRATED` command.
+    pub fn calibrate_latency(&self) {
+        if let Err(e) = self.cmd_tx.send(Cmd::CalibrateLatency) {
+            log::error!("audio thread exited unexpectedly: {}", e);
+        }
+    }
+
     /// Seeks the audio to a new location, and possibly also a different speed.
     fn seek(&self, time: Time, velocity: f64) {
         if let Err(e) = self.cmd_tx.send(Cmd::Seek(time, velocity)) {
@@ -1,39 +1,71 @@
 //! This module is in charge of audio (both recording and playback).
 
-use anyhow::{Context, Result};
-use gstreamer as gst;
+use druid::Data;
 
-use scribl_curves::Time;
+use scribl_curves::{DrawSnippets, Time};
 
 use crate::config::AudioInput as InputConfig;
 
-mod appsrc;
+mod backend;
+mod cpal_backend;
+mod gst_backend;
 mod handle;
-mod snippets;
 mod thread;
+pub mod vad;
 
-pub use appsrc::create_appsrc;
+pub use backend::{AudioBackendKind, AudioError};
 pub use handle::AudioHandle;
-pub use snippets::{TalkSnippet, TalkSnippetId, TalkSnippets};
+pub use scribl_core::audio::{
+    create_appsrc, create_gst_elt, find_duplicates, loudness_gain, Duplicate, GainEnvelope,
+    GainPoint, MissingElement, MusicOutput, MusicTrack, OutputData, PencilOutput, Spectrogram,
+    TalkSnippet, TalkSnippetId, TalkSnippets, DEFAULT_PENCIL_VOLUME, MAX_GAIN, SAMPLE_RATE,
+};
 
-/// We do all of our internal audio processing at 48kHz.
-pub const SAMPLE_RATE: u32 = 48000;
-
-/// All the information needed to specify some audio for playback (or encoding).
+/// The drawings that the pencil sound should follow, and how loud it should be.
+///
+/// Working out when the strokes are being drawn takes a while, so the audio state just holds on
+/// to the drawings, and the pencil sound gets made when playback starts.
 #[derive(Clone)]
-pub struct OutputData {
-    /// The collection of audio snippets. They will be mixed into the final audio output.
-    pub snips: TalkSnippets,
-    /// The time at which to start playing.
-    pub start_time: Time,
-    /// The velocity at which to play back the audio. (1.0 is normal, forwards, playback)
-    pub velocity: f64,
+pub struct PencilSource {
+    pub draw: DrawSnippets,
+    pub volume: f32,
+}
+
+impl PencilSource {
+    pub fn output(&self) -> PencilOutput {
+        PencilOutput::new(&self.draw, self.volume)
+    }
+}
+
+// The audio state gets compared on every update, so this only does a cheap comparison of the
+// drawings.
+impl PartialEq for PencilSource {
+    fn eq(&self, other: &PencilSource) -> bool {
+        self.draw.same(&other.draw) && self.volume == other.volume
+    }
+}
+
+/// A description of a "punch-in": re-recording part of an existing snippet.
+#[derive(Clone, Copy, Data, Debug, PartialEq)]
+pub struct PunchIn {
+    /// The snippet that is being re-recorded.
+    pub snippet: TalkSnippetId,
+    /// The beginning of the range to replace.
+    pub start: Time,
+    /// The end of the range to replace.
+    pub end: Time,
+    /// The time at which we started recording. This is a bit before `start`, so that the user has
+    /// some context before the re-recording begins.
+    pub record_start: Time,
 }
 
 /// The result of recording audio: a buffer, and a bit of metadata.
 pub struct AudioRecording {
     /// The audio signal.
     pub buf: Vec<i16>,
+    /// The audio signal before noise removal, if we were asked to keep it. It has the same length
+    /// as `buf`.
+    pub raw: Option<Vec<i16>>,
     /// The perceptual loudness (in dB) of the audio.
     pub loudness: f64,
     /// The peak (as a number in [0.0, 1.0]) of the signal.
@@ -45,29 +77,18 @@ pub struct AudioRecording {
 pub struct AudioRecordingStatus {
     /// The perceptual loudness (in dB) of some recent chunk of audio input.
     pub loudness: f32,
+    /// The sample peak (as a number in [0.0, 1.0]) of the same chunk of audio input.
+    pub peak: f32,
     /// The estimated probability that the input is speech.
     pub vad: f32,
 }
 
-impl OutputData {
-    fn new() -> OutputData {
-        OutputData {
-            snips: TalkSnippets::default(),
-            start_time: Time::ZERO,
-            velocity: 1.0,
-        }
-    }
-
-    fn forwards(&self) -> bool {
-        self.velocity > 0.0
-    }
-}
-
-fn create_gst_elt(kind: &str, name: &str) -> Result<gst::Element> {
-    gst::ElementFactory::make(kind, Some(name)).with_context(|| {
-        format!(
-            "tried to create {}, of type {}. You are probably missing a gstreamer plugin",
-            name, kind
-        )
-    })
+/// Does this look like the name of a device that records the system audio? Linux audio servers
+/// call these "monitors"; on other platforms they come from third-party virtual devices.
+#[cfg(not(target_os = "windows"))]
+fn is_loopback_device(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["monitor", "loopback", "blackhole", "soundflower"]
+        .iter()
+        .any(|s| name.contains(s))
 }
@@ -1,66 +1,37 @@
 use anyhow::{anyhow, Result};
-use crossbeam_channel::{select, unbounded, Receiver, Sender};
+use crossbeam_channel::{select, Receiver};
 use druid::{ExtEventSink, Target};
-use ebur128::EbuR128;
-use gstreamer as gst;
-use gstreamer::prelude::*;
-use gstreamer_app as gst_app;
-use gstreamer_audio::{AudioFormat, AudioInfo};
-use nnnoiseless::DenoiseState;
-use std::ops::DerefMut;
-use std::sync::{Arc, Mutex};
+use std::ops::Range;
+use std::time::{Duration, Instant, SystemTime};
 
-use scribl_curves::Time;
+use scribl_curves::{Time, TimeDiff};
 
 use crate::cmd;
+use crate::config::AudioSource;
 
+use super::backend::{AudioBackend, AudioBackendKind, AudioError};
+use super::cpal_backend::CpalBackend;
+use super::gst_backend::GstBackend;
 use super::{
-    create_appsrc, create_gst_elt, AudioRecording, AudioRecordingStatus, InputConfig, OutputData,
-    TalkSnippet, SAMPLE_RATE,
+    AudioRecording, AudioRecordingStatus, InputConfig, OutputData, PunchIn, TalkSnippet,
+    TalkSnippets, SAMPLE_RATE,
 };
 
-// We don't simply drop frames where voice was not detected: doing so tends to cut off consonants
-// like "t". Instead, we do some "smoothing in time": if no voice was detected within
-// `VOICELESS_FRAME_LAG` frames (either forwards or backwards in time) of the current frame, we
-// drop the current frame.
-const VOICELESS_FRAME_LAG: usize = 10;
-
-/// This contains the audio pipelines and the various channels that are used to communicate with
-/// the gstreamer callbacks. Essentially, this is the main state in the audio loop.
-struct AudioState {
-    // The other end of this lives in the app_src callback. We send new output data along here when
-    // we want to update the audio data that's playing.
-    output_tx: Sender<OutputData>,
-    // The pipeline will be `None` if there was an error while creating it. In that case, we
-    // already printed an error message so we'll just silently (heh) not play any audio.
-    output_pipeline: Option<gst::Pipeline>,
-    // The current output data (i.e., a copy of the last thing we sent along output_tx).
-    output_data: OutputData,
-
-    // The receiver of this lives in the app_sink callback. We send input configs to it when we
-    // want to change the input settings. We send `None` when we want to stop storing the input
-    // audio.
-    input_tx: Sender<Option<InputConfig>>,
-    // The sender of this lives in the app_sink callback. It regularly sends us messages about
-    // things like input levels.
-    input_status_rx: Receiver<AudioRecordingStatus>,
-    // The current input settings (i.e. a copy of the ones that we most recently sent on input_tx).
-    input_config: InputConfig,
-    // This is how the audio thread communicates the received audio back to the main thread: it
-    // unlocks this mutex and appends its audio to the buffer. This seems to work ok so far, as
-    // we're careful to only hold the mutex for as long as we need to copy the data in or out.
-    // But the strategy could do with more testing (TODO). E.g., does gstreamer glitch if we block
-    // in appsink? Or does it have enough buffers of its own?
-    input_data: Arc<Mutex<InputData>>,
-}
-
-struct InputData {
-    buf: Vec<i16>,
-    /// For every frame (of size `DenoiseState::FRAME_SIZE`) in `buf`, we store an estimate of how
-    /// likely that frame was to contain speech.
-    vad: Vec<f32>,
-    loudness: EbuR128,
-}
+// When calibrating the latency, we play a beep at this time and listen for it on the input.
+const CALIBRATION_BEEP_OFFSET: TimeDiff = TimeDiff::from_micros(500_000);
+const CALIBRATION_BEEP_LEN: TimeDiff = TimeDiff::from_micros(100_000);
+const CALIBRATION_BEEP_FREQ: f32 = 1000.0;
+const CALIBRATION_DURATION: Duration = Duration::from_millis(1500);
+// If the loudest thing we hear during calibration is quieter than this, we assume that we didn't
+// hear the beep at all.
+const CALIBRATION_MIN_PEAK: i16 = 1000;
+
+// How often we check for changes in the audio devices.
+const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// If the device checks are delayed by more than this, we assume that the system was suspended.
+// (We measure this with the system clock, because monotonic clocks don't necessarily advance while
+// the system is suspended.)
+const SUSPEND_THRESHOLD: Duration = Duration::from_secs(5);
 
 /// These are the commands that can be sent to the audio thread.
 pub enum Cmd {
@@ -68,140 +39,104 @@ pub enum Cmd {
     StopPlaying,
     Record(InputConfig),
     StopRecording(Time),
+    StartPunchIn(OutputData, InputConfig),
+    StopPunchIn(PunchIn),
     Seek(Time, f64),
+    CalibrateLatency,
 }
 
-impl AudioState {
-    /// Initializes the audio input and output pipelines.
-    fn init() -> AudioState {
-        let (output_tx, output_rx) = unbounded();
-        let output_pipeline = create_output_pipeline(output_rx);
-        if let Err(e) = &output_pipeline {
-            log::error!(
-                "Error initializing audio output, there will be no sound: {}",
-                e
-            );
-        }
+/// This is the main state in the audio loop: an audio backend, plus a little bit of state for
+/// processing the recorded audio.
+struct AudioState {
+    backend: Box<dyn AudioBackend>,
+    // A channel on which the backend sends us messages about things like input levels.
+    input_status_rx: Receiver<AudioRecordingStatus>,
+    // The current input settings (i.e. a copy of the ones that we most recently sent to the
+    // backend).
+    input_config: InputConfig,
+    // Errors from setting up the backend, that the backend itself doesn't know about.
+    errors: Vec<AudioError>,
+    // If we're measuring the latency, this fires when the measurement is done.
+    calibration: Option<Receiver<Instant>>,
+}
 
-        let (input_tx, input_rx) = unbounded();
-        let (status_tx, status_rx) = unbounded();
-        let input_data = Arc::new(Mutex::new(InputData::new()));
-        let input_pipeline = create_input_pipeline(Arc::clone(&input_data), input_rx, status_tx);
-        // We keep the input pipeline running, even if we aren't recording audio. This is because
-        // starting and starting the input pipeline tends to lead to "pops" in the recording.
-        match input_pipeline {
+/// Creates the audio backend of the requested kind. Also returns the errors from any backends
+/// that we tried and gave up on (but only the ones that the user would want to hear about).
+fn create_backend(kind: AudioBackendKind) -> (Box<dyn AudioBackend>, Vec<AudioError>) {
+    match kind {
+        AudioBackendKind::Gstreamer => (Box::new(GstBackend::new()), Vec::new()),
+        AudioBackendKind::Cpal => match CpalBackend::new() {
+            Ok(b) => (Box::new(b), Vec::new()),
             Err(e) => {
-                log::error!(
-                    "Error initializing audio input, there will be no audio recording: {}",
-                    e
-                )
+                log::error!("failed to initialize cpal audio, falling back to gstreamer: {}", e);
+                let err = AudioError::new("initializing cpal audio", &e);
+                (Box::new(GstBackend::new()), vec![err])
             }
-            Ok(pipe) => {
-                if let Err(e) = pipe.set_state(gst::State::Playing) {
-                    log::error!("failed to start recording audio: {}", e);
+        },
+        AudioBackendKind::Auto => {
+            let gst = GstBackend::new();
+            if gst.is_working() {
+                return (Box::new(gst), Vec::new());
+            }
+            log::warn!("gstreamer audio isn't working, trying cpal");
+            match CpalBackend::new() {
+                Ok(b) => (Box::new(b), Vec::new()),
+                Err(e) => {
+                    log::error!("failed to initialize cpal audio: {}", e);
+                    let err = AudioError::new("initializing cpal audio", &e);
+                    (Box::new(gst), vec![err])
                 }
             }
-        };
+        }
+    }
+}
 
+impl AudioState {
+    fn init(kind: AudioBackendKind) -> AudioState {
+        let (backend, errors) = create_backend(kind);
         AudioState {
-            output_data: OutputData::new(),
-            output_tx,
-            output_pipeline: output_pipeline.ok(),
-            input_tx,
-            input_status_rx: status_rx,
+            input_status_rx: backend.input_status(),
+            backend,
             input_config: InputConfig::default(),
-            input_data,
+            errors,
+            calibration: None,
         }
     }
 
-    fn seek(&mut self, time: Time, velocity: f64) {
-        self.output_data.velocity = velocity;
-        self.output_data.start_time = time;
-        let result = || -> Result<()> {
-            if let Some(pipe) = self.output_pipeline.as_ref() {
-                if let Some(sink) = pipe.by_name("playback-sink") {
-                    // The "scaletempo" gstreamer plugin has some issues with playing backwards. We
-                    // avoid them by always playing forwards, but adapting our appsrc to produce
-                    // the samples backwards.
-                    sink.seek(
-                        velocity.abs(),
-                        gst::SeekFlags::FLUSH,
-                        gst::SeekType::Set,
-                        Some(gst::ClockTime::from_useconds(time.as_micros() as u64)),
-                        gst::SeekType::Set,
-                        None,
-                    )?;
-                }
-            }
-            Ok(())
-        }();
-        if let Err(e) = result {
-            log::error!(
-                "failed to seek (time {}, velocity {}): {}",
-                e,
-                time.as_micros(),
-                velocity
-            );
-        }
+    /// Returns all the errors that the user hasn't been told about yet.
+    fn take_errors(&mut self) -> Vec<AudioError> {
+        let mut ret = std::mem::take(&mut self.errors);
+        ret.extend(self.backend.take_errors());
+        ret
     }
 
     fn start_recording(&mut self, config: InputConfig) {
         self.input_config = config.clone();
-        {
-            let mut lock = self.input_data.lock().unwrap();
-            lock.buf.clear();
-            lock.vad.clear();
-        }
-        if self.input_tx.send(Some(config)).is_err() {
-            log::error!("audio input thread died, no audio will be recorded");
-        }
+        self.backend.start_recording(config);
     }
 
-    fn stop_recording(&mut self) -> AudioRecording {
-        let mut data = std::mem::replace(
-            self.input_data.lock().unwrap().deref_mut(),
-            InputData::new(),
-        );
-        if self.input_tx.send(None).is_err() {
-            log::error!("audio input thread died, no audio will be recorded");
-        }
-
-        // Which frames are worth keeping, according to voice detection?
-        let vad_threshold = self.input_config.vad_threshold;
-        let mut keep: Vec<_> = data.vad.iter().map(|&v| v > vad_threshold).collect();
-        keep.push(false);
-        let mut weights = vec![0.0f32; keep.len()];
-        convolve_bools(&keep[..], &mut weights[..], VOICELESS_FRAME_LAG);
-
-        // Windows for fading in and out when voice is detected or not.
-        let constant = vec![1.0; DenoiseState::FRAME_SIZE];
-        let fade_out: Vec<_> = (0..DenoiseState::FRAME_SIZE)
-            .rev()
-            .map(|x| x as f32 / DenoiseState::FRAME_SIZE as f32)
-            .collect();
-        let fade_in: Vec<_> = (0..DenoiseState::FRAME_SIZE)
-            .map(|x| x as f32 / DenoiseState::FRAME_SIZE as f32)
-            .collect();
-
-        for (frame, w) in data
-            .buf
-            .chunks_exact_mut(DenoiseState::FRAME_SIZE)
-            .zip(weights.windows(2))
-        {
-            let window = if w[0] < w[1] {
-                &fade_in
-            } else if w[0] > w[1] {
-                &fade_out
-            } else {
-                &constant
-            };
-            let lo = w[0].min(w[1]);
-            let hi = w[0].max(w[1]);
-            for (x, &y) in frame.iter_mut().zip(window) {
-                let weight = lo + (lo - hi) * y;
-                *x = (*x as f32 * weight).round() as i16;
-            }
-        }
+    /// Stops recording, and returns the recording along with the amount that was trimmed from its
+    /// beginning. (So the recording starts that much later than the recording was started.)
+    fn stop_recording(&mut self) -> (AudioRecording, TimeDiff) {
+        let mut data = self.backend.take_input_data();
+
+        // Cut out the parts where nobody is talking.
+        super::vad::gate(&mut data.buf, &data.vad, self.input_config.vad_threshold);
+
+        // Drop the beginning of the recording (if requested), and then fade in and out to avoid
+        // pops at the boundaries.
+        let trim = ms_to_samples(self.input_config.trim_start_ms).min(data.buf.len());
+        let fade = ms_to_samples(self.input_config.fade_ms);
+        data.buf.drain(..trim);
+        apply_fades(&mut data.buf, fade);
+        // The raw audio gets the same trimming and fading, so that it stays aligned.
+        let raw = if data.raw.is_empty() {
+            None
+        } else {
+            data.raw.drain(..trim.min(data.raw.len()));
+            apply_fades(&mut data.raw, fade);
+            Some(data.raw)
+        };
 
         // Now that we've changed the data, recalculate the loudness.
         data.loudness.reset();
@@ -210,146 +145,255 @@ impl AudioState {
         }
         let loudness = data.loudness.loudness_global().unwrap_or(-f64::INFINITY);
         let peak = data.loudness.sample_peak(0).unwrap_or(-f64::INFINITY);
-        AudioRecording {
+        let rec = AudioRecording {
             buf: data.buf,
+            raw,
             loudness,
             peak,
-        }
+        };
+        (rec, TimeDiff::from_audio_idx(trim as i64, SAMPLE_RATE))
     }
 
-    fn start_playing(&mut self, data: OutputData) {
-        self.output_data = data;
-        if self.output_tx.send(self.output_data.clone()).is_err() {
-            log::error!("audio thread not present");
+    /// Starts estimating the round-trip audio latency by playing a beep and recording it. The
+    /// measurement takes a second or two; when `calibration` fires, call
+    /// [`finish_calibration`](AudioState::finish_calibration) for the result.
+    ///
+    /// The estimate includes the time it takes the pipelines to start up, which is also what
+    /// happens when they record audio while the animation is playing.
+    fn start_calibration(&mut self) -> Result<()> {
+        if !self.backend.can_play() {
+            return Err(anyhow!("audio output isn't working"));
         }
 
-        if let Some(pipe) = self.output_pipeline.as_ref() {
-            if let Err(e) = pipe.set_state(gst::State::Playing) {
-                log::error!("failed to start playing audio: {}", e);
-                return;
-            }
-        }
-        self.seek(self.output_data.start_time, self.output_data.velocity);
+        let beep = TalkSnippet::new(make_beep(), Time::ZERO + CALIBRATION_BEEP_OFFSET, 1.0);
+        let (snips, _) = TalkSnippets::default().with_new_snippet(beep);
+
+        // We want to hear the beep exactly as it arrives, so turn off all the input processing.
+        let mut config = self.input_config.clone();
+        config.remove_noise = false;
+        config.vad_threshold = 0.0;
+        config.monitor = false;
+        config.source = AudioSource::Microphone;
+        self.backend.start_recording(config);
+        self.backend.start_playing(OutputData {
+            crossfades: snips.crossfades(),
+            snips,
+            music: None,
+            pencil: None,
+            start_time: Time::ZERO,
+            velocity: 1.0,
+        });
+        self.calibration = Some(crossbeam_channel::after(CALIBRATION_DURATION));
+        Ok(())
     }
 
-    fn stop_playing(&mut self) {
-        if let Some(pipe) = self.output_pipeline.as_ref() {
-            if let Err(e) = pipe.set_state(gst::State::Paused) {
-                log::error!("failed to stop audio: {}", e);
-            }
+    /// Stops measuring the latency, and returns the audio that was recorded while measuring.
+    fn stop_calibration(&mut self) -> Vec<i16> {
+        self.calibration = None;
+        self.backend.stop_playing();
+        self.backend.take_input_data().buf
+    }
+
+    /// Finishes measuring the latency (see [`start_calibration`](AudioState::start_calibration)).
+    fn finish_calibration(&mut self) -> Result<TimeDiff> {
+        let buf = self.stop_calibration();
+
+        let peak = buf.iter().map(|x| x.saturating_abs()).max().unwrap_or(0);
+        if peak < CALIBRATION_MIN_PEAK {
+            return Err(anyhow!(
+                "couldn't hear the beep. Check that your speakers and microphone are turned on"
+            ));
         }
+        let onset = buf
+            .iter()
+            .position(|x| x.saturating_abs() >= peak / 2)
+            .unwrap_or(0);
+        Ok(TimeDiff::from_audio_idx(onset as i64, SAMPLE_RATE) - CALIBRATION_BEEP_OFFSET)
     }
 }
 
-impl InputData {
-    fn new() -> InputData {
-        InputData {
-            buf: Vec::new(),
-            vad: Vec::new(),
-            // TODO: what are the failure cases for Ebur128::new?
-            loudness: EbuR128::new(
-                1,
-                SAMPLE_RATE,
-                ebur128::Mode::I | ebur128::Mode::M | ebur128::Mode::SAMPLE_PEAK,
-            )
-            .unwrap(),
-        }
+/// Compensates for the audio latency by moving a recording (that started at `time`) earlier. If
+/// that would move it before the beginning, we drop the part that doesn't fit. Returns the new
+/// start time of the recording.
+fn compensate_latency(rec: &mut AudioRecording, time: Time, latency_ms: i32) -> Time {
+    let latency = TimeDiff::from_micros(latency_ms as i64 * 1000);
+    let time_shifted = time - latency;
+    let overhang = latency - (time - time_shifted);
+    let overhang = overhang.as_audio_idx(SAMPLE_RATE).max(0) as usize;
+    rec.buf.drain(..overhang.min(rec.buf.len()));
+    if let Some(raw) = &mut rec.raw {
+        raw.drain(..overhang.min(raw.len()));
     }
+    time_shifted
+}
 
-    fn append_buffer(&mut self, buf: &[i16], vad: &[f32]) -> AudioRecordingStatus {
-        // What are the error cases here?
-        if let Err(e) = self.loudness.add_frames_i16(buf) {
-            log::error!("failed to calculate loudness: {}", e);
-        }
-        self.vad.extend_from_slice(vad);
-        self.buf.extend_from_slice(buf);
-
-        AudioRecordingStatus {
-            vad: *self.vad.last().unwrap_or(&0.0),
-            loudness: self
-                .loudness
-                .prev_sample_peak(0)
-                .ok()
-                .map(|x| (x.log10() * 20.0) as f32)
-                .unwrap_or(-f32::INFINITY),
-        }
+/// Returns the volume multiplier that normalizes the loudness of a recording.
+fn normalization_multiplier(rec: &AudioRecording) -> f64 {
+    // By default, we normalize to loudness -20. This is quieter than many sources ask for (e.g.
+    // youtube recommends -13 to -15), but going louder tends to introduce clipping.  Maybe some
+    // sort of dynamic range compression would be appropriate?
+    let target_loudness = -20.0;
+
+    // Multiplying a signal by x has the effect of adding 20 * log_10(x) to the loudness.
+    10.0f64
+        .powf((target_loudness - rec.loudness) / 20.0)
+        // Truncate the multiplier so that we don't clip. (Also make sure the peak isn't really
+        // small, because often the sample is all-zero or close to it.)
+        .min(1.0 / rec.peak.max(1.0 / 500.0))
+}
+
+/// Converts a number of milliseconds to a number of audio samples.
+fn ms_to_samples(ms: u32) -> usize {
+    (ms as usize * SAMPLE_RATE as usize) / 1000
+}
+
+/// Applies a linear fade-in to the first `len` samples of `buf`, and a linear fade-out to the last
+/// `len` samples. If the buffer is too short, the fades are shortened so that they don't overlap.
+fn apply_fades(buf: &mut [i16], len: usize) {
+    let len = len.min(buf.len() / 2);
+    if len == 0 {
+        return;
+    }
+
+    let n = buf.len();
+    for i in 0..len {
+        let weight = i as f32 / len as f32;
+        buf[i] = (buf[i] as f32 * weight).round() as i16;
+        buf[n - 1 - i] = (buf[n - 1 - i] as f32 * weight).round() as i16;
     }
 }
 
-/// Given a slice of bools, modifies it so that everything within `width` of a `true` is set to
-/// `true`.
-fn convolve_bools(xs: &[bool], out: &mut [f32], width: usize) {
-    let mut count = width;
-    let next_count = |x, count| if x { 0 } else { count + 1 };
-    let step = 1.0 / width as f32;
-
-    let mut weight = 0.0f32;
-    for (&x, y) in xs.iter().zip(&mut out[..]) {
-        count = next_count(x, count);
-        if count <= width {
-            *y = 1.0;
-            weight = 1.0;
-        } else {
-            weight = (weight - step).max(0.0);
-            *y = weight;
-        }
+/// Splits a buffer into pieces, cutting in the middle of every run of at least `min_len` zeros.
+/// (Voice detection sets the non-speech parts of a recording to zero.) The returned ranges cover
+/// the whole buffer. If `min_len` is zero, we don't split at all.
+fn split_at_silences(buf: &[i16], min_len: usize) -> Vec<Range<usize>> {
+    if min_len == 0 {
+        return vec![0..buf.len()];
     }
 
-    count = width;
-    weight = 0.0;
-    for (&x, y) in xs.iter().zip(&mut out[..]).rev() {
-        count = next_count(x, count);
-        if count <= width {
-            *y = 1.0;
-            weight = 1.0;
-        } else {
-            weight = (weight - step).max(0.0);
-            *y = y.max(weight);
+    let mut ret = Vec::new();
+    let mut seg_start = 0;
+    let mut run_start = None;
+    for (i, &x) in buf.iter().enumerate() {
+        if x == 0 {
+            run_start.get_or_insert(i);
+        } else if let Some(start) = run_start.take() {
+            // We don't split at the very beginning of the buffer: `trimmed` will take care of
+            // leading silence.
+            if i - start >= min_len && start > seg_start {
+                let mid = start + (i - start) / 2;
+                ret.push(seg_start..mid);
+                seg_start = mid;
+            }
         }
     }
+    ret.push(seg_start..buf.len());
+    ret
+}
+
+/// Creates a short sine-wave beep, for latency calibration.
+fn make_beep() -> Vec<i16> {
+    let len = CALIBRATION_BEEP_LEN.as_audio_idx(SAMPLE_RATE) as usize;
+    let amplitude = std::i16::MAX as f32 / 2.0;
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            (amplitude * (2.0 * std::f32::consts::PI * CALIBRATION_BEEP_FREQ * t).sin()) as i16
+        })
+        .collect()
 }
 
 /// The main function in this module. Spawn it in a new thread, and it will take care of audio
 /// input and output. Send commands to it through the `cmd` channel to make it play, stop, record,
 /// and so on. The audio loop will send things back through `sink`, targeted at `target`.
-pub fn audio_loop(cmd: Receiver<Cmd>, sink: ExtEventSink, target: Target) {
-    let mut state = AudioState::init();
+pub fn audio_loop(
+    cmd: Receiver<Cmd>,
+    sink: ExtEventSink,
+    target: Target,
+    kind: AudioBackendKind,
+) {
+    let mut state = AudioState::init(kind);
+    let device_check = crossbeam_channel::tick(DEVICE_CHECK_INTERVAL);
+    let mut last_device_check = SystemTime::now();
 
     loop {
+        // Some of the commands (and the initialization) can break the audio, so check for new
+        // errors every time around.
+        let errors = state.take_errors();
+        if !errors.is_empty() {
+            let _ = sink.submit_command(cmd::AUDIO_ERRORS, errors, target);
+        }
+
+        let calibration = state
+            .calibration
+            .clone()
+            .unwrap_or_else(crossbeam_channel::never);
         select! {
             recv(cmd) -> msg => {
                 use Cmd::*;
+                // Measuring the latency uses the audio input and output, so any other command
+                // interrupts it.
+                if state.calibration.is_some() {
+                    state.stop_calibration();
+                    let result = Err("interrupted by other audio".to_owned());
+                    let _ = sink.submit_command(cmd::LATENCY_CALIBRATED, result, target);
+                }
                 match msg {
-                    Ok(Play(data)) => state.start_playing(data),
-                    Ok(Seek(time, velocity)) => state.seek(time, velocity),
-                    Ok(StopPlaying) => state.stop_playing(),
+                    Ok(Play(data)) => state.backend.start_playing(data),
+                    Ok(Seek(time, velocity)) => state.backend.seek(time, velocity),
+                    Ok(StopPlaying) => state.backend.stop_playing(),
                     Ok(Record(config)) => {
                         state.start_recording(config);
                     }
+                    Ok(CalibrateLatency) => {
+                        if let Err(e) = state.start_calibration() {
+                            let result = Err(e.to_string());
+                            let _ = sink.submit_command(cmd::LATENCY_CALIBRATED, result, target);
+                        }
+                    }
                     Ok(StopRecording(time)) => {
-                        let rec = state.stop_recording();
-
-                        // By default, we normalize to loudness -20. This is quieter than many
-                        // sources ask for (e.g. youtube recommends -13 to -15), but going louder
-                        // tends to introduce clipping.  Maybe some sort of dynamic range
-                        // compression would be appropriate?
-                        let target_loudness = -20.0;
-
-                        // Multiplying a signal by x has the effect of adding 20 * log_10(x) to the
-                        // loudness.
-                        let multiplier = 10.0f64
-                            .powf((target_loudness - rec.loudness) / 20.0)
-                            // Truncate the multiplier so that we don't clip. (Also make sure the
-                            // peak isn't really small, because often the sample is all-zero or
-                            // close to it.)
-                            .min(1.0 / rec.peak.max(1.0 / 500.0));
-
-                        let snip = TalkSnippet::new(rec.buf, time, multiplier as f32);
-                        if let Some(trimmed) = snip.trimmed() {
-                            let cmd = cmd::TalkSnippetCmd { snip: trimmed, orig_start: snip.start_time() };
+                        let (mut rec, trimmed) = state.stop_recording();
+                        let latency_ms = state.input_config.latency_ms;
+                        let time = compensate_latency(&mut rec, time + trimmed, latency_ms);
+                        let multiplier = normalization_multiplier(&rec);
+
+                        let min_silence = ms_to_samples(state.input_config.split_silence_ms);
+                        let snips: Vec<_> = split_at_silences(&rec.buf, min_silence)
+                            .into_iter()
+                            .filter_map(|r| {
+                                let offset = r.start as i64;
+                                let start = time + TimeDiff::from_audio_idx(offset, SAMPLE_RATE);
+                                let buf = rec.buf[r.clone()].to_owned();
+                                let mut snip = TalkSnippet::new(buf, start, multiplier as f32);
+                                if let Some(raw) = &rec.raw {
+                                    snip = snip.with_raw_recording(raw[r].to_owned());
+                                }
+                                snip.trimmed()
+                            })
+                            .collect();
+                        if !snips.is_empty() {
+                            let cmd = cmd::TalkSnippetCmd { snips, orig_start: time };
                             let _ = sink.submit_command(cmd::ADD_TALK_SNIPPET, cmd, target);
                         }
                     }
+                    Ok(StartPunchIn(output, config)) => {
+                        state.start_recording(config);
+                        state.backend.start_playing(output);
+                    }
+                    Ok(StopPunchIn(punch)) => {
+                        state.backend.stop_playing();
+                        let (mut rec, trimmed) = state.stop_recording();
+                        let latency_ms = state.input_config.latency_ms;
+                        let start = punch.record_start + trimmed;
+                        let time = compensate_latency(&mut rec, start, latency_ms);
+                        let multiplier = normalization_multiplier(&rec);
+                        let mut take = TalkSnippet::new(rec.buf, time, multiplier as f32);
+                        if let Some(raw) = rec.raw {
+                            take = take.with_raw_recording(raw);
+                        }
+                        let cmd = cmd::PunchInCmd { punch, take };
+                        let _ = sink.submit_command(cmd::PUNCH_IN_RECORDED, cmd, target);
+                    }
                     Err(_) => {
                         // Failure to receive here just means that the main program exited.
                         break;
@@ -357,127 +401,100 @@ pub fn audio_loop(cmd: Receiver<Cmd>, sink: ExtEventSink, target: Target) {
                 }
             }
             recv(state.input_status_rx) -> msg => {
-                    let _ = sink.submit_command(cmd::RECORDING_AUDIO_STATUS, msg.unwrap(), target);
+                match msg {
+                    Ok(status) => {
+                        let _ = sink.submit_command(cmd::RECORDING_AUDIO_STATUS, status, target);
+                    }
+                    Err(_) => {
+                        // The input pipeline is gone (probably because it failed to start). We
+                        // already logged an error about that, so just stop listening to it.
+                        state.input_status_rx = crossbeam_channel::never();
+                    }
+                }
+            }
+            recv(calibration) -> _ => {
+                let result = state.finish_calibration().map_err(|e| e.to_string());
+                let _ = sink.submit_command(cmd::LATENCY_CALIBRATED, result, target);
+            }
+            recv(device_check) -> _ => {
+                let now = SystemTime::now();
+                let resumed = now
+                    .duration_since(last_device_check)
+                    .map(|d| d > SUSPEND_THRESHOLD)
+                    .unwrap_or(false);
+                last_device_check = now;
+
+                // Audio streams often don't survive the system being suspended, and they don't
+                // necessarily report errors about it. So we restart them whenever we wake up.
+                let msg = if resumed {
+                    state.backend.rebuild();
+                    Some("Restarted audio after the system resumed".to_owned())
+                } else {
+                    state.backend.check_devices()
+                };
+                if let Some(msg) = msg {
+                    state.input_status_rx = state.backend.input_status();
+                    let _ = sink.submit_command(cmd::AUDIO_DEVICES_CHANGED, msg, target);
+                }
             }
 
         }
     }
 }
 
-fn create_input_pipeline(
-    data: Arc<Mutex<InputData>>,
-    config_rx: Receiver<Option<InputConfig>>,
-    status_tx: Sender<AudioRecordingStatus>,
-) -> Result<gst::Pipeline> {
-    let pipeline = gst::Pipeline::new(None);
-    let src = create_gst_elt("autoaudiosrc", "record-source")?;
-    let resample = create_gst_elt("audioresample", "record-resample")?;
-    let convert = create_gst_elt("audioconvert", "record-convert")?;
-    let queue = create_gst_elt("queue", "record-queue")?;
-    let sink = create_gst_elt("appsink", "record-sink")?;
-    pipeline.add_many(&[&src, &resample, &convert, &queue, &sink])?;
-    gst::Element::link_many(&[&src, &resample, &convert, &queue, &sink])?;
-
-    let sink = sink
-        .dynamic_cast::<gst_app::AppSink>()
-        .map_err(|_| anyhow!("bug: couldn't cast sink to an AppSink"))?;
-    let audio_info = AudioInfo::builder(AudioFormat::S16le, SAMPLE_RATE as u32, 1).build()?;
-    sink.set_caps(Some(&audio_info.to_caps()?));
-
-    let mut denoise_state = DenoiseState::new();
-    let mut denoise_in_buf = Vec::with_capacity(DenoiseState::FRAME_SIZE);
-    let mut denoise_out_buf = vec![0.0; DenoiseState::FRAME_SIZE];
-    let mut i16_buf = Vec::with_capacity(DenoiseState::FRAME_SIZE);
-    let mut vad_buf = Vec::new();
-    let mut config = None;
-
-    let new_sample = move |sink: &gst_app::AppSink| -> Result<gst::FlowSuccess, gst::FlowError> {
-        let sample = match sink.pull_sample() {
-            Ok(s) => s,
-            Err(e) => {
-                log::error!("Failed to pull sample: {}", e);
-                return Err(gst::FlowError::CustomError);
-            }
-        };
-
-        for c in config_rx.try_iter() {
-            config = c;
-        }
-        let config = match config.as_ref() {
-            Some(c) => c,
-            None => {
-                return Ok(gst::FlowSuccess::Ok);
-            }
-        };
-
-        let buffer = match sample.buffer() {
-            Some(b) => b,
-            None => {
-                log::error!("Failed to get sample buffer");
-                return Err(gst::FlowError::CustomError);
-            }
-        };
-
-        let buffer = match buffer.map_readable() {
-            Ok(b) => b,
-            Err(e) => {
-                log::error!("Failed to map buffer as readable: {}", e);
-                return Err(gst::FlowError::CustomError);
-            }
-        };
-
-        // The buffer is in bytes; each sample is two bytes.
-        let mut buffer = buffer.as_slice();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        while !buffer.is_empty() {
-            let cap_remaining = DenoiseState::FRAME_SIZE - denoise_in_buf.len();
-            let size = (buffer.len() / 2).min(cap_remaining);
-            for sample in buffer[..(size * 2)].chunks_exact(2) {
-                denoise_in_buf.push(i16::from_le_bytes([sample[0], sample[1]]) as f32);
-            }
-            buffer = &buffer[(size * 2)..];
-
-            let vad = if config.remove_noise {
-                denoise_state.process_frame(&mut denoise_out_buf, &denoise_in_buf)
-            } else {
-                for (&src, dst) in denoise_in_buf.iter().zip(&mut denoise_out_buf[..]) {
-                    *dst = src;
-                }
-                1.0
-            };
-            denoise_in_buf.clear();
-
-            vad_buf.push(vad);
-            for sample in &denoise_out_buf {
-                i16_buf.push(sample.round() as i16);
-            }
-        }
+    #[test]
+    fn samples() {
+        assert_eq!(ms_to_samples(0), 0);
+        assert_eq!(ms_to_samples(1000), SAMPLE_RATE as usize);
+        assert_eq!(ms_to_samples(10), SAMPLE_RATE as usize / 100);
+    }
 
-        let status = data.lock().unwrap().append_buffer(&i16_buf, &vad_buf);
-        let _ = status_tx.send(status);
-        i16_buf.clear();
-        vad_buf.clear();
-        Ok(gst::FlowSuccess::Ok)
-    };
-    sink.set_callbacks(
-        gst_app::AppSinkCallbacks::builder()
-            .new_sample(new_sample)
-            .build(),
-    );
-    Ok(pipeline)
-}
+    #[test]
+    fn fades() {
+        let mut buf = vec![1000; 10];
+        apply_fades(&mut buf, 3);
+        assert_eq!(buf, vec![0, 333, 667, 1000, 1000, 1000, 1000, 667, 333, 0]);
+
+        // The fades get shortened so that they don't overlap.
+        let mut buf = vec![1000; 4];
+        apply_fades(&mut buf, 10);
+        assert_eq!(buf, vec![0, 500, 500, 0]);
+
+        // Buffers that are too short to fade are left alone.
+        let mut buf = vec![1000];
+        apply_fades(&mut buf, 10);
+        assert_eq!(buf, vec![1000]);
+        apply_fades(&mut [], 10);
+    }
 
-fn create_output_pipeline(rx: Receiver<OutputData>) -> Result<gst::Pipeline> {
-    let pipeline = gst::Pipeline::new(None);
-    let src = create_appsrc(rx, "playback-source")?;
-    let scale = create_gst_elt("scaletempo", "playback-scale")?;
-    let resample = create_gst_elt("audioresample", "playback-resample")?;
-    let convert = create_gst_elt("audioconvert", "playback-convert")?;
-    let queue = create_gst_elt("queue", "playback-queue")?;
-    let sink = create_gst_elt("autoaudiosink", "playback-sink")?;
+    #[test]
+    fn silences() {
+        // Without silence, there's nothing to split.
+        assert_eq!(split_at_silences(&[1, 2, 3, 4], 2), vec![0..4]);
+
+        // Silences shorter than `min_len` don't split, but ones that are exactly `min_len` do.
+        assert_eq!(split_at_silences(&[1, 0, 1], 2), vec![0..3]);
+        assert_eq!(split_at_silences(&[1, 0, 0, 1], 2), vec![0..2, 2..4]);
+        assert_eq!(
+            split_at_silences(&[1, 0, 0, 0, 1, 0, 0, 1], 2),
+            vec![0..2, 2..6, 6..8]
+        );
 
-    pipeline.add_many(&[&src, &scale, &resample, &convert, &queue, &sink])?;
-    gst::Element::link_many(&[&src, &scale, &resample, &convert, &queue, &sink])?;
+        // Leading and trailing silences don't split.
+        assert_eq!(split_at_silences(&[0, 0, 0, 1, 1], 2), vec![0..5]);
+        assert_eq!(split_at_silences(&[1, 1, 0, 0, 0], 2), vec![0..5]);
+        assert_eq!(
+            split_at_silences(&[0, 0, 1, 0, 0, 1, 0, 0], 2),
+            vec![0..4, 4..8]
+        );
 
-    Ok(pipeline)
+        // A `min_len` of zero means not splitting at all.
+        assert_eq!(split_at_silences(&[1, 0, 0, 1], 0), vec![0..4]);
+        assert_eq!(split_at_silences(&[], 0), vec![0..0]);
+        assert_eq!(split_at_silences(&[], 2), vec![0..0]);
+    }
 }
@@ -0,0 +1,175 @@
+//! Voice activity detection, for cutting out the parts of a recording where nobody is talking.
+//!
+//! While recording, the audio backend gets the voice probabilities for free from the noise
+//! removal. For audio that has already been recorded, [`denoise`] and [`detect_speech`] work them
+//! out again.
+
+use nnnoiseless::DenoiseState;
+
+// We don't simply drop frames where voice was not detected: doing so tends to cut off consonants
+// like "t". Instead, we do some "smoothing in time": if no voice was detected within
+// `VOICELESS_FRAME_LAG` frames (either forwards or backwards in time) of the current frame, we
+// drop the current frame.
+const VOICELESS_FRAME_LAG: usize = 10;
+
+/// Removes the noise from `buf`, the same way that it gets removed while recording. Returns the
+/// denoised audio (which has the same length as `buf`), and the probability that each frame (of
+/// size `DenoiseState::FRAME_SIZE`) contains speech. The last frame gets padded with silence if it
+/// isn't full.
+///
+/// `progress` gets called every now and then with the fraction of `buf` that has been processed
+/// so far. This is slow for long recordings, so it should be done in the background.
+pub fn denoise(buf: &[i16], mut progress: impl FnMut(f64)) -> (Vec<i16>, Vec<f32>) {
+    let mut state = DenoiseState::new();
+    let mut in_buf = vec![0.0; DenoiseState::FRAME_SIZE];
+    let mut out_buf = vec![0.0; DenoiseState::FRAME_SIZE];
+    let mut out = Vec::with_capacity(buf.len() + DenoiseState::FRAME_SIZE);
+    let mut vad = Vec::with_capacity(buf.len() / DenoiseState::FRAME_SIZE + 1);
+    for frame in buf.chunks(DenoiseState::FRAME_SIZE) {
+        for (x, &y) in in_buf
+            .iter_mut()
+            .zip(frame.iter().chain(std::iter::repeat(&0)))
+        {
+            *x = y as f32;
+        }
+        vad.push(state.process_frame(&mut out_buf, &in_buf));
+        out.extend(out_buf.iter().map(|x| x.round() as i16));
+        progress(out.len().min(buf.len()) as f64 / buf.len() as f64);
+    }
+    out.truncate(buf.len());
+    (out, vad)
+}
+
+/// For every frame (of size `DenoiseState::FRAME_SIZE`) in `buf`, estimates the probability that
+/// it contains speech.
+///
+/// This runs the noise removal over all of `buf`, so it should be done in the background.
+pub fn detect_speech(buf: &[i16]) -> Vec<f32> {
+    denoise(buf, |_| {}).1
+}
+
+/// Silences the frames of `buf` that don't have speech in them, according to the probabilities
+/// in `vad` (one for each frame). A frame counts as speech if its probability is more than
+/// `threshold`; we keep a few frames on either side of the speech, and fade in and out at the
+/// boundaries.
+///
+/// If `vad` doesn't cover all of `buf`, the rest of `buf` is left alone.
+pub fn gate(buf: &mut [i16], vad: &[f32], threshold: f32) {
+    // Which frames are worth keeping, according to voice detection?
+    let mut keep: Vec<_> = vad.iter().map(|&v| v > threshold).collect();
+    keep.push(false);
+    let mut weights = vec![0.0f32; keep.len()];
+    convolve_bools(&keep[..], &mut weights[..], VOICELESS_FRAME_LAG);
+
+    // Windows for fading in and out when voice is detected or not.
+    let constant = vec![1.0; DenoiseState::FRAME_SIZE];
+    let fade_out: Vec<_> = (0..DenoiseState::FRAME_SIZE)
+        .rev()
+        .map(|x| x as f32 / DenoiseState::FRAME_SIZE as f32)
+        .collect();
+    let fade_in: Vec<_> = (0..DenoiseState::FRAME_SIZE)
+        .map(|x| x as f32 / DenoiseState::FRAME_SIZE as f32)
+        .collect();
+
+    for (frame, w) in buf
+        .chunks_mut(DenoiseState::FRAME_SIZE)
+        .zip(weights.windows(2))
+    {
+        let window = if w[0] < w[1] {
+            &fade_in
+        } else if w[0] > w[1] {
+            &fade_out
+        } else {
+            &constant
+        };
+        let lo = w[0].min(w[1]);
+        let hi = w[0].max(w[1]);
+        for (x, &y) in frame.iter_mut().zip(window) {
+            let weight = lo + (hi - lo) * y;
+            *x = (*x as f32 * weight).round() as i16;
+        }
+    }
+}
+
+/// Given a slice of bools, modifies it so that everything within `width` of a `true` is set to
+/// `true`.
+fn convolve_bools(xs: &[bool], out: &mut [f32], width: usize) {
+    let mut count = width;
+    let next_count = |x, count| if x { 0 } else { count + 1 };
+    let step = 1.0 / width as f32;
+
+    let mut weight = 0.0f32;
+    for (&x, y) in xs.iter().zip(&mut out[..]) {
+        count = next_count(x, count);
+        if count <= width {
+            *y = 1.0;
+            weight = 1.0;
+        } else {
+            weight = (weight - step).max(0.0);
+            *y = weight;
+        }
+    }
+
+    count = width;
+    weight = 0.0;
+    for (&x, y) in xs.iter().zip(&mut out[..]).rev() {
+        count = next_count(x, count);
+        if count <= width {
+            *y = 1.0;
+            weight = 1.0;
+        } else {
+            weight = (weight - step).max(0.0);
+            *y = y.max(weight);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: usize = DenoiseState::FRAME_SIZE;
+
+    #[test]
+    fn gating() {
+        // Speech in the middle frame only.
+        let frames = 4 * VOICELESS_FRAME_LAG + 1;
+        let mut vad = vec![0.1; frames];
+        vad[frames / 2] = 0.9;
+        let mut buf = vec![1000i16; frames * FRAME];
+        gate(&mut buf, &vad, 0.5);
+
+        // The speech (and the frames next to it) are kept.
+        assert!(buf[(frames / 2 - 1) * FRAME..(frames / 2 + 2) * FRAME]
+            .iter()
+            .all(|&x| x == 1000));
+        // Far away from the speech, everything is silenced.
+        assert!(buf[..FRAME].iter().all(|&x| x == 0));
+        assert!(buf[(frames - 1) * FRAME..].iter().all(|&x| x == 0));
+        // In between, it fades in and out without ever getting louder than the original.
+        assert!(buf.iter().all(|&x| (0..=1000).contains(&x)));
+
+        // With a high enough threshold, there's no speech at all.
+        let mut buf = vec![1000i16; frames * FRAME];
+        gate(&mut buf, &vad, 0.95);
+        assert!(buf.iter().all(|&x| x == 0));
+
+        // The part of the buffer that isn't covered by `vad` doesn't change.
+        let mut buf = vec![1000i16; (frames + 1) * FRAME];
+        gate(&mut buf, &vad, 0.95);
+        assert!(buf[frames * FRAME..].iter().all(|&x| x == 1000));
+    }
+
+    #[test]
+    fn denoising() {
+        // One and a half frames, so the last one gets padded.
+        let buf = vec![0i16; FRAME * 3 / 2];
+        let mut fractions = Vec::new();
+        let (out, vad) = denoise(&buf, |f| fractions.push(f));
+        assert_eq!(out.len(), buf.len());
+        assert_eq!(vad.len(), 2);
+        assert_eq!(fractions, vec![2.0 / 3.0, 1.0]);
+        // There's no speech in silence.
+        assert!(vad.iter().all(|&v| v < 0.5));
+    }
+}
@@ -2,45 +2,185 @@ use crossbeam_channel::Sender;
 use directories_next::ProjectDirs;
 use druid::{ExtEventSink, WindowId};
 use std::ffi::OsStr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
+use crate::cache::CacheKind;
 use crate::cmd::{AsyncSaveResult, FINISHED_ASYNC_SAVE};
+use crate::config::Config;
 use crate::SaveFileData;
 
+/// All autosaved files have names ending with this.
+const AUTOSAVE_SUFFIX: &str = "autosave.scb";
+
 pub struct AutosaveData {
     pub path: Option<PathBuf>,
     pub data: SaveFileData,
 }
 
+/// An autosaved file, as shown in the "Open autosave" dialog.
+#[derive(Clone, Debug)]
+pub struct AutosaveEntry {
+    pub path: PathBuf,
+    pub modified: SystemTime,
+}
+
+/// The directory where autosaved files go.
+pub fn autosave_dir(config: &Config) -> Option<PathBuf> {
+    if let Some(dir) = &config.autosave.dir {
+        Some(dir.clone())
+    } else {
+        crate::cache::subdir(&config.cache, CacheKind::Autosave)
+    }
+}
+
+/// The directory where autosaved files went before they were kept in the cache directory.
+fn old_autosave_dir() -> Option<PathBuf> {
+    ProjectDirs::from("ink", "scribl", "scribl").map(|d| d.data_local_dir().to_owned())
+}
+
+/// Moves the autosaved files in `from` to `to`, so that autosaves made by older versions can
+/// still be found. If there's already an autosave with the same name in `to`, the one in `from`
+/// is left where it is.
+fn move_autosaves(from: &Path, to: &Path) {
+    if from == to {
+        return;
+    }
+    // Usually, there's nothing to move (or even no directory to move from).
+    let entries = match std::fs::read_dir(from) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name();
+        if !name.to_string_lossy().ends_with(AUTOSAVE_SUFFIX) {
+            continue;
+        }
+        let new_path = to.join(&name);
+        if new_path.exists() {
+            continue;
+        }
+        // Renaming doesn't work across file systems, so fall back to copying.
+        let result = std::fs::create_dir_all(to)
+            .and_then(|_| std::fs::rename(entry.path(), &new_path))
+            .or_else(|_| {
+                std::fs::copy(entry.path(), &new_path)?;
+                std::fs::remove_file(entry.path())
+            });
+        match result {
+            Ok(()) => log::info!(
+                "moved autosave {} to {}",
+                entry.path().display(),
+                new_path.display()
+            ),
+            Err(e) => log::warn!("failed to move autosave {}: {}", entry.path().display(), e),
+        }
+    }
+}
+
 impl AutosaveData {
-    fn autosave_path(&self) -> Option<PathBuf> {
-        if let Some(proj_dirs) = ProjectDirs::from("ink", "scribl", "scribl") {
-            let autosave_name =
-                if let Some(orig_name) = self.path.as_ref().and_then(|p| p.file_stem()) {
-                    let mut name = orig_name.to_owned();
-                    name.push(".autosave.scb");
-                    name
-                } else {
-                    OsStr::new("untitled-autosave.scb").to_os_string()
-                };
-            let mut ret = proj_dirs.data_local_dir().to_owned();
-            ret.push(autosave_name);
-            Some(ret)
+    fn autosave_path(&self, dir: &Path) -> PathBuf {
+        let autosave_name = if let Some(orig_name) = self.path.as_ref().and_then(|p| p.file_stem())
+        {
+            let mut name = orig_name.to_owned();
+            name.push(".");
+            name.push(AUTOSAVE_SUFFIX);
+            name
         } else {
-            None
+            let mut name = OsStr::new("untitled-").to_os_string();
+            name.push(AUTOSAVE_SUFFIX);
+            name
+        };
+        dir.join(autosave_name)
+    }
+}
+
+/// Returns all the autosaved files in `dir`, newest first.
+pub fn list_autosaves(dir: &Path) -> Vec<AutosaveEntry> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(e) => {
+            log::warn!("couldn't read the autosave directory: {}", e);
+            return Vec::new();
+        }
+    };
+    let mut ret: Vec<_> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().ends_with(AUTOSAVE_SUFFIX))
+        .filter_map(|e| {
+            let modified = e.metadata().and_then(|m| m.modified()).ok()?;
+            Some(AutosaveEntry {
+                path: e.path(),
+                modified,
+            })
+        })
+        .collect();
+    ret.sort_by(|a, b| b.modified.cmp(&a.modified));
+    ret
+}
+
+/// Given some autosaves (newest first), returns the ones that should be deleted so that only
+/// `max_count` of them are left.
+fn excess_autosaves(autosaves: &[AutosaveEntry], max_count: usize) -> &[AutosaveEntry] {
+    &autosaves[max_count.min(autosaves.len())..]
+}
+
+/// Deletes the oldest autosaves in `dir`, so that at most `max_count` are left.
+fn clean_up(dir: &Path, max_count: usize) {
+    for old in excess_autosaves(&list_autosaves(dir), max_count) {
+        if let Err(e) = std::fs::remove_file(&old.path) {
+            log::warn!(
+                "failed to delete old autosave {}: {}",
+                old.path.display(),
+                e
+            );
         }
     }
 }
 
-pub fn spawn_autosave_thread(ext_cmd: ExtEventSink, id: WindowId) -> Sender<AutosaveData> {
+/// A description of how long ago something happened, like "5 minutes ago".
+pub fn describe_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    let (n, unit) = if secs < 60 {
+        return "just now".to_owned();
+    } else if secs < 60 * 60 {
+        (secs / 60, "minute")
+    } else if secs < 24 * 60 * 60 {
+        (secs / (60 * 60), "hour")
+    } else {
+        (secs / (24 * 60 * 60), "day")
+    };
+    if n == 1 {
+        format!("1 {} ago", unit)
+    } else {
+        format!("{} {}s ago", n, unit)
+    }
+}
+
+pub fn spawn_autosave_thread(
+    ext_cmd: ExtEventSink,
+    id: WindowId,
+    config: &Config,
+) -> Sender<AutosaveData> {
+    let dir = autosave_dir(config);
+    let max_count = config.autosave.max_count;
     let (tx, rx) = crossbeam_channel::unbounded::<AutosaveData>();
     std::thread::spawn(move || {
+        if let (Some(dir), Some(old_dir)) = (&dir, old_autosave_dir()) {
+            move_autosaves(&old_dir, dir);
+        }
         while let Ok(autosave) = rx.recv() {
             // We save only the most recent requested file (so as not to fall behind in case saving
             // is really slow, or the autosave interval is really short).
             let autosave = rx.try_iter().last().unwrap_or(autosave);
-            if let Some(path) = autosave.autosave_path() {
-                let result = autosave.data.save_to_path(&path);
+            if let Some(dir) = &dir {
+                let path = autosave.autosave_path(dir);
+                let result = std::fs::create_dir_all(dir)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|_| autosave.data.save_to_path(&path));
+                if result.is_ok() {
+                    clean_up(dir, max_count);
+                }
                 let _ = ext_cmd.submit_command(
                     FINISHED_ASYNC_SAVE,
                     Box::new(AsyncSaveResult {
@@ -59,3 +199,56 @@ pub fn spawn_autosave_thread(ext_cmd: ExtEventSink, id: WindowId) -> Sender<Auto
 
     tx
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excess() {
+        let entry = |secs| AutosaveEntry {
+            path: PathBuf::from(format!("{}.autosave.scb", secs)),
+            modified: SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+        };
+        let autosaves = vec![entry(3), entry(2), entry(1)];
+        let excess = excess_autosaves(&autosaves, 2);
+        assert_eq!(excess.len(), 1);
+        assert_eq!(excess[0].path, Path::new("1.autosave.scb"));
+        assert!(excess_autosaves(&autosaves, 5).is_empty());
+    }
+
+    #[test]
+    fn move_old_autosaves() {
+        let from = tempfile::tempdir().unwrap();
+        let to = tempfile::tempdir().unwrap();
+        let to_dir = to.path().join("autosave");
+        std::fs::write(from.path().join("a.autosave.scb"), "old a").unwrap();
+        std::fs::write(from.path().join("b.autosave.scb"), "old b").unwrap();
+        std::fs::write(from.path().join("other.txt"), "other").unwrap();
+        std::fs::create_dir(&to_dir).unwrap();
+        std::fs::write(to_dir.join("b.autosave.scb"), "new b").unwrap();
+
+        move_autosaves(from.path(), &to_dir);
+        let read = |path: PathBuf| std::fs::read_to_string(path).unwrap();
+        assert_eq!(read(to_dir.join("a.autosave.scb")), "old a");
+        assert_eq!(read(to_dir.join("b.autosave.scb")), "new b");
+        assert!(!from.path().join("a.autosave.scb").exists());
+        assert_eq!(read(from.path().join("b.autosave.scb")), "old b");
+        assert_eq!(read(from.path().join("other.txt")), "other");
+        assert_eq!(list_autosaves(&to_dir).len(), 2);
+    }
+
+    #[test]
+    fn ages() {
+        assert_eq!(describe_age(Duration::from_secs(5)), "just now");
+        assert_eq!(describe_age(Duration::from_secs(60)), "1 minute ago");
+        assert_eq!(
+            describe_age(Duration::from_secs(3 * 60 * 60)),
+            "3 hours ago"
+        );
+        assert_eq!(
+            describe_age(Duration::from_secs(2 * 24 * 60 * 60)),
+            "2 days ago"
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Exporting lots of files at once, from the command line.
+//!
+//! Progress gets reported on stdout as a stream of JSON objects, one per line, so that scripts
+//! can keep track of what's going on.
+
+use anyhow::{anyhow, Result};
+use crossbeam_channel::unbounded;
+use std::path::{Path, PathBuf};
+
+use crate::config::Export;
+use crate::encode::EncodingStatus;
+use crate::progress::{Event, Rate};
+use crate::{cmd, SaveFileData, ScriblState};
+
+/// What to export, and how.
+pub struct BatchOptions {
+    /// Glob patterns matching the files to export.
+    pub patterns: Vec<String>,
+    /// The directory to put the videos in.
+    pub out_dir: PathBuf,
+    /// How many files to export at the same time.
+    pub jobs: usize,
+    pub config: Export,
+}
+
+/// Finds all the files matching the patterns, without repeating any of them.
+fn find_inputs(patterns: &[String]) -> Result<Vec<PathBuf>> {
+    let mut ret = Vec::new();
+    for pattern in patterns {
+        let mut found = false;
+        for path in glob::glob(pattern)? {
+            let path = path?;
+            found = true;
+            if !ret.contains(&path) {
+                ret.push(path);
+            }
+        }
+        if !found {
+            return Err(anyhow!("no files matched \"{}\"", pattern));
+        }
+    }
+    Ok(ret)
+}
+
+/// The video that `input` gets exported to.
+fn output_path(input: &Path, out_dir: &Path) -> PathBuf {
+    let mut name = input
+        .file_stem()
+        .unwrap_or_else(|| input.as_os_str())
+        .to_owned();
+    name.push(".mp4");
+    out_dir.join(name)
+}
+
+/// Exports a single file, blocking until it's done. Returns true if it succeeded.
+fn export_one(input: &Path, out_dir: &Path, config: &Export) -> bool {
+    let input_name = input.to_string_lossy();
+    let output = output_path(input, out_dir);
+    let output_name = output.to_string_lossy();
+
+    let save_file = match SaveFileData::load_from_path(input) {
+        Ok(s) => s,
+        Err(e) => {
+            Event::Failed {
+                input: Some(&*input_name),
+                error: e.to_string(),
+            }
+            .print();
+            return false;
+        }
+    };
+
+    Event::Started {
+        input: Some(&*input_name),
+        output: &output_name,
+    }
+    .print();
+    let export = cmd::ExportCmd {
+        scribl: ScriblState::from_save_file(&save_file),
+        filename: output.clone(),
+        config: config.clone(),
+        range: None,
+    };
+    let (tx, rx) = unbounded();
+    std::thread::spawn(move || crate::encode::encode_blocking(export, tx));
+
+    let mut rate = Rate::start();
+    let mut ok = false;
+    for msg in rx.iter() {
+        match msg {
+            EncodingStatus::Encoding { frame, out_of } => {
+                if rate.percent_changed(frame, out_of) {
+                    Event::Progress {
+                        input: Some(&*input_name),
+                        frame,
+                        out_of,
+                        fps: rate.fps(frame),
+                        eta: rate.eta(frame, out_of),
+                    }
+                    .print();
+                }
+            }
+            EncodingStatus::Error(error) => Event::Failed {
+                input: Some(&*input_name),
+                error,
+            }
+            .print(),
+            EncodingStatus::Finished(_, summary) => {
+                ok = true;
+                Event::Finished {
+                    input: Some(&*input_name),
+                    output: &output_name,
+                    summary: Some((&summary).into()),
+                }
+                .print();
+            }
+        }
+    }
+    ok
+}
+
+/// Exports all the files matching the patterns, returning an error if any of them failed.
+pub fn export_batch(opts: BatchOptions) -> Result<()> {
+    let inputs = find_inputs(&opts.patterns)?;
+    std::fs::create_dir_all(&opts.out_dir)?;
+
+    let (input_tx, input_rx) = unbounded();
+    for input in inputs {
+        let _ = input_tx.send(input);
+    }
+    drop(input_tx);
+
+    let (result_tx, result_rx) = unbounded();
+    let workers: Vec<_> = (0..opts.jobs.max(1))
+        .map(|_| {
+            let input_rx = input_rx.clone();
+            let result_tx = result_tx.clone();
+            let out_dir = opts.out_dir.clone();
+            let config = opts.config.clone();
+            std::thread::spawn(move || {
+                for input in input_rx.iter() {
+                    let _ = result_tx.send(export_one(&input, &out_dir, &config));
+                }
+            })
+        })
+        .collect();
+    drop(result_tx);
+
+    let results: Vec<bool> = result_rx.iter().collect();
+    for w in workers {
+        let _ = w.join();
+    }
+
+    let finished = results.iter().filter(|&&ok| ok).count();
+    let failed = results.len() - finished;
+    Event::Done { finished, failed }.print();
+    if failed > 0 {
+        Err(anyhow!(
+            "failed to export {} of {} files",
+            failed,
+            results.len()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_paths() {
+        assert_eq!(
+            output_path(Path::new("talks/intro.scb"), Path::new("videos")),
+            Path::new("videos/intro.mp4")
+        );
+        assert_eq!(
+            output_path(Path::new("v1.2.scb"), Path::new("videos")),
+            Path::new("videos/v1.2.mp4")
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! A headless benchmark, for tracking down slow rendering without the UI getting in the way.
+//!
+//! It steps through an animation one frame at a time (like an export would), renders every frame,
+//! and reports how long the frames took.
+
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+use scribl_curves::Time;
+
+use crate::encode::FrameStats;
+use crate::ScriblState;
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// Describes the frame statistics, one per line.
+pub fn describe(stats: &FrameStats) -> String {
+    format!(
+        "frames: {}\n\
+         total:  {:.1}s\n\
+         min:    {:.2}ms\n\
+         mean:   {:.2}ms\n\
+         median: {:.2}ms\n\
+         p95:    {:.2}ms\n\
+         max:    {:.2}ms (frame {})\n\
+         slower than real time: {}",
+        stats.frames,
+        stats.total.as_secs_f64(),
+        ms(stats.min),
+        ms(stats.mean),
+        ms(stats.median),
+        ms(stats.p95),
+        ms(stats.max),
+        stats.slowest,
+        stats.slow,
+    )
+}
+
+/// Renders every frame of the animation at the given frame rate and height, and returns the
+/// statistics of how long they took.
+///
+/// `progress` gets called after each frame, with the number of frames done so far and the total.
+pub fn run(
+    scribl: &ScriblState,
+    height: u32,
+    fps: f64,
+    mut progress: impl FnMut(u32, u32),
+) -> Result<Option<FrameStats>> {
+    let frames = scribl.end_time().as_video_frame(fps) + 1;
+    let mut times = Vec::with_capacity(frames as usize);
+    for frame in 0..frames {
+        let start = Instant::now();
+        scribl_core::frame::render(scribl, Time::from_video_frame(frame, fps), height)?;
+        times.push(start.elapsed());
+        progress(frame + 1, frames);
+    }
+    Ok(FrameStats::new(&times, Duration::from_secs_f64(1.0 / fps)))
+}
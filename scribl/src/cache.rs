@@ -0,0 +1,179 @@
+//! The cache directory, where we keep files that scribl can do without.
+//!
+//! Each feature that keeps files here gets its own subdirectory (see [`CacheKind`]). The cache as
+//! a whole is kept below the maximum size from the config file by deleting the oldest files, and
+//! it can be emptied from the file menu.
+
+use anyhow::{anyhow, Result};
+use directories_next::ProjectDirs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::Cache;
+
+/// The things that we keep in the cache.
+///
+/// The timeline's waveforms aren't here: they're kept in memory, and they're quicker to compute
+/// from the audio than they would be to read back from disk. Scribl doesn't make thumbnails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheKind {
+    /// Autosaved animations (unless the config file puts them somewhere else).
+    Autosave,
+    /// Undo states that didn't fit in memory.
+    Undo,
+    /// Long audio recordings, which get memory-mapped instead of kept in memory. These files don't
+    /// have names (so they aren't counted or cleared), and they go away when scribl closes.
+    Audio,
+}
+
+impl CacheKind {
+    fn dir_name(self) -> &'static str {
+        match self {
+            CacheKind::Autosave => "autosave",
+            CacheKind::Undo => "undo",
+            CacheKind::Audio => "audio",
+        }
+    }
+}
+
+/// The directory containing all the cached files.
+pub fn cache_dir(config: &Cache) -> Option<PathBuf> {
+    if let Some(dir) = &config.dir {
+        Some(dir.clone())
+    } else {
+        ProjectDirs::from("ink", "scribl", "scribl").map(|d| d.cache_dir().to_owned())
+    }
+}
+
+/// The directory for one kind of cached file. It might not exist yet.
+pub fn subdir(config: &Cache, kind: CacheKind) -> Option<PathBuf> {
+    cache_dir(config).map(|d| d.join(kind.dir_name()))
+}
+
+struct CachedFile {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// Finds all the files in `dir` and its subdirectories.
+fn list_files(dir: &Path, out: &mut Vec<CachedFile>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let meta = match entry.metadata() {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        if meta.is_dir() {
+            list_files(&entry.path(), out);
+        } else {
+            out.push(CachedFile {
+                path: entry.path(),
+                size: meta.len(),
+                modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+    }
+}
+
+/// Given all the cached files, returns the ones that should be deleted (oldest first) to bring
+/// the total size down to `max_bytes`.
+fn excess_files(files: &mut [CachedFile], max_bytes: u64) -> &[CachedFile] {
+    files.sort_by_key(|f| f.modified);
+    let mut total: u64 = files.iter().map(|f| f.size).sum();
+    let mut count = 0;
+    for f in files.iter() {
+        if total <= max_bytes {
+            break;
+        }
+        total -= f.size;
+        count += 1;
+    }
+    &files[..count]
+}
+
+/// Deletes some files, returning the number of bytes freed.
+fn delete(files: &[CachedFile]) -> u64 {
+    let mut freed = 0;
+    for f in files {
+        match std::fs::remove_file(&f.path) {
+            Ok(()) => freed += f.size,
+            Err(e) => log::warn!("failed to delete {}: {}", f.path.display(), e),
+        }
+    }
+    freed
+}
+
+/// If the cache is bigger than the maximum size, deletes the oldest files. Returns the number of
+/// bytes freed.
+pub fn enforce_limit(config: &Cache) -> u64 {
+    let mut files = Vec::new();
+    if let Some(dir) = cache_dir(config) {
+        list_files(&dir, &mut files);
+    }
+    let max_bytes = config.max_size_mb.saturating_mul(1024 * 1024);
+    let freed = delete(excess_files(&mut files, max_bytes));
+    if freed > 0 {
+        log::info!("deleted {} bytes of old cached files", freed);
+    }
+    freed
+}
+
+/// Deletes all the cached files, returning the number of bytes freed.
+pub fn clear(config: &Cache) -> Result<u64> {
+    let dir = cache_dir(config).ok_or_else(|| anyhow!("couldn't find the cache directory"))?;
+    let mut files = Vec::new();
+    list_files(&dir, &mut files);
+    Ok(delete(&files))
+}
+
+/// A description of a number of bytes, like "3.1 MB".
+pub fn describe_size(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes < 1024 * 1024 {
+        format!("{:.0} kB", bytes as f64 / 1024.0)
+    } else if bytes < 1024 * 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / MB)
+    } else {
+        format!("{:.1} GB", bytes as f64 / (MB * 1024.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn file(name: &str, size: u64, secs: u64) -> CachedFile {
+        CachedFile {
+            path: PathBuf::from(name),
+            size,
+            modified: SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+        }
+    }
+
+    #[test]
+    fn excess() {
+        let mut files = vec![file("b", 10, 2), file("c", 10, 3), file("a", 10, 1)];
+        let names = |files: &[CachedFile]| {
+            files
+                .iter()
+                .map(|f| f.path.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        assert!(excess_files(&mut files, 30).is_empty());
+        assert_eq!(names(excess_files(&mut files, 25)), vec!["a"]);
+        assert_eq!(names(excess_files(&mut files, 10)), vec!["a", "b"]);
+        assert_eq!(names(excess_files(&mut files, 0)), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(describe_size(2048), "2 kB");
+        assert_eq!(describe_size(3 * 1024 * 1024 + 100 * 1024), "3.1 MB");
+        assert_eq!(describe_size(2 * 1024 * 1024 * 1024), "2.0 GB");
+    }
+}
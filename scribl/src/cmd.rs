@@ -1,11 +1,22 @@
 use druid::{FileInfo, Selector};
 use std::path::PathBuf;
+use std::sync::Arc;
 
-use scribl_curves::Time;
+use scribl_core::live::LiveTarget;
+use scribl_core::underlay::{Frame, Underlay};
+use scribl_curves::{LabelColor, Time, TimeDiff};
 
-use crate::audio::{AudioRecordingStatus, TalkSnippet};
+use crate::audio::{
+    AudioError, AudioRecordingStatus, Duplicate, MusicTrack, PunchIn, Spectrogram, TalkSnippet,
+    TalkSnippetId, TalkSnippets,
+};
+use crate::data::export_queue::ExportJobId;
+use crate::data::RowHints;
 use crate::encode::EncodingStatus;
-use crate::{SaveFileData, ScriblState};
+use crate::remote::RemoteRequest;
+use crate::{SaveFileData, SnippetId};
+
+pub use crate::encode::ExportCmd;
 
 /// Selects the snippet below (in the timeline) the currently selected snippet.
 pub const SELECT_SNIPPET_BELOW: Selector = Selector::new("scribl.select-snippet-below");
@@ -13,6 +24,56 @@ pub const SELECT_SNIPPET_BELOW: Selector = Selector::new("scribl.select-snippet-
 /// Selects the snippet above (in the timeline) the currently selected snippet.
 pub const SELECT_SNIPPET_ABOVE: Selector = Selector::new("scribl.select-snippet-above");
 
+/// Selects the snippet starting just before the currently selected snippet.
+pub const SELECT_SNIPPET_LEFT: Selector = Selector::new("scribl.select-snippet-left");
+
+/// Selects the snippet starting just after the currently selected snippet.
+pub const SELECT_SNIPPET_RIGHT: Selector = Selector::new("scribl.select-snippet-right");
+
+/// Remembers the rows that the snippets were laid out in. See [`RowHints`].
+pub const SET_ROW_HINTS: Selector<RowHints> = Selector::new("scribl.set-row-hints");
+
+/// Deletes a snippet.
+pub const DELETE_SNIPPET: Selector<SnippetId> = Selector::new("scribl.delete-snippet");
+
+/// Copies a snippet to the current time.
+pub const DUPLICATE_SNIPPET: Selector<SnippetId> = Selector::new("scribl.duplicate-snippet");
+
+/// Splits a snippet in two at the current time.
+pub const SPLIT_SNIPPET: Selector<SnippetId> = Selector::new("scribl.split-snippet");
+
+/// Mutes or unmutes an audio snippet.
+pub const TOGGLE_SNIPPET_MUTED: Selector<SnippetId> = Selector::new("scribl.toggle-snippet-muted");
+
+/// Switches an audio snippet between its raw and processed audio. Does nothing for snippets that
+/// didn't keep their raw audio, or for drawings.
+pub const TOGGLE_SNIPPET_RAW: Selector<SnippetId> = Selector::new("scribl.toggle-snippet-raw");
+
+/// Makes a drawing play backwards, or forwards if it was already playing backwards. Does nothing
+/// for audio snippets.
+pub const TOGGLE_SNIPPET_REVERSED: Selector<SnippetId> =
+    Selector::new("scribl.toggle-snippet-reversed");
+
+/// Changes (or removes) the label color of a snippet.
+pub const SET_SNIPPET_LABEL: Selector<(SnippetId, Option<LabelColor>)> =
+    Selector::new("scribl.set-snippet-label");
+
+/// Links a snippet with the snippets that overlap it in time.
+pub const LINK_SNIPPET: Selector<SnippetId> = Selector::new("scribl.link-snippet");
+
+/// Removes a snippet from its group of linked snippets.
+pub const UNLINK_SNIPPET: Selector<SnippetId> = Selector::new("scribl.unlink-snippet");
+
+/// Shows a dialog for renaming a snippet.
+pub const RENAME_SNIPPET: Selector<SnippetId> = Selector::new("scribl.rename-snippet");
+
+/// Shows a dialog with the details of a snippet.
+pub const SHOW_SNIPPET_PROPERTIES: Selector<SnippetId> =
+    Selector::new("scribl.show-snippet-properties");
+
+/// Shows a dialog for changing how fast a drawing gets drawn. Does nothing for audio snippets.
+pub const RETIME_SNIPPET: Selector<SnippetId> = Selector::new("scribl.retime-snippet");
+
 /// This command is sent by the audio thread each time it records a small chunk.
 pub const RECORDING_AUDIO_STATUS: Selector<AudioRecordingStatus> =
     Selector::new("scribl.recording-audio-status");
@@ -20,29 +81,239 @@ pub const RECORDING_AUDIO_STATUS: Selector<AudioRecordingStatus> =
 /// Adds a new audio snippet.
 pub const ADD_TALK_SNIPPET: Selector<TalkSnippetCmd> = Selector::new("scribl.add-talk-snippet");
 
+/// The audio thread sends this when it finishes re-recording part of a snippet.
+pub const PUNCH_IN_RECORDED: Selector<PunchInCmd> = Selector::new("scribl.punch-in-recorded");
+
+/// Starts measuring the round-trip latency of the audio system.
+pub const CALIBRATE_LATENCY: Selector = Selector::new("scribl.calibrate-latency");
+
+/// The audio thread sends this when it finishes measuring the audio latency. It contains either the
+/// measured latency or an error message.
+pub const LATENCY_CALIBRATED: Selector<Result<TimeDiff, String>> =
+    Selector::new("scribl.latency-calibrated");
+
+/// Tells the user that some gstreamer plugins are missing. The payload is a description of what's
+/// missing.
+pub const SHOW_MISSING_PLUGINS: Selector<String> = Selector::new("scribl.show-missing-plugins");
+
+/// Tells the user that some exports were interrupted, and offers to delete the files that they
+/// left behind. The payload is the list of files.
+pub const SHOW_PARTIAL_EXPORTS: Selector<Vec<PathBuf>> =
+    Selector::new("scribl.show-partial-exports");
+
+/// Tells the user that scribl crashed last time, and offers to open the files that were saved
+/// while crashing. The payload is the list of files.
+pub const SHOW_CRASH_RECOVERY: Selector<Vec<PathBuf>> = Selector::new("scribl.show-crash-recovery");
+
+/// Sent by the audio thread when something broke the audio (like a missing gstreamer plugin, or
+/// a device that couldn't be opened). The user gets shown the details.
+pub const AUDIO_ERRORS: Selector<Vec<AudioError>> = Selector::new("scribl.audio-errors");
+
+/// Sent by the audio thread when something happened to the audio devices (like a device being
+/// unplugged). The payload is a description of what happened.
+pub const AUDIO_DEVICES_CHANGED: Selector<String> = Selector::new("scribl.audio-devices-changed");
+
+/// Imports an audio file as background music.
+pub const IMPORT_MUSIC: Selector<FileInfo> = Selector::new("scribl.import-music");
+
+/// Music files are decoded asynchronously. When decoding is done, one of these commands gets sent,
+/// containing either the decoded music or an error message.
+pub const FINISHED_MUSIC_IMPORT: Selector<Result<MusicTrack, String>> =
+    Selector::new("scribl.finished-music-import");
+
+/// Imports an audio file as a new audio snippet, starting at the current time.
+pub const IMPORT_AUDIO: Selector<FileInfo> = Selector::new("scribl.import-audio");
+
+/// Audio files are decoded asynchronously. When decoding is done, one of these commands gets sent,
+/// containing either the new snippet or an error message.
+pub const FINISHED_AUDIO_IMPORT: Selector<Result<TalkSnippet, String>> =
+    Selector::new("scribl.finished-audio-import");
+
+/// Screen recordings are finished asynchronously. When the video file has been written, one of
+/// these commands gets sent, containing either the new underlay or an error message.
+pub const FINISHED_SCREEN_CAPTURE: Selector<Result<Underlay, String>> =
+    Selector::new("scribl.finished-screen-capture");
+
+/// Like `FINISHED_SCREEN_CAPTURE`, but for webcam recordings.
+pub const FINISHED_WEBCAM_CAPTURE: Selector<Result<Underlay, String>> =
+    Selector::new("scribl.finished-webcam-capture");
+
+/// Sent to the drawing pane when the underlay frame that it should show changes.
+pub const UNDERLAY_FRAME: Selector<Option<Frame>> = Selector::new("scribl.underlay-frame");
+
+/// Sent to the drawing pane when the webcam frame that it should show changes.
+pub const WEBCAM_FRAME: Selector<Option<Frame>> = Selector::new("scribl.webcam-frame");
+
+/// Starts sending the canvas to a virtual camera or an RTMP server.
+pub const START_LIVE_OUTPUT: Selector<LiveTarget> = Selector::new("scribl.start-live-output");
+
+/// Stops sending the canvas to the live output.
+pub const STOP_LIVE_OUTPUT: Selector = Selector::new("scribl.stop-live-output");
+
+/// Sent (from another thread) when the live output fails. The argument is the error message.
+pub const LIVE_OUTPUT_FAILED: Selector<String> = Selector::new("scribl.live-output-failed");
+
+/// A request from the remote-control server. These get sent to the app delegate first, which
+/// passes them on to one of the editor windows.
+pub const REMOTE_REQUEST: Selector<RemoteRequest> = Selector::new("scribl.remote-request");
+
+/// Turns the system-wide hotkeys on or off. This should be sent to the app delegate.
+pub const SET_GLOBAL_HOTKEYS: Selector<bool> = Selector::new("scribl.set-global-hotkeys");
+
+/// Reads the paths in an SVG file, and asks how they should be imported as a drawing.
+pub const IMPORT_SVG: Selector<FileInfo> = Selector::new("scribl.import-svg");
+
 /// Changes the current animation time, assuming that the UI is in the idle state.
 pub const WARP_TIME_TO: Selector<Time> = Selector::new("scribl.warp-time-to");
 
-/// Exports the current animation as a video.
+/// Shows a dialog for jumping to a specific time.
+pub const SHOW_GO_TO_TIME: Selector = Selector::new("scribl.show-go-to-time");
+
+/// Shows the script console, for editing and running Rhai scripts.
+pub const SHOW_SCRIPT_CONSOLE: Selector = Selector::new("scribl.show-script-console");
+
+/// Runs the script in the script console.
+pub const RUN_SCRIPT: Selector = Selector::new("scribl.run-script");
+
+/// Adds a marker at the current time, and shows a dialog for naming it.
+pub const ADD_MARKER: Selector = Selector::new("scribl.add-marker");
+
+/// Shows a dialog for renaming the marker at the current time.
+pub const RENAME_MARKER: Selector = Selector::new("scribl.rename-marker");
+
+/// Shows the panel for creating, previewing, and restoring checkpoints.
+pub const SHOW_CHECKPOINTS: Selector = Selector::new("scribl.show-checkpoints");
+
+/// Shows the drawing from the checkpoint with this index (or stops showing it, if `None`).
+pub const PREVIEW_CHECKPOINT: Selector<Option<usize>> = Selector::new("scribl.preview-checkpoint");
+
+/// Replaces the current animation with the checkpoint that has this index.
+pub const RESTORE_CHECKPOINT: Selector<usize> = Selector::new("scribl.restore-checkpoint");
+
+/// Deletes the checkpoint with this index.
+pub const DELETE_CHECKPOINT: Selector<usize> = Selector::new("scribl.delete-checkpoint");
+
+/// Moves the current time a little bit, assuming that the UI is in the idle state.
+pub const NUDGE_TIME: Selector<Nudge> = Selector::new("scribl.nudge-time");
+
+/// Moves the selected snippet earlier or later.
+pub const NUDGE_SNIPPET: Selector<Nudge> = Selector::new("scribl.nudge-snippet");
+
+/// Exports the current animation as a video. If something else is already being exported, this
+/// export gets queued.
 pub const EXPORT: Selector<FileInfo> = Selector::new("scribl.export");
 
-/// While the video is encoding asynchronously, it periodically sends these commands.
-pub const ENCODING_STATUS: Selector<EncodingStatus> = Selector::new("scribl.encoding-status");
+/// Exports the range between the mark and the current time as a video.
+pub const EXPORT_RANGE: Selector<FileInfo> = Selector::new("scribl.export-range");
+
+/// Exports the drawings of the current animation as an animated SVG or a Lottie animation,
+/// depending on the file's extension.
+pub const EXPORT_WEB: Selector<FileInfo> = Selector::new("scribl.export-web");
+
+/// Draws the timeline into a PNG file.
+pub const EXPORT_TIMELINE_IMAGE: Selector<FileInfo> = Selector::new("scribl.export-timeline-image");
+
+/// Writes the structure of the timeline (snippets and markers) to an OpenTimelineIO file.
+pub const EXPORT_OTIO: Selector<FileInfo> = Selector::new("scribl.export-otio");
+
+/// Sent when a web export finishes, containing either the exported file or an error message.
+pub const FINISHED_WEB_EXPORT: Selector<Result<PathBuf, String>> =
+    Selector::new("scribl.finished-web-export");
+
+/// Shows the panel listing the queued, running, and finished exports.
+pub const SHOW_EXPORT_QUEUE: Selector = Selector::new("scribl.show-export-queue");
+
+/// Removes an export from the queue, if it hasn't started yet.
+pub const CANCEL_EXPORT: Selector<ExportJobId> = Selector::new("scribl.cancel-export");
+
+/// While a video is encoding asynchronously, it periodically sends these commands.
+pub const ENCODING_STATUS: Selector<EncodingStatusCmd> = Selector::new("scribl.encoding-status");
+
+/// Shows the list of autosaved files.
+pub const SHOW_AUTOSAVES: Selector = Selector::new("scribl.show-autosaves");
+
+/// Opens an autosaved (or crash-recovery) file. Unlike opening a normal file, this doesn't change the path that the
+/// file will be saved to.
+pub const OPEN_AUTOSAVE: Selector<PathBuf> = Selector::new("scribl.open-autosave");
+
+/// Shows the list of templates that a new animation can be started from.
+pub const SHOW_TEMPLATES: Selector = Selector::new("scribl.show-templates");
+
+/// Opens a new window with a new animation, started from the template at this path.
+pub const NEW_FROM_TEMPLATE: Selector<PathBuf> = Selector::new("scribl.new-from-template");
+
+/// Shows a dialog asking for the name of a new template.
+pub const SHOW_SAVE_TEMPLATE: Selector = Selector::new("scribl.show-save-template");
+
+/// Saves the current animation (and palette) as a template with this name.
+pub const SAVE_TEMPLATE: Selector<String> = Selector::new("scribl.save-template");
 
 /// Reading and parsing of save-files is done asynchronously. When a file is done being read and
 /// parsed, one of these commands gets sent.
 pub const FINISHED_ASYNC_LOAD: Selector<AsyncLoadResult> =
     Selector::new("scribl.finished-async-load");
 
+/// While a file is being loaded, this gets sent periodically with the fraction of the file that
+/// has been read so far.
+pub const LOAD_PROGRESS: Selector<f64> = Selector::new("scribl.load-progress");
+
 /// Writing save-files is done asynchronously. When a file is done being written one of these
 /// commands gets sent.
 pub const FINISHED_ASYNC_SAVE: Selector<AsyncSaveResult> =
     Selector::new("scribl.finished-async-save");
 
+/// Measures the integrated loudness of the whole animation, in the background. If this contains
+/// a target loudness (in LUFS), the audio snippets get made louder or quieter to match it once
+/// the measurement is done.
+pub const MEASURE_LOUDNESS: Selector<Option<f64>> = Selector::new("scribl.measure-loudness");
+
+/// Sent when a loudness measurement finishes.
+pub const FINISHED_LOUDNESS_MEASUREMENT: Selector<LoudnessMeasurement> =
+    Selector::new("scribl.finished-loudness-measurement");
+
+/// Looks for audio snippets that were accidentally recorded twice, in the background.
+pub const FIND_DUPLICATE_AUDIO: Selector = Selector::new("scribl.find-duplicate-audio");
+
+/// Sent when the search for duplicate audio finishes.
+pub const FINISHED_FIND_DUPLICATE_AUDIO: Selector<DuplicateAudio> =
+    Selector::new("scribl.finished-find-duplicate-audio");
+
+/// Works out where the speech is in an audio snippet (in the background), and then opens a dialog
+/// for previewing how much of it gets cut out by speech detection.
+pub const DETECT_SPEECH: Selector<TalkSnippetId> = Selector::new("scribl.detect-speech");
+
+/// Sent when the speech detection for an audio snippet finishes.
+pub const FINISHED_DETECT_SPEECH: Selector<SpeechDetection> =
+    Selector::new("scribl.finished-detect-speech");
+
+/// Removes the noise from an audio snippet (and cuts out the parts without speech, if voice
+/// detection is on), in the background. This works on the snippet's raw recording if it kept
+/// one, so the processing can be redone with different settings.
+pub const PROCESS_AUDIO: Selector<TalkSnippetId> = Selector::new("scribl.process-audio");
+
+/// While an audio snippet is being processed, this gets sent periodically with the fraction of
+/// the snippet that has been processed so far.
+pub const PROCESS_AUDIO_PROGRESS: Selector<f64> = Selector::new("scribl.process-audio-progress");
+
+/// Sent when the processing of an audio snippet finishes.
+pub const FINISHED_PROCESS_AUDIO: Selector<ProcessedAudio> =
+    Selector::new("scribl.finished-process-audio");
+
+/// Deletes all the files in the cache directory, in the background.
+pub const CLEAR_CACHE: Selector = Selector::new("scribl.clear-cache");
+
+/// Sent when the cache has been cleared, with the number of bytes freed.
+pub const FINISHED_CLEAR_CACHE: Selector<Result<u64, String>> =
+    Selector::new("scribl.finished-clear-cache");
+
+/// Sent to the timeline when the spectrogram of an audio snippet has been computed.
+pub const SPECTROGRAM_READY: Selector<SpectrogramReady> = Selector::new("scribl.spectrogram-ready");
+
 #[derive(Clone)]
 pub struct AsyncLoadResult {
     pub path: PathBuf,
     pub save_data: Result<SaveFileData, String>,
+    pub autosave: bool,
 }
 
 #[derive(Clone)]
@@ -53,15 +324,75 @@ pub struct AsyncSaveResult {
     pub autosave: bool,
 }
 
-#[derive(Clone)]
-pub struct ExportCmd {
-    pub scribl: ScriblState,
-    pub filename: PathBuf,
-    pub config: crate::config::Export,
+/// How far to move the current time or a snippet, for the `NUDGE_TIME` and `NUDGE_SNIPPET`
+/// commands.
+#[derive(Clone, Copy, Debug)]
+pub enum Nudge {
+    /// Move by this many video frames (at the export frame rate).
+    Frames(i64),
+    /// Move by this many seconds.
+    Seconds(i64),
+}
+
+pub struct SpectrogramReady {
+    pub id: TalkSnippetId,
+    /// Identifies the audio that the spectrogram was computed from. If the snippet's audio has
+    /// changed since then, the spectrogram is stale.
+    pub key: (usize, i64),
+    pub spectrogram: Arc<Spectrogram>,
+}
+
+pub struct LoudnessMeasurement {
+    /// The audio snippets that were measured. If they've changed since then, the measurement is
+    /// stale.
+    pub talk: TalkSnippets,
+    /// The integrated loudness, in LUFS.
+    pub loudness: Result<f64, String>,
+    /// The loudness that was asked for, if the audio should be normalized.
+    pub target: Option<f64>,
+}
+
+pub struct DuplicateAudio {
+    /// The audio snippets that were searched. If they've changed since then, the results are
+    /// stale.
+    pub talk: TalkSnippets,
+    pub duplicates: Vec<Duplicate>,
+}
+
+pub struct SpeechDetection {
+    /// The audio snippets at the time of the detection. If they've changed since then, the
+    /// detection is stale.
+    pub talk: TalkSnippets,
+    pub id: TalkSnippetId,
+    /// The probability of speech in each frame of the snippet.
+    pub vad: Vec<f32>,
+}
+
+pub struct ProcessedAudio {
+    /// The audio snippets at the time of the processing. If they've changed since then, the
+    /// result is stale.
+    pub talk: TalkSnippets,
+    pub id: TalkSnippetId,
+    /// The processed audio, to replace the snippet's audio.
+    pub buf: Vec<i16>,
+}
+
+pub struct EncodingStatusCmd {
+    /// The export job that this status is about.
+    pub job: ExportJobId,
+    pub status: EncodingStatus,
+}
+
+pub struct PunchInCmd {
+    pub punch: PunchIn,
+    /// The newly recorded audio, already aligned in time with the snippet that it will replace.
+    pub take: TalkSnippet,
 }
 
 pub struct TalkSnippetCmd {
-    pub snip: TalkSnippet,
-    /// The start time of the talk snippet *before* it got trimmed.
+    /// The new snippets. A single recording can produce more than one snippet, if it was split at
+    /// silences.
+    pub snips: Vec<TalkSnippet>,
+    /// The start time of the recording *before* it got trimmed.
     pub orig_start: Time,
 }
@@ -1,48 +1,799 @@
 use anyhow::{anyhow, Context, Result};
 use directories_next::ProjectDirs;
+use druid::{Color, Data, Env};
 use serde::Deserialize;
+use std::path::PathBuf;
+use std::time::Duration;
 
-fn default_video_height() -> u32 {
-    1080
+pub use scribl_core::encode::{Export, VideoCodec};
+use scribl_core::live::LiveConfig;
+use scribl_core::underlay::{CaptureRegion, Corner};
+use scribl_curves::{Brush, Time, TimeDiff};
+
+use crate::data::PenSize;
+
+fn default_remove_noise() -> bool {
+    true
 }
 
-fn default_video_fps() -> f64 {
-    30.0
+fn default_vad_threshold() -> f32 {
+    0.3
 }
 
-fn default_video_bitrate() -> u32 {
-    4096
+fn default_fade_ms() -> u32 {
+    30
 }
 
-fn default_remove_noise() -> bool {
+fn default_trim_start_ms() -> u32 {
+    0
+}
+
+fn default_latency_ms() -> i32 {
+    0
+}
+
+fn default_monitor() -> bool {
+    false
+}
+
+fn default_split_silence_ms() -> u32 {
+    2000
+}
+
+fn default_stroke_group_ms() -> u32 {
+    500
+}
+
+fn default_undo_memory_mb() -> usize {
+    512
+}
+
+fn default_undo_spill() -> bool {
     true
 }
 
-fn default_vad_threshold() -> f32 {
-    0.3
+fn default_autosave_interval_secs() -> u64 {
+    60
+}
+
+fn default_autosave_max_count() -> usize {
+    20
+}
+
+fn default_cache_max_size_mb() -> u64 {
+    1024
+}
+
+fn default_capture_fps() -> f64 {
+    15.0
+}
+
+fn default_webcam_fps() -> f64 {
+    30.0
+}
+
+fn default_webcam_size() -> f64 {
+    0.25
+}
+
+fn default_live_height() -> u32 {
+    720
+}
+
+fn default_live_fps() -> f64 {
+    30.0
+}
+
+fn default_live_bitrate() -> u32 {
+    2500
+}
+
+fn default_remote_port() -> u16 {
+    7373
+}
+
+fn default_talk_hotkey() -> String {
+    "Ctrl+Shift+F9".to_owned()
+}
+
+fn default_stop_hotkey() -> String {
+    "Ctrl+Shift+F10".to_owned()
+}
+
+fn default_silence_threshold_db() -> f64 {
+    -40.0
+}
+
+fn default_silence_padding_ms() -> u32 {
+    150
+}
+
+fn default_max_pause_ms() -> u32 {
+    1000
+}
+
+fn default_pause_factor() -> f64 {
+    0.5
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
 pub struct Config {
     pub audio_input: AudioInput,
     pub export: Export,
+
+    #[serde(default)]
+    pub drawing: Drawing,
+
+    #[serde(default)]
+    pub undo: Undo,
+
+    #[serde(default)]
+    pub timeline: Timeline,
+
+    #[serde(default)]
+    pub autosave: Autosave,
+
+    #[serde(default)]
+    pub screen_capture: ScreenCapture,
+
+    #[serde(default)]
+    pub webcam: Webcam,
+
+    #[serde(default)]
+    pub live: Live,
+
+    #[serde(default)]
+    pub remote: Remote,
+
+    #[serde(default)]
+    pub midi: Midi,
+
+    #[serde(default)]
+    pub global_hotkeys: GlobalHotkeys,
+
+    #[serde(default)]
+    pub silence: Silence,
+
+    #[serde(default)]
+    pub templates: Templates,
+
+    #[serde(default)]
+    pub cache: Cache,
+
+    /// The colors that the UI uses to show things like selected buttons. It can also be changed
+    /// in the view menu.
+    #[serde(default)]
+    pub ui_palette: UiPalette,
+
+    /// Which audio backend to use for recording and playback.
+    #[serde(default)]
+    pub audio_backend: crate::audio::AudioBackendKind,
+
+    /// Extra export presets, in addition to the built-in ones. A preset with the same name as a
+    /// built-in one replaces it.
+    #[serde(default)]
+    pub export_presets: Vec<ExportPreset>,
+
+    /// Pens that can be picked all at once, from the pen toolbar or with Alt and a number key.
+    #[serde(default)]
+    pub pen_presets: Vec<PenPreset>,
+}
+
+/// A named collection of export settings, like "youtube".
+#[derive(Clone, Debug, Deserialize)]
+pub struct ExportPreset {
+    pub name: String,
+    #[serde(flatten)]
+    pub export: Export,
+}
+
+impl ExportPreset {
+    fn new(name: &str, height: u32, fps: f64, bitrate: u32, loudness_lufs: f64) -> ExportPreset {
+        ExportPreset {
+            name: name.to_owned(),
+            export: Export {
+                height,
+                fps,
+                bitrate,
+                codec: VideoCodec::H264,
+                loudness_lufs: Some(loudness_lufs),
+                ..Export::default()
+            },
+        }
+    }
+}
+
+/// A named combination of pen settings, like "thick red marker".
+///
+/// Everything except the name is optional: choosing a preset only changes the settings that it
+/// mentions. So a preset can be a whole pen, or just (for example) a size and a brush that work
+/// with whatever color is selected.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PenPreset {
+    pub name: String,
+
+    /// The color, as a hex string like "#bf5700".
+    #[serde(default)]
+    pub color: Option<String>,
+
+    #[serde(default)]
+    pub size: Option<PenSize>,
+
+    #[serde(default)]
+    pub brush: Option<Brush>,
+
+    #[serde(default)]
+    pub fade: Option<bool>,
+
+    /// The names of the pen effects (like "wiggle") to turn on. The other effects get turned off.
+    #[serde(default)]
+    pub effects: Option<Vec<String>>,
+}
+
+fn builtin_export_presets() -> Vec<ExportPreset> {
+    vec![
+        ExportPreset::new("youtube", 1080, 30.0, 8000, -14.0),
+        ExportPreset::new("twitter", 720, 30.0, 5000, -16.0),
+        ExportPreset::new("low-bandwidth", 480, 15.0, 500, -16.0),
+    ]
+}
+
+impl Config {
+    /// All the available export presets: the built-in ones, followed by the ones from the config
+    /// file.
+    pub fn export_presets(&self) -> Vec<ExportPreset> {
+        let mut ret = builtin_export_presets();
+        ret.retain(|p| self.export_presets.iter().all(|q| q.name != p.name));
+        ret.extend(self.export_presets.iter().cloned());
+        ret
+    }
+
+    /// Finds the export preset with this name, ignoring case.
+    pub fn export_preset(&self, name: &str) -> Option<ExportPreset> {
+        self.export_presets()
+            .into_iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The export settings for the preset with this name, or the settings from the config file if
+    /// there's no preset.
+    pub fn export_settings(&self, preset: Option<&str>) -> Result<Export> {
+        let name = match preset {
+            Some(name) => name,
+            None => return Ok(self.export.clone()),
+        };
+        match self.export_preset(name) {
+            Some(p) => Ok(p.export),
+            None => {
+                let names: Vec<_> = self.export_presets().into_iter().map(|p| p.name).collect();
+                Err(anyhow!(
+                    "unknown export preset \"{}\". The available presets are: {}",
+                    name,
+                    names.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+/// How much drawing gets undone at once, while drawing.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UndoGranularity {
+    /// Every stroke can be undone separately.
+    Stroke,
+    /// Strokes that are drawn in quick succession get undone together.
+    PenDownGroup,
+    /// Undoing while drawing undoes the whole snippet.
+    Snippet,
+}
+
+impl Default for UndoGranularity {
+    fn default() -> UndoGranularity {
+        UndoGranularity::Stroke
+    }
+}
+
+impl UndoGranularity {
+    /// Should a stroke that starts at `start` get undone together with the strokes before it?
+    ///
+    /// `prev_end` is the time that the previous stroke of the same snippet ended (or `None` if
+    /// this is the first one), and strokes that start less than `gap` after that are in the same
+    /// pen-down group.
+    pub fn merges_stroke(self, prev_end: Option<Time>, start: Time, gap: TimeDiff) -> bool {
+        match self {
+            UndoGranularity::Stroke => false,
+            UndoGranularity::PenDownGroup => prev_end.map_or(false, |end| start < end + gap),
+            UndoGranularity::Snippet => true,
+        }
+    }
+}
+
+/// Where recorded audio comes from.
+#[derive(Clone, Copy, Data, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioSource {
+    /// The default input device (usually a microphone).
+    Microphone,
+    /// Whatever is playing through the default output device (for example, a video playing in a
+    /// browser).
+    System,
+}
+
+impl Default for AudioSource {
+    fn default() -> AudioSource {
+        AudioSource::Microphone
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Drawing {
+    #[serde(default)]
+    pub undo_granularity: UndoGranularity,
+
+    /// With the "pen_down_group" undo granularity, a stroke that starts less than this many
+    /// milliseconds (of animation time) after the previous one ended gets undone along with it.
+    #[serde(default = "default_stroke_group_ms")]
+    pub stroke_group_ms: u32,
+
+    /// Should we try to ignore touches from a palm resting on a touchscreen? This holds back the
+    /// beginning of each stroke until we're sure that it came from a pen, so it's off by default.
+    #[serde(default)]
+    pub palm_rejection: bool,
+
+    /// Should scrolling and zooming the drawing be turned off while recording? This can be
+    /// changed from the "View" menu; this is just the initial value.
+    #[serde(default)]
+    pub lock_viewport: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Autosave {
+    /// How often (in seconds) to autosave. Zero turns off autosaving.
+    #[serde(default = "default_autosave_interval_secs")]
+    pub interval_secs: u64,
+
+    /// How many autosaved files to keep. When there are more than this, the oldest ones get
+    /// deleted.
+    #[serde(default = "default_autosave_max_count")]
+    pub max_count: usize,
+
+    /// Where to put the autosaved files. By default, they go in the cache directory.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+}
+
+impl Autosave {
+    /// How often to autosave, or `None` if we shouldn't.
+    pub fn interval(&self) -> Option<Duration> {
+        if self.interval_secs == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(self.interval_secs))
+        }
+    }
+}
+
+impl Default for Autosave {
+    fn default() -> Autosave {
+        Autosave {
+            interval_secs: default_autosave_interval_secs(),
+            max_count: default_autosave_max_count(),
+            dir: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Templates {
+    /// Where to keep the templates that new animations can be started from. By default, they go
+    /// in a "templates" directory inside scribl's data directory.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+}
+
+impl Templates {
+    /// The directory where templates are kept.
+    pub fn dir(&self) -> Option<PathBuf> {
+        if let Some(dir) = &self.dir {
+            Some(dir.clone())
+        } else {
+            ProjectDirs::from("ink", "scribl", "scribl")
+                .map(|d| d.data_local_dir().join("templates"))
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Cache {
+    /// Where to keep files that scribl can do without, like autosaves and undo states that don't
+    /// fit in memory. By default, this is scribl's directory in the system's cache directory.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+
+    /// When the cache gets bigger than this (in megabytes), the oldest files get deleted.
+    #[serde(default = "default_cache_max_size_mb")]
+    pub max_size_mb: u64,
+}
+
+impl Default for Cache {
+    fn default() -> Cache {
+        Cache {
+            dir: None,
+            max_size_mb: default_cache_max_size_mb(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
-pub struct Export {
-    /// Height of the exported video, in pixels.
-    #[serde(default = "default_video_height")]
+pub struct ScreenCapture {
+    /// The part of the screen to record while talking. By default, we record the whole screen.
+    /// This is only supported on X11.
+    #[serde(default)]
+    pub region: Option<CaptureRegion>,
+
+    /// How many frames per second to record.
+    #[serde(default = "default_capture_fps")]
+    pub fps: f64,
+}
+
+impl Default for ScreenCapture {
+    fn default() -> ScreenCapture {
+        ScreenCapture {
+            region: None,
+            fps: default_capture_fps(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Webcam {
+    /// Which webcam to record from (for example, "/dev/video1"). This is only supported on
+    /// linux; by default, we use the first webcam.
+    #[serde(default)]
+    pub device: Option<String>,
+
+    /// How many frames per second to record.
+    #[serde(default = "default_webcam_fps")]
+    pub fps: f64,
+
+    /// Which corner of the drawing the webcam video goes in, when it's first recorded. It can be
+    /// moved later from the edit menu.
+    #[serde(default)]
+    pub corner: Corner,
+
+    /// The initial width of the webcam video, as a fraction of the width of the drawing.
+    #[serde(default = "default_webcam_size")]
+    pub size: f64,
+}
+
+impl Default for Webcam {
+    fn default() -> Webcam {
+        Webcam {
+            device: None,
+            fps: default_webcam_fps(),
+            corner: Corner::default(),
+            size: default_webcam_size(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Live {
+    /// The virtual camera to send the drawing to (for example, a v4l2loopback device like
+    /// "/dev/video10").
+    #[serde(default)]
+    pub virtual_camera: Option<String>,
+
+    /// The RTMP server to stream the drawing to (for example, "rtmp://localhost/live/scribl").
+    #[serde(default)]
+    pub rtmp_url: Option<String>,
+
+    /// The height of the live video, in pixels. The width is determined by the aspect ratio.
+    #[serde(default = "default_live_height")]
     pub height: u32,
 
-    /// Frames per second in the exported video.
-    #[serde(default = "default_video_fps")]
+    #[serde(default = "default_live_fps")]
     pub fps: f64,
 
-    /// Bitrate of the exported video.
-    #[serde(default = "default_video_bitrate")]
+    /// The bitrate of the RTMP stream, in kbit/s.
+    #[serde(default = "default_live_bitrate")]
     pub bitrate: u32,
 }
 
+impl Default for Live {
+    fn default() -> Live {
+        Live {
+            virtual_camera: None,
+            rtmp_url: None,
+            height: default_live_height(),
+            fps: default_live_fps(),
+            bitrate: default_live_bitrate(),
+        }
+    }
+}
+
+impl Live {
+    pub fn live_config(&self) -> LiveConfig {
+        LiveConfig {
+            height: self.height,
+            fps: self.fps,
+            bitrate: self.bitrate,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Remote {
+    /// Whether to listen for remote-control requests. This is off by default.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// The port to listen on. We only listen on localhost.
+    #[serde(default = "default_remote_port")]
+    pub port: u16,
+
+    /// Every connection needs to start by sending this token. The server doesn't start if it's
+    /// empty, because then any program (or web page) could control scribl.
+    #[serde(default)]
+    pub token: String,
+
+    /// Remote requests can only export into this directory. By default, it's the videos
+    /// directory.
+    #[serde(default)]
+    pub export_dir: Option<PathBuf>,
+}
+
+impl Default for Remote {
+    fn default() -> Remote {
+        Remote {
+            enabled: false,
+            port: default_remote_port(),
+            token: String::new(),
+            export_dir: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Midi {
+    /// Whether to listen to a MIDI controller (like a foot pedal). This is off by default.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// We listen to the first MIDI device whose name contains this. If it's empty, we listen to
+    /// the first MIDI device that we find.
+    #[serde(default)]
+    pub device: String,
+
+    /// What the buttons on the controller do.
+    #[serde(default)]
+    pub bindings: Vec<MidiBinding>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GlobalHotkeys {
+    /// Whether the system-wide hotkeys start out turned on. They can also be turned on and off
+    /// from the edit menu.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// The hotkey for starting to record audio.
+    #[serde(default = "default_talk_hotkey")]
+    pub talk: String,
+
+    /// The hotkey for stopping whatever is going on.
+    #[serde(default = "default_stop_hotkey")]
+    pub stop: String,
+}
+
+impl Default for GlobalHotkeys {
+    fn default() -> GlobalHotkeys {
+        GlobalHotkeys {
+            enabled: false,
+            talk: default_talk_hotkey(),
+            stop: default_stop_hotkey(),
+        }
+    }
+}
+
+/// A button on a MIDI controller, and what it does.
+///
+/// The button is either a note or a controller. A controller counts as being pressed when its
+/// value goes to 64 or more, which is what sustain pedals (on controller 64) do.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct MidiBinding {
+    #[serde(default)]
+    pub note: Option<u8>,
+
+    #[serde(default)]
+    pub controller: Option<u8>,
+
+    /// The MIDI channel (from 1 to 16) of the button. If this is missing, any channel works.
+    #[serde(default)]
+    pub channel: Option<u8>,
+
+    pub action: MidiAction,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MidiAction {
+    Play,
+    /// Stops playing (but not recording).
+    Pause,
+    /// Stops whatever is going on (playing or recording).
+    Stop,
+    /// Starts recording a drawing.
+    Draw,
+    /// Starts recording audio.
+    Talk,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Silence {
+    /// Audio that's quieter than this (in dB, relative to the loudest possible sound) counts as
+    /// silence when trimming and tightening audio snippets.
+    #[serde(default = "default_silence_threshold_db")]
+    pub threshold_db: f64,
+
+    /// When trimming silence from the ends of a snippet, we leave this many milliseconds of it.
+    #[serde(default = "default_silence_padding_ms")]
+    pub padding_ms: u32,
+
+    /// When tightening the pauses in a snippet, only pauses that are longer than this many
+    /// milliseconds get shortened.
+    #[serde(default = "default_max_pause_ms")]
+    pub max_pause_ms: u32,
+
+    /// When tightening the pauses in a snippet, they get shortened by this factor (but not to
+    /// less than `max_pause_ms`).
+    #[serde(default = "default_pause_factor")]
+    pub pause_factor: f64,
+}
+
+impl Default for Silence {
+    fn default() -> Silence {
+        Silence {
+            threshold_db: default_silence_threshold_db(),
+            padding_ms: default_silence_padding_ms(),
+            max_pause_ms: default_max_pause_ms(),
+            pause_factor: default_pause_factor(),
+        }
+    }
+}
+
+impl Silence {
+    /// The silence threshold, as a fraction of full scale.
+    pub fn threshold(&self) -> f32 {
+        10.0f64.powf(self.threshold_db / 20.0) as f32
+    }
+
+    pub fn padding(&self) -> TimeDiff {
+        TimeDiff::from_micros(self.padding_ms as i64 * 1000)
+    }
+
+    pub fn max_pause(&self) -> TimeDiff {
+        TimeDiff::from_micros(self.max_pause_ms as i64 * 1000)
+    }
+}
+
+/// How the timeline scrolls to keep up with the current time.
+#[derive(Clone, Copy, Data, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrollMode {
+    /// Scroll continuously, keeping the cursor a little way from the edge.
+    Follow,
+    /// When the cursor reaches the edge, scroll by a whole page.
+    Page,
+    /// Never scroll automatically.
+    Off,
+}
+
+impl Default for ScrollMode {
+    fn default() -> ScrollMode {
+        ScrollMode::Follow
+    }
+}
+
+/// How the snippets in the timeline are arranged.
+#[derive(Clone, Copy, Data, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RowLayout {
+    /// Keep the timeline as short as possible, even if that means moving snippets around.
+    Compact,
+    /// Keep snippets in the same rows, only moving them if they no longer fit.
+    Stable,
+}
+
+impl Default for RowLayout {
+    fn default() -> RowLayout {
+        RowLayout::Compact
+    }
+}
+
+/// The accent colors of the UI. Apart from the standard palette, there are palettes that avoid
+/// relying on the difference between red and green.
+#[derive(Clone, Copy, Data, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UiPalette {
+    Standard,
+    /// For people who have trouble seeing green.
+    Deuteranopia,
+    /// For people who have trouble seeing red.
+    Protanopia,
+}
+
+impl Default for UiPalette {
+    fn default() -> UiPalette {
+        UiPalette::Standard
+    }
+}
+
+impl UiPalette {
+    /// The color of buttons that are switched on.
+    pub fn selected_color(self) -> Color {
+        match self {
+            UiPalette::Standard => crate::UI_DARK_GREEN,
+            // The "blue" and "sky blue" from the Okabe-Ito palette.
+            UiPalette::Deuteranopia | UiPalette::Protanopia => Color::rgb8(0, 114, 178),
+        }
+    }
+
+    /// The outline color of buttons that the mouse is over.
+    pub fn hot_color(self) -> Color {
+        match self {
+            UiPalette::Standard => crate::UI_DARK_GREEN,
+            UiPalette::Deuteranopia | UiPalette::Protanopia => Color::rgb8(86, 180, 233),
+        }
+    }
+
+    /// The color for showing problems, like overlapping audio.
+    pub fn warning_color(self) -> Color {
+        match self {
+            UiPalette::Standard => Color::rgb8(0xff, 0x40, 0x40),
+            // Orange is still bright without red-sensitive cones, but red isn't.
+            UiPalette::Deuteranopia => Color::rgb8(230, 159, 0),
+            UiPalette::Protanopia => Color::rgb8(240, 228, 66),
+        }
+    }
+
+    pub fn configure_env(self, env: &mut Env) {
+        env.set(
+            scribl_widget::BUTTON_ICON_SELECTED_COLOR,
+            self.selected_color(),
+        );
+        env.set(
+            scribl_widget::BUTTON_ICON_HOT_STROKE_COLOR,
+            self.hot_color(),
+        );
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Timeline {
+    /// The initial scroll mode; it can also be changed in the view menu.
+    #[serde(default)]
+    pub scroll_mode: ScrollMode,
+
+    /// The initial arrangement of the snippets; it can also be changed in the view menu.
+    #[serde(default)]
+    pub rows: RowLayout,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Undo {
+    /// Roughly how much memory (in megabytes) the undo history is allowed to use. Snippets that
+    /// are shared between undo states are only counted once.
+    #[serde(default = "default_undo_memory_mb")]
+    pub memory_mb: usize,
+
+    /// When the undo history uses too much memory, should we move the oldest parts of it to a
+    /// temporary file? If not, the oldest parts are forgotten.
+    #[serde(default = "default_undo_spill")]
+    pub spill_to_disk: bool,
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct AudioInput {
     /// Should we do noise removal on the incoming audio?
@@ -53,6 +804,44 @@ pub struct AudioInput {
     /// sounds; 1.0 means we remove everything.)
     #[serde(default = "default_vad_threshold")]
     pub vad_threshold: f32,
+
+    /// When noise removal (or voice detection) is on, should we also keep the unprocessed
+    /// recording? Audio snippets that have it can be switched between the processed and the
+    /// unprocessed audio, but they take twice as much space.
+    #[serde(default)]
+    pub keep_raw: bool,
+
+    /// The length (in milliseconds) of the fade-in and fade-out that we apply to each recording.
+    /// This avoids pops at the beginning and end of the recording.
+    #[serde(default = "default_fade_ms")]
+    pub fade_ms: u32,
+
+    /// How many milliseconds to discard from the beginning of each recording. This can be useful
+    /// for getting rid of the sound of the keypress that started the recording.
+    #[serde(default = "default_trim_start_ms")]
+    pub trim_start_ms: u32,
+
+    /// How late (in milliseconds) the recorded audio arrives, compared to the animation. New audio
+    /// snippets are moved earlier by this amount. Use "Calibrate audio latency" in the edit menu
+    /// to measure it.
+    #[serde(default = "default_latency_ms")]
+    pub latency_ms: i32,
+
+    /// Should we play the incoming audio through the speakers while recording? There will be a
+    /// small delay, which some people find distracting.
+    #[serde(default = "default_monitor")]
+    pub monitor: bool,
+
+    /// If a recording contains a silence (as detected by voice detection) that's longer than this
+    /// many milliseconds, we split the recording into separate snippets at the silence. Set this
+    /// to zero to keep every recording in one piece.
+    #[serde(default = "default_split_silence_ms")]
+    pub split_silence_ms: u32,
+
+    /// Should we record from the microphone or from the system audio? On macOS, recording the
+    /// system audio requires a loopback device like BlackHole.
+    #[serde(default)]
+    pub source: AudioSource,
 }
 
 impl Default for AudioInput {
@@ -60,30 +849,108 @@ impl Default for AudioInput {
         AudioInput {
             remove_noise: default_remove_noise(),
             vad_threshold: default_vad_threshold(),
+            keep_raw: false,
+            fade_ms: default_fade_ms(),
+            trim_start_ms: default_trim_start_ms(),
+            latency_ms: default_latency_ms(),
+            monitor: default_monitor(),
+            split_silence_ms: default_split_silence_ms(),
+            source: AudioSource::default(),
         }
     }
 }
 
-impl Default for Export {
-    fn default() -> Export {
-        Export {
-            height: default_video_height(),
-            fps: default_video_fps(),
-            bitrate: default_video_bitrate(),
+impl Default for Drawing {
+    fn default() -> Drawing {
+        Drawing {
+            undo_granularity: UndoGranularity::default(),
+            stroke_group_ms: default_stroke_group_ms(),
+            palm_rejection: false,
+            lock_viewport: false,
         }
     }
 }
 
+impl Default for Undo {
+    fn default() -> Undo {
+        Undo {
+            memory_mb: default_undo_memory_mb(),
+            spill_to_disk: default_undo_spill(),
+        }
+    }
+}
+
+/// The path of the configuration file.
+fn config_path() -> Result<PathBuf> {
+    ProjectDirs::from("ink", "scribl", "scribl")
+        .map(|d| d.config_dir().join("config.toml"))
+        .ok_or_else(|| anyhow!("couldn't determine config directory"))
+}
+
 fn do_load_config() -> Result<Config> {
-    if let Some(proj_dirs) = ProjectDirs::from("ink", "scribl", "scribl") {
-        let mut path = proj_dirs.config_dir().to_owned();
-        path.push("config.toml");
-        let data = std::fs::read_to_string(&path).context(format!("config path {:?}", path))?;
-        let conf = toml::from_str(&data)?;
-        Ok(conf)
-    } else {
-        Err(anyhow!("couldn't determine config directory"))
+    let path = config_path()?;
+    let data = std::fs::read_to_string(&path).context(format!("config path {:?}", path))?;
+    let conf = toml::from_str(&data)?;
+    Ok(conf)
+}
+
+/// Sets `key = value` (where `value` is in TOML syntax) in the `[section]` table of the
+/// configuration file, so that it's still set after restarting. The rest of the file, including
+/// the comments, stays the same. If there's no configuration file yet, we start from the sample
+/// one.
+pub fn save_setting(section: &str, key: &str, value: &str) -> Result<()> {
+    let path = config_path()?;
+    let old = match std::fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => SAMPLE_CONFIG.to_owned(),
+        Err(e) => return Err(e).context(format!("config path {:?}", path)),
+    };
+    let new = with_setting(&old, section, key, value);
+    // Don't break a file that used to work.
+    if toml::from_str::<Config>(&old).is_ok() {
+        toml::from_str::<Config>(&new).context("setting the value made the config invalid")?;
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, new).context(format!("config path {:?}", path))?;
+    Ok(())
+}
+
+const SAMPLE_CONFIG: &str = include_str!("../sample/config.toml");
+
+// Returns the contents of a configuration file, modified to have `key = value` in the
+// `[section]` table.
+fn with_setting(text: &str, section: &str, key: &str, value: &str) -> String {
+    let header = format!("[{}]", section);
+    let setting = format!("{} = {}", key, value);
+    let mut lines: Vec<String> = text.lines().map(|l| l.to_owned()).collect();
+    match lines.iter().position(|l| l.trim() == header) {
+        Some(start) => {
+            let end = lines[(start + 1)..]
+                .iter()
+                .position(|l| l.trim_start().starts_with('['))
+                .map_or(lines.len(), |p| start + 1 + p);
+            let existing = (start + 1..end).find(|&i| {
+                let (k, _) = lines[i].split_once('=').unwrap_or(("", ""));
+                k.trim() == key
+            });
+            match existing {
+                Some(i) => lines[i] = setting,
+                None => lines.insert(start + 1, setting),
+            }
+        }
+        None => {
+            if lines.last().map_or(false, |l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(header);
+            lines.push(setting);
+        }
     }
+    let mut ret = lines.join("\n");
+    ret.push('\n');
+    ret
 }
 
 pub fn load_config() -> Config {
@@ -98,3 +965,38 @@ pub fn load_config() -> Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_config() {
+        toml::from_str::<Config>(SAMPLE_CONFIG).unwrap();
+    }
+
+    #[test]
+    fn settings() {
+        let text = "a = 1\n\n[audio_input]\n# b = 0\nb = 0\n\n[export]\nfps = 30\n";
+
+        // Existing settings get replaced, and everything else (including comments) stays.
+        assert_eq!(
+            with_setting(text, "audio_input", "b", "40"),
+            "a = 1\n\n[audio_input]\n# b = 0\nb = 40\n\n[export]\nfps = 30\n"
+        );
+        // New settings go at the start of their section.
+        assert_eq!(
+            with_setting(text, "export", "c", "720"),
+            "a = 1\n\n[audio_input]\n# b = 0\nb = 0\n\n[export]\nc = 720\nfps = 30\n"
+        );
+        // Settings in other sections don't count.
+        assert_eq!(
+            with_setting("[export]\nfps = 30\n", "live", "fps", "24"),
+            "[export]\nfps = 30\n\n[live]\nfps = 24\n"
+        );
+
+        let config = with_setting(SAMPLE_CONFIG, "audio_input", "latency_ms", "40");
+        let config: Config = toml::from_str(&config).unwrap();
+        assert_eq!(config.audio_input.latency_ms, 40);
+    }
+}
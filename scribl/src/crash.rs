@@ -0,0 +1,139 @@
+//! Salvaging unsaved work when scribl crashes.
+//!
+//! Every open editor keeps a snapshot of its current state here. If scribl panics, a panic hook
+//! writes all the snapshots to the crash-recovery directory before the process goes down. On the
+//! next launch, we offer to open whatever we find there.
+
+use directories_next::ProjectDirs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, TryLockError};
+
+use crate::SaveFileData;
+
+/// The latest state of every open editor.
+static SNAPSHOTS: Mutex<Vec<Snapshot>> = Mutex::new(Vec::new());
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+struct Snapshot {
+    id: u64,
+    path: Option<PathBuf>,
+    data: SaveFileData,
+}
+
+/// An editor's entry in the list of snapshots. When this is dropped, the entry is removed.
+pub struct Registration {
+    id: u64,
+}
+
+impl Registration {
+    pub fn new() -> Registration {
+        Registration {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Records the current state of the editor, to be saved if we crash.
+    pub fn update(&self, path: Option<PathBuf>, data: SaveFileData) {
+        let mut snapshots = SNAPSHOTS.lock().unwrap_or_else(|e| e.into_inner());
+        let snap = Snapshot {
+            id: self.id,
+            path,
+            data,
+        };
+        if let Some(old) = snapshots.iter_mut().find(|s| s.id == self.id) {
+            *old = snap;
+        } else {
+            snapshots.push(snap);
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut snapshots = SNAPSHOTS.lock().unwrap_or_else(|e| e.into_inner());
+        snapshots.retain(|s| s.id != self.id);
+    }
+}
+
+/// The directory where we write files when crashing.
+fn recovery_dir() -> Option<PathBuf> {
+    ProjectDirs::from("ink", "scribl", "scribl").map(|d| d.data_local_dir().join("crash-recovery"))
+}
+
+/// The name of the crash-recovery file for an editor that was editing `path`.
+fn recovery_name(path: Option<&Path>, id: u64) -> String {
+    match path.and_then(|p| p.file_stem()) {
+        Some(stem) => format!("{}.scb", stem.to_string_lossy()),
+        None => format!("untitled-{}.scb", id),
+    }
+}
+
+fn save_snapshots() {
+    let snapshots = match SNAPSHOTS.try_lock() {
+        Ok(s) => s,
+        Err(TryLockError::Poisoned(e)) => e.into_inner(),
+        // The panicking thread might be the one holding the lock, so don't wait for it.
+        Err(TryLockError::WouldBlock) => {
+            eprintln!("couldn't save the current animations, they were being updated");
+            return;
+        }
+    };
+    let dir = match recovery_dir() {
+        Some(d) => d,
+        None => {
+            eprintln!("couldn't save the current animations, no crash-recovery directory");
+            return;
+        }
+    };
+    for snap in snapshots.iter() {
+        let path = dir.join(recovery_name(snap.path.as_deref(), snap.id));
+        match snap.data.save_to_path(&path) {
+            Ok(()) => eprintln!("saved the current animation to {}", path.display()),
+            Err(e) => eprintln!("failed to save the current animation: {}", e),
+        }
+    }
+}
+
+/// Installs a panic hook that saves all the snapshots before handing over to the default hook.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        save_snapshots();
+        default_hook(info);
+    }));
+}
+
+/// Returns the files that were saved when scribl last crashed.
+pub fn recovered_files() -> Vec<PathBuf> {
+    let entries = match recovery_dir().map(std::fs::read_dir) {
+        Some(Ok(e)) => e,
+        _ => return Vec::new(),
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().map_or(false, |ext| ext == "scb"))
+        .collect()
+}
+
+/// Deletes some crash-recovery files.
+pub fn remove(paths: &[PathBuf]) {
+    for p in paths {
+        if let Err(e) = std::fs::remove_file(p) {
+            log::warn!("failed to delete {}: {}", p.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(recovery_name(Some(Path::new("a/talk.scb")), 3), "talk.scb");
+        assert_eq!(recovery_name(None, 3), "untitled-3.scb");
+    }
+}
@@ -1,16 +1,52 @@
+use druid::im::Vector;
+use druid::kurbo::BezPath;
 use druid::{Data, Lens, Point};
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Instant;
 
-use scribl_curves::{DrawSnippet, DrawSnippetId, StrokeInProgress, StrokeSeq, Time, TimeDiff};
-
-use crate::audio::{TalkSnippetId, TalkSnippets};
-use crate::config::Config;
-use crate::data::{DenoiseSetting, ScriblState, Settings};
-use crate::encode::EncodingStatus;
+use scribl_core::svg_import;
+use scribl_core::underlay::{Corner, Pip, Underlay};
+use scribl_curves::{
+    DrawSnippet, DrawSnippetId, DrawSnippets, GroupId, LabelColor, StrokeInProgress, StrokeSeq,
+    Time, TimeDiff,
+};
+
+use crate::audio::{
+    MusicOutput, MusicTrack, PencilSource, PunchIn, TalkSnippet, TalkSnippetId, TalkSnippets,
+};
+use crate::cache::CacheKind;
+use crate::cmd::EncodingStatusCmd;
+use crate::config::{AudioSource, Config};
+use crate::data::{
+    Checkpoint, DenoiseSetting, ExportQueue, RowHints, ScriblState, Settings, MAX_PLAYBACK_SPEED,
+    MIN_PLAYBACK_SPEED,
+};
+use crate::encode::{EncodingStatus, EncodingSummary};
+use crate::template::Template;
 use crate::undo::{UndoStack, UndoState};
 use crate::SaveFileData;
 
+/// When re-recording part of a snippet, we start playing (and recording) this long before the
+/// re-recorded range, so that the user can hear what comes before.
+const PUNCH_IN_PREROLL: TimeDiff = TimeDiff::from_micros(2_000_000);
+
+/// When re-recording part of a snippet, we automatically stop this long after the end of the
+/// re-recorded range.
+pub const PUNCH_IN_POSTROLL: TimeDiff = TimeDiff::from_micros(500_000);
+
+/// The length of the crossfade between the old and new audio when re-recording part of a snippet.
+const PUNCH_IN_CROSSFADE: TimeDiff = TimeDiff::from_micros(20_000);
+
+/// The most that we're willing to squash or stretch an audio snippet. Beyond this, speech
+/// becomes hard to understand.
+const MIN_STRETCH: f64 = 0.5;
+const MAX_STRETCH: f64 = 2.0;
+
+/// The smallest and largest allowed sizes of the webcam video, as fractions of the drawing width.
+const MIN_WEBCAM_SIZE: f64 = 0.1;
+const MAX_WEBCAM_SIZE: f64 = 0.5;
+
 impl From<DrawSnippetId> for SnippetId {
     fn from(id: DrawSnippetId) -> SnippetId {
         SnippetId::Draw(id)
@@ -32,6 +68,11 @@ pub struct InProgressStatus {
     pub autosaving: Option<PathBuf>,
     #[data(same_fn = "PartialEq::eq")]
     pub loading: Option<PathBuf>,
+    /// If we're loading, this is the fraction of the file that has been read so far.
+    pub loading_progress: f64,
+    /// If we're processing an audio snippet (see [`crate::cmd::PROCESS_AUDIO`]), this is the
+    /// fraction of it that has been processed so far.
+    pub processing_audio: Option<f64>,
 }
 
 #[derive(Clone, Data)]
@@ -53,8 +94,13 @@ pub enum FinishedStatus {
         path: PathBuf,
         #[data(same_fn = "PartialEq::eq")]
         time: Instant,
+        /// How the export went. Web exports don't have this.
+        #[data(same_fn = "PartialEq::eq")]
+        summary: Option<EncodingSummary>,
     },
     Error(String),
+    /// Something happened that the user should know about, but that isn't an error.
+    Notice(String),
 }
 
 // This is not the right thing. we should have something for operations in progress,
@@ -63,12 +109,108 @@ pub enum FinishedStatus {
 pub struct AsyncOpsStatus {
     pub in_progress: InProgressStatus,
     pub last_finished: Option<FinishedStatus>,
+    pub exports: ExportQueue,
+}
+
+/// How many recent input levels do we remember?
+const INPUT_HISTORY_LEN: usize = 24;
+
+/// If the sample peak gets above this, we consider the input to be clipping.
+const CLIPPING_THRESHOLD: f64 = 0.99;
+
+/// Recent information about the audio input levels, for displaying in the UI.
+#[derive(Clone, Data)]
+pub struct InputLevels {
+    /// The volume of the most recent chunk of input. This is on a logarithmic scale (and 0.0 is
+    /// very loud).
+    pub loudness: f64,
+    /// The volumes of the most recent chunks of input, oldest first.
+    pub history: Vector<f64>,
+    /// The loudest volume in `history`.
+    pub peak_hold: f64,
+    /// Whether the input has come close to clipping since we started recording.
+    pub clipped: bool,
+}
+
+impl Default for InputLevels {
+    fn default() -> InputLevels {
+        InputLevels {
+            loudness: -f64::INFINITY,
+            history: Vector::new(),
+            peak_hold: -f64::INFINITY,
+            clipped: false,
+        }
+    }
+}
+
+impl InputLevels {
+    /// Records the volume and sample peak of a new chunk of input.
+    pub fn push(&mut self, loudness: f64, peak: f64) {
+        self.loudness = loudness;
+        self.history.push_back(loudness);
+        while self.history.len() > INPUT_HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.peak_hold = self.history.iter().cloned().fold(-f64::INFINITY, f64::max);
+        self.clipped |= peak >= CLIPPING_THRESHOLD;
+    }
+}
+
+/// The state of the speech detection dialog, which plays an audio snippet with the parts that
+/// don't sound like speech cut out, so that they can hear what the threshold does before using
+/// it.
+#[derive(Clone, Data)]
+pub struct VadPreview {
+    /// The snippet being previewed.
+    pub id: TalkSnippetId,
+    /// The audio snippets that the preview was made from. If they change, the preview is stale.
+    talk: TalkSnippets,
+    /// The probability of speech in each frame of the snippet (see `vad::detect_speech`).
+    vad: Arc<Vec<f32>>,
+    threshold: f64,
+    /// The audio snippets, with the previewed one cut according to `threshold`.
+    gated: TalkSnippets,
+}
+
+impl VadPreview {
+    pub fn new(talk: TalkSnippets, id: TalkSnippetId, vad: Vec<f32>, threshold: f64) -> VadPreview {
+        let mut ret = VadPreview {
+            id,
+            gated: talk.clone(),
+            talk,
+            vad: Arc::new(vad),
+            threshold: f64::NAN,
+        };
+        ret.set_threshold(threshold);
+        ret
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    fn set_threshold(&mut self, threshold: f64) {
+        if threshold == self.threshold {
+            return;
+        }
+        self.threshold = threshold;
+        let snip = self.talk.snippet(self.id);
+        let mut buf = snip.samples().into_owned();
+        crate::audio::vad::gate(&mut buf, &self.vad, threshold as f32);
+        self.gated = self
+            .talk
+            .with_replacement_snippet(self.id, snip.with_samples(buf));
+    }
 }
 
 #[derive(Clone, Data, Debug)]
 pub struct RecordingState {
     pub time_factor: f64,
+    /// The clock doesn't start until they start drawing, so we start out paused.
     pub paused: bool,
+    /// They've explicitly paused the clock. Unlike `paused`, this doesn't go away when they
+    /// draw: it lasts until they resume.
+    pub held: bool,
     pub new_stroke: StrokeInProgress,
     pub new_stroke_seq: StrokeSeq,
 }
@@ -93,6 +235,36 @@ pub struct EditorState {
     #[lens(ignore)]
     pub undo: UndoStack,
 
+    /// If true, the drawing pane shows the drawing as it was before the most recent undoable
+    /// action, so that it can be compared with the current one.
+    pub comparing: bool,
+
+    /// If they're previewing one of the checkpoints, this is its index. While previewing, the
+    /// drawing pane shows the checkpoint's drawing instead of the current one.
+    pub checkpoint_preview: Option<usize>,
+
+    /// If the speech detection dialog is open, this is what it's previewing. While previewing,
+    /// playback uses the previewed audio instead of the real audio.
+    pub vad_preview: Option<VadPreview>,
+
+    /// The snippet that the mouse is over in the timeline, if any. Its name gets shown in a
+    /// tooltip.
+    pub hovered_snippet: Option<SnippetId>,
+
+    /// True while the canvas is being sent to a virtual camera or an RTMP server.
+    pub live: bool,
+
+    /// True if we're in presentation mode, where the window shows only the drawing (and a few
+    /// playback controls).
+    pub presenting: bool,
+
+    /// The script in the script console. It stays around when the console is closed, so that it
+    /// can be run again later.
+    pub script: String,
+
+    /// What the script printed the last time it ran (or why it failed).
+    pub script_output: String,
+
     /// The current (logical) animation time.
     ///
     /// This isn't public because of some invariants that need to be upheld; use `warp_time_to()`
@@ -109,9 +281,8 @@ pub struct EditorState {
     #[data(ignore)]
     time_snapshot: (Instant, Time),
 
-    /// The volume of the current audio input, if we're recording audio. This is on a logarithmic
-    /// scale (and 0.0 is very loud).
-    pub input_loudness: f64,
+    /// The volume of the current audio input, if we're recording audio.
+    pub input_levels: InputLevels,
 
     // There are several actions that we do asynchronously. Here, we have the most recent status of
     // these actions.
@@ -138,11 +309,22 @@ impl EditorState {
             mark: None,
 
             action: CurrentAction::Idle,
-            undo: UndoStack::new(),
+            undo: UndoStack::new(
+                &config.undo,
+                crate::cache::subdir(&config.cache, CacheKind::Undo),
+            ),
+            comparing: false,
+            checkpoint_preview: None,
+            vad_preview: None,
+            hovered_snippet: None,
+            live: false,
+            presenting: false,
+            script: String::new(),
+            script_output: String::new(),
 
             time_snapshot: (Instant::now(), Time::ZERO),
             time: Time::ZERO,
-            input_loudness: -f64::INFINITY,
+            input_levels: InputLevels::default(),
 
             status: AsyncOpsStatus::default(),
 
@@ -150,7 +332,7 @@ impl EditorState {
             saved_data: None,
             config,
         };
-        ret.saved_data = Some(SaveFileData::from_editor_state(&ret));
+        ret.saved_data = Some(SaveFileData::from_scribl(&ret.scribl));
         ret
     }
 
@@ -172,30 +354,466 @@ impl EditorState {
         self.push_undo_state(prev_state.with_time(time), action_text);
     }
 
+    /// Runs the script in the script console. Its changes get undone in a single step, and
+    /// nothing changes if it fails. Returns the files that it asked to export to.
+    pub fn run_script(&mut self) -> Vec<PathBuf> {
+        let output = match crate::script::run(&self.script, self.scribl.clone(), self.time) {
+            Ok(output) => output,
+            Err(e) => {
+                self.script_output = e.to_string();
+                return Vec::new();
+            }
+        };
+        self.script_output = output.log.join("\n");
+        let (scribl, time) = (output.scribl, output.time);
+        self.with_undo("run script", |state| {
+            state.scribl = scribl;
+            let selection_exists = match state.selected_snippet {
+                Some(SnippetId::Draw(id)) => state.scribl.draw.has_snippet(id),
+                Some(SnippetId::Talk(id)) => state.scribl.talk.has_snippet(id),
+                None => true,
+            };
+            if !selection_exists {
+                state.selected_snippet = None;
+            }
+            state.warp_time_to(time);
+        });
+        output.exports
+    }
+
     pub fn add_draw_snippet(&mut self, snip: DrawSnippet) {
         self.with_undo_at("add drawing", snip.start_time(), |state| {
             state.selected_snippet = Some(state.scribl.add_draw_snippet(snip).into());
         });
     }
 
+    /// Adds a drawing made of paths imported from an SVG file. It starts at the current time, and
+    /// takes as long to draw as the setting in `svg_import_secs`.
+    pub fn import_svg(&mut self, paths: &[BezPath]) {
+        let secs = self.settings.svg_import_secs.max(0.0);
+        let duration = TimeDiff::from_micros((secs * 1e6).round() as i64);
+        let seq = svg_import::to_strokes(paths, &self.settings.cur_style(), self.time(), duration);
+        if seq.is_empty() {
+            log::warn!("not importing an empty drawing");
+        } else {
+            self.add_draw_snippet(DrawSnippet::new(seq));
+        }
+    }
+
     pub fn delete_selected_snippet(&mut self) {
-        match self.selected_snippet {
-            Some(SnippetId::Draw(id)) => {
-                self.with_undo("delete drawing", |state| {
-                    state.scribl.delete_draw_snippet(id);
+        if let Some(id) = self.selected_snippet {
+            self.delete_snippet(id);
+        } else {
+            log::error!("No snippet id to delete");
+        }
+    }
+
+    /// Deletes a snippet (along with the snippets that are linked to it), unselecting it if it
+    /// was selected.
+    pub fn delete_snippet(&mut self, id: SnippetId) {
+        let members = self.group_members(id);
+        let desc = match id {
+            _ if members.len() > 1 => "delete linked snippets",
+            SnippetId::Draw(_) => "delete drawing",
+            SnippetId::Talk(_) => "delete audio",
+        };
+        self.with_undo(desc, |state| {
+            for member in members {
+                match member {
+                    SnippetId::Draw(id) => state.scribl.delete_draw_snippet(id),
+                    SnippetId::Talk(id) => state.scribl.delete_talk_snippet(id),
+                }
+                if state.selected_snippet == Some(member) {
                     state.selected_snippet = None;
-                });
+                }
             }
-            Some(SnippetId::Talk(id)) => self.with_undo("delete audio", |state| {
-                state.scribl.delete_talk_snippet(id);
-                state.selected_snippet = None;
+        });
+    }
+
+    /// Copies a snippet so that the copy starts at the current time, and selects the copy.
+    pub fn duplicate_snippet(&mut self, id: SnippetId) {
+        match id {
+            SnippetId::Draw(id) => self.with_undo("duplicate drawing", |state| {
+                let snip = state.scribl.draw.snippet(id);
+                // The copy doesn't get linked to the original's group.
+                let copy = snip
+                    .shifted(state.time() - snip.start_time())
+                    .with_group(None);
+                state.selected_snippet = Some(state.scribl.add_draw_snippet(copy).into());
             }),
-            None => {
-                log::error!("No snippet id to delete");
+            SnippetId::Talk(id) => self.with_undo("duplicate audio", |state| {
+                let snip = state.scribl.talk.snippet(id);
+                let copy = snip
+                    .shifted(state.time() - snip.start_time())
+                    .with_group(None);
+                state.selected_snippet = Some(state.scribl.add_talk_snippet(copy).into());
+            }),
+        }
+    }
+
+    /// Can the snippet be split in two at the current time?
+    pub fn can_split_snippet(&self, id: SnippetId) -> bool {
+        match id {
+            SnippetId::Draw(id) => self.scribl.draw.snippet(id).split(self.time()).is_some(),
+            SnippetId::Talk(id) => self.scribl.talk.snippet(id).split(self.time()).is_some(),
+        }
+    }
+
+    /// Splits a snippet in two at the current time, and selects the second part.
+    ///
+    /// For a drawing, the strokes that start before the current time stay in the original
+    /// snippet and the rest go to the new one.
+    pub fn split_snippet(&mut self, id: SnippetId) {
+        let time = self.time();
+        match id {
+            SnippetId::Draw(id) => {
+                if let Some((first, second)) = self.scribl.draw.snippet(id).split(time) {
+                    self.with_undo("split drawing", |state| {
+                        state.scribl.draw = state.scribl.draw.with_replacement_snippet(id, first);
+                        state.selected_snippet = Some(state.scribl.add_draw_snippet(second).into());
+                    });
+                } else {
+                    log::error!("cannot split drawing, no strokes on one side of the cursor");
+                }
+            }
+            SnippetId::Talk(id) => {
+                if let Some((first, second)) = self.scribl.talk.snippet(id).split(time) {
+                    self.with_undo("split audio", |state| {
+                        state.scribl.talk = state.scribl.talk.with_replacement_snippet(id, first);
+                        state.selected_snippet = Some(state.scribl.add_talk_snippet(second).into());
+                    });
+                } else {
+                    log::error!("cannot split audio, the cursor isn't inside it");
+                }
             }
         }
     }
 
+    /// Mutes an audio snippet, or unmutes it if it was already muted.
+    pub fn toggle_snippet_muted(&mut self, id: TalkSnippetId) {
+        let muted = self.scribl.talk.snippet(id).muted();
+        let text = if muted { "unmute audio" } else { "mute audio" };
+        self.with_undo(text, |state| {
+            state.scribl.talk = state.scribl.talk.with_muted_snippet(id, !muted);
+        });
+    }
+
+    /// Replaces the audio of a snippet with a processed version of its raw audio (see
+    /// [`TalkSnippet::with_processed_audio`]).
+    pub fn set_processed_audio(&mut self, id: TalkSnippetId, buf: Vec<i16>) {
+        self.with_undo("process audio", |state| {
+            let snip = state.scribl.talk.snippet(id).with_processed_audio(buf);
+            state.scribl.talk = state.scribl.talk.with_replacement_snippet(id, snip);
+        });
+    }
+
+    /// Switches an audio snippet between its raw and processed audio (see
+    /// [`TalkSnippet::is_raw`]).
+    pub fn toggle_snippet_raw(&mut self, id: TalkSnippetId) {
+        let snip = self.scribl.talk.snippet(id);
+        if !snip.has_raw() {
+            return;
+        }
+        let raw = snip.is_raw();
+        let text = if raw {
+            "use processed audio"
+        } else {
+            "use raw audio"
+        };
+        self.with_undo(text, |state| {
+            state.scribl.talk = state.scribl.talk.with_raw_snippet(id, !raw);
+        });
+    }
+
+    /// Makes a drawing play backwards, or forwards if it was already playing backwards.
+    pub fn toggle_snippet_reversed(&mut self, id: DrawSnippetId) {
+        let reversed = self.scribl.draw.snippet(id).reversed();
+        let text = if reversed {
+            "play drawing forwards"
+        } else {
+            "play drawing backwards"
+        };
+        self.with_undo(text, |state| {
+            let snip = state.scribl.draw.snippet(id).with_reversed(!reversed);
+            state.scribl.draw = state.scribl.draw.with_replacement_snippet(id, snip);
+        });
+    }
+
+    pub fn snippet_name(&self, id: SnippetId) -> Option<&str> {
+        match id {
+            SnippetId::Draw(id) => self.scribl.draw.snippet(id).name(),
+            SnippetId::Talk(id) => self.scribl.talk.snippet(id).name(),
+        }
+    }
+
+    /// A description of a snippet, like "drawing \"intro\", from 1.5s to 4.0s".
+    pub fn describe_snippet(&self, id: SnippetId) -> String {
+        let kind = match id {
+            SnippetId::Draw(_) => "drawing",
+            SnippetId::Talk(_) => "audio",
+        };
+        let (start, end) = self.snippet_span(id);
+        let secs = |t: Time| t.as_micros() as f64 / 1e6;
+        match self.snippet_name(id) {
+            Some(name) => format!(
+                "{} \"{}\", from {:.1}s to {:.1}s",
+                kind,
+                name,
+                secs(start),
+                secs(end)
+            ),
+            None => format!("{}, from {:.1}s to {:.1}s", kind, secs(start), secs(end)),
+        }
+    }
+
+    /// A longer, multi-line description of a snippet, for showing in a tooltip. It has the name,
+    /// the start and end times and the duration, and (for audio) the loudness.
+    ///
+    /// Measuring the loudness looks at the whole snippet, so don't call this too often.
+    pub fn snippet_details(&self, id: SnippetId) -> String {
+        let secs = |micros: i64| micros as f64 / 1e6;
+        let span = |start: Time, end: Time| {
+            format!(
+                "{:.1}s to {:.1}s ({:.1}s)",
+                secs(start.as_micros()),
+                secs(end.as_micros()),
+                secs((end - start).as_micros())
+            )
+        };
+        let mut lines = Vec::new();
+        match id {
+            SnippetId::Draw(id) => {
+                let snip = self.scribl.draw.snippet(id);
+                let start = snip.start_time();
+                lines.push(snip.name().unwrap_or("Drawing").to_owned());
+                lines.push(match snip.end_time() {
+                    Some(end) => span(start, end),
+                    None => format!("From {:.1}s", secs(start.as_micros())),
+                });
+                let drawing_time = snip.last_draw_time() - start;
+                lines.push(format!("Drawn in {:.1}s", secs(drawing_time.as_micros())));
+            }
+            SnippetId::Talk(id) => {
+                let snip = self.scribl.talk.snippet(id);
+                lines.push(snip.name().unwrap_or("Audio").to_owned());
+                lines.push(span(snip.start_time(), snip.end_time()));
+                match snip.loudness() {
+                    Ok(l) if l.is_finite() => lines.push(format!("Loudness: {:.1} LUFS", l)),
+                    Ok(_) => lines.push("Silent".to_owned()),
+                    Err(e) => log::error!("failed to measure loudness: {}", e),
+                }
+                if snip.is_raw() {
+                    lines.push("Playing the raw recording".to_owned());
+                } else if snip.has_raw() {
+                    lines.push("The raw recording was kept".to_owned());
+                }
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Changes the name of a snippet. This doesn't push an undo state, because it gets called
+    /// every time they type a character; the rename dialog takes care of undo.
+    pub fn set_snippet_name(&mut self, id: SnippetId, name: Option<String>) {
+        match id {
+            SnippetId::Draw(id) => {
+                let snip = self.scribl.draw.snippet(id).with_name(name);
+                self.scribl.draw = self.scribl.draw.with_replacement_snippet(id, snip);
+            }
+            SnippetId::Talk(id) => {
+                self.scribl.talk = self.scribl.talk.with_snippet_name(id, name);
+            }
+        }
+    }
+
+    /// Replaces the audio snippet `id` by the result of `f`, without pushing an undo state. The
+    /// snippet properties dialog uses this as they type, and pushes an undo state when it closes.
+    pub fn edit_talk_snippet(
+        &mut self,
+        id: TalkSnippetId,
+        f: impl FnOnce(&TalkSnippet) -> TalkSnippet,
+    ) {
+        let snip = f(self.scribl.talk.snippet(id));
+        self.scribl.talk = self.scribl.talk.with_replacement_snippet(id, snip);
+    }
+
+    /// Like [`edit_talk_snippet`](EditorState::edit_talk_snippet), but for drawing snippets.
+    pub fn edit_draw_snippet(
+        &mut self,
+        id: DrawSnippetId,
+        f: impl FnOnce(&DrawSnippet) -> DrawSnippet,
+    ) {
+        let snip = f(self.scribl.draw.snippet(id));
+        self.scribl.draw = self.scribl.draw.with_replacement_snippet(id, snip);
+    }
+
+    pub fn snippet_label(&self, id: SnippetId) -> Option<LabelColor> {
+        match id {
+            SnippetId::Draw(id) => self.scribl.draw.snippet(id).label(),
+            SnippetId::Talk(id) => self.scribl.talk.snippet(id).label(),
+        }
+    }
+
+    pub fn set_snippet_label(&mut self, id: SnippetId, label: Option<LabelColor>) {
+        self.with_undo("change label", |state| match id {
+            SnippetId::Draw(id) => {
+                let snip = state.scribl.draw.snippet(id).with_label(label);
+                state.scribl.draw = state.scribl.draw.with_replacement_snippet(id, snip);
+            }
+            SnippetId::Talk(id) => {
+                state.scribl.talk = state.scribl.talk.with_snippet_label(id, label);
+            }
+        });
+    }
+
+    /// The group of linked snippets that a snippet belongs to, if any.
+    pub fn snippet_group(&self, id: SnippetId) -> Option<GroupId> {
+        match id {
+            SnippetId::Draw(id) => self.scribl.draw.snippet(id).group(),
+            SnippetId::Talk(id) => self.scribl.talk.snippet(id).group(),
+        }
+    }
+
+    fn set_snippet_group(&mut self, id: SnippetId, group: Option<GroupId>) {
+        match id {
+            SnippetId::Draw(id) => {
+                let snip = self.scribl.draw.snippet(id).with_group(group);
+                self.scribl.draw = self.scribl.draw.with_replacement_snippet(id, snip);
+            }
+            SnippetId::Talk(id) => {
+                self.scribl.talk = self.scribl.talk.with_snippet_group(id, group);
+            }
+        }
+    }
+
+    fn snippet_ids(&self) -> impl Iterator<Item = SnippetId> + '_ {
+        let draw = self
+            .scribl
+            .draw
+            .snippets()
+            .map(|(id, _)| SnippetId::Draw(id));
+        let talk = self
+            .scribl
+            .talk
+            .snippets()
+            .map(|(id, _)| SnippetId::Talk(id));
+        draw.chain(talk)
+    }
+
+    /// All the snippets that are linked with `id`, including `id` itself.
+    pub fn group_members(&self, id: SnippetId) -> Vec<SnippetId> {
+        match self.snippet_group(id) {
+            Some(group) => self
+                .snippet_ids()
+                .filter(|&other| self.snippet_group(other) == Some(group))
+                .collect(),
+            None => vec![id],
+        }
+    }
+
+    /// Is this snippet linked with the selected snippet (without being the selected snippet)?
+    pub fn linked_to_selected(&self, id: SnippetId) -> bool {
+        match self.selected_snippet {
+            Some(sel) if sel != id && self.has_snippet(sel) => {
+                let group = self.snippet_group(sel);
+                group.is_some() && group == self.snippet_group(id)
+            }
+            _ => false,
+        }
+    }
+
+    /// The time interval in which a snippet is being drawn or heard.
+    fn snippet_span(&self, id: SnippetId) -> (Time, Time) {
+        match id {
+            SnippetId::Draw(id) => {
+                let snip = self.scribl.draw.snippet(id);
+                (snip.start_time(), snip.last_draw_time())
+            }
+            SnippetId::Talk(id) => {
+                let snip = self.scribl.talk.snippet(id);
+                (snip.start_time(), snip.end_time())
+            }
+        }
+    }
+
+    /// Selects the snippet starting next after (or, if `forward` is false, just before) the
+    /// selected one. If nothing is selected, picks the first snippet starting after (or before)
+    /// the current time.
+    pub fn select_adjacent_snippet(&mut self, forward: bool) {
+        let mut ids: Vec<_> = self
+            .snippet_ids()
+            .map(|id| (self.snippet_span(id).0, id))
+            .collect();
+        // The sort is stable, so snippets starting at the same time keep a consistent order.
+        ids.sort_by_key(|&(start, _)| start);
+
+        let pos = self
+            .selected_snippet
+            .and_then(|sel| ids.iter().position(|&(_, id)| id == sel));
+        let next = match (pos, forward) {
+            (Some(pos), true) => ids.get(pos + 1),
+            (Some(pos), false) => pos.checked_sub(1).and_then(|p| ids.get(p)),
+            (None, true) => ids.iter().find(|&&(start, _)| start >= self.time),
+            (None, false) => ids.iter().rev().find(|&&(start, _)| start <= self.time),
+        };
+        if let Some(&(_, id)) = next {
+            self.selected_snippet = Some(id);
+        }
+    }
+
+    /// Links a snippet with all the snippets that overlap it in time (and with anything that
+    /// they're already linked with), so that they get selected, moved and deleted together.
+    pub fn link_overlapping_snippets(&mut self, id: SnippetId) {
+        let (start, end) = self.snippet_span(id);
+        let mut members: Vec<SnippetId> = self
+            .snippet_ids()
+            .filter(|&other| {
+                let (other_start, other_end) = self.snippet_span(other);
+                other != id && other_start <= end && start <= other_end
+            })
+            .flat_map(|other| self.group_members(other))
+            .collect();
+        if members.is_empty() {
+            self.status.last_finished = Some(FinishedStatus::Notice(
+                "There's nothing overlapping to link with".to_owned(),
+            ));
+            return;
+        }
+        members.push(id);
+
+        let group = self.snippet_group(id).unwrap_or_else(|| {
+            GroupId::unused(self.snippet_ids().filter_map(|s| self.snippet_group(s)))
+        });
+        self.with_undo("link snippets", |state| {
+            for member in members {
+                state.set_snippet_group(member, Some(group));
+            }
+        });
+    }
+
+    /// Removes a snippet from its group of linked snippets. If that leaves only one snippet in
+    /// the group, that one gets unlinked too.
+    pub fn unlink_snippet(&mut self, id: SnippetId) {
+        let members = self.group_members(id);
+        self.with_undo("unlink snippet", |state| {
+            state.set_snippet_group(id, None);
+            if members.len() <= 2 {
+                for member in members {
+                    state.set_snippet_group(member, None);
+                }
+            }
+        });
+    }
+
+    /// Does this snippet (still) exist? Dialogs that refer to a snippet need to check this,
+    /// because the snippet might disappear (because of an undo, say) while they're open.
+    pub fn has_snippet(&self, id: SnippetId) -> bool {
+        match id {
+            SnippetId::Draw(id) => self.scribl.draw.has_snippet(id),
+            SnippetId::Talk(id) => self.scribl.talk.has_snippet(id),
+        }
+    }
+
     /// Truncates the currently selected snippet at the current time.
     ///
     /// This only has an effect if the current snippet is a drawing.
@@ -226,22 +844,92 @@ impl EditorState {
         }
     }
 
-    /// Shifts the given snippet in time.
-    pub fn shift_snippet(&mut self, id: SnippetId, by: TimeDiff) {
-        match id {
-            SnippetId::Draw(id) => {
-                self.with_undo("time-shift drawing", |data| {
-                    data.scribl.draw = data.scribl.draw.with_shifted_snippet(id, by);
+    /// Makes the selected drawing hold still between the marked time and the current time.
+    ///
+    /// Whatever the drawing was going to draw after that gets delayed until the end of the hold,
+    /// for example to let the narration catch up.
+    pub fn insert_hold(&mut self) {
+        if let (Some(mark_time), Some(SnippetId::Draw(id))) = (self.mark, self.selected_snippet) {
+            let start = mark_time.min(self.time());
+            let end = mark_time.max(self.time());
+            if start < end {
+                self.with_undo("insert hold", |data| {
+                    data.scribl.draw = data.scribl.draw.with_hold(id, start, end);
                 });
             }
-            SnippetId::Talk(id) => {
-                self.with_undo("time-shift speech", |data| {
-                    data.scribl.talk = data.scribl.talk.with_shifted_snippet(id, by);
+        } else if self.mark.is_none() {
+            log::error!("cannot insert hold, no marked time");
+        } else {
+            log::error!("cannot insert hold, nothing selected");
+        }
+    }
+
+    /// Is the given snippet holding still at the current time?
+    pub fn has_hold_at_time(&self, id: SnippetId) -> bool {
+        let time = self.time();
+        match id {
+            SnippetId::Draw(id) if self.scribl.draw.has_snippet(id) => self
+                .scribl
+                .draw
+                .snippet(id)
+                .holds()
+                .any(|(start, end)| start <= time && time <= end),
+            _ => false,
+        }
+    }
+
+    /// Removes the hold that the given snippet has at the current time.
+    pub fn remove_hold(&mut self, id: SnippetId) {
+        if let SnippetId::Draw(draw_id) = id {
+            if self.has_hold_at_time(id) {
+                self.with_undo("remove hold", |data| {
+                    data.scribl.draw = data.scribl.draw.without_hold(draw_id, data.time());
                 });
             }
         }
     }
 
+    /// Shifts the given snippet in time.
+    pub fn shift_snippet(&mut self, id: SnippetId, by: TimeDiff) {
+        let desc = match id {
+            SnippetId::Draw(_) => "time-shift drawing",
+            SnippetId::Talk(_) => "time-shift speech",
+        };
+        self.shift_snippet_with_undo(id, by, desc, false);
+    }
+
+    /// Shifts a snippet by a small amount. Repeatedly nudging the same snippet only creates one
+    /// undo state.
+    pub fn nudge_snippet(&mut self, id: SnippetId, by: TimeDiff) {
+        let desc = match id {
+            SnippetId::Draw(_) => "nudge drawing",
+            SnippetId::Talk(_) => "nudge speech",
+        };
+        self.shift_snippet_with_undo(id, by, desc, true);
+    }
+
+    fn shift_snippet_with_undo(&mut self, id: SnippetId, by: TimeDiff, desc: &str, merge: bool) {
+        let prev_state = self.undo_state();
+        // Linked snippets move together.
+        for member in self.group_members(id) {
+            match member {
+                SnippetId::Draw(id) => {
+                    self.scribl.draw = self.scribl.draw.with_shifted_snippet(id, by);
+                }
+                SnippetId::Talk(id) => {
+                    self.scribl.talk = self.scribl.talk.with_shifted_snippet(id, by);
+                }
+            }
+        }
+        if merge {
+            self.comparing = false;
+            self.undo
+                .push_merged(prev_state, self.undo_state(), desc.to_owned());
+        } else {
+            self.push_undo_state(prev_state, desc);
+        }
+    }
+
     /// Silences the currently selected range of audio.
     pub fn silence_audio(&mut self) {
         if let (Some(mark_time), Some(SnippetId::Talk(id))) = (self.mark, self.selected_snippet) {
@@ -271,6 +959,115 @@ impl EditorState {
         }
     }
 
+    /// Deletes the quiet audio at the beginning and end of the selected audio snippet.
+    pub fn trim_silence(&mut self) {
+        if let Some(SnippetId::Talk(id)) = self.selected_snippet {
+            let silence = &self.config.silence;
+            let snip = self.scribl.talk.snippet(id);
+            match snip.trimmed_quiet(silence.threshold(), silence.padding()) {
+                Some(trimmed) => {
+                    if trimmed.buf().len() < snip.buf().len() {
+                        self.with_undo("trim silence", |data| {
+                            data.scribl.talk =
+                                data.scribl.talk.with_replacement_snippet(id, trimmed);
+                        });
+                    }
+                }
+                None => {
+                    self.status.last_finished = Some(FinishedStatus::Notice(
+                        "Not trimming: the whole snippet is silent".to_owned(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Shortens the long pauses in the selected audio snippet.
+    pub fn tighten_pauses(&mut self) {
+        if let Some(SnippetId::Talk(id)) = self.selected_snippet {
+            let silence = &self.config.silence;
+            let snip = self.scribl.talk.snippet(id);
+            let tight = snip.tightened(
+                silence.threshold(),
+                silence.max_pause(),
+                silence.pause_factor,
+            );
+            if tight.buf().len() < snip.buf().len() {
+                self.with_undo("tighten pauses", |data| {
+                    data.scribl.talk = data.scribl.talk.with_replacement_snippet(id, tight);
+                });
+            }
+        }
+    }
+
+    /// Changes the speed (but not the pitch) of the selected audio snippet, so that it becomes
+    /// `ratio` times as long. It still starts at the same time.
+    pub fn stretch_audio(&mut self, ratio: f64) {
+        if let Some(SnippetId::Talk(id)) = self.selected_snippet {
+            let stretched = self.scribl.talk.snippet(id).stretched(ratio);
+            self.with_undo("change speech speed", |data| {
+                data.scribl.talk = data.scribl.talk.with_replacement_snippet(id, stretched);
+            });
+        }
+    }
+
+    /// Changes the speed (but not the pitch) of the selected audio snippet, so that it ends at the
+    /// current time.
+    pub fn stretch_audio_to_time(&mut self) {
+        if let Some(SnippetId::Talk(id)) = self.selected_snippet {
+            let snip = self.scribl.talk.snippet(id);
+            let old_len = (snip.end_time() - snip.start_time()).as_micros() as f64;
+            let new_len = (self.time() - snip.start_time()).as_micros() as f64;
+            let ratio = new_len / old_len;
+            if (MIN_STRETCH..=MAX_STRETCH).contains(&ratio) {
+                self.stretch_audio(ratio);
+            } else {
+                self.status.last_finished = Some(FinishedStatus::Notice(format!(
+                    "Can't stretch the audio that much (it can be between {}x and {}x as long)",
+                    MIN_STRETCH, MAX_STRETCH
+                )));
+            }
+        }
+    }
+
+    /// Starts re-recording the selected range of the selected audio snippet.
+    pub fn punch_in(&mut self) {
+        if let (Some(mark_time), Some(SnippetId::Talk(id))) = (self.mark, self.selected_snippet) {
+            let start = mark_time.min(self.time());
+            let end = mark_time.max(self.time());
+            if start == end {
+                return;
+            }
+
+            self.finish_action();
+            let record_start = start - PUNCH_IN_PREROLL;
+            self.warp_time_to(record_start);
+            self.action = CurrentAction::PunchingIn(PunchIn {
+                snippet: id,
+                start,
+                end,
+                record_start,
+            });
+        }
+    }
+
+    /// Replaces part of a snippet with newly recorded audio.
+    pub fn finish_punch_in(&mut self, punch: &PunchIn, take: &TalkSnippet) {
+        if !self.scribl.talk.has_snippet(punch.snippet) {
+            log::error!("the re-recorded snippet no longer exists");
+            return;
+        }
+        self.with_undo_at("re-record speech", punch.start, |data| {
+            data.scribl.talk = data.scribl.talk.with_punched_in_snippet(
+                punch.snippet,
+                take,
+                punch.start,
+                punch.end,
+                PUNCH_IN_CROSSFADE,
+            );
+        });
+    }
+
     /// Multiplies the volume of the selected audio snippet by the given factor.
     pub fn multiply_volume(&mut self, factor: f64) {
         if let Some(SnippetId::Talk(id)) = self.selected_snippet {
@@ -285,11 +1082,148 @@ impl EditorState {
         }
     }
 
+    /// Changes the volume of all the audio snippets so that the integrated loudness of the
+    /// animation goes from `loudness` to `target` (both in LUFS).
+    ///
+    /// The music's volume stays the same, so if there's music then the new loudness will only be
+    /// approximately `target`.
+    pub fn normalize_loudness(&mut self, loudness: f64, target: f64) {
+        let factor = crate::audio::loudness_gain(loudness, target);
+        self.with_undo("normalize loudness", |data| {
+            data.scribl.talk = data.scribl.talk.with_all_multiplied(factor);
+        });
+    }
+
+    /// Adds a point to the volume envelope of an audio snippet.
+    pub fn add_gain_point(&mut self, id: TalkSnippetId, offset: TimeDiff, gain: f32) {
+        self.with_undo("add volume point", |data| {
+            let gain = data.scribl.talk.snippet(id).gain().with_point(offset, gain);
+            data.scribl.talk = data.scribl.talk.with_gain_envelope(id, gain);
+        });
+    }
+
+    /// Removes a point from the volume envelope of an audio snippet.
+    pub fn remove_gain_point(&mut self, id: TalkSnippetId, idx: usize) {
+        self.with_undo("remove volume point", |data| {
+            let gain = data.scribl.talk.snippet(id).gain().without_point(idx);
+            data.scribl.talk = data.scribl.talk.with_gain_envelope(id, gain);
+        });
+    }
+
+    /// Replaces the background music.
+    pub fn set_music(&mut self, music: MusicTrack) {
+        self.with_undo("import music", |data| {
+            data.scribl.music = Some(music);
+        });
+    }
+
+    /// Adds an audio snippet that was imported from a file, and selects it.
+    pub fn import_audio(&mut self, snip: TalkSnippet) {
+        self.with_undo("import audio", |data| {
+            data.selected_snippet = Some(data.scribl.add_talk_snippet(snip).into());
+        });
+    }
+
+    /// Removes the background music.
+    pub fn remove_music(&mut self) {
+        self.with_undo("remove music", |data| {
+            data.scribl.music = None;
+        });
+    }
+
+    /// Toggles whether the background music repeats until the end of the animation.
+    pub fn toggle_music_looping(&mut self) {
+        self.with_undo("loop music", |data| {
+            if let Some(music) = &data.scribl.music {
+                data.scribl.music = Some(music.with_looping(!music.looping()));
+            }
+        });
+    }
+
+    /// Multiplies the volume of the background music by the given factor.
+    pub fn multiply_music_volume(&mut self, factor: f32) {
+        let text = if factor > 1.0 {
+            "increase music volume"
+        } else {
+            "decrease music volume"
+        };
+        self.with_undo(text, |data| {
+            if let Some(music) = &data.scribl.music {
+                data.scribl.music = Some(music.with_volume(music.volume() * factor));
+            }
+        });
+    }
+
+    /// Replaces the screen recording that plays behind the drawing.
+    pub fn set_underlay(&mut self, underlay: Underlay) {
+        self.with_undo("record screen", |data| {
+            data.scribl.underlay = Some(underlay);
+        });
+    }
+
+    /// Removes the screen recording that plays behind the drawing.
+    pub fn remove_underlay(&mut self) {
+        self.with_undo("remove screen recording", |data| {
+            data.scribl.underlay = None;
+        });
+    }
+
+    /// Replaces the webcam recording. If there was one already, the new one goes in the same
+    /// place; otherwise, its position comes from the config.
+    pub fn set_webcam_video(&mut self, video: Underlay) {
+        let pip = match &self.scribl.webcam {
+            Some(old) => Pip {
+                video,
+                ..old.clone()
+            },
+            None => Pip {
+                video,
+                corner: self.config.webcam.corner,
+                size: self.config.webcam.size,
+            },
+        };
+        self.with_undo("record webcam", |data| {
+            data.scribl.webcam = Some(pip);
+        });
+    }
+
+    /// Removes the webcam recording.
+    pub fn remove_webcam(&mut self) {
+        self.with_undo("remove webcam", |data| {
+            data.scribl.webcam = None;
+        });
+    }
+
+    /// Moves the webcam video to a different corner.
+    pub fn set_webcam_corner(&mut self, corner: Corner) {
+        self.with_undo("move webcam", |data| {
+            if let Some(pip) = &mut data.scribl.webcam {
+                pip.corner = corner;
+            }
+        });
+    }
+
+    /// Multiplies the size of the webcam video by the given factor.
+    pub fn resize_webcam(&mut self, factor: f64) {
+        self.with_undo("resize webcam", |data| {
+            if let Some(pip) = &mut data.scribl.webcam {
+                pip.size = (pip.size * factor).clamp(MIN_WEBCAM_SIZE, MAX_WEBCAM_SIZE);
+            }
+        });
+    }
+
     /// Sets the timeline mark to the current time.
     pub fn set_mark(&mut self) {
         self.with_undo("set mark", |state| state.mark = Some(state.time()));
     }
 
+    /// The range between the mark and the current time, if there's a mark and they're different.
+    pub fn marked_range(&self) -> Option<(Time, Time)> {
+        self.mark
+            .filter(|&m| m != self.time())
+            .map(|m| (m.min(self.time()), m.max(self.time())))
+    }
+
     /// Removes the current timeline mark.
     pub fn clear_mark(&mut self) {
         if self.mark.is_some() {
@@ -297,6 +1231,51 @@ impl EditorState {
         }
     }
 
+    /// Makes the animation end at the current time.
+    pub fn set_project_end(&mut self) {
+        if self.time() > Time::ZERO && self.scribl.project_end != Some(self.time()) {
+            self.with_undo("set end", |data| {
+                data.scribl.project_end = Some(data.time());
+            });
+        }
+    }
+
+    /// Goes back to having the animation end when its last snippet does.
+    pub fn clear_project_end(&mut self) {
+        if self.scribl.project_end.is_some() {
+            self.with_undo("clear end", |data| data.scribl.project_end = None);
+        }
+    }
+
+    /// Removes the marker at the current time, if there is one.
+    pub fn remove_marker(&mut self) {
+        if self.scribl.markers.get(self.time()).is_some() {
+            self.with_undo("remove marker", |data| {
+                data.scribl.markers = data.scribl.markers.without_marker(data.time());
+            });
+        }
+    }
+
+    /// Starts a new page at the current time.
+    pub fn add_page_break(&mut self) {
+        if self.time() > Time::ZERO && !self.scribl.pages.has_break(self.time()) {
+            self.with_undo("new page", |data| {
+                data.scribl.pages = data.scribl.pages.with_break(data.time());
+            });
+        }
+    }
+
+    /// Removes the page break at the start of the current page, merging it with the previous
+    /// page.
+    pub fn remove_page_break(&mut self) {
+        let start = self.scribl.pages.page_start(self.time());
+        if start > Time::ZERO {
+            self.with_undo("remove page break", |data| {
+                data.scribl.pages = data.scribl.pages.without_break(start);
+            });
+        }
+    }
+
     /// Updates `self.time` according to the current wall clock time.
     pub fn update_time(&mut self) {
         self.time = self.accurate_time();
@@ -374,11 +1353,36 @@ impl EditorState {
         self.take_time_snapshot();
     }
 
+    /// Moves the current time to the end of the animation.
+    pub fn warp_time_to_end(&mut self) {
+        self.warp_time_to(self.scribl.end_time());
+    }
+
+    /// The frame rate of exported videos. When stepping through the animation frame by frame,
+    /// these are the frames that we step through.
+    ///
+    /// This is the animation's own frame rate if it has one, and otherwise the one from the
+    /// export settings. It has nothing to do with how often the UI gets redrawn.
+    pub fn fps(&self) -> f64 {
+        self.scribl.fps.unwrap_or_else(|| self.export_config().fps)
+    }
+
+    /// The video frame (at the export frame rate) that the current time belongs to.
+    pub fn frame(&self) -> u32 {
+        self.time.as_video_frame(self.fps())
+    }
+
+    /// Sets the current time to the beginning of a video frame, so that the drawing shows exactly
+    /// what that frame will look like when exported.
+    pub fn warp_time_to_frame(&mut self, frame: u32) {
+        self.warp_time_to(Time::from_video_frame(frame, self.fps()));
+    }
+
     pub fn add_point_to_stroke(&mut self, p: Point, t: Time) {
         let mut unpause = false;
         if let CurrentAction::Recording(rec_state) = &mut self.action {
             rec_state.new_stroke.add_point(p, t);
-            if rec_state.paused {
+            if rec_state.paused && !rec_state.held {
                 rec_state.paused = false;
                 unpause = true;
             }
@@ -398,6 +1402,18 @@ impl EditorState {
         if let CurrentAction::Recording(rec_state) = &mut self.action {
             let stroke = std::mem::replace(&mut rec_state.new_stroke, StrokeInProgress::new());
             let start_time = stroke.start_time().unwrap_or(Time::ZERO);
+            let seq = &rec_state.new_stroke_seq;
+            let prev_end = if seq.is_empty() {
+                None
+            } else {
+                Some(seq.last_time())
+            };
+            let gap = TimeDiff::from_micros(self.config.drawing.stroke_group_ms as i64 * 1000);
+            let merge = self
+                .config
+                .drawing
+                .undo_granularity
+                .merges_stroke(prev_end, start_time, gap);
 
             // Note that cloning and appending to a StrokeSeq is cheap, because it uses im::Vector
             // internally.
@@ -405,7 +1421,17 @@ impl EditorState {
             seq.append_stroke(stroke, style, shape_detect, 0.0005);
             rec_state.new_stroke_seq = seq.clone();
 
-            self.push_transient_undo_state(prev_state.with_time(start_time), "add stroke");
+            if merge {
+                self.comparing = false;
+                self.undo.push_transient_merged(
+                    prev_state.with_time(start_time),
+                    self.undo_state(),
+                    "add stroke".to_owned(),
+                );
+            } else {
+                self.push_transient_undo_state(prev_state.with_time(start_time), "add stroke");
+            }
+            self.settings.palette.mark_used();
         } else {
             log::error!("tried to finish a stroke, but we weren't recording");
         }
@@ -434,17 +1460,29 @@ impl EditorState {
     pub fn from_save_file(data: SaveFileData, config: Config) -> EditorState {
         let mut ret = EditorState {
             scribl: ScriblState::from_save_file(&data),
-            undo: UndoStack::new(),
             ..EditorState::new(config)
         };
         ret.saved_data = Some(data);
         ret
     }
 
+    /// Starts a new (unsaved) animation from a template.
+    pub fn from_template(template: Template, config: Config) -> EditorState {
+        let mut ret = EditorState::from_save_file(template.project.clone(), config);
+        ret.settings.palette = template.palette_data();
+        ret
+    }
+
     pub fn undo_state(&self) -> UndoState {
         UndoState {
             snippets: self.scribl.draw.clone(),
             audio_snippets: self.scribl.talk.clone(),
+            music: self.scribl.music.clone(),
+            underlay: self.scribl.underlay.clone(),
+            webcam: self.scribl.webcam.clone(),
+            markers: self.scribl.markers.clone(),
+            pages: self.scribl.pages.clone(),
+            project_end: self.scribl.project_end,
             selected_snippet: self.selected_snippet.clone(),
             mark: self.mark,
             time: self.time,
@@ -453,11 +1491,13 @@ impl EditorState {
     }
 
     pub fn push_undo_state(&mut self, prev_state: UndoState, description: impl ToString) {
+        self.comparing = false;
         self.undo
             .push(prev_state, self.undo_state(), description.to_string());
     }
 
     pub fn push_transient_undo_state(&mut self, prev_state: UndoState, description: impl ToString) {
+        self.comparing = false;
         self.undo
             .push_transient(prev_state, self.undo_state(), description.to_string());
     }
@@ -467,7 +1507,7 @@ impl EditorState {
     }
 
     fn restore_undo_state(&mut self, undo: UndoState) {
-        self.scribl.restore_undo_state(&undo);
+        undo.restore_scribl(&mut self.scribl);
         self.selected_snippet = undo.selected_snippet;
         self.mark = undo.mark;
         self.warp_time_to(undo.time);
@@ -480,6 +1520,7 @@ impl EditorState {
         // the state is recording but paused.
         if let CurrentAction::Recording(mut rec_state) = undo.action {
             rec_state.paused = true;
+            rec_state.held = false;
             rec_state.new_stroke = StrokeInProgress::new();
 
             if !rec_state.new_stroke_seq.is_empty() {
@@ -496,6 +1537,7 @@ impl EditorState {
     }
 
     pub fn undo(&mut self) {
+        self.comparing = false;
         let state = self.undo.undo();
         if let Some(state) = state {
             self.restore_undo_state(state);
@@ -503,22 +1545,106 @@ impl EditorState {
     }
 
     pub fn redo(&mut self) {
+        self.comparing = false;
         let state = self.undo.redo();
         if let Some(state) = state {
             self.restore_undo_state(state);
         }
     }
 
-    pub fn update_encoding_status(&mut self, enc_status: &EncodingStatus) {
-        match enc_status {
+    /// Saves the current drawing and audio as a new checkpoint.
+    pub fn add_checkpoint(&mut self) {
+        let name = format!("Checkpoint {}", self.scribl.checkpoints.len() + 1);
+        self.scribl.checkpoints.push_back(Checkpoint {
+            name,
+            draw: self.scribl.draw.clone(),
+            talk: self.scribl.talk.clone(),
+        });
+    }
+
+    /// Replaces the current drawing and audio with the ones from a checkpoint. This can be undone.
+    pub fn restore_checkpoint(&mut self, idx: usize) {
+        self.checkpoint_preview = None;
+        if let Some(checkpoint) = self.scribl.checkpoints.get(idx).cloned() {
+            self.with_undo("restore checkpoint", |data| {
+                data.scribl.draw = checkpoint.draw;
+                data.scribl.talk = checkpoint.talk;
+                data.selected_snippet = None;
+            });
+        }
+    }
+
+    pub fn delete_checkpoint(&mut self, idx: usize) {
+        self.checkpoint_preview = None;
+        if idx < self.scribl.checkpoints.len() {
+            self.scribl.checkpoints.remove(idx);
+        }
+    }
+
+    /// Toggles between showing the current drawing and the drawing from before the most recent
+    /// undoable action.
+    pub fn toggle_comparing(&mut self) {
+        self.comparing = !self.comparing && self.undo.can_undo();
+    }
+
+    /// Enters or leaves presentation mode. We only present while idle or playing.
+    pub fn toggle_presenting(&mut self) {
+        if self.presenting {
+            self.presenting = false;
+        } else if self.action.is_idle() || self.action.is_playing() {
+            self.presenting = true;
+            self.comparing = false;
+        }
+    }
+
+    /// Switches to the pen preset (from the config file) with this index, if there is one.
+    pub fn select_pen_preset(&mut self, idx: usize) {
+        if let Some(preset) = self.config.pen_presets.get(idx) {
+            self.settings.apply_pen_preset(preset);
+        }
+    }
+
+    /// Jumps to the next (if `forward` is true) or previous marker, if there is one. If we're
+    /// playing, we keep playing from the marker.
+    pub fn jump_to_marker(&mut self, forward: bool) {
+        let time = if forward {
+            self.scribl.markers.next_after(self.time())
+        } else {
+            self.scribl.markers.prev_before(self.time())
+        };
+        // While playing, the audio thread notices the new time snapshot and seeks to it.
+        if let Some(time) = time {
+            self.warp_time_to(time);
+        }
+    }
+
+    /// The drawing that should be shown in the drawing pane. This is usually just the current
+    /// drawing, but see [`EditorState::comparing`] and [`EditorState::checkpoint_preview`].
+    pub fn visible_drawing(&self) -> &DrawSnippets {
+        let preview = self
+            .checkpoint_preview
+            .and_then(|idx| self.scribl.checkpoints.get(idx));
+        if let Some(checkpoint) = preview {
+            return &checkpoint.draw;
+        }
+        match self.undo.undo_preview() {
+            Some(prev) if self.comparing && self.action.is_idle() => &prev.snippets,
+            _ => &self.scribl.draw,
+        }
+    }
+
+    pub fn update_encoding_status(&mut self, cmd: &EncodingStatusCmd) {
+        self.status.exports.update(cmd.job, &cmd.status);
+        match &cmd.status {
             EncodingStatus::Encoding { frame, out_of } => {
                 self.status.in_progress.encoding = Some((*frame, *out_of));
             }
-            EncodingStatus::Finished(path) => {
+            EncodingStatus::Finished(path, summary) => {
                 self.status.in_progress.encoding = None;
                 self.status.last_finished = Some(FinishedStatus::Encoded {
                     path: path.clone(),
                     time: Instant::now(),
+                    summary: Some(summary.clone()),
                 });
             }
             EncodingStatus::Error(s) => {
@@ -567,17 +1693,48 @@ impl EditorState {
     }
 
     pub fn changed_since_last_save(&self) -> bool {
-        let new_save = SaveFileData::from_editor_state(self);
+        let new_save = SaveFileData::from_scribl(&self.scribl);
         !self.saved_data.same(&Some(new_save))
     }
 
+    /// Remembers the timeline rows of the snippets. These get saved with the animation, but
+    /// they aren't really an edit: they don't get an undo state, and they don't count as an
+    /// unsaved change.
+    pub fn set_row_hints(&mut self, hints: RowHints) {
+        let saved = !self.changed_since_last_save();
+        self.scribl.row_hints = hints.clone();
+        if saved {
+            if let Some(saved_data) = &mut self.saved_data {
+                saved_data.row_hints = hints;
+            }
+        }
+    }
+
+    /// The settings to use for exporting, taking into account the selected export preset.
+    pub fn export_config(&self) -> crate::config::Export {
+        self.settings
+            .export_preset
+            .as_ref()
+            .and_then(|name| self.config.export_preset(name))
+            .map(|preset| preset.export)
+            .unwrap_or_else(|| self.config.export.clone())
+    }
+
     pub fn audio_state(&self) -> AudioState {
         use CurrentAction::*;
 
-        let snips = self.scribl.talk.clone();
+        let snips = match &self.vad_preview {
+            Some(preview) if preview.talk.same(&self.scribl.talk) => preview.gated.clone(),
+            _ => self.scribl.talk.clone(),
+        };
         let play = |velocity: f64| AudioState::Playing {
             start_time: self.time_snapshot.1,
             snips,
+            music: self.scribl.music_output(),
+            pencil: self.scribl.pencil_volume.map(|volume| PencilSource {
+                draw: self.scribl.draw.clone(),
+                volume,
+            }),
             velocity,
         };
 
@@ -596,15 +1753,34 @@ impl EditorState {
                 config.remove_noise = true;
             }
         }
+        // Playing the system audio back through the speakers would just feed it back into the
+        // recording.
+        config.source = self.settings.audio_source;
+        config.monitor = self.settings.monitor_input && config.source == AudioSource::Microphone;
 
         match &self.action {
-            Playing => play(1.0),
+            Playing(x) => play(*x),
             Scanning(x) => play(*x),
-            Recording(state) if !state.paused => play(state.time_factor),
+            Recording(state) if !state.paused && !state.held => play(state.time_factor),
             RecordingAudio(t) => AudioState::Recording {
                 start_time: *t,
                 config,
             },
+            PunchingIn(punch) => {
+                // Trimming the beginning of the recording would mess up the alignment with the
+                // original audio.
+                config.trim_start_ms = 0;
+                // They should hear the original audio up until the punch-in, but not after.
+                let snips = self
+                    .scribl
+                    .talk
+                    .with_silenced_snippet(punch.snippet, punch.start, punch.end);
+                AudioState::PunchingIn {
+                    snips,
+                    config,
+                    punch: *punch,
+                }
+            }
             _ => AudioState::Idle,
         }
     }
@@ -615,6 +1791,7 @@ impl EditorState {
             state.action = CurrentAction::Recording(RecordingState {
                 time_factor: state.settings.recording_speed.factor(),
                 paused: true,
+                held: false,
                 new_stroke: StrokeInProgress::new(),
                 new_stroke_seq: StrokeSeq::default(),
             });
@@ -622,12 +1799,77 @@ impl EditorState {
         });
     }
 
+    /// While recording a drawing, pauses or resumes the clock. Anything drawn while the clock is
+    /// paused still goes into the same drawing snippet, all at the same time.
+    pub fn toggle_recording_pause(&mut self) {
+        self.update_time();
+        if let CurrentAction::Recording(rec_state) = &mut self.action {
+            rec_state.held = !rec_state.held;
+            // When resuming, the clock starts right away instead of waiting for them to draw.
+            rec_state.paused = rec_state.held;
+        }
+        self.take_time_snapshot();
+    }
+
     pub fn play(&mut self) {
         self.finish_action();
-        self.action = CurrentAction::Playing;
+        self.action = CurrentAction::Playing(self.settings.playback_speed);
         self.take_time_snapshot();
     }
 
+    /// Changes the playback speed. If we're currently playing, the new speed takes effect
+    /// immediately.
+    pub fn set_playback_speed(&mut self, speed: f64) {
+        // Round to a multiple of 5%, so that it's easy to get back to exactly 1.0.
+        let speed = (speed * 20.0).round() / 20.0;
+        let speed = speed.max(MIN_PLAYBACK_SPEED).min(MAX_PLAYBACK_SPEED);
+        if speed == self.settings.playback_speed {
+            return;
+        }
+        self.settings.playback_speed = speed;
+        if self.action.is_playing() {
+            self.update_time();
+            self.action = CurrentAction::Playing(speed);
+            self.take_time_snapshot();
+        }
+    }
+
+    /// Changes the threshold in the speech detection preview. If we're currently playing, the
+    /// preview keeps playing from the current time with the new threshold.
+    pub fn set_vad_preview_threshold(&mut self, threshold: f64) {
+        // Round to a multiple of 1%, so that the threshold is easy to copy into the config file.
+        let threshold = (threshold * 100.0).round() / 100.0;
+        if let Some(preview) = &mut self.vad_preview {
+            preview.set_threshold(threshold);
+            if self.action.is_playing() {
+                self.update_time();
+                self.take_time_snapshot();
+            }
+        }
+    }
+
+    /// Plays the snippet in the speech detection preview from the beginning, or stops playing if
+    /// we're already playing.
+    pub fn toggle_vad_preview_playing(&mut self) {
+        if self.action.is_playing() {
+            self.finish_action();
+        } else if let Some(preview) = &self.vad_preview {
+            if self.scribl.talk.has_snippet(preview.id) {
+                let start = self.scribl.talk.snippet(preview.id).start_time();
+                self.finish_action();
+                self.warp_time_to(start);
+                self.play();
+            }
+        }
+    }
+
+    /// Closes the speech detection preview, stopping the playback if it was playing.
+    pub fn close_vad_preview(&mut self) {
+        if self.vad_preview.take().is_some() && self.action.is_playing() {
+            self.finish_action();
+        }
+    }
+
     pub fn talk(&mut self) {
         self.finish_action();
         self.action = CurrentAction::RecordingAudio(self.time);
@@ -643,8 +1885,8 @@ impl EditorState {
                     self.pop_transient_undo_states();
                 }
             }
-            CurrentAction::RecordingAudio(_) => {
-                self.input_loudness = -f64::INFINITY;
+            CurrentAction::RecordingAudio(_) | CurrentAction::PunchingIn(_) => {
+                self.input_levels = InputLevels::default();
             }
             _ => {}
         }
@@ -660,12 +1902,15 @@ pub enum CurrentAction {
     /// They are drawing an animation, while the time is ticking.
     Recording(RecordingState),
 
-    /// They are watching the animation.
-    Playing,
+    /// They are watching the animation. The parameter is the playback speed.
+    Playing(f64),
 
     /// The argument is the time at which audio capture started.
     RecordingAudio(Time),
 
+    /// They are re-recording part of an audio snippet.
+    PunchingIn(PunchIn),
+
     /// Fast-forward or reverse. The parameter is the speed factor, negative for reverse.
     Scanning(f64),
 
@@ -677,6 +1922,10 @@ pub enum CurrentAction {
 
     /// We are waiting for some async task to finish, and when it's done we will exit.
     WaitingToExit,
+
+    /// A dialog with a text input (like the "go to time" dialog) is open, so we don't want any
+    /// keyboard shortcuts to be active.
+    EditingText,
 }
 
 impl Default for CurrentAction {
@@ -687,11 +1936,15 @@ impl Default for CurrentAction {
 
 impl CurrentAction {
     pub fn is_playing(&self) -> bool {
-        matches!(*self, CurrentAction::Playing)
+        matches!(*self, CurrentAction::Playing(_))
     }
 
+    /// Are we recording audio? This includes re-recording part of a snippet.
     pub fn is_recording_audio(&self) -> bool {
-        matches!(self, &CurrentAction::RecordingAudio(_))
+        matches!(
+            self,
+            &CurrentAction::RecordingAudio(_) | &CurrentAction::PunchingIn(_)
+        )
     }
 
     pub fn is_idle(&self) -> bool {
@@ -702,13 +1955,19 @@ impl CurrentAction {
         matches!(*self, CurrentAction::Recording(_))
     }
 
+    /// Are we recording a drawing, with the clock explicitly paused?
+    pub fn is_recording_held(&self) -> bool {
+        matches!(self, CurrentAction::Recording(state) if state.held)
+    }
+
     pub fn time_factor(&self) -> f64 {
         use CurrentAction::*;
         match self {
-            Playing => 1.0,
+            Playing(x) => *x,
             RecordingAudio(_) => 1.0,
+            PunchingIn(_) => 1.0,
             Recording(state) => {
-                if state.paused {
+                if state.paused || state.held {
                     0.0
                 } else {
                     state.time_factor
@@ -730,6 +1989,8 @@ pub enum AudioState {
     Idle,
     Playing {
         snips: TalkSnippets,
+        music: Option<MusicOutput>,
+        pencil: Option<PencilSource>,
         start_time: Time,
         velocity: f64,
     },
@@ -737,4 +1998,52 @@ pub enum AudioState {
         start_time: Time,
         config: crate::config::AudioInput,
     },
+    /// Playing and recording at the same time, in order to re-record part of a snippet.
+    PunchingIn {
+        snips: TalkSnippets,
+        config: crate::config::AudioInput,
+        punch: PunchIn,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::UndoGranularity;
+
+    // Draws some strokes with the given undo granularity, and then undoes them. Returns the
+    // number of strokes in the drawing before the first undo and after each of the others.
+    fn undo_lens(granularity: UndoGranularity) -> Vec<usize> {
+        let mut config = Config::default();
+        config.undo.spill_to_disk = false;
+        config.drawing.undo_granularity = granularity;
+        config.drawing.stroke_group_ms = 500;
+        let mut state = EditorState::new(config);
+        state.draw();
+
+        // The start and end times (in milliseconds) of some strokes, in two quick groups.
+        let strokes = [(0, 100), (150, 300), (2000, 2100), (2200, 2300)];
+        for &(start, end) in &strokes {
+            for &t in &[start, end] {
+                let p = Point::new(t as f64 / 1000.0, 0.5);
+                state.add_point_to_stroke(p, Time::from_micros(t * 1000));
+            }
+            state.finish_stroke(false);
+        }
+
+        let mut ret = Vec::new();
+        while let Some(seq) = state.new_stroke_seq() {
+            ret.push(seq.len());
+            state.undo();
+        }
+        ret
+    }
+
+    #[test]
+    fn undo_granularity() {
+        assert_eq!(undo_lens(UndoGranularity::Stroke), vec![4, 3, 2, 1, 0]);
+        assert_eq!(undo_lens(UndoGranularity::PenDownGroup), vec![4, 2, 0]);
+        // The strokes get merged into starting the drawing, so one undo goes back to before that.
+        assert_eq!(undo_lens(UndoGranularity::Snippet), vec![4]);
+    }
 }
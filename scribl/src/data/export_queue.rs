@@ -0,0 +1,205 @@
+use druid::im::Vector;
+use druid::Data;
+use std::sync::Arc;
+
+use scribl_curves::Time;
+
+use crate::cmd::ExportCmd;
+use crate::encode::EncodingStatus;
+
+/// Identifies an export job, so that the encoding thread can say which job it's reporting on.
+#[derive(Clone, Copy, Data, Debug, Eq, Hash, PartialEq)]
+pub struct ExportJobId(u64);
+
+#[derive(Clone, Data, Debug, PartialEq)]
+pub enum ExportJobStatus {
+    /// The job is waiting for the jobs in front of it to finish.
+    Queued,
+    /// The job is being encoded, and it's up to `frame` out of `out_of` frames.
+    Running {
+        frame: u64,
+        out_of: u64,
+    },
+    Finished,
+    Failed(String),
+}
+
+#[derive(Clone, Data)]
+pub struct ExportJob {
+    pub id: ExportJobId,
+    pub cmd: Arc<ExportCmd>,
+    /// The name of the export preset, if there was one.
+    pub preset: Option<String>,
+    pub status: ExportJobStatus,
+}
+
+/// The exports that they've asked for, in the order that they asked.
+///
+/// Exports run one at a time, in the background. Finished jobs stay in the queue (so that they
+/// can see how it went) until they get cleared.
+#[derive(Clone, Data, Default)]
+pub struct ExportQueue {
+    jobs: Vector<ExportJob>,
+    next_id: u64,
+}
+
+fn format_time(t: Time) -> String {
+    let secs = t.as_micros() / 1_000_000;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+impl ExportJob {
+    /// A short, human-readable description of what this job exports.
+    pub fn description(&self) -> String {
+        let name = self
+            .cmd
+            .filename
+            .file_name()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut details = Vec::new();
+        if let Some(preset) = &self.preset {
+            details.push(preset.clone());
+        }
+        if let Some((start, end)) = self.cmd.range {
+            details.push(format!("{}-{}", format_time(start), format_time(end)));
+        }
+        if details.is_empty() {
+            name
+        } else {
+            format!("{} ({})", name, details.join(", "))
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(
+            self.status,
+            ExportJobStatus::Finished | ExportJobStatus::Failed(_)
+        )
+    }
+}
+
+impl ExportQueue {
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn jobs(&self) -> &Vector<ExportJob> {
+        &self.jobs
+    }
+
+    /// Are there any exports that are running or waiting to run?
+    pub fn is_busy(&self) -> bool {
+        self.jobs.iter().any(|j| !j.is_done())
+    }
+
+    /// Adds a job to the end of the queue.
+    pub fn push(&mut self, cmd: ExportCmd, preset: Option<String>) -> ExportJobId {
+        let id = ExportJobId(self.next_id);
+        self.next_id += 1;
+        self.jobs.push_back(ExportJob {
+            id,
+            cmd: Arc::new(cmd),
+            preset,
+            status: ExportJobStatus::Queued,
+        });
+        id
+    }
+
+    /// If nothing is running, marks the first queued job as running and returns it.
+    pub fn start_next(&mut self) -> Option<ExportJob> {
+        if self
+            .jobs
+            .iter()
+            .any(|j| matches!(j.status, ExportJobStatus::Running { .. }))
+        {
+            return None;
+        }
+        let job = self
+            .jobs
+            .iter_mut()
+            .find(|j| j.status == ExportJobStatus::Queued)?;
+        job.status = ExportJobStatus::Running {
+            frame: 0,
+            out_of: 0,
+        };
+        Some(job.clone())
+    }
+
+    /// Updates the status of a job, according to a message from the encoder.
+    pub fn update(&mut self, id: ExportJobId, status: &EncodingStatus) {
+        if let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) {
+            job.status = match status {
+                EncodingStatus::Encoding { frame, out_of } => ExportJobStatus::Running {
+                    frame: *frame,
+                    out_of: *out_of,
+                },
+                EncodingStatus::Finished(..) => ExportJobStatus::Finished,
+                EncodingStatus::Error(e) => ExportJobStatus::Failed(e.clone()),
+            };
+        }
+    }
+
+    /// Removes a job from the queue, if it hasn't started yet.
+    pub fn cancel(&mut self, id: ExportJobId) {
+        self.jobs
+            .retain(|j| j.id != id || j.status != ExportJobStatus::Queued);
+    }
+
+    /// Removes all the jobs that have finished (successfully or not).
+    pub fn clear_finished(&mut self) {
+        self.jobs.retain(|j| !j.is_done());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::ScriblState;
+
+    fn export(name: &str) -> ExportCmd {
+        ExportCmd {
+            scribl: ScriblState::default(),
+            filename: name.into(),
+            config: Default::default(),
+            range: None,
+        }
+    }
+
+    #[test]
+    fn runs_in_order() {
+        let mut q = ExportQueue::default();
+        let a = q.push(export("a.mp4"), None);
+        let b = q.push(export("b.mp4"), Some("youtube".to_owned()));
+        assert!(q.is_busy());
+
+        assert_eq!(q.start_next().unwrap().id, a);
+        // Only one job runs at a time.
+        assert!(q.start_next().is_none());
+
+        q.update(
+            a,
+            &EncodingStatus::Finished("a.mp4".into(), Default::default()),
+        );
+        let job = q.start_next().unwrap();
+        assert_eq!(job.id, b);
+        assert_eq!(job.description(), "b.mp4 (youtube)");
+
+        q.update(b, &EncodingStatus::Error("oops".to_owned()));
+        assert!(!q.is_busy());
+        q.clear_finished();
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn cancel_only_queued() {
+        let mut q = ExportQueue::default();
+        let a = q.push(export("a.mp4"), None);
+        let b = q.push(export("b.mp4"), None);
+        q.start_next();
+        q.cancel(a);
+        q.cancel(b);
+        assert_eq!(q.jobs().len(), 1);
+        assert_eq!(q.jobs()[0].id, a);
+    }
+}
@@ -1,11 +1,16 @@
 pub mod editor;
-pub mod save;
-pub mod scribl;
+pub mod export_queue;
 pub mod settings;
 
 pub use editor::{
-    AsyncOpsStatus, AudioState, CurrentAction, EditorState, FinishedStatus, SnippetId,
+    AsyncOpsStatus, AudioState, CurrentAction, EditorState, FinishedStatus, InputLevels,
+    SnippetId, VadPreview, PUNCH_IN_POSTROLL,
+};
+pub use export_queue::{ExportJob, ExportJobStatus, ExportQueue};
+pub use scribl_core::{
+    Checkpoint, CheckpointSave, Markers, Pages, RowHints, SaveFileData, ScriblState,
+};
+pub use settings::{
+    DenoiseSetting, PenSize, RecordingSpeed, Settings, TimeDisplay, MAX_PLAYBACK_SPEED, MAX_ZOOM,
+    MIN_PLAYBACK_SPEED,
 };
-pub use save::SaveFileData;
-pub use scribl::ScriblState;
-pub use settings::{DenoiseSetting, PenSize, RecordingSpeed, Settings, MAX_ZOOM};
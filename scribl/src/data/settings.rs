@@ -1,11 +1,17 @@
-use druid::{Data, Lens};
-use scribl_curves::{Effect, Effects, FadeEffect, StrokeStyle, TimeDiff};
+use druid::im::Vector;
+use druid::{Color, Data, Lens};
+use scribl_curves::{Brush, Effect, Effects, FadeEffect, NamedEffect, StrokeStyle, TimeDiff};
+use serde::Deserialize;
 
-use crate::config::Config;
+use crate::config::{AudioSource, Config, PenPreset, RowLayout, ScrollMode, UiPalette};
 
 /// How far are they allowed to zoom in?
 pub const MAX_ZOOM: f64 = 8.0;
 
+/// The slowest and fastest allowed playback speeds.
+pub const MIN_PLAYBACK_SPEED: f64 = 0.5;
+pub const MAX_PLAYBACK_SPEED: f64 = 2.0;
+
 /// This piece of data contains the various settings that affect recording.
 ///
 /// Many of these fields have a button in the UI for changing that setting.
@@ -23,13 +29,75 @@ pub struct Settings {
     /// When true, the "fade out" toggle button is pressed down.
     pub fade_enabled: bool,
 
+    /// The names of the other effects (from `scribl_curves::registry`) that new strokes get.
+    pub pen_effects: Vector<String>,
+
     /// The current pen size, as selected in the UI.
     pub pen_size: PenSize,
 
+    /// The brush that new strokes get drawn with.
+    pub brush: Brush,
+
     /// The current denoise setting, as selected in the UI.
     pub denoise_setting: DenoiseSetting,
 
+    /// When true, we play back the microphone input while recording audio.
+    pub monitor_input: bool,
+
+    /// Whether we're recording from the microphone or from the system audio.
+    pub audio_source: AudioSource,
+
+    /// How the current time is shown in the status bar.
+    pub time_display: TimeDisplay,
+
+    /// How fast the animation plays back. The audio gets sped up or slowed down without changing
+    /// its pitch.
+    pub playback_speed: f64,
+
+    /// How the timeline scrolls to keep the cursor visible.
+    pub scroll_mode: ScrollMode,
+
+    /// How the snippets are arranged in the timeline.
+    pub row_layout: RowLayout,
+
+    /// The accent colors of the UI.
+    pub ui_palette: UiPalette,
+
+    /// When true, audio snippets in the timeline show their spectrograms instead of their
+    /// waveforms.
+    pub spectrograms: bool,
+
+    /// When true, the drawing snippets are hidden from the timeline.
+    pub drawings_collapsed: bool,
+
+    /// When true, the audio snippets are hidden from the timeline.
+    pub audio_collapsed: bool,
+
     pub palette: crate::widgets::PaletteData,
+
+    /// When true, clicking on the drawing picks up the color under the mouse instead of panning.
+    pub eyedropper: bool,
+
+    /// The name of the export preset to use. If this is `None`, we use the export settings from
+    /// the config file.
+    pub export_preset: Option<String>,
+
+    /// How long (in seconds) it takes to draw an imported SVG file. If this is zero, the whole
+    /// drawing appears at once.
+    pub svg_import_secs: f64,
+
+    /// When true, we record the screen (as an underlay) while recording audio.
+    pub capture_screen: bool,
+
+    /// When true, we record the webcam while recording audio.
+    pub capture_webcam: bool,
+
+    /// When true, we show paint timings and repainted regions on top of the editor.
+    pub debug_overlay: bool,
+
+    /// When true, the drawing pane ignores panning and zooming gestures while recording, so that
+    /// a stray touch doesn't move the paper out from under the pen.
+    pub lock_viewport: bool,
 }
 
 impl Settings {
@@ -44,11 +112,30 @@ impl Settings {
 
         Settings {
             denoise_setting,
+            monitor_input: config.audio_input.monitor,
+            audio_source: config.audio_input.source,
+            time_display: TimeDisplay::Clock,
+            scroll_mode: config.timeline.scroll_mode,
+            row_layout: config.timeline.rows,
+            ui_palette: config.ui_palette,
+            playback_speed: 1.0,
+            spectrograms: false,
+            drawings_collapsed: false,
+            audio_collapsed: false,
             recording_speed: RecordingSpeed::Slow,
             zoom: 1.0,
             fade_enabled: false,
+            pen_effects: Vector::new(),
             pen_size: PenSize::Small,
+            brush: Brush::Round,
             palette: crate::widgets::PaletteData::default(),
+            eyedropper: false,
+            export_preset: None,
+            svg_import_secs: 0.0,
+            capture_screen: false,
+            capture_webcam: false,
+            debug_overlay: false,
+            lock_viewport: config.drawing.lock_viewport,
         }
     }
 
@@ -60,14 +147,67 @@ impl Settings {
                 fade: TimeDiff::from_micros(250_000),
             }));
         }
+        for name in &self.pen_effects {
+            ret.add(Effect::Named(NamedEffect::new(name)));
+        }
         ret
     }
 
+    /// Turns one of the pen effects on or off.
+    pub fn toggle_pen_effect(&mut self, name: &str) {
+        if let Some(idx) = self.pen_effects.iter().position(|n| n == name) {
+            self.pen_effects.remove(idx);
+        } else {
+            self.pen_effects.push_back(name.to_owned());
+        }
+    }
+
+    /// Changes the pen settings to the ones in a preset.
+    pub fn apply_pen_preset(&mut self, preset: &PenPreset) {
+        if let Some(color) = &preset.color {
+            match Color::from_hex_str(color) {
+                Ok(c) => self.palette.select_color(c),
+                Err(e) => log::warn!("invalid color \"{}\" in pen preset: {}", color, e),
+            }
+        }
+        if let Some(size) = preset.size {
+            self.pen_size = size;
+        }
+        if let Some(brush) = preset.brush {
+            self.brush = brush;
+        }
+        if let Some(fade) = preset.fade {
+            self.fade_enabled = fade;
+        }
+        if let Some(effects) = &preset.effects {
+            self.pen_effects = effects.iter().cloned().collect();
+        }
+    }
+
+    /// Are the current pen settings the same as the ones in this preset?
+    pub fn matches_pen_preset(&self, preset: &PenPreset) -> bool {
+        let color_matches = preset.color.as_ref().map_or(true, |c| {
+            Color::from_hex_str(c).map_or(false, |c| {
+                c.as_rgba_u32() == self.palette.selected_color().as_rgba_u32()
+            })
+        });
+        let effects_matches = preset.effects.as_ref().map_or(true, |effects| {
+            effects.len() == self.pen_effects.len()
+                && effects.iter().all(|e| self.pen_effects.contains(e))
+        });
+        color_matches
+            && effects_matches
+            && preset.size.map_or(true, |s| s == self.pen_size)
+            && preset.brush.map_or(true, |b| b == self.brush)
+            && preset.fade.map_or(true, |f| f == self.fade_enabled)
+    }
+
     pub fn cur_style(&self) -> StrokeStyle {
         StrokeStyle {
             color: self.palette.selected_color().clone(),
             thickness: self.pen_size.size_fraction(),
             effects: self.selected_effects(),
+            brush: self.brush,
         }
     }
 
@@ -87,6 +227,12 @@ impl Settings {
         self.zoom = (self.zoom / 1.25).max(1.0);
     }
 
+    /// Sets the zoom level, clamping it to the allowed range.
+    pub fn set_zoom(&mut self, zoom: f64) {
+        self.zoom = zoom.max(1.0).min(MAX_ZOOM);
+    }
+
+    /// Resets the zoom level so that the whole drawing fits in the drawing pane.
     pub fn zoom_reset(&mut self) {
         self.zoom = 1.0;
     }
@@ -111,7 +257,8 @@ impl RecordingSpeed {
     }
 }
 
-#[derive(Clone, Copy, Data, PartialEq, Eq)]
+#[derive(Clone, Copy, Data, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
 pub enum PenSize {
     Small,
     Medium,
@@ -135,3 +282,55 @@ pub enum DenoiseSetting {
     DenoiseOn,
     Vad,
 }
+
+/// The ways that we can show the current time.
+#[derive(Clone, Copy, Data, PartialEq, Eq)]
+pub enum TimeDisplay {
+    /// Minutes, seconds, and milliseconds.
+    Clock,
+    /// The frame number, at the export frame rate.
+    Frames,
+}
+
+impl TimeDisplay {
+    pub fn toggled(self) -> TimeDisplay {
+        match self {
+            TimeDisplay::Clock => TimeDisplay::Frames,
+            TimeDisplay::Frames => TimeDisplay::Clock,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pen_presets() {
+        let preset: PenPreset = toml::from_str(
+            r##"
+            name = "Red marker"
+            color = "#bf5700"
+            brush = "marker"
+            effects = ["wiggle"]
+            "##,
+        )
+        .unwrap();
+        let mut settings = Settings::new(&Config::default());
+        settings.pen_size = PenSize::Big;
+        assert!(!settings.matches_pen_preset(&preset));
+
+        settings.apply_pen_preset(&preset);
+        assert!(settings.matches_pen_preset(&preset));
+        assert_eq!(settings.brush, Brush::Marker);
+        assert_eq!(
+            settings.pen_effects,
+            Vector::from(vec!["wiggle".to_owned()])
+        );
+        // The preset doesn't mention the size, so it stays the same.
+        assert_eq!(settings.pen_size, PenSize::Big);
+
+        settings.toggle_pen_effect("wiggle");
+        assert!(!settings.matches_pen_preset(&preset));
+    }
+}
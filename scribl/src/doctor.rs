@@ -0,0 +1,219 @@
+//! A headless check of the audio and video setup (`scribl doctor`).
+//!
+//! Problems with gstreamer (like missing plugins, or audio devices that can't be opened) otherwise
+//! only show up when someone tries to record or export. This runs everything that we know how to
+//! check, and collects the results into a report that can be attached to a bug report.
+
+use anyhow::{anyhow, Result};
+use gstreamer as gst;
+use gstreamer::prelude::*;
+
+use crate::plugins;
+
+/// Pipelines that end by themselves get this long to finish.
+const FINITE_TIMEOUT_SECS: u64 = 10;
+/// Pipelines that record from a device never end by themselves, so we just check that they run
+/// for this long without any errors.
+const LIVE_TIMEOUT_SECS: u64 = 1;
+
+/// The outcome of one of the checks.
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    /// What we found out. If the check failed, this says why.
+    pub details: String,
+}
+
+impl Check {
+    fn new(name: &str, result: Result<String>) -> Check {
+        let (ok, details) = match result {
+            Ok(details) => (true, details),
+            Err(e) => (false, format!("{:#}", e)),
+        };
+        Check {
+            name: name.to_owned(),
+            ok,
+            details,
+        }
+    }
+}
+
+/// The outcomes of all the checks.
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// Did all the checks pass?
+    pub fn is_ok(&self) -> bool {
+        self.checks.iter().all(|c| c.ok)
+    }
+
+    /// Describes the results, one check per line.
+    pub fn to_text(&self) -> String {
+        let mut ret = format!(
+            "scribl version: {}\nOS: {} ({})\n\n",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+        );
+        for c in &self.checks {
+            let status = if c.ok { "ok" } else { "FAILED" };
+            ret.push_str(&format!("[{}] {}: {}\n", status, c.name, c.details));
+        }
+        let failed = self.checks.iter().filter(|c| !c.ok).count();
+        if failed == 0 {
+            ret.push_str("\nEverything looks fine.\n");
+        } else {
+            ret.push_str(&format!(
+                "\n{} of {} checks failed.\n",
+                failed,
+                self.checks.len()
+            ));
+        }
+        ret
+    }
+}
+
+/// Runs a pipeline (in `gst-launch` syntax) until it finishes, or for at most `timeout_secs`.
+/// Pipelines that don't finish by themselves pass as long as they don't report any errors before
+/// the timeout.
+fn run_pipeline(description: &str, timeout_secs: u64) -> Result<String> {
+    let pipeline = gst::parse_launch(description)?;
+    let bus = pipeline
+        .bus()
+        .ok_or_else(|| anyhow!("couldn't get pipeline bus"))?;
+
+    // If the state change fails, the bus usually has a more useful description of what went
+    // wrong, so keep looking.
+    let mut result = pipeline
+        .set_state(gst::State::Playing)
+        .map(|_| ())
+        .map_err(anyhow::Error::from);
+    for msg in bus.iter_timed(gst::ClockTime::from_seconds(timeout_secs)) {
+        use gst::MessageView::*;
+
+        match msg.view() {
+            Eos(..) => break,
+            Error(err) => {
+                let element = msg
+                    .src()
+                    .map(|src| src.path_string().to_string())
+                    .unwrap_or_else(|| "unknown element".to_owned());
+                let mut desc = format!("{}: {}", element, err.error());
+                if let Some(debug) = err.debug() {
+                    desc.push_str(&format!(" ({})", debug));
+                }
+                result = Err(anyhow!(desc));
+                break;
+            }
+            _ => {}
+        }
+    }
+    let _ = pipeline.set_state(gst::State::Null);
+    result.map(|_| "works".to_owned())
+}
+
+/// Lists the audio devices of one class (like "Audio/Source"), failing if there aren't any.
+fn list_devices(class: &str) -> Result<String> {
+    let monitor = gst::DeviceMonitor::new();
+    monitor.add_filter(Some(class), None);
+    monitor
+        .start()
+        .map_err(|e| anyhow!("couldn't look for devices: {}", e))?;
+    let names: Vec<_> = monitor
+        .devices()
+        .into_iter()
+        .map(|d| d.display_name().to_string())
+        .collect();
+    monitor.stop();
+    if names.is_empty() {
+        Err(anyhow!("no devices found"))
+    } else {
+        Ok(names.join(", "))
+    }
+}
+
+/// Runs all the checks. This takes a few seconds, and it makes a (silent) attempt to play and
+/// record audio.
+pub fn run() -> Report {
+    let mut checks = Vec::new();
+    let init = gst::init()
+        .map(|_| gst::version_string().to_string())
+        .map_err(anyhow::Error::from);
+    let initialized = init.is_ok();
+    checks.push(Check::new("gstreamer", init));
+    if !initialized {
+        // Nothing else is going to work.
+        return Report { checks };
+    }
+
+    for e in plugins::required_elements() {
+        let result = if gst::ElementFactory::find(e.name).is_some() {
+            Ok("found".to_owned())
+        } else {
+            Err(anyhow!(
+                "missing (from {}), needed for {}",
+                e.collection,
+                e.feature.description()
+            ))
+        };
+        checks.push(Check::new(&format!("element {}", e.name), result));
+    }
+
+    checks.push(Check::new("audio inputs", list_devices("Audio/Source")));
+    checks.push(Check::new("audio outputs", list_devices("Audio/Sink")));
+    checks.push(Check::new(
+        "recording audio",
+        run_pipeline("autoaudiosrc ! audioconvert ! fakesink", LIVE_TIMEOUT_SECS),
+    ));
+    checks.push(Check::new(
+        "playing audio",
+        run_pipeline(
+            "audiotestsrc volume=0 num-buffers=20 ! audioconvert ! audioresample ! autoaudiosink",
+            FINITE_TIMEOUT_SECS,
+        ),
+    ));
+    checks.push(Check::new(
+        "encoding video",
+        run_pipeline(
+            "videotestsrc num-buffers=10 ! video/x-raw,width=320,height=240 ! videoconvert \
+             ! x264enc ! mp4mux ! fakesink",
+            FINITE_TIMEOUT_SECS,
+        ),
+    ));
+    checks.push(Check::new(
+        "encoding audio",
+        run_pipeline(
+            "audiotestsrc num-buffers=10 ! audioconvert ! lamemp3enc ! fakesink",
+            FINITE_TIMEOUT_SECS,
+        ),
+    ));
+    Report { checks }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_text() {
+        let report = Report {
+            checks: vec![
+                Check::new("gstreamer", Ok("1.20.3".to_owned())),
+                Check::new("element x264enc", Err(anyhow!("missing"))),
+            ],
+        };
+        assert!(!report.is_ok());
+        let text = report.to_text();
+        assert!(text.contains("[ok] gstreamer: 1.20.3\n"));
+        assert!(text.contains("[FAILED] element x264enc: missing\n"));
+        assert!(text.ends_with("1 of 2 checks failed.\n"));
+
+        let report = Report {
+            checks: vec![Check::new("gstreamer", Ok("1.20.3".to_owned()))],
+        };
+        assert!(report.is_ok());
+        assert!(report.to_text().ends_with("Everything looks fine.\n"));
+    }
+}
@@ -0,0 +1,82 @@
+//! System-wide hotkeys, which work even when some other window has the focus.
+//!
+//! These are for screen-capture workflows, where the window being recorded has the focus but we
+//! still want to start and stop recording audio. Like MIDI controllers, the hotkeys send the same
+//! requests that the remote control makes (see [`crate::remote`]).
+//!
+//! The platform-specific parts (X11, Windows and macOS all do this differently) are handled by
+//! `livesplit_hotkey`.
+
+use anyhow::{anyhow, Result};
+use druid::{ExtEventSink, Target};
+use livesplit_hotkey::{Hook, Hotkey, KeyCode, Modifiers};
+use std::str::FromStr;
+
+use crate::cmd;
+use crate::config::GlobalHotkeys as Config;
+use crate::remote::{Method, RemoteRequest};
+
+/// The registered hotkeys. They get unregistered when this is dropped.
+pub struct GlobalHotkeys {
+    _hook: Hook,
+}
+
+/// Parses a hotkey like "Ctrl+Shift+F9". The key is named the way browsers name keys (like "F9",
+/// "KeyR" or "Digit1"), and the modifiers can be "Ctrl", "Shift", "Alt" or "Meta".
+fn parse_hotkey(s: &str) -> Result<Hotkey> {
+    let mut parts: Vec<&str> = s.split('+').map(|p| p.trim()).collect();
+    let key = parts.pop().unwrap_or_default();
+    let key_code = KeyCode::from_str(key).map_err(|_| anyhow!("unknown key \"{}\"", key))?;
+    let mut modifiers = Modifiers::empty();
+    for m in parts {
+        modifiers |= match m.to_lowercase().as_str() {
+            "ctrl" | "control" => Modifiers::CONTROL,
+            "shift" => Modifiers::SHIFT,
+            "alt" => Modifiers::ALT,
+            "meta" | "super" | "cmd" => Modifiers::META,
+            _ => return Err(anyhow!("unknown modifier \"{}\"", m)),
+        };
+    }
+    Ok(key_code.with_modifiers(modifiers))
+}
+
+impl GlobalHotkeys {
+    /// Registers the hotkeys in the config.
+    pub fn register(config: &Config, ext_cmd: ExtEventSink) -> Result<GlobalHotkeys> {
+        let hook = Hook::new().map_err(|e| anyhow!("{}", e))?;
+        for (key, method) in &[(&config.talk, Method::Talk), (&config.stop, Method::Stop)] {
+            let hotkey = parse_hotkey(key)?;
+            let ext_cmd = ext_cmd.clone();
+            let method = method.clone();
+            hook.register(hotkey, move || {
+                let (req, _) = RemoteRequest::new(method.clone());
+                let _ = ext_cmd.submit_command(cmd::REMOTE_REQUEST, Box::new(req), Target::Global);
+            })
+            .map_err(|e| anyhow!("failed to register {}: {}", key, e))?;
+        }
+        Ok(GlobalHotkeys { _hook: hook })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        assert_eq!(
+            parse_hotkey("Ctrl+Shift+F9").unwrap(),
+            KeyCode::F9.with_modifiers(Modifiers::CONTROL | Modifiers::SHIFT)
+        );
+        assert_eq!(
+            parse_hotkey("alt + KeyR").unwrap(),
+            KeyCode::KeyR.with_modifiers(Modifiers::ALT)
+        );
+        assert_eq!(
+            parse_hotkey("F10").unwrap(),
+            KeyCode::F10.with_modifiers(Modifiers::empty())
+        );
+        assert!(parse_hotkey("Hyper+F9").is_err());
+        assert!(parse_hotkey("Ctrl+Banana").is_err());
+    }
+}
@@ -1,20 +1,34 @@
 use clap::{App, Arg};
 use crossbeam_channel::unbounded;
 use druid::theme;
-use druid::{AppLauncher, Color, Key};
+use druid::{AppLauncher, Color, Key, Target};
 use std::io::Write;
 
+use progress::{Event, ProgressFormat};
+use scribl_core::{encode, partial_exports};
+
 mod app_delegate;
 mod app_state;
 mod audio;
 mod autosave;
+mod batch;
+mod benchmark;
+mod cache;
 mod cmd;
 mod config;
+mod crash;
 mod cursor;
 mod data;
-mod encode;
+mod doctor;
+mod global_hotkeys;
 mod menus;
+mod midi;
+mod plugins;
+mod progress;
+mod remote;
+mod script;
 mod snippet_layout;
+mod template;
 mod undo;
 mod widgets;
 
@@ -52,11 +66,6 @@ pub const UI_LIGHT_STEEL_BLUE: Color = Color::rgb8(156, 173, 183);
 fn main() {
     env_logger::init();
 
-    if let Err(e) = gstreamer::init() {
-        log::error!("failed to init gstreamer: {}", e);
-        return;
-    }
-
     let matches = App::new("scribl")
         .version(format!("{}.{}.{}", MAJOR, MINOR, PATCH).as_str())
         .author("Joe Neeman <joeneeman@gmail.com>")
@@ -71,9 +80,200 @@ fn main() {
                 .long("export-to")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("export-preset")
+                .help("The export preset to use (for example, \"youtube\")")
+                .long("export-preset")
+                .takes_value(true)
+                .requires("export-to"),
+        )
+        .arg(
+            Arg::with_name("from")
+                .help("Only export the part of the animation after this time (like 1:23.5)")
+                .long("from")
+                .takes_value(true)
+                .requires("export-to"),
+        )
+        .arg(
+            Arg::with_name("to")
+                .help("Only export the part of the animation before this time (like 1:23.5)")
+                .long("to")
+                .takes_value(true)
+                .requires("export-to"),
+        )
+        .arg(
+            Arg::with_name("progress-format")
+                .help("How to report export progress: \"text\" on stderr or \"json\" on stdout")
+                .long("progress-format")
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .requires("export-to"),
+        )
+        .subcommand(
+            App::new("export-batch")
+                .about("Export many animations as videos")
+                .arg(
+                    Arg::with_name("FILES")
+                        .help("The files to export (glob patterns like \"*.scb\" are allowed)")
+                        .takes_value(true)
+                        .multiple_values(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("out-dir")
+                        .help("The directory to put the videos in")
+                        .long("out-dir")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("jobs")
+                        .help("How many files to export at the same time")
+                        .long("jobs")
+                        .short('j')
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("export-preset")
+                        .help("The export preset to use (for example, \"youtube\")")
+                        .long("export-preset")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("run-script")
+                .about("Edit an animation with a Rhai script")
+                .arg(
+                    Arg::with_name("SCRIPT")
+                        .help("The script to run")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("The animation to edit")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .help("Where to save the edited animation (by default, it replaces FILE)")
+                        .long("output")
+                        .short('o')
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("export-preset")
+                        .help("The export preset to use for any exports in the script")
+                        .long("export-preset")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("render-frame")
+                .about("Render a single frame of an animation as a PNG image")
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("The animation to render")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("at")
+                        .help("The time of the frame (like 1:23.5, 83.5s or 120@30)")
+                        .long("at")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .help("The image file to write")
+                        .long("out")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("height")
+                        .help("The height of the image, in pixels (by default, the export height)")
+                        .long("height")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("renderer")
+                        .help("How to draw the frame (piet, or tiny-skia if it was enabled)")
+                        .long("renderer")
+                        .takes_value(true)
+                        .default_value("piet"),
+                ),
+        )
+        .subcommand(
+            App::new("benchmark")
+                .about("Render every frame of an animation, and print how long the frames took")
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("The animation to render")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("height")
+                        .help("The height of the frames, in pixels (by default, the export height)")
+                        .long("height")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("fps")
+                        .help("The number of frames per second (by default, the export frame rate)")
+                        .long("fps")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("doctor")
+                .about("Check that recording, playback and export will work, and print a report")
+                .arg(
+                    Arg::with_name("out")
+                        .help("Also write the report to this file")
+                        .long("out")
+                        .takes_value(true),
+                ),
+        )
         .get_matches();
 
+    // The doctor initializes gstreamer itself, because it wants to report on any failures.
+    if let Some(matches) = matches.subcommand_matches("doctor") {
+        doctor(matches);
+        return;
+    }
+
+    if let Err(e) = gstreamer::init() {
+        log::error!("failed to init gstreamer: {}", e);
+        return;
+    }
+
     let config = crate::config::load_config();
+    scribl_core::audio::set_map_dir(cache::subdir(&config.cache, cache::CacheKind::Audio));
+
+    if let Some(matches) = matches.subcommand_matches("export-batch") {
+        export_batch(matches, &config);
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("run-script") {
+        run_script(matches, &config);
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("render-frame") {
+        render_frame(matches, &config);
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("benchmark") {
+        benchmark(matches, &config);
+        return;
+    }
+    let remote_config = config.remote.clone();
+    let midi_config = config.midi.clone();
+    let global_hotkeys = config.global_hotkeys.enabled;
 
     let initial_editor = if let Some(path) = matches.value_of("FILE") {
         match crate::SaveFileData::load_from_path(path) {
@@ -92,10 +292,41 @@ fn main() {
     };
 
     if let Some(output_path) = matches.value_of("export-to") {
-        encode(initial_editor, output_path);
+        let preset = matches.value_of("export-preset");
+        // The unwrap is ok because clap checks the possible values.
+        let format = matches
+            .value_of("progress-format")
+            .unwrap_or("text")
+            .parse()
+            .unwrap();
+        let time_arg = |name| {
+            matches
+                .value_of(name)
+                .map(|s| match s.parse::<scribl_curves::Time>() {
+                    Ok(time) => time,
+                    Err(e) => fail(format!("--{}: {}", name, e)),
+                })
+        };
+        let range = match (time_arg("from"), time_arg("to")) {
+            (None, None) => None,
+            (from, to) => {
+                let from = from.unwrap_or(scribl_curves::Time::ZERO);
+                let to = to.unwrap_or_else(|| initial_editor.scribl.end_time());
+                if to <= from {
+                    fail(format!("nothing to export between {} and {}", from, to));
+                }
+                Some((from, to))
+            }
+        };
+        encode(initial_editor, output_path, preset, format, range);
         return;
     }
 
+    crash::install_panic_hook();
+
+    let cache_config = initial_editor.config.cache.clone();
+    std::thread::spawn(move || cache::enforce_limit(&cache_config));
+
     let mut initial_state = AppState::default();
     let editor_window_desc = initial_state.add_editor(initial_editor);
 
@@ -114,32 +345,309 @@ fn main() {
         scribl_widget::configure_env(e);
     });
 
+    let missing = plugins::missing_elements();
+    if !missing.is_empty() {
+        let msg = plugins::describe_missing(&missing);
+        log::error!("{}", msg);
+        let _ = launcher.get_external_handle().submit_command(
+            cmd::SHOW_MISSING_PLUGINS,
+            msg,
+            Target::Global,
+        );
+    }
+
+    let orphans = partial_exports::orphans();
+    if !orphans.is_empty() {
+        let _ = launcher.get_external_handle().submit_command(
+            cmd::SHOW_PARTIAL_EXPORTS,
+            orphans,
+            Target::Global,
+        );
+    }
+
+    let recovered = crash::recovered_files();
+    if !recovered.is_empty() {
+        let _ = launcher.get_external_handle().submit_command(
+            cmd::SHOW_CRASH_RECOVERY,
+            recovered,
+            Target::Global,
+        );
+    }
+
+    if remote_config.enabled {
+        if let Err(e) = remote::spawn(&remote_config, launcher.get_external_handle()) {
+            log::error!("failed to start the remote control server: {}", e);
+        }
+    }
+    if global_hotkeys {
+        let _ = launcher.get_external_handle().submit_command(
+            cmd::SET_GLOBAL_HOTKEYS,
+            true,
+            Target::Global,
+        );
+    }
+    // The MIDI listener stops when the connection is dropped, so keep it until the app exits.
+    let _midi_connection = if midi_config.enabled {
+        midi::connect(&midi_config, launcher.get_external_handle())
+            .map_err(|e| log::error!("failed to connect to the MIDI controller: {}", e))
+            .ok()
+    } else {
+        None
+    };
+
     launcher
         .delegate(app_delegate::Delegate::default())
         .launch(initial_state)
         .expect("failed to launch");
 }
 
-fn encode(data: EditorState, path: &str) {
-    let config = crate::config::load_config();
+fn export_batch(matches: &clap::ArgMatches, config: &config::Config) {
+    let missing = plugins::missing_for(plugins::Feature::Export);
+    if !missing.is_empty() {
+        eprintln!("{}", plugins::describe_missing(&missing));
+        std::process::exit(1);
+    }
+
+    let jobs = match matches.value_of("jobs").map(str::parse) {
+        None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            eprintln!("The number of jobs must be a positive integer");
+            std::process::exit(1);
+        }
+    };
+    let export_config = match config.export_settings(matches.value_of("export-preset")) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let opts = batch::BatchOptions {
+        patterns: matches
+            .values_of("FILES")
+            .into_iter()
+            .flatten()
+            .map(|s| s.to_owned())
+            .collect(),
+        // The unwrap is ok because clap checks that required arguments are present.
+        out_dir: matches.value_of("out-dir").unwrap().into(),
+        jobs,
+        config: export_config,
+    };
+    if let Err(e) = batch::export_batch(opts) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Prints an error from one of the subcommands, and exits.
+fn fail(error: String) -> ! {
+    eprintln!("{}", error);
+    std::process::exit(1);
+}
+
+fn run_script(matches: &clap::ArgMatches, config: &config::Config) {
+    // The unwraps are ok because clap checks that required arguments are present.
+    let script_path = matches.value_of("SCRIPT").unwrap();
+    let path = matches.value_of("FILE").unwrap();
+    let source = match std::fs::read_to_string(script_path) {
+        Ok(s) => s,
+        Err(e) => fail(format!("failed to read {}: {}", script_path, e)),
+    };
+    let save_file = match SaveFileData::load_from_path(path) {
+        Ok(s) => s,
+        Err(e) => fail(format!("failed to open {}: {}", path, e)),
+    };
+
+    let scribl = ScriblState::from_save_file(&save_file);
+    let output = match script::run(&source, scribl, scribl_curves::Time::ZERO) {
+        Ok(o) => o,
+        Err(e) => fail(e.to_string()),
+    };
+    for line in &output.log {
+        println!("{}", line);
+    }
+
+    let out_path = matches.value_of("output").unwrap_or(path);
+    let save_file = SaveFileData::from_scribl(&output.scribl);
+    if let Err(e) = save_file.save_to_path(out_path) {
+        fail(format!("failed to save {}: {}", out_path, e));
+    }
+    let preset = matches.value_of("export-preset");
+    for export in &output.exports {
+        let data = EditorState::from_save_file(save_file.clone(), config.clone());
+        let export = export.to_string_lossy();
+        encode(data, &export, preset, ProgressFormat::Text, None);
+    }
+}
+
+fn render_frame(matches: &clap::ArgMatches, config: &config::Config) {
+    // The unwraps are ok because clap checks that required arguments are present.
+    let path = matches.value_of("FILE").unwrap();
+    let out = matches.value_of("out").unwrap();
+    let time: scribl_curves::Time = match matches.value_of("at").unwrap().parse() {
+        Ok(time) => time,
+        Err(e) => fail(e.to_string()),
+    };
+    let height = match matches.value_of("height").map(str::parse) {
+        None => config.export.height,
+        Some(Ok(h)) if h > 0 => h,
+        Some(_) => fail("the height must be a positive integer".to_owned()),
+    };
+    let save_file = match SaveFileData::load_from_path(path) {
+        Ok(s) => s,
+        Err(e) => fail(format!("failed to open {}: {}", path, e)),
+    };
+
+    let mut renderer = match scribl_core::frame::renderer(matches.value_of("renderer").unwrap()) {
+        Ok(r) => r,
+        Err(e) => fail(e.to_string()),
+    };
+
+    let scribl = ScriblState::from_save_file(&save_file);
+    let result = scribl_core::frame::render_with(renderer.as_mut(), &scribl, time, height)
+        .and_then(|i| i.save_png(out));
+    if let Err(e) = result {
+        fail(format!("failed to render {}: {}", out, e));
+    }
+}
+
+fn benchmark(matches: &clap::ArgMatches, config: &config::Config) {
+    // The unwrap is ok because clap checks that required arguments are present.
+    let path = matches.value_of("FILE").unwrap();
+    let height = match matches.value_of("height").map(str::parse) {
+        None => config.export.height,
+        Some(Ok(h)) if h > 0 => h,
+        Some(_) => fail("the height must be a positive integer".to_owned()),
+    };
+    let save_file = match SaveFileData::load_from_path(path) {
+        Ok(s) => s,
+        Err(e) => fail(format!("failed to open {}: {}", path, e)),
+    };
+    let scribl = ScriblState::from_save_file(&save_file);
+    let fps = match matches.value_of("fps").map(str::parse) {
+        None => scribl.fps.unwrap_or(config.export.fps),
+        Some(Ok(fps)) if fps > 0.0 => fps,
+        Some(_) => fail("the frame rate must be a positive number".to_owned()),
+    };
+
+    let result = benchmark::run(&scribl, height, fps, |done, total| {
+        eprint!("\rRendered frame {}/{}", done, total);
+    });
+    eprintln!();
+    match result {
+        Ok(Some(stats)) => println!("{}", benchmark::describe(&stats)),
+        Ok(None) => fail(format!("{} has no frames", path)),
+        Err(e) => fail(format!("failed to render {}: {}", path, e)),
+    }
+}
+
+fn doctor(matches: &clap::ArgMatches) {
+    let report = doctor::run();
+    let text = report.to_text();
+    print!("{}", text);
+    if let Some(out) = matches.value_of("out") {
+        if let Err(e) = std::fs::write(out, &text) {
+            fail(format!("failed to write {}: {}", out, e));
+        }
+    }
+    if !report.is_ok() {
+        std::process::exit(1);
+    }
+}
+
+fn encode(
+    data: EditorState,
+    path: &str,
+    preset: Option<&str>,
+    format: ProgressFormat,
+    range: Option<(scribl_curves::Time, scribl_curves::Time)>,
+) {
+    let input = data
+        .save_path
+        .as_ref()
+        .map(|p| p.to_string_lossy().into_owned());
+    let input = input.as_deref();
+    let fail = |error: String| match format {
+        ProgressFormat::Text => eprintln!("{}", error),
+        ProgressFormat::Json => Event::Failed { input, error }.print(),
+    };
+
+    let missing = plugins::missing_for(plugins::Feature::Export);
+    if !missing.is_empty() {
+        fail(plugins::describe_missing(&missing));
+        return;
+    }
+
+    let export_config = match crate::config::load_config().export_settings(preset) {
+        Ok(c) => c,
+        Err(e) => {
+            fail(e.to_string());
+            return;
+        }
+    };
     let export = cmd::ExportCmd {
         scribl: data.scribl,
         filename: path.into(),
-        config: config.export,
+        config: export_config,
+        range,
     };
     let (tx, rx) = unbounded();
     std::thread::spawn(move || crate::encode::encode_blocking(export, tx));
 
+    if format == ProgressFormat::Json {
+        Event::Started {
+            input,
+            output: path,
+        }
+        .print();
+    }
     let mut term = console::Term::stderr();
+    let mut rate = progress::Rate::start();
     for msg in rx.iter() {
         use crate::encode::EncodingStatus;
-        match msg {
-            EncodingStatus::Encoding { frame, out_of } => {
+        match (msg, format) {
+            (EncodingStatus::Encoding { frame, out_of }, ProgressFormat::Text) => {
                 let _ = term.clear_line();
-                let _ = write!(term, "Encoding frame {} of {}", frame, out_of);
+                let _ = write!(
+                    term,
+                    "Encoding frame {} of {} ({:.1} fps)",
+                    frame,
+                    out_of,
+                    rate.fps(frame)
+                );
+                if let Some(eta) = rate.eta(frame, out_of) {
+                    let _ = write!(term, " ({:.0} seconds left)", eta);
+                }
+            }
+            (EncodingStatus::Encoding { frame, out_of }, ProgressFormat::Json) => {
+                if rate.percent_changed(frame, out_of) {
+                    Event::Progress {
+                        input,
+                        frame,
+                        out_of,
+                        fps: rate.fps(frame),
+                        eta: rate.eta(frame, out_of),
+                    }
+                    .print();
+                }
+            }
+            (EncodingStatus::Error(s), ProgressFormat::Text) => {
+                eprintln!("\nEncoding error: {}", s)
+            }
+            (EncodingStatus::Error(error), ProgressFormat::Json) => {
+                Event::Failed { input, error }.print()
+            }
+            (EncodingStatus::Finished(_, summary), ProgressFormat::Text) => {
+                eprintln!("\nFinished! Encoded {}", summary)
+            }
+            (EncodingStatus::Finished(_, summary), ProgressFormat::Json) => Event::Finished {
+                input,
+                output: path,
+                summary: Some((&summary).into()),
             }
-            EncodingStatus::Error(s) => eprintln!("\nEncoding error: {}", s),
-            EncodingStatus::Finished(_) => eprintln!("\nFinished!"),
+            .print(),
         }
     }
 }
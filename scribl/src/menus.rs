@@ -2,15 +2,31 @@ use druid::commands;
 use druid::menu::MenuEventCtx;
 use druid::platform_menus;
 use druid::{
-    Env, FileDialogOptions, FileSpec, HotKey, KbKey, LocalizedString, Menu, MenuItem, SysMods,
-    WindowId,
+    Env, FileDialogOptions, FileInfo, FileSpec, HotKey, KbKey, LocalizedString, Menu, MenuItem,
+    Selector, SysMods, WindowId,
 };
 
+use scribl_core::live::LiveTarget;
+use scribl_core::underlay::Corner;
+use scribl_curves::{Brush, LabelColor, Time};
+
 use crate::app_state::AppState;
+use crate::cmd::Nudge;
+use crate::config::{AudioSource, RowLayout, ScrollMode, UiPalette};
+use crate::data::{DenoiseSetting, TimeDisplay};
 use crate::{cmd, CurrentAction, EditorState, SnippetId};
 
 const SCRIBL_FILE_TYPE: FileSpec = FileSpec::new("Scribl animation (.scb)", &["scb"]);
 const EXPORT_FILE_TYPE: FileSpec = FileSpec::new("mp4 video (.mp4)", &["mp4"]);
+const SVG_FILE_TYPE: FileSpec = FileSpec::new("Animated SVG (.svg)", &["svg"]);
+const LOTTIE_FILE_TYPE: FileSpec = FileSpec::new("Lottie animation (.json)", &["json"]);
+const PNG_FILE_TYPE: FileSpec = FileSpec::new("PNG image (.png)", &["png"]);
+const OTIO_FILE_TYPE: FileSpec = FileSpec::new("OpenTimelineIO (.otio)", &["otio"]);
+const SVG_IMPORT_FILE_TYPE: FileSpec = FileSpec::new("SVG image (.svg)", &["svg"]);
+const MUSIC_FILE_TYPE: FileSpec = FileSpec::new(
+    "Audio file (.mp3, .ogg, .flac, .wav)",
+    &["mp3", "ogg", "flac", "wav"],
+);
 
 trait EditorMenu {
     fn action<F: FnMut(&mut MenuEventCtx, &mut EditorState) + 'static>(
@@ -53,7 +69,77 @@ pub fn save_dialog_options() -> FileDialogOptions {
     FileDialogOptions::new().allowed_types(vec![SCRIBL_FILE_TYPE])
 }
 
-fn file_menu(window_id: WindowId, _data: &AppState) -> Menu<AppState> {
+/// The options for the dialog that asks where to export a video. When they accept, `accept` gets
+/// sent along with the file name.
+fn export_dialog_options(data: &EditorState, accept: Selector<FileInfo>) -> FileDialogOptions {
+    let mut export_options = FileDialogOptions::new()
+        .allowed_types(vec![EXPORT_FILE_TYPE])
+        .title("Export to video")
+        .button_text("Export")
+        .accept_command(accept);
+    if let Some(save_path) = &data.save_path {
+        if let Some(save_name) = save_path.file_stem() {
+            if let Some(save_name) = save_name.to_str() {
+                export_options = export_options.default_name(save_name);
+            }
+        }
+    }
+    export_options
+}
+
+fn export_preset_menu(window_id: WindowId, data: &AppState) -> Menu<AppState> {
+    let item = |label: String, preset: Option<String>| {
+        let selected = preset.clone();
+        MenuItem::new(label)
+            .action(window_id, move |_, data| {
+                data.settings.export_preset = preset.clone()
+            })
+            .selected_if(move |data, _env| {
+                data.editor(window_id)
+                    .map(|e| e.settings.export_preset == selected)
+                    .unwrap_or(false)
+            })
+    };
+
+    let presets = data
+        .editor(window_id)
+        .map(|e| e.config.export_presets())
+        .unwrap_or_default();
+    let mut menu = Menu::new(
+        LocalizedString::new("scribl-menu-file-export-preset").with_placeholder("Export preset"),
+    )
+    .entry(item("Settings from config file".to_owned(), None));
+    for p in presets {
+        menu = menu.entry(item(p.name.clone(), Some(p.name)));
+    }
+    menu
+}
+
+// The frame rates that can be picked from the menu.
+const FRAME_RATES: &[f64] = &[24.0, 25.0, 30.0, 50.0, 60.0];
+
+fn frame_rate_menu(window_id: WindowId) -> Menu<AppState> {
+    let item = |label: String, fps: Option<f64>| {
+        MenuItem::new(label)
+            .action(window_id, move |_, data| data.scribl.fps = fps)
+            .selected_if(move |data, _env| {
+                data.editor(window_id)
+                    .map(|e| e.scribl.fps == fps)
+                    .unwrap_or(false)
+            })
+    };
+
+    let mut menu = Menu::new(
+        LocalizedString::new("scribl-menu-file-frame-rate").with_placeholder("Frame rate"),
+    )
+    .entry(item("From export preset".to_owned(), None));
+    for &fps in FRAME_RATES {
+        menu = menu.entry(item(format!("{} fps", fps), Some(fps)));
+    }
+    menu
+}
+
+fn file_menu(window_id: WindowId, data: &AppState) -> Menu<AppState> {
     let new = platform_menus::win::file::new();
 
     let open_cmd = commands::SHOW_OPEN_PANEL
@@ -62,6 +148,45 @@ fn file_menu(window_id: WindowId, _data: &AppState) -> Menu<AppState> {
         .command(open_cmd)
         .hotkey(SysMods::Cmd, "o");
 
+    let open_autosave = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-open-autosave").with_placeholder("Open autosave..."),
+    )
+    .action(window_id, |ctx, _| ctx.submit_command(cmd::SHOW_AUTOSAVES));
+
+    let new_from_template = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-new-from-template")
+            .with_placeholder("New from template..."),
+    )
+    .action(window_id, |ctx, _| ctx.submit_command(cmd::SHOW_TEMPLATES));
+
+    let import_svg = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-import-svg").with_placeholder("Import SVG..."),
+    )
+    .command(
+        commands::SHOW_OPEN_PANEL.with(
+            FileDialogOptions::new()
+                .allowed_types(vec![SVG_IMPORT_FILE_TYPE])
+                .title("Import an SVG drawing")
+                .button_text("Import")
+                .accept_command(cmd::IMPORT_SVG),
+        ),
+    )
+    .active_if(window_id, |data| data.action.is_idle());
+
+    let import_audio = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-import-audio").with_placeholder("Import audio..."),
+    )
+    .command(
+        commands::SHOW_OPEN_PANEL.with(
+            FileDialogOptions::new()
+                .allowed_types(vec![MUSIC_FILE_TYPE])
+                .title("Import audio at the current time")
+                .button_text("Import")
+                .accept_command(cmd::IMPORT_AUDIO),
+        ),
+    )
+    .active_if(window_id, |data| data.action.is_idle());
+
     let save = MenuItem::new(LocalizedString::new("common-menu-file-save"))
         .action(window_id, move |ctx, data| {
             let save_as_command = commands::SHOW_SAVE_PANEL.with(save_dialog_options());
@@ -77,39 +202,168 @@ fn file_menu(window_id: WindowId, _data: &AppState) -> Menu<AppState> {
         .command(commands::SHOW_SAVE_PANEL.with(save_dialog_options()))
         .hotkey(SysMods::CmdShift, "S");
 
+    let save_template = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-save-template")
+            .with_placeholder("Save as template..."),
+    )
+    .action(window_id, |ctx, _| {
+        ctx.submit_command(cmd::SHOW_SAVE_TEMPLATE)
+    })
+    .active_if(window_id, |data| data.action.is_idle());
+
     let export =
         MenuItem::new(LocalizedString::new("scribl-menu-file-export").with_placeholder("Export"))
             .action(window_id, move |ctx, data| {
-                let mut export_options = FileDialogOptions::new()
-                    .allowed_types(vec![EXPORT_FILE_TYPE])
-                    .title("Export to video")
-                    .button_text("Export")
-                    .accept_command(cmd::EXPORT);
-                if let Some(save_path) = &data.save_path {
-                    if let Some(save_name) = save_path.file_stem() {
-                        if let Some(save_name) = save_name.to_str() {
-                            export_options = export_options.default_name(save_name);
-                        }
-                    }
-                }
+                let export_options = export_dialog_options(data, cmd::EXPORT);
                 ctx.submit_command(commands::SHOW_SAVE_PANEL.with(export_options))
             })
             .hotkey(SysMods::Cmd, "e");
 
+    let export_range = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-export-range")
+            .with_placeholder("Export marked range..."),
+    )
+    .action(window_id, move |ctx, data| {
+        let export_options = export_dialog_options(data, cmd::EXPORT_RANGE);
+        ctx.submit_command(commands::SHOW_SAVE_PANEL.with(export_options))
+    })
+    .active_if(window_id, |data| data.marked_range().is_some());
+
+    let export_web = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-export-web")
+            .with_placeholder("Export for the web..."),
+    )
+    .action(window_id, move |ctx, data| {
+        let mut options = FileDialogOptions::new()
+            .allowed_types(vec![SVG_FILE_TYPE, LOTTIE_FILE_TYPE])
+            .default_type(SVG_FILE_TYPE)
+            .title("Export for the web")
+            .button_text("Export")
+            .accept_command(cmd::EXPORT_WEB);
+        if let Some(name) = data.save_path.as_ref().and_then(|p| p.file_stem()) {
+            options = options.default_name(name.to_string_lossy());
+        }
+        ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options))
+    });
+
+    let export_timeline = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-export-timeline")
+            .with_placeholder("Export timeline image..."),
+    )
+    .action(window_id, move |ctx, data| {
+        let mut options = FileDialogOptions::new()
+            .allowed_types(vec![PNG_FILE_TYPE])
+            .default_type(PNG_FILE_TYPE)
+            .title("Export timeline image")
+            .button_text("Export")
+            .accept_command(cmd::EXPORT_TIMELINE_IMAGE);
+        if let Some(name) = data.save_path.as_ref().and_then(|p| p.file_stem()) {
+            options = options.default_name(format!("{}-timeline", name.to_string_lossy()));
+        }
+        ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options))
+    });
+
+    let export_otio = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-export-otio")
+            .with_placeholder("Export to video editor..."),
+    )
+    .action(window_id, move |ctx, data| {
+        let mut options = FileDialogOptions::new()
+            .allowed_types(vec![OTIO_FILE_TYPE])
+            .default_type(OTIO_FILE_TYPE)
+            .title("Export to video editor")
+            .button_text("Export")
+            .accept_command(cmd::EXPORT_OTIO);
+        if let Some(name) = data.save_path.as_ref().and_then(|p| p.file_stem()) {
+            options = options.default_name(name.to_string_lossy());
+        }
+        ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options))
+    });
+
+    let export_queue = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-export-queue").with_placeholder("Export queue..."),
+    )
+    .action(window_id, |ctx, _| {
+        ctx.submit_command(cmd::SHOW_EXPORT_QUEUE)
+    });
+
+    let live_camera = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-live-camera")
+            .with_placeholder("Stream to virtual camera"),
+    )
+    .action(window_id, |ctx, data| {
+        if let Some(device) = &data.config.live.virtual_camera {
+            ctx.submit_command(
+                cmd::START_LIVE_OUTPUT.with(LiveTarget::VirtualCamera(device.clone())),
+            );
+        }
+    })
+    .active_if(window_id, |data| {
+        data.config.live.virtual_camera.is_some() && !data.live
+    });
+
+    let live_rtmp = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-live-rtmp")
+            .with_placeholder("Stream to RTMP server"),
+    )
+    .action(window_id, |ctx, data| {
+        if let Some(url) = &data.config.live.rtmp_url {
+            ctx.submit_command(cmd::START_LIVE_OUTPUT.with(LiveTarget::Rtmp(url.clone())));
+        }
+    })
+    .active_if(window_id, |data| {
+        data.config.live.rtmp_url.is_some() && !data.live
+    });
+
+    let live_stop = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-live-stop").with_placeholder("Stop live output"),
+    )
+    .action(window_id, |ctx, _| {
+        ctx.submit_command(cmd::STOP_LIVE_OUTPUT)
+    })
+    .active_if(window_id, |data| data.live);
+
     let close = MenuItem::new(LocalizedString::new("common-menu-file-close"))
         .command(druid::commands::CLOSE_WINDOW)
         .hotkey(SysMods::Cmd, "q");
 
+    let clear_cache = MenuItem::new(
+        LocalizedString::new("scribl-menu-file-clear-cache").with_placeholder("Clear cached files"),
+    )
+    .action(window_id, |ctx, _| ctx.submit_command(cmd::CLEAR_CACHE));
+
     Menu::new(LocalizedString::new("common-menu-file-menu"))
         .entry(new)
+        .entry(new_from_template)
         .entry(open)
+        .entry(open_autosave)
+        .entry(import_svg)
+        .entry(import_audio)
         .entry(save)
         .entry(save_as)
+        .entry(save_template)
         .entry(export)
+        .entry(export_range)
+        .entry(export_web)
+        .entry(export_timeline)
+        .entry(export_otio)
+        .entry(export_preset_menu(window_id, data))
+        .entry(frame_rate_menu(window_id))
+        .entry(export_queue)
+        .separator()
+        .entry(live_camera)
+        .entry(live_rtmp)
+        .entry(live_stop)
+        .separator()
+        .entry(clear_cache)
         .separator()
         .entry(close)
 }
 
+// The loudness (in LUFS) that the "normalize loudness" menu item aims for. This is what most
+// podcast platforms ask for.
+const NORMALIZE_LUFS: f64 = -16.0;
+
 fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
     fn undo_desc(id: WindowId, data: &AppState) -> String {
         // FIXME: figure out how localization is expected to work
@@ -144,16 +398,41 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
         })
         .hotkey(SysMods::CmdShift, "z");
 
+    let checkpoints = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-checkpoints").with_placeholder("Checkpoints..."),
+    )
+    .action(id, |ctx, _| ctx.submit_command(cmd::SHOW_CHECKPOINTS))
+    .active_if(id, move |data| data.action.is_idle());
+
+    let script_console = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-script-console")
+            .with_placeholder("Script console..."),
+    )
+    .action(id, |ctx, _| ctx.submit_command(cmd::SHOW_SCRIPT_CONSOLE))
+    .active_if(id, move |data| data.action.is_idle());
+
     let draw =
         MenuItem::new(LocalizedString::new("scribl-menu-edit-draw").with_placeholder("Draw"))
             .action(id, |_, data| data.draw())
-            .active_if(id, move |data| data.action.is_idle())
+            .active_if(id, move |data| data.action.is_idle() && !data.presenting)
             .hotkey(SysMods::None, " ");
 
+    let pause_clock = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-pause-clock").with_placeholder("Pause clock"),
+    )
+    .action(id, |_, data| data.toggle_recording_pause())
+    .active_if(id, move |data| data.action.is_recording())
+    .selected_if(move |data, _env| {
+        data.editor(id)
+            .map(|e| e.action.is_recording_held())
+            .unwrap_or(false)
+    })
+    .hotkey(SysMods::None, "p");
+
     let talk =
         MenuItem::new(LocalizedString::new("scribl-menu-edit-talk").with_placeholder("Talk"))
             .action(id, |_, data| data.talk())
-            .active_if(id, move |data| data.action.is_idle())
+            .active_if(id, move |data| data.action.is_idle() && !data.presenting)
             .hotkey(SysMods::Shift, " ");
 
     let play =
@@ -166,9 +445,10 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
         MenuItem::new(LocalizedString::new("scribl-menu-edit-stop").with_placeholder("Stop"))
             .action(id, |_, data| data.finish_action())
             .active_if(id, move |data| match data.action {
-                CurrentAction::Playing => true,
+                CurrentAction::Playing(_) => true,
                 CurrentAction::Recording(_) => true,
                 CurrentAction::RecordingAudio(_) => true,
+                CurrentAction::PunchingIn(_) => true,
                 _ => false,
             })
             .dynamic_hotkey(move |data, _| {
@@ -178,13 +458,117 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
                     .unwrap_or(&CurrentAction::Idle);
                 match action {
                     // The stop hotkey matches the hotkey that was used to start the current action.
-                    CurrentAction::Playing => Some(HotKey::new(SysMods::None, KbKey::Enter)),
+                    CurrentAction::Playing(_) => Some(HotKey::new(SysMods::None, KbKey::Enter)),
                     CurrentAction::Recording(_) => Some(HotKey::new(SysMods::None, " ")),
                     CurrentAction::RecordingAudio(_) => Some(HotKey::new(SysMods::Shift, " ")),
+                    CurrentAction::PunchingIn(_) => Some(HotKey::new(SysMods::Shift, " ")),
                     _ => None,
                 }
             });
 
+    let go_to_time = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-go-to-time").with_placeholder("Go to time..."),
+    )
+    .action(id, |ctx, _| ctx.submit_command(cmd::SHOW_GO_TO_TIME))
+    .hotkey(SysMods::Cmd, "g")
+    .active_if(id, move |data| data.action.is_idle());
+
+    let nudge = |name: &str, placeholder: &str, nudge: Nudge, mods: SysMods, key: &str| {
+        MenuItem::new(LocalizedString::new(name).with_placeholder(placeholder))
+            .action(id, move |ctx, _| {
+                ctx.submit_command(cmd::NUDGE_TIME.with(nudge))
+            })
+            .hotkey(mods, key)
+            .active_if(id, move |data| data.action.is_idle())
+    };
+    let prev_frame = nudge(
+        "scribl-menu-edit-prev-frame",
+        "Previous frame",
+        Nudge::Frames(-1),
+        SysMods::None,
+        ",",
+    );
+    let next_frame = nudge(
+        "scribl-menu-edit-next-frame",
+        "Next frame",
+        Nudge::Frames(1),
+        SysMods::None,
+        ".",
+    );
+    // With shift held down, the comma and period keys usually produce "<" and ">".
+    let back_second = nudge(
+        "scribl-menu-edit-back-second",
+        "Back one second",
+        Nudge::Seconds(-1),
+        SysMods::Shift,
+        "<",
+    );
+    let forward_second = nudge(
+        "scribl-menu-edit-forward-second",
+        "Forward one second",
+        Nudge::Seconds(1),
+        SysMods::Shift,
+        ">",
+    );
+
+    let go_to_start = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-go-to-start").with_placeholder("Go to start"),
+    )
+    .action(id, |_, data| data.warp_time_to(Time::ZERO))
+    .hotkey(SysMods::None, KbKey::Home)
+    .active_if(id, move |data| data.action.is_idle());
+
+    let go_to_end = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-go-to-end").with_placeholder("Go to end"),
+    )
+    .action(id, |_, data| data.warp_time_to_end())
+    .hotkey(SysMods::None, KbKey::End)
+    .active_if(id, move |data| data.action.is_idle());
+
+    let prev_snippet = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-select-prev-snippet")
+            .with_placeholder("Select previous snippet"),
+    )
+    .action(id, |ctx, _| ctx.submit_command(cmd::SELECT_SNIPPET_LEFT))
+    .hotkey(SysMods::Cmd, KbKey::ArrowLeft)
+    .active_if(id, move |data| data.action.is_idle());
+
+    let next_snippet = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-select-next-snippet")
+            .with_placeholder("Select next snippet"),
+    )
+    .action(id, |ctx, _| ctx.submit_command(cmd::SELECT_SNIPPET_RIGHT))
+    .hotkey(SysMods::Cmd, KbKey::ArrowRight)
+    .active_if(id, move |data| data.action.is_idle());
+
+    // Plain Enter is already taken by "Play".
+    let properties = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-snippet-properties")
+            .with_placeholder("Snippet properties..."),
+    )
+    .action(id, |ctx, data| {
+        if let Some(snip) = data.selected_snippet {
+            ctx.submit_command(cmd::SHOW_SNIPPET_PROPERTIES.with(snip));
+        }
+    })
+    .hotkey(SysMods::Alt, KbKey::Enter)
+    .active_if(id, move |data| {
+        data.selected_snippet.is_some() && data.action.is_idle()
+    });
+
+    let retime = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-retime-drawing")
+            .with_placeholder("Retime drawing..."),
+    )
+    .action(id, |ctx, data| {
+        if let Some(snip) = data.selected_snippet {
+            ctx.submit_command(cmd::RETIME_SNIPPET.with(snip));
+        }
+    })
+    .active_if(id, move |data| {
+        data.action.is_idle() && matches!(data.selected_snippet, Some(SnippetId::Draw(_)))
+    });
+
     let mark =
         MenuItem::new(LocalizedString::new("scribl-menu-edit-mark").with_placeholder("Set mark"))
             .action(id, move |_, data| data.set_mark())
@@ -197,6 +581,25 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
     .hotkey(SysMods::None, KbKey::Escape)
     .active_if(id, move |data| data.mark.is_some());
 
+    let set_end = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-set-end").with_placeholder("Set end of animation"),
+    )
+    .action(id, |_, data| data.set_project_end())
+    .active_if(id, move |data| {
+        data.action.is_idle()
+            && data.time() > Time::ZERO
+            && data.scribl.project_end != Some(data.time())
+    });
+
+    let clear_end = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-clear-end")
+            .with_placeholder("Clear end of animation"),
+    )
+    .action(id, |_, data| data.clear_project_end())
+    .active_if(id, move |data| {
+        data.action.is_idle() && data.scribl.project_end.is_some()
+    });
+
     let warp = MenuItem::new(
         LocalizedString::new("scribl-menu-edit-warp").with_placeholder("Warp snippet"),
     )
@@ -206,6 +609,30 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
         data.mark.is_some() && matches!(data.selected_snippet, Some(SnippetId::Draw(_)))
     });
 
+    let hold = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-insert-hold").with_placeholder("Insert hold"),
+    )
+    .action(id, |_, data| data.insert_hold())
+    .hotkey(SysMods::None, "h")
+    .active_if(id, move |data| {
+        data.mark.is_some()
+            && data.mark != Some(data.time())
+            && matches!(data.selected_snippet, Some(SnippetId::Draw(_)))
+    });
+
+    let unhold = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-remove-hold").with_placeholder("Remove hold"),
+    )
+    .action(id, |_, data| {
+        if let Some(snip) = data.selected_snippet {
+            data.remove_hold(snip);
+        }
+    })
+    .active_if(id, move |data| {
+        data.selected_snippet
+            .map_or(false, |snip| data.has_hold_at_time(snip))
+    });
+
     let trunc = MenuItem::new(
         LocalizedString::new("scribl-menu-edit-truncate").with_placeholder("Truncate snippet"),
     )
@@ -224,6 +651,7 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
 
     let talk_selected =
         move |data: &EditorState| matches!(data.selected_snippet, Some(SnippetId::Talk(_)));
+    let has_talk = move |data: &EditorState| data.scribl.talk.snippets().next().is_some();
 
     let increase_volume = MenuItem::new(
         LocalizedString::new("scribl-menu-edit-increase-volume")
@@ -254,48 +682,922 @@ fn edit_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
             .hotkey(SysMods::Shift, KbKey::Backspace)
             .active_if(id, talk_selected);
 
+    let rerecord = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-rerecord").with_placeholder("Re-record range"),
+    )
+    .action(id, |_, data| data.punch_in())
+    .hotkey(SysMods::None, "r")
+    .active_if(id, move |data| {
+        data.action.is_idle() && data.mark.is_some() && talk_selected(data)
+    });
+
+    let measure_loudness = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-measure-loudness")
+            .with_placeholder("Measure loudness"),
+    )
+    .action(id, |ctx, _| {
+        ctx.submit_command(cmd::MEASURE_LOUDNESS.with(None))
+    })
+    .active_if(id, has_talk);
+
+    let normalize_loudness = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-normalize-loudness")
+            .with_placeholder("Normalize loudness to -16 LUFS"),
+    )
+    .action(id, |ctx, _| {
+        ctx.submit_command(cmd::MEASURE_LOUDNESS.with(Some(NORMALIZE_LUFS)))
+    })
+    .active_if(id, move |data| data.action.is_idle() && has_talk(data));
+
+    let find_duplicates = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-find-duplicate-audio")
+            .with_placeholder("Find duplicate audio"),
+    )
+    .action(id, |ctx, _| ctx.submit_command(cmd::FIND_DUPLICATE_AUDIO))
+    .active_if(id, move |data| data.action.is_idle() && has_talk(data));
+
+    let detect_speech = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-detect-speech")
+            .with_placeholder("Speech detection threshold..."),
+    )
+    .action(id, |ctx, data| {
+        if let Some(SnippetId::Talk(talk_id)) = data.selected_snippet {
+            ctx.submit_command(cmd::DETECT_SPEECH.with(talk_id));
+        }
+    })
+    .active_if(id, move |data| data.action.is_idle() && talk_selected(data));
+
+    let process_audio = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-process-audio").with_placeholder("Process audio"),
+    )
+    .action(id, |ctx, data| {
+        if let Some(SnippetId::Talk(talk_id)) = data.selected_snippet {
+            ctx.submit_command(cmd::PROCESS_AUDIO.with(talk_id));
+        }
+    })
+    .active_if(id, move |data| {
+        data.action.is_idle()
+            && talk_selected(data)
+            && data.settings.denoise_setting != DenoiseSetting::DenoiseOff
+            && data.status.in_progress.processing_audio.is_none()
+    });
+
+    let speed_item = |key: &'static str, placeholder: &'static str, ratio: f64| {
+        MenuItem::new(LocalizedString::new(key).with_placeholder(placeholder))
+            .action(id, move |_, data| data.stretch_audio(ratio))
+            .active_if(id, move |data| data.action.is_idle() && talk_selected(data))
+    };
+    let speech_speed = Menu::new(
+        LocalizedString::new("scribl-menu-edit-speech-speed").with_placeholder("Speech speed"),
+    )
+    .entry(speed_item(
+        "scribl-menu-edit-speech-faster",
+        "10% faster",
+        1.0 / 1.1,
+    ))
+    .entry(speed_item(
+        "scribl-menu-edit-speech-slower",
+        "10% slower",
+        1.1,
+    ))
+    .separator()
+    .entry(
+        MenuItem::new(
+            LocalizedString::new("scribl-menu-edit-speech-fit")
+                .with_placeholder("Stretch to end here"),
+        )
+        .action(id, |_, data| data.stretch_audio_to_time())
+        .active_if(id, move |data| data.action.is_idle() && talk_selected(data)),
+    );
+
+    let mut pen_effects = Menu::new(
+        LocalizedString::new("scribl-menu-edit-pen-effects").with_placeholder("Pen effects"),
+    );
+    for effect in scribl_curves::registry() {
+        let name = effect.name();
+        pen_effects = pen_effects.entry(
+            MenuItem::new(effect.title())
+                .action(id, move |_, data| data.settings.toggle_pen_effect(name))
+                .selected_if(move |data, _env| {
+                    data.editor(id)
+                        .map(|e| e.settings.pen_effects.iter().any(|n| n == name))
+                        .unwrap_or(false)
+                }),
+        );
+    }
+
+    let trim_silence = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-trim-silence").with_placeholder("Trim silence"),
+    )
+    .action(id, |_, data| data.trim_silence())
+    .active_if(id, move |data| data.action.is_idle() && talk_selected(data));
+
+    let tighten_pauses = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-tighten-pauses").with_placeholder("Tighten pauses"),
+    )
+    .action(id, |_, data| data.tighten_pauses())
+    .active_if(id, move |data| data.action.is_idle() && talk_selected(data));
+
+    let mut brush_menu =
+        Menu::new(LocalizedString::new("scribl-menu-edit-brush").with_placeholder("Brush"));
+    for &brush in &Brush::ALL {
+        brush_menu = brush_menu.entry(
+            MenuItem::new(brush.title())
+                .action(id, move |_, data| data.settings.brush = brush)
+                .selected_if(move |data, _env| {
+                    data.editor(id)
+                        .map(|e| e.settings.brush == brush)
+                        .unwrap_or(false)
+                }),
+        );
+    }
+
+    let eyedropper = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-pick-color")
+            .with_placeholder("Pick color from drawing"),
+    )
+    .action(id, |_, data| {
+        data.settings.eyedropper = !data.settings.eyedropper
+    })
+    .hotkey(SysMods::None, "i")
+    .selected_if(move |data, _env| {
+        data.editor(id)
+            .map(|e| e.settings.eyedropper)
+            .unwrap_or(false)
+    })
+    .active_if(id, move |data| data.action.is_idle());
+
+    let source_item = |name: &str, placeholder: &str, source: AudioSource| {
+        MenuItem::new(LocalizedString::new(name).with_placeholder(placeholder))
+            .action(id, move |_, data| data.settings.audio_source = source)
+            .selected_if(move |data, _env| {
+                data.editor(id)
+                    .map(|e| e.settings.audio_source == source)
+                    .unwrap_or(false)
+            })
+            .active_if(id, move |data| data.action.is_idle())
+    };
+    let record_mic = source_item(
+        "scribl-menu-edit-record-microphone",
+        "Record from microphone",
+        AudioSource::Microphone,
+    );
+    let record_system = source_item(
+        "scribl-menu-edit-record-system",
+        "Record system audio",
+        AudioSource::System,
+    );
+
+    let capture_screen = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-capture-screen")
+            .with_placeholder("Record screen while talking"),
+    )
+    .action(id, |_, data| {
+        data.settings.capture_screen = !data.settings.capture_screen
+    })
+    .selected_if(move |data, _env| {
+        data.editor(id)
+            .map(|e| e.settings.capture_screen)
+            .unwrap_or(false)
+    })
+    .active_if(id, move |data| data.action.is_idle());
+
+    let remove_underlay = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-remove-underlay")
+            .with_placeholder("Remove screen recording"),
+    )
+    .action(id, |_, data| data.remove_underlay())
+    .active_if(id, move |data| {
+        data.action.is_idle() && data.scribl.underlay.is_some()
+    });
+
+    let capture_webcam = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-capture-webcam")
+            .with_placeholder("Record webcam while talking"),
+    )
+    .action(id, |_, data| {
+        data.settings.capture_webcam = !data.settings.capture_webcam
+    })
+    .selected_if(move |data, _env| {
+        data.editor(id)
+            .map(|e| e.settings.capture_webcam)
+            .unwrap_or(false)
+    })
+    .active_if(id, move |data| data.action.is_idle());
+
+    let has_webcam = move |data: &EditorState| data.scribl.webcam.is_some();
+    let webcam_corner = |name: &str, placeholder: &str, corner: Corner| {
+        MenuItem::new(LocalizedString::new(name).with_placeholder(placeholder))
+            .action(id, move |_, data| data.set_webcam_corner(corner))
+            .selected_if(move |data, _env| {
+                data.editor(id)
+                    .and_then(|e| e.scribl.webcam.as_ref())
+                    .map(|w| w.corner)
+                    == Some(corner)
+            })
+            .active_if(id, has_webcam)
+    };
+    let webcam_position = Menu::new(
+        LocalizedString::new("scribl-menu-edit-webcam-position")
+            .with_placeholder("Webcam position"),
+    )
+    .entry(webcam_corner(
+        "scribl-menu-edit-webcam-top-left",
+        "Top left",
+        Corner::TopLeft,
+    ))
+    .entry(webcam_corner(
+        "scribl-menu-edit-webcam-top-right",
+        "Top right",
+        Corner::TopRight,
+    ))
+    .entry(webcam_corner(
+        "scribl-menu-edit-webcam-bottom-left",
+        "Bottom left",
+        Corner::BottomLeft,
+    ))
+    .entry(webcam_corner(
+        "scribl-menu-edit-webcam-bottom-right",
+        "Bottom right",
+        Corner::BottomRight,
+    ))
+    .separator()
+    .entry(
+        MenuItem::new(
+            LocalizedString::new("scribl-menu-edit-webcam-larger").with_placeholder("Larger"),
+        )
+        .action(id, |_, data| data.resize_webcam(1.25))
+        .active_if(id, has_webcam),
+    )
+    .entry(
+        MenuItem::new(
+            LocalizedString::new("scribl-menu-edit-webcam-smaller").with_placeholder("Smaller"),
+        )
+        .action(id, |_, data| data.resize_webcam(0.8))
+        .active_if(id, has_webcam),
+    );
+
+    let remove_webcam = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-remove-webcam")
+            .with_placeholder("Remove webcam recording"),
+    )
+    .action(id, |_, data| data.remove_webcam())
+    .active_if(id, move |data| data.action.is_idle() && has_webcam(data));
+
+    let calibrate = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-calibrate-latency")
+            .with_placeholder("Calibrate audio latency..."),
+    )
+    .action(id, |ctx, _| ctx.submit_command(cmd::CALIBRATE_LATENCY))
+    .active_if(id, move |data| data.action.is_idle());
+
+    let global_hotkeys = MenuItem::new(
+        LocalizedString::new("scribl-menu-edit-global-hotkeys")
+            .with_placeholder("System-wide hotkeys"),
+    )
+    .on_activate(|ctx, data: &mut AppState, _env| {
+        ctx.submit_command(cmd::SET_GLOBAL_HOTKEYS.with(!data.global_hotkeys))
+    })
+    .selected_if(|data, _env| data.global_hotkeys);
+
     Menu::new(LocalizedString::new("common-menu-edit-menu"))
         .entry(undo)
         .entry(redo)
+        .entry(checkpoints)
+        .entry(script_console)
         .separator()
         .entry(draw)
+        .entry(pause_clock)
         .entry(talk)
         .entry(play)
         .entry(stop)
         .separator()
+        .entry(go_to_time)
+        .entry(prev_frame)
+        .entry(next_frame)
+        .entry(back_second)
+        .entry(forward_second)
+        .entry(go_to_start)
+        .entry(go_to_end)
+        .separator()
+        .entry(prev_snippet)
+        .entry(next_snippet)
+        .entry(properties)
+        .separator()
         .entry(mark)
         .entry(clear_mark)
+        .entry(set_end)
+        .entry(clear_end)
         .entry(warp)
+        .entry(hold)
+        .entry(unhold)
         .entry(trunc)
+        .entry(retime)
         .entry(delete)
         .separator()
         .entry(increase_volume)
         .entry(decrease_volume)
         .entry(silence)
         .entry(snip)
+        .entry(rerecord)
+        .entry(trim_silence)
+        .entry(tighten_pauses)
+        .entry(speech_speed)
+        .entry(measure_loudness)
+        .entry(normalize_loudness)
+        .entry(find_duplicates)
+        .entry(detect_speech)
+        .entry(process_audio)
+        .separator()
+        .entry(eyedropper)
+        .entry(brush_menu)
+        .entry(pen_effects)
+        .separator()
+        .entry(record_mic)
+        .entry(record_system)
+        .entry(calibrate)
+        .entry(global_hotkeys)
+        .separator()
+        .entry(capture_screen)
+        .entry(remove_underlay)
+        .entry(capture_webcam)
+        .entry(webcam_position)
+        .entry(remove_webcam)
+}
+
+// The pencil sound volumes that can be picked from the menu.
+const PENCIL_VOLUMES: &[(&str, f32)] = &[
+    ("Quiet", 0.15),
+    ("Medium", crate::audio::DEFAULT_PENCIL_VOLUME),
+    ("Loud", 0.6),
+];
+
+fn pencil_sound_menu(window_id: WindowId) -> Menu<AppState> {
+    let item = |label: &str, volume: Option<f32>| {
+        MenuItem::new(label)
+            .action(window_id, move |_, data| data.scribl.pencil_volume = volume)
+            .selected_if(move |data, _env| {
+                data.editor(window_id)
+                    .map(|e| e.scribl.pencil_volume == volume)
+                    .unwrap_or(false)
+            })
+    };
+
+    let mut menu = Menu::new(
+        LocalizedString::new("scribl-menu-music-pencil-sound").with_placeholder("Drawing sound"),
+    )
+    .entry(item("Off", None));
+    for &(label, volume) in PENCIL_VOLUMES {
+        menu = menu.entry(item(label, Some(volume)));
+    }
+    menu
+}
+
+fn music_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
+    let has_music = move |data: &EditorState| data.scribl.music.is_some();
+
+    let import = MenuItem::new(
+        LocalizedString::new("scribl-menu-music-import").with_placeholder("Import music..."),
+    )
+    .command(
+        commands::SHOW_OPEN_PANEL.with(
+            FileDialogOptions::new()
+                .allowed_types(vec![MUSIC_FILE_TYPE])
+                .title("Import background music")
+                .button_text("Import")
+                .accept_command(cmd::IMPORT_MUSIC),
+        ),
+    );
+
+    let looping = MenuItem::new(
+        LocalizedString::new("scribl-menu-music-loop").with_placeholder("Loop music"),
+    )
+    .action(id, |_, data| data.toggle_music_looping())
+    .selected_if(move |data, _env| {
+        data.editor(id)
+            .and_then(|e| e.scribl.music.as_ref())
+            .map(|m| m.looping())
+            .unwrap_or(false)
+    })
+    .active_if(id, has_music);
+
+    let louder = MenuItem::new(
+        LocalizedString::new("scribl-menu-music-louder").with_placeholder("Increase music volume"),
+    )
+    .action(id, |_, data| data.multiply_music_volume(1.25))
+    .active_if(id, has_music);
+
+    let quieter = MenuItem::new(
+        LocalizedString::new("scribl-menu-music-quieter").with_placeholder("Decrease music volume"),
+    )
+    .action(id, |_, data| data.multiply_music_volume(0.8))
+    .active_if(id, has_music);
+
+    let remove = MenuItem::new(move |data: &AppState, _env: &Env| {
+        // FIXME: figure out how localization is expected to work
+        match data.editor(id).and_then(|e| e.scribl.music.as_ref()) {
+            Some(music) => format!("Remove music ({})", music.name()),
+            None => "Remove music".to_owned(),
+        }
+    })
+    .action(id, |_, data| data.remove_music())
+    .active_if(id, has_music);
+
+    Menu::new(LocalizedString::new("scribl-menu-music-menu").with_placeholder("Music"))
+        .entry(import)
+        .entry(looping)
+        .entry(louder)
+        .entry(quieter)
+        .separator()
+        .entry(remove)
+        .separator()
+        .entry(pencil_sound_menu(id))
+}
+
+fn markers_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
+    let at_marker = move |data: &EditorState| data.scribl.markers.get(data.time()).is_some();
+
+    let add = MenuItem::new(
+        LocalizedString::new("scribl-menu-markers-add").with_placeholder("Add marker..."),
+    )
+    .action(id, |ctx, _| ctx.submit_command(cmd::ADD_MARKER))
+    .hotkey(SysMods::Shift, "M")
+    .active_if(id, move |data| data.action.is_idle());
+
+    let rename = MenuItem::new(
+        LocalizedString::new("scribl-menu-markers-rename").with_placeholder("Rename marker..."),
+    )
+    .action(id, |ctx, _| ctx.submit_command(cmd::RENAME_MARKER))
+    .active_if(id, move |data| data.action.is_idle() && at_marker(data));
+
+    let remove = MenuItem::new(
+        LocalizedString::new("scribl-menu-markers-remove").with_placeholder("Remove marker"),
+    )
+    .action(id, |_, data| data.remove_marker())
+    .active_if(id, move |data| data.action.is_idle() && at_marker(data));
+
+    let prev = MenuItem::new(
+        LocalizedString::new("scribl-menu-markers-prev").with_placeholder("Previous marker"),
+    )
+    .action(id, |_, data| {
+        if let Some(time) = data.scribl.markers.prev_before(data.time()) {
+            data.warp_time_to(time);
+        }
+    })
+    .hotkey(SysMods::None, "[")
+    .active_if(id, move |data| {
+        data.action.is_idle() && data.scribl.markers.prev_before(data.time()).is_some()
+    });
+
+    let next = MenuItem::new(
+        LocalizedString::new("scribl-menu-markers-next").with_placeholder("Next marker"),
+    )
+    .action(id, |_, data| {
+        if let Some(time) = data.scribl.markers.next_after(data.time()) {
+            data.warp_time_to(time);
+        }
+    })
+    .hotkey(SysMods::None, "]")
+    .active_if(id, move |data| {
+        data.action.is_idle() && data.scribl.markers.next_after(data.time()).is_some()
+    });
+
+    Menu::new(LocalizedString::new("scribl-menu-markers-menu").with_placeholder("Markers"))
+        .entry(add)
+        .entry(rename)
+        .entry(remove)
+        .separator()
+        .entry(prev)
+        .entry(next)
+}
+
+fn pages_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
+    let new_page =
+        MenuItem::new(LocalizedString::new("scribl-menu-pages-new").with_placeholder("New page"))
+            .action(id, |_, data| data.add_page_break())
+            .hotkey(SysMods::Shift, "N")
+            .active_if(id, move |data| {
+                data.action.is_idle()
+                    && data.time() > Time::ZERO
+                    && !data.scribl.pages.has_break(data.time())
+            });
+
+    let remove = MenuItem::new(
+        LocalizedString::new("scribl-menu-pages-remove").with_placeholder("Remove page break"),
+    )
+    .action(id, |_, data| data.remove_page_break())
+    .active_if(id, move |data| {
+        data.action.is_idle() && data.scribl.pages.page_at(data.time()) > 0
+    });
+
+    let prev = MenuItem::new(
+        LocalizedString::new("scribl-menu-pages-prev").with_placeholder("Previous page"),
+    )
+    .action(id, |_, data| {
+        if let Some(time) = data.scribl.pages.prev_start(data.time()) {
+            data.warp_time_to(time);
+        }
+    })
+    .hotkey(SysMods::None, KbKey::PageUp)
+    .active_if(id, move |data| {
+        data.action.is_idle() && data.scribl.pages.prev_start(data.time()).is_some()
+    });
+
+    let next =
+        MenuItem::new(LocalizedString::new("scribl-menu-pages-next").with_placeholder("Next page"))
+            .action(id, |_, data| {
+                if let Some(time) = data.scribl.pages.next_start(data.time()) {
+                    data.warp_time_to(time);
+                }
+            })
+            .hotkey(SysMods::None, KbKey::PageDown)
+            .active_if(id, move |data| {
+                data.action.is_idle() && data.scribl.pages.next_start(data.time()).is_some()
+            });
+
+    Menu::new(LocalizedString::new("scribl-menu-pages-menu").with_placeholder("Pages"))
+        .entry(new_page)
+        .entry(remove)
+        .separator()
+        .entry(prev)
+        .entry(next)
 }
 
 fn view_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
     let zoom_in =
         MenuItem::new(LocalizedString::new("scribl-menu-view-zoom-in").with_placeholder("Zoom in"))
             .action(id, |_, data| data.settings.zoom_in())
+            .hotkey(SysMods::Cmd, "=")
             .active_if(id, move |data| data.settings.can_zoom_in());
 
     let zoom_out = MenuItem::new(
         LocalizedString::new("scribl-menu-view-zoom-out").with_placeholder("Zoom out"),
     )
     .action(id, |_, data| data.settings.zoom_out())
+    .hotkey(SysMods::Cmd, "-")
     .active_if(id, move |data| data.settings.can_zoom_out());
 
     let zoom_reset = MenuItem::new(
-        LocalizedString::new("scribl-menu-view-zoom-reset").with_placeholder("Reset zoom"),
+        LocalizedString::new("scribl-menu-view-zoom-reset").with_placeholder("Zoom to fit"),
+    )
+    .action(id, |_, data| data.settings.zoom_reset())
+    .hotkey(SysMods::Cmd, "0")
+    .active_if(id, move |data| data.settings.can_zoom_out());
+
+    let show_frames = MenuItem::new(
+        LocalizedString::new("scribl-menu-view-show-frames").with_placeholder("Show frame numbers"),
+    )
+    .action(id, |_, data| {
+        data.settings.time_display = data.settings.time_display.toggled()
+    })
+    .selected_if(move |data, _env| {
+        data.editor(id).map(|e| e.settings.time_display) == Some(TimeDisplay::Frames)
+    });
+
+    let show_spectrograms = MenuItem::new(
+        LocalizedString::new("scribl-menu-view-show-spectrograms")
+            .with_placeholder("Show audio spectrograms"),
+    )
+    .action(id, |_, data| {
+        data.settings.spectrograms = !data.settings.spectrograms
+    })
+    .selected_if(move |data, _env| {
+        data.editor(id)
+            .map(|e| e.settings.spectrograms)
+            .unwrap_or(false)
+    });
+
+    let show_drawings = MenuItem::new(
+        LocalizedString::new("scribl-menu-view-timeline-drawings")
+            .with_placeholder("Show drawings in timeline"),
+    )
+    .action(id, |_, data| {
+        data.settings.drawings_collapsed = !data.settings.drawings_collapsed
+    })
+    .selected_if(move |data, _env| {
+        data.editor(id)
+            .map(|e| !e.settings.drawings_collapsed)
+            .unwrap_or(true)
+    });
+
+    let show_audio = MenuItem::new(
+        LocalizedString::new("scribl-menu-view-timeline-audio")
+            .with_placeholder("Show audio in timeline"),
+    )
+    .action(id, |_, data| {
+        data.settings.audio_collapsed = !data.settings.audio_collapsed
+    })
+    .selected_if(move |data, _env| {
+        data.editor(id)
+            .map(|e| !e.settings.audio_collapsed)
+            .unwrap_or(true)
+    });
+
+    let scroll_mode = |name: &str, placeholder: &str, mode: ScrollMode| {
+        MenuItem::new(LocalizedString::new(name).with_placeholder(placeholder))
+            .action(id, move |_, data| data.settings.scroll_mode = mode)
+            .selected_if(move |data, _env| {
+                data.editor(id).map(|e| e.settings.scroll_mode) == Some(mode)
+            })
+    };
+    let scrolling = Menu::new(
+        LocalizedString::new("scribl-menu-view-scrolling").with_placeholder("Timeline scrolling"),
     )
-    .action(id, |_, data| data.settings.zoom_reset());
+    .entry(scroll_mode(
+        "scribl-menu-view-scroll-follow",
+        "Follow the cursor",
+        ScrollMode::Follow,
+    ))
+    .entry(scroll_mode(
+        "scribl-menu-view-scroll-page",
+        "Page by page",
+        ScrollMode::Page,
+    ))
+    .entry(scroll_mode(
+        "scribl-menu-view-scroll-off",
+        "Don't scroll",
+        ScrollMode::Off,
+    ));
+
+    let row_layout = |name: &str, placeholder: &str, layout: RowLayout| {
+        MenuItem::new(LocalizedString::new(name).with_placeholder(placeholder))
+            .action(id, move |_, data| data.settings.row_layout = layout)
+            .selected_if(move |data, _env| {
+                data.editor(id).map(|e| e.settings.row_layout) == Some(layout)
+            })
+    };
+    let rows =
+        Menu::new(LocalizedString::new("scribl-menu-view-rows").with_placeholder("Timeline rows"))
+            .entry(row_layout(
+                "scribl-menu-view-rows-compact",
+                "Compact",
+                RowLayout::Compact,
+            ))
+            .entry(row_layout(
+                "scribl-menu-view-rows-stable",
+                "Keep snippets in place",
+                RowLayout::Stable,
+            ));
+
+    let ui_palette = |name: &str, placeholder: &str, palette: UiPalette| {
+        MenuItem::new(LocalizedString::new(name).with_placeholder(placeholder))
+            .action(id, move |_, data| data.settings.ui_palette = palette)
+            .selected_if(move |data, _env| {
+                data.editor(id).map(|e| e.settings.ui_palette) == Some(palette)
+            })
+    };
+    let colors =
+        Menu::new(LocalizedString::new("scribl-menu-view-colors").with_placeholder("Colors"))
+            .entry(ui_palette(
+                "scribl-menu-view-colors-standard",
+                "Standard",
+                UiPalette::Standard,
+            ))
+            .entry(ui_palette(
+                "scribl-menu-view-colors-deuteranopia",
+                "Deuteranopia-friendly",
+                UiPalette::Deuteranopia,
+            ))
+            .entry(ui_palette(
+                "scribl-menu-view-colors-protanopia",
+                "Protanopia-friendly",
+                UiPalette::Protanopia,
+            ));
+
+    let lock_viewport = MenuItem::new(
+        LocalizedString::new("scribl-menu-view-lock-viewport")
+            .with_placeholder("Lock view while recording"),
+    )
+    .action(id, |_, data| {
+        data.settings.lock_viewport = !data.settings.lock_viewport
+    })
+    .selected_if(move |data, _env| {
+        data.editor(id)
+            .map(|e| e.settings.lock_viewport)
+            .unwrap_or(false)
+    });
+
+    let compare = MenuItem::new(
+        LocalizedString::new("scribl-menu-view-compare")
+            .with_placeholder("Compare with previous state"),
+    )
+    .action(id, |_, data| data.toggle_comparing())
+    .hotkey(SysMods::None, "c")
+    .selected_if(move |data, _env| data.editor(id).map(|e| e.comparing).unwrap_or(false))
+    .active_if(id, move |data| {
+        data.action.is_idle() && data.undo.can_undo()
+    });
+
+    let present = MenuItem::new(
+        LocalizedString::new("scribl-menu-view-present").with_placeholder("Presentation mode"),
+    )
+    .action(id, |_, data| data.toggle_presenting())
+    .hotkey(SysMods::None, KbKey::F5)
+    .selected_if(move |data, _env| data.editor(id).map(|e| e.presenting).unwrap_or(false))
+    .active_if(id, move |data| {
+        data.presenting || data.action.is_idle() || data.action.is_playing()
+    });
 
     Menu::new(LocalizedString::new("scribl-menu-view-menu").with_placeholder("View"))
         .entry(zoom_in)
         .entry(zoom_out)
         .entry(zoom_reset)
+        .entry(lock_viewport)
+        .separator()
+        .entry(show_frames)
+        .entry(show_spectrograms)
+        .entry(show_drawings)
+        .entry(show_audio)
+        .entry(scrolling)
+        .entry(rows)
+        .entry(colors)
+        .entry(compare)
+        .separator()
+        .entry(present)
+}
+
+/// The menu that appears when they right-click on a snippet in the timeline.
+pub fn snippet_context_menu(id: WindowId, snip: SnippetId, data: &EditorState) -> Menu<AppState> {
+    let idle = move |data: &EditorState| data.action.is_idle() && data.has_snippet(snip);
+
+    let delete = MenuItem::new(
+        LocalizedString::new("scribl-menu-snippet-delete").with_placeholder("Delete"),
+    )
+    .action(id, move |ctx, _| {
+        ctx.submit_command(cmd::DELETE_SNIPPET.with(snip))
+    })
+    .active_if(id, idle);
+
+    let duplicate = MenuItem::new(
+        LocalizedString::new("scribl-menu-snippet-duplicate")
+            .with_placeholder("Duplicate at cursor"),
+    )
+    .action(id, move |ctx, _| {
+        ctx.submit_command(cmd::DUPLICATE_SNIPPET.with(snip))
+    })
+    .active_if(id, idle);
+
+    let split = MenuItem::new(
+        LocalizedString::new("scribl-menu-snippet-split").with_placeholder("Split at cursor"),
+    )
+    .action(id, move |ctx, _| {
+        ctx.submit_command(cmd::SPLIT_SNIPPET.with(snip))
+    })
+    .active_if(id, move |data| idle(data) && data.can_split_snippet(snip));
+
+    let mute_label = match snip {
+        SnippetId::Talk(talk_id) if data.scribl.talk.snippet(talk_id).muted() => "Unmute",
+        _ => "Mute",
+    };
+    let mute = MenuItem::new(mute_label)
+        .action(id, move |ctx, _| {
+            ctx.submit_command(cmd::TOGGLE_SNIPPET_MUTED.with(snip))
+        })
+        .active_if(id, move |data| {
+            matches!(snip, SnippetId::Talk(_)) && idle(data)
+        });
+
+    let (has_raw, is_raw) = match snip {
+        SnippetId::Talk(talk_id) => {
+            let talk = data.scribl.talk.snippet(talk_id);
+            (talk.has_raw(), talk.is_raw())
+        }
+        SnippetId::Draw(_) => (false, false),
+    };
+    let raw = MenuItem::new(
+        LocalizedString::new("scribl-menu-snippet-raw").with_placeholder("Use raw recording"),
+    )
+    .action(id, move |ctx, _| {
+        ctx.submit_command(cmd::TOGGLE_SNIPPET_RAW.with(snip))
+    })
+    .selected_if(move |_, _| is_raw)
+    .active_if(id, move |data| has_raw && idle(data));
+
+    let reversed = match snip {
+        SnippetId::Draw(draw_id) => data.scribl.draw.snippet(draw_id).reversed(),
+        SnippetId::Talk(_) => false,
+    };
+    let reverse = MenuItem::new(
+        LocalizedString::new("scribl-menu-snippet-reverse").with_placeholder("Play backwards"),
+    )
+    .action(id, move |ctx, _| {
+        ctx.submit_command(cmd::TOGGLE_SNIPPET_REVERSED.with(snip))
+    })
+    .selected_if(move |_, _| reversed)
+    .active_if(id, move |data| {
+        matches!(snip, SnippetId::Draw(_)) && idle(data)
+    });
+
+    let current_label = data.snippet_label(snip);
+    let mut label = Menu::new(
+        LocalizedString::new("scribl-menu-snippet-label").with_placeholder("Label color"),
+    )
+    .entry(
+        MenuItem::new(
+            LocalizedString::new("scribl-menu-snippet-label-none").with_placeholder("None"),
+        )
+        .action(id, move |ctx, _| {
+            ctx.submit_command(cmd::SET_SNIPPET_LABEL.with((snip, None)))
+        })
+        .active_if(id, idle)
+        .selected(current_label.is_none()),
+    );
+    for &color in &LabelColor::ALL {
+        label = label.entry(
+            MenuItem::new(color.name())
+                .action(id, move |ctx, _| {
+                    ctx.submit_command(cmd::SET_SNIPPET_LABEL.with((snip, Some(color))))
+                })
+                .active_if(id, idle)
+                .selected(current_label == Some(color)),
+        );
+    }
+
+    let link = MenuItem::new(
+        LocalizedString::new("scribl-menu-snippet-link")
+            .with_placeholder("Link with overlapping snippets"),
+    )
+    .action(id, move |ctx, _| {
+        ctx.submit_command(cmd::LINK_SNIPPET.with(snip))
+    })
+    .active_if(id, idle);
+
+    let unlink = MenuItem::new(
+        LocalizedString::new("scribl-menu-snippet-unlink").with_placeholder("Unlink"),
+    )
+    .action(id, move |ctx, _| {
+        ctx.submit_command(cmd::UNLINK_SNIPPET.with(snip))
+    })
+    .active_if(id, move |data| {
+        idle(data) && data.snippet_group(snip).is_some()
+    });
+
+    let rename = MenuItem::new(
+        LocalizedString::new("scribl-menu-snippet-rename").with_placeholder("Rename..."),
+    )
+    .action(id, move |ctx, _| {
+        ctx.submit_command(cmd::RENAME_SNIPPET.with(snip))
+    })
+    .active_if(id, idle);
+
+    let properties = MenuItem::new(
+        LocalizedString::new("scribl-menu-snippet-properties").with_placeholder("Properties..."),
+    )
+    .action(id, move |ctx, _| {
+        ctx.submit_command(cmd::SHOW_SNIPPET_PROPERTIES.with(snip))
+    })
+    .active_if(id, move |data| {
+        data.has_snippet(snip) && data.action.is_idle()
+    });
+
+    let unhold = MenuItem::new(
+        LocalizedString::new("scribl-menu-snippet-remove-hold").with_placeholder("Remove hold"),
+    )
+    .action(id, move |_, data| data.remove_hold(snip))
+    .active_if(id, move |data| idle(data) && data.has_hold_at_time(snip));
+
+    let retime = MenuItem::new(
+        LocalizedString::new("scribl-menu-snippet-retime").with_placeholder("Retime..."),
+    )
+    .action(id, move |ctx, _| {
+        ctx.submit_command(cmd::RETIME_SNIPPET.with(snip))
+    })
+    .active_if(id, move |data| {
+        matches!(snip, SnippetId::Draw(_)) && idle(data)
+    });
+
+    Menu::empty()
+        .entry(delete)
+        .entry(duplicate)
+        .entry(split)
+        .entry(mute)
+        .entry(raw)
+        .entry(reverse)
+        .separator()
+        .entry(link)
+        .entry(unlink)
+        .separator()
+        .entry(label)
+        .entry(rename)
+        .entry(unhold)
+        .entry(retime)
+        .entry(properties)
+}
+
+fn debug_menu(id: WindowId, _data: &AppState) -> Menu<AppState> {
+    let overlay = MenuItem::new(
+        LocalizedString::new("scribl-menu-debug-overlay").with_placeholder("Show paint timings"),
+    )
+    .action(id, |_, data| {
+        data.settings.debug_overlay = !data.settings.debug_overlay
+    })
+    .selected_if(move |data, _env| {
+        data.editor(id)
+            .map(|e| e.settings.debug_overlay)
+            .unwrap_or(false)
+    });
+
+    Menu::new(LocalizedString::new("scribl-menu-debug").with_placeholder("Debug")).entry(overlay)
 }
 
 pub fn make_menu(window_id: Option<WindowId>, data: &AppState) -> Menu<AppState> {
@@ -304,7 +1606,11 @@ pub fn make_menu(window_id: Option<WindowId>, data: &AppState) -> Menu<AppState>
         Menu::empty()
             .entry(file_menu(id, data))
             .entry(edit_menu(id, data))
+            .entry(music_menu(id, data))
+            .entry(markers_menu(id, data))
+            .entry(pages_menu(id, data))
             .entry(view_menu(id, data))
+            .entry(debug_menu(id, data))
     } else {
         Menu::empty()
     }
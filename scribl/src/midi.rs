@@ -0,0 +1,164 @@
+//! Control by MIDI controllers, like foot pedals.
+//!
+//! If it's enabled in the config file, we listen to a MIDI device and turn its button presses into
+//! the same requests that the remote control makes (see [`crate::remote`]). That way, someone
+//! narrating can start and stop recording without taking their hands off the tablet.
+
+use anyhow::{anyhow, Result};
+use druid::{ExtEventSink, Target};
+use midir::{Ignore, MidiInput, MidiInputConnection};
+
+use crate::cmd;
+use crate::config::{Midi, MidiAction, MidiBinding};
+use crate::remote::{Method, RemoteRequest};
+
+// The top four bits of a MIDI status byte say what kind of message it is.
+const NOTE_ON: u8 = 0x90;
+const CONTROL_CHANGE: u8 = 0xB0;
+
+/// Controllers (like sustain pedals) count as pressed when their value is at least this much.
+const CONTROLLER_PRESSED: u8 = 64;
+
+/// A button press on a MIDI controller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Press {
+    Note { channel: u8, note: u8 },
+    Controller { channel: u8, controller: u8 },
+}
+
+/// Figures out whether a MIDI message is a button press. Channels are numbered from 1, like in the
+/// config file.
+fn parse_message(msg: &[u8]) -> Option<Press> {
+    let (status, data1, data2) = match *msg {
+        [status, data1, data2] => (status, data1, data2),
+        _ => return None,
+    };
+    let channel = (status & 0x0F) + 1;
+    match status & 0xF0 {
+        // A note-on with velocity zero is really a note-off.
+        NOTE_ON if data2 > 0 => Some(Press::Note {
+            channel,
+            note: data1,
+        }),
+        CONTROL_CHANGE if data2 >= CONTROLLER_PRESSED => Some(Press::Controller {
+            channel,
+            controller: data1,
+        }),
+        _ => None,
+    }
+}
+
+impl MidiBinding {
+    fn matches(&self, press: Press) -> bool {
+        let (channel, matches) = match press {
+            Press::Note { channel, note } => (channel, self.note == Some(note)),
+            Press::Controller {
+                channel,
+                controller,
+            } => (channel, self.controller == Some(controller)),
+        };
+        matches && self.channel.map_or(true, |c| c == channel)
+    }
+}
+
+impl From<MidiAction> for Method {
+    fn from(action: MidiAction) -> Method {
+        match action {
+            MidiAction::Play => Method::Play,
+            MidiAction::Pause => Method::Pause,
+            MidiAction::Stop => Method::Stop,
+            MidiAction::Draw => Method::Draw,
+            MidiAction::Talk => Method::Talk,
+        }
+    }
+}
+
+/// Returns the action bound to a MIDI message, if there is one.
+fn action(bindings: &[MidiBinding], msg: &[u8]) -> Option<MidiAction> {
+    let press = parse_message(msg)?;
+    bindings.iter().find(|b| b.matches(press)).map(|b| b.action)
+}
+
+/// Starts listening to a MIDI device. We keep listening until the returned connection is dropped.
+pub fn connect(config: &Midi, ext_cmd: ExtEventSink) -> Result<MidiInputConnection<()>> {
+    let mut input = MidiInput::new("scribl").map_err(|e| anyhow!("{}", e))?;
+    input.ignore(Ignore::All);
+    let ports = input.ports();
+    let port = ports
+        .iter()
+        .find(|p| {
+            input
+                .port_name(p)
+                .map_or(false, |name| name.contains(&config.device))
+        })
+        .ok_or_else(|| anyhow!("no MIDI device matching \"{}\"", config.device))?;
+    let name = input.port_name(port).map_err(|e| anyhow!("{}", e))?;
+    log::info!("listening to MIDI device {}", name);
+
+    let bindings = config.bindings.clone();
+    input
+        .connect(
+            port,
+            "scribl-input",
+            move |_, msg, _| {
+                if let Some(action) = action(&bindings, msg) {
+                    // The request gets handled the same way as a remote-control request. Nobody
+                    // is waiting for the reply, though.
+                    let (req, _) = RemoteRequest::new(action.into());
+                    let _ =
+                        ext_cmd.submit_command(cmd::REMOTE_REQUEST, Box::new(req), Target::Global);
+                }
+            },
+            (),
+        )
+        .map_err(|e| anyhow!("failed to connect to {}: {}", name, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(note: Option<u8>, controller: Option<u8>, channel: Option<u8>) -> MidiBinding {
+        MidiBinding {
+            note,
+            controller,
+            channel,
+            action: MidiAction::Talk,
+        }
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!(
+            parse_message(&[0x90, 60, 100]),
+            Some(Press::Note {
+                channel: 1,
+                note: 60
+            })
+        );
+        assert_eq!(
+            parse_message(&[0xB3, 64, 127]),
+            Some(Press::Controller {
+                channel: 4,
+                controller: 64
+            })
+        );
+        // Releases don't count.
+        assert_eq!(parse_message(&[0x90, 60, 0]), None);
+        assert_eq!(parse_message(&[0x80, 60, 100]), None);
+        assert_eq!(parse_message(&[0xB0, 64, 0]), None);
+        assert_eq!(parse_message(&[0xF8]), None);
+    }
+
+    #[test]
+    fn bindings() {
+        let pedal = [binding(None, Some(64), None)];
+        assert_eq!(action(&pedal, &[0xB5, 64, 127]), Some(MidiAction::Talk));
+        assert_eq!(action(&pedal, &[0xB0, 65, 127]), None);
+        assert_eq!(action(&pedal, &[0x90, 64, 127]), None);
+
+        let note = [binding(Some(60), None, Some(2))];
+        assert_eq!(action(&note, &[0x91, 60, 1]), Some(MidiAction::Talk));
+        assert_eq!(action(&note, &[0x90, 60, 1]), None);
+    }
+}
@@ -0,0 +1,109 @@
+//! Scribl relies on a bunch of gstreamer plugins, some of which aren't always installed. This
+//! module checks which of the ones we need are missing, so that we can tell the user about it
+//! (instead of just failing mysteriously when they try to record or export).
+
+use gstreamer as gst;
+
+/// The things that a gstreamer element might be needed for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    Recording,
+    Playback,
+    Export,
+}
+
+/// A gstreamer element that we use.
+#[derive(Debug)]
+pub struct RequiredElement {
+    /// The name of the element factory.
+    pub name: &'static str,
+    /// The gstreamer plugin collection that the element usually comes from.
+    pub collection: &'static str,
+    /// What we need the element for.
+    pub feature: Feature,
+}
+
+const fn elt(name: &'static str, collection: &'static str, feature: Feature) -> RequiredElement {
+    RequiredElement {
+        name,
+        collection,
+        feature,
+    }
+}
+
+const REQUIRED_ELEMENTS: &[RequiredElement] = &[
+    elt("autoaudiosrc", "gst-plugins-good", Feature::Recording),
+    elt("audioresample", "gst-plugins-base", Feature::Recording),
+    elt("audioconvert", "gst-plugins-base", Feature::Recording),
+    elt("tee", "gstreamer (core)", Feature::Recording),
+    elt("appsink", "gst-plugins-base", Feature::Recording),
+    elt("appsrc", "gst-plugins-base", Feature::Playback),
+    elt("scaletempo", "gst-plugins-good", Feature::Playback),
+    elt("autoaudiosink", "gst-plugins-good", Feature::Playback),
+    elt("videoconvert", "gst-plugins-base", Feature::Export),
+    elt("x264enc", "gst-plugins-ugly", Feature::Export),
+    elt("volume", "gst-plugins-base", Feature::Export),
+    elt("lamemp3enc", "gst-plugins-good", Feature::Export),
+    elt("mp4mux", "gst-plugins-good", Feature::Export),
+    elt("filesink", "gstreamer (core)", Feature::Export),
+    // Used for checking the exported file.
+    elt("qtdemux", "gst-plugins-good", Feature::Export),
+];
+
+impl Feature {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Feature::Recording => "recording audio",
+            Feature::Playback => "playing audio",
+            Feature::Export => "exporting video",
+        }
+    }
+}
+
+/// Returns all the gstreamer elements that we use.
+pub fn required_elements() -> &'static [RequiredElement] {
+    REQUIRED_ELEMENTS
+}
+
+/// Returns the list of required elements that couldn't be found. Gstreamer must be initialized
+/// before calling this.
+pub fn missing_elements() -> Vec<&'static RequiredElement> {
+    REQUIRED_ELEMENTS
+        .iter()
+        .filter(|e| gst::ElementFactory::find(e.name).is_none())
+        .collect()
+}
+
+/// Returns the list of missing elements that are needed for `feature`.
+pub fn missing_for(feature: Feature) -> Vec<&'static RequiredElement> {
+    missing_elements()
+        .into_iter()
+        .filter(|e| e.feature == feature)
+        .collect()
+}
+
+fn install_hint() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "Try reinstalling gstreamer using the \"complete\" installation option."
+    } else if cfg!(target_os = "macos") {
+        "Try installing them with \"brew install gst-plugins-base gst-plugins-good gst-plugins-ugly\"."
+    } else {
+        "Try installing them with your package manager (the packages are usually called \
+         something like gstreamer1.0-plugins-good and gstreamer1.0-plugins-ugly)."
+    }
+}
+
+/// Creates a human-readable description of what's missing, and how to fix it.
+pub fn describe_missing(missing: &[&RequiredElement]) -> String {
+    let mut ret = String::from("Some gstreamer plugins are missing, so some features won't work:\n");
+    for e in missing {
+        ret.push_str(&format!(
+            "  - {} (from {}), needed for {}\n",
+            e.name,
+            e.collection,
+            e.feature.description()
+        ));
+    }
+    ret.push_str(install_hint());
+    ret
+}
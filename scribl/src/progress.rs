@@ -0,0 +1,216 @@
+//! Reporting the progress of command-line exports.
+//!
+//! By default, progress is shown to people on stderr. It can also be written to stdout as a
+//! stream of JSON objects, one per line, so that scripts and other programs can keep track of
+//! what's going on.
+
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::encode::EncodingSummary;
+
+/// How to report progress.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressFormat {
+    /// A human-readable progress line on stderr.
+    Text,
+    /// JSON lines on stdout.
+    Json,
+}
+
+impl std::str::FromStr for ProgressFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<ProgressFormat> {
+        match s {
+            "text" => Ok(ProgressFormat::Text),
+            "json" => Ok(ProgressFormat::Json),
+            _ => Err(anyhow::anyhow!(
+                "unknown progress format \"{}\" (expected \"text\" or \"json\")",
+                s
+            )),
+        }
+    }
+}
+
+/// One line of the JSON progress stream.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// We started exporting a file.
+    Started {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        input: Option<&'a str>,
+        output: &'a str,
+    },
+    /// We're part way through exporting a file.
+    Progress {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        input: Option<&'a str>,
+        frame: u64,
+        out_of: u64,
+        /// How many frames per second we're encoding.
+        fps: f64,
+        /// The estimated number of seconds until we're done.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        eta: Option<f64>,
+    },
+    /// We finished exporting a file.
+    Finished {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        input: Option<&'a str>,
+        output: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        summary: Option<Summary>,
+    },
+    /// Exporting a file failed.
+    Failed {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        input: Option<&'a str>,
+        error: String,
+    },
+    /// A batch of exports is done. This is always the last line of a batch export.
+    Done { finished: usize, failed: usize },
+}
+
+/// How an export went, for the JSON progress stream. The times are in milliseconds, except for
+/// `seconds`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Summary {
+    pub frames: usize,
+    pub seconds: f64,
+    pub fps: f64,
+    pub min_frame_ms: f64,
+    pub median_frame_ms: f64,
+    pub max_frame_ms: f64,
+    /// The number of frames that took longer to render than the time they're shown for.
+    pub slow_frames: usize,
+}
+
+impl<'a> From<&'a EncodingSummary> for Summary {
+    fn from(summary: &'a EncodingSummary) -> Summary {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let frames = summary.frames.as_ref();
+        Summary {
+            frames: frames.map_or(0, |f| f.frames),
+            seconds: summary.elapsed.as_secs_f64(),
+            fps: summary.fps(),
+            min_frame_ms: frames.map_or(0.0, |f| ms(f.min)),
+            median_frame_ms: frames.map_or(0.0, |f| ms(f.median)),
+            max_frame_ms: frames.map_or(0.0, |f| ms(f.max)),
+            slow_frames: frames.map_or(0, |f| f.slow),
+        }
+    }
+}
+
+impl<'a> Event<'a> {
+    /// Writes this event to stdout, as a single line.
+    pub fn print(&self) {
+        // Serializing this can't fail: there are no maps, and all the strings are valid.
+        if let Ok(line) = serde_json::to_string(self) {
+            // `println` locks stdout, so lines from different threads won't get interleaved.
+            println!("{}", line);
+        }
+    }
+}
+
+/// Keeps track of how fast an export is going.
+pub struct Rate {
+    start: Instant,
+    last_percent: Option<u64>,
+}
+
+impl Rate {
+    /// Starts timing an export.
+    pub fn start() -> Rate {
+        Rate {
+            start: Instant::now(),
+            last_percent: None,
+        }
+    }
+
+    /// The number of frames per second that we've encoded so far.
+    pub fn fps(&self, frame: u64) -> f64 {
+        let secs = self.start.elapsed().as_secs_f64();
+        if secs > 0.0 {
+            frame as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// The estimated number of seconds left, if we've made enough progress to guess.
+    pub fn eta(&self, frame: u64, out_of: u64) -> Option<f64> {
+        let fps = self.fps(frame);
+        if fps > 0.0 {
+            Some(out_of.saturating_sub(frame) as f64 / fps)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the percentage of frames done is different from the last time this was
+    /// called. This is for limiting the number of progress events.
+    pub fn percent_changed(&mut self, frame: u64, out_of: u64) -> bool {
+        let percent = frame * 100 / out_of.max(1);
+        let changed = self.last_percent != Some(percent);
+        self.last_percent = Some(percent);
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_format() {
+        let line = serde_json::to_string(&Event::Progress {
+            input: Some("a.scb"),
+            frame: 3,
+            out_of: 10,
+            fps: 1.5,
+            eta: None,
+        })
+        .unwrap();
+        assert_eq!(
+            line,
+            r#"{"event":"progress","input":"a.scb","frame":3,"out_of":10,"fps":1.5}"#
+        );
+
+        let line = serde_json::to_string(&Event::Finished {
+            input: None,
+            output: "a.mp4",
+            summary: None,
+        })
+        .unwrap();
+        assert_eq!(line, r#"{"event":"finished","output":"a.mp4"}"#);
+
+        let line = serde_json::to_string(&Event::Finished {
+            input: None,
+            output: "a.mp4",
+            summary: Some(Summary {
+                frames: 30,
+                seconds: 2.0,
+                fps: 15.0,
+                min_frame_ms: 1.0,
+                median_frame_ms: 2.0,
+                max_frame_ms: 40.0,
+                slow_frames: 1,
+            }),
+        })
+        .unwrap();
+        assert_eq!(
+            line,
+            r#"{"event":"finished","output":"a.mp4","summary":{"frames":30,"seconds":2.0,"fps":15.0,"min_frame_ms":1.0,"median_frame_ms":2.0,"max_frame_ms":40.0,"slow_frames":1}}"#
+        );
+    }
+
+    #[test]
+    fn percent_changed() {
+        let mut rate = Rate::start();
+        assert!(rate.percent_changed(0, 200));
+        assert!(!rate.percent_changed(1, 200));
+        assert!(rate.percent_changed(2, 200));
+    }
+}
@@ -0,0 +1,502 @@
+//! Remote control over a local socket.
+//!
+//! If it's enabled in the config file, we listen on a TCP port on localhost for JSON-RPC 2.0
+//! requests, one per line. Each request gets a response on a single line. This is meant for
+//! things like stream decks and scripts: they can start and stop playback and recording, start
+//! exports, and ask what time it is.
+//!
+//! Since any program on this computer (including web pages, which can send requests to localhost)
+//! can connect to the port, every connection has to start by sending the token from the config
+//! file, and we hang up on anything that isn't JSON-RPC. Exports can only create new files in the
+//! export directory.
+//!
+//! The server runs on its own threads. Requests get passed to the UI as commands (see
+//! [`cmd::REMOTE_REQUEST`]), and the UI replies on a channel.
+
+use anyhow::{anyhow, Result};
+use crossbeam_channel::{bounded, Receiver, Sender};
+use directories_next::UserDirs;
+use druid::{ExtEventSink, Target};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cmd;
+use crate::config::Remote;
+use crate::{CurrentAction, EditorState};
+
+// How long we wait for the UI to answer a request.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// The longest line (in bytes, including the newline) that we accept. None of the requests need
+// to be anywhere near this long, so we hang up on anyone who sends a longer one.
+const MAX_LINE: usize = 64 * 1024;
+
+// Error codes from the JSON-RPC spec.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// JSON-RPC leaves the codes from -32000 to -32099 for us.
+const FAILED: i64 = -32000;
+const UNAUTHORIZED: i64 = -32001;
+
+/// The things that can be asked of us remotely.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Method {
+    /// Starts playing.
+    Play,
+    /// Stops playing (but not recording).
+    Pause,
+    /// Stops whatever is going on (playing or recording).
+    Stop,
+    /// Starts recording a drawing.
+    Draw,
+    /// Starts recording audio.
+    Talk,
+    /// Adds the whole animation to the export queue.
+    Export(PathBuf),
+    /// Doesn't do anything; this is just for getting the status.
+    Status,
+}
+
+/// A request for the UI, along with a way to reply to it.
+#[derive(Clone)]
+pub struct RemoteRequest {
+    pub method: Method,
+    reply: Sender<Result<Value, String>>,
+}
+
+impl RemoteRequest {
+    /// Creates a request, along with the channel that the reply will arrive on.
+    pub fn new(method: Method) -> (RemoteRequest, Receiver<Result<Value, String>>) {
+        let (tx, rx) = bounded(1);
+        (RemoteRequest { method, reply: tx }, rx)
+    }
+
+    /// Sends the result back to whoever made the request.
+    pub fn reply(&self, result: Result<Value, String>) {
+        let _ = self.reply.send(result);
+    }
+}
+
+/// The result of every successful request: what we're doing, and what time it is.
+pub fn status(data: &EditorState) -> Value {
+    let action = match data.action {
+        CurrentAction::Recording(_) => "drawing",
+        CurrentAction::Playing(_) => "playing",
+        CurrentAction::RecordingAudio(_) | CurrentAction::PunchingIn(_) => "talking",
+        CurrentAction::Scanning(_) => "scanning",
+        CurrentAction::Idle | CurrentAction::EditingText => "idle",
+        CurrentAction::Loading => "loading",
+        CurrentAction::WaitingToExit => "exiting",
+    };
+    json!({
+        "action": action,
+        "time": data.accurate_time().as_micros() as f64 / 1e6,
+    })
+}
+
+#[derive(Debug, PartialEq)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct ExportParams {
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct AuthParams {
+    token: String,
+}
+
+/// The parts of the config that the connections need.
+struct ServerConfig {
+    token: String,
+    export_dir: PathBuf,
+}
+
+/// Starts listening for remote-control connections.
+pub fn spawn(config: &Remote, ext_cmd: ExtEventSink) -> Result<()> {
+    if config.token.is_empty() {
+        return Err(anyhow!(
+            "the remote control needs a token in the config file"
+        ));
+    }
+    let export_dir = match &config.export_dir {
+        Some(dir) => dir.clone(),
+        None => UserDirs::new()
+            .and_then(|d| d.video_dir().map(Path::to_owned))
+            .ok_or_else(|| anyhow!("couldn't find the videos directory"))?,
+    };
+    let server_config = Arc::new(ServerConfig {
+        token: config.token.clone(),
+        export_dir,
+    });
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, config.port))?;
+    log::info!("listening for remote control on {}", listener.local_addr()?);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let ext_cmd = ext_cmd.clone();
+                    let config = Arc::clone(&server_config);
+                    std::thread::spawn(move || {
+                        if let Err(e) = serve(stream, &ext_cmd, &config) {
+                            log::warn!("remote control connection failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => log::warn!("failed to accept remote control connection: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn serve(stream: TcpStream, ext_cmd: &ExtEventSink, config: &ServerConfig) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut conn = Connection::default();
+    let mut reader = BufReader::new(stream);
+    loop {
+        let line = match read_line(&mut reader) {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) if e.kind() == ErrorKind::InvalidData => {
+                log::warn!("closing a remote control connection: {}", e);
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match conn.handle_line(&line, config) {
+            Reply::Now(resp) => writeln!(writer, "{}", resp)?,
+            Reply::Hangup(resp) => {
+                writeln!(writer, "{}", resp)?;
+                log::warn!("closing a remote control connection that sent a bad request");
+                break;
+            }
+            Reply::Forward(id, method) => writeln!(writer, "{}", forward(id, method, ext_cmd))?,
+        }
+    }
+    Ok(())
+}
+
+/// Reads a line (without the newline) from `reader`, or returns `None` at the end of the stream.
+/// Lines that are longer than `MAX_LINE` (or aren't UTF-8) are `InvalidData` errors.
+fn read_line(reader: &mut impl BufRead) -> std::io::Result<Option<String>> {
+    let mut line = String::new();
+    let len = reader.take(MAX_LINE as u64).read_line(&mut line)?;
+    if len == 0 {
+        return Ok(None);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    } else if len == MAX_LINE {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "the line was too long",
+        ));
+    }
+    Ok(Some(line))
+}
+
+/// What to do about a line that a connection sent us.
+#[derive(Debug, PartialEq)]
+enum Reply {
+    /// Send this response.
+    Now(String),
+    /// Send this response, and then close the connection.
+    Hangup(String),
+    /// Ask the UI to do this, and send back what it says.
+    Forward(Value, Method),
+}
+
+#[derive(Default)]
+struct Connection {
+    authenticated: bool,
+}
+
+impl Connection {
+    fn handle_line(&mut self, line: &str, config: &ServerConfig) -> Reply {
+        // If it isn't JSON-RPC, it could be something like a web page trying to send us an HTTP
+        // request, so we hang up.
+        let req = match parse_request(line) {
+            Ok(req) => req,
+            Err(e) => return Reply::Hangup(response(Value::Null, Err(e))),
+        };
+
+        if req.method == "auth" {
+            let authenticated = serde_json::from_value::<AuthParams>(req.params)
+                .map_or(false, |p| same_token(&p.token, &config.token));
+            return if authenticated {
+                self.authenticated = true;
+                Reply::Now(response(req.id, Ok(json!(true))))
+            } else {
+                Reply::Hangup(response(
+                    req.id,
+                    Err(RpcError::new(UNAUTHORIZED, "wrong token")),
+                ))
+            };
+        } else if !self.authenticated {
+            return Reply::Hangup(response(
+                req.id,
+                Err(RpcError::new(
+                    UNAUTHORIZED,
+                    "the first request must be \"auth\"",
+                )),
+            ));
+        }
+
+        match parse_method(&req, config) {
+            Ok(method) => Reply::Forward(req.id, method),
+            Err(e) => Reply::Now(response(req.id, Err(e))),
+        }
+    }
+}
+
+/// Compares tokens without giving away (through the time it takes) how much of them matched.
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// Passes a request on to the UI, returning the response.
+fn forward(id: Value, method: Method, ext_cmd: &ExtEventSink) -> String {
+    let (req, rx) = RemoteRequest::new(method);
+    if ext_cmd
+        .submit_command(cmd::REMOTE_REQUEST, Box::new(req), Target::Global)
+        .is_err()
+    {
+        return response(id, Err(RpcError::new(FAILED, "scribl is shutting down")));
+    }
+    let result = match rx.recv_timeout(REPLY_TIMEOUT) {
+        Ok(result) => result.map_err(|e| RpcError::new(FAILED, e)),
+        Err(_) => Err(RpcError::new(FAILED, "timed out waiting for scribl")),
+    };
+    response(id, result)
+}
+
+/// Parses a request, failing if it isn't JSON-RPC.
+fn parse_request(line: &str) -> std::result::Result<Request, RpcError> {
+    let value: Value =
+        serde_json::from_str(line).map_err(|e| RpcError::new(PARSE_ERROR, e.to_string()))?;
+    serde_json::from_value(value).map_err(|e| RpcError::new(INVALID_REQUEST, e.to_string()))
+}
+
+/// Works out what a request is asking for.
+fn parse_method(req: &Request, config: &ServerConfig) -> std::result::Result<Method, RpcError> {
+    let method = match req.method.as_str() {
+        "play" => Method::Play,
+        "pause" => Method::Pause,
+        "stop" => Method::Stop,
+        "draw" => Method::Draw,
+        "talk" => Method::Talk,
+        "status" => Method::Status,
+        "export" => {
+            let params: ExportParams = serde_json::from_value(req.params.clone())
+                .map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?;
+            let path = export_path(&params.path, &config.export_dir)
+                .map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            Method::Export(path)
+        }
+        m => {
+            return Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method \"{}\"", m),
+            ))
+        }
+    };
+    Ok(method)
+}
+
+/// Remote exports can only create new files in the export directory, so `path` has to be a
+/// relative path that stays in it.
+fn export_path(path: &Path, dir: &Path) -> std::result::Result<PathBuf, String> {
+    let mut components = path.components().peekable();
+    if components.peek().is_none() || !components.all(|c| matches!(c, Component::Normal(_))) {
+        return Err(format!(
+            "\"{}\" isn't a file name in the export directory",
+            path.display()
+        ));
+    }
+    let mut ret = dir.join(path);
+    if ret.extension().is_none() {
+        ret.set_extension("mp4");
+    }
+    if ret.exists() {
+        return Err(format!("\"{}\" already exists", ret.display()));
+    }
+    Ok(ret)
+}
+
+fn response(id: Value, result: std::result::Result<Value, RpcError>) -> String {
+    let resp = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": e.code, "message": e.message },
+        }),
+    };
+    resp.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(dir: &Path) -> ServerConfig {
+        ServerConfig {
+            token: "secret".to_owned(),
+            export_dir: dir.to_owned(),
+        }
+    }
+
+    #[test]
+    fn long_lines() {
+        let line = |reader: &mut &[u8]| read_line(reader).unwrap();
+        let mut input = b"{}\r\nshort\n".to_vec();
+        input.extend(vec![b'x'; MAX_LINE]);
+        input.extend(b"\nnever read\n");
+        let mut reader = &input[..];
+        assert_eq!(line(&mut reader), Some("{}".to_owned()));
+        assert_eq!(line(&mut reader), Some("short".to_owned()));
+        let err = read_line(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        // A line that's just short enough, and one at the end without a newline.
+        let mut input = vec![b'x'; MAX_LINE - 1];
+        input.extend(b"\nlast");
+        let mut reader = &input[..];
+        assert_eq!(line(&mut reader).unwrap().len(), MAX_LINE - 1);
+        assert_eq!(line(&mut reader), Some("last".to_owned()));
+        assert_eq!(line(&mut reader), None);
+    }
+
+    fn method(line: &str, config: &ServerConfig) -> std::result::Result<Method, RpcError> {
+        parse_method(&parse_request(line).unwrap(), config)
+    }
+
+    #[test]
+    fn parsing() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path());
+        assert_eq!(
+            method(r#"{"jsonrpc": "2.0", "id": 1, "method": "play"}"#, &config),
+            Ok(Method::Play)
+        );
+        assert_eq!(
+            method(
+                r#"{"id": "a", "method": "export", "params": {"path": "out"}}"#,
+                &config
+            ),
+            Ok(Method::Export(dir.path().join("out.mp4")))
+        );
+
+        assert_eq!(parse_request("{").unwrap_err().code, PARSE_ERROR);
+        assert_eq!(
+            parse_request(r#"{"id": 1}"#).unwrap_err().code,
+            INVALID_REQUEST
+        );
+        assert_eq!(
+            method(r#"{"id": 2, "method": "fly"}"#, &config),
+            Err(RpcError::new(METHOD_NOT_FOUND, "unknown method \"fly\""))
+        );
+        assert_eq!(
+            method(r#"{"id": 3, "method": "export"}"#, &config)
+                .unwrap_err()
+                .code,
+            INVALID_PARAMS
+        );
+    }
+
+    #[test]
+    fn export_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("taken.mp4"), b"").unwrap();
+        assert_eq!(
+            export_path(Path::new("sub/out.webm"), dir.path()),
+            Ok(dir.path().join("sub/out.webm"))
+        );
+        assert!(export_path(Path::new("taken"), dir.path()).is_err());
+        assert!(export_path(Path::new("../out.mp4"), dir.path()).is_err());
+        assert!(export_path(Path::new("/etc/passwd"), dir.path()).is_err());
+        assert!(export_path(Path::new(""), dir.path()).is_err());
+    }
+
+    #[test]
+    fn authentication() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path());
+        let play = r#"{"id": 1, "method": "play"}"#;
+        let is_hangup = |r: Reply| matches!(r, Reply::Hangup(_));
+
+        // Nothing works before authenticating.
+        assert!(is_hangup(Connection::default().handle_line(play, &config)));
+        // The wrong token gets us hung up on.
+        let wrong = r#"{"id": 0, "method": "auth", "params": {"token": "guess"}}"#;
+        assert!(is_hangup(Connection::default().handle_line(wrong, &config)));
+
+        let mut conn = Connection::default();
+        let right = r#"{"id": 0, "method": "auth", "params": {"token": "secret"}}"#;
+        assert_eq!(
+            conn.handle_line(right, &config),
+            Reply::Now(r#"{"id":0,"jsonrpc":"2.0","result":true}"#.to_owned())
+        );
+        assert_eq!(
+            conn.handle_line(play, &config),
+            Reply::Forward(json!(1), Method::Play)
+        );
+        // Unknown methods are fine, but things that aren't JSON-RPC aren't.
+        let fly = r#"{"id": 2, "method": "fly"}"#;
+        assert!(matches!(conn.handle_line(fly, &config), Reply::Now(_)));
+        assert!(is_hangup(conn.handle_line("POST / HTTP/1.1", &config)));
+    }
+
+    #[test]
+    fn responses() {
+        assert_eq!(
+            response(json!(1), Ok(json!({"time": 1.5}))),
+            r#"{"id":1,"jsonrpc":"2.0","result":{"time":1.5}}"#
+        );
+        assert_eq!(
+            response(Value::Null, Err(RpcError::new(FAILED, "oops"))),
+            r#"{"error":{"code":-32000,"message":"oops"},"id":null,"jsonrpc":"2.0"}"#
+        );
+    }
+}
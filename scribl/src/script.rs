@@ -0,0 +1,349 @@
+//! Automating edits with [Rhai](https://rhai.rs) scripts.
+//!
+//! Scripts get a small set of functions for looking at and changing an animation: they can list,
+//! shift, copy, rename and delete snippets, add and remove markers, move the current time, and
+//! ask for exports. Times are always in seconds. Scripts can't touch the file system (apart from
+//! naming the files to export to), and they get stopped if they run for too long.
+//!
+//! Scripts run the same way from the script console and from `scribl run-script`: they work on a
+//! copy of the animation, and the caller decides what to do with the result.
+
+use anyhow::{anyhow, Result};
+use rhai::{Array, Dynamic, Engine, EvalAltResult};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use scribl_curves::{Time, TimeDiff};
+
+use crate::{ScriblState, SnippetId};
+
+// Limits on how much work a script can do, so that an infinite loop doesn't hang the UI.
+const MAX_OPERATIONS: u64 = 10_000_000;
+const MAX_EXPR_DEPTH: usize = 64;
+
+type FnResult<T> = std::result::Result<T, Box<EvalAltResult>>;
+
+/// The result of running a script.
+#[derive(Clone, Default)]
+pub struct ScriptOutput {
+    /// The animation, after the script's changes.
+    pub scribl: ScriblState,
+    /// The current time, after the script is done.
+    pub time: Time,
+    /// The files that the script asked to export the animation to, in order.
+    pub exports: Vec<PathBuf>,
+    /// Everything that the script printed.
+    pub log: Vec<String>,
+}
+
+fn to_secs(time: Time) -> f64 {
+    time.as_micros() as f64 / 1e6
+}
+
+fn from_secs(secs: f64) -> Time {
+    Time::from_micros(((secs * 1e6).round() as i64).max(0))
+}
+
+/// Scripts are allowed to write times as either integers or floats.
+fn secs(val: Dynamic) -> FnResult<f64> {
+    if let Ok(x) = val.as_float() {
+        Ok(x)
+    } else if let Ok(x) = val.as_int() {
+        Ok(x as f64)
+    } else {
+        Err(format!("expected a number of seconds, found {}", val.type_name()).into())
+    }
+}
+
+fn check_exists(scribl: &ScriblState, id: SnippetId) -> FnResult<()> {
+    let exists = match id {
+        SnippetId::Draw(id) => scribl.draw.has_snippet(id),
+        SnippetId::Talk(id) => scribl.talk.has_snippet(id),
+    };
+    if exists {
+        Ok(())
+    } else {
+        Err("this snippet has been deleted".into())
+    }
+}
+
+fn start_time(scribl: &ScriblState, id: SnippetId) -> Time {
+    match id {
+        SnippetId::Draw(id) => scribl.draw.snippet(id).start_time(),
+        SnippetId::Talk(id) => scribl.talk.snippet(id).start_time(),
+    }
+}
+
+/// The snippets, sorted by start time.
+fn sorted(mut snips: Vec<(Time, SnippetId)>) -> Array {
+    snips.sort_by_key(|(time, _)| *time);
+    snips.into_iter().map(|(_, id)| Dynamic::from(id)).collect()
+}
+
+fn shift(scribl: &mut ScriblState, id: SnippetId, by: TimeDiff) {
+    match id {
+        SnippetId::Draw(id) => scribl.draw = scribl.draw.with_shifted_snippet(id, by),
+        SnippetId::Talk(id) => scribl.talk = scribl.talk.with_shifted_snippet(id, by),
+    }
+}
+
+fn engine(state: &Rc<RefCell<ScriptOutput>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH);
+
+    let st = state.clone();
+    engine.on_print(move |s| st.borrow_mut().log.push(s.to_owned()));
+    let st = state.clone();
+    engine.on_debug(move |s, _, _| st.borrow_mut().log.push(s.to_owned()));
+
+    engine.register_type_with_name::<SnippetId>("Snippet");
+    let st = state.clone();
+    engine.register_get("start", move |id: &mut SnippetId| -> FnResult<f64> {
+        let st = st.borrow();
+        check_exists(&st.scribl, *id)?;
+        Ok(to_secs(start_time(&st.scribl, *id)))
+    });
+    // Drawings that stay until the end of the animation don't have an end time.
+    let st = state.clone();
+    engine.register_get("end", move |id: &mut SnippetId| -> FnResult<Dynamic> {
+        let st = st.borrow();
+        check_exists(&st.scribl, *id)?;
+        let end = match *id {
+            SnippetId::Draw(id) => st.scribl.draw.snippet(id).end_time(),
+            SnippetId::Talk(id) => Some(st.scribl.talk.snippet(id).end_time()),
+        };
+        Ok(end.map_or(Dynamic::UNIT, |t| Dynamic::from(to_secs(t))))
+    });
+    let st = state.clone();
+    engine.register_get("name", move |id: &mut SnippetId| -> FnResult<String> {
+        let st = st.borrow();
+        check_exists(&st.scribl, *id)?;
+        let name = match *id {
+            SnippetId::Draw(id) => st.scribl.draw.snippet(id).name(),
+            SnippetId::Talk(id) => st.scribl.talk.snippet(id).name(),
+        };
+        Ok(name.unwrap_or_default().to_owned())
+    });
+    engine.register_get("kind", |id: &mut SnippetId| -> String {
+        match id {
+            SnippetId::Draw(_) => "drawing".to_owned(),
+            SnippetId::Talk(_) => "audio".to_owned(),
+        }
+    });
+
+    let st = state.clone();
+    engine.register_fn("drawings", move || -> Array {
+        let st = st.borrow();
+        sorted(
+            st.scribl
+                .draw
+                .snippets()
+                .map(|(id, s)| (s.start_time(), id.into()))
+                .collect(),
+        )
+    });
+    let st = state.clone();
+    engine.register_fn("audio", move || -> Array {
+        let st = st.borrow();
+        sorted(
+            st.scribl
+                .talk
+                .snippets()
+                .map(|(id, s)| (s.start_time(), id.into()))
+                .collect(),
+        )
+    });
+
+    let st = state.clone();
+    engine.register_fn("shift", move |id: SnippetId, by: Dynamic| -> FnResult<()> {
+        let mut st = st.borrow_mut();
+        check_exists(&st.scribl, id)?;
+        let start = start_time(&st.scribl, id);
+        // Snippets can't be shifted to before the start of the animation.
+        let by = from_secs(to_secs(start) + secs(by)?) - start;
+        shift(&mut st.scribl, id, by);
+        Ok(())
+    });
+    let st = state.clone();
+    engine.register_fn(
+        "copy",
+        move |id: SnippetId, to: Dynamic| -> FnResult<SnippetId> {
+            let mut st = st.borrow_mut();
+            check_exists(&st.scribl, id)?;
+            let by = from_secs(secs(to)?) - start_time(&st.scribl, id);
+            let new_id: SnippetId = match id {
+                SnippetId::Draw(id) => {
+                    let snip = st.scribl.draw.snippet(id).clone();
+                    st.scribl.add_draw_snippet(snip).into()
+                }
+                SnippetId::Talk(id) => {
+                    let snip = st.scribl.talk.snippet(id).clone();
+                    st.scribl.add_talk_snippet(snip).into()
+                }
+            };
+            shift(&mut st.scribl, new_id, by);
+            Ok(new_id)
+        },
+    );
+    let st = state.clone();
+    engine.register_fn("delete", move |id: SnippetId| -> FnResult<()> {
+        let mut st = st.borrow_mut();
+        check_exists(&st.scribl, id)?;
+        match id {
+            SnippetId::Draw(id) => st.scribl.delete_draw_snippet(id),
+            SnippetId::Talk(id) => st.scribl.delete_talk_snippet(id),
+        }
+        Ok(())
+    });
+    let st = state.clone();
+    engine.register_fn("rename", move |id: SnippetId, name: &str| -> FnResult<()> {
+        let mut st = st.borrow_mut();
+        check_exists(&st.scribl, id)?;
+        let name = Some(name.to_owned()).filter(|n| !n.is_empty());
+        match id {
+            SnippetId::Draw(id) => {
+                let snip = st.scribl.draw.snippet(id).with_name(name);
+                st.scribl.draw = st.scribl.draw.with_replacement_snippet(id, snip);
+            }
+            SnippetId::Talk(id) => {
+                st.scribl.talk = st.scribl.talk.with_snippet_name(id, name);
+            }
+        }
+        Ok(())
+    });
+
+    let st = state.clone();
+    engine.register_fn(
+        "add_marker",
+        move |time: Dynamic, name: &str| -> FnResult<()> {
+            let mut st = st.borrow_mut();
+            st.scribl.markers = st.scribl.markers.with_marker(from_secs(secs(time)?), name);
+            Ok(())
+        },
+    );
+    let st = state.clone();
+    engine.register_fn("remove_marker", move |time: Dynamic| -> FnResult<()> {
+        let mut st = st.borrow_mut();
+        st.scribl.markers = st.scribl.markers.without_marker(from_secs(secs(time)?));
+        Ok(())
+    });
+
+    let st = state.clone();
+    engine.register_fn("time", move || to_secs(st.borrow().time));
+    let st = state.clone();
+    engine.register_fn("set_time", move |time: Dynamic| -> FnResult<()> {
+        st.borrow_mut().time = from_secs(secs(time)?);
+        Ok(())
+    });
+    let st = state.clone();
+    engine.register_fn("end_time", move || to_secs(st.borrow().scribl.end_time()));
+
+    let st = state.clone();
+    engine.register_fn("export", move |path: &str| {
+        let mut path = PathBuf::from(path);
+        if path.extension().is_none() {
+            path.set_extension("mp4");
+        }
+        st.borrow_mut().exports.push(path);
+    });
+
+    engine
+}
+
+/// Runs a script on a copy of `scribl`, starting at time `time`.
+///
+/// If the script fails, none of its changes get kept; the error message includes the line where
+/// the script failed.
+pub fn run(source: &str, scribl: ScriblState, time: Time) -> Result<ScriptOutput> {
+    let state = Rc::new(RefCell::new(ScriptOutput {
+        scribl,
+        time,
+        ..ScriptOutput::default()
+    }));
+    let engine = engine(&state);
+    let result = engine.run(source);
+    // The engine's callbacks hold references to the state, so drop them before unwrapping it.
+    drop(engine);
+    result.map_err(|e| anyhow!("{}", e))?;
+
+    let ret = state.borrow().clone();
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::TalkSnippet;
+
+    fn at(s: i64) -> Time {
+        Time::from_micros(s * 1_000_000)
+    }
+
+    fn scribl() -> ScriblState {
+        let mut ret = ScriblState::default();
+        ret.add_talk_snippet(TalkSnippet::new(vec![0; 10], at(2), 1.0));
+        ret.add_talk_snippet(TalkSnippet::new(vec![0; 10], at(1), 1.0));
+        ret
+    }
+
+    fn starts(scribl: &ScriblState) -> Vec<Time> {
+        let mut ret: Vec<_> = scribl
+            .talk
+            .snippets()
+            .map(|(_, s)| s.start_time())
+            .collect();
+        ret.sort();
+        ret
+    }
+
+    #[test]
+    fn shift_and_delete() {
+        let out = run(
+            "let a = audio(); shift(a[0], 3); delete(a[1]); print(a[0].start);",
+            scribl(),
+            Time::ZERO,
+        )
+        .unwrap();
+        assert_eq!(starts(&out.scribl), vec![at(4)]);
+        assert_eq!(out.log, vec!["4.0".to_owned()]);
+    }
+
+    #[test]
+    fn copy_and_markers() {
+        let out = run(
+            "for s in audio() { copy(s, s.start + 10.0); add_marker(s.start, \"here\"); }",
+            scribl(),
+            Time::ZERO,
+        )
+        .unwrap();
+        assert_eq!(starts(&out.scribl), vec![at(1), at(2), at(11), at(12)]);
+        assert_eq!(out.scribl.markers.get(at(1)), Some("here"));
+        assert_eq!(out.scribl.markers.get(at(2)), Some("here"));
+    }
+
+    #[test]
+    fn time_and_export() {
+        let out = run(
+            "set_time(end_time()); export(\"out\");",
+            scribl(),
+            Time::ZERO,
+        )
+        .unwrap();
+        assert_eq!(out.time, out.scribl.end_time());
+        assert_eq!(out.exports, vec![PathBuf::from("out.mp4")]);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(run(
+            "let a = audio(); delete(a[0]); delete(a[0]);",
+            scribl(),
+            Time::ZERO
+        )
+        .is_err());
+        assert!(run("shift(audio()[0], \"soon\");", scribl(), Time::ZERO).is_err());
+        assert!(run("loop {}", scribl(), Time::ZERO).is_err());
+    }
+}
@@ -3,6 +3,16 @@
 //! The rough idea is: we lay out the snippets from top to bottom. At each step, we "drape" the new
 //! snippet around the "contours" of the existing snippets. More precisely, we use a skyline data
 //! structure to represent the existing snippets.
+//!
+//! The height of the result depends a lot on the order in which the snippets are added, so we
+//! also try adding them "track by track": we assign the snippets to tracks (like coloring an
+//! interval graph, so that snippets in the same track don't overlap in time) and then add the
+//! snippets in the bottom track first. Usually that ends up lower than adding them in their
+//! original order but not always, so we keep whichever of the two layouts is lower.
+//!
+//! Both of those can move lots of snippets around when one of them changes, so there's also a
+//! "stable" layout that assigns tracks (or "rows") the same way, except that snippets stay in the
+//! row they were in last time unless they no longer fit there.
 
 use druid::kurbo::{BezPath, Point, Rect, Vec2};
 use std::collections::HashMap;
@@ -199,6 +209,13 @@ impl SnippetShape {
         ret
     }
 
+    /// Moves this shape down by `dy`.
+    pub fn translate_y(&mut self, dy: f64) {
+        for r in &mut self.rects {
+            *r = *r + Vec2::new(0.0, dy);
+        }
+    }
+
     /// Reflects this shape vertically, so that `0.0` is mapped to `bottom`, `1.0` is mapped to
     /// `bottom - 1.0`, etc.
     pub fn reflect_y(&mut self, bottom: f64) {
@@ -504,6 +521,8 @@ pub struct Layout<T> {
     /// The maximum height of any snippet. This is redundant, in that it can be recomputed from
     /// `positions`.
     pub max_y: f64,
+    /// The row of each snippet, if the snippets were laid out row by row.
+    pub rows: HashMap<T, usize>,
 }
 
 #[derive(Clone)]
@@ -549,7 +568,80 @@ impl From<(TalkSnippetId, &TalkSnippet)> for SnippetBounds<TalkSnippetId> {
     }
 }
 
-pub fn layout<Id: Copy + Hash + Eq + Ord, T: Into<SnippetBounds<Id>>, I: Iterator<Item = T>>(
+impl<T> SnippetBounds<T> {
+    /// The horizontal extent of this snippet, including enough padding that snippets whose
+    /// extents don't overlap will never be stacked on top of one another.
+    fn x_extent(&self, params: &Parameters) -> (f64, f64) {
+        let p = |x: Time| x.as_micros() as f64 * params.pixels_per_usec;
+        let start = p(self.start) - params.h_padding;
+        let end = self.end.map(p).unwrap_or(params.end_x);
+        let end = end.max(p(self.start) + params.min_width) + params.h_padding + params.overlap;
+        (start, end)
+    }
+}
+
+/// Assigns the snippets to rows, so that snippets in the same row don't overlap. Snippets with a
+/// hint go in their hinted row if they fit; everything else goes in the lowest row that it fits
+/// in. Snippets are considered in order of their start times (those with hints first), with ties
+/// broken by id, so the result doesn't depend on the original order.
+///
+/// Without any hints, this is the usual greedy coloring of an interval graph, which uses as few
+/// rows as possible.
+///
+/// The return value is sorted by row, and within each row by start time.
+fn assign_rows<'a, Id: Copy + Ord>(
+    bounds: &'a [SnippetBounds<Id>],
+    hint: impl Fn(Id) -> Option<usize>,
+    params: &Parameters,
+) -> Vec<(usize, &'a SnippetBounds<Id>)> {
+    let mut sorted: Vec<_> = bounds
+        .iter()
+        .map(|b| (hint(b.id), b, b.x_extent(params)))
+        .collect();
+    sorted.sort_by(|(a_hint, a, a_ext), (b_hint, b, b_ext)| {
+        a_hint
+            .is_none()
+            .cmp(&b_hint.is_none())
+            .then_with(|| a_ext.0.partial_cmp(&b_ext.0).unwrap())
+            .then_with(|| a.id.cmp(&b.id))
+    });
+
+    // For each row, the extents of the snippets in it.
+    let mut rows: Vec<Vec<(f64, f64)>> = Vec::new();
+    let fits = |row: &[(f64, f64)], (start, end): (f64, f64)| {
+        row.iter().all(|&(s, e)| e <= start || end <= s)
+    };
+    let mut ret = Vec::with_capacity(sorted.len());
+    for (hint, b, ext) in sorted {
+        let row = match hint {
+            Some(h) if rows.get(h).map_or(true, |r| fits(r, ext)) => h,
+            _ => rows.iter().position(|r| fits(r, ext)).unwrap_or(rows.len()),
+        };
+        if row >= rows.len() {
+            rows.resize(row + 1, Vec::new());
+        }
+        rows[row].push(ext);
+        ret.push((row, b));
+    }
+
+    // The sort is stable, so within each row the snippets stay sorted by start time.
+    ret.sort_by_key(|&(row, _)| row);
+    ret
+}
+
+/// Orders the snippets by track, with no hints about where they should go.
+fn track_order<'a, Id: Copy + Ord>(
+    bounds: &'a [SnippetBounds<Id>],
+    params: &Parameters,
+) -> Vec<&'a SnippetBounds<Id>> {
+    assign_rows(bounds, |_| None, params)
+        .into_iter()
+        .map(|(_, b)| b)
+        .collect()
+}
+
+/// Lays out the snippets by adding them one by one, in the given order, to the skyline.
+fn skyline_layout<'a, Id: 'a + Copy + Hash + Eq, I: Iterator<Item = &'a SnippetBounds<Id>>>(
     iter: I,
     params: &Parameters,
 ) -> Layout<Id> {
@@ -557,10 +649,11 @@ pub fn layout<Id: Copy + Hash + Eq + Ord, T: Into<SnippetBounds<Id>>, I: Iterato
     let mut ret = Layout {
         positions: HashMap::new(),
         max_y: 0.0,
+        rows: HashMap::new(),
     };
 
-    for b in iter.map(|t| t.into()) {
-        let shape = sky.add_snippet(&b, params);
+    for b in iter {
+        let shape = sky.add_snippet(b, params);
         ret.max_y = ret.max_y.max(
             shape
                 .rects
@@ -575,9 +668,43 @@ pub fn layout<Id: Copy + Hash + Eq + Ord, T: Into<SnippetBounds<Id>>, I: Iterato
     ret
 }
 
+pub fn layout<Id: Copy + Hash + Eq + Ord, T: Into<SnippetBounds<Id>>, I: Iterator<Item = T>>(
+    iter: I,
+    params: &Parameters,
+) -> Layout<Id> {
+    let bounds: Vec<SnippetBounds<Id>> = iter.map(|t| t.into()).collect();
+    let in_order = skyline_layout(bounds.iter(), params);
+    let by_track = skyline_layout(track_order(&bounds, params).into_iter(), params);
+    if by_track.max_y < in_order.max_y {
+        by_track
+    } else {
+        in_order
+    }
+}
+
+/// Lays out the snippets row by row, trying to keep each snippet in the row given by `hint`. The
+/// rows that the snippets end up in are returned in [`Layout::rows`], so that they can be used as
+/// the hints next time.
+pub fn stable_layout<
+    Id: Copy + Hash + Eq + Ord,
+    T: Into<SnippetBounds<Id>>,
+    I: Iterator<Item = T>,
+>(
+    iter: I,
+    hint: impl Fn(Id) -> Option<usize>,
+    params: &Parameters,
+) -> Layout<Id> {
+    let bounds: Vec<SnippetBounds<Id>> = iter.map(|t| t.into()).collect();
+    let rows = assign_rows(&bounds, hint, params);
+    let mut ret = skyline_layout(rows.iter().map(|&(_, b)| b), params);
+    ret.rows = rows.into_iter().map(|(row, b)| (b.id, row)).collect();
+    ret
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     // Creates a snippet that is empty, but has a starting and (possibly) an ending time.
     fn snip(id: usize, start: Time, thin: Option<Time>, end: Option<Time>) -> SnippetBounds<usize> {
@@ -782,4 +909,81 @@ mod tests {
         s.add_rect(15.0, 25.0, 1.0, min_width, &mut new_s);
         assert_eq!(new_s, sky(&[(20.0, 2.0), (25.0, 1.0)]));
     }
+
+    #[test]
+    fn layout_by_track() {
+        // In their original order, the first snippet gets draped over the second one, and then
+        // the third snippet goes on top of both. Laying out the second one first leaves room for
+        // the others to share the bottom track.
+        let snips: Vec<_> = snips!(
+            (30, Some(40), Some(40)),
+            (5, Some(20), Some(35)),
+            (40, Some(55), Some(65))
+        )
+        .collect();
+        assert_eq!(skyline_layout(snips.iter(), &PARAMS).max_y, 4.0);
+        assert_eq!(layout(snips.into_iter(), &PARAMS).max_y, 3.0);
+    }
+
+    #[test]
+    fn stable_rows() {
+        let snips = || snips!((0, Some(10), Some(20)), (5, Some(15), Some(30)));
+        let rows = |hints: &[(usize, usize)]| {
+            let hints: HashMap<_, _> = hints.iter().copied().collect();
+            let mut rows: Vec<_> = stable_layout(snips(), |id| hints.get(&id).copied(), &PARAMS)
+                .rows
+                .into_iter()
+                .collect();
+            rows.sort();
+            rows
+        };
+
+        assert_eq!(rows(&[]), vec![(1, 0), (2, 1)]);
+        // The hints are respected, even if it isn't the most compact.
+        assert_eq!(rows(&[(1, 1), (2, 0)]), vec![(1, 1), (2, 0)]);
+        // Snippets without hints don't bump the ones with hints.
+        assert_eq!(rows(&[(2, 0)]), vec![(1, 1), (2, 0)]);
+        // If two snippets want the same row, the first one gets it.
+        assert_eq!(rows(&[(1, 0), (2, 0)]), vec![(1, 0), (2, 1)]);
+    }
+
+    fn arb_snips() -> impl Strategy<Value = Vec<SnippetBounds<usize>>> {
+        prop::collection::vec((0i64..100, 0i64..50, prop::option::of(0i64..50)), 0..20).prop_map(
+            |v| {
+                v.into_iter()
+                    .enumerate()
+                    .map(|(id, (start, thick, thin))| {
+                        let thin_start = start + thick;
+                        snip(
+                            id,
+                            Time::from_micros(start),
+                            Some(Time::from_micros(thin_start)),
+                            thin.map(|t| Time::from_micros(thin_start + t)),
+                        )
+                    })
+                    .collect()
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn never_taller(snips in arb_snips()) {
+            let in_order = skyline_layout(snips.iter(), &PARAMS_PADDED);
+            let best = layout(snips.clone().into_iter(), &PARAMS_PADDED);
+            prop_assert!(best.max_y <= in_order.max_y);
+            prop_assert_eq!(best.positions.len(), snips.len());
+        }
+
+        #[test]
+        fn track_order_is_deterministic(snips in arb_snips()) {
+            let mut reversed = snips.clone();
+            reversed.reverse();
+            let ids = |v: Vec<&SnippetBounds<usize>>| v.into_iter().map(|b| b.id).collect::<Vec<_>>();
+            prop_assert_eq!(
+                ids(track_order(&snips, &PARAMS_PADDED)),
+                ids(track_order(&reversed, &PARAMS_PADDED))
+            );
+        }
+    }
 }
@@ -0,0 +1,163 @@
+//! Templates, for starting new animations from something other than a blank canvas.
+//!
+//! A template is a snapshot of an animation (including its background, and any snippets that
+//! should start off every new animation, like an intro) together with the color palette. They
+//! live in the templates directory (see [`crate::config::Templates`]), one file per template.
+
+use anyhow::{anyhow, Result};
+use druid::Color;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::widgets::PaletteData;
+use crate::{EditorState, SaveFileData};
+
+/// All template files have this extension.
+const TEMPLATE_EXTENSION: &str = "scbt";
+
+/// A template, as shown in the "New from template" dialog.
+#[derive(Clone, Debug)]
+pub struct TemplateEntry {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Clone)]
+pub struct Template {
+    /// The palette's colors, and their names.
+    pub palette: Vec<(Color, String)>,
+    pub project: SaveFileData,
+}
+
+#[derive(Deserialize, Serialize)]
+struct TemplateRepr {
+    palette: Vec<(u32, String)>,
+    project: SaveFileData,
+}
+
+impl Template {
+    /// Makes a template out of the current state of an editor.
+    pub fn from_editor(data: &EditorState) -> Template {
+        let mut project = SaveFileData::from_scribl(&data.scribl);
+        // The checkpoints are part of the history of this animation, not the new ones.
+        project.checkpoints.clear();
+        Template {
+            palette: data.settings.palette.colors().to_vec(),
+            project,
+        }
+    }
+
+    pub fn palette_data(&self) -> PaletteData {
+        PaletteData::with_colors(self.palette.clone())
+    }
+
+    pub fn load_from_path(path: &Path) -> Result<Template> {
+        let repr: TemplateRepr = serde_cbor::from_reader(BufReader::new(File::open(path)?))?;
+        Ok(Template {
+            palette: repr
+                .palette
+                .into_iter()
+                .map(|(rgba, name)| (Color::from_rgba32_u32(rgba), name))
+                .collect(),
+            project: repr.project,
+        })
+    }
+
+    pub fn save_to_path(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let repr = TemplateRepr {
+            palette: self
+                .palette
+                .iter()
+                .map(|(c, name)| (c.as_rgba_u32(), name.clone()))
+                .collect(),
+            project: self.project.clone(),
+        };
+        serde_cbor::to_writer(BufWriter::new(File::create(path)?), &repr)?;
+        Ok(())
+    }
+}
+
+/// The path of the template called `name`. Anything in the name that can't go in a file name gets
+/// replaced by an underscore.
+pub fn template_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(anyhow!("the template needs a name"));
+    }
+    let file_name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect();
+    Ok(dir.join(format!("{}.{}", file_name, TEMPLATE_EXTENSION)))
+}
+
+/// Returns all the templates in `dir`, sorted by name.
+pub fn list_templates(dir: &Path) -> Vec<TemplateEntry> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(e) => {
+            // The directory only gets created when the first template is saved.
+            log::info!("couldn't read the templates directory: {}", e);
+            return Vec::new();
+        }
+    };
+    let mut ret: Vec<_> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().map_or(false, |ext| ext == TEMPLATE_EXTENSION))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().into_owned();
+            Some(TemplateEntry { name, path })
+        })
+        .collect();
+    ret.sort_by(|a, b| a.name.cmp(&b.name));
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths() {
+        let dir = Path::new("templates");
+        assert_eq!(
+            template_path(dir, " Lecture ").unwrap(),
+            Path::new("templates/Lecture.scbt")
+        );
+        assert_eq!(
+            template_path(dir, "a/b: c").unwrap(),
+            Path::new("templates/a_b_ c.scbt")
+        );
+        assert!(template_path(dir, "  ").is_err());
+    }
+
+    #[test]
+    fn save_and_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = Template {
+            palette: vec![(Color::rgb8(1, 2, 3), "Dark".to_owned())],
+            project: SaveFileData::from_scribl(&Default::default()),
+        };
+        let path = template_path(dir.path(), "Intro").unwrap();
+        template.save_to_path(&path).unwrap();
+        std::fs::write(dir.path().join("other.scb"), b"not a template").unwrap();
+
+        let list = list_templates(dir.path());
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].name, "Intro");
+
+        let loaded = Template::load_from_path(&list[0].path).unwrap();
+        assert_eq!(loaded.palette.len(), 1);
+        assert_eq!(loaded.palette[0].0.as_rgba_u32(), 0x010203ff);
+        assert_eq!(loaded.palette[0].1, "Dark");
+    }
+}
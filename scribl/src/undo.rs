@@ -1,12 +1,25 @@
+use anyhow::{anyhow, Result};
+use crossbeam_channel::Sender;
 use druid::im::Vector;
 use druid::Data;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use tempfile::NamedTempFile;
 
-use scribl_curves::{DrawSnippets, Time};
+use scribl_core::underlay::{Pip, Underlay};
+use scribl_curves::{DrawSnippet, DrawSnippetId, DrawSnippets, Time};
 
-use crate::audio::TalkSnippets;
+use crate::audio::{MusicTrack, TalkSnippet, TalkSnippetId, TalkSnippets};
+use crate::data::{Markers, Pages, ScriblState};
 use crate::{CurrentAction, SnippetId};
 
-const MAX_UNDO_STACK: usize = 128;
+const MAX_UNDO_STACK: usize = 1024;
+
+/// The newest few undo states are always kept in memory, no matter how much memory they use.
+const MIN_IN_MEMORY: usize = 8;
 
 /// This is the part of the editor state that gets restored when we undo/redo.
 /// Note that undoing/redoing might also affect other parts of the editor state
@@ -15,6 +28,12 @@ const MAX_UNDO_STACK: usize = 128;
 pub struct UndoState {
     pub snippets: DrawSnippets,
     pub audio_snippets: TalkSnippets,
+    pub music: Option<MusicTrack>,
+    pub underlay: Option<Underlay>,
+    pub webcam: Option<Pip>,
+    pub markers: Markers,
+    pub pages: Pages,
+    pub project_end: Option<Time>,
     pub selected_snippet: Option<SnippetId>,
     pub mark: Option<Time>,
     pub time: Time,
@@ -26,6 +45,18 @@ impl UndoState {
         self.time = time;
         self
     }
+
+    /// Replaces the parts of `scribl` that can be undone with the ones from this state.
+    pub fn restore_scribl(&self, scribl: &mut ScriblState) {
+        scribl.draw = self.snippets.clone();
+        scribl.talk = self.audio_snippets.clone();
+        scribl.music = self.music.clone();
+        scribl.underlay = self.underlay.clone();
+        scribl.webcam = self.webcam.clone();
+        scribl.markers = self.markers.clone();
+        scribl.pages = self.pages.clone();
+        scribl.project_end = self.project_end;
+    }
 }
 
 #[derive(Clone, Data)]
@@ -47,6 +78,191 @@ struct UndoData {
     // then when the snippet is done being drawn we delete all the little
     // transient undo states and replace it with just one for the whole snippet.
     transient: bool,
+
+    // If true, the snippets in this state were moved to disk to save memory, and the snippets in
+    // `undo_state` and `redo_state` are empty. They can be read back from `disk`.
+    spilled: bool,
+
+    // The copy of this state's snippets on disk, if there is one. We keep it after reading the
+    // snippets back in, so that if they need to go back to disk we don't need to write them again.
+    //
+    // The snippets are stored relative to the undo state of the next newer undo state (see
+    // `SpilledSnippets`), so this is only valid as long as that one stays the same.
+    disk: Option<Arc<SpillSlot>>,
+}
+
+/// The location of some spilled snippets in the spill file.
+#[derive(Clone, Copy)]
+struct SpillLocation {
+    offset: u64,
+    len: u64,
+}
+
+/// Where some spilled snippets ended up. They get written in the background, so we might need to
+/// wait for them.
+#[derive(Default)]
+struct SpillSlot {
+    location: Mutex<Option<Result<SpillLocation, String>>>,
+    written: Condvar,
+}
+
+impl SpillSlot {
+    fn finish(&self, result: Result<SpillLocation>) {
+        *self.location.lock().unwrap() = Some(result.map_err(|e| e.to_string()));
+        self.written.notify_all();
+    }
+
+    /// Waits until the snippets have been written, and returns where they are.
+    fn wait(&self) -> Result<SpillLocation> {
+        let mut location = self.location.lock().unwrap();
+        while location.is_none() {
+            location = self.written.wait(location).unwrap();
+        }
+        location.clone().unwrap().map_err(|e| anyhow!(e))
+    }
+}
+
+/// The difference between two collections of snippets: the snippets that were added or changed,
+/// and the ids of the ones that were removed.
+#[derive(Deserialize, Serialize)]
+struct SnippetsDelta<Id, Snip> {
+    changed: Vec<(Id, Snip)>,
+    removed: Vec<Id>,
+}
+
+impl<Id: Copy + Ord, Snip: Clone + Data> SnippetsDelta<Id, Snip> {
+    /// The changes that turn `base` into `snips`.
+    fn new<'a>(
+        base: impl Iterator<Item = (Id, &'a Snip)>,
+        snips: impl Iterator<Item = (Id, &'a Snip)>,
+    ) -> SnippetsDelta<Id, Snip>
+    where
+        Snip: 'a,
+    {
+        let mut base: BTreeMap<Id, &Snip> = base.collect();
+        let mut changed = Vec::new();
+        for (id, snip) in snips {
+            match base.remove(&id) {
+                Some(b) if b.same(snip) => {}
+                _ => changed.push((id, snip.clone())),
+            }
+        }
+        SnippetsDelta {
+            changed,
+            removed: base.into_iter().map(|(id, _)| id).collect(),
+        }
+    }
+
+    /// Applies the changes to `base`.
+    fn apply<'a, C>(self, base: impl Iterator<Item = (Id, &'a Snip)>) -> C
+    where
+        Snip: 'a,
+        C: std::iter::FromIterator<(Id, Snip)>,
+    {
+        let mut ret: BTreeMap<Id, Snip> = base.map(|(id, snip)| (id, snip.clone())).collect();
+        for id in self.removed {
+            ret.remove(&id);
+        }
+        ret.extend(self.changed);
+        ret.into_iter().collect()
+    }
+}
+
+/// The snippets of an undo state, in the form that we write them to disk.
+///
+/// Undo states share most of their snippets with each other, so instead of writing all the
+/// snippets, we only write the ones that are different from the undo state of the next newer undo
+/// state. Otherwise, every state would contain all of the project's audio.
+#[derive(Deserialize, Serialize)]
+struct SpilledSnippets {
+    undo_draw: SnippetsDelta<DrawSnippetId, DrawSnippet>,
+    undo_talk: SnippetsDelta<TalkSnippetId, TalkSnippet>,
+    redo_draw: SnippetsDelta<DrawSnippetId, DrawSnippet>,
+    redo_talk: SnippetsDelta<TalkSnippetId, TalkSnippet>,
+}
+
+impl SpilledSnippets {
+    fn new(data: &UndoData, base: &UndoState) -> SpilledSnippets {
+        let draw =
+            |snips: &DrawSnippets| SnippetsDelta::new(base.snippets.snippets(), snips.snippets());
+        let talk = |snips: &TalkSnippets| {
+            SnippetsDelta::new(base.audio_snippets.snippets(), snips.snippets())
+        };
+        SpilledSnippets {
+            undo_draw: draw(&data.undo_state.snippets),
+            undo_talk: talk(&data.undo_state.audio_snippets),
+            redo_draw: draw(&data.redo_state.snippets),
+            redo_talk: talk(&data.redo_state.audio_snippets),
+        }
+    }
+}
+
+/// A temporary file containing undo states that were moved out of memory.
+///
+/// The file has a name (even though nothing ever opens it by name) so that it counts towards the
+/// size of the cache directory.
+///
+/// Writing happens on a background thread, so that spilling a big undo state doesn't make the
+/// editor stutter. We only ever append to this file, so it grows until the editor is closed (at
+/// which point it gets deleted). Since we only write the snippets that changed, and each undo
+/// state only gets written once, it doesn't grow much faster than the animation itself.
+struct SpillFile {
+    file: Arc<Mutex<NamedTempFile>>,
+    jobs: Sender<(SpilledSnippets, Arc<SpillSlot>)>,
+}
+
+impl SpillFile {
+    /// Creates a spill file in `dir`, or in the system's temporary directory if `dir` is `None`.
+    fn new(dir: Option<&Path>) -> Result<SpillFile> {
+        let mut builder = tempfile::Builder::new();
+        builder.prefix("scribl-undo-");
+        let file = match dir {
+            Some(dir) => {
+                std::fs::create_dir_all(dir)?;
+                builder.tempfile_in(dir)?
+            }
+            None => builder.tempfile()?,
+        };
+        let file = Arc::new(Mutex::new(file));
+        let (tx, rx) = crossbeam_channel::unbounded::<(SpilledSnippets, Arc<SpillSlot>)>();
+        let thread_file = Arc::clone(&file);
+        // The thread stops when the spill file is dropped, because that closes the channel.
+        std::thread::spawn(move || {
+            while let Ok((snippets, slot)) = rx.recv() {
+                slot.finish(SpillFile::append(&thread_file, &snippets));
+            }
+        });
+        Ok(SpillFile { file, jobs: tx })
+    }
+
+    fn append(file: &Mutex<NamedTempFile>, snippets: &SpilledSnippets) -> Result<SpillLocation> {
+        let bytes = serde_cbor::to_vec(snippets)?;
+        let mut file = file.lock().unwrap();
+        let offset = file.seek(SeekFrom::End(0))?;
+        file.write_all(&bytes)?;
+        Ok(SpillLocation {
+            offset,
+            len: bytes.len() as u64,
+        })
+    }
+
+    /// Starts writing some snippets to the file.
+    fn write(&self, snippets: SpilledSnippets) -> Result<Arc<SpillSlot>> {
+        let slot = Arc::new(SpillSlot::default());
+        self.jobs
+            .send((snippets, Arc::clone(&slot)))
+            .map_err(|_| anyhow!("the spill thread stopped"))?;
+        Ok(slot)
+    }
+
+    fn read(&self, slot: &SpillSlot) -> Result<SpilledSnippets> {
+        let loc = slot.wait()?;
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(loc.offset))?;
+        let mut bytes = vec![0; loc.len as usize];
+        file.read_exact(&mut bytes)?;
+        Ok(serde_cbor::from_slice(&bytes)?)
+    }
 }
 
 #[derive(Clone, Data)]
@@ -57,22 +273,185 @@ pub struct UndoStack {
     // The index of the current position in the stack. When this is zero, it means that nothing was
     // undone. When this is `stack.len()`, it means there is nothing left to undo.
     current_state: usize,
+    // We try to keep the memory used by the undo states below this many bytes.
+    memory_budget: usize,
+    // If true, undo states that don't fit in the memory budget get moved to `spill_file`.
+    // Otherwise, they get forgotten.
+    spill: bool,
+    // The directory to put `spill_file` in.
+    #[data(same_fn = "PartialEq::eq")]
+    spill_dir: Option<PathBuf>,
+    // This gets created the first time that we need it.
+    spill_file: Option<Arc<SpillFile>>,
 }
 
 impl std::fmt::Debug for UndoData {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
         fmt.debug_struct("UndoData")
             .field("transient", &self.transient)
+            .field("spilled", &self.spilled)
             .finish()
     }
 }
 
+impl UndoData {
+    /// Estimates the memory used by this undo state, not counting anything in `seen`.
+    fn memory_size(&self, seen: &mut HashSet<usize>) -> usize {
+        if self.spilled {
+            return 0;
+        }
+        self.undo_state.snippets.memory_size(seen)
+            + self.undo_state.audio_snippets.memory_size(seen)
+            + self.redo_state.snippets.memory_size(seen)
+            + self.redo_state.audio_snippets.memory_size(seen)
+    }
+
+    /// Moves the snippets in this undo state to disk. `base` is the undo state of the next newer
+    /// undo state.
+    fn spill(&mut self, file: &SpillFile, base: &UndoState) -> Result<()> {
+        if self.spilled {
+            return Ok(());
+        }
+        if self.disk.is_none() {
+            self.disk = Some(file.write(SpilledSnippets::new(self, base))?);
+        }
+        self.spilled = true;
+        self.undo_state.snippets = DrawSnippets::default();
+        self.undo_state.audio_snippets = TalkSnippets::default();
+        self.redo_state.snippets = DrawSnippets::default();
+        self.redo_state.audio_snippets = TalkSnippets::default();
+        Ok(())
+    }
+
+    /// Brings the snippets in this undo state back into memory. `base` is the undo state of the
+    /// next newer undo state, which must be in memory.
+    fn unspill(&mut self, file: &SpillFile, base: &UndoState) -> Result<()> {
+        if !self.spilled {
+            return Ok(());
+        }
+        let slot = self
+            .disk
+            .as_ref()
+            .ok_or_else(|| anyhow!("bug: spilled undo state isn't on disk"))?;
+        let snippets = file.read(slot)?;
+        let draw = || base.snippets.snippets();
+        let talk = || base.audio_snippets.snippets();
+        self.undo_state.snippets = snippets.undo_draw.apply(draw());
+        self.undo_state.audio_snippets = snippets.undo_talk.apply(talk());
+        self.redo_state.snippets = snippets.redo_draw.apply(draw());
+        self.redo_state.audio_snippets = snippets.redo_talk.apply(talk());
+        self.spilled = false;
+        Ok(())
+    }
+}
+
 impl UndoStack {
-    /// Creates a new, empty undo stack.
-    pub fn new() -> UndoStack {
+    /// Creates a new, empty undo stack. If the undo states get moved to disk, they go in
+    /// `spill_dir` (or in the system's temporary directory, if that's `None`).
+    pub fn new(config: &crate::config::Undo, spill_dir: Option<PathBuf>) -> UndoStack {
         UndoStack {
             stack: Vector::new(),
             current_state: 0,
+            memory_budget: config.memory_mb.saturating_mul(1024 * 1024),
+            spill: config.spill_to_disk,
+            spill_dir,
+            spill_file: None,
+        }
+    }
+
+    /// If the undo states are using too much memory, moves the oldest ones to disk (or forgets
+    /// them, if we aren't allowed to use the disk).
+    ///
+    /// Since undo states share most of their snippets with each other, the memory used by an undo
+    /// state is only the memory that it doesn't share with any newer state.
+    fn enforce_memory_budget(&mut self) {
+        let mut seen = HashSet::new();
+        let mut total = 0;
+        let mut first_over_budget = None;
+        for (idx, data) in self.stack.iter().enumerate() {
+            total += data.memory_size(&mut seen);
+            if total > self.memory_budget && idx >= MIN_IN_MEMORY.max(self.current_state + 1) {
+                first_over_budget = Some(idx);
+                break;
+            }
+        }
+
+        if let Some(idx) = first_over_budget {
+            if self.spill {
+                match self.spill_from(idx) {
+                    Ok(()) => return,
+                    Err(e) => log::error!("failed to move undo states to disk: {}", e),
+                }
+            }
+            log::info!("forgetting {} old undo states", self.stack.len() - idx);
+            self.stack.truncate(idx);
+        }
+    }
+
+    /// Moves the undo state at `idx`, and all the older ones, to disk.
+    fn spill_from(&mut self, idx: usize) -> Result<()> {
+        if self.spill_file.is_none() {
+            self.spill_file = Some(Arc::new(SpillFile::new(self.spill_dir.as_deref())?));
+        }
+        let file = Arc::clone(self.spill_file.as_ref().unwrap());
+        // Each state is stored relative to the next newer one, so we start with the oldest one
+        // (while the newer ones are still in memory).
+        for i in (idx.max(1)..self.stack.len()).rev() {
+            let base = self.stack[i - 1].undo_state.clone();
+            self.stack[i].spill(&file, &base)?;
+        }
+        Ok(())
+    }
+
+    /// Makes sure that the undo state at `idx` is in memory. If it was moved to disk and we fail
+    /// to read it back, it gets forgotten (along with the states that can only be reached through
+    /// it), and we return false.
+    fn load(&mut self, idx: usize) -> bool {
+        if idx >= self.stack.len() || !self.stack[idx].spilled {
+            return true;
+        }
+        // The spilled states are stored relative to the newer ones, so we need to load all the
+        // states between here and the newest state that's in memory.
+        let first_spilled = (0..idx)
+            .rev()
+            .take_while(|&i| self.stack[i].spilled)
+            .last()
+            .unwrap_or(idx);
+        for i in first_spilled..=idx {
+            let result = match (&self.spill_file, i) {
+                (None, _) => Err(anyhow!("bug: no spill file")),
+                (Some(_), 0) => Err(anyhow!("bug: the newest undo state was spilled")),
+                (Some(file), _) => {
+                    let file = Arc::clone(file);
+                    let base = self.stack[i - 1].undo_state.clone();
+                    self.stack[i].unspill(&file, &base)
+                }
+            };
+            if let Err(e) = result {
+                log::error!("failed to read undo state from disk: {}", e);
+                if i >= self.current_state {
+                    self.stack.truncate(i);
+                } else {
+                    self.stack = self.stack.skip(i + 1);
+                    self.current_state -= i + 1;
+                }
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Forgets the `count` newest undo states.
+    fn drop_newest(&mut self, count: usize) {
+        // The spilled states are stored relative to the newer ones, so the one that becomes the
+        // newest needs to be in memory, and its copy on disk is no longer any good.
+        if count == 0 {
+            return;
+        }
+        self.load(count);
+        self.stack = self.stack.skip(count.min(self.stack.len()));
+        if let Some(front) = self.stack.front_mut() {
+            front.disk = None;
         }
     }
 
@@ -85,7 +464,7 @@ impl UndoStack {
     ) {
         // In case the current state is not the newest one, remove all the newer ones from the
         // stack.
-        self.stack = self.stack.skip(self.current_state);
+        self.drop_newest(self.current_state);
 
         if !transient {
             self.pop_transients();
@@ -96,12 +475,15 @@ impl UndoStack {
             redo_state,
             description,
             transient,
+            spilled: false,
+            disk: None,
         };
         self.stack.push_front(new_state);
         if self.stack.len() > MAX_UNDO_STACK {
             self.stack.pop_back();
         }
         self.current_state = 0;
+        self.enforce_memory_budget();
     }
 
     /// Registers a new action that can be undone. Any states that were previously undone will be
@@ -113,6 +495,29 @@ impl UndoStack {
         self.do_push(undo_state, redo_state, description, false);
     }
 
+    /// Like [`push`](UndoStack::push), except that if the most recent action has the same
+    /// description and selected snippet (and wasn't undone), the new action gets merged into it.
+    /// This is for actions like nudging a snippet, where lots of small repeated changes should all
+    /// get undone at once.
+    pub fn push_merged(
+        &mut self,
+        undo_state: UndoState,
+        redo_state: UndoState,
+        description: String,
+    ) {
+        let mergeable = |s: &UndoData| {
+            !s.transient
+                && !s.spilled
+                && s.description == description
+                && s.redo_state.selected_snippet == redo_state.selected_snippet
+        };
+        if self.current_state == 0 && self.stack.front().map_or(false, mergeable) {
+            self.stack[0].redo_state = redo_state;
+        } else {
+            self.do_push(undo_state, redo_state, description, false);
+        }
+    }
+
     /// Registers a new transient action that can be undone. Any states that were previously undone
     /// will be forgotten.
     ///
@@ -129,6 +534,24 @@ impl UndoStack {
         self.do_push(undo_state, redo_state, description, true);
     }
 
+    /// Like [`push_transient`](UndoStack::push_transient), except that if the most recent action
+    /// is also transient then the new action gets merged into it, so that they both get undone at
+    /// once.
+    pub fn push_transient_merged(
+        &mut self,
+        undo_state: UndoState,
+        redo_state: UndoState,
+        description: String,
+    ) {
+        if self.current_state == 0 && self.stack.front().map_or(false, |s| s.transient) {
+            let newest = &mut self.stack[0];
+            newest.redo_state = redo_state;
+            newest.description = description;
+        } else {
+            self.do_push(undo_state, redo_state, description, true);
+        }
+    }
+
     /// When we create transient undo states, it's with the intention of eventually replacing them
     /// with a real undo state (like, while drawing we create transient undo states and then
     /// eventually replace them with the whole snippet). But sometimes we cancel the action instead
@@ -139,21 +562,33 @@ impl UndoStack {
             .iter()
             .position(|s| !s.transient)
             .unwrap_or(self.stack.len());
-        self.stack = self.stack.skip(last_permanent);
+        self.drop_newest(last_permanent);
     }
 
     /// If there is a most recent action to undo, rewinds the undo stack to that action and returns
     /// the state that should be restored.
     pub fn undo(&mut self) -> Option<UndoState> {
-        if self.current_state < self.stack.len() {
+        if self.current_state < self.stack.len() && self.load(self.current_state) {
             let state = self.stack[self.current_state].undo_state.clone();
             self.current_state += 1;
+            // Load the next state also, so that it can be previewed.
+            self.load(self.current_state);
             Some(state)
         } else {
             None
         }
     }
 
+    /// Returns the state that would be restored by undoing, without actually undoing anything.
+    ///
+    /// If that state was moved to disk, this returns `None`.
+    pub fn undo_preview(&self) -> Option<&UndoState> {
+        self.stack
+            .get(self.current_state)
+            .filter(|undo| !undo.spilled)
+            .map(|undo| &undo.undo_state)
+    }
+
     /// Returns a description of the action that can be undone.
     pub fn undo_description(&self) -> Option<&str> {
         self.stack
@@ -164,7 +599,7 @@ impl UndoStack {
     /// If there is an undone action that can be redone, advances the undo stack to that action and
     /// returns the state that should be restored.
     pub fn redo(&mut self) -> Option<UndoState> {
-        if self.current_state > 0 {
+        if self.current_state > 0 && self.load(self.current_state - 1) {
             self.current_state -= 1;
             Some(self.stack[self.current_state].redo_state.clone())
         } else {
@@ -191,3 +626,146 @@ impl UndoStack {
         self.current_state > 0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::TalkSnippet;
+
+    fn state_with_audio(len: usize) -> UndoState {
+        let snip = TalkSnippet::new(vec![1; len], Time::ZERO, 1.0);
+        UndoState {
+            audio_snippets: TalkSnippets::default().with_new_snippet(snip).0,
+            ..UndoState::default()
+        }
+    }
+
+    #[test]
+    fn spill_and_restore() {
+        let config = crate::config::Undo {
+            memory_mb: 0,
+            spill_to_disk: true,
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let mut stack = UndoStack::new(&config, Some(dir.path().to_owned()));
+        let count = MIN_IN_MEMORY + 4;
+        for i in 1..=count {
+            stack.push(state_with_audio(i), state_with_audio(i), format!("{}", i));
+        }
+        assert_eq!(stack.stack.len(), count);
+        assert!(!stack.stack[MIN_IN_MEMORY - 1].spilled);
+        assert!(stack.stack[MIN_IN_MEMORY].spilled);
+
+        // The spill file is visible in the directory, so it counts towards the cache size.
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // Undoing everything brings the spilled states back.
+        for i in (1..=count).rev() {
+            let state = stack.undo().unwrap();
+            let (_, snip) = state.audio_snippets.snippets().next().unwrap();
+            assert_eq!(snip.buf().len(), i);
+        }
+        assert!(stack.undo().is_none());
+    }
+
+    fn spill_file_len(file: &SpillFile) -> u64 {
+        let file = file.file.lock().unwrap();
+        file.as_file().metadata().unwrap().len()
+    }
+
+    #[test]
+    fn spill_only_changes() {
+        let config = crate::config::Undo {
+            memory_mb: 0,
+            spill_to_disk: true,
+        };
+        let mut stack = UndoStack::new(&config, None);
+
+        // A long narration that is in every undo state, and a short snippet that changes with
+        // every edit.
+        let narration = TalkSnippet::new(vec![1; 1_000_000], Time::ZERO, 1.0);
+        let (narrated, _) = TalkSnippets::default().with_new_snippet(narration);
+        let state = |len: usize| {
+            let snip = TalkSnippet::new(vec![2; len], Time::ZERO, 1.0);
+            UndoState {
+                audio_snippets: narrated.with_new_snippet(snip).0,
+                ..UndoState::default()
+            }
+        };
+        let count = 100;
+        for i in 1..=count {
+            stack.push(state(i), state(i + 1), format!("{}", i));
+        }
+
+        let file = stack.spill_file.clone().unwrap();
+        for data in stack.stack.iter().filter(|d| d.spilled) {
+            data.disk.as_ref().unwrap().wait().unwrap();
+        }
+        let size = spill_file_len(&file);
+        // The narration never gets written to disk.
+        assert!(size < 1_000_000, "the spill file has {} bytes", size);
+
+        // Everything can still be undone.
+        for i in (1..=count).rev() {
+            let state = stack.undo().unwrap();
+            let lens: Vec<_> = state
+                .audio_snippets
+                .snippets()
+                .map(|(_, s)| s.buf().len())
+                .collect();
+            assert_eq!(lens, vec![1_000_000, i]);
+        }
+
+        // Redoing and then making more changes doesn't write the old states again.
+        for _ in 0..count {
+            stack.redo().unwrap();
+        }
+        stack.push(state(1), state(2), "again".to_owned());
+        for data in stack.stack.iter().filter(|d| d.spilled) {
+            data.disk.as_ref().unwrap().wait().unwrap();
+        }
+        let new_size = spill_file_len(&file);
+        assert!(new_size < size + 10_000);
+    }
+
+    #[test]
+    fn restore() {
+        let state = UndoState {
+            project_end: Some(Time::from_micros(5_000_000)),
+            ..state_with_audio(3)
+        };
+        let mut scribl = ScriblState::default();
+        state.restore_scribl(&mut scribl);
+        assert_eq!(scribl.project_end, Some(Time::from_micros(5_000_000)));
+        assert_eq!(scribl.talk.snippets().count(), 1);
+
+        UndoState::default().restore_scribl(&mut scribl);
+        assert_eq!(scribl.project_end, None);
+        assert_eq!(scribl.talk.snippets().count(), 0);
+    }
+
+    #[test]
+    fn merged() {
+        let config = crate::config::Undo {
+            memory_mb: 100,
+            spill_to_disk: false,
+        };
+        let mut stack = UndoStack::new(&config, None);
+        stack.push(state_with_audio(1), state_with_audio(2), "add".to_owned());
+        stack.push_merged(state_with_audio(2), state_with_audio(3), "nudge".to_owned());
+        stack.push_merged(state_with_audio(3), state_with_audio(4), "nudge".to_owned());
+        assert_eq!(stack.stack.len(), 2);
+
+        // Undoing the merged action goes all the way back to before the first nudge.
+        let state = stack.undo().unwrap();
+        let (_, snip) = state.audio_snippets.snippets().next().unwrap();
+        assert_eq!(snip.buf().len(), 2);
+        let state = stack.redo().unwrap();
+        let (_, snip) = state.audio_snippets.snippets().next().unwrap();
+        assert_eq!(snip.buf().len(), 4);
+
+        // Actions with a different description don't get merged.
+        stack.push_merged(state_with_audio(4), state_with_audio(5), "other".to_owned());
+        assert_eq!(stack.stack.len(), 3);
+    }
+}
@@ -1,10 +1,28 @@
+use druid::im::Vector;
+use druid::kurbo::BezPath;
+use druid::text::{Formatter, ParseFormatter, Selection, Validation, ValidationError};
 use druid::widget::prelude::*;
-use druid::widget::{Button, Controller, Flex, Label, Spinner};
-use druid::{SingleUse, Widget, WidgetExt};
+use druid::widget::{
+    Button, Checkbox, Controller, CrossAxisAlignment, Either, Flex, Label, LineBreaking, List,
+    ProgressBar, Scroll, Slider, Spinner, TextBox,
+};
+use druid::{lens, KbKey, Lens, LensExt, SingleUse, Widget, WidgetExt};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::SystemTime;
 
+use scribl_curves::{DrawSnippet, DrawSnippetId, Time, TimeDiff};
 use scribl_widget::ModalHost;
 
-use crate::{CurrentAction, EditorState};
+use crate::audio::{AudioError, Duplicate, TalkSnippet, TalkSnippetId};
+use crate::autosave::AutosaveEntry;
+use crate::data::{Checkpoint, ExportJob, ExportJobStatus, FinishedStatus, Settings};
+use crate::template::TemplateEntry;
+use crate::undo::UndoState;
+use crate::widgets::retime::RetimeGraph;
+use crate::{cmd, CurrentAction, EditorState, SnippetId};
 
 pub fn make_unsaved_changes_alert() -> impl Widget<EditorState> {
     let close =
@@ -82,7 +100,7 @@ impl<W: Widget<EditorState>> Controller<EditorState, W> for Waiter {
         data: &EditorState,
         env: &Env,
     ) {
-        if data.status.in_progress.saving.is_none() && data.status.in_progress.encoding.is_none() {
+        if data.status.in_progress.saving.is_none() && !data.status.exports.is_busy() {
             ctx.submit_command(druid::commands::CLOSE_WINDOW);
         }
         child.update(ctx, old_data, data, env);
@@ -99,7 +117,7 @@ impl<W: Widget<EditorState>> Controller<EditorState, W> for Waiter {
         // We check for termination in lifecycle as well as update, because it's possible that
         // the condition was triggered before we were instantiated, in which case we'll get a
         // lifecycle event when we're added to the widget tree but we won't get any updates.
-        if data.status.in_progress.saving.is_none() && data.status.in_progress.encoding.is_none() {
+        if data.status.in_progress.saving.is_none() && !data.status.exports.is_busy() {
             ctx.submit_command(druid::commands::CLOSE_WINDOW);
         }
         child.lifecycle(ctx, ev, data, env);
@@ -126,3 +144,1549 @@ pub fn make_waiting_to_exit_alert() -> impl Widget<EditorState> {
         .border(druid::theme::FOREGROUND_DARK, 1.0)
         .controller(Waiter {})
 }
+
+pub fn make_calibrating_latency_alert() -> impl Widget<EditorState> {
+    let label = Label::new("Measuring audio latency. Please make sure that your speakers are on.");
+    let spinner = Spinner::new();
+
+    Flex::column()
+        .with_child(label)
+        .with_spacer(15.0)
+        .with_child(spinner)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+}
+
+pub fn make_latency_result_alert(result: Result<TimeDiff, String>) -> impl Widget<EditorState> {
+    let mut col = Flex::column();
+    match result {
+        Ok(latency) => {
+            let ms = (latency.as_micros() / 1000) as i32;
+            let cancel = Button::new("Cancel").on_click(|ctx, _data, _env| {
+                ctx.submit_command(ModalHost::DISMISS_MODAL);
+            });
+            let apply = Button::new("Use this latency").on_click(
+                move |ctx, data: &mut EditorState, _env| {
+                    data.config.audio_input.latency_ms = ms;
+                    let value = ms.to_string();
+                    let result = crate::config::save_setting("audio_input", "latency_ms", &value);
+                    if let Err(e) = result {
+                        log::error!("failed to save the latency: {}", e);
+                    }
+                    ctx.submit_command(ModalHost::DISMISS_MODAL);
+                },
+            );
+            let button_row = Flex::row()
+                .with_child(cancel)
+                .with_spacer(5.0)
+                .with_child(apply);
+            col.add_child(Label::new(format!(
+                "The measured audio latency is {}ms.",
+                ms
+            )));
+            col.add_child(Label::new("It will be saved in your configuration file."));
+            col.add_spacer(15.0);
+            col.add_child(button_row);
+        }
+        Err(e) => {
+            let ok = Button::new("Ok").on_click(|ctx, _data, _env| {
+                ctx.submit_command(ModalHost::DISMISS_MODAL);
+            });
+            col.add_child(Label::new(format!(
+                "Failed to measure the audio latency: {}",
+                e
+            )));
+            col.add_spacer(15.0);
+            col.add_child(ok);
+        }
+    }
+
+    col.padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+}
+
+pub fn make_missing_plugins_alert(msg: String) -> impl Widget<EditorState> {
+    let ok = Button::new("Ok").on_click(|ctx, _data, _env| {
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+
+    Flex::column()
+        .with_child(Label::new(msg).with_line_break_mode(LineBreaking::WordWrap))
+        .with_spacer(15.0)
+        .with_child(ok)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+}
+
+pub fn make_audio_errors_alert(errors: Vec<AudioError>) -> impl Widget<EditorState> {
+    let mut col = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    col.add_child(
+        Label::new("Something went wrong with the audio, so playing and recording might not work.")
+            .with_line_break_mode(LineBreaking::WordWrap),
+    );
+    for e in &errors {
+        col.add_spacer(10.0);
+        col.add_child(Label::new(format!("Error {}:", e.action)));
+        if let Some(element) = &e.element {
+            col.add_child(Label::new(format!("Element: {}", element)));
+        }
+        col.add_child(Label::new(e.message.clone()).with_line_break_mode(LineBreaking::WordWrap));
+        if let Some(debug) = &e.debug {
+            col.add_child(
+                Label::new(debug.clone())
+                    .with_text_size(crate::TEXT_SIZE_SMALL)
+                    .with_line_break_mode(LineBreaking::WordWrap),
+            );
+        }
+    }
+
+    let report = AudioError::report(&errors);
+    let copy = Button::new("Copy to clipboard").on_click(move |_ctx, _data, _env| {
+        druid::Application::global().clipboard().put_string(&report);
+    });
+    let ok = Button::new("Ok").on_click(|ctx, _data, _env| {
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+
+    col.with_spacer(15.0)
+        .with_child(Flex::row().with_child(copy).with_spacer(5.0).with_child(ok))
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+}
+
+pub fn make_partial_exports_alert(paths: Vec<PathBuf>) -> impl Widget<EditorState> {
+    let mut msg = String::from(
+        "Some exports were interrupted before they finished. They left behind these files:\n",
+    );
+    for p in &paths {
+        msg.push_str(&format!("  {}\n", p.display()));
+    }
+    let paths = Arc::new(paths);
+    let paths_clone = Arc::clone(&paths);
+
+    let keep = Button::new("Keep them").on_click(move |ctx, _data, _env| {
+        crate::partial_exports::forget(&paths);
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+    let delete = Button::new("Delete them").on_click(move |ctx, _data, _env| {
+        crate::partial_exports::remove(&paths_clone);
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+
+    Flex::column()
+        .with_child(Label::new(msg).with_line_break_mode(LineBreaking::WordWrap))
+        .with_spacer(15.0)
+        .with_child(
+            Flex::row()
+                .with_child(keep)
+                .with_spacer(5.0)
+                .with_child(delete),
+        )
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+}
+
+pub fn make_crash_recovery_alert(paths: Vec<PathBuf>) -> impl Widget<EditorState> {
+    let mut list = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    for p in &paths {
+        let path = p.clone();
+        let open = Button::new("Open").on_click(move |ctx, _data, _env| {
+            ctx.submit_command(ModalHost::DISMISS_MODAL);
+            ctx.submit_command(cmd::OPEN_AUTOSAVE.with(path.clone()));
+        });
+        list.add_child(
+            Flex::row()
+                .with_child(Label::new(p.display().to_string()).fix_width(300.0))
+                .with_spacer(5.0)
+                .with_child(open)
+                .padding((0.0, 2.0)),
+        );
+    }
+
+    let keep = Button::new("Keep them").on_click(|ctx, _data, _env| {
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+    let delete = Button::new("Delete them").on_click(move |ctx, _data, _env| {
+        crate::crash::remove(&paths);
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+
+    Flex::column()
+        .with_child(
+            Label::new("Scribl crashed last time. These animations were saved while crashing:")
+                .with_line_break_mode(LineBreaking::WordWrap),
+        )
+        .with_spacer(5.0)
+        .with_child(list)
+        .with_spacer(15.0)
+        .with_child(
+            Flex::row()
+                .with_child(keep)
+                .with_spacer(5.0)
+                .with_child(delete),
+        )
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+}
+
+/// Creates a list of autosaved files (newest first), each with a button to open it.
+pub fn make_autosaves_alert(autosaves: Vec<AutosaveEntry>) -> impl Widget<EditorState> {
+    let now = SystemTime::now();
+    let mut list = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    if autosaves.is_empty() {
+        list.add_child(Label::new("There are no autosaved files."));
+    }
+    for entry in autosaves {
+        let name = entry
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let age =
+            crate::autosave::describe_age(now.duration_since(entry.modified).unwrap_or_default());
+        let path = entry.path;
+        let open = Button::new("Open").on_click(move |ctx, _data, _env| {
+            ctx.submit_command(ModalHost::DISMISS_MODAL);
+            ctx.submit_command(cmd::OPEN_AUTOSAVE.with(path.clone()));
+        });
+        list.add_child(
+            Flex::row()
+                .with_child(Label::new(format!("{} ({})", name, age)).fix_width(300.0))
+                .with_spacer(5.0)
+                .with_child(open)
+                .padding((0.0, 2.0)),
+        );
+    }
+
+    let close = Button::new("Close").on_click(|ctx, _data, _env| {
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+
+    Flex::column()
+        .with_child(Label::new("Autosaved files"))
+        .with_spacer(5.0)
+        .with_child(Scroll::new(list).vertical().fix_height(200.0))
+        .with_spacer(15.0)
+        .with_child(close)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+}
+
+/// Creates a list of templates, each with a button to start a new animation from it.
+pub fn make_templates_alert(templates: Vec<TemplateEntry>) -> impl Widget<EditorState> {
+    let mut list = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    if templates.is_empty() {
+        list.add_child(
+            Label::new("There are no templates yet. Make one with \"File > Save as template\".")
+                .with_line_break_mode(LineBreaking::WordWrap)
+                .fix_width(300.0),
+        );
+    }
+    for entry in templates {
+        let path = entry.path;
+        let new = Button::new("New").on_click(move |ctx, _data, _env| {
+            ctx.submit_command(ModalHost::DISMISS_MODAL);
+            ctx.submit_command(cmd::NEW_FROM_TEMPLATE.with(path.clone()));
+        });
+        list.add_child(
+            Flex::row()
+                .with_child(Label::new(entry.name).fix_width(300.0))
+                .with_spacer(5.0)
+                .with_child(new)
+                .padding((0.0, 2.0)),
+        );
+    }
+
+    let close = Button::new("Close").on_click(|ctx, _data, _env| {
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+
+    Flex::column()
+        .with_child(Label::new("Templates"))
+        .with_spacer(5.0)
+        .with_child(Scroll::new(list).vertical().fix_height(200.0))
+        .with_spacer(15.0)
+        .with_child(close)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+}
+
+/// Closes the template name dialog, saving the template unless `name` is `None`.
+fn close_template_name(ctx: &mut EventCtx, data: &mut EditorState, name: Option<String>) {
+    if matches!(data.action, CurrentAction::EditingText) {
+        data.action = CurrentAction::Idle;
+        if let Some(name) = name {
+            ctx.submit_command(cmd::SAVE_TEMPLATE.with(name));
+        }
+    }
+    ctx.submit_command(ModalHost::DISMISS_MODAL);
+}
+
+/// Saves the template when they press Enter, and cancels when they press Escape.
+struct TemplateNameController {
+    name: Rc<RefCell<String>>,
+}
+
+impl<W: Widget<EditorState>> Controller<EditorState, W> for TemplateNameController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut EditorState,
+        env: &Env,
+    ) {
+        child.event(ctx, event, data, env);
+        if let Event::KeyDown(ev) = event {
+            if ev.key == KbKey::Enter {
+                close_template_name(ctx, data, Some(self.name.borrow().clone()));
+                ctx.set_handled();
+            } else if ev.key == KbKey::Escape {
+                close_template_name(ctx, data, None);
+                ctx.set_handled();
+            }
+        }
+    }
+}
+
+/// Creates a dialog that asks for the name of a new template.
+pub fn make_template_name_alert() -> impl Widget<EditorState> {
+    // The name isn't part of the editor's state, so it lives here until the dialog closes.
+    let name = Rc::new(RefCell::new(String::new()));
+    let get_name = Rc::clone(&name);
+    let set_name = Rc::clone(&name);
+    let name_lens = lens::Identity.map(
+        move |_: &EditorState| get_name.borrow().clone(),
+        move |_: &mut EditorState, new_name: String| *set_name.borrow_mut() = new_name,
+    );
+    let input = TextBox::new()
+        .with_placeholder("Lecture")
+        .lens(name_lens)
+        .fix_width(200.0);
+
+    let save_name = Rc::clone(&name);
+    let save = Button::new("Save").on_click(move |ctx, data, _env| {
+        close_template_name(ctx, data, Some(save_name.borrow().clone()))
+    });
+    let cancel =
+        Button::new("Cancel").on_click(|ctx, data, _env| close_template_name(ctx, data, None));
+
+    Flex::column()
+        .with_child(Label::new("Template name:"))
+        .with_spacer(5.0)
+        .with_child(input)
+        .with_spacer(15.0)
+        .with_child(
+            Flex::row()
+                .with_child(save)
+                .with_spacer(5.0)
+                .with_child(cancel),
+        )
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+        .controller(TemplateNameController { name })
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("expected a time, like 1:23.5")]
+struct TimeParseError;
+
+/// Converts between times and text like "1:23.500". Besides that format, it also understands
+/// seconds ("83.5s") and video frames ("120@30"): see `Time::from_str`.
+struct TimeFormatter;
+
+impl Formatter<Time> for TimeFormatter {
+    fn format(&self, value: &Time) -> String {
+        value.to_string()
+    }
+
+    fn validate_partial_input(&self, input: &str, _sel: &Selection) -> Validation {
+        if input
+            .chars()
+            .all(|c| c.is_ascii_digit() || c.is_whitespace() || ":.s@".contains(c))
+        {
+            Validation::success()
+        } else {
+            Validation::failure(TimeParseError)
+        }
+    }
+
+    fn value(&self, input: &str) -> Result<Time, ValidationError> {
+        input.parse::<Time>().map_err(ValidationError::new)
+    }
+}
+
+/// Like [`TimeFormatter`], but for optional times: empty text means `None`.
+struct OptionalTimeFormatter;
+
+impl Formatter<Option<Time>> for OptionalTimeFormatter {
+    fn format(&self, value: &Option<Time>) -> String {
+        value
+            .as_ref()
+            .map(|t| TimeFormatter.format(t))
+            .unwrap_or_default()
+    }
+
+    fn validate_partial_input(&self, input: &str, sel: &Selection) -> Validation {
+        TimeFormatter.validate_partial_input(input, sel)
+    }
+
+    fn value(&self, input: &str) -> Result<Option<Time>, ValidationError> {
+        if input.trim().is_empty() {
+            Ok(None)
+        } else {
+            TimeFormatter.value(input).map(Some)
+        }
+    }
+}
+
+fn close_go_to_time(ctx: &mut EventCtx, data: &mut EditorState) {
+    if matches!(data.action, CurrentAction::EditingText) {
+        data.action = CurrentAction::Idle;
+    }
+    ctx.submit_command(ModalHost::DISMISS_MODAL);
+}
+
+/// Closes the "go to time" dialog when they press Enter or Escape.
+struct GoToTimeController;
+
+impl<W: Widget<EditorState>> Controller<EditorState, W> for GoToTimeController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut EditorState,
+        env: &Env,
+    ) {
+        child.event(ctx, event, data, env);
+        if let Event::KeyDown(ev) = event {
+            if ev.key == KbKey::Enter || ev.key == KbKey::Escape {
+                close_go_to_time(ctx, data);
+                ctx.set_handled();
+            }
+        }
+    }
+}
+
+pub fn make_go_to_time_alert() -> impl Widget<EditorState> {
+    // The time gets updated as they type (whenever the text is a valid time).
+    let time_lens = lens::Identity.map(
+        |data: &EditorState| data.time(),
+        |data: &mut EditorState, time: Time| {
+            if matches!(data.action, CurrentAction::EditingText) && time != data.time() {
+                data.warp_time_to(time);
+            }
+        },
+    );
+    let input = TextBox::new()
+        .with_placeholder("1:23.5")
+        .with_formatter(TimeFormatter)
+        .update_data_while_editing(true)
+        .lens(time_lens)
+        .fix_width(120.0);
+
+    let ok = Button::new("Done").on_click(|ctx, data, _env| close_go_to_time(ctx, data));
+
+    Flex::column()
+        .with_child(Label::new("Go to time:"))
+        .with_spacer(5.0)
+        .with_child(input)
+        .with_spacer(15.0)
+        .with_child(ok)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+        .controller(GoToTimeController)
+}
+
+fn close_script_console(ctx: &mut EventCtx, data: &mut EditorState) {
+    if matches!(data.action, CurrentAction::EditingText) {
+        data.action = CurrentAction::Idle;
+    }
+    ctx.submit_command(ModalHost::DISMISS_MODAL);
+}
+
+/// Runs the script when they press Ctrl+Enter, and closes the console when they press Escape.
+struct ScriptConsoleController;
+
+impl<W: Widget<EditorState>> Controller<EditorState, W> for ScriptConsoleController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut EditorState,
+        env: &Env,
+    ) {
+        if let Event::KeyDown(ev) = event {
+            if ev.key == KbKey::Enter && ev.mods.ctrl() {
+                ctx.submit_command(cmd::RUN_SCRIPT);
+                ctx.set_handled();
+                return;
+            } else if ev.key == KbKey::Escape {
+                close_script_console(ctx, data);
+                ctx.set_handled();
+                return;
+            }
+        }
+        child.event(ctx, event, data, env);
+    }
+}
+
+/// Creates a dialog for editing and running scripts.
+pub fn make_script_console_alert() -> impl Widget<EditorState> {
+    let input = TextBox::multiline()
+        .with_placeholder("for s in audio() { shift(s, 1.5); }")
+        .lens(EditorState::script)
+        .fix_size(500.0, 250.0);
+    let output = Label::dynamic(|data: &EditorState, _env| data.script_output.clone())
+        .with_line_break_mode(LineBreaking::WordWrap)
+        .fix_width(500.0);
+
+    let run = Button::new("Run").on_click(|ctx, _data, _env| ctx.submit_command(cmd::RUN_SCRIPT));
+    let close = Button::new("Close").on_click(|ctx, data, _env| close_script_console(ctx, data));
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new("Script (Ctrl+Enter to run):"))
+        .with_spacer(5.0)
+        .with_child(input)
+        .with_spacer(5.0)
+        .with_child(output)
+        .with_spacer(15.0)
+        .with_child(
+            Flex::row()
+                .with_child(run)
+                .with_spacer(5.0)
+                .with_child(close),
+        )
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+        .controller(ScriptConsoleController)
+}
+
+fn close_svg_import(ctx: &mut EventCtx, data: &mut EditorState, paths: Option<&[BezPath]>) {
+    if matches!(data.action, CurrentAction::EditingText) {
+        data.action = CurrentAction::Idle;
+        if let Some(paths) = paths {
+            data.import_svg(paths);
+        }
+    }
+    ctx.submit_command(ModalHost::DISMISS_MODAL);
+}
+
+/// Imports the drawing when they press Enter, and cancels when they press Escape.
+struct SvgImportController {
+    paths: Arc<Vec<BezPath>>,
+}
+
+impl<W: Widget<EditorState>> Controller<EditorState, W> for SvgImportController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut EditorState,
+        env: &Env,
+    ) {
+        child.event(ctx, event, data, env);
+        if let Event::KeyDown(ev) = event {
+            if ev.key == KbKey::Enter {
+                close_svg_import(ctx, data, Some(&self.paths));
+                ctx.set_handled();
+            } else if ev.key == KbKey::Escape {
+                close_svg_import(ctx, data, None);
+                ctx.set_handled();
+            }
+        }
+    }
+}
+
+/// Creates a dialog that asks how long an imported SVG drawing should take to draw.
+pub fn make_svg_import_alert(paths: Vec<BezPath>) -> impl Widget<EditorState> {
+    let paths = Arc::new(paths);
+    let input = TextBox::new()
+        .with_formatter(ParseFormatter::new())
+        .update_data_while_editing(true)
+        .lens(EditorState::settings.then(Settings::svg_import_secs))
+        .fix_width(80.0);
+
+    let import_paths = Arc::clone(&paths);
+    let import = Button::new("Import")
+        .on_click(move |ctx, data, _env| close_svg_import(ctx, data, Some(&import_paths)));
+    let cancel =
+        Button::new("Cancel").on_click(|ctx, data, _env| close_svg_import(ctx, data, None));
+
+    Flex::column()
+        .with_child(Label::new(format!(
+            "Importing {} path{}.",
+            paths.len(),
+            if paths.len() == 1 { "" } else { "s" }
+        )))
+        .with_spacer(5.0)
+        .with_child(
+            Flex::row()
+                .with_child(Label::new("Draw it over"))
+                .with_spacer(5.0)
+                .with_child(input)
+                .with_spacer(5.0)
+                .with_child(Label::new("seconds")),
+        )
+        .with_child(Label::new("(or 0 to show it all at once)"))
+        .with_spacer(15.0)
+        .with_child(
+            Flex::row()
+                .with_child(import)
+                .with_spacer(5.0)
+                .with_child(cancel),
+        )
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+        .controller(SvgImportController { paths })
+}
+
+/// Finishes editing the name of a marker. If the name was changed, this pushes an undo state
+/// (whose "before" state is `prev_state`).
+fn close_marker_name(
+    ctx: &mut EventCtx,
+    data: &mut EditorState,
+    prev_state: &UndoState,
+    time: Time,
+    desc: &str,
+) {
+    if matches!(data.action, CurrentAction::EditingText) {
+        data.action = CurrentAction::Idle;
+        // Erasing the name is the same as removing the marker.
+        if let Some(true) = data.scribl.markers.get(time).map(|n| n.trim().is_empty()) {
+            data.scribl.markers = data.scribl.markers.without_marker(time);
+        }
+        if data.scribl.markers != prev_state.markers {
+            data.push_undo_state(prev_state.clone(), desc);
+        }
+    }
+    ctx.submit_command(ModalHost::DISMISS_MODAL);
+}
+
+/// Closes the marker name dialog when they press Enter or Escape.
+struct MarkerNameController {
+    prev_state: UndoState,
+    time: Time,
+    desc: &'static str,
+}
+
+impl<W: Widget<EditorState>> Controller<EditorState, W> for MarkerNameController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut EditorState,
+        env: &Env,
+    ) {
+        child.event(ctx, event, data, env);
+        if let Event::KeyDown(ev) = event {
+            if ev.key == KbKey::Enter || ev.key == KbKey::Escape {
+                close_marker_name(ctx, data, &self.prev_state, self.time, self.desc);
+                ctx.set_handled();
+            }
+        }
+    }
+}
+
+/// Creates a dialog for editing the name of the marker at `time`.
+///
+/// The name gets updated as they type, but the undo state only gets pushed when the dialog is
+/// closed. `prev_state` is the state to go back to when undoing, and `desc` is the description of
+/// the undo action.
+pub fn make_marker_name_alert(
+    prev_state: UndoState,
+    time: Time,
+    desc: &'static str,
+) -> impl Widget<EditorState> {
+    let name_lens = lens::Identity.map(
+        move |data: &EditorState| data.scribl.markers.get(time).unwrap_or("").to_owned(),
+        move |data: &mut EditorState, name: String| {
+            if matches!(data.action, CurrentAction::EditingText)
+                && data.scribl.markers.get(time) != Some(name.as_str())
+            {
+                data.scribl.markers = data.scribl.markers.with_marker(time, &name);
+            }
+        },
+    );
+    let input = TextBox::new().lens(name_lens).fix_width(200.0);
+
+    let prev_clone = prev_state.clone();
+    let ok = Button::new("Done")
+        .on_click(move |ctx, data, _env| close_marker_name(ctx, data, &prev_clone, time, desc));
+
+    Flex::column()
+        .with_child(Label::new("Marker name:"))
+        .with_spacer(5.0)
+        .with_child(input)
+        .with_spacer(15.0)
+        .with_child(ok)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+        .controller(MarkerNameController {
+            prev_state,
+            time,
+            desc,
+        })
+}
+
+/// Finishes editing the name of a snippet. If the name was changed, this pushes an undo state
+/// (whose "before" state is `prev_state`).
+fn close_snippet_name(
+    ctx: &mut EventCtx,
+    data: &mut EditorState,
+    prev_state: &UndoState,
+    id: SnippetId,
+) {
+    if matches!(data.action, CurrentAction::EditingText) {
+        data.action = CurrentAction::Idle;
+        if data.has_snippet(id) {
+            // Erasing the name is the same as removing it.
+            if let Some(true) = data.snippet_name(id).map(|n| n.trim().is_empty()) {
+                data.set_snippet_name(id, None);
+            }
+            let prev_name = match id {
+                SnippetId::Draw(id) => prev_state.snippets.snippet(id).name(),
+                SnippetId::Talk(id) => prev_state.audio_snippets.snippet(id).name(),
+            };
+            if data.snippet_name(id) != prev_name {
+                data.push_undo_state(prev_state.clone(), "rename snippet");
+            }
+        }
+    }
+    ctx.submit_command(ModalHost::DISMISS_MODAL);
+}
+
+/// Closes the snippet name dialog when they press Enter or Escape.
+struct SnippetNameController {
+    prev_state: UndoState,
+    id: SnippetId,
+}
+
+impl<W: Widget<EditorState>> Controller<EditorState, W> for SnippetNameController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut EditorState,
+        env: &Env,
+    ) {
+        child.event(ctx, event, data, env);
+        if let Event::KeyDown(ev) = event {
+            if ev.key == KbKey::Enter || ev.key == KbKey::Escape {
+                close_snippet_name(ctx, data, &self.prev_state, self.id);
+                ctx.set_handled();
+            }
+        }
+    }
+}
+
+/// Creates a dialog for editing the name of a snippet.
+///
+/// Like the marker name dialog, the name gets updated as they type and the undo state gets pushed
+/// when the dialog is closed.
+pub fn make_snippet_name_alert(prev_state: UndoState, id: SnippetId) -> impl Widget<EditorState> {
+    let name_lens = lens::Identity.map(
+        move |data: &EditorState| {
+            if data.has_snippet(id) {
+                data.snippet_name(id).unwrap_or("").to_owned()
+            } else {
+                String::new()
+            }
+        },
+        move |data: &mut EditorState, name: String| {
+            if matches!(data.action, CurrentAction::EditingText)
+                && data.has_snippet(id)
+                && data.snippet_name(id).unwrap_or("") != name
+            {
+                data.set_snippet_name(id, Some(name));
+            }
+        },
+    );
+    let input = TextBox::new().lens(name_lens).fix_width(200.0);
+
+    let prev_clone = prev_state.clone();
+    let ok = Button::new("Done")
+        .on_click(move |ctx, data, _env| close_snippet_name(ctx, data, &prev_clone, id));
+
+    Flex::column()
+        .with_child(Label::new("Snippet name:"))
+        .with_spacer(5.0)
+        .with_child(input)
+        .with_spacer(15.0)
+        .with_child(ok)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+        .controller(SnippetNameController { prev_state, id })
+}
+
+/// Finishes editing the properties of a snippet. If anything was changed, this pushes an undo
+/// state (whose "before" state is `prev_state`).
+fn close_snippet_properties(
+    ctx: &mut EventCtx,
+    data: &mut EditorState,
+    prev_state: &UndoState,
+    id: SnippetId,
+) {
+    if matches!(data.action, CurrentAction::EditingText) {
+        data.action = CurrentAction::Idle;
+        if data.has_snippet(id) {
+            // Erasing the name is the same as removing it.
+            if let Some(true) = data.snippet_name(id).map(|n| n.trim().is_empty()) {
+                data.set_snippet_name(id, None);
+            }
+            let changed = match id {
+                SnippetId::Draw(id) => !data
+                    .scribl
+                    .draw
+                    .snippet(id)
+                    .is_copy_of(prev_state.snippets.snippet(id)),
+                SnippetId::Talk(id) => {
+                    data.scribl.talk.snippet(id) != prev_state.audio_snippets.snippet(id)
+                }
+            };
+            if changed {
+                data.push_undo_state(prev_state.clone(), "edit snippet properties");
+            }
+        }
+    }
+    ctx.submit_command(ModalHost::DISMISS_MODAL);
+}
+
+/// Closes the snippet properties dialog when they press Enter or Escape.
+struct SnippetPropertiesController {
+    prev_state: UndoState,
+    id: SnippetId,
+}
+
+impl<W: Widget<EditorState>> Controller<EditorState, W> for SnippetPropertiesController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut EditorState,
+        env: &Env,
+    ) {
+        child.event(ctx, event, data, env);
+        if let Event::KeyDown(ev) = event {
+            if ev.key == KbKey::Enter || ev.key == KbKey::Escape {
+                close_snippet_properties(ctx, data, &self.prev_state, self.id);
+                ctx.set_handled();
+            }
+        }
+    }
+}
+
+/// A lens onto one property of the audio snippet `id`. The snippet gets edited (without an undo
+/// state) as they type, but only while the properties dialog is open.
+fn audio_property<T: Data + Default + PartialEq>(
+    id: TalkSnippetId,
+    get: impl Fn(&TalkSnippet) -> T + Copy + 'static,
+    set: impl Fn(&TalkSnippet, T) -> TalkSnippet + 'static,
+) -> impl Lens<EditorState, T> {
+    let snip_id = SnippetId::Talk(id);
+    lens::Identity.map(
+        move |data: &EditorState| {
+            if data.has_snippet(snip_id) {
+                get(data.scribl.talk.snippet(id))
+            } else {
+                T::default()
+            }
+        },
+        move |data: &mut EditorState, value: T| {
+            if matches!(data.action, CurrentAction::EditingText)
+                && data.has_snippet(snip_id)
+                && get(data.scribl.talk.snippet(id)) != value
+            {
+                data.edit_talk_snippet(id, |snip| set(snip, value));
+            }
+        },
+    )
+}
+
+/// Like [`audio_property`], but for a drawing snippet.
+fn drawing_property<T: Data + Default + PartialEq>(
+    id: DrawSnippetId,
+    get: impl Fn(&DrawSnippet) -> T + Copy + 'static,
+    set: impl Fn(&DrawSnippet, T) -> DrawSnippet + 'static,
+) -> impl Lens<EditorState, T> {
+    let snip_id = SnippetId::Draw(id);
+    lens::Identity.map(
+        move |data: &EditorState| {
+            if data.has_snippet(snip_id) {
+                get(data.scribl.draw.snippet(id))
+            } else {
+                T::default()
+            }
+        },
+        move |data: &mut EditorState, value: T| {
+            if matches!(data.action, CurrentAction::EditingText)
+                && data.has_snippet(snip_id)
+                && get(data.scribl.draw.snippet(id)) != value
+            {
+                data.edit_draw_snippet(id, |snip| set(snip, value));
+            }
+        },
+    )
+}
+
+/// One row of a snippet properties dialog.
+fn property_row(
+    label: &str,
+    input: impl Widget<EditorState> + 'static,
+    unit: &str,
+) -> impl Widget<EditorState> {
+    Flex::row()
+        .with_child(Label::new(label).fix_width(100.0))
+        .with_child(input)
+        .with_spacer(5.0)
+        .with_child(Label::new(unit))
+}
+
+/// A text box for editing a snippet's name.
+fn name_input(lens: impl Lens<EditorState, String> + 'static) -> impl Widget<EditorState> {
+    TextBox::new().lens(lens).fix_width(200.0)
+}
+
+/// A text box for editing a time.
+fn time_input(lens: impl Lens<EditorState, Time> + 'static) -> impl Widget<EditorState> {
+    TextBox::new()
+        .with_placeholder("1:23.5")
+        .with_formatter(TimeFormatter)
+        .update_data_while_editing(true)
+        .lens(lens)
+        .fix_width(120.0)
+}
+
+/// A text box for editing a number.
+fn number_input<T: Data + std::fmt::Display + std::str::FromStr>(
+    lens: impl Lens<EditorState, T> + 'static,
+) -> impl Widget<EditorState>
+where
+    <T as std::str::FromStr>::Err: std::error::Error + 'static,
+{
+    TextBox::new()
+        .with_formatter(ParseFormatter::new())
+        .update_data_while_editing(true)
+        .lens(lens)
+        .fix_width(80.0)
+}
+
+/// Wraps up the rows of a snippet properties dialog.
+fn properties_dialog(
+    prev_state: UndoState,
+    id: SnippetId,
+    rows: Flex<EditorState>,
+) -> impl Widget<EditorState> {
+    let prev_clone = prev_state.clone();
+    let ok = Button::new("Done")
+        .on_click(move |ctx, data, _env| close_snippet_properties(ctx, data, &prev_clone, id));
+
+    rows.cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_spacer(15.0)
+        .with_child(ok)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+        .controller(SnippetPropertiesController { prev_state, id })
+}
+
+/// Creates a dialog for editing the properties of an audio snippet.
+///
+/// Like the snippet name dialog, the snippet gets updated as they type and the undo state gets
+/// pushed when the dialog is closed.
+pub fn make_audio_properties_alert(
+    prev_state: UndoState,
+    id: TalkSnippetId,
+) -> impl Widget<EditorState> {
+    let name = name_input(audio_property(
+        id,
+        |snip| snip.name().unwrap_or("").to_owned(),
+        |snip, name| snip.with_name(Some(name)),
+    ));
+    let start = time_input(audio_property(
+        id,
+        |snip| snip.start_time(),
+        |snip, time| snip.shifted(time - snip.start_time()),
+    ));
+    // The gain is rounded to a tenth of a decibel, so that the text box doesn't show a long
+    // string of digits.
+    let gain = number_input(audio_property(
+        id,
+        |snip| (200.0 * (snip.multiplier() as f64).log10()).round() / 10.0,
+        |snip, db: f64| snip.with_multiplier(10.0f64.powf(db / 20.0) as f32),
+    ));
+    let fade_in = number_input(audio_property(
+        id,
+        |snip| snip.fade_in().as_micros() / 1000,
+        |snip, ms: i64| snip.with_fades(TimeDiff::from_micros(ms * 1000), snip.fade_out()),
+    ));
+    let fade_out = number_input(audio_property(
+        id,
+        |snip| snip.fade_out().as_micros() / 1000,
+        |snip, ms: i64| snip.with_fades(snip.fade_in(), TimeDiff::from_micros(ms * 1000)),
+    ));
+    let length = Label::new(move |data: &EditorState, _env: &Env| {
+        if data.has_snippet(SnippetId::Talk(id)) {
+            let snip = data.scribl.talk.snippet(id);
+            let length = Time::ZERO + (snip.end_time() - snip.start_time());
+            format!("Length: {}", length)
+        } else {
+            "This snippet no longer exists.".to_owned()
+        }
+    });
+
+    let rows = Flex::column()
+        .with_child(property_row("Name:", name, ""))
+        .with_spacer(5.0)
+        .with_child(property_row("Start:", start, ""))
+        .with_spacer(5.0)
+        .with_child(property_row("Gain:", gain, "dB"))
+        .with_spacer(5.0)
+        .with_child(property_row("Fade in:", fade_in, "ms"))
+        .with_spacer(5.0)
+        .with_child(property_row("Fade out:", fade_out, "ms"))
+        .with_spacer(5.0)
+        .with_child(length);
+    properties_dialog(prev_state, SnippetId::Talk(id), rows)
+}
+
+/// Creates a dialog for editing the properties of a drawing snippet. It works like the one for
+/// audio snippets.
+pub fn make_drawing_properties_alert(
+    prev_state: UndoState,
+    id: DrawSnippetId,
+) -> impl Widget<EditorState> {
+    let name = name_input(drawing_property(
+        id,
+        |snip| snip.name().unwrap_or("").to_owned(),
+        |snip, name| snip.with_name(Some(name)),
+    ));
+    let start = time_input(drawing_property(
+        id,
+        |snip| snip.start_time(),
+        |snip, time| snip.shifted(time - snip.start_time()),
+    ));
+    let end = TextBox::new()
+        .with_placeholder("never")
+        .with_formatter(OptionalTimeFormatter)
+        .update_data_while_editing(true)
+        .lens(drawing_property(
+            id,
+            |snip| snip.end_time(),
+            |snip, end| snip.with_end_time(end),
+        ))
+        .fix_width(120.0);
+    // Like the gain of audio snippets, the speed is rounded to keep the text short.
+    let speed = number_input(drawing_property(
+        id,
+        |snip| (snip.speed() * 100.0).round() / 100.0,
+        |snip, speed: f64| snip.with_speed(speed),
+    ));
+    let layer = number_input(drawing_property(
+        id,
+        |snip| snip.layer(),
+        |snip, layer: i32| snip.with_layer(layer),
+    ));
+    let hidden = Checkbox::new("Hidden").lens(drawing_property(
+        id,
+        |snip| snip.hidden(),
+        |snip, hidden| snip.with_hidden(hidden),
+    ));
+    let details = Label::new(move |data: &EditorState, _env: &Env| {
+        if data.has_snippet(SnippetId::Draw(id)) {
+            let snip = data.scribl.draw.snippet(id);
+            format!(
+                "Strokes: {}\nLast stroke: {}",
+                snip.strokes().count(),
+                snip.last_draw_time()
+            )
+        } else {
+            "This snippet no longer exists.".to_owned()
+        }
+    });
+
+    let rows = Flex::column()
+        .with_child(property_row("Name:", name, ""))
+        .with_spacer(5.0)
+        .with_child(property_row("Start:", start, ""))
+        .with_spacer(5.0)
+        .with_child(property_row("End:", end, "(empty for never)"))
+        .with_spacer(5.0)
+        .with_child(property_row("Speed:", speed, "times as fast as drawn"))
+        .with_spacer(5.0)
+        .with_child(property_row("Layer:", layer, "(higher is on top)"))
+        .with_spacer(5.0)
+        .with_child(hidden)
+        .with_spacer(5.0)
+        .with_child(details);
+    properties_dialog(prev_state, SnippetId::Draw(id), rows)
+}
+
+/// A description of a pair of duplicate snippets, and what has been done about them.
+fn describe_duplicate(data: &EditorState, dup: &Duplicate) -> String {
+    let copy = SnippetId::Talk(dup.copy);
+    let original = SnippetId::Talk(dup.original);
+    if !data.has_snippet(copy) {
+        return "Deleted".to_owned();
+    }
+    let mut desc = format!(
+        "The {} is {:.0}% similar to ",
+        data.describe_snippet(copy),
+        dup.similarity * 100.0
+    );
+    if data.has_snippet(original) {
+        desc.push_str(&data.describe_snippet(original));
+    } else {
+        desc.push_str("a deleted snippet");
+    }
+    if data.scribl.talk.snippet(dup.copy).muted() {
+        desc.push_str(" (muted)");
+    }
+    desc
+}
+
+/// Creates a list of audio snippets that overlap with copies of themselves, with buttons for
+/// muting or deleting the copies.
+pub fn make_duplicate_audio_alert(duplicates: Vec<Duplicate>) -> impl Widget<EditorState> {
+    let mut list = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    for dup in duplicates {
+        let id = dup.copy;
+        let exists = move |data: &EditorState| data.scribl.talk.has_snippet(id);
+        let desc = Label::new(move |data: &EditorState, _env: &Env| describe_duplicate(data, &dup))
+            .with_line_break_mode(LineBreaking::WordWrap);
+        let mute = Button::new("Mute copy")
+            .on_click(move |_ctx, data: &mut EditorState, _env| data.toggle_snippet_muted(id))
+            .disabled_if(move |data, _| !exists(data) || data.scribl.talk.snippet(id).muted());
+        let delete = Button::new("Delete copy")
+            .on_click(move |_ctx, data: &mut EditorState, _env| {
+                data.delete_snippet(SnippetId::Talk(id))
+            })
+            .disabled_if(move |data, _| !exists(data));
+        list.add_child(
+            Flex::row()
+                .with_child(desc.fix_width(300.0))
+                .with_spacer(5.0)
+                .with_child(mute)
+                .with_spacer(5.0)
+                .with_child(delete)
+                .padding((0.0, 2.0)),
+        );
+    }
+
+    let close = Button::new("Close").on_click(|ctx, _data, _env| {
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+
+    Flex::column()
+        .with_child(
+            Label::new(
+                "These audio snippets play at the same time as a copy of themselves, which \
+                 sounds like an echo:",
+            )
+            .with_line_break_mode(LineBreaking::WordWrap),
+        )
+        .with_spacer(5.0)
+        .with_child(Scroll::new(list).vertical().fix_height(200.0))
+        .with_spacer(15.0)
+        .with_child(close)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+}
+
+/// Closes the speech detection dialog. If `apply` is true, the threshold they chose gets used for
+/// new recordings.
+fn close_vad_preview(ctx: &mut EventCtx, data: &mut EditorState, apply: bool) {
+    if let Some(preview) = &data.vad_preview {
+        if apply {
+            let threshold = preview.threshold();
+            data.config.audio_input.vad_threshold = threshold as f32;
+            data.status.last_finished = Some(FinishedStatus::Notice(format!(
+                "Set the speech detection threshold to {}",
+                threshold
+            )));
+        }
+    }
+    data.close_vad_preview();
+    ctx.submit_command(ModalHost::DISMISS_MODAL);
+}
+
+/// Closes the speech detection dialog (without applying the threshold) when they press Escape.
+struct VadPreviewController;
+
+impl<W: Widget<EditorState>> Controller<EditorState, W> for VadPreviewController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut EditorState,
+        env: &Env,
+    ) {
+        child.event(ctx, event, data, env);
+        if let Event::KeyDown(ev) = event {
+            if ev.key == KbKey::Escape {
+                close_vad_preview(ctx, data, false);
+                ctx.set_handled();
+            }
+        }
+    }
+}
+
+/// Creates a dialog for choosing how sure the speech detection needs to be before it keeps some
+/// audio. The selected snippet can be played with the quiet parts cut out, and it gets updated
+/// as they drag the slider.
+pub fn make_vad_preview_alert() -> impl Widget<EditorState> {
+    let threshold = |data: &EditorState| data.vad_preview.as_ref().map_or(0.0, |p| p.threshold());
+    let slider = Flex::row()
+        .with_child(Slider::new().with_range(0.0, 1.0).fix_width(200.0))
+        .with_spacer(5.0)
+        .with_child(Label::dynamic(|threshold: &f64, _env: &Env| {
+            format!("{:.2}", threshold)
+        }))
+        .lens(lens::Identity.map(
+            move |data: &EditorState| threshold(data),
+            |data: &mut EditorState, threshold: f64| data.set_vad_preview_threshold(threshold),
+        ));
+    let config_note = Label::new(move |data: &EditorState, _env: &Env| {
+        format!(
+            "To keep using this threshold after restarting, add \"vad_threshold = {:.2}\" \
+             to the [audio_input] section of your configuration file.",
+            threshold(data)
+        )
+    })
+    .with_line_break_mode(LineBreaking::WordWrap)
+    .fix_width(400.0);
+    let play = Button::dynamic(|data: &EditorState, _| {
+        if data.action.is_playing() {
+            "Stop".to_owned()
+        } else {
+            "Play".to_owned()
+        }
+    })
+    .on_click(|_ctx, data: &mut EditorState, _env| data.toggle_vad_preview_playing());
+    let cancel = Button::new("Cancel").on_click(|ctx, data, _env| {
+        close_vad_preview(ctx, data, false);
+    });
+    let apply = Button::new("Use this threshold").on_click(|ctx, data, _env| {
+        close_vad_preview(ctx, data, true);
+    });
+    let button_row = Flex::row()
+        .with_child(play)
+        .with_spacer(5.0)
+        .with_child(cancel)
+        .with_spacer(5.0)
+        .with_child(apply);
+
+    Flex::column()
+        .with_child(
+            Label::new(
+                "When speech detection is on, the parts of a recording that don't sound like \
+                 speech get cut out. Higher thresholds cut out more. Press \"Play\" to hear the \
+                 selected audio snippet with the current threshold.",
+            )
+            .with_line_break_mode(LineBreaking::WordWrap)
+            .fix_width(400.0),
+        )
+        .with_spacer(10.0)
+        .with_child(slider)
+        .with_spacer(10.0)
+        .with_child(config_note)
+        .with_spacer(15.0)
+        .with_child(button_row)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+        .controller(VadPreviewController)
+}
+
+/// Finishes retiming a drawing. If the timing was changed, this pushes an undo state (whose
+/// "before" state is `prev_state`).
+fn close_retime(ctx: &mut EventCtx, data: &mut EditorState, prev_state: &UndoState) {
+    if matches!(data.action, CurrentAction::EditingText) {
+        data.action = CurrentAction::Idle;
+        if !data.scribl.draw.same(&prev_state.snippets) {
+            data.push_undo_state(prev_state.clone(), "retime drawing");
+        }
+    }
+    ctx.submit_command(ModalHost::DISMISS_MODAL);
+}
+
+/// Closes the retiming dialog when they press Enter or Escape.
+struct RetimeController {
+    prev_state: UndoState,
+}
+
+impl<W: Widget<EditorState>> Controller<EditorState, W> for RetimeController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut EditorState,
+        env: &Env,
+    ) {
+        child.event(ctx, event, data, env);
+        if let Event::KeyDown(ev) = event {
+            if ev.key == KbKey::Enter || ev.key == KbKey::Escape {
+                close_retime(ctx, data, &self.prev_state);
+                ctx.set_handled();
+            }
+        }
+    }
+}
+
+/// Creates a dialog for changing how fast a drawing gets drawn.
+///
+/// Like the name dialogs, the drawing gets updated as they drag, and the undo state gets pushed
+/// when the dialog is closed.
+pub fn make_retime_alert(prev_state: UndoState, id: DrawSnippetId) -> impl Widget<EditorState> {
+    let prev_clone = prev_state.clone();
+    let ok =
+        Button::new("Done").on_click(move |ctx, data, _env| close_retime(ctx, data, &prev_clone));
+
+    Flex::column()
+        .with_child(Label::new(
+            "Drag the points sideways to change when that part of the drawing gets drawn.\n\
+             Double-click to add or remove a point. Right-click between two points\n\
+             to change whether the drawing speeds up or slows down between them.",
+        ))
+        .with_spacer(10.0)
+        .with_child(RetimeGraph::new(id))
+        .with_spacer(15.0)
+        .with_child(ok)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+        .controller(RetimeController { prev_state })
+}
+
+/// Gives each row in the list of checkpoints its index, so that the buttons in that row know which
+/// checkpoint they refer to. Changes to the checkpoint names get written back.
+struct IndexedCheckpoints;
+
+fn indexed(checkpoints: &Vector<Checkpoint>) -> Vector<(usize, Checkpoint)> {
+    checkpoints.iter().cloned().enumerate().collect()
+}
+
+impl Lens<EditorState, Vector<(usize, Checkpoint)>> for IndexedCheckpoints {
+    fn with<V, F: FnOnce(&Vector<(usize, Checkpoint)>) -> V>(&self, data: &EditorState, f: F) -> V {
+        f(&indexed(&data.scribl.checkpoints))
+    }
+
+    fn with_mut<V, F: FnOnce(&mut Vector<(usize, Checkpoint)>) -> V>(
+        &self,
+        data: &mut EditorState,
+        f: F,
+    ) -> V {
+        let mut rows = indexed(&data.scribl.checkpoints);
+        let ret = f(&mut rows);
+        for (idx, checkpoint) in rows {
+            let changed = data
+                .scribl
+                .checkpoints
+                .get(idx)
+                .map_or(false, |old| !old.same(&checkpoint));
+            if changed {
+                data.scribl.checkpoints.set(idx, checkpoint);
+            }
+        }
+        ret
+    }
+}
+
+fn close_checkpoints(ctx: &mut EventCtx, data: &mut EditorState) {
+    if matches!(data.action, CurrentAction::EditingText) {
+        data.action = CurrentAction::Idle;
+    }
+    data.checkpoint_preview = None;
+    ctx.submit_command(ModalHost::DISMISS_MODAL);
+}
+
+/// Closes the checkpoints panel when they press Escape.
+struct CheckpointsController;
+
+impl<W: Widget<EditorState>> Controller<EditorState, W> for CheckpointsController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut EditorState,
+        env: &Env,
+    ) {
+        child.event(ctx, event, data, env);
+        if let Event::KeyDown(ev) = event {
+            if ev.key == KbKey::Escape {
+                close_checkpoints(ctx, data);
+                ctx.set_handled();
+            }
+        }
+    }
+}
+
+fn make_checkpoint_row() -> impl Widget<(usize, Checkpoint)> {
+    let name = TextBox::new()
+        .lens(lens!((usize, Checkpoint), 1).then(Checkpoint::name))
+        .fix_width(160.0);
+    let preview = Button::new("Preview").on_click(|ctx, data: &mut (usize, Checkpoint), _env| {
+        ctx.submit_command(cmd::PREVIEW_CHECKPOINT.with(Some(data.0)));
+    });
+    let restore = Button::new("Restore").on_click(|ctx, data: &mut (usize, Checkpoint), _env| {
+        ctx.submit_command(cmd::RESTORE_CHECKPOINT.with(data.0));
+    });
+    let delete = Button::new("Delete").on_click(|ctx, data: &mut (usize, Checkpoint), _env| {
+        ctx.submit_command(cmd::DELETE_CHECKPOINT.with(data.0));
+    });
+
+    Flex::row()
+        .with_child(name)
+        .with_spacer(5.0)
+        .with_child(preview)
+        .with_spacer(5.0)
+        .with_child(restore)
+        .with_spacer(5.0)
+        .with_child(delete)
+        .padding((0.0, 2.0))
+}
+
+/// Creates a panel for creating, renaming, previewing, and restoring checkpoints.
+pub fn make_checkpoints_alert() -> impl Widget<EditorState> {
+    let list = Scroll::new(List::new(make_checkpoint_row).lens(IndexedCheckpoints))
+        .vertical()
+        .fix_height(200.0);
+    let empty = Label::new("There are no checkpoints yet.");
+    let list = Either::new(
+        |data: &EditorState, _| data.scribl.checkpoints.is_empty(),
+        empty,
+        list,
+    );
+
+    let add = Button::new("Save current state as a checkpoint")
+        .on_click(|_ctx, data: &mut EditorState, _env| data.add_checkpoint());
+    let stop_preview = Button::new("Stop previewing")
+        .on_click(|ctx, _data, _env| ctx.submit_command(cmd::PREVIEW_CHECKPOINT.with(None)))
+        .disabled_if(|data: &EditorState, _| data.checkpoint_preview.is_none());
+    let close = Button::new("Close").on_click(|ctx, data, _env| close_checkpoints(ctx, data));
+    let button_row = Flex::row()
+        .with_child(add)
+        .with_spacer(5.0)
+        .with_child(stop_preview)
+        .with_spacer(5.0)
+        .with_child(close);
+
+    Flex::column()
+        .with_child(Label::new("Checkpoints"))
+        .with_spacer(5.0)
+        .with_child(list)
+        .with_spacer(15.0)
+        .with_child(button_row)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+        .controller(CheckpointsController)
+}
+
+fn make_export_job_row() -> impl Widget<ExportJob> {
+    let description = Label::dynamic(|job: &ExportJob, _env| job.description())
+        .with_line_break_mode(LineBreaking::WordWrap)
+        .fix_width(240.0);
+    let status = Label::dynamic(|job: &ExportJob, _env| match &job.status {
+        ExportJobStatus::Queued => "Queued".to_owned(),
+        ExportJobStatus::Running { frame, out_of } if *out_of > 0 => {
+            format!("Frame {} of {}", frame, out_of)
+        }
+        ExportJobStatus::Running { .. } => "Starting".to_owned(),
+        ExportJobStatus::Finished => "Done".to_owned(),
+        ExportJobStatus::Failed(e) => format!("Failed: {}", e),
+    })
+    .with_line_break_mode(LineBreaking::WordWrap)
+    .fix_width(160.0);
+    let progress = ProgressBar::new()
+        .lens(lens::Identity.map(
+            |job: &ExportJob| match job.status {
+                ExportJobStatus::Running { frame, out_of } if out_of > 0 => {
+                    frame as f64 / out_of as f64
+                }
+                ExportJobStatus::Finished => 1.0,
+                _ => 0.0,
+            },
+            |_, _| {},
+        ))
+        .fix_width(100.0);
+    let cancel = Button::new("Cancel")
+        .on_click(|ctx, job: &mut ExportJob, _env| {
+            ctx.submit_command(cmd::CANCEL_EXPORT.with(job.id))
+        })
+        .disabled_if(|job: &ExportJob, _| job.status != ExportJobStatus::Queued);
+
+    Flex::row()
+        .with_child(description)
+        .with_spacer(5.0)
+        .with_child(progress)
+        .with_spacer(5.0)
+        .with_child(status)
+        .with_spacer(5.0)
+        .with_child(cancel)
+        .padding((0.0, 2.0))
+}
+
+/// Creates a panel that shows the progress of all the exports.
+pub fn make_export_queue_alert() -> impl Widget<EditorState> {
+    // The list is only for display (the cancel buttons send commands), so changes to it get
+    // thrown away.
+    let jobs = lens::Identity.map(
+        |data: &EditorState| data.status.exports.jobs().clone(),
+        |_, _| {},
+    );
+    let list = Scroll::new(List::new(make_export_job_row).lens(jobs))
+        .vertical()
+        .fix_height(200.0);
+    let empty = Label::new("Nothing has been exported yet.");
+    let list = Either::new(
+        |data: &EditorState, _| data.status.exports.is_empty(),
+        empty,
+        list,
+    );
+
+    let clear = Button::new("Clear finished exports")
+        .on_click(|_ctx, data: &mut EditorState, _env| data.status.exports.clear_finished());
+    let close = Button::new("Close").on_click(|ctx, _data, _env| {
+        ctx.submit_command(ModalHost::DISMISS_MODAL);
+    });
+    let button_row = Flex::row()
+        .with_child(clear)
+        .with_spacer(5.0)
+        .with_child(close);
+
+    Flex::column()
+        .with_child(Label::new("Exports"))
+        .with_spacer(5.0)
+        .with_child(list)
+        .with_spacer(15.0)
+        .with_child(button_row)
+        .padding(10.0)
+        .background(druid::theme::BACKGROUND_LIGHT)
+        .border(druid::theme::FOREGROUND_DARK, 1.0)
+}
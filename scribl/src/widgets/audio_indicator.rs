@@ -22,6 +22,9 @@ static BAR_COLORS: &[Color] = &[
     Color::rgb8(248, 151, 31),
 ];
 
+/// How many columns of level history we show.
+const HISTORY_COLUMNS: usize = 8;
+
 pub struct AudioIndicator {
     icon: IconWidget<EditorState>,
 }
@@ -33,9 +36,12 @@ fn calc_bands(loudness: f64) -> usize {
         .saturating_sub(((-loudness).max(0.0) / 4.0).floor() as usize)
 }
 
+static CLIPPING_COLOR: Color = Color::rgb8(220, 30, 30);
+static PEAK_HOLD_COLOR: Color = Color::WHITE;
+
 pub fn audio_indicator() -> Painter<EditorState> {
     Painter::new(|ctx, data: &EditorState, env| {
-        let bands = calc_bands(data.input_loudness);
+        let levels = &data.input_levels;
         let background = if data.action.is_recording_audio() {
             scribl_widget::BUTTON_ICON_SELECTED_COLOR
         } else {
@@ -52,11 +58,31 @@ pub fn audio_indicator() -> Painter<EditorState> {
         if data.action.is_recording_audio() {
             let band_height = rect.height() / (BAR_COLORS.len() as f64 * 1.5);
             let band_offset = band_height * 1.5;
+            let band_bottom = |i: usize| rect.height() - i as f64 * band_offset;
+
+            // The history scrolls from right to left, with the most recent level on the right.
+            let col_width = rect.width() / HISTORY_COLUMNS as f64;
+            let skip = levels.history.len().saturating_sub(HISTORY_COLUMNS);
+            let cols = levels.history.len() - skip;
+            for (col, &loudness) in levels.history.iter().skip(skip).enumerate() {
+                let x0 = rect.width() - (cols - col) as f64 * col_width;
+                for i in 0..calc_bands(loudness) {
+                    let bottom = band_bottom(i);
+                    let rect = Rect::new(x0, bottom - band_height, x0 + col_width, bottom);
+                    ctx.fill(rect, &BAR_COLORS[i]);
+                }
+            }
 
-            for i in 0..bands {
-                let bottom = rect.height() - i as f64 * band_offset;
+            let peak_bands = calc_bands(levels.peak_hold);
+            if peak_bands > 0 {
+                let bottom = band_bottom(peak_bands - 1);
                 let rect = Rect::new(0.0, bottom - band_height, rect.width(), bottom);
-                ctx.fill(rect, &BAR_COLORS[i]);
+                ctx.fill(rect, &PEAK_HOLD_COLOR);
+            }
+
+            if levels.clipped {
+                let rect = Rect::new(0.0, 0.0, rect.width(), band_height * 2.0);
+                ctx.fill(rect, &CLIPPING_COLOR);
             }
         }
     })
@@ -86,7 +112,7 @@ impl Widget<EditorState> for AudioIndicator {
         data: &EditorState,
         _env: &Env,
     ) {
-        if calc_bands(data.input_loudness) != calc_bands(old_data.input_loudness) {
+        if !data.input_levels.same(&old_data.input_levels) {
             ctx.request_paint();
         }
         // Don't bother calling update on the icon, because it all it ever does is request paint
@@ -0,0 +1,203 @@
+use druid::piet::{FontFamily, Text, TextLayoutBuilder};
+use druid::widget::prelude::*;
+use druid::{Color, Point, Rect, TimerToken, WidgetPod};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::EditorState;
+
+const HUD_SIZE: Size = Size::new(250.0, 96.0);
+const HUD_MARGIN: f64 = 8.0;
+const HUD_PADDING: f64 = 6.0;
+const HUD_FONT_SIZE: f64 = 11.0;
+const HUD_BACKGROUND: Color = Color::rgba8(0x00, 0x00, 0x00, 0xc0);
+const HUD_TEXT_COLOR: Color = Color::WHITE;
+const DAMAGE_COLOR: Color = Color::rgba8(0xff, 0x40, 0x40, 0xa0);
+
+// The statistics are over this many of the most recent frames.
+const HISTORY_LEN: usize = 120;
+// The HUD gets redrawn this often, so that the numbers in it stay current. (We can't just redraw
+// it along with everything else, because then it would never show an idle window.)
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A paint of the editor window.
+struct Frame {
+    at: Instant,
+    paint_time: Duration,
+    /// The number of update passes since the previous paint.
+    updates: usize,
+    /// How much of the window was repainted, as a fraction of its area.
+    damage: f64,
+}
+
+/// When the debug overlay is turned on (in the "Debug" menu), draws a HUD on top of its child
+/// showing how long painting takes, and outlines the parts of the window that just got
+/// repainted.
+///
+/// This is for tracking down unnecessary repainting (like the timeline repainting the whole window
+/// during playback). Outside of debug mode, this widget just shows its child.
+pub struct DebugOverlay {
+    inner: WidgetPod<EditorState, Box<dyn Widget<EditorState>>>,
+    frames: VecDeque<Frame>,
+    updates: usize,
+    refresh_timer: TimerToken,
+}
+
+impl DebugOverlay {
+    pub fn new(inner: impl Widget<EditorState> + 'static) -> DebugOverlay {
+        DebugOverlay {
+            inner: WidgetPod::new(inner).boxed(),
+            frames: VecDeque::new(),
+            updates: 0,
+            refresh_timer: TimerToken::INVALID,
+        }
+    }
+
+    fn hud_rect(size: Size) -> Rect {
+        let origin = Point::new(size.width - HUD_SIZE.width - HUD_MARGIN, HUD_MARGIN);
+        Rect::from_origin_size(origin, HUD_SIZE)
+    }
+
+    fn hud_text(&self) -> String {
+        let last = match self.frames.back() {
+            Some(f) => f,
+            None => return "No frames yet".to_owned(),
+        };
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let mean =
+            self.frames.iter().map(|f| f.paint_time).sum::<Duration>() / self.frames.len() as u32;
+        let max = self
+            .frames
+            .iter()
+            .map(|f| f.paint_time)
+            .max()
+            .unwrap_or_default();
+        let one_sec_ago = last.at - Duration::from_secs(1);
+        let per_sec = self.frames.iter().filter(|f| f.at > one_sec_ago).count();
+        format!(
+            "paint: {:.2}ms (mean {:.2}ms, max {:.2}ms)\n\
+             paints in the last second: {}\n\
+             updates before last paint: {}\n\
+             repainted area: {:.0}%",
+            ms(last.paint_time),
+            ms(mean),
+            ms(max),
+            per_sec,
+            last.updates,
+            last.damage * 100.0,
+        )
+    }
+}
+
+impl Widget<EditorState> for DebugOverlay {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut EditorState, env: &Env) {
+        if let Event::Timer(tok) = event {
+            if *tok == self.refresh_timer {
+                if data.settings.debug_overlay {
+                    ctx.request_paint_rect(DebugOverlay::hud_rect(ctx.size()));
+                    self.refresh_timer = ctx.request_timer(REFRESH_INTERVAL);
+                } else {
+                    self.refresh_timer = TimerToken::INVALID;
+                }
+                ctx.set_handled();
+                return;
+            }
+        }
+        self.inner.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &EditorState,
+        env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            if data.settings.debug_overlay {
+                self.refresh_timer = ctx.request_timer(REFRESH_INTERVAL);
+            }
+        }
+        self.inner.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &EditorState,
+        data: &EditorState,
+        env: &Env,
+    ) {
+        self.updates += 1;
+        if old_data.settings.debug_overlay != data.settings.debug_overlay {
+            self.frames.clear();
+            ctx.request_paint();
+            if data.settings.debug_overlay && self.refresh_timer == TimerToken::INVALID {
+                self.refresh_timer = ctx.request_timer(REFRESH_INTERVAL);
+            }
+        }
+        self.inner.update(ctx, data, env);
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &EditorState,
+        env: &Env,
+    ) -> Size {
+        let size = self.inner.layout(ctx, bc, data, env);
+        self.inner.set_origin(ctx, data, env, Point::ZERO);
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &EditorState, env: &Env) {
+        let start = Instant::now();
+        self.inner.paint(ctx, data, env);
+        let paint_time = start.elapsed();
+
+        if !data.settings.debug_overlay {
+            return;
+        }
+        let size = ctx.size();
+        let hud_rect = DebugOverlay::hud_rect(size);
+        let region = ctx.region().clone();
+        let damaged: Vec<Rect> = region
+            .rects()
+            .iter()
+            .map(|r| r.intersect(size.to_rect()))
+            .filter(|r| r.area() > 0.0)
+            .collect();
+
+        // Repaints of just the HUD come from the refresh timer, so they don't count as frames.
+        if damaged.iter().any(|r| hud_rect.union(*r) != hud_rect) {
+            let damaged_area: f64 = damaged.iter().map(|r| r.area()).sum();
+            if self.frames.len() == HISTORY_LEN {
+                self.frames.pop_front();
+            }
+            self.frames.push_back(Frame {
+                at: Instant::now(),
+                paint_time,
+                updates: self.updates,
+                damage: (damaged_area / size.to_rect().area().max(1.0)).min(1.0),
+            });
+            self.updates = 0;
+            for r in &damaged {
+                ctx.stroke(r.inset(-1.0), &DAMAGE_COLOR, 2.0);
+            }
+        }
+
+        ctx.fill(hud_rect, &HUD_BACKGROUND);
+        let layout = ctx
+            .text()
+            .new_text_layout(self.hud_text())
+            .font(FontFamily::MONOSPACE, HUD_FONT_SIZE)
+            .text_color(HUD_TEXT_COLOR)
+            .max_width(HUD_SIZE.width - 2.0 * HUD_PADDING)
+            .build();
+        if let Ok(layout) = layout {
+            let origin = hud_rect.origin() + (HUD_PADDING, HUD_PADDING);
+            ctx.draw_text(&layout, origin);
+        }
+    }
+}
@@ -1,13 +1,20 @@
+use anyhow::{anyhow, Error};
 use druid::kurbo::TranslateScale;
+use druid::piet::{Device, FontFamily, ImageFormat, Text, TextLayout, TextLayoutBuilder};
 use druid::{
-    BoxConstraints, Color, Cursor, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
-    PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Vec2, Widget, WindowHandle,
+    BoxConstraints, Color, Cursor, Data, Env, Event, EventCtx, ExtEventSink, LayoutCtx, LifeCycle,
+    LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Selector, Size, UpdateCtx, Vec2, Widget,
+    WidgetId, WindowHandle,
 };
+use std::time::Instant;
 
-use scribl_curves::{DrawCursor, Time};
+use scribl_core::underlay::{Frame, Underlay, UnderlayPlayer};
+use scribl_curves::{DrawCursor, DrawSnippet, DrawSnippetId, Time};
 
+use crate::cmd;
 use crate::cursor::CursorCache;
-use crate::EditorState;
+use crate::widgets::palm_rejection::{PendingStroke, Verdict};
+use crate::{EditorState, SnippetId};
 
 // The drawing coordinates are chosen so that the width of the image is always
 // 1.0. For now we also fix the height, but eventually we will support other aspect
@@ -17,6 +24,19 @@ pub const DRAWING_HEIGHT: f64 = 0.75;
 
 const ASPECT_RATIO: f64 = DRAWING_WIDTH / DRAWING_HEIGHT;
 const PAPER_COLOR: Color = Color::rgb8(0xff, 0xff, 0xff);
+const VERSION_LABEL_COLOR: Color = Color::rgb8(0xd0, 0x30, 0x30);
+const VERSION_LABEL_FONT_SIZE: f64 = 14.0;
+const ZOOM_LABEL_COLOR: Color = Color::rgb8(0x80, 0x80, 0x80);
+const ZOOM_LABEL_FONT_SIZE: f64 = 12.0;
+// The strokes of the selected snippet get a glow around them, made of a faint wide outline and a
+// stronger narrow one. The widths are in pixels, on each side of the stroke.
+const HIGHLIGHT_COLOR: Color = crate::UI_LIGHT_BLUE;
+const HIGHLIGHT_OUTER_WIDTH: f64 = 5.0;
+const HIGHLIGHT_OUTER_ALPHA: f64 = 0.25;
+const HIGHLIGHT_INNER_WIDTH: f64 = 2.0;
+const HIGHLIGHT_INNER_ALPHA: f64 = 0.6;
+// Alt-clicking within this many pixels of a stroke selects its snippet.
+const HIT_TOLERANCE: f64 = 4.0;
 
 pub struct DrawingPane {
     paper_rect: Rect,
@@ -27,7 +47,41 @@ pub struct DrawingPane {
     offset: Vec2,
     /// The last interesting position of the mouse (used for figuring out how much to pan by).
     last_mouse_pos: Point,
+    /// True if we're panning the view with the middle mouse button. (With the left button, we
+    /// only pan when we aren't drawing.)
+    middle_panning: bool,
+    /// If palm rejection is on, this holds the start of the current stroke until we decide
+    /// whether to keep it. The `Instant` is the wall-clock time at which the stroke started.
+    pending_stroke: Option<(PendingStroke, Instant)>,
+    /// True if we rejected the current stroke, and so we're ignoring the pointer until it's
+    /// released.
+    rejecting_stroke: bool,
     cursors: CursorCache,
+    /// Decodes the underlay (if there is one) in the background. The frames come back to us in
+    /// `UNDERLAY_FRAME` commands.
+    underlay_player: Option<UnderlayPlayer>,
+    /// The underlay frame to show behind the drawing.
+    underlay_frame: Option<Frame>,
+    /// Like `underlay_player`, but for the webcam video. Its frames come in `WEBCAM_FRAME`
+    /// commands.
+    webcam_player: Option<UnderlayPlayer>,
+    /// The webcam frame to show on top of the drawing.
+    webcam_frame: Option<Frame>,
+}
+
+/// Starts decoding a video in the background, sending its frames to the widget `id`.
+fn start_player(
+    video: Option<&Underlay>,
+    selector: Selector<Option<Frame>>,
+    ext_cmd: ExtEventSink,
+    id: WidgetId,
+    time: Time,
+) -> Option<UnderlayPlayer> {
+    let player = UnderlayPlayer::new(video?, move |frame| {
+        let _ = ext_cmd.submit_command(selector, Box::new(frame), id);
+    });
+    player.seek(time);
+    Some(player)
 }
 
 impl DrawingPane {
@@ -51,6 +105,153 @@ impl DrawingPane {
             .pen(window_id, data.settings.palette.selected_color())
     }
 
+    /// The snippets that are visible at the current time, on the current page.
+    fn visible_snippets<'a>(
+        &'a self,
+        data: &'a EditorState,
+    ) -> impl Iterator<Item = (DrawSnippetId, &'a DrawSnippet)> {
+        let drawing = data.visible_drawing();
+        self.cursor
+            .active_ids_in_drawing_order(drawing)
+            .into_iter()
+            .map(move |id| (id, drawing.snippet(id)))
+            .filter(move |(_, snip)| data.scribl.pages.shows_snippet(snip, data.time()))
+    }
+
+    /// The draw snippet that's selected in the timeline, if there is one.
+    fn highlighted_snippet(data: &EditorState) -> Option<DrawSnippetId> {
+        match data.selected_snippet {
+            Some(SnippetId::Draw(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Draws a glow around the visible strokes of `snip`. This should be drawn before the snippet
+    /// itself, so that the glow is behind the strokes.
+    fn render_highlight(&self, ctx: &mut impl RenderContext, snip: &DrawSnippet, time: Time) {
+        let px = 1.0 / self.from_image_scale();
+        let style = scribl_curves::render::stroke_style();
+        for stroke in snip.visible_strokes(time) {
+            for &(width, alpha) in &[
+                (HIGHLIGHT_OUTER_WIDTH, HIGHLIGHT_OUTER_ALPHA),
+                (HIGHLIGHT_INNER_WIDTH, HIGHLIGHT_INNER_ALPHA),
+            ] {
+                let color = HIGHLIGHT_COLOR.with_alpha(alpha * stroke.alpha);
+                let thickness = stroke.thickness + 2.0 * width * px;
+                ctx.stroke_styled(&stroke.path, &color, thickness, &style);
+            }
+        }
+    }
+
+    /// Finds the snippet with a stroke under `pos` (in screen coordinates). If there are several,
+    /// this is the one that was drawn on top.
+    fn snippet_at(&self, data: &EditorState, pos: Point) -> Option<DrawSnippetId> {
+        let p = self.to_image_coords() * pos;
+        let tolerance = HIT_TOLERANCE / self.from_image_scale();
+        self.visible_snippets(data)
+            .filter(|(_, snip)| snip.hit_test(p, data.time(), tolerance))
+            .map(|(id, _)| id)
+            .last()
+    }
+
+    /// Finds the color of the drawing at the pixel under `pos`.
+    fn pick_color(&self, data: &EditorState, pos: Point) -> Result<Color, Error> {
+        let mut device = Device::new().map_err(|e| anyhow!("failed to get device: {}", e))?;
+        let mut bitmap = device
+            .bitmap_target(1, 1, 1.0)
+            .map_err(|e| anyhow!("failed to make bitmap: {}", e))?;
+        // Render into a single pixel, positioned so that `pos` is at its center.
+        let transform = TranslateScale::translate(Vec2::new(0.5, 0.5) - pos.to_vec2())
+            * self.from_image_coords();
+        {
+            let mut ctx = bitmap.render_context();
+            ctx.clear(None, PAPER_COLOR);
+            ctx.transform(transform.into());
+            for (_, snip) in self.visible_snippets(data) {
+                snip.render(&mut ctx, data.time());
+            }
+            ctx.finish()
+                .map_err(|e| anyhow!("failed to finish context: {}", e))?;
+        }
+        let mut pixel = [0u8; 4];
+        bitmap
+            .copy_raw_pixels(ImageFormat::RgbaPremul, &mut pixel)
+            .map_err(|e| anyhow!("failed to get pixels: {}", e))?;
+        // The paper is opaque, so there's no need to undo the alpha premultiplication.
+        Ok(Color::rgb8(pixel[0], pixel[1], pixel[2]))
+    }
+
+    /// Adds a point (in screen coordinates) to the stroke that's being drawn.
+    fn add_point(&mut self, ctx: &mut EventCtx, data: &mut EditorState, pos: Point, time: Time) {
+        // Compute the rectangle that needs to be invalidated in order to draw this new point.
+        let mut invalid = Rect::from_origin_size(pos, (0.0, 0.0));
+        let last_point = data.new_stroke().and_then(|s| s.last_point());
+        if let Some(last_point) = last_point {
+            invalid = invalid.union_pt(self.from_image_coords() * last_point);
+        }
+        let pen_width = data.settings.pen_size.size_fraction() * self.from_image_scale();
+        ctx.request_paint_rect(invalid.inset(pen_width).expand());
+
+        data.add_point_to_stroke(self.to_image_coords() * pos, time);
+    }
+
+    /// We've decided that the pending stroke is real, so add all of its points to the drawing.
+    fn flush_pending_stroke(&mut self, ctx: &mut EventCtx, data: &mut EditorState) {
+        if let Some((mut pending, _)) = self.pending_stroke.take() {
+            for (pos, time) in pending.take_points() {
+                self.add_point(ctx, data, pos, time);
+            }
+        }
+    }
+
+    fn reject_pending_stroke(&mut self) {
+        self.pending_stroke = None;
+        self.rejecting_stroke = true;
+    }
+
+    /// Starts decoding the current underlay, replacing the old one.
+    fn reset_underlay(&mut self, ext_cmd: ExtEventSink, id: WidgetId, data: &EditorState) {
+        self.underlay_frame = None;
+        self.underlay_player = start_player(
+            data.scribl.underlay.as_ref(),
+            cmd::UNDERLAY_FRAME,
+            ext_cmd,
+            id,
+            data.time(),
+        );
+    }
+
+    /// Starts decoding the current webcam video, replacing the old one.
+    fn reset_webcam(&mut self, ext_cmd: ExtEventSink, id: WidgetId, data: &EditorState) {
+        self.webcam_frame = None;
+        self.webcam_player = start_player(
+            data.scribl.webcam.as_ref().map(|w| &w.video),
+            cmd::WEBCAM_FRAME,
+            ext_cmd,
+            id,
+            data.time(),
+        );
+    }
+
+    /// Are we ignoring attempts to pan and zoom? (See `Settings::lock_viewport`.)
+    fn viewport_locked(data: &EditorState) -> bool {
+        data.settings.lock_viewport && data.action.is_recording()
+    }
+
+    fn pan(&mut self, size: Size, delta: Vec2, zoom: f64) {
+        self.offset -= delta / zoom;
+        self.recompute_paper_rect(size, zoom);
+    }
+
+    /// Changes the zoom level, trying to keep the part of the drawing under `pos` in place.
+    fn zoom_about(&mut self, size: Size, data: &mut EditorState, pos: Point, zoom: f64) {
+        let old_zoom = data.settings.zoom;
+        data.settings.set_zoom(zoom);
+        let zoom_factor = data.settings.zoom / old_zoom;
+        self.offset += pos.to_vec2() / old_zoom * (zoom_factor - 1.0);
+        self.recompute_paper_rect(size, data.settings.zoom);
+    }
+
     fn recompute_paper_rect(&mut self, size: Size, zoom: f64) {
         // Find the largest rectangle of the correct aspect ratio that will fit in the size.
         let paper_width = size.width.min(ASPECT_RATIO * size.height);
@@ -90,8 +291,15 @@ impl Default for DrawingPane {
             cursor: DrawCursor::empty(Time::ZERO),
             offset: Vec2::ZERO,
             last_mouse_pos: Point::ZERO,
+            middle_panning: false,
+            pending_stroke: None,
+            rejecting_stroke: false,
             // TODO: detect the default cursor size somehow
             cursors: CursorCache::new(32),
+            underlay_player: None,
+            underlay_frame: None,
+            webcam_player: None,
+            webcam_frame: None,
         }
     }
 }
@@ -101,67 +309,142 @@ impl Widget<EditorState> for DrawingPane {
         match event {
             Event::MouseMove(ev) => {
                 if ctx.is_active() {
-                    if data.action.is_recording() {
-                        let time = data.accurate_time();
-
-                        // Compute the rectangle that needs to be invalidated in order to draw this new
-                        // point.
-                        let mut invalid = Rect::from_origin_size(ev.pos, (0.0, 0.0));
-                        let last_point = data.new_stroke().and_then(|s| s.last_point());
-                        if let Some(last_point) = last_point {
-                            invalid = invalid.union_pt(self.from_image_coords() * last_point);
+                    if self.middle_panning || !data.action.is_recording() {
+                        if !DrawingPane::viewport_locked(data) {
+                            self.pan(ctx.size(), ev.pos - self.last_mouse_pos, data.settings.zoom);
+                            ctx.request_paint();
                         }
-                        let pen_width =
-                            data.settings.pen_size.size_fraction() * self.from_image_scale();
-                        ctx.request_paint_rect(invalid.inset(pen_width).expand());
-
-                        data.add_point_to_stroke(self.to_image_coords() * ev.pos, time);
                     } else {
-                        // Pan the view.
-                        self.offset -= (ev.pos - self.last_mouse_pos) / data.settings.zoom;
-                        self.recompute_paper_rect(ctx.size(), data.settings.zoom);
-                        ctx.request_paint();
-                        // TODO: change the mouse cursor
+                        let time = data.accurate_time();
+                        let verdict = self.pending_stroke.as_mut().map(|(pending, start)| {
+                            pending.add_point(ev.pos, time, start.elapsed())
+                        });
+                        match verdict {
+                            Some(Verdict::Accept) => self.flush_pending_stroke(ctx, data),
+                            Some(Verdict::Reject) => self.reject_pending_stroke(),
+                            Some(Verdict::Undecided) => {}
+                            None if !self.rejecting_stroke => {
+                                self.add_point(ctx, data, ev.pos, time)
+                            }
+                            None => {}
+                        }
                     }
-                    self.last_mouse_pos = ev.pos;
                 }
+                self.last_mouse_pos = ev.pos;
 
-                if data.action.is_recording() {
+                if data.action.is_recording() && !self.middle_panning {
                     let cursor = self.cursor(data, ctx.window());
                     ctx.set_cursor(cursor);
+                } else if data.settings.eyedropper {
+                    ctx.set_cursor(&Cursor::Crosshair);
                 }
             }
+            Event::MouseDown(ev)
+                if ev.button.is_left()
+                    && data.settings.eyedropper
+                    && !data.action.is_recording() =>
+            {
+                match self.pick_color(data, ev.pos) {
+                    Ok(color) => {
+                        data.settings.palette.select_color(color);
+                        data.settings.palette.mark_used();
+                    }
+                    Err(e) => log::error!("failed to pick a color: {}", e),
+                }
+                data.settings.eyedropper = false;
+                ctx.set_cursor(&Cursor::Arrow);
+            }
+            Event::MouseDown(ev)
+                if ev.button.is_left() && ev.mods.alt() && !data.action.is_recording() =>
+            {
+                // Clicking on empty paper clears the selection.
+                data.selected_snippet = self.snippet_at(data, ev.pos).map(SnippetId::Draw);
+            }
             Event::MouseDown(ev) if ev.button.is_left() => {
                 ctx.set_active(true);
                 self.last_mouse_pos = ev.pos;
                 if data.action.is_recording() {
                     let time = data.accurate_time();
-                    data.add_point_to_stroke(self.to_image_coords() * ev.pos, time);
+                    self.rejecting_stroke = false;
+                    if data.config.drawing.palm_rejection {
+                        self.pending_stroke =
+                            Some((PendingStroke::new(ev.pos, time), Instant::now()));
+                    } else {
+                        data.add_point_to_stroke(self.to_image_coords() * ev.pos, time);
+                    }
                     ctx.request_anim_frame();
                 }
             }
+            Event::MouseDown(ev)
+                if ev.button.is_middle() && !DrawingPane::viewport_locked(data) =>
+            {
+                ctx.set_active(true);
+                self.middle_panning = true;
+                self.last_mouse_pos = ev.pos;
+            }
+            Event::MouseUp(ev) if self.middle_panning => {
+                if ev.button.is_middle() {
+                    ctx.set_active(false);
+                    self.middle_panning = false;
+                }
+            }
             Event::MouseUp(ev) => {
                 ctx.set_active(false);
                 if ev.button.is_left() && data.action.is_recording() {
-                    data.finish_stroke(ev.mods.shift());
+                    let verdict = self
+                        .pending_stroke
+                        .as_mut()
+                        .map(|(pending, start)| pending.release(start.elapsed()));
+                    if verdict == Some(Verdict::Accept) {
+                        self.flush_pending_stroke(ctx, data);
+                    }
+                    let accepted = verdict.map_or(!self.rejecting_stroke, |v| v == Verdict::Accept);
+                    self.pending_stroke = None;
+                    self.rejecting_stroke = false;
+                    if accepted {
+                        data.finish_stroke(ev.mods.shift());
+                    }
                 }
             }
+            Event::Wheel(_) if DrawingPane::viewport_locked(data) => {
+                ctx.set_handled();
+            }
             Event::Wheel(ev) => {
-                let old_zoom = data.settings.zoom;
-                let zoom =
-                    (old_zoom * (-ev.wheel_delta.y / 500.0).exp()).clamp(1.0, crate::MAX_ZOOM);
-                let zoom_factor = zoom / old_zoom;
-
-                // Try to translate so that the mouse stays over whatever part of the drawing it's
-                // currently over.
-                self.offset += ev.pos.to_vec2() / old_zoom * (zoom_factor - 1.0);
-                data.settings.zoom = zoom;
-                self.recompute_paper_rect(ctx.size(), zoom);
+                if ev.mods.ctrl() || ev.mods.meta() {
+                    let zoom = data.settings.zoom * (-ev.wheel_delta.y / 500.0).exp();
+                    self.zoom_about(ctx.size(), data, ev.pos, zoom);
+                } else {
+                    self.pan(ctx.size(), -ev.wheel_delta, data.settings.zoom);
+                }
                 ctx.request_paint();
+                ctx.set_handled();
+            }
+            Event::Zoom(delta) => {
+                // Pinching takes two fingers, so whatever we were drawing wasn't a real stroke.
+                if self.pending_stroke.is_some() {
+                    self.reject_pending_stroke();
+                }
+                // Pinch-to-zoom doesn't come with a position, so zoom around the last known mouse
+                // position.
+                if !DrawingPane::viewport_locked(data) {
+                    let zoom = data.settings.zoom * (1.0 + delta);
+                    self.zoom_about(ctx.size(), data, self.last_mouse_pos, zoom);
+                    ctx.request_paint();
+                }
             }
             Event::WindowConnected => {
                 ctx.request_paint();
             }
+            Event::Command(c) if c.is(cmd::UNDERLAY_FRAME) => {
+                self.underlay_frame = c.get_unchecked(cmd::UNDERLAY_FRAME).clone();
+                ctx.request_paint();
+                ctx.set_handled();
+            }
+            Event::Command(c) if c.is(cmd::WEBCAM_FRAME) => {
+                self.webcam_frame = c.get_unchecked(cmd::WEBCAM_FRAME).clone();
+                ctx.request_paint();
+                ctx.set_handled();
+            }
             _ => {}
         }
     }
@@ -176,12 +459,29 @@ impl Widget<EditorState> for DrawingPane {
         if data.action.is_recording() && ctx.is_hot() {
             let cursor = self.cursor(data, ctx.window());
             ctx.set_cursor(cursor);
-        } else if old_data.action.is_recording() {
+        } else if data.settings.eyedropper && ctx.is_hot() {
+            ctx.set_cursor(&Cursor::Crosshair);
+        } else if old_data.action.is_recording() || old_data.settings.eyedropper {
             ctx.set_cursor(&Cursor::Arrow);
         }
 
-        if !old_data.scribl.draw.same(&data.scribl.draw) {
-            self.cursor = data.scribl.draw.create_cursor(data.time());
+        // Toggling the comparison mode or the preview needs a repaint for the label, even if the
+        // drawings are the same.
+        // Changing pages replaces the whole drawing.
+        let page_changed = !old_data.scribl.pages.same(&data.scribl.pages)
+            || !data.scribl.pages.same_page(old_data.time(), data.time());
+        // The highlight around the selected snippet comes and goes with the selection, and it's
+        // hidden when we aren't idle.
+        let highlight_changed = old_data.selected_snippet != data.selected_snippet
+            || old_data.action.is_idle() != data.action.is_idle()
+            || old_data.presenting != data.presenting;
+        if !old_data.visible_drawing().same(data.visible_drawing())
+            || old_data.comparing != data.comparing
+            || old_data.checkpoint_preview != data.checkpoint_preview
+            || page_changed
+            || highlight_changed
+        {
+            self.cursor = data.visible_drawing().create_cursor(data.time());
             ctx.request_paint();
         } else if old_data.time() != data.time() {
             let start_time = old_data.time().min(data.time());
@@ -191,8 +491,14 @@ impl Widget<EditorState> for DrawingPane {
             // they differ then we didn't get here.
             // TODO: consider invalidating everything if there are many bboxes.
             let transform = self.from_image_coords();
-            for bbox in self.cursor.bboxes(&data.scribl.draw) {
-                ctx.request_paint_rect(transform * bbox);
+            // The glow around the selected snippet sticks out past the strokes' bounding boxes.
+            let margin = if DrawingPane::highlighted_snippet(data).is_some() {
+                HIGHLIGHT_OUTER_WIDTH
+            } else {
+                0.0
+            };
+            for bbox in self.cursor.bboxes(data.visible_drawing()) {
+                ctx.request_paint_rect((transform * bbox).inflate(margin, margin));
             }
             if let Some(strokes) = &data.new_stroke_seq() {
                 for stroke in strokes.strokes() {
@@ -241,17 +547,46 @@ impl Widget<EditorState> for DrawingPane {
             self.recompute_paper_rect(ctx.size(), data.settings.zoom);
             ctx.request_paint();
         }
+
+        if !old_data.scribl.underlay.same(&data.scribl.underlay) {
+            self.reset_underlay(ctx.get_external_handle(), ctx.widget_id(), data);
+            ctx.request_paint();
+        } else if old_data.time() != data.time() {
+            if let Some(player) = &self.underlay_player {
+                player.seek(data.time());
+            }
+        }
+
+        let webcam_video_changed = match (&old_data.scribl.webcam, &data.scribl.webcam) {
+            (Some(old), Some(new)) => !old.video.same(&new.video),
+            (None, None) => false,
+            _ => true,
+        };
+        if webcam_video_changed {
+            self.reset_webcam(ctx.get_external_handle(), ctx.widget_id(), data);
+            ctx.request_paint();
+        } else if old_data.time() != data.time() {
+            if let Some(player) = &self.webcam_player {
+                player.seek(data.time());
+            }
+        }
+        // Moving or resizing the webcam video doesn't change its frames.
+        if !old_data.scribl.webcam.same(&data.scribl.webcam) {
+            ctx.request_paint();
+        }
     }
 
     fn lifecycle(
         &mut self,
-        _ctx: &mut LifeCycleCtx,
+        ctx: &mut LifeCycleCtx,
         event: &LifeCycle,
         data: &EditorState,
         _env: &Env,
     ) {
         if matches!(event, LifeCycle::WidgetAdded) {
-            self.cursor = data.scribl.draw.create_cursor(data.time());
+            self.cursor = data.visible_drawing().create_cursor(data.time());
+            self.reset_underlay(ctx.get_external_handle(), ctx.widget_id(), data);
+            self.reset_webcam(ctx.get_external_handle(), ctx.widget_id(), data);
         }
     }
 
@@ -300,13 +635,20 @@ impl Widget<EditorState> for DrawingPane {
                 }
             }
             ctx.fill(&self.paper_rect, &PAPER_COLOR);
+            if let Some(frame) = &self.underlay_frame {
+                frame.render(ctx.render_ctx, self.paper_rect);
+            }
 
             ctx.transform(self.from_image_coords().into());
-            for id in self.cursor.active_ids() {
-                data.scribl
-                    .draw
-                    .snippet(id)
-                    .render(ctx.render_ctx, data.time());
+            // The selected snippet is highlighted, but only when there's nothing going on,
+            // because the glow would be distracting while drawing or playing.
+            let highlighted = DrawingPane::highlighted_snippet(data)
+                .filter(|_| data.action.is_idle() && !data.presenting);
+            for (id, snip) in self.visible_snippets(data) {
+                if highlighted == Some(id) {
+                    self.render_highlight(ctx.render_ctx, snip, data.time());
+                }
+                snip.render(ctx.render_ctx, data.time());
             }
             if let Some(curve) = data.new_stroke_seq() {
                 curve.render(ctx.render_ctx, data.time());
@@ -314,6 +656,49 @@ impl Widget<EditorState> for DrawingPane {
             if let Some(snip) = data.new_stroke() {
                 snip.render(ctx.render_ctx, data.settings.cur_style(), data.time());
             }
+            if let (Some(webcam), Some(frame)) = (&data.scribl.webcam, &self.webcam_frame) {
+                webcam.render(ctx.render_ctx, frame);
+            }
         });
+
+        // When comparing or previewing, label the drawing so that it's obvious which version is
+        // showing.
+        let preview = data
+            .checkpoint_preview
+            .and_then(|idx| data.scribl.checkpoints.get(idx));
+        let label = if let Some(checkpoint) = preview {
+            Some(format!("Checkpoint \"{}\"", checkpoint.name))
+        } else if data.comparing && data.action.is_idle() {
+            data.undo
+                .undo_description()
+                .map(|desc| format!("Before \"{}\"", desc))
+        } else {
+            None
+        };
+        if let Some(label) = label {
+            let layout = ctx
+                .text()
+                .new_text_layout(label)
+                .font(FontFamily::SYSTEM_UI, VERSION_LABEL_FONT_SIZE)
+                .text_color(VERSION_LABEL_COLOR)
+                .build();
+            if let Ok(layout) = layout {
+                ctx.draw_text(&layout, self.paper_rect.origin() + Vec2::new(8.0, 8.0));
+            }
+        }
+
+        if data.settings.zoom != 1.0 {
+            let layout = ctx
+                .text()
+                .new_text_layout(format!("{:.0}%", data.settings.zoom * 100.0))
+                .font(FontFamily::SYSTEM_UI, ZOOM_LABEL_FONT_SIZE)
+                .text_color(ZOOM_LABEL_COLOR)
+                .build();
+            if let Ok(layout) = layout {
+                let x = size.width - layout.size().width - 8.0;
+                let y = size.height - layout.size().height - 8.0;
+                ctx.draw_text(&layout, Point::new(x, y));
+            }
+        }
     }
 }
@@ -1,47 +1,81 @@
 use crossbeam_channel::Sender;
-use druid::widget::{Flex, Scroll};
+use druid::widget::{Either, EnvScope, Flex, Label, Scroll, SizedBox, ViewSwitcher};
 use druid::{
-    theme, BoxConstraints, Command, Data, Env, Event, EventCtx, ExtEventSink, KbKey, KeyEvent,
-    LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, SingleUse, Size, TimerToken, UpdateCtx, Widget,
-    WidgetExt, WidgetId, WindowId,
+    theme, BoxConstraints, Code, Color, Command, Data, Env, Event, EventCtx, ExtEventSink, KbKey,
+    KeyEvent, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Selector, SingleUse, Size, TimerToken,
+    UpdateCtx, Widget, WidgetExt, WidgetId, WindowId, WindowState,
 };
-use std::path::PathBuf;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-use scribl_widget::{ModalHost, RadioGroup, Separator, SunkenContainer, ToggleButton, TooltipExt};
+use scribl_core::live::{LiveFrame, LiveOutput};
+use scribl_core::underlay::{self, Underlay, VideoRecorder, VideoSource};
+use scribl_core::{otio, svg_import, web_export};
+use scribl_curves::{Time, TimeDiff};
+use scribl_widget::{
+    Icon, ModalHost, RadioGroup, Separator, SunkenContainer, ToggleButton, TooltipExt,
+};
 
-use crate::audio::AudioHandle;
+use crate::audio::{vad, AudioHandle, MusicTrack, TalkSnippet, TalkSnippetId, TalkSnippets};
 use crate::autosave::AutosaveData;
-use crate::data::Settings;
+use crate::config::{Cache, Export, PenPreset};
+use crate::data::{DenoiseSetting, FinishedStatus, Settings, VadPreview, PUNCH_IN_POSTROLL};
+use crate::template::Template;
 use crate::widgets::{
-    alert, icons, make_status_bar, AudioIndicator, DrawingPane, Palette, Timeline,
+    alert, icons, make_page_list, make_status_bar, save_summary, AudioIndicator, DebugOverlay,
+    DrawingPane, Palette, PresentOverlay, Timeline,
 };
 use crate::{
-    cmd, CurrentAction, DenoiseSetting, EditorState, PenSize, RecordingSpeed, SaveFileData,
+    cmd, crash, remote, CurrentAction, DenoiseSetting, EditorState, PenSize, RecordingSpeed,
+    SaveFileData, ScriblState,
 };
 
-const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
 const ICON_PADDING: f64 = 6.0;
 const TOOLBAR_WIDTH: f64 = 52.0;
 const SECONDARY_BUTTON_PADDING: f64 = 4.0;
 
 pub struct Editor {
-    // Every AUTOSAVE_DURATION, we will attempt to save the current file.
+    // Every autosave interval (from the config), we will attempt to save the current file.
     autosave_timer_id: TimerToken,
     // We won't save the current file if it hasn't changed since the last autosave.
     last_autosave_data: Option<SaveFileData>,
     // We send the autosave data on this channel.
     autosave_tx: Option<Sender<AutosaveData>>,
+    // Keeps a copy of the current state, to be saved if we crash.
+    crash_snapshot: crash::Registration,
     // A handle to the audio thread. We initialize this on WidgetAdded, so it should rarely be
     // `None`.
     //
     // The audio state is derived from our EditorState, and our `update` method is where the actual
     // commands get sent to the audio thread.
     audio: Option<AudioHandle>,
+    // If they asked for it, we record the screen and the webcam while they're talking.
+    video_captures: Vec<VideoCapture>,
+    // If they asked for it, we send the canvas to a virtual camera or an RTMP server.
+    live_output: Option<LiveOutput>,
 
     inner: Box<dyn Widget<EditorState>>,
 }
 
+/// A video that they asked us to record while they talk.
+struct VideoCaptureRequest {
+    /// What kind of video this is, for naming the file.
+    kind: &'static str,
+    source: VideoSource,
+    fps: f64,
+    /// The command to send when the recording is finished.
+    finished: Selector<Result<Underlay, String>>,
+}
+
+/// A video that's being recorded while they talk.
+struct VideoCapture {
+    /// The animation time at which the recording started.
+    start: Time,
+    recorder: VideoRecorder,
+    /// The command to send when the recording is finished.
+    finished: Selector<Result<Underlay, String>>,
+}
+
 fn make_draw_button_group() -> impl Widget<EditorState> {
     let rec_button = ToggleButton::from_icon(
         &icons::VIDEO,
@@ -59,6 +93,28 @@ fn make_draw_button_group() -> impl Widget<EditorState> {
         |_, state, _| state.finish_action(),
     );
 
+    let pause_button = ToggleButton::from_icon(
+        &icons::PAUSE,
+        ICON_PADDING,
+        |state: &EditorState, _env: &Env| {
+            if state.action.is_recording_held() {
+                "Resume the clock (P)"
+            } else {
+                "Pause the clock, and keep drawing (P)"
+            }
+            .to_owned()
+        },
+        |state: &EditorState| state.action.is_recording_held(),
+        |_, state, _| state.toggle_recording_pause(),
+        |_, state, _| state.toggle_recording_pause(),
+    );
+    // The pause button only makes sense while recording.
+    let pause_button = Either::new(
+        |state: &EditorState, _env| state.action.is_recording(),
+        pause_button.padding(SECONDARY_BUTTON_PADDING),
+        SizedBox::empty(),
+    );
+
     let rec_speed_group = RadioGroup::icon_column(
         vec![
             (
@@ -109,6 +165,7 @@ fn make_draw_button_group() -> impl Widget<EditorState> {
 
     let draw_button_group = Flex::column()
         .with_child(rec_button)
+        .with_child(pause_button)
         .with_spacer(5.0)
         .with_child(rec_speed_group)
         .with_spacer(5.0)
@@ -146,11 +203,78 @@ fn make_pen_group() -> impl Widget<EditorState> {
     .background(theme::BACKGROUND_LIGHT)
     .rounded(theme::BUTTON_BORDER_RADIUS);
 
+    // The presets come from the config file, so they don't change. But we need the data to find
+    // out what they are.
+    let presets = ViewSwitcher::new(
+        |data: &EditorState, _env| data.config.pen_presets.len(),
+        |_, data: &EditorState, _env| make_pen_presets(&data.config.pen_presets),
+    );
+
     Flex::column()
-        .with_child(palette)
+        .with_child(presets)
+        .with_child(palette.lens(EditorState::settings))
         .with_default_spacer()
-        .with_child(pen_size_group.lens(Settings::pen_size))
-        .lens(EditorState::settings)
+        .with_child(
+            pen_size_group
+                .lens(Settings::pen_size)
+                .lens(EditorState::settings),
+        )
+}
+
+/// A column of buttons, one for each pen preset. Each button is labelled with its number, and has
+/// the preset's color (if it has one).
+fn make_pen_presets(presets: &[PenPreset]) -> Box<dyn Widget<EditorState>> {
+    if presets.is_empty() {
+        return SizedBox::empty().boxed();
+    }
+
+    let mut col = Flex::column();
+    for (idx, preset) in presets.iter().enumerate() {
+        let tooltip = if idx < 9 {
+            format!("{} (Alt+{})", preset.name, idx + 1)
+        } else {
+            preset.name.clone()
+        };
+        let color = preset
+            .color
+            .as_ref()
+            .and_then(|c| Color::from_hex_str(c).ok())
+            .unwrap_or(Color::TRANSPARENT);
+        let label = Label::new(format!("{}", idx + 1))
+            .center()
+            .fix_height(24.0)
+            .background(color)
+            .rounded(theme::BUTTON_BORDER_RADIUS)
+            .padding(SECONDARY_BUTTON_PADDING)
+            .tooltip(tooltip);
+        let button = ToggleButton::from_widget(
+            label,
+            move |data: &EditorState| {
+                data.config
+                    .pen_presets
+                    .get(idx)
+                    .map_or(false, |p| data.settings.matches_pen_preset(p))
+            },
+            move |_, data, _| data.select_pen_preset(idx),
+            |_, _, _| {},
+        );
+        col.add_child(button);
+        col.add_spacer(5.0);
+    }
+    col.padding(5.0)
+        .background(theme::BACKGROUND_LIGHT)
+        .rounded(theme::BUTTON_BORDER_RADIUS)
+        .padding((0.0, 0.0, 0.0, 10.0))
+        .boxed()
+}
+
+/// The timeline's tooltip: the details of the snippet that the mouse is over. Tooltips with no
+/// text don't get shown, so nothing happens when the mouse isn't over a snippet.
+fn hovered_snippet_details(data: &EditorState, _env: &Env) -> String {
+    data.hovered_snippet
+        .filter(|&id| data.has_snippet(id))
+        .map(|id| data.snippet_details(id))
+        .unwrap_or_default()
 }
 
 fn make_audio_button_group() -> impl Widget<EditorState> {
@@ -196,15 +320,62 @@ fn make_audio_button_group() -> impl Widget<EditorState> {
     .lens(Settings::denoise_setting)
     .lens(EditorState::settings);
 
+    let monitor_button = ToggleButton::from_icon(
+        &icons::HEADPHONES,
+        ICON_PADDING,
+        |state: &bool, _env: &Env| {
+            if *state {
+                "Stop listening to the microphone while recording"
+            } else {
+                "Listen to the microphone while recording (there may be a delay)"
+            }
+            .to_owned()
+        },
+        |&b: &bool| b,
+        |_, data, _| *data = true,
+        |_, data, _| *data = false,
+    )
+    .padding(SECONDARY_BUTTON_PADDING)
+    .lens(Settings::monitor_input)
+    .lens(EditorState::settings);
+
     Flex::column()
         .with_child(rec_audio_button)
         .with_spacer(5.0)
         .with_child(noise_group)
+        .with_spacer(5.0)
+        .with_child(monitor_button)
         .padding(5.0)
         .background(theme::BACKGROUND_LIGHT)
         .rounded(theme::BUTTON_BORDER_RADIUS)
 }
 
+/// If the key code is one of the number keys, returns its number.
+fn digit_code(code: Code) -> Option<usize> {
+    let digits = [
+        Code::Digit0,
+        Code::Digit1,
+        Code::Digit2,
+        Code::Digit3,
+        Code::Digit4,
+        Code::Digit5,
+        Code::Digit6,
+        Code::Digit7,
+        Code::Digit8,
+        Code::Digit9,
+    ];
+    digits.iter().position(|&c| c == code)
+}
+
+/// Hides a widget while we're in presentation mode.
+fn hide_when_presenting(widget: impl Widget<EditorState> + 'static) -> impl Widget<EditorState> {
+    Either::new(
+        |data: &EditorState, _env| data.presenting,
+        SizedBox::empty(),
+        widget,
+    )
+}
+
 impl Editor {
     pub fn new() -> Editor {
         let drawing = DrawingPane::default();
@@ -227,8 +398,25 @@ impl Editor {
         let draw_button_group = make_draw_button_group();
         let audio_button_group = make_audio_button_group();
 
+        let step_button = |icon: &Icon, tooltip: &'static str, frames: i64| {
+            ToggleButton::from_icon(
+                icon,
+                ICON_PADDING,
+                tooltip,
+                |_: &EditorState| false,
+                move |ctx, _, _| {
+                    ctx.submit_command(cmd::NUDGE_TIME.with(cmd::Nudge::Frames(frames)))
+                },
+                |_, _, _| {},
+            )
+        };
+
         let watch_button_group = Flex::column()
+            .with_child(step_button(&icons::STEP_BACKWARD, "Previous frame (,)", -1))
+            .with_default_spacer()
             .with_child(play_button)
+            .with_default_spacer()
+            .with_child(step_button(&icons::STEP_FORWARD, "Next frame (.)", 1))
             .padding(5.0)
             .background(theme::BACKGROUND_LIGHT)
             .rounded(theme::BUTTON_BORDER_RADIUS);
@@ -243,8 +431,16 @@ impl Editor {
         let pen_col = Scroll::new(make_pen_group())
             .vertical()
             .fix_width(TOOLBAR_WIDTH);
+        // The page list only shows up once there's more than one page.
+        let page_list = Either::new(
+            |data: &EditorState, _env| data.scribl.pages.is_empty(),
+            SizedBox::empty(),
+            make_page_list(),
+        );
         let timeline_id = WidgetId::next();
-        let timeline = Timeline::new().with_id(timeline_id);
+        let timeline = Timeline::new()
+            .with_id(timeline_id)
+            .tooltip(hovered_snippet_details);
         /*
         TODO: Issues with split:
          - can't get timeline to use up the vertical space it has available
@@ -256,23 +452,32 @@ impl Editor {
             .with_flex_child(
                 SunkenContainer::new(
                     Flex::row()
-                        .with_child(button_col)
+                        .with_child(hide_when_presenting(button_col))
                         .with_flex_child(drawing, 1.0)
-                        .with_child(pen_col),
+                        .with_child(hide_when_presenting(page_list))
+                        .with_child(hide_when_presenting(pen_col)),
                 ),
                 1.0,
             )
-            .with_child(Separator::new().height(10.0).color(theme::BACKGROUND_LIGHT))
-            .with_child(timeline)
-            .with_child(make_status_bar())
+            .with_child(hide_when_presenting(
+                Separator::new().height(10.0).color(theme::BACKGROUND_LIGHT),
+            ))
+            .with_child(hide_when_presenting(timeline))
+            .with_child(hide_when_presenting(make_status_bar()))
             .background(theme::BACKGROUND_DARK);
 
         Editor {
-            inner: Box::new(ModalHost::new(column)),
+            inner: Box::new(EnvScope::new(
+                |env, data: &EditorState| data.settings.ui_palette.configure_env(env),
+                ModalHost::new(DebugOverlay::new(PresentOverlay::new(column))),
+            )),
             autosave_timer_id: TimerToken::INVALID,
             audio: None,
+            video_captures: Vec::new(),
+            live_output: None,
             last_autosave_data: None,
             autosave_tx: None,
+            crash_snapshot: crash::Registration::new(),
         }
     }
 }
@@ -285,6 +490,10 @@ impl Editor {
         data: &mut EditorState,
         _env: &Env,
     ) {
+        if data.presenting && self.handle_present_key(ctx, ev, data) {
+            return;
+        }
+
         // If they push another non-shift key while holding down the arrow, cancel the scanning.
         if let CurrentAction::Scanning(speed) = data.action {
             let direction = if speed > 0.0 {
@@ -302,6 +511,21 @@ impl Editor {
         }
 
         match &ev.key {
+            // With alt held down, the arrows move the selected snippet instead of the time.
+            KbKey::ArrowRight | KbKey::ArrowLeft
+                if ev.mods.alt() && data.selected_snippet.is_some() =>
+            {
+                let dir = if ev.key == KbKey::ArrowRight { 1 } else { -1 };
+                let nudge = if ev.mods.shift() {
+                    cmd::Nudge::Seconds(dir)
+                } else {
+                    cmd::Nudge::Frames(dir)
+                };
+                ctx.submit_command(cmd::NUDGE_SNIPPET.with(nudge));
+                ctx.set_handled();
+            }
+            // Cmd and the arrows select the previous or next snippet (see the edit menu).
+            KbKey::ArrowRight | KbKey::ArrowLeft if ev.mods.ctrl() || ev.mods.meta() => {}
             KbKey::ArrowRight | KbKey::ArrowLeft => {
                 let speed = if ev.mods.shift() { 3.0 } else { 1.5 };
                 let dir = if ev.key == KbKey::ArrowRight {
@@ -341,6 +565,34 @@ impl Editor {
         }
     }
 
+    /// In presentation mode, the keyboard controls playback and moves between chapters. Returns
+    /// true if we handled the key.
+    fn handle_present_key(
+        &mut self,
+        ctx: &mut EventCtx,
+        ev: &KeyEvent,
+        data: &mut EditorState,
+    ) -> bool {
+        match &ev.key {
+            KbKey::Escape => data.presenting = false,
+            KbKey::ArrowRight => data.jump_to_marker(true),
+            KbKey::ArrowLeft => data.jump_to_marker(false),
+            KbKey::Home => data.warp_time_to(Time::ZERO),
+            KbKey::Character(c) if c == "]" => data.jump_to_marker(true),
+            KbKey::Character(c) if c == "[" => data.jump_to_marker(false),
+            KbKey::Character(c) if c == " " => {
+                if data.action.is_playing() {
+                    data.finish_action();
+                } else {
+                    data.play();
+                }
+            }
+            _ => return false,
+        }
+        ctx.set_handled();
+        true
+    }
+
     fn handle_key_up(
         &mut self,
         ctx: &mut EventCtx,
@@ -362,6 +614,14 @@ impl Editor {
             }
             KbKey::ArrowUp => ctx.submit_command(cmd::SELECT_SNIPPET_ABOVE),
             KbKey::ArrowDown => ctx.submit_command(cmd::SELECT_SNIPPET_BELOW),
+            KbKey::Character(_) if matches!(data.action, CurrentAction::EditingText) => {}
+            KbKey::Character(_) if ev.mods.alt() && !ev.mods.ctrl() => {
+                // Alt and a number selects a pen preset. We look at the key code instead of the
+                // character, because on some keyboards Alt changes the character.
+                if let Some(idx) = digit_code(ev.code).filter(|&d| d > 0) {
+                    data.select_pen_preset(idx - 1);
+                }
+            }
             KbKey::Character(s) if !ev.mods.shift() && !ev.mods.ctrl() && !ev.mods.alt() => {
                 match s.chars().next().unwrap() {
                     c @ '0'..='9' => {
@@ -382,19 +642,81 @@ impl Editor {
         }
     }
 
-    fn export(&self, ctx: &mut EventCtx, data: &mut EditorState, export: cmd::ExportCmd) {
-        if data.status.in_progress.encoding.is_some() {
-            log::warn!("already encoding, not doing another one");
-        } else {
+    /// Adds an export to the queue, and starts it if nothing else is being exported.
+    fn export(
+        &self,
+        ctx: &mut EventCtx,
+        data: &mut EditorState,
+        path: &Path,
+        range: Option<(Time, Time)>,
+    ) {
+        let mut path = path.to_owned();
+        if path.extension().is_none() {
+            path.set_extension("mp4");
+        }
+        let export = cmd::ExportCmd {
+            scribl: data.scribl.clone(),
+            filename: path,
+            config: data.export_config(),
+            range,
+        };
+        let preset = data.settings.export_preset.clone();
+        data.status.exports.push(export, preset);
+        self.start_next_export(ctx, data);
+    }
+
+    /// Carries out a request from the remote-control server.
+    fn handle_remote(
+        &self,
+        ctx: &mut EventCtx,
+        method: &remote::Method,
+        data: &mut EditorState,
+    ) -> Result<serde_json::Value, String> {
+        use remote::Method;
+
+        let idle = data.action.is_idle();
+        let stoppable = matches!(
+            data.action,
+            CurrentAction::Playing(_)
+                | CurrentAction::Recording(_)
+                | CurrentAction::RecordingAudio(_)
+                | CurrentAction::PunchingIn(_)
+        );
+        match method {
+            Method::Play if data.action.is_playing() => {}
+            Method::Play | Method::Draw | Method::Talk if !idle => {
+                return Err("scribl is busy".to_owned());
+            }
+            Method::Draw | Method::Talk if data.presenting => {
+                return Err("can't record in presentation mode".to_owned());
+            }
+            Method::Play => data.play(),
+            Method::Draw => data.draw(),
+            Method::Talk => data.talk(),
+            Method::Pause if data.action.is_playing() => data.finish_action(),
+            Method::Stop if stoppable => data.finish_action(),
+            Method::Export(path) => self.export(ctx, data, path, None),
+            Method::Pause | Method::Stop | Method::Status => {}
+        }
+        Ok(remote::status(data))
+    }
+
+    /// If no export is running, starts the next one in the queue.
+    fn start_next_export(&self, ctx: &mut EventCtx, data: &mut EditorState) {
+        if let Some(job) = data.status.exports.start_next() {
             // This is a little wasteful, but it's probably fine. We spin up a thread to
             // translate between the Receiver that encode_blocking sends to, and the
             // ExtEventSink that sends commands to us.
-            let export = export.clone();
+            let export = (*job.cmd).clone();
             let (tx, rx) = crossbeam_channel::unbounded();
             let window_id = ctx.window_id();
             let ext_cmd = ctx.get_external_handle();
             std::thread::spawn(move || {
-                while let Ok(msg) = rx.recv() {
+                while let Ok(status) = rx.recv() {
+                    let msg = cmd::EncodingStatusCmd {
+                        job: job.id,
+                        status,
+                    };
                     let _ = ext_cmd.submit_command(cmd::ENCODING_STATUS, Box::new(msg), window_id);
                 }
             });
@@ -402,19 +724,84 @@ impl Editor {
         }
     }
 
+    /// Starts loading a file in the background. If `autosave` is true, the file is an autosave
+    /// and so we won't save back to it.
+    fn load(&self, ctx: &mut EventCtx, data: &mut EditorState, path: &Path, autosave: bool) {
+        if data.status.in_progress.loading.is_some() {
+            log::error!("not loading, already loading");
+        } else {
+            data.status.in_progress.loading = Some(path.to_owned());
+            data.status.in_progress.loading_progress = 0.0;
+            spawn_async_load(
+                ctx.get_external_handle(),
+                path.to_owned(),
+                autosave,
+                ctx.window_id(),
+            );
+            data.set_loading();
+        }
+    }
+
     fn handle_command(
         &mut self,
         ctx: &mut EventCtx,
         cmd: &Command,
         data: &mut EditorState,
-        _env: &Env,
+        env: &Env,
     ) -> bool {
         let ret = if let Some(snip_cmd) = cmd.get(cmd::ADD_TALK_SNIPPET) {
-            let snip = snip_cmd.snip.clone();
             let prev_state = data.undo_state();
-            data.selected_snippet = Some(data.scribl.add_talk_snippet(snip).into());
+            for snip in &snip_cmd.snips {
+                data.selected_snippet = Some(data.scribl.add_talk_snippet(snip.clone()).into());
+            }
             data.push_undo_state(prev_state.with_time(snip_cmd.orig_start), "add audio");
             true
+        } else if let Some(punch_cmd) = cmd.get(cmd::PUNCH_IN_RECORDED) {
+            data.finish_punch_in(&punch_cmd.punch, &punch_cmd.take);
+            true
+        } else if cmd.is(cmd::CALIBRATE_LATENCY) {
+            if !data.action.is_idle() {
+                log::warn!("not calibrating: state is {:?}", data.action);
+            } else if let Some(audio) = &self.audio {
+                audio.calibrate_latency();
+                ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(
+                    alert::make_calibrating_latency_alert(),
+                ))));
+            }
+            true
+        } else if let Some(result) = cmd.get(cmd::LATENCY_CALIBRATED) {
+            ctx.submit_command(ModalHost::DISMISS_MODAL);
+            ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(
+                alert::make_latency_result_alert(result.clone()),
+            ))));
+            true
+        } else if let Some(msg) = cmd.get(cmd::SHOW_MISSING_PLUGINS) {
+            ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(
+                alert::make_missing_plugins_alert(msg.clone()),
+            ))));
+            true
+        } else if let Some(paths) = cmd.get(cmd::SHOW_PARTIAL_EXPORTS) {
+            ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(
+                alert::make_partial_exports_alert(paths.clone()),
+            ))));
+            true
+        } else if let Some(paths) = cmd.get(cmd::SHOW_CRASH_RECOVERY) {
+            ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(
+                alert::make_crash_recovery_alert(paths.clone()),
+            ))));
+            true
+        } else if let Some(errors) = cmd.get(cmd::AUDIO_ERRORS) {
+            // In case there's already a dialog open, leave a message in the status bar too.
+            if let Some(e) = errors.first() {
+                data.status.last_finished = Some(FinishedStatus::Error(format!("Audio {}", e)));
+            }
+            ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(
+                alert::make_audio_errors_alert(errors.clone()),
+            ))));
+            true
+        } else if let Some(msg) = cmd.get(cmd::AUDIO_DEVICES_CHANGED) {
+            data.status.last_finished = Some(FinishedStatus::Notice(msg.clone()));
+            true
         } else if let Some(time) = cmd.get(cmd::WARP_TIME_TO) {
             if data.action.is_idle() {
                 data.warp_time_to(*time);
@@ -422,17 +809,507 @@ impl Editor {
                 log::warn!("not warping: state is {:?}", data.action)
             }
             true
+        } else if cmd.is(cmd::SHOW_GO_TO_TIME) {
+            if data.action.is_idle() {
+                data.action = CurrentAction::EditingText;
+                ctx.submit_command(
+                    ModalHost::SHOW_MODAL
+                        .with(SingleUse::new(Box::new(alert::make_go_to_time_alert()))),
+                );
+            }
+            true
+        } else if cmd.is(cmd::SHOW_SCRIPT_CONSOLE) {
+            if data.action.is_idle() {
+                data.action = CurrentAction::EditingText;
+                let alert = alert::make_script_console_alert();
+                ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(alert))));
+            }
+            true
+        } else if cmd.is(cmd::RUN_SCRIPT) {
+            for path in data.run_script() {
+                self.export(ctx, data, &path, None);
+            }
+            true
+        } else if cmd.is(cmd::ADD_MARKER) || cmd.is(cmd::RENAME_MARKER) {
+            let time = data.time();
+            let existing = data.scribl.markers.get(time).is_some();
+            if data.action.is_idle() && (existing || cmd.is(cmd::ADD_MARKER)) {
+                // The undo state gets pushed when the dialog closes, so that we don't get a new
+                // undo state for every character they type.
+                let prev_state = data.undo_state();
+                let desc = if existing {
+                    "rename marker"
+                } else {
+                    let name = data.scribl.markers.fresh_name();
+                    data.scribl.markers = data.scribl.markers.with_marker(time, &name);
+                    "add marker"
+                };
+                data.action = CurrentAction::EditingText;
+                let alert = alert::make_marker_name_alert(prev_state, time, desc);
+                ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(alert))));
+            }
+            true
+        } else if let Some(&id) = cmd.get(cmd::DELETE_SNIPPET) {
+            if data.action.is_idle() && data.has_snippet(id) {
+                data.delete_snippet(id);
+            }
+            true
+        } else if let Some(&id) = cmd.get(cmd::DUPLICATE_SNIPPET) {
+            if data.action.is_idle() && data.has_snippet(id) {
+                data.duplicate_snippet(id);
+            }
+            true
+        } else if let Some(&id) = cmd.get(cmd::SPLIT_SNIPPET) {
+            if data.action.is_idle() && data.has_snippet(id) {
+                data.split_snippet(id);
+            }
+            true
+        } else if let Some(&id) = cmd.get(cmd::TOGGLE_SNIPPET_MUTED) {
+            if let SnippetId::Talk(talk_id) = id {
+                if data.action.is_idle() && data.has_snippet(id) {
+                    data.toggle_snippet_muted(talk_id);
+                }
+            }
+            true
+        } else if let Some(&id) = cmd.get(cmd::TOGGLE_SNIPPET_RAW) {
+            if let SnippetId::Talk(talk_id) = id {
+                if data.action.is_idle() && data.has_snippet(id) {
+                    data.toggle_snippet_raw(talk_id);
+                }
+            }
+            true
+        } else if let Some(&id) = cmd.get(cmd::TOGGLE_SNIPPET_REVERSED) {
+            if let SnippetId::Draw(draw_id) = id {
+                if data.action.is_idle() && data.has_snippet(id) {
+                    data.toggle_snippet_reversed(draw_id);
+                }
+            }
+            true
+        } else if let Some(&(id, label)) = cmd.get(cmd::SET_SNIPPET_LABEL) {
+            if data.action.is_idle() && data.has_snippet(id) && data.snippet_label(id) != label {
+                data.set_snippet_label(id, label);
+            }
+            true
+        } else if let Some(&id) = cmd.get(cmd::LINK_SNIPPET) {
+            if data.action.is_idle() && data.has_snippet(id) {
+                data.link_overlapping_snippets(id);
+            }
+            true
+        } else if let Some(&id) = cmd.get(cmd::UNLINK_SNIPPET) {
+            if data.action.is_idle() && data.has_snippet(id) && data.snippet_group(id).is_some() {
+                data.unlink_snippet(id);
+            }
+            true
+        } else if let Some(&id) = cmd.get(cmd::RENAME_SNIPPET) {
+            if data.action.is_idle() && data.has_snippet(id) {
+                // As with markers, the undo state gets pushed when the dialog closes.
+                let prev_state = data.undo_state();
+                data.action = CurrentAction::EditingText;
+                let alert = alert::make_snippet_name_alert(prev_state, id);
+                ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(alert))));
+            }
+            true
+        } else if let Some(hints) = cmd.get(cmd::SET_ROW_HINTS) {
+            data.set_row_hints(hints.clone());
+            true
+        } else if let Some(&id) = cmd.get(cmd::SHOW_SNIPPET_PROPERTIES) {
+            // The properties can be edited, so this works like the rename dialog.
+            if data.action.is_idle() && data.has_snippet(id) {
+                let prev_state = data.undo_state();
+                data.action = CurrentAction::EditingText;
+                let alert: Box<dyn Widget<EditorState>> = match id {
+                    SnippetId::Draw(id) => {
+                        Box::new(alert::make_drawing_properties_alert(prev_state, id))
+                    }
+                    SnippetId::Talk(id) => {
+                        Box::new(alert::make_audio_properties_alert(prev_state, id))
+                    }
+                };
+                ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(alert)));
+            }
+            true
+        } else if let Some(&id) = cmd.get(cmd::RETIME_SNIPPET) {
+            if let SnippetId::Draw(draw_id) = id {
+                if data.action.is_idle() && data.has_snippet(id) {
+                    let prev_state = data.undo_state();
+                    data.action = CurrentAction::EditingText;
+                    let alert = alert::make_retime_alert(prev_state, draw_id);
+                    ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(alert))));
+                }
+            }
+            true
+        } else if cmd.is(cmd::SHOW_CHECKPOINTS) {
+            if data.action.is_idle() {
+                data.action = CurrentAction::EditingText;
+                ctx.submit_command(
+                    ModalHost::SHOW_MODAL
+                        .with(SingleUse::new(Box::new(alert::make_checkpoints_alert()))),
+                );
+            }
+            true
+        } else if let Some(idx) = cmd.get(cmd::PREVIEW_CHECKPOINT) {
+            data.checkpoint_preview = *idx;
+            true
+        } else if let Some(idx) = cmd.get(cmd::RESTORE_CHECKPOINT) {
+            data.restore_checkpoint(*idx);
+            true
+        } else if let Some(idx) = cmd.get(cmd::DELETE_CHECKPOINT) {
+            data.delete_checkpoint(*idx);
+            true
+        } else if let Some(nudge) = cmd.get(cmd::NUDGE_TIME) {
+            if data.action.is_idle() {
+                match *nudge {
+                    cmd::Nudge::Frames(n) => {
+                        // Go to the beginning of the frame, so that repeated nudging doesn't
+                        // accumulate rounding errors.
+                        let frame = (data.frame() as i64 + n).max(0);
+                        data.warp_time_to_frame(frame as u32);
+                    }
+                    cmd::Nudge::Seconds(n) => {
+                        data.warp_time_to(data.time() + TimeDiff::from_micros(n * 1_000_000));
+                    }
+                }
+            }
+            true
+        } else if let Some(nudge) = cmd.get(cmd::NUDGE_SNIPPET) {
+            if let Some(id) = data.selected_snippet {
+                if data.action.is_idle() && data.has_snippet(id) {
+                    let by = match *nudge {
+                        cmd::Nudge::Frames(n) => {
+                            let fps = data.fps();
+                            TimeDiff::from_micros((n as f64 * 1e6 / fps).round() as i64)
+                        }
+                        cmd::Nudge::Seconds(n) => TimeDiff::from_micros(n * 1_000_000),
+                    };
+                    data.nudge_snippet(id, by);
+                }
+            }
+            true
         } else if let Some(info) = cmd.get(cmd::EXPORT) {
+            self.export(ctx, data, info.path(), None);
+            true
+        } else if let Some(info) = cmd.get(cmd::EXPORT_RANGE) {
+            let range = data.marked_range();
+            self.export(ctx, data, info.path(), range);
+            true
+        } else if let Some(id) = cmd.get(cmd::CANCEL_EXPORT) {
+            data.status.exports.cancel(*id);
+            true
+        } else if cmd.is(cmd::SHOW_EXPORT_QUEUE) {
+            ctx.submit_command(
+                ModalHost::SHOW_MODAL
+                    .with(SingleUse::new(Box::new(alert::make_export_queue_alert()))),
+            );
+            true
+        } else if let Some(info) = cmd.get(cmd::EXPORT_WEB) {
+            let mut path = info.path().to_owned();
+            if path.extension().is_none() {
+                path.set_extension("svg");
+            }
+            spawn_async_web_export(
+                ctx.get_external_handle(),
+                data.scribl.clone(),
+                path,
+                data.export_config(),
+                ctx.window_id(),
+            );
+            true
+        } else if let Some(info) = cmd.get(cmd::EXPORT_TIMELINE_IMAGE) {
             let mut path = info.path().to_owned();
             if path.extension().is_none() {
-                path.set_extension("mp4");
+                path.set_extension("png");
             }
-            let export = cmd::ExportCmd {
-                scribl: data.scribl.clone(),
-                filename: path,
-                config: data.config.export.clone(),
+            data.status.last_finished = Some(match save_summary(data, env, &path) {
+                Ok(()) => FinishedStatus::Saved {
+                    path,
+                    time: Instant::now(),
+                },
+                Err(e) => FinishedStatus::Error(format!("Failed to export timeline: {}", e)),
+            });
+            true
+        } else if let Some(info) = cmd.get(cmd::EXPORT_OTIO) {
+            let mut path = info.path().to_owned();
+            if path.extension().is_none() {
+                path.set_extension("otio");
+            }
+            let fps = data.fps();
+            data.status.last_finished = Some(match otio::export(&data.scribl, &path, fps) {
+                Ok(()) => FinishedStatus::Saved {
+                    path,
+                    time: Instant::now(),
+                },
+                Err(e) => FinishedStatus::Error(format!("Failed to export timeline: {}", e)),
+            });
+            true
+        } else if cmd.is(cmd::CLEAR_CACHE) {
+            spawn_cache_clear(
+                ctx.get_external_handle(),
+                data.config.cache.clone(),
+                ctx.window_id(),
+            );
+            true
+        } else if let Some(result) = cmd.get(cmd::FINISHED_CLEAR_CACHE) {
+            data.status.last_finished = Some(match result {
+                Ok(freed) => FinishedStatus::Notice(format!(
+                    "Cleared {} of cached files",
+                    crate::cache::describe_size(*freed)
+                )),
+                Err(e) => FinishedStatus::Error(format!("Failed to clear the cache: {}", e)),
+            });
+            true
+        } else if cmd.is(cmd::FIND_DUPLICATE_AUDIO) {
+            spawn_duplicate_search(
+                ctx.get_external_handle(),
+                data.scribl.talk.clone(),
+                ctx.window_id(),
+            );
+            data.status.last_finished = Some(FinishedStatus::Notice(
+                "Looking for duplicate audio...".to_owned(),
+            ));
+            true
+        } else if let Some(result) = cmd.get(cmd::FINISHED_FIND_DUPLICATE_AUDIO) {
+            if result.duplicates.is_empty() {
+                data.status.last_finished = Some(FinishedStatus::Notice(
+                    "No duplicate audio found".to_owned(),
+                ));
+            } else if !result.talk.same(&data.scribl.talk) {
+                data.status.last_finished = Some(FinishedStatus::Error(
+                    "The audio changed while it was being checked for duplicates".to_owned(),
+                ));
+            } else {
+                data.status.last_finished = Some(FinishedStatus::Notice(format!(
+                    "Found {} duplicate audio snippet(s)",
+                    result.duplicates.len()
+                )));
+                ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(
+                    alert::make_duplicate_audio_alert(result.duplicates.clone()),
+                ))));
+            }
+            true
+        } else if let Some(&id) = cmd.get(cmd::DETECT_SPEECH) {
+            if data.scribl.talk.has_snippet(id) {
+                spawn_speech_detection(
+                    ctx.get_external_handle(),
+                    data.scribl.talk.clone(),
+                    id,
+                    ctx.window_id(),
+                );
+                data.status.last_finished =
+                    Some(FinishedStatus::Notice("Detecting speech...".to_owned()));
+            }
+            true
+        } else if let Some(result) = cmd.get(cmd::FINISHED_DETECT_SPEECH) {
+            if !result.talk.same(&data.scribl.talk) {
+                data.status.last_finished = Some(FinishedStatus::Error(
+                    "The audio changed while detecting speech".to_owned(),
+                ));
+            } else {
+                data.status.last_finished = Some(FinishedStatus::Notice(
+                    "Finished detecting speech".to_owned(),
+                ));
+                data.finish_action();
+                data.vad_preview = Some(VadPreview::new(
+                    result.talk.clone(),
+                    result.id,
+                    result.vad.clone(),
+                    data.config.audio_input.vad_threshold as f64,
+                ));
+                ctx.submit_command(
+                    ModalHost::SHOW_MODAL
+                        .with(SingleUse::new(Box::new(alert::make_vad_preview_alert()))),
+                );
+            }
+            true
+        } else if let Some(&id) = cmd.get(cmd::PROCESS_AUDIO) {
+            let vad_threshold = match data.settings.denoise_setting {
+                DenoiseSetting::DenoiseOff => return true,
+                DenoiseSetting::DenoiseOn => None,
+                DenoiseSetting::Vad => Some(data.config.audio_input.vad_threshold),
             };
-            self.export(ctx, data, export);
+            if data.scribl.talk.has_snippet(id)
+                && data.status.in_progress.processing_audio.is_none()
+            {
+                spawn_audio_processing(
+                    ctx.get_external_handle(),
+                    data.scribl.talk.clone(),
+                    id,
+                    vad_threshold,
+                    ctx.window_id(),
+                );
+                data.status.in_progress.processing_audio = Some(0.0);
+            }
+            true
+        } else if let Some(fraction) = cmd.get(cmd::PROCESS_AUDIO_PROGRESS) {
+            if data.status.in_progress.processing_audio.is_some() {
+                data.status.in_progress.processing_audio = Some(*fraction);
+            }
+            true
+        } else if let Some(result) = cmd.get(cmd::FINISHED_PROCESS_AUDIO) {
+            data.status.in_progress.processing_audio = None;
+            if !result.talk.same(&data.scribl.talk) {
+                data.status.last_finished = Some(FinishedStatus::Error(
+                    "The audio changed while it was being processed".to_owned(),
+                ));
+            } else {
+                data.set_processed_audio(result.id, result.buf.clone());
+                data.status.last_finished = Some(FinishedStatus::Notice(
+                    "Finished processing audio".to_owned(),
+                ));
+            }
+            true
+        } else if let Some(target) = cmd.get(cmd::MEASURE_LOUDNESS) {
+            spawn_loudness_measurement(
+                ctx.get_external_handle(),
+                data.scribl.clone(),
+                *target,
+                ctx.window_id(),
+            );
+            data.status.last_finished =
+                Some(FinishedStatus::Notice("Measuring loudness...".to_owned()));
+            true
+        } else if let Some(m) = cmd.get(cmd::FINISHED_LOUDNESS_MEASUREMENT) {
+            data.status.last_finished = Some(match (&m.loudness, m.target) {
+                (Err(e), _) => FinishedStatus::Error(format!("Failed to measure loudness: {}", e)),
+                (Ok(loudness), _) if !loudness.is_finite() => {
+                    FinishedStatus::Notice("There is no audio to measure".to_owned())
+                }
+                (Ok(loudness), None) => {
+                    FinishedStatus::Notice(format!("Integrated loudness: {:.1} LUFS", loudness))
+                }
+                (Ok(_), Some(_)) if !m.talk.same(&data.scribl.talk) => FinishedStatus::Error(
+                    "The audio changed while it was being measured, so it wasn't normalized"
+                        .to_owned(),
+                ),
+                (Ok(loudness), Some(target)) => {
+                    data.normalize_loudness(*loudness, target);
+                    FinishedStatus::Notice(format!(
+                        "Normalized loudness from {:.1} LUFS to {:.1} LUFS",
+                        loudness, target
+                    ))
+                }
+            });
+            true
+        } else if let Some(result) = cmd.get(cmd::FINISHED_WEB_EXPORT) {
+            data.status.last_finished = Some(match result {
+                Ok(path) => FinishedStatus::Encoded {
+                    path: path.clone(),
+                    time: Instant::now(),
+                    summary: None,
+                },
+                Err(e) => FinishedStatus::Error(format!("Failed to export: {}", e)),
+            });
+            true
+        } else if let Some(target) = cmd.get(cmd::START_LIVE_OUTPUT) {
+            let ext_cmd = ctx.get_external_handle();
+            let id = ctx.window_id();
+            let on_error = move |e: String| {
+                let _ = ext_cmd.submit_command(cmd::LIVE_OUTPUT_FAILED, Box::new(e), id);
+            };
+            match LiveOutput::start(target, &data.config.live.live_config(), on_error) {
+                Ok(output) => {
+                    output.update(live_frame(data));
+                    self.live_output = Some(output);
+                    data.live = true;
+                }
+                Err(e) => {
+                    data.status.last_finished = Some(FinishedStatus::Error(format!(
+                        "Failed to start live output: {}",
+                        e
+                    )));
+                }
+            }
+            true
+        } else if cmd.is(cmd::STOP_LIVE_OUTPUT) {
+            self.live_output = None;
+            data.live = false;
+            true
+        } else if let Some(e) = cmd.get(cmd::LIVE_OUTPUT_FAILED) {
+            self.live_output = None;
+            data.live = false;
+            data.status.last_finished =
+                Some(FinishedStatus::Error(format!("Live output stopped: {}", e)));
+            true
+        } else if let Some(req) = cmd.get(cmd::REMOTE_REQUEST) {
+            req.reply(self.handle_remote(ctx, &req.method, data));
+            true
+        } else if let Some(info) = cmd.get(cmd::IMPORT_SVG) {
+            if data.action.is_idle() {
+                match svg_import::load(info.path()) {
+                    Ok(paths) => {
+                        data.action = CurrentAction::EditingText;
+                        let alert = alert::make_svg_import_alert(paths);
+                        ctx.submit_command(
+                            ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(alert))),
+                        );
+                    }
+                    Err(e) => {
+                        data.status.last_finished = Some(FinishedStatus::Error(format!(
+                            "Failed to import {}: {}",
+                            info.path().display(),
+                            e
+                        )))
+                    }
+                }
+            }
+            true
+        } else if let Some(info) = cmd.get(cmd::IMPORT_MUSIC) {
+            spawn_async_music_import(
+                ctx.get_external_handle(),
+                info.path().to_owned(),
+                ctx.window_id(),
+            );
+            true
+        } else if let Some(result) = cmd.get(cmd::FINISHED_MUSIC_IMPORT) {
+            match result {
+                Ok(music) => data.set_music(music.clone()),
+                Err(e) => {
+                    data.status.last_finished = Some(FinishedStatus::Error(format!(
+                        "Failed to import music: {}",
+                        e
+                    )))
+                }
+            }
+            true
+        } else if let Some(info) = cmd.get(cmd::IMPORT_AUDIO) {
+            spawn_async_audio_import(
+                ctx.get_external_handle(),
+                info.path().to_owned(),
+                data.time(),
+                ctx.window_id(),
+            );
+            true
+        } else if let Some(result) = cmd.get(cmd::FINISHED_AUDIO_IMPORT) {
+            match result {
+                Ok(snip) => data.import_audio(snip.clone()),
+                Err(e) => {
+                    data.status.last_finished = Some(FinishedStatus::Error(format!(
+                        "Failed to import audio: {}",
+                        e
+                    )))
+                }
+            }
+            true
+        } else if let Some(result) = cmd.get(cmd::FINISHED_SCREEN_CAPTURE) {
+            match result {
+                Ok(underlay) => data.set_underlay(underlay.clone()),
+                Err(e) => {
+                    data.status.last_finished = Some(FinishedStatus::Error(format!(
+                        "Failed to record the screen: {}",
+                        e
+                    )))
+                }
+            }
+            true
+        } else if let Some(result) = cmd.get(cmd::FINISHED_WEBCAM_CAPTURE) {
+            match result {
+                Ok(video) => data.set_webcam_video(video.clone()),
+                Err(e) => {
+                    data.status.last_finished = Some(FinishedStatus::Error(format!(
+                        "Failed to record the webcam: {}",
+                        e
+                    )))
+                }
+            }
             true
         } else if cmd.is(druid::commands::SAVE_FILE_AS) || cmd.is(druid::commands::SAVE_FILE) {
             let mut path = if let Some(info) = cmd.get(druid::commands::SAVE_FILE_AS) {
@@ -450,23 +1327,63 @@ impl Editor {
             data.status.in_progress.saving = Some(path.clone());
             spawn_async_save(
                 ctx.get_external_handle(),
-                SaveFileData::from_editor_state(data),
+                SaveFileData::from_scribl(&data.scribl),
                 path,
                 ctx.window_id(),
             );
             true
-        } else if cmd.is(druid::commands::OPEN_FILE) {
+        } else if let Some(info) = cmd.get(druid::commands::OPEN_FILE) {
+            self.load(ctx, data, info.path(), false);
+            true
+        } else if let Some(path) = cmd.get(cmd::OPEN_AUTOSAVE) {
+            self.load(ctx, data, path, true);
+            true
+        } else if cmd.is(cmd::SHOW_AUTOSAVES) {
+            let autosaves = crate::autosave::autosave_dir(&data.config)
+                .map(|dir| crate::autosave::list_autosaves(&dir))
+                .unwrap_or_default();
+            ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(
+                alert::make_autosaves_alert(autosaves),
+            ))));
+            true
+        } else if let Some(text) = cmd.get(scribl_widget::ANNOUNCE) {
+            log::info!("announcing \"{}\"", text);
+            data.status.last_finished = Some(FinishedStatus::Notice(text.clone()));
+            true
+        } else if cmd.is(cmd::SHOW_TEMPLATES) {
+            let templates = data
+                .config
+                .templates
+                .dir()
+                .map(|dir| crate::template::list_templates(&dir))
+                .unwrap_or_default();
+            ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(
+                alert::make_templates_alert(templates),
+            ))));
+            true
+        } else if cmd.is(cmd::SHOW_SAVE_TEMPLATE) {
+            if data.action.is_idle() {
+                data.action = CurrentAction::EditingText;
+                let alert = alert::make_template_name_alert();
+                ctx.submit_command(ModalHost::SHOW_MODAL.with(SingleUse::new(Box::new(alert))));
+            }
+            true
+        } else if let Some(name) = cmd.get(cmd::SAVE_TEMPLATE) {
+            let result = data
+                .config
+                .templates
+                .dir()
+                .ok_or_else(|| anyhow::anyhow!("couldn't determine the templates directory"))
+                .and_then(|dir| crate::template::template_path(&dir, name))
+                .and_then(|path| Template::from_editor(data).save_to_path(&path));
+            data.status.last_finished = Some(match result {
+                Ok(()) => FinishedStatus::Notice(format!("Saved template \"{}\"", name.trim())),
+                Err(e) => FinishedStatus::Error(format!("Failed to save template: {}", e)),
+            });
+            true
+        } else if let Some(fraction) = cmd.get(cmd::LOAD_PROGRESS) {
             if data.status.in_progress.loading.is_some() {
-                log::error!("not loading, already loading");
-            } else {
-                let info = cmd.get_unchecked(druid::commands::OPEN_FILE);
-                data.status.in_progress.loading = Some(info.path().to_owned());
-                spawn_async_load(
-                    ctx.get_external_handle(),
-                    info.path().to_owned(),
-                    ctx.window_id(),
-                );
-                data.set_loading();
+                data.status.in_progress.loading_progress = *fraction;
             }
             true
         } else if cmd.is(cmd::FINISHED_ASYNC_LOAD) {
@@ -474,7 +1391,13 @@ impl Editor {
             data.update_load_status(result);
             if let Ok(save_data) = &result.save_data {
                 *data = EditorState::from_save_file(save_data.clone(), data.config.clone());
-                data.save_path = Some(result.path.clone());
+                if result.autosave {
+                    // The autosave hasn't been saved anywhere that the user chose, so treat it as
+                    // unsaved.
+                    data.saved_data = None;
+                } else {
+                    data.save_path = Some(result.path.clone());
+                }
             }
             true
         } else if cmd.is(cmd::FINISHED_ASYNC_SAVE) {
@@ -487,15 +1410,17 @@ impl Editor {
         } else if cmd.is(cmd::ENCODING_STATUS) {
             let status = cmd.get_unchecked(cmd::ENCODING_STATUS);
             data.update_encoding_status(status);
+            self.start_next_export(ctx, data);
             true
         } else if let Some(status) = cmd.get(cmd::RECORDING_AUDIO_STATUS) {
             let vad = data.settings.denoise_setting != DenoiseSetting::Vad
                 || status.vad >= data.config.audio_input.vad_threshold;
-            data.input_loudness = if vad {
+            let loudness = if vad {
                 status.loudness as f64
             } else {
                 -f64::INFINITY
             };
+            data.input_levels.push(loudness, status.peak as f64);
             true
         } else {
             false
@@ -520,16 +1445,184 @@ fn spawn_async_save(ext_cmd: ExtEventSink, save_data: SaveFileData, path: PathBu
     });
 }
 
-fn spawn_async_load(ext_cmd: ExtEventSink, path: PathBuf, id: WindowId) {
+fn spawn_async_load(ext_cmd: ExtEventSink, path: PathBuf, autosave: bool, id: WindowId) {
     std::thread::spawn(move || {
+        // Only send progress updates when they're big enough to be visible, so that we don't
+        // flood the UI thread with commands.
+        let mut last_progress = 0.0;
+        let progress = |fraction: f64| {
+            if fraction - last_progress >= 0.01 {
+                last_progress = fraction;
+                let _ = ext_cmd.submit_command(cmd::LOAD_PROGRESS, Box::new(fraction), id);
+            }
+        };
+        let save_data = SaveFileData::load_from_path_with_progress(&path, progress);
         let data = cmd::AsyncLoadResult {
             path: path.clone(),
-            save_data: SaveFileData::load_from_path(&path).map_err(|e| e.to_string()),
+            save_data: save_data.map_err(|e| e.to_string()),
+            autosave,
         };
         let _ = ext_cmd.submit_command(cmd::FINISHED_ASYNC_LOAD, Box::new(data), id);
     });
 }
 
+fn spawn_async_web_export(
+    ext_cmd: ExtEventSink,
+    scribl: ScriblState,
+    path: PathBuf,
+    config: Export,
+    id: WindowId,
+) {
+    std::thread::spawn(move || {
+        let result = web_export::export(&scribl, &path, &config)
+            .map(|_| path)
+            .map_err(|e| e.to_string());
+        let _ = ext_cmd.submit_command(cmd::FINISHED_WEB_EXPORT, Box::new(result), id);
+    });
+}
+
+fn spawn_cache_clear(ext_cmd: ExtEventSink, config: Cache, id: WindowId) {
+    std::thread::spawn(move || {
+        let result = crate::cache::clear(&config).map_err(|e| e.to_string());
+        let _ = ext_cmd.submit_command(cmd::FINISHED_CLEAR_CACHE, Box::new(result), id);
+    });
+}
+
+fn spawn_duplicate_search(ext_cmd: ExtEventSink, talk: TalkSnippets, id: WindowId) {
+    std::thread::spawn(move || {
+        let result = cmd::DuplicateAudio {
+            duplicates: crate::audio::find_duplicates(&talk),
+            talk,
+        };
+        let _ = ext_cmd.submit_command(cmd::FINISHED_FIND_DUPLICATE_AUDIO, Box::new(result), id);
+    });
+}
+
+fn spawn_speech_detection(
+    ext_cmd: ExtEventSink,
+    talk: TalkSnippets,
+    id: TalkSnippetId,
+    win: WindowId,
+) {
+    std::thread::spawn(move || {
+        let vad = vad::detect_speech(&talk.snippet(id).samples());
+        let result = cmd::SpeechDetection { talk, id, vad };
+        let _ = ext_cmd.submit_command(cmd::FINISHED_DETECT_SPEECH, Box::new(result), win);
+    });
+}
+
+fn spawn_audio_processing(
+    ext_cmd: ExtEventSink,
+    talk: TalkSnippets,
+    id: TalkSnippetId,
+    vad_threshold: Option<f32>,
+    win: WindowId,
+) {
+    std::thread::spawn(move || {
+        // Only send progress updates when they're big enough to be visible, so that we don't
+        // flood the UI thread with commands.
+        let mut last_progress = 0.0;
+        let progress = |fraction: f64| {
+            if fraction - last_progress >= 0.01 {
+                last_progress = fraction;
+                let _ =
+                    ext_cmd.submit_command(cmd::PROCESS_AUDIO_PROGRESS, Box::new(fraction), win);
+            }
+        };
+        let (mut buf, vad) = vad::denoise(&talk.snippet(id).raw_samples(), progress);
+        if let Some(threshold) = vad_threshold {
+            vad::gate(&mut buf, &vad, threshold);
+        }
+        let result = cmd::ProcessedAudio { talk, id, buf };
+        let _ = ext_cmd.submit_command(cmd::FINISHED_PROCESS_AUDIO, Box::new(result), win);
+    });
+}
+
+fn spawn_loudness_measurement(
+    ext_cmd: ExtEventSink,
+    scribl: ScriblState,
+    target: Option<f64>,
+    id: WindowId,
+) {
+    std::thread::spawn(move || {
+        let result = cmd::LoudnessMeasurement {
+            loudness: scribl.loudness().map_err(|e| e.to_string()),
+            talk: scribl.talk,
+            target,
+        };
+        let _ = ext_cmd.submit_command(cmd::FINISHED_LOUDNESS_MEASUREMENT, Box::new(result), id);
+    });
+}
+
+/// Everything that's on the canvas right now, for sending to the live output.
+fn live_frame(data: &EditorState) -> LiveFrame {
+    LiveFrame {
+        snippets: data.scribl.draw.clone(),
+        pages: data.scribl.pages.clone(),
+        new_strokes: data.new_stroke_seq().cloned(),
+        current_stroke: data
+            .new_stroke()
+            .and_then(|s| s.visible_stroke(data.settings.cur_style(), data.time())),
+        time: data.time(),
+    }
+}
+
+/// The videos that they asked us to record while they talk.
+fn requested_video_captures(data: &EditorState) -> Vec<VideoCaptureRequest> {
+    let mut ret = Vec::new();
+    if data.settings.capture_screen {
+        let config = &data.config.screen_capture;
+        ret.push(VideoCaptureRequest {
+            kind: "screen",
+            source: VideoSource::Screen(config.region),
+            fps: config.fps,
+            finished: cmd::FINISHED_SCREEN_CAPTURE,
+        });
+    }
+    if data.settings.capture_webcam {
+        let config = &data.config.webcam;
+        ret.push(VideoCaptureRequest {
+            kind: "webcam",
+            source: VideoSource::Webcam(config.device.clone()),
+            fps: config.fps,
+            finished: cmd::FINISHED_WEBCAM_CAPTURE,
+        });
+    }
+    ret
+}
+
+fn start_video_capture(
+    data: &EditorState,
+    req: &VideoCaptureRequest,
+) -> anyhow::Result<VideoRecorder> {
+    let path = underlay::capture_path(req.kind, data.save_path.as_deref())?;
+    VideoRecorder::start(&path, &req.source, req.fps)
+}
+
+fn spawn_async_capture_finish(ext_cmd: ExtEventSink, capture: VideoCapture, id: WindowId) {
+    std::thread::spawn(move || {
+        let result = capture
+            .recorder
+            .finish(capture.start)
+            .map_err(|e| e.to_string());
+        let _ = ext_cmd.submit_command(capture.finished, Box::new(result), id);
+    });
+}
+
+fn spawn_async_music_import(ext_cmd: ExtEventSink, path: PathBuf, id: WindowId) {
+    std::thread::spawn(move || {
+        let result = MusicTrack::from_path(&path).map_err(|e| e.to_string());
+        let _ = ext_cmd.submit_command(cmd::FINISHED_MUSIC_IMPORT, Box::new(result), id);
+    });
+}
+
+fn spawn_async_audio_import(ext_cmd: ExtEventSink, path: PathBuf, time: Time, id: WindowId) {
+    std::thread::spawn(move || {
+        let result = TalkSnippet::from_path(&path, time).map_err(|e| e.to_string());
+        let _ = ext_cmd.submit_command(cmd::FINISHED_AUDIO_IMPORT, Box::new(result), id);
+    });
+}
+
 impl Widget<EditorState> for Editor {
     fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut EditorState, env: &Env) {
         match event {
@@ -543,10 +1636,33 @@ impl Widget<EditorState> for Editor {
                     ctx.set_handled();
                 }
             }
+            // If one of the controls has keyboard focus, it gets the first chance at the key.
+            // Escape gives the focus back to the editor.
+            Event::KeyDown(ev) if !ctx.is_focused() => {
+                self.inner.event(ctx, event, data, env);
+                if !ctx.is_handled() {
+                    if ev.key == KbKey::Escape && !data.presenting {
+                        ctx.request_focus();
+                        ctx.set_handled();
+                    } else {
+                        self.handle_key_down(ctx, ev, data, env);
+                    }
+                }
+                return;
+            }
+            // Tab moves the focus to the controls, so that they can be used without a mouse.
+            Event::KeyDown(ev) if ev.key == KbKey::Tab && data.action.is_idle() => {
+                if ev.mods.shift() {
+                    ctx.focus_prev();
+                } else {
+                    ctx.focus_next();
+                }
+                ctx.set_handled();
+            }
             Event::KeyDown(ev) => self.handle_key_down(ctx, ev, data, env),
             Event::KeyUp(ev) => self.handle_key_up(ctx, ev, data, env),
             Event::Timer(tok) if tok == &self.autosave_timer_id => {
-                let autosave_data = SaveFileData::from_editor_state(data);
+                let autosave_data = SaveFileData::from_scribl(&data.scribl);
                 if !self.last_autosave_data.same(&Some(autosave_data.clone())) {
                     let autosave_data = AutosaveData {
                         data: autosave_data.clone(),
@@ -559,11 +1675,29 @@ impl Widget<EditorState> for Editor {
                     }
                 }
                 self.last_autosave_data = Some(autosave_data);
-                self.autosave_timer_id = ctx.request_timer(AUTOSAVE_INTERVAL);
+                if let Some(interval) = data.config.autosave.interval() {
+                    self.autosave_timer_id = ctx.request_timer(interval);
+                }
             }
             Event::AnimFrame(_) => {
                 if data.action.time_factor() != 0.0 {
+                    let before = data.time();
                     data.update_time();
+                    // Playback stops at the end of the animation (unless it started after the
+                    // end, in which case there's nowhere to stop).
+                    let end = data.scribl.end_time();
+                    if matches!(data.action, CurrentAction::Playing(_))
+                        && before < end
+                        && data.time() >= end
+                    {
+                        data.finish_action();
+                        data.warp_time_to(end);
+                    }
+                }
+                if let CurrentAction::PunchingIn(punch) = &data.action {
+                    if data.time() >= punch.end + PUNCH_IN_POSTROLL {
+                        data.finish_action();
+                    }
                 }
             }
             Event::WindowCloseRequested => {
@@ -599,11 +1733,61 @@ impl Widget<EditorState> for Editor {
         }
         self.inner.update(ctx, old_data, data, env);
 
+        if old_data.selected_snippet != data.selected_snippet && data.action.is_idle() {
+            if let Some(id) = data.selected_snippet.filter(|&id| data.has_snippet(id)) {
+                ctx.submit_command(
+                    scribl_widget::ANNOUNCE.with(format!("Selected {}", data.describe_snippet(id))),
+                );
+            }
+        }
+
+        if !old_data.scribl.same(&data.scribl) || old_data.save_path != data.save_path {
+            self.crash_snapshot.update(
+                data.save_path.clone(),
+                SaveFileData::from_scribl(&data.scribl),
+            );
+        }
+
         let old_audio_state = old_data.audio_state();
         let new_audio_state = data.audio_state();
         if let Some(audio) = &mut self.audio {
             audio.update(old_audio_state, new_audio_state);
         }
+
+        match (&old_data.action, &data.action) {
+            (CurrentAction::RecordingAudio(_), CurrentAction::RecordingAudio(_)) => {}
+            (_, CurrentAction::RecordingAudio(start)) => {
+                for req in requested_video_captures(data) {
+                    match start_video_capture(data, &req) {
+                        Ok(recorder) => self.video_captures.push(VideoCapture {
+                            start: *start,
+                            recorder,
+                            finished: req.finished,
+                        }),
+                        Err(e) => ctx.submit_command(req.finished.with(Err(e.to_string()))),
+                    }
+                }
+            }
+            _ => {
+                for capture in self.video_captures.drain(..) {
+                    spawn_async_capture_finish(ctx.get_external_handle(), capture, ctx.window_id());
+                }
+            }
+        }
+
+        if old_data.presenting != data.presenting {
+            let window = ctx.window();
+            window.show_titlebar(!data.presenting);
+            window.set_window_state(if data.presenting {
+                WindowState::Maximized
+            } else {
+                WindowState::Restored
+            });
+        }
+
+        if let Some(output) = &self.live_output {
+            output.update(live_frame(data));
+        }
     }
 
     fn lifecycle(
@@ -615,15 +1799,26 @@ impl Widget<EditorState> for Editor {
     ) {
         match event {
             LifeCycle::WidgetAdded => {
+                self.crash_snapshot.update(
+                    data.save_path.clone(),
+                    SaveFileData::from_scribl(&data.scribl),
+                );
                 self.autosave_tx = Some(crate::autosave::spawn_autosave_thread(
                     ctx.get_external_handle(),
                     ctx.window_id(),
+                    &data.config,
                 ));
-                self.autosave_timer_id = ctx.request_timer(AUTOSAVE_INTERVAL);
+                if let Some(interval) = data.config.autosave.interval() {
+                    self.autosave_timer_id = ctx.request_timer(interval);
+                }
                 self.audio = Some(AudioHandle::initialize_audio(
                     ctx.get_external_handle(),
                     ctx.widget_id().into(),
+                    data.config.audio_backend,
                 ));
+                // We register before the controls do, so that Tab goes from the editor to the
+                // first control.
+                ctx.register_for_focus();
             }
             _ => {}
         }
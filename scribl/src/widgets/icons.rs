@@ -1,8 +1,8 @@
 //! This module contains icons from font awesome. Instead of using the svg
 //! files, we embed the paths directly (because that makes it easier to change
 //! their colors). Some of these images (specifically CAMERA, VIDEO,
-//! MICROPHONE, PLAY, PAUSE, FADE_OUT) were derived from font-awesome, which is
-//! available under a CC-BY license.
+//! MICROPHONE, PLAY, PAUSE, STEP_BACKWARD, STEP_FORWARD, FADE_OUT, HEADPHONES)
+//! were derived from font-awesome, which is available under a CC-BY license.
 //! (TODO: is this comment enough for the attribution requirements?)
 
 use scribl_widget::Icon;
@@ -26,6 +26,12 @@ pub const MICROPHONE: Icon = Icon {
     path: "M176 352c53.02 0 96-42.98 96-96V96c0-53.02-42.98-96-96-96S80 42.98 80 96v160c0 53.02 42.98 96 96 96zm160-160h-16c-8.84 0-16 7.16-16 16v48c0 74.8-64.49 134.82-140.79 127.38C96.71 376.89 48 317.11 48 250.3V208c0-8.84-7.16-16-16-16H16c-8.84 0-16 7.16-16 16v40.16c0 89.64 63.97 169.55 152 181.69V464H96c-8.84 0-16 7.16-16 16v16c0 8.84 7.16 16 16 16h160c8.84 0 16-7.16 16-16v-16c0-8.84-7.16-16-16-16h-56v-33.77C285.71 418.47 352 344.9 352 256v-48c0-8.84-7.16-16-16-16z",
 };
 
+pub const HEADPHONES: Icon = Icon {
+    width: 512,
+    height: 512,
+    path: "M256 32C114.52 32 0 146.5 0 288v48a32 32 0 0017.69 28.62l14.38 7.19C34.08 431.9 83.42 480 144 480h24c13.26 0 24-10.74 24-24V280c0-13.26-10.74-24-24-24h-24c-31.34 0-59.67 12.88-80 33.63V288c0-105.87 86.13-192 192-192s192 86.13 192 192v1.63C427.67 268.88 399.34 256 368 256h-24c-13.26 0-24 10.74-24 24v176c0 13.26 10.74 24 24 24h24c60.58 0 109.92-48.1 111.93-108.19l14.38-7.19A32 32 0 00512 336v-48c0-141.48-114.5-256-256-256z",
+};
+
 pub const PLAY: Icon = Icon {
     width: 448,
     height: 512,
@@ -38,6 +44,18 @@ pub const PAUSE: Icon = Icon {
     path: "M144 479H48c-26.5 0-48-21.5-48-48V79c0-26.5 21.5-48 48-48h96c26.5 0 48 21.5 48 48v352c0 26.5-21.5 48-48 48zm304-48V79c0-26.5-21.5-48-48-48h-96c-26.5 0-48 21.5-48 48v352c0 26.5 21.5 48 48 48h96c26.5 0 48-21.5 48-48z",
 };
 
+pub const STEP_BACKWARD: Icon = Icon {
+    width: 448,
+    height: 512,
+    path: "M64 468V44c0-6.6 5.4-12 12-12h48c6.6 0 12 5.4 12 12v176.4l195.5-181C352.1 22.3 384 36.6 384 64v384c0 27.4-31.9 41.7-52.5 24.6L136 292.7V468c0 6.6-5.4 12-12 12H76c-6.6 0-12-5.4-12-12z",
+};
+
+pub const STEP_FORWARD: Icon = Icon {
+    width: 448,
+    height: 512,
+    path: "M384 44v424c0 6.6-5.4 12-12 12h-48c-6.6 0-12-5.4-12-12V291.6l-195.5 181C95.9 489.7 64 475.4 64 448V64c0-27.4 31.9-41.7 52.5-24.6L312 219.3V44c0-6.6 5.4-12 12-12h48c6.6 0 12 5.4 12 12z",
+};
+
 pub const SNAIL: Icon = Icon {
     width: 148,
     height: 135,
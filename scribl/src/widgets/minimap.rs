@@ -0,0 +1,154 @@
+use druid::widget::prelude::*;
+use druid::{Color, Point, Rect};
+
+use scribl_curves::Time;
+
+use crate::EditorState;
+
+pub const MINIMAP_HEIGHT: f64 = 16.0;
+
+const BACKGROUND_COLOR: Color = Color::rgb8(0x30, 0x30, 0x30);
+const DENSITY_COLOR: Color = crate::UI_LIGHT_YELLOW;
+const VIEWPORT_FILL_COLOR: Color = Color::rgba8(0xff, 0xff, 0xff, 0x30);
+const VIEWPORT_STROKE_COLOR: Color = Color::WHITE;
+const CURSOR_COLOR: Color = crate::UI_LIGHT_BLUE;
+
+/// A thin strip that shows an overview of the whole animation, along with the part of it that's
+/// currently visible in the timeline.
+///
+/// This widget doesn't do any scrolling itself; the timeline tells it which part is visible, and
+/// it tells the timeline where they clicked.
+pub struct Minimap {
+    /// The range of times that are currently visible in the timeline.
+    visible_times: (Time, Time),
+    /// If they clicked or dragged in the minimap, this is the time that they want to see in the
+    /// middle of the timeline.
+    scroll_request: Option<Time>,
+}
+
+impl Minimap {
+    pub fn new() -> Minimap {
+        Minimap {
+            visible_times: (Time::ZERO, Time::ZERO),
+            scroll_request: None,
+        }
+    }
+
+    /// Tells the minimap which part of the timeline is visible. Returns true if that changed.
+    pub fn set_visible(&mut self, start_time: Time, end_time: Time) -> bool {
+        let changed = self.visible_times != (start_time, end_time);
+        self.visible_times = (start_time, end_time);
+        changed
+    }
+
+    /// If they asked to scroll somewhere since the last time this was called, returns the time
+    /// that should be centered in the timeline.
+    pub fn take_scroll_request(&mut self) -> Option<Time> {
+        self.scroll_request.take()
+    }
+
+    /// The minimap shows everything from the beginning to this time.
+    fn end_time(&self, data: &EditorState) -> Time {
+        data.scribl
+            .end_time()
+            .max(data.time())
+            .max(self.visible_times.1)
+    }
+
+    fn time_to_x(&self, data: &EditorState, width: f64, t: Time) -> f64 {
+        let end = self.end_time(data).as_micros().max(1) as f64;
+        t.as_micros() as f64 / end * width
+    }
+
+    fn x_to_time(&self, data: &EditorState, width: f64, x: f64) -> Time {
+        let end = self.end_time(data).as_micros() as f64;
+        let frac = (x / width.max(1.0)).max(0.0).min(1.0);
+        Time::from_micros((frac * end) as i64)
+    }
+
+    /// For every pixel column in the minimap, counts the number of snippets that are active.
+    fn density(&self, data: &EditorState, width: f64) -> Vec<u32> {
+        let columns = width.ceil().max(1.0) as usize;
+        let mut ret = vec![0; columns];
+        let draw = data
+            .scribl
+            .draw
+            .snippets()
+            .map(|(_, s)| (s.start_time(), s.last_draw_time()));
+        let talk = data
+            .scribl
+            .talk
+            .snippets()
+            .map(|(_, s)| (s.start_time(), s.end_time()));
+        for (start, end) in draw.chain(talk) {
+            let start_col = self.time_to_x(data, width, start).floor().max(0.0) as usize;
+            let end_col = self.time_to_x(data, width, end).ceil() as usize;
+            let end_col = end_col.max(start_col + 1).min(columns);
+            for count in &mut ret[start_col.min(columns)..end_col] {
+                *count += 1;
+            }
+        }
+        ret
+    }
+}
+
+impl Widget<EditorState> for Minimap {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut EditorState, _env: &Env) {
+        let width = ctx.size().width;
+        match event {
+            Event::MouseDown(ev) if ev.button.is_left() => {
+                ctx.set_active(true);
+                self.scroll_request = Some(self.x_to_time(data, width, ev.pos.x));
+                ctx.set_handled();
+            }
+            Event::MouseMove(ev) if ctx.is_active() => {
+                self.scroll_request = Some(self.x_to_time(data, width, ev.pos.x));
+                ctx.set_handled();
+            }
+            Event::MouseUp(_) if ctx.is_active() => {
+                ctx.set_active(false);
+                ctx.set_handled();
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(&mut self, _: &mut LifeCycleCtx, _: &LifeCycle, _: &EditorState, _: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &EditorState, data: &EditorState, _: &Env) {
+        if !old_data.scribl.same(&data.scribl) || old_data.time() != data.time() {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(&mut self, _: &mut LayoutCtx, bc: &BoxConstraints, _: &EditorState, _: &Env) -> Size {
+        bc.constrain((bc.max().width, MINIMAP_HEIGHT))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &EditorState, _env: &Env) {
+        let size = ctx.size();
+        ctx.fill(size.to_rect(), &BACKGROUND_COLOR);
+
+        let density = self.density(data, size.width);
+        let max_density = density.iter().cloned().max().unwrap_or(0).max(1) as f64;
+        for (x, &count) in density.iter().enumerate() {
+            if count > 0 {
+                let alpha = 0.3 + 0.7 * count as f64 / max_density;
+                let rect = Rect::new(x as f64, 0.0, x as f64 + 1.0, size.height);
+                ctx.fill(rect, &DENSITY_COLOR.with_alpha(alpha));
+            }
+        }
+
+        let cursor_x = self.time_to_x(data, size.width, data.time());
+        ctx.fill(
+            Rect::new(cursor_x - 1.0, 0.0, cursor_x + 1.0, size.height),
+            &CURSOR_COLOR,
+        );
+
+        let x0 = self.time_to_x(data, size.width, self.visible_times.0);
+        let x1 = self.time_to_x(data, size.width, self.visible_times.1);
+        let viewport = Rect::from_points(Point::new(x0, 0.5), Point::new(x1, size.height - 0.5));
+        ctx.fill(viewport, &VIEWPORT_FILL_COLOR);
+        ctx.stroke(viewport, &VIEWPORT_STROKE_COLOR, 1.0);
+    }
+}
@@ -1,15 +1,24 @@
 mod alert;
 mod audio_indicator;
+mod debug_overlay;
 mod drawing_pane;
 mod editor;
+mod minimap;
 pub mod icons;
+mod page_list;
 mod palette;
+mod palm_rejection;
+mod present;
+mod retime;
 mod status;
 mod timeline;
 
 pub use audio_indicator::AudioIndicator;
+pub use debug_overlay::DebugOverlay;
 pub use drawing_pane::DrawingPane;
 pub use editor::Editor;
+pub use page_list::make_page_list;
 pub use palette::{Palette, PaletteData};
+pub use present::PresentOverlay;
 pub use status::make_status_bar;
-pub use timeline::Timeline;
+pub use timeline::{save_summary, Timeline};
@@ -0,0 +1,83 @@
+use druid::im::Vector;
+use druid::widget::{Label, List, Painter, Scroll};
+use druid::{theme, Color, Data, Env, Lens, RenderContext, Widget, WidgetExt};
+
+use scribl_curves::Time;
+
+use crate::{cmd, EditorState};
+
+const PAGE_LIST_WIDTH: f64 = 80.0;
+const CURRENT_PAGE_COLOR: Color = crate::UI_DARK_BLUE;
+
+/// One row in the list of pages.
+#[derive(Clone, Data)]
+struct PageEntry {
+    index: usize,
+    start: Time,
+    /// Is this the page that's currently showing?
+    current: bool,
+}
+
+/// A read-only lens from the editor state to the list of pages.
+struct PageEntries;
+
+impl PageEntries {
+    fn entries(data: &EditorState) -> Vector<PageEntry> {
+        let current = data.scribl.pages.page_at(data.time());
+        data.scribl
+            .pages
+            .starts()
+            .enumerate()
+            .map(|(index, start)| PageEntry {
+                index,
+                start,
+                current: index == current,
+            })
+            .collect()
+    }
+}
+
+impl Lens<EditorState, Vector<PageEntry>> for PageEntries {
+    fn with<V, F: FnOnce(&Vector<PageEntry>) -> V>(&self, data: &EditorState, f: F) -> V {
+        f(&PageEntries::entries(data))
+    }
+
+    fn with_mut<V, F: FnOnce(&mut Vector<PageEntry>) -> V>(
+        &self,
+        data: &mut EditorState,
+        f: F,
+    ) -> V {
+        // The list is only for display, so any changes get thrown away.
+        f(&mut PageEntries::entries(data))
+    }
+}
+
+fn make_page_row() -> impl Widget<PageEntry> {
+    let background = Painter::new(|ctx, entry: &PageEntry, env| {
+        if entry.current {
+            let rect = ctx
+                .size()
+                .to_rounded_rect(env.get(theme::BUTTON_BORDER_RADIUS));
+            ctx.fill(rect, &CURRENT_PAGE_COLOR);
+        }
+    });
+
+    Label::new(|entry: &PageEntry, _env: &Env| {
+        let secs = entry.start.as_micros() / 1_000_000;
+        format!("Page {}\n{}:{:02}", entry.index + 1, secs / 60, secs % 60)
+    })
+    .padding(5.0)
+    .expand_width()
+    .background(background)
+    .on_click(|ctx, entry, _env| ctx.submit_command(cmd::WARP_TIME_TO.with(entry.start)))
+}
+
+/// A sidebar that lists the pages of the animation, and jumps to the start of a page when it gets
+/// clicked.
+pub fn make_page_list() -> impl Widget<EditorState> {
+    Scroll::new(List::new(make_page_row).with_spacing(2.0))
+        .vertical()
+        .lens(PageEntries)
+        .padding(5.0)
+        .fix_width(PAGE_LIST_WIDTH)
+}
@@ -1,12 +1,19 @@
 use druid::widget::prelude::*;
-use druid::{theme, Color, Data, Lens, RenderContext};
+use druid::{theme, Color, Data, Lens, Point, RenderContext, WidgetPod};
 use std::sync::Arc;
 
 use scribl_widget::{RadioGroup, TooltipExt};
 
+/// The number of recently used colors that we remember.
+const MAX_RECENT_COLORS: usize = 5;
+/// The vertical space between the palette and the row of recently used colors.
+const RECENT_COLORS_SPACING: f64 = 8.0;
+
 #[derive(Clone, Data, Lens)]
 pub struct PaletteData {
     colors: Arc<Vec<(Color, String)>>,
+    /// The colors that were used most recently, starting with the most recent.
+    recent: Arc<Vec<Color>>,
     selected: Color,
 }
 
@@ -28,12 +35,32 @@ impl Default for PaletteData {
         let selected = colors[0].0.clone();
         PaletteData {
             colors: Arc::new(colors),
+            recent: Arc::new(Vec::new()),
             selected,
         }
     }
 }
 
 impl PaletteData {
+    /// Creates a palette with the given colors (and their names), with the first one selected.
+    ///
+    /// If `colors` is empty, this is the default palette.
+    pub fn with_colors(colors: Vec<(Color, String)>) -> PaletteData {
+        match colors.first() {
+            Some((selected, _)) => PaletteData {
+                selected: selected.clone(),
+                colors: Arc::new(colors),
+                recent: Arc::new(Vec::new()),
+            },
+            None => PaletteData::default(),
+        }
+    }
+
+    /// The colors in the palette, along with their names.
+    pub fn colors(&self) -> &[(Color, String)] {
+        &self.colors
+    }
+
     pub fn selected_color(&self) -> &Color {
         &self.selected
     }
@@ -46,10 +73,34 @@ impl PaletteData {
             Err(())
         }
     }
+
+    /// Selects a color, which doesn't need to be one of the colors in the palette.
+    pub fn select_color(&mut self, color: Color) {
+        self.selected = color;
+    }
+
+    /// Records that the selected color was just used, moving it to the front of the recent colors.
+    pub fn mark_used(&mut self) {
+        let rgba = self.selected.as_rgba_u32();
+        if self.recent.first().map(|c| c.as_rgba_u32()) == Some(rgba) {
+            return;
+        }
+        let mut recent = vec![self.selected.clone()];
+        recent.extend(
+            self.recent
+                .iter()
+                .filter(|c| c.as_rgba_u32() != rgba)
+                .take(MAX_RECENT_COLORS - 1)
+                .cloned(),
+        );
+        self.recent = Arc::new(recent);
+    }
 }
 
 pub struct Palette {
     inner: RadioGroup<Color>,
+    /// A row of the recently used colors, underneath the main palette.
+    recent: WidgetPod<Color, RadioGroup<Color>>,
 }
 
 pub struct PaletteElement {
@@ -82,6 +133,7 @@ impl Palette {
     pub fn new() -> Palette {
         Palette {
             inner: RadioGroup::column(None),
+            recent: WidgetPod::new(RadioGroup::row(None)),
         }
     }
 
@@ -95,13 +147,25 @@ impl Palette {
                 Box::new(elt) as Box<dyn Widget<Color>>
             };
             (widget, c.clone())
-        }));
+        }))
+        .with_names(colors.iter().map(|(_, name)| name.clone()));
+    }
+
+    fn resize_recent(&mut self, recent: &[Color]) {
+        self.recent = WidgetPod::new(
+            RadioGroup::row(recent.iter().map(|c| {
+                let elt = PaletteElement { color: c.clone() }.tooltip("Recently used");
+                (Box::new(elt) as Box<dyn Widget<Color>>, c.clone())
+            }))
+            .with_names((1..=recent.len()).map(|i| format!("Recent color {}", i))),
+        );
     }
 }
 
 impl Widget<PaletteData> for Palette {
     fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut PaletteData, env: &Env) {
         self.inner.event(ctx, event, &mut data.selected, env);
+        self.recent.event(ctx, event, &mut data.selected, env);
     }
 
     fn update(
@@ -119,6 +183,13 @@ impl Widget<PaletteData> for Palette {
             self.inner
                 .update(ctx, &old_data.selected, &data.selected, env);
         }
+        if data.recent != old_data.recent {
+            self.resize_recent(&data.recent);
+            ctx.children_changed();
+            ctx.request_layout();
+        } else {
+            self.recent.update(ctx, &data.selected, env);
+        }
     }
 
     fn lifecycle(
@@ -130,10 +201,12 @@ impl Widget<PaletteData> for Palette {
     ) {
         if let LifeCycle::WidgetAdded = event {
             self.resize(&data.colors);
+            self.resize_recent(&data.recent);
             ctx.children_changed();
             ctx.request_layout();
         }
         self.inner.lifecycle(ctx, event, &data.selected, env);
+        self.recent.lifecycle(ctx, event, &data.selected, env);
     }
 
     fn layout(
@@ -143,10 +216,26 @@ impl Widget<PaletteData> for Palette {
         data: &PaletteData,
         env: &Env,
     ) -> Size {
-        self.inner.layout(ctx, bc, &data.selected, env)
+        let size = self.inner.layout(ctx, bc, &data.selected, env);
+        if data.recent.is_empty() {
+            return size;
+        }
+
+        // The recent colors are smaller than the palette colors, so that a full row of them fits
+        // in the width of the palette. (The radio group leaves a one-pixel gap between buttons.)
+        let swatch_size = (size.width + 1.0) / MAX_RECENT_COLORS as f64 - 1.0;
+        let recent_bc = BoxConstraints::new(Size::ZERO, Size::new(size.width, swatch_size));
+        let recent_size = self.recent.layout(ctx, &recent_bc, &data.selected, env);
+        let recent_y = size.height + RECENT_COLORS_SPACING;
+        self.recent
+            .set_origin(ctx, &data.selected, env, Point::new(0.0, recent_y));
+        bc.constrain(Size::new(size.width, recent_y + recent_size.height))
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &PaletteData, env: &Env) {
         self.inner.paint(ctx, &data.selected, env);
+        if !data.recent.is_empty() {
+            self.recent.paint(ctx, &data.selected, env);
+        }
     }
 }
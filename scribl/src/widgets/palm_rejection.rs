@@ -0,0 +1,139 @@
+use druid::Point;
+use std::time::Duration;
+
+use scribl_curves::Time;
+
+/// If the pointer jumps by more than this many pixels in a single event, it probably means that
+/// a second finger (or a palm) touched the screen, and the touchscreen driver moved the emulated
+/// mouse pointer over there.
+const MAX_JUMP: f64 = 60.0;
+/// Once a stroke has moved this many pixels (without jumping), we accept it.
+const ACCEPT_DISTANCE: f64 = 12.0;
+/// Once a stroke has lasted for this long (without jumping), we accept it.
+const ACCEPT_DURATION: Duration = Duration::from_millis(150);
+/// Contacts that are shorter than this, and that don't move much, are probably a palm brushing
+/// the screen.
+const MIN_TAP_DURATION: Duration = Duration::from_millis(40);
+
+/// What we've decided about a stroke.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// It looks like it was drawn with a pen (or a mouse), so it should be drawn.
+    Accept,
+    /// It looks like a palm, or a multi-finger gesture, so it should be ignored.
+    Reject,
+    /// We haven't seen enough of it to tell.
+    Undecided,
+}
+
+/// Holds back the first few points of a stroke until we can tell whether it's a real stroke or
+/// just a palm resting on the touchscreen.
+///
+/// Touchscreens present themselves to us as a mouse, so the only information we have is where and
+/// when the pointer moves. A pen moves continuously; a palm tends to make brief contacts, and
+/// extra fingers make the pointer jump around.
+pub struct PendingStroke {
+    /// The points that we've held back so far, in screen coordinates, along with their times in
+    /// the animation.
+    points: Vec<(Point, Time)>,
+    /// How long (in wall-clock time) the contact has lasted.
+    elapsed: Duration,
+    /// The total distance (in screen coordinates) that the pointer has moved.
+    distance: f64,
+    verdict: Verdict,
+}
+
+impl PendingStroke {
+    pub fn new(pos: Point, time: Time) -> PendingStroke {
+        PendingStroke {
+            points: vec![(pos, time)],
+            elapsed: Duration::ZERO,
+            distance: 0.0,
+            verdict: Verdict::Undecided,
+        }
+    }
+
+    /// Adds a point to the stroke, returning the new verdict. `elapsed` is the wall-clock time
+    /// since the stroke started.
+    pub fn add_point(&mut self, pos: Point, time: Time, elapsed: Duration) -> Verdict {
+        if self.verdict != Verdict::Undecided {
+            return self.verdict;
+        }
+
+        // The unwrap is ok because `points` starts out non-empty.
+        let jump = pos.distance(self.points.last().unwrap().0);
+        self.points.push((pos, time));
+        self.elapsed = elapsed;
+        self.distance += jump;
+
+        self.verdict = if jump > MAX_JUMP {
+            Verdict::Reject
+        } else if self.distance >= ACCEPT_DISTANCE || self.elapsed >= ACCEPT_DURATION {
+            Verdict::Accept
+        } else {
+            Verdict::Undecided
+        };
+        self.verdict
+    }
+
+    /// The pointer was released, so we need to make a final decision.
+    pub fn release(&mut self, elapsed: Duration) -> Verdict {
+        if self.verdict == Verdict::Undecided {
+            self.elapsed = elapsed;
+            self.verdict = if self.elapsed < MIN_TAP_DURATION {
+                Verdict::Reject
+            } else {
+                Verdict::Accept
+            };
+        }
+        self.verdict
+    }
+
+    /// Takes the points that were held back, so that they can be added to the real stroke.
+    pub fn take_points(&mut self) -> Vec<(Point, Time)> {
+        std::mem::take(&mut self.points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(x: u64) -> Duration {
+        Duration::from_millis(x)
+    }
+
+    #[test]
+    fn accept_smooth_stroke() {
+        let mut s = PendingStroke::new(Point::new(0.0, 0.0), Time::ZERO);
+        assert_eq!(
+            s.add_point(Point::new(5.0, 0.0), Time::ZERO, ms(10)),
+            Verdict::Undecided
+        );
+        assert_eq!(
+            s.add_point(Point::new(10.0, 0.0), Time::ZERO, ms(20)),
+            Verdict::Undecided
+        );
+        assert_eq!(
+            s.add_point(Point::new(15.0, 0.0), Time::ZERO, ms(30)),
+            Verdict::Accept
+        );
+        assert_eq!(s.take_points().len(), 4);
+    }
+
+    #[test]
+    fn reject_jumps_and_brushes() {
+        let mut s = PendingStroke::new(Point::new(0.0, 0.0), Time::ZERO);
+        assert_eq!(
+            s.add_point(Point::new(200.0, 0.0), Time::ZERO, ms(10)),
+            Verdict::Reject
+        );
+
+        let mut s = PendingStroke::new(Point::new(0.0, 0.0), Time::ZERO);
+        assert_eq!(s.release(ms(10)), Verdict::Reject);
+
+        // A deliberate dot lasts a bit longer.
+        let mut s = PendingStroke::new(Point::new(0.0, 0.0), Time::ZERO);
+        assert_eq!(s.release(ms(100)), Verdict::Accept);
+    }
+}
@@ -0,0 +1,185 @@
+use druid::widget::prelude::*;
+use druid::widget::{Button, Flex, Label};
+use druid::{Color, Point, TimerToken, WidgetExt, WidgetPod};
+use std::time::{Duration, Instant};
+
+use scribl_widget::ToggleButton;
+
+use crate::widgets::icons;
+use crate::EditorState;
+
+const BAR_COLOR: Color = Color::rgba8(0x00, 0x00, 0x00, 0xa0);
+const BAR_PADDING: f64 = 5.0;
+
+// The controls disappear if the mouse hasn't moved for this long.
+const HIDE_DELAY: Duration = Duration::from_secs(2);
+
+/// In presentation mode, shows a thin bar of playback controls on top of the bottom of its child.
+///
+/// The bar only appears when the mouse moves, and it goes away again if the mouse stays still for
+/// a couple of seconds (unless the mouse is over the bar). Outside of presentation mode, this
+/// widget just shows its child.
+pub struct PresentOverlay {
+    inner: WidgetPod<EditorState, Box<dyn Widget<EditorState>>>,
+    bar: WidgetPod<EditorState, Box<dyn Widget<EditorState>>>,
+    bar_visible: bool,
+    hide_timer: TimerToken,
+    last_mouse_move: Instant,
+}
+
+impl PresentOverlay {
+    pub fn new(inner: impl Widget<EditorState> + 'static) -> PresentOverlay {
+        PresentOverlay {
+            inner: WidgetPod::new(inner).boxed(),
+            bar: WidgetPod::new(make_bar()).boxed(),
+            bar_visible: false,
+            hide_timer: TimerToken::INVALID,
+            last_mouse_move: Instant::now(),
+        }
+    }
+}
+
+fn make_bar() -> impl Widget<EditorState> {
+    let play_button = ToggleButton::from_icon(
+        &icons::PLAY,
+        BAR_PADDING,
+        |state: &EditorState, _env: &Env| {
+            if state.action.is_playing() {
+                "Pause (Space)"
+            } else {
+                "Play (Space)"
+            }
+            .to_owned()
+        },
+        |state: &EditorState| state.action.is_playing(),
+        |_, state, _| state.play(),
+        |_, state, _| state.finish_action(),
+    );
+    let prev = Button::new("Previous chapter")
+        .on_click(|_, data: &mut EditorState, _| data.jump_to_marker(false))
+        .disabled_if(|data: &EditorState, _| {
+            data.scribl.markers.prev_before(data.time()).is_none()
+        });
+    let next = Button::new("Next chapter")
+        .on_click(|_, data: &mut EditorState, _| data.jump_to_marker(true))
+        .disabled_if(|data: &EditorState, _| data.scribl.markers.next_after(data.time()).is_none());
+    let chapter = Label::dynamic(|data: &EditorState, _| {
+        data.scribl
+            .markers
+            .chapter_at(data.time())
+            .unwrap_or("")
+            .to_owned()
+    });
+    let exit = Button::new("Exit presentation (Esc)")
+        .on_click(|_, data: &mut EditorState, _| data.presenting = false);
+
+    Flex::row()
+        .with_child(play_button)
+        .with_default_spacer()
+        .with_child(prev)
+        .with_default_spacer()
+        .with_child(next)
+        .with_default_spacer()
+        .with_child(chapter)
+        .with_flex_spacer(1.0)
+        .with_child(exit)
+        .padding(BAR_PADDING)
+        .background(BAR_COLOR)
+}
+
+impl Widget<EditorState> for PresentOverlay {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut EditorState, env: &Env) {
+        if data.presenting {
+            match event {
+                Event::MouseMove(_) => {
+                    if !self.bar_visible {
+                        self.bar_visible = true;
+                        ctx.request_paint();
+                    }
+                    self.last_mouse_move = Instant::now();
+                    if self.hide_timer == TimerToken::INVALID {
+                        self.hide_timer = ctx.request_timer(HIDE_DELAY);
+                    }
+                }
+                Event::Timer(tok) if *tok == self.hide_timer => {
+                    let elapsed = self.last_mouse_move.elapsed();
+                    if self.bar.is_hot() {
+                        self.hide_timer = ctx.request_timer(HIDE_DELAY);
+                    } else if elapsed >= HIDE_DELAY {
+                        self.bar_visible = false;
+                        self.hide_timer = TimerToken::INVALID;
+                        ctx.request_paint();
+                    } else {
+                        self.hide_timer = ctx.request_timer(HIDE_DELAY - elapsed);
+                    }
+                    ctx.set_handled();
+                    return;
+                }
+                _ => {}
+            }
+            if self.bar_visible {
+                self.bar.event(ctx, event, data, env);
+            }
+        }
+        if !ctx.is_handled() {
+            self.inner.event(ctx, event, data, env);
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &EditorState,
+        env: &Env,
+    ) {
+        self.inner.lifecycle(ctx, event, data, env);
+        self.bar.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &EditorState,
+        data: &EditorState,
+        env: &Env,
+    ) {
+        if old_data.presenting != data.presenting {
+            self.bar_visible = false;
+            self.hide_timer = TimerToken::INVALID;
+        }
+        self.inner.update(ctx, data, env);
+        self.bar.update(ctx, data, env);
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &EditorState,
+        env: &Env,
+    ) -> Size {
+        let size = self.inner.layout(ctx, bc, data, env);
+        self.inner.set_origin(ctx, data, env, Point::ZERO);
+
+        let bar_bc = BoxConstraints::new(
+            Size::new(size.width, 0.0),
+            Size::new(size.width, size.height),
+        );
+        let bar_size = self.bar.layout(ctx, &bar_bc, data, env);
+        self.bar.set_origin(
+            ctx,
+            data,
+            env,
+            Point::new(0.0, size.height - bar_size.height),
+        );
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &EditorState, env: &Env) {
+        self.inner.paint(ctx, data, env);
+        if data.presenting && self.bar_visible {
+            self.bar.paint(ctx, data, env);
+        }
+    }
+}
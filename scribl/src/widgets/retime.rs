@@ -0,0 +1,262 @@
+use druid::kurbo::{BezPath, Circle, Line};
+use druid::widget::prelude::*;
+use druid::{Color, Point, Rect};
+
+use scribl_curves::{DrawSnippetId, Easing, Lerp, Time};
+
+use crate::{CurrentAction, EditorState};
+
+const GRAPH_SIZE: Size = Size::new(400.0, 200.0);
+const PADDING: f64 = 10.0;
+const KEYFRAME_RADIUS: f64 = 4.0;
+const HOT_KEYFRAME_RADIUS: f64 = 6.0;
+// How close (in pixels) the mouse needs to be to a key-frame in order to grab it.
+const GRAB_DISTANCE: f64 = 8.0;
+// The number of line segments to use when drawing the curve between two key-frames.
+const CURVE_STEPS: usize = 32;
+
+const BACKGROUND_COLOR: Color = Color::rgb8(0x30, 0x30, 0x30);
+const BORDER_COLOR: Color = Color::rgb8(0x70, 0x70, 0x70);
+const REFERENCE_COLOR: Color = Color::rgb8(0x55, 0x55, 0x55);
+const CURVE_COLOR: Color = crate::UI_LIGHT_YELLOW;
+const KEYFRAME_COLOR: Color = crate::UI_LIGHT_GREEN;
+const HOT_KEYFRAME_COLOR: Color = crate::UI_DARK_GREEN;
+const CURSOR_COLOR: Color = crate::UI_LIGHT_BLUE;
+
+/// A graph of how far a drawing has progressed, against the time in the animation.
+///
+/// The graph goes through the key-frames of the drawing's [`Lerp`]. Dragging a key-frame sideways
+/// changes when that part of the drawing gets drawn, so that (for example) a drawing can start
+/// slowly and then speed up. Double-clicking adds a key-frame, or removes one, and right-clicking
+/// between two key-frames changes the [`Easing`] between them.
+pub struct RetimeGraph {
+    id: DrawSnippetId,
+    /// The key-frame that the mouse is over.
+    hot: Option<usize>,
+    /// The key-frame that's being dragged.
+    dragging: Option<usize>,
+    /// While dragging, the times shown in the graph stay fixed. Otherwise, the graph would
+    /// rescale whenever the first or last key-frame moved.
+    frozen_range: Option<(Time, Time)>,
+}
+
+impl RetimeGraph {
+    pub fn new(id: DrawSnippetId) -> RetimeGraph {
+        RetimeGraph {
+            id,
+            hot: None,
+            dragging: None,
+            frozen_range: None,
+        }
+    }
+
+    fn lerp<'a>(&self, data: &'a EditorState) -> Option<&'a Lerp> {
+        let draw = &data.scribl.draw;
+        draw.has_snippet(self.id)
+            .then(|| draw.snippet(self.id).lerp())
+    }
+
+    /// The range of animation times shown in the graph: the times of the key-frames, with some
+    /// room on either side for dragging the first and last ones.
+    fn time_range(&self, lerp: &Lerp) -> (Time, Time) {
+        if let Some(range) = self.frozen_range {
+            return range;
+        }
+        let span = (lerp.last() - lerp.first()).as_micros().max(100_000);
+        let margin = span / 10;
+        let start = (lerp.first().as_micros() - margin).max(0);
+        let end = lerp.last().as_micros() + margin;
+        (Time::from_micros(start), Time::from_micros(end))
+    }
+
+    /// The original times of the first and last key-frames.
+    fn original_range(lerp: &Lerp) -> (Time, Time) {
+        let (first, _) = lerp.keyframes().next().unwrap();
+        let (last, _) = lerp.keyframes().last().unwrap();
+        (first, last)
+    }
+
+    fn graph_rect(size: Size) -> Rect {
+        size.to_rect().inset(-PADDING)
+    }
+
+    fn to_point(&self, lerp: &Lerp, rect: Rect, original: Time, lerped: Time) -> Point {
+        let (start, end) = self.time_range(lerp);
+        let x_frac = (lerped - start).as_micros() as f64 / (end - start).as_micros().max(1) as f64;
+        let (first, last) = RetimeGraph::original_range(lerp);
+        let y_frac =
+            (original - first).as_micros() as f64 / (last - first).as_micros().max(1) as f64;
+        Point::new(
+            rect.x0 + x_frac * rect.width(),
+            rect.y1 - y_frac * rect.height(),
+        )
+    }
+
+    fn x_to_time(&self, lerp: &Lerp, rect: Rect, x: f64) -> Time {
+        let (start, end) = self.time_range(lerp);
+        let frac = ((x - rect.x0) / rect.width()).max(0.0).min(1.0);
+        let us = start.as_micros() as f64 + frac * (end - start).as_micros() as f64;
+        Time::from_micros(us.round() as i64)
+    }
+
+    /// The original time (that is, the amount of progress through the drawing) at height `y`.
+    fn y_to_original(lerp: &Lerp, rect: Rect, y: f64) -> Time {
+        let (first, last) = RetimeGraph::original_range(lerp);
+        let frac = ((rect.y1 - y) / rect.height()).max(0.0).min(1.0);
+        let us = first.as_micros() as f64 + frac * (last - first).as_micros() as f64;
+        Time::from_micros(us.round() as i64)
+    }
+
+    /// The index of the key-frame that starts the interval containing the horizontal position `x`.
+    fn interval_at(&self, lerp: &Lerp, rect: Rect, x: f64) -> Option<usize> {
+        let time = self.x_to_time(lerp, rect, x);
+        let times = lerp.times();
+        times.windows(2).position(|w| w[0] <= time && time < w[1])
+    }
+
+    /// The key-frame closest to `pos`, if it's close enough to grab.
+    fn keyframe_at(&self, lerp: &Lerp, rect: Rect, pos: Point) -> Option<usize> {
+        lerp.keyframes()
+            .enumerate()
+            .map(|(i, (orig, lerped))| (i, self.to_point(lerp, rect, orig, lerped).distance(pos)))
+            .filter(|(_, dist)| *dist <= GRAB_DISTANCE)
+            .min_by(|(_, d1), (_, d2)| d1.partial_cmp(d2).unwrap())
+            .map(|(i, _)| i)
+    }
+
+    fn set_lerp(&self, data: &mut EditorState, lerp: Lerp) {
+        // The undo state gets pushed when the dialog is closed, so don't change anything after
+        // that.
+        if matches!(data.action, CurrentAction::EditingText) {
+            data.scribl.draw = data.scribl.draw.with_lerp(self.id, lerp);
+        }
+    }
+}
+
+impl Widget<EditorState> for RetimeGraph {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut EditorState, _env: &Env) {
+        let lerp = match self.lerp(data) {
+            Some(lerp) => lerp.clone(),
+            None => return,
+        };
+        let rect = RetimeGraph::graph_rect(ctx.size());
+        match event {
+            Event::MouseDown(ev) if ev.button.is_left() => {
+                let hit = self.keyframe_at(&lerp, rect, ev.pos);
+                match (hit, ev.count) {
+                    (Some(idx), 2) => self.set_lerp(data, lerp.without_keyframe(idx)),
+                    (Some(idx), _) => {
+                        self.frozen_range = Some(self.time_range(&lerp));
+                        self.dragging = Some(idx);
+                        ctx.set_active(true);
+                    }
+                    (None, 2) => {
+                        let original = RetimeGraph::y_to_original(&lerp, rect, ev.pos.y);
+                        self.set_lerp(data, lerp.with_keyframe(original));
+                    }
+                    (None, _) => {}
+                }
+                ctx.set_handled();
+            }
+            Event::MouseDown(ev) if ev.button.is_right() => {
+                if let Some(idx) = self.interval_at(&lerp, rect, ev.pos.x) {
+                    let current = lerp.easing(idx);
+                    let pos = Easing::ALL.iter().position(|&e| e == current).unwrap_or(0);
+                    let next = Easing::ALL[(pos + 1) % Easing::ALL.len()];
+                    self.set_lerp(data, lerp.with_easing(idx, next));
+                }
+                ctx.set_handled();
+            }
+            Event::MouseMove(ev) => {
+                if let Some(idx) = self.dragging {
+                    let time = self.x_to_time(&lerp, rect, ev.pos.x);
+                    self.set_lerp(data, lerp.with_moved_keyframe(idx, time));
+                } else {
+                    let hot = self.keyframe_at(&lerp, rect, ev.pos);
+                    if hot != self.hot {
+                        self.hot = hot;
+                        ctx.request_paint();
+                    }
+                }
+            }
+            Event::MouseUp(_) if ctx.is_active() => {
+                ctx.set_active(false);
+                self.dragging = None;
+                self.frozen_range = None;
+                ctx.request_paint();
+                ctx.set_handled();
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(&mut self, _: &mut LifeCycleCtx, _: &LifeCycle, _: &EditorState, _: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &EditorState, data: &EditorState, _: &Env) {
+        if !old_data.scribl.draw.same(&data.scribl.draw) || old_data.time() != data.time() {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(&mut self, _: &mut LayoutCtx, bc: &BoxConstraints, _: &EditorState, _: &Env) -> Size {
+        bc.constrain(GRAPH_SIZE)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &EditorState, _env: &Env) {
+        let size = ctx.size();
+        ctx.fill(size.to_rect(), &BACKGROUND_COLOR);
+        let rect = RetimeGraph::graph_rect(size);
+        ctx.stroke(rect, &BORDER_COLOR, 1.0);
+        let lerp = match self.lerp(data) {
+            Some(lerp) => lerp,
+            None => return,
+        };
+
+        // For comparison, the straight line is what the drawing would look like if it were
+        // drawn at a constant speed.
+        let keyframes: Vec<_> = lerp
+            .keyframes()
+            .map(|(orig, lerped)| self.to_point(lerp, rect, orig, lerped))
+            .collect();
+        let (first, last) = (keyframes[0], keyframes[keyframes.len() - 1]);
+        ctx.stroke(Line::new(first, last), &REFERENCE_COLOR, 1.0);
+
+        let mut curve = BezPath::new();
+        curve.move_to(first);
+        let originals: Vec<_> = lerp.keyframes().map(|(orig, _)| orig).collect();
+        for (i, w) in keyframes.windows(2).enumerate() {
+            let easing = lerp.easing(i);
+            if easing == Easing::Linear {
+                curve.line_to(w[1]);
+                continue;
+            }
+            // The y coordinate is the original time, which is the eased fraction of the interval.
+            let (orig_start, orig_end) = (originals[i], originals[i + 1]);
+            let y_range = self.to_point(lerp, rect, orig_end, Time::ZERO).y
+                - self.to_point(lerp, rect, orig_start, Time::ZERO).y;
+            for step in 1..=CURVE_STEPS {
+                let frac = step as f64 / CURVE_STEPS as f64;
+                let x = w[0].x + frac * (w[1].x - w[0].x);
+                let y = w[0].y + lerp.eased_fraction(i, frac) * y_range;
+                curve.line_to((x, y));
+            }
+        }
+        ctx.stroke(curve, &CURVE_COLOR, 2.0);
+
+        let (start, end) = self.time_range(lerp);
+        if start <= data.time() && data.time() <= end {
+            let x = self.to_point(lerp, rect, Time::ZERO, data.time()).x;
+            ctx.stroke(Line::new((x, rect.y0), (x, rect.y1)), &CURSOR_COLOR, 1.0);
+        }
+
+        for (i, &p) in keyframes.iter().enumerate() {
+            let hot = self.hot == Some(i) || self.dragging == Some(i);
+            let (radius, color) = if hot {
+                (HOT_KEYFRAME_RADIUS, &HOT_KEYFRAME_COLOR)
+            } else {
+                (KEYFRAME_RADIUS, &KEYFRAME_COLOR)
+            };
+            ctx.fill(Circle::new(p, radius), color);
+        }
+    }
+}
@@ -1,13 +1,17 @@
 use druid::piet::{FontFamily, PietText};
 use druid::widget::prelude::*;
-use druid::widget::{Align, Either, Flex, Label, ProgressBar, WidgetExt};
+use druid::widget::{Align, Either, Flex, Label, ProgressBar, Slider, WidgetExt};
 use druid::{lens, ArcStr, Color, Data, FontDescriptor, LensExt, Point, TextLayout};
 use std::borrow::Cow;
 use std::path::Path;
 
 use scribl_curves::Time;
 
-use crate::data::{AsyncOpsStatus, FinishedStatus};
+use scribl_widget::TooltipExt;
+
+use crate::data::{
+    AsyncOpsStatus, FinishedStatus, TimeDisplay, MAX_PLAYBACK_SPEED, MIN_PLAYBACK_SPEED,
+};
 use crate::EditorState;
 
 const LINE_HEIGHT_FACTOR: f64 = 1.2;
@@ -32,7 +36,16 @@ fn status_type(status: &AsyncOpsStatus) -> StatusType {
     } else if let Some(path) = &status.in_progress.saving {
         StatusType::Label(format!("Saving {}...", f_name(path)))
     } else if let Some(path) = &status.in_progress.loading {
-        StatusType::Label(format!("Loading {}...", f_name(path)))
+        let fraction = status.in_progress.loading_progress;
+        StatusType::Progress(
+            format!("Loading {} ({:.0}%): ", f_name(path), fraction * 100.0),
+            fraction,
+        )
+    } else if let Some(fraction) = status.in_progress.processing_audio {
+        StatusType::Progress(
+            format!("Processing audio ({:.0}%): ", fraction * 100.0),
+            fraction,
+        )
     } else if let Some(finished) = &status.last_finished {
         match finished {
             FinishedStatus::Saved { path, time: _ } => {
@@ -41,10 +54,18 @@ fn status_type(status: &AsyncOpsStatus) -> StatusType {
             FinishedStatus::Loaded { path, time: _ } => {
                 StatusType::Label(format!("Loaded {}", f_name(path)))
             }
-            FinishedStatus::Encoded { path, time: _ } => {
-                StatusType::Label(format!("Encoded {}", f_name(path)))
-            }
+            FinishedStatus::Encoded {
+                path,
+                time: _,
+                summary: None,
+            } => StatusType::Label(format!("Encoded {}", f_name(path))),
+            FinishedStatus::Encoded {
+                path,
+                time: _,
+                summary: Some(summary),
+            } => StatusType::Label(format!("Encoded {}: {}", f_name(path), summary)),
             FinishedStatus::Error(s) => StatusType::Label(format!("Error: {}", s)),
+            FinishedStatus::Notice(s) => StatusType::Label(s.clone()),
         }
     } else {
         StatusType::Label(String::new())
@@ -52,7 +73,10 @@ fn status_type(status: &AsyncOpsStatus) -> StatusType {
 }
 
 pub fn make_status_bar() -> impl Widget<EditorState> {
-    let time_label = Clock::new().lens(EditorState::time_lens);
+    let time_label = Clock::new().lens(lens::Identity.map(
+        |data: &EditorState| (data.time(), data.settings.time_display, data.fps()),
+        |data: &mut EditorState, clock: ClockData| data.settings.time_display = clock.1,
+    ));
 
     // TODO: label requests layout every time the string changes, which isn't necessary here.
     // Make a fixed-size label that doesn't re-layout itself.
@@ -92,16 +116,48 @@ pub fn make_status_bar() -> impl Widget<EditorState> {
     )
     .fix_width(250.0); // TODO: can we make this depend on the text width?
 
+    let snippet_label = Label::dynamic(|data: &EditorState, _env: &Env| {
+        data.selected_snippet
+            .filter(|&id| data.has_snippet(id))
+            .and_then(|id| data.snippet_name(id))
+            .unwrap_or("")
+            .to_owned()
+    });
+
+    let speed = Flex::row()
+        .with_child(Label::dynamic(|speed: &f64, _env: &Env| {
+            format!("{:.2}x", speed)
+        }))
+        .with_child(
+            Slider::new()
+                .with_range(MIN_PLAYBACK_SPEED, MAX_PLAYBACK_SPEED)
+                .fix_width(100.0),
+        )
+        .tooltip("Playback speed")
+        .lens(lens::Identity.map(
+            |data: &EditorState| data.settings.playback_speed,
+            |data: &mut EditorState, speed: f64| data.set_playback_speed(speed),
+        ));
+
     let row = Flex::row()
         .with_child(time_label)
+        .with_spacer(10.0)
+        .with_child(snippet_label)
         .with_flex_spacer(1.0)
+        .with_child(speed)
+        .with_spacer(10.0)
         .with_child(status_label.lens(EditorState::status))
         .background(druid::theme::BACKGROUND_LIGHT);
     Align::centered(row)
 }
 
+/// The clock needs to know the current time, how to show it, and the frame rate (for showing the
+/// time as a frame number).
+type ClockData = (Time, TimeDisplay, f64);
+
 // This is basically a Label, but with a fixed width: Label calls `request_layout` every time its
-// text changes, which is too much for this purpose.
+// text changes, which is too much for this purpose. Clicking on it switches between showing the
+// time and showing the frame number.
 struct Clock {
     text: TextLayout<ArcStr>,
     // Does the layout need to be changed?
@@ -116,15 +172,21 @@ impl Clock {
         }
     }
 
-    fn make_layout_if_needed(&mut self, time: Time, t: &mut PietText, env: &Env) {
+    fn make_layout_if_needed(&mut self, data: &ClockData, t: &mut PietText, env: &Env) {
         if self.needs_update {
             let font_size = env.get(druid::theme::TEXT_SIZE_NORMAL);
-            let usecs = time.as_micros();
-            let mins = usecs / 60_000_000;
-            let secs = (usecs / 1_000_000) % 60;
-            let cents = (usecs / 10_000) % 100;
-            self.text
-                .set_text(format!("{:02}:{:02}.{:02}", mins, secs, cents).into());
+            let (time, display, fps) = *data;
+            let text = match display {
+                TimeDisplay::Clock => {
+                    let usecs = time.as_micros();
+                    let mins = usecs / 60_000_000;
+                    let secs = (usecs / 1_000_000) % 60;
+                    let millis = (usecs / 1_000) % 1000;
+                    format!("{:02}:{:02}.{:03}", mins, secs, millis)
+                }
+                TimeDisplay::Frames => format!("frame {:6}", time.as_video_frame(fps)),
+            };
+            self.text.set_text(text.into());
             self.text
                 .set_font(FontDescriptor::new(FontFamily::MONOSPACE).with_size(font_size));
             self.text.set_text_color(Color::WHITE);
@@ -135,28 +197,45 @@ impl Clock {
     }
 }
 
-impl Widget<Time> for Clock {
-    fn event(&mut self, _: &mut EventCtx, _: &Event, _: &mut Time, _: &Env) {}
+impl Widget<ClockData> for Clock {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut ClockData, _: &Env) {
+        if let Event::MouseDown(ev) = event {
+            if ev.button.is_left() {
+                data.1 = data.1.toggled();
+                ctx.set_handled();
+            }
+        }
+    }
 
-    fn lifecycle(&mut self, _: &mut LifeCycleCtx, _: &LifeCycle, _: &Time, _: &Env) {}
+    fn lifecycle(&mut self, _: &mut LifeCycleCtx, _: &LifeCycle, _: &ClockData, _: &Env) {}
 
-    fn update(&mut self, ctx: &mut UpdateCtx, _: &Time, _: &Time, _: &Env) {
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &ClockData, data: &ClockData, _: &Env) {
         // TODO: update on env changes also
         self.needs_update = true;
+        if old_data.1 != data.1 {
+            // The two display formats have different widths.
+            ctx.request_layout();
+        }
         ctx.request_paint();
     }
 
-    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, time: &Time, env: &Env) -> Size {
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &ClockData,
+        env: &Env,
+    ) -> Size {
         let font_size = env.get(druid::theme::TEXT_SIZE_NORMAL);
-        self.make_layout_if_needed(*time, &mut ctx.text(), env);
+        self.make_layout_if_needed(data, &mut ctx.text(), env);
         bc.constrain((
             self.text.size().width + 2.0 * X_PADDING,
             font_size * LINE_HEIGHT_FACTOR,
         ))
     }
 
-    fn paint(&mut self, ctx: &mut PaintCtx, data: &Time, env: &Env) {
-        self.make_layout_if_needed(*data, &mut ctx.text(), env);
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &ClockData, env: &Env) {
+        self.make_layout_if_needed(data, &mut ctx.text(), env);
         let origin = Point::new(X_PADDING, 0.0);
         self.text.draw(ctx, origin);
     }
@@ -1,22 +1,43 @@
-use druid::kurbo::{BezPath, Line, Shape, Vec2};
-use druid::piet::StrokeStyle;
+use anyhow::{anyhow, Result};
+use druid::kurbo::{BezPath, Circle, Line, Shape, Vec2};
+use druid::piet::{
+    Device, FontFamily, ImageFormat, InterpolationMode, PietImage, StrokeStyle, Text,
+    TextLayoutBuilder,
+};
 use druid::widget::ClipBox;
 use druid::{
-    Affine, BoxConstraints, Color, Data, Env, Event, EventCtx, KbKey, LayoutCtx, LifeCycle,
-    LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Widget, WidgetPod,
+    Affine, BoxConstraints, Color, Data, Env, Event, EventCtx, ExtEventSink, ImageBuf, KbKey,
+    LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size, Target,
+    UpdateCtx, Widget, WidgetId, WidgetPod, WindowHandle,
 };
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Arc;
 
 use scribl_curves::{DrawSnippet, DrawSnippets, Time, TimeDiff};
 use scribl_widget::SunkenContainer;
 
-use crate::audio::{TalkSnippet, TalkSnippets};
+use crate::audio::{Spectrogram, TalkSnippet, TalkSnippetId, TalkSnippets, MAX_GAIN};
+use crate::config::{RowLayout, ScrollMode};
+use crate::data::RowHints;
 use crate::snippet_layout::{self, SnippetShape};
+use crate::undo::UndoState;
+use crate::widgets::minimap::{Minimap, MINIMAP_HEIGHT};
 use crate::{cmd, EditorState, SnippetId};
 
 const PIXELS_PER_USEC: f64 = 40.0 / 1000000.0;
+/// When drawing the whole timeline as an image, we break it into rows that are this wide (one
+/// minute, at the usual scale).
+const SUMMARY_ROW_WIDTH: f64 = 2400.0;
+/// The vertical space between rows of the timeline image.
+const SUMMARY_ROW_SPACING: f64 = 8.0;
 const CURSOR_THICKNESS: f64 = 2.0;
 const SELECTION_FILL_COLOR: Color = Color::rgba8(0xff, 0xff, 0xff, 0x20);
+/// The part of the timeline after the end of the animation is darkened with this.
+const PAST_END_FILL_COLOR: Color = Color::rgba8(0x00, 0x00, 0x00, 0x60);
+const END_LINE_COLOR: Color = Color::rgb8(0xe0, 0x50, 0x50);
 
 const AUDIO_SNIPPET_COLOR: Color = crate::UI_LIGHT_YELLOW;
 const AUDIO_SNIPPET_SELECTED_COLOR: Color = crate::UI_LIGHT_YELLOW;
@@ -24,9 +45,47 @@ const SNIPPET_STROKE_COLOR: Color = Color::rgb8(0x00, 0x00, 0x00);
 const SNIPPET_SELECTED_STROKE_COLOR: Color = Color::rgb8(0xff, 0xff, 0xff);
 const SNIPPET_STROKE_THICKNESS: f64 = 1.0;
 const SNIPPET_SELECTED_STROKE_THICKNESS: f64 = 3.0;
+const GROUP_STROKE_THICKNESS: f64 = 2.0;
 const SNIPPET_WAVEFORM_COLOR: Color = crate::UI_DARK_BLUE;
+// Holds (where a drawing stops advancing for a while) get darkened.
+const SNIPPET_HOLD_COLOR: Color = Color::rgba8(0x00, 0x00, 0x00, 0x60);
+// The audio waveform gets sampled once every this many device pixels.
+const WAVEFORM_SAMPLE_SPACING: f64 = 5.0;
+// Parts of audio snippets that overlap with other audio snippets get highlighted in this color.
+// The opacity of the highlight on overlapping audio. The color comes from the UI palette.
+const OVERLAP_ALPHA: f64 = 0.375;
+// Overlapping audio is also hatched, so that it doesn't rely on color alone.
+const OVERLAP_HATCH_SPACING: f64 = 6.0;
+const OVERLAP_HATCH_THICKNESS: f64 = 1.0;
+const GAIN_ENVELOPE_COLOR: Color = Color::rgb8(0xd0, 0x30, 0x30);
+const GAIN_ENVELOPE_THICKNESS: f64 = 1.5;
+const GAIN_HANDLE_RADIUS: f64 = 3.0;
+// How close (in pixels) the mouse needs to be to a gain handle in order to grab it.
+const GAIN_HANDLE_GRAB_DISTANCE: f64 = 6.0;
 
 const MIN_TIMELINE_HEIGHT: f64 = 100.0;
+/// The timeline doesn't get taller than this; if the snippets need more room, it scrolls.
+const MAX_TIMELINE_HEIGHT: f64 = 300.0;
+
+/// The drawing and audio snippets each have a header, which can be clicked to hide them.
+const SECTION_HEADER_HEIGHT: f64 = 14.0;
+const SECTION_HEADER_COLOR: Color = Color::rgb8(0x30, 0x30, 0x30);
+const SECTION_HEADER_TEXT_COLOR: Color = Color::rgb8(0xd0, 0xd0, 0xd0);
+const SECTION_HEADER_FONT_SIZE: f64 = 10.0;
+
+const RULER_HEIGHT: f64 = 16.0;
+const RULER_BACKGROUND_COLOR: Color = Color::rgb8(0x20, 0x20, 0x20);
+const RULER_TICK_COLOR: Color = Color::rgb8(0xa0, 0xa0, 0xa0);
+const RULER_TEXT_COLOR: Color = Color::rgb8(0xd0, 0xd0, 0xd0);
+const RULER_FONT_SIZE: f64 = 10.0;
+const MARKER_COLOR: Color = Color::rgb8(0xff, 0xa0, 0x20);
+const MARKER_FLAG_WIDTH: f64 = 6.0;
+/// The labelled ticks on the ruler are at least this far apart (in pixels).
+const RULER_MIN_LABEL_SPACING: f64 = 60.0;
+/// The possible intervals (in milliseconds) between labelled ticks on the ruler.
+const RULER_INTERVALS_MS: &[i64] = &[
+    100, 250, 500, 1_000, 2_000, 5_000, 10_000, 15_000, 30_000, 60_000, 120_000, 300_000, 600_000,
+];
 
 /// We don't allow the cursor to get closer to the edge of the window than this (unless it's at the
 /// very beginning). If the cursor gets closer than this, we scroll the timeline to get it within
@@ -67,6 +126,30 @@ fn x_pix(p: f64) -> Time {
     Time::from_micros((p / PIXELS_PER_USEC) as i64)
 }
 
+/// Chooses how far apart the labelled ticks on the ruler should be, given the current scale.
+fn ruler_interval() -> TimeDiff {
+    let ms = RULER_INTERVALS_MS
+        .iter()
+        .cloned()
+        .find(|&ms| pix_width(TimeDiff::from_micros(ms * 1000)) >= RULER_MIN_LABEL_SPACING)
+        .unwrap_or(*RULER_INTERVALS_MS.last().unwrap());
+    TimeDiff::from_micros(ms * 1000)
+}
+
+/// Formats a time for the ruler, as minutes and seconds. If the ticks are closer together than a
+/// second, we also show tenths of a second.
+fn format_ruler_time(t: Time, interval: TimeDiff) -> String {
+    let usecs = t.as_micros();
+    let mins = usecs / 60_000_000;
+    let secs = (usecs / 1_000_000) % 60;
+    if interval < TimeDiff::from_micros(1_000_000) {
+        let tenths = (usecs / 100_000) % 10;
+        format!("{}:{:02}.{}", mins, secs, tenths)
+    } else {
+        format!("{}:{:02}", mins, secs)
+    }
+}
+
 /// The cached "waveform" of an audio snippet.
 struct AudioWaveform {
     // The shape of the waveform.
@@ -91,7 +174,13 @@ enum Snip {
 }
 
 impl AudioWaveform {
-    fn new(data: TalkSnippet, shape: &crate::snippet_layout::SnippetShape) -> AudioWaveform {
+    /// Creates the waveform of an audio snippet. `scale` is the number of device pixels per
+    /// display point: on high-DPI screens, we sample the waveform more finely.
+    fn new(
+        data: TalkSnippet,
+        shape: &crate::snippet_layout::SnippetShape,
+        scale: f64,
+    ) -> AudioWaveform {
         if shape.rects.is_empty() {
             return AudioWaveform {
                 wave: BezPath::new(),
@@ -106,26 +195,27 @@ impl AudioWaveform {
                 .min(1.0)
         };
 
-        let pix_per_sample = 5;
-        let buf = data.buf();
+        let pix_per_sample = WAVEFORM_SAMPLE_SPACING / scale;
+        let buf = data.samples();
         let mut path_back = Vec::new();
         let mut path = BezPath::new();
         let x0 = shape.rects[0].x0;
         path.move_to((0.0, shape.rects[0].center().y));
         for (i, r) in shape.rects.iter().enumerate() {
             let start = if i == 0 {
-                0
+                0.0
             } else {
-                (LAYOUT_PARAMS.overlap / 2.0) as usize
+                (LAYOUT_PARAMS.overlap / 2.0).floor()
             };
             let width = if i + 1 == shape.rects.len() {
-                r.width() as usize
+                r.width().floor()
             } else {
-                (r.width() - LAYOUT_PARAMS.overlap / 2.0) as usize
+                (r.width() - LAYOUT_PARAMS.overlap / 2.0).floor()
             };
-            for p in (start..width).step_by(pix_per_sample) {
-                let start_time = x_pix(p as f64 + r.x0 - x0) - Time::ZERO;
-                let end_time = x_pix((p + pix_per_sample) as f64 + r.x0 - x0) - Time::ZERO;
+            let mut p = start;
+            while p < width {
+                let start_time = x_pix(p + r.x0 - x0) - Time::ZERO;
+                let end_time = x_pix(p + pix_per_sample + r.x0 - x0) - Time::ZERO;
                 let start_idx =
                     (start_time.as_audio_idx(crate::audio::SAMPLE_RATE) as usize).min(buf.len());
                 let end_idx =
@@ -136,10 +226,12 @@ impl AudioWaveform {
                     - sub_buf.iter().cloned().min().unwrap_or(0) as f64)
                     / 2.0;
 
-                let x = p as f64 + r.x0 - x0;
-                let dy = audio_height(mag) / 2.0 * r.height();
+                let x = p + r.x0 - x0;
+                let gain = data.gain().gain_at(start_time) as f64;
+                let dy = audio_height(mag * gain) / 2.0 * r.height();
                 path.line_to((x, r.center().y + dy));
                 path_back.push((x, r.center().y - dy));
+                p += pix_per_sample;
             }
         }
 
@@ -205,10 +297,13 @@ impl Snip {
 
 /// The "outer" timeline widget.
 ///
-/// This basically just contains the scroll area, which contains the inner widget.
+/// This basically just contains the scroll area, which contains the inner widget. Above the
+/// scroll area there's a minimap, which shows an overview of the whole animation, and a ruler
+/// showing the times in the visible part of the timeline.
 pub struct Timeline {
     inner:
         WidgetPod<EditorState, SunkenContainer<EditorState, ClipBox<EditorState, TimelineInner>>>,
+    minimap: WidgetPod<EditorState, Minimap>,
 }
 
 /// The main timeline widget.
@@ -217,10 +312,79 @@ struct TimelineInner {
     /// scroll region's offset; this is handled by the outer Timeline widget.
     visible_times: (Time, Time),
     height: f64,
+    /// The vertical positions of the tops of the "Drawings" and "Audio" headers.
+    header_ys: (f64, f64),
     /// If the cursor is being dragged to near the edge of the timeline, this is how fast we should
     /// scroll in response.
     cursor_drag_scroll_speed: Option<f64>,
     children: HashMap<SnippetId, WidgetPod<EditorState, TimelineSnippet>>,
+    /// If they're dragging a point on the volume envelope of an audio snippet, this is it.
+    gain_drag: Option<GainDrag>,
+    /// If they're sliding the audio within an audio snippet, this is it.
+    slip_drag: Option<SlipDrag>,
+    /// The number of device pixels per display point, which affects how the waveforms are drawn.
+    scale: f64,
+    /// Spectrograms of the audio snippets. These get computed in the background, and only if
+    /// they're being shown.
+    spectrograms: HashMap<TalkSnippetId, CachedSpectrogram>,
+    /// Our id, so that the background thread can send us spectrograms.
+    id: WidgetId,
+}
+
+/// The spectrogram of an audio snippet, or a placeholder for one that's being computed.
+struct CachedSpectrogram {
+    /// Identifies the audio that the spectrogram belongs to, so that we know when it's stale.
+    key: (usize, i64),
+    spectrogram: Option<Arc<Spectrogram>>,
+}
+
+/// Identifies the audio in a snippet, for deciding whether a spectrogram is stale.
+fn spectrogram_key(snip: &TalkSnippet) -> (usize, i64) {
+    (snip.buf().as_ptr() as usize, snip.offset().as_micros())
+}
+
+/// The state of a drag on a point of an audio snippet's volume envelope.
+struct GainDrag {
+    id: TalkSnippetId,
+    // The index of the point being dragged.
+    idx: usize,
+    // The undo state from before the drag started. We only add an undo state when the drag ends.
+    prev_state: UndoState,
+}
+
+/// The state of an alt-drag that slides the audio within an audio snippet, without moving the
+/// snippet itself.
+struct SlipDrag {
+    id: TalkSnippetId,
+    // The time under the mouse when the drag started.
+    start_time: Time,
+    // The snippet's offset when the drag started.
+    start_offset: TimeDiff,
+    // The undo state from before the drag started. We only add an undo state when the drag ends.
+    prev_state: UndoState,
+}
+
+/// The number of device pixels per display point.
+fn device_scale(window: &WindowHandle) -> f64 {
+    window.get_scale().map(|s| s.x()).unwrap_or(1.0)
+}
+
+/// Rounds a line width (in display points) to a whole number of device pixels, but at least one.
+/// Lines that cover fractions of a pixel get blurred, which happens at non-integer scale factors.
+fn snap_width(width: f64, scale: f64) -> f64 {
+    (width * scale).round().max(1.0) / scale
+}
+
+/// Diagonal lines covering a rectangle (and sticking out of it, so they need to be clipped).
+fn hatch(rect: Rect) -> BezPath {
+    let mut ret = BezPath::new();
+    let mut x = rect.x0 - rect.height();
+    while x < rect.x1 {
+        ret.move_to((x, rect.y1));
+        ret.line_to((x + rect.height(), rect.y0));
+        x += OVERLAP_HATCH_SPACING;
+    }
+    ret
 }
 
 impl Timeline {
@@ -228,9 +392,11 @@ impl Timeline {
         let inner = TimelineInner::default();
         let clip = ClipBox::managed(inner)
             .constrain_horizontal(false)
-            .constrain_vertical(true);
+            .constrain_vertical(false)
+            .content_must_fill(true);
         Timeline {
             inner: WidgetPod::new(SunkenContainer::new(clip)),
+            minimap: WidgetPod::new(Minimap::new()),
         }
     }
 
@@ -242,25 +408,165 @@ impl Timeline {
         self.inner.widget_mut().child_mut()
     }
 
+    /// Tells the inner timeline and the minimap which times are visible. Returns true if the
+    /// minimap needs to be repainted.
     // TODO: druid now has a mechanism for this (SCROLL_TO_VIEW). Use it.
-    fn update_visible_times(&mut self, size: Size) {
+    fn update_visible_times(&mut self, size: Size) -> bool {
+        let offset = self.clip_box().viewport_origin().x;
+        let start = x_pix(offset);
+        let end = x_pix(offset + size.width);
+        self.clip_box_mut().child_mut().set_visible(start, end);
+        self.minimap.widget_mut().set_visible(start, end)
+    }
+
+    /// If a snippet is completely scrolled out of view, scrolls so that it's visible. Returns
+    /// true if we scrolled.
+    fn scroll_to_snippet(&mut self, id: SnippetId) -> bool {
+        let bbox = match self.clip_box().child().snippet_bbox(id) {
+            Some(bbox) => bbox,
+            None => return false,
+        };
+        let view = Rect::from_origin_size(
+            self.clip_box().viewport_origin(),
+            self.inner.layout_rect().size(),
+        );
+        let delta_x = if bbox.x1 < view.x0 || bbox.x0 > view.x1 {
+            // Put the start of the snippet in the middle.
+            bbox.x0 - view.width() / 2.0 - view.x0
+        } else {
+            0.0
+        };
+        let delta_y = if bbox.y1 < view.y0 {
+            bbox.y0 - view.y0
+        } else if bbox.y0 > view.y1 {
+            bbox.y1 - view.y1
+        } else {
+            0.0
+        };
+        if delta_x != 0.0 || delta_y != 0.0 {
+            self.clip_box_mut().pan_by(Vec2::new(delta_x, delta_y));
+            true
+        } else {
+            false
+        }
+    }
+
+    fn ruler_rect(&self, width: f64) -> Rect {
+        Rect::new(0.0, MINIMAP_HEIGHT, width, MINIMAP_HEIGHT + RULER_HEIGHT)
+    }
+
+    fn paint_ruler(&self, ctx: &mut PaintCtx, data: &EditorState) {
+        let rect = self.ruler_rect(ctx.size().width);
+        let line_width = snap_width(1.0, device_scale(ctx.window()));
         let offset = self.clip_box().viewport_origin().x;
-        self.clip_box_mut()
-            .child_mut()
-            .set_visible(x_pix(offset), x_pix(offset + size.width));
+        paint_ruler(ctx.render_ctx, data, rect, offset, line_width);
     }
 }
 
+/// Draws the ruler (with the time labels and the markers) in `rect`, which is assumed to start at
+/// x-position zero. `offset` is the x-position on the timeline that corresponds to zero.
+fn paint_ruler(
+    ctx: &mut impl RenderContext,
+    data: &EditorState,
+    rect: Rect,
+    offset: f64,
+    line_width: f64,
+) {
+    let start = x_pix(offset);
+    let end = x_pix(offset + rect.width());
+    let interval = ruler_interval();
+    let minor_interval = TimeDiff::from_micros(interval.as_micros() / 5);
+
+    let _ = ctx.with_save(|ctx| {
+        ctx.clip(rect);
+        ctx.fill(rect, &RULER_BACKGROUND_COLOR);
+
+        // Start from the last minor tick before the visible region.
+        let minor = minor_interval.as_micros();
+        let mut t = start.as_micros() / minor * minor;
+        while t <= end.as_micros() {
+            let time = Time::from_micros(t);
+            let x = pix_x(time) - offset;
+            let is_major = t % interval.as_micros() == 0;
+            let tick_height = if is_major {
+                RULER_HEIGHT
+            } else {
+                RULER_HEIGHT / 4.0
+            };
+            let line = Line::new((x, rect.y1 - tick_height), (x, rect.y1));
+            ctx.stroke(line, &RULER_TICK_COLOR, line_width);
+
+            if is_major {
+                let layout = ctx
+                    .text()
+                    .new_text_layout(format_ruler_time(time, interval))
+                    .font(FontFamily::SYSTEM_UI, RULER_FONT_SIZE)
+                    .text_color(RULER_TEXT_COLOR)
+                    .build();
+                if let Ok(layout) = layout {
+                    ctx.draw_text(&layout, (x + 3.0, rect.y0));
+                }
+            }
+            t += minor;
+        }
+
+        // Draw a little flag for each marker, with its name next to it. We start a bit before
+        // the visible region so that the names of markers just off the left edge are visible.
+        let label_start = start - width_pix(RULER_MIN_LABEL_SPACING * 2.0);
+        for (time, name) in data.scribl.markers.iter() {
+            if time < label_start || time > end {
+                continue;
+            }
+            let x = pix_x(time) - offset;
+            let line = Line::new((x, rect.y0), (x, rect.y1));
+            ctx.stroke(line, &MARKER_COLOR, line_width);
+            let mut flag = BezPath::new();
+            flag.move_to((x, rect.y0));
+            flag.line_to((x + MARKER_FLAG_WIDTH, rect.y0 + RULER_HEIGHT / 4.0));
+            flag.line_to((x, rect.y0 + RULER_HEIGHT / 2.0));
+            flag.close_path();
+            ctx.fill(flag, &MARKER_COLOR);
+
+            let layout = ctx
+                .text()
+                .new_text_layout(name.to_owned())
+                .font(FontFamily::SYSTEM_UI, RULER_FONT_SIZE)
+                .text_color(MARKER_COLOR)
+                .build();
+            if let Ok(layout) = layout {
+                ctx.draw_text(&layout, (x + MARKER_FLAG_WIDTH + 2.0, rect.y0));
+            }
+        }
+        Ok(())
+    });
+}
+
 impl Widget<EditorState> for Timeline {
     fn event(&mut self, ctx: &mut EventCtx, ev: &Event, data: &mut EditorState, env: &Env) {
         if let Event::Wheel(wheel_ev) = ev {
-            let delta = Vec2::new(wheel_ev.wheel_delta.x, 0.0);
+            let delta = wheel_ev.wheel_delta;
             self.clip_box_mut().pan_by(delta);
             ctx.request_paint();
             ctx.set_handled();
         }
+        if let Event::MouseDown(mouse_ev) = ev {
+            if self.ruler_rect(ctx.size().width).contains(mouse_ev.pos) && data.action.is_idle() {
+                let offset = self.clip_box().viewport_origin().x;
+                ctx.submit_command(cmd::WARP_TIME_TO.with(x_pix(mouse_ev.pos.x + offset)));
+                ctx.set_handled();
+            }
+        }
+        self.minimap.event(ctx, ev, data, env);
+        if let Some(time) = self.minimap.widget_mut().take_scroll_request() {
+            let origin = self.clip_box().viewport_origin().x;
+            let delta_x = pix_x(time) - ctx.size().width / 2.0 - origin;
+            self.clip_box_mut().pan_by(Vec2::new(delta_x, 0.0));
+            ctx.request_paint();
+        }
         self.inner.event(ctx, ev, data, env);
-        self.update_visible_times(ctx.size());
+        if self.update_visible_times(ctx.size()) {
+            ctx.request_paint();
+        }
     }
 
     fn update(
@@ -281,12 +587,28 @@ impl Widget<EditorState> for Timeline {
             // Scroll this much past the cursor, so it isn't right at the edge.
             let padding = CURSOR_BOUNDARY_PADDING.min(width_pix(size.width / 4.0));
 
-            let delta_x = if time + padding > max_vis_time {
-                pix_width(time - max_vis_time + padding)
-            } else if time - padding < min_vis_time {
-                pix_width(time - min_vis_time - padding)
-            } else {
-                0.0
+            let delta_x = match data.settings.scroll_mode {
+                ScrollMode::Follow => {
+                    if time + padding > max_vis_time {
+                        pix_width(time - max_vis_time + padding)
+                    } else if time - padding < min_vis_time {
+                        pix_width(time - min_vis_time - padding)
+                    } else {
+                        0.0
+                    }
+                }
+                // When the cursor goes off the edge, flip the page so that the cursor is near the
+                // opposite edge.
+                ScrollMode::Page => {
+                    if time > max_vis_time {
+                        pix_width(time - min_vis_time - padding)
+                    } else if time < min_vis_time {
+                        pix_width(time - max_vis_time + padding)
+                    } else {
+                        0.0
+                    }
+                }
+                ScrollMode::Off => 0.0,
             };
 
             if delta_x != 0.0 {
@@ -294,13 +616,32 @@ impl Widget<EditorState> for Timeline {
                 ctx.request_paint();
             }
         }
+        if !old_data.scribl.markers.same(&data.scribl.markers) {
+            ctx.request_paint();
+        }
+        self.minimap.update(ctx, data, env);
         self.inner.update(ctx, data, env);
-        self.update_visible_times(ctx.size());
+        // The selection can change from outside the timeline (like by clicking on a stroke in the
+        // drawing pane), so make sure the selected snippet can be seen. This happens after
+        // updating the inner timeline, because that's where the snippet positions get updated.
+        if data.selected_snippet != old_data.selected_snippet {
+            if let Some(id) = data.selected_snippet {
+                if self.scroll_to_snippet(id) {
+                    ctx.request_paint();
+                }
+            }
+        }
+        if self.update_visible_times(ctx.size()) {
+            ctx.request_paint();
+        }
     }
 
     fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, ev: &LifeCycle, data: &EditorState, env: &Env) {
+        self.minimap.lifecycle(ctx, ev, data, env);
         self.inner.lifecycle(ctx, ev, data, env);
-        self.update_visible_times(ctx.size());
+        if self.update_visible_times(ctx.size()) {
+            ctx.request_paint();
+        }
     }
 
     fn layout(
@@ -310,13 +651,35 @@ impl Widget<EditorState> for Timeline {
         data: &EditorState,
         env: &Env,
     ) -> Size {
-        let child_size = self.inner.layout(ctx, bc, data, env);
-        self.inner
-            .set_layout_rect(ctx, data, env, child_size.to_rect());
-        child_size
+        let minimap_bc = BoxConstraints::new(
+            Size::new(bc.min().width, MINIMAP_HEIGHT),
+            Size::new(bc.max().width, MINIMAP_HEIGHT),
+        );
+        let minimap_size = self.minimap.layout(ctx, &minimap_bc, data, env);
+        self.minimap
+            .set_layout_rect(ctx, data, env, minimap_size.to_rect());
+
+        let header_height = MINIMAP_HEIGHT + RULER_HEIGHT;
+        let child_bc = bc.shrink((0.0, header_height));
+        let max_height = child_bc.max().height.min(MAX_TIMELINE_HEIGHT);
+        let child_bc = BoxConstraints::new(
+            Size::new(child_bc.min().width, child_bc.min().height.min(max_height)),
+            Size::new(child_bc.max().width, max_height),
+        );
+        let child_size = self.inner.layout(ctx, &child_bc, data, env);
+        let child_origin = Point::new(0.0, header_height);
+        self.inner.set_layout_rect(
+            ctx,
+            data,
+            env,
+            Rect::from_origin_size(child_origin, child_size),
+        );
+        Size::new(child_size.width, child_size.height + header_height)
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &EditorState, env: &Env) {
+        self.minimap.paint(ctx, data, env);
+        self.paint_ruler(ctx, data);
         self.inner.paint(ctx, data, env);
     }
 }
@@ -326,21 +689,76 @@ impl Default for TimelineInner {
         TimelineInner {
             visible_times: (Time::ZERO, Time::ZERO),
             height: MIN_TIMELINE_HEIGHT,
+            header_ys: (0.0, MIN_TIMELINE_HEIGHT - SECTION_HEADER_HEIGHT),
             cursor_drag_scroll_speed: None,
             children: HashMap::new(),
+            gain_drag: None,
+            slip_drag: None,
+            scale: 1.0,
+            spectrograms: HashMap::new(),
+            id: WidgetId::next(),
         }
     }
 }
 
 impl TimelineInner {
-    // Recreates the child widgets, and organizes them into rows so that they don't overlap.
-    fn recreate_children(&mut self, snippets: &DrawSnippets, audio: &TalkSnippets) {
-        let draw_shapes = snippet_layout::layout(snippets.snippets(), &LAYOUT_PARAMS);
-        let audio_shapes = snippet_layout::layout(audio.snippets(), &LAYOUT_PARAMS);
-        self.height = (draw_shapes.max_y + audio_shapes.max_y).max(MIN_TIMELINE_HEIGHT);
+    // Recreates the child widgets, and organizes them into rows so that they don't overlap. In the
+    // stable layout, returns the rows that the snippets ended up in.
+    fn recreate_children(&mut self, data: &EditorState) -> Option<RowHints> {
+        let snippets = &data.scribl.draw;
+        let audio = &data.scribl.talk;
+        let (draw_shapes, audio_shapes, rows) = match data.settings.row_layout {
+            RowLayout::Compact => (
+                snippet_layout::layout(snippets.snippets(), &LAYOUT_PARAMS),
+                snippet_layout::layout(audio.snippets(), &LAYOUT_PARAMS),
+                None,
+            ),
+            RowLayout::Stable => {
+                let hints = &data.scribl.row_hints;
+                let draw_shapes = snippet_layout::stable_layout(
+                    snippets.snippets(),
+                    |id| hints.draw_row(id),
+                    &LAYOUT_PARAMS,
+                );
+                let audio_shapes = snippet_layout::stable_layout(
+                    audio.snippets(),
+                    |id| hints.talk_row(id),
+                    &LAYOUT_PARAMS,
+                );
+                let rows = RowHints::new(
+                    draw_shapes.rows.iter().map(|(&id, &row)| (id, row)),
+                    audio_shapes.rows.iter().map(|(&id, &row)| (id, row)),
+                );
+                (draw_shapes, audio_shapes, Some(rows))
+            }
+        };
+        let draw_height = if data.settings.drawings_collapsed {
+            0.0
+        } else {
+            draw_shapes.max_y
+        };
+        let audio_height = if data.settings.audio_collapsed {
+            0.0
+        } else {
+            audio_shapes.max_y
+        };
+        self.height =
+            (draw_height + audio_height + 2.0 * SECTION_HEADER_HEIGHT).max(MIN_TIMELINE_HEIGHT);
+        self.header_ys = (0.0, self.height - audio_height - SECTION_HEADER_HEIGHT);
 
         self.children.clear();
-        for (id, shape) in draw_shapes.positions {
+        let draw_positions = if data.settings.drawings_collapsed {
+            HashMap::new()
+        } else {
+            draw_shapes.positions
+        };
+        let audio_positions = if data.settings.audio_collapsed {
+            HashMap::new()
+        } else {
+            audio_shapes.positions
+        };
+        for (id, mut shape) in draw_positions {
+            shape.translate_y(SECTION_HEADER_HEIGHT);
             let snip = snippets.snippet(id);
             let id = SnippetId::Draw(id);
             let interior = SnippetInterior::Drawing(DrawingWaveform::new(&snip));
@@ -356,15 +774,23 @@ impl TimelineInner {
                     drag_shift: None,
                     shape,
                     interior,
+                    spectrogram: None,
+                    spectrogram_image: None,
                 }),
             );
         }
-        for (id, mut shape) in audio_shapes.positions {
+        for (talk_id, mut shape) in audio_positions {
             shape.reflect_y(self.height);
-            let audio_data = audio.snippet(id);
-            let id = SnippetId::Talk(id);
-            let interior = SnippetInterior::Audio(AudioWaveform::new(audio_data.clone(), &shape));
+            let audio_data = audio.snippet(talk_id);
+            let id = SnippetId::Talk(talk_id);
+            let waveform = AudioWaveform::new(audio_data.clone(), &shape, self.scale);
+            let interior = SnippetInterior::Audio(waveform);
             let path = shape.to_path(LAYOUT_PARAMS.overlap);
+            let spectrogram = self
+                .spectrograms
+                .get(&talk_id)
+                .filter(|s| s.key == spectrogram_key(audio_data))
+                .and_then(|s| s.spectrogram.clone());
             self.children.insert(
                 id,
                 WidgetPod::new(TimelineSnippet {
@@ -376,9 +802,107 @@ impl TimelineInner {
                     drag_shift: None,
                     shape: shape.clone(),
                     interior,
+                    spectrogram,
+                    spectrogram_image: None,
                 }),
             );
         }
+        rows
+    }
+
+    /// The header of a section, as it appears in the visible part of the timeline.
+    fn header_rect(&self, y: f64) -> Rect {
+        Rect::new(
+            pix_x(self.visible_times.0),
+            y,
+            pix_x(self.visible_times.1),
+            y + SECTION_HEADER_HEIGHT,
+        )
+    }
+
+    fn paint_header(
+        &self,
+        ctx: &mut PaintCtx,
+        y: f64,
+        title: &str,
+        snippet_count: usize,
+        collapsed: bool,
+    ) {
+        let rect = self.header_rect(y);
+        if !ctx.region().intersects(rect) {
+            return;
+        }
+        ctx.fill(rect, &SECTION_HEADER_COLOR);
+        let text = if collapsed {
+            format!("\u{25b8} {} ({} hidden)", title, snippet_count)
+        } else {
+            format!("\u{25be} {}", title)
+        };
+        let layout = ctx
+            .text()
+            .new_text_layout(text)
+            .font(FontFamily::SYSTEM_UI, SECTION_HEADER_FONT_SIZE)
+            .text_color(SECTION_HEADER_TEXT_COLOR)
+            .build();
+        if let Ok(layout) = layout {
+            ctx.draw_text(&layout, (rect.x0 + 4.0, rect.y0));
+        }
+    }
+
+    /// If spectrograms are being shown, starts computing the ones that we don't have yet.
+    fn request_spectrograms(&mut self, data: &EditorState, ext_cmd: ExtEventSink) {
+        if !data.settings.spectrograms {
+            return;
+        }
+
+        // Forget about the spectrograms of audio that isn't there anymore.
+        let keys: HashMap<TalkSnippetId, (usize, i64)> = data
+            .scribl
+            .talk
+            .snippets()
+            .map(|(id, snip)| (id, spectrogram_key(snip)))
+            .collect();
+        self.spectrograms
+            .retain(|id, s| keys.get(id) == Some(&s.key));
+
+        let mut todo = Vec::new();
+        for (id, snip) in data.scribl.talk.snippets() {
+            if !self.spectrograms.contains_key(&id) {
+                let key = spectrogram_key(snip);
+                self.spectrograms.insert(
+                    id,
+                    CachedSpectrogram {
+                        key,
+                        spectrogram: None,
+                    },
+                );
+                todo.push((id, key, snip.clone()));
+            }
+        }
+        if todo.is_empty() {
+            return;
+        }
+
+        let widget_id = self.id;
+        std::thread::spawn(move || {
+            // One column per pixel.
+            let step_secs = width_pix(1.0).as_micros() as f64 / 1e6;
+            for (id, key, snip) in todo {
+                let spectrogram = Arc::new(Spectrogram::compute(&snip.samples(), step_secs));
+                let msg = cmd::SpectrogramReady {
+                    id,
+                    key,
+                    spectrogram,
+                };
+                let target = Target::Widget(widget_id);
+                if ext_cmd
+                    .submit_command(cmd::SPECTROGRAM_READY, Box::new(msg), target)
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
     }
 
     fn invalid_rect(s: Time, t: Time, height: f64) -> Rect {
@@ -405,6 +929,10 @@ struct TimelineSnippet {
     bbox: Rect,
     shape: SnippetShape,
     interior: SnippetInterior,
+    // For audio snippets, the spectrogram (if it has been computed), and an image of it for
+    // drawing on the screen.
+    spectrogram: Option<Arc<Spectrogram>>,
+    spectrogram_image: Option<PietImage>,
 }
 
 impl TimelineSnippet {
@@ -416,6 +944,9 @@ impl TimelineSnippet {
     }
 
     fn fill_color(&self, data: &EditorState) -> Option<Color> {
+        if let Some(label) = data.snippet_label(self.id) {
+            return Some(label.color());
+        }
         match self.id {
             SnippetId::Draw(_) => None,
             SnippetId::Talk(_) => {
@@ -476,45 +1007,234 @@ impl TimelineSnippet {
         (0.0, 0.0)
     }
 
+    /// Converts a gain to a y coordinate at horizontal position `x`. The bottom of the snippet is
+    /// zero gain, and the top is `MAX_GAIN`.
+    fn gain_y(&self, x: f64, gain: f32) -> f64 {
+        let (y0, y1) = self.closest_y_interval(x);
+        y1 - (gain / MAX_GAIN) as f64 * (y1 - y0)
+    }
+
+    /// The inverse of `gain_y`.
+    fn y_gain(&self, x: f64, y: f64) -> f32 {
+        let (y0, y1) = self.closest_y_interval(x);
+        if y1 > y0 {
+            ((y1 - y) / (y1 - y0)) as f32 * MAX_GAIN
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns the positions of the handles of the volume envelope.
+    fn gain_handles(&self, snip: &TalkSnippet) -> Vec<Point> {
+        snip.gain()
+            .points()
+            .iter()
+            .map(|p| {
+                let x = pix_x(snip.start_time() + p.offset);
+                Point::new(x, self.gain_y(x, p.gain))
+            })
+            .collect()
+    }
+
+    /// If there is a volume envelope handle close to `pos`, returns its index.
+    fn gain_handle_at(&self, snip: &TalkSnippet, pos: Point) -> Option<usize> {
+        self.gain_handles(snip)
+            .into_iter()
+            .position(|p| p.distance(pos) <= GAIN_HANDLE_GRAB_DISTANCE)
+    }
+
+    /// Draws the volume envelope of an audio snippet.
+    fn render_gain(
+        &self,
+        ctx: &mut impl RenderContext,
+        snip: &TalkSnippet,
+        is_selected: bool,
+        scale: f64,
+    ) {
+        if snip.gain().is_empty() && !is_selected {
+            return;
+        }
+
+        // We sample the envelope every few pixels, because the height of the snippet can vary.
+        let start_x = pix_x(snip.start_time());
+        let end_x = pix_x(snip.end_time());
+        let mut path = BezPath::new();
+        let mut x = start_x;
+        path.move_to((x, self.gain_y(x, snip.gain().gain_at(TimeDiff::ZERO))));
+        while x < end_x {
+            x = (x + 4.0).min(end_x);
+            let gain = snip.gain().gain_at(width_pix(x - start_x));
+            path.line_to((x, self.gain_y(x, gain)));
+        }
+        let width = snap_width(GAIN_ENVELOPE_THICKNESS, scale);
+        ctx.stroke(&path, &GAIN_ENVELOPE_COLOR, width);
+
+        if is_selected {
+            for p in self.gain_handles(snip) {
+                ctx.fill(Circle::new(p, GAIN_HANDLE_RADIUS), &GAIN_ENVELOPE_COLOR);
+            }
+        }
+    }
+
     /// Draws the "interior" of the snippet (i.e., everything but the bounding rect).
-    fn render_interior(&self, ctx: &mut PaintCtx, snip: &Snip, height: f64) {
+    fn render_interior<R: RenderContext>(
+        &self,
+        ctx: &mut R,
+        snip: &Snip,
+        height: f64,
+        scale: f64,
+        spectrogram: Option<&R::Image>,
+    ) {
         match snip {
-            Snip::Audio(_data) => {
-                ctx.with_save(|ctx| match &self.interior {
-                    SnippetInterior::Audio(a) => {
-                        ctx.fill(&a.wave, &SNIPPET_WAVEFORM_COLOR);
-                    }
-                    _ => panic!("audio snippet should have a cached waveform"),
-                });
-            }
+            Snip::Audio(_data) => match (&self.interior, spectrogram) {
+                (_, Some(image)) => self.render_spectrogram(ctx, image),
+                (SnippetInterior::Audio(a), None) => {
+                    ctx.fill(&a.wave, &SNIPPET_WAVEFORM_COLOR);
+                }
+                _ => panic!("audio snippet should have a cached waveform"),
+            },
             Snip::Drawing(data) => {
-                let segs = match &self.interior {
-                    SnippetInterior::Drawing(s) => s,
-                    _ => panic!("drawing widget should have cached segment extents"),
-                };
-                let mut start_x = 0.0;
-                let mut last_color = &Color::BLACK;
-                for &(start, ref color) in &segs.strokes {
-                    let end_x = pix_width(start - data.start_time());
-                    let rect = Rect::from_points((start_x, 0.0), (end_x, height));
-                    ctx.fill(&rect, last_color);
-                    last_color = color;
-                    start_x = end_x;
+                // If the snippet has a label, it was already filled with the label's color.
+                // Otherwise, we show the colors of its strokes.
+                if data.label().is_none() {
+                    let segs = match &self.interior {
+                        SnippetInterior::Drawing(s) => s,
+                        _ => panic!("drawing widget should have cached segment extents"),
+                    };
+                    let mut start_x = 0.0;
+                    let mut last_color = &Color::BLACK;
+                    for &(start, ref color) in &segs.strokes {
+                        let end_x = pix_width(start - data.start_time());
+                        let rect = Rect::from_points((start_x, 0.0), (end_x, height));
+                        ctx.fill(&rect, last_color);
+                        last_color = color;
+                        start_x = end_x;
+                    }
+
+                    let last_rect =
+                        Rect::from_points((start_x, 0.0), (LAYOUT_PARAMS.end_x, height));
+                    ctx.fill(&last_rect, last_color);
                 }
 
-                let last_rect = Rect::from_points((start_x, 0.0), (LAYOUT_PARAMS.end_x, height));
-                ctx.fill(&last_rect, last_color);
+                for (start, end) in data.holds() {
+                    let x0 = pix_width(start - data.start_time());
+                    let x1 = pix_width(end - data.start_time());
+                    let rect = Rect::from_points((x0, 0.0), (x1, height));
+                    ctx.fill(&rect, &SNIPPET_HOLD_COLOR);
+                }
 
                 // Draw the lerp lines.
                 for t in snip.inner_lerp_times() {
                     let x = pix_width(t);
-                    ctx.stroke(Line::new((x, 0.0), (x, height)), &SNIPPET_STROKE_COLOR, 1.0);
+                    let line = Line::new((x, 0.0), (x, height));
+                    ctx.stroke(line, &SNIPPET_STROKE_COLOR, snap_width(1.0, scale));
                 }
             }
         }
     }
 }
 
+impl TimelineSnippet {
+    /// Draws the spectrogram of an audio snippet. The image has one column per pixel, and gets
+    /// stretched vertically to fit each row of the snippet.
+    fn render_spectrogram<R: RenderContext>(&self, ctx: &mut R, image: &R::Image) {
+        let columns = image.size().width;
+        let rows = image.size().height;
+        let x0 = match self.shape.rects.first() {
+            Some(r) => r.x0,
+            None => return,
+        };
+        for r in &self.shape.rects {
+            let start = r.x0 - x0;
+            let end = (r.x1 - x0).min(columns);
+            if end <= start {
+                continue;
+            }
+            let src = Rect::new(start, 0.0, end, rows);
+            let dst = Rect::new(start, r.y0, end, r.y1);
+            ctx.draw_image_area(image, src, dst, InterpolationMode::Bilinear);
+        }
+    }
+
+    /// Makes the image of the spectrogram, if there's a spectrogram but no image yet.
+    fn ensure_spectrogram_image(&mut self, ctx: &mut impl RenderContext<Image = PietImage>) {
+        if let (Some(spec), None) = (&self.spectrogram, &self.spectrogram_image) {
+            let image = ctx.make_image(
+                spec.columns(),
+                spec.rows(),
+                &spec.to_rgba(),
+                ImageFormat::RgbaSeparate,
+            );
+            match image {
+                Ok(image) => self.spectrogram_image = Some(image),
+                Err(e) => log::error!("failed to make spectrogram image: {}", e),
+            }
+        }
+    }
+
+    fn set_spectrogram(&mut self, spectrogram: Arc<Spectrogram>) {
+        self.spectrogram = Some(spectrogram);
+        self.spectrogram_image = None;
+    }
+
+    /// Draws the snippet, apart from the things (like hover effects) that depend on the mouse.
+    ///
+    /// This doesn't need a `PaintCtx`, so that it can also be used for drawing the timeline
+    /// off-screen.
+    fn paint_contents<R: RenderContext>(
+        &self,
+        ctx: &mut R,
+        data: &EditorState,
+        height: f64,
+        scale: f64,
+        is_selected: bool,
+        spectrogram: Option<&R::Image>,
+    ) {
+        let snippet = self.snip(data);
+        let path = self.path();
+
+        if let Some(c) = self.fill_color(data) {
+            ctx.fill(path, &c);
+        }
+        let _ = ctx.with_save(|ctx| {
+            ctx.clip(path);
+            ctx.transform(Affine::translate((pix_x(snippet.start_time()), 0.0)));
+            self.render_interior(ctx, &snippet, height, scale, spectrogram);
+            Ok(())
+        });
+        if let (Snip::Audio(snip), SnippetId::Talk(id)) = (&snippet, self.id) {
+            let _ = ctx.with_save(|ctx| {
+                ctx.clip(path);
+                let color = data.settings.ui_palette.warning_color();
+                let fill_color = color.clone().with_alpha(OVERLAP_ALPHA);
+                let hatch_width = snap_width(OVERLAP_HATCH_THICKNESS, scale);
+                for (start, end) in data.scribl.talk.overlaps(id) {
+                    let rect = Rect::new(pix_x(start), 0.0, pix_x(end), height);
+                    ctx.fill(rect, &fill_color);
+                    let _ = ctx.with_save(|ctx| {
+                        ctx.clip(rect);
+                        ctx.stroke(hatch(rect), &color, hatch_width);
+                        Ok(())
+                    });
+                }
+                Ok(())
+            });
+            self.render_gain(ctx, snip, is_selected, scale);
+        }
+
+        // Linked snippets share an outline color, and they get highlighted along with the
+        // selected snippet.
+        if let Some(group) = data.snippet_group(self.id) {
+            let width = snap_width(GROUP_STROKE_THICKNESS, scale);
+            ctx.stroke(path, &group.color(), width);
+        }
+        if is_selected || data.linked_to_selected(self.id) {
+            let width = snap_width(SNIPPET_SELECTED_STROKE_THICKNESS, scale);
+            ctx.stroke(path, &SNIPPET_SELECTED_STROKE_COLOR, width);
+        }
+    }
+}
+
 impl Widget<EditorState> for TimelineSnippet {
     fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut EditorState, _env: &Env) {
         match event {
@@ -526,6 +1246,14 @@ impl Widget<EditorState> for TimelineSnippet {
                 ctx.request_paint();
                 ctx.set_handled();
             }
+            Event::MouseDown(ev)
+                if ev.button.is_right() && self.contains(ev.pos) && data.action.is_idle() =>
+            {
+                data.selected_snippet = Some(self.id);
+                let menu = crate::menus::snippet_context_menu(ctx.window_id(), self.id, data);
+                ctx.show_context_menu(menu, ev.window_pos);
+                ctx.set_handled();
+            }
             Event::MouseUp(ev) if ev.button.is_left() => {
                 if ctx.is_active() {
                     ctx.set_active(false);
@@ -543,6 +1271,11 @@ impl Widget<EditorState> for TimelineSnippet {
             }
             Event::MouseMove(ev) => {
                 let new_hot = self.contains(ev.pos);
+                if new_hot {
+                    data.hovered_snippet = Some(self.id);
+                } else if data.hovered_snippet == Some(self.id) {
+                    data.hovered_snippet = None;
+                }
                 if self.hot != new_hot {
                     self.hot = new_hot;
                     ctx.request_paint_rect(
@@ -587,6 +1320,13 @@ impl Widget<EditorState> for TimelineSnippet {
         if old_data.selected_snippet != data.selected_snippet {
             ctx.request_paint();
         }
+
+        // Other audio snippets moving around might change how this one overlaps with them.
+        if let SnippetId::Talk(_) = self.id {
+            if !old_data.scribl.talk.same(&data.scribl.talk) {
+                ctx.request_paint();
+            }
+        }
     }
 
     fn lifecycle(
@@ -603,40 +1343,44 @@ impl Widget<EditorState> for TimelineSnippet {
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &EditorState, _env: &Env) {
-        let snippet = self.snip(data);
         let height = ctx.size().height;
         let is_selected = data.selected_snippet == Some(self.id);
         let path = self.path().clone();
-        let fill_color = self.fill_color(data);
+        let scale = device_scale(ctx.window());
+        let width = snap_width(SNIPPET_STROKE_THICKNESS, scale);
+        let selected_width = snap_width(SNIPPET_SELECTED_STROKE_THICKNESS, scale);
+        if data.settings.spectrograms {
+            self.ensure_spectrogram_image(ctx.render_ctx);
+        }
+        let spectrogram = self
+            .spectrogram_image
+            .as_ref()
+            .filter(|_| data.settings.spectrograms);
 
         ctx.with_save(|ctx| {
             let clip = ctx.region().bounding_box();
             ctx.clip(clip);
-            if let Some(c) = fill_color {
-                ctx.fill(&path, &c);
-            }
-            ctx.with_save(|ctx| {
-                ctx.clip(&path);
-                ctx.transform(Affine::translate((pix_x(snippet.start_time()), 0.0)));
-                self.render_interior(ctx, &snippet, height);
-            });
+            self.paint_contents(
+                ctx.render_ctx,
+                data,
+                height,
+                scale,
+                is_selected,
+                spectrogram,
+            );
 
-            if is_selected || (self.hot && ctx.is_active()) {
-                ctx.stroke(
-                    &path,
-                    &SNIPPET_SELECTED_STROKE_COLOR,
-                    SNIPPET_SELECTED_STROKE_THICKNESS,
-                );
+            if !is_selected && self.hot && ctx.is_active() {
+                ctx.stroke(&path, &SNIPPET_SELECTED_STROKE_COLOR, selected_width);
             }
             if self.hot {
-                ctx.stroke(&path, &SNIPPET_STROKE_COLOR, SNIPPET_STROKE_THICKNESS);
+                ctx.stroke(&path, &SNIPPET_STROKE_COLOR, width);
             }
 
             if let Some(drag_shift) = self.drag_shift {
                 ctx.paint_with_z_index(1, move |ctx| {
                     ctx.with_save(|ctx| {
                         ctx.transform(Affine::translate((pix_width(drag_shift), 0.0)));
-                        ctx.stroke(&path, &SNIPPET_STROKE_COLOR, SNIPPET_STROKE_THICKNESS);
+                        ctx.stroke(&path, &SNIPPET_STROKE_COLOR, width);
                     });
                 });
             }
@@ -660,17 +1404,162 @@ impl TimelineInner {
             .and_then(|id| self.children.get(&id).map(|w| w.widget()))
     }
 
+    /// The bounding box of a snippet's shape, if it's shown in the timeline.
+    fn snippet_bbox(&self, id: SnippetId) -> Option<Rect> {
+        self.children.get(&id).map(|w| w.widget().bbox)
+    }
+
     fn set_visible(&mut self, start_time: Time, end_time: Time) {
         self.visible_times = (start_time, end_time);
     }
+
+    /// Handles mouse events for editing the volume envelope of the selected audio snippet:
+    /// dragging a point moves it, ctrl-clicking a point removes it, and ctrl-clicking elsewhere
+    /// on the snippet adds a point. Returns true if the event was handled.
+    fn gain_event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut EditorState) -> bool {
+        match event {
+            Event::MouseDown(ev) if ev.button.is_left() && data.action.is_idle() => {
+                let id = match data.selected_snippet {
+                    Some(SnippetId::Talk(id)) => id,
+                    _ => return false,
+                };
+                let widget = match self.children.get(&SnippetId::Talk(id)) {
+                    Some(w) => w.widget(),
+                    None => return false,
+                };
+                let snip = data.scribl.talk.snippet(id);
+                if let Some(idx) = widget.gain_handle_at(snip, ev.pos) {
+                    if ev.mods.ctrl() {
+                        data.remove_gain_point(id, idx);
+                    } else {
+                        self.gain_drag = Some(GainDrag {
+                            id,
+                            idx,
+                            prev_state: data.undo_state(),
+                        });
+                        ctx.set_active(true);
+                    }
+                } else if ev.mods.ctrl() && widget.contains(ev.pos) {
+                    let offset = x_pix(ev.pos.x) - snip.start_time();
+                    let gain = widget.y_gain(ev.pos.x, ev.pos.y);
+                    data.add_gain_point(id, offset, gain);
+                } else {
+                    return false;
+                }
+                ctx.set_handled();
+                true
+            }
+            Event::MouseMove(ev) => {
+                if let Some(drag) = &self.gain_drag {
+                    if let Some(w) = self.children.get(&SnippetId::Talk(drag.id)) {
+                        let snip = data.scribl.talk.snippet(drag.id);
+                        let offset = x_pix(ev.pos.x.max(0.0)) - snip.start_time();
+                        let gain = w.widget().y_gain(ev.pos.x, ev.pos.y);
+                        let env = snip.gain().with_moved_point(drag.idx, offset, gain);
+                        // We modify the snippet directly (without adding an undo state), because
+                        // we'll add an undo state when the drag is finished.
+                        data.scribl.talk = data.scribl.talk.with_gain_envelope(drag.id, env);
+                    }
+                    ctx.set_handled();
+                    true
+                } else {
+                    false
+                }
+            }
+            Event::MouseUp(_) => {
+                if let Some(drag) = self.gain_drag.take() {
+                    ctx.set_active(false);
+                    if !drag.prev_state.audio_snippets.same(&data.scribl.talk) {
+                        data.push_undo_state(drag.prev_state, "change volume");
+                    }
+                    ctx.set_handled();
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Handles mouse events for sliding the audio within an audio snippet: alt-dragging
+    /// horizontally moves the audio relative to the start of the snippet, which stays where it is.
+    /// This is for re-syncing narration to the drawings. Returns true if the event was handled.
+    fn slip_event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut EditorState) -> bool {
+        match event {
+            Event::MouseDown(ev)
+                if ev.button.is_left() && ev.mods.alt() && data.action.is_idle() =>
+            {
+                let id = self.children.iter().find_map(|(id, w)| match id {
+                    SnippetId::Talk(id) if w.widget().contains(ev.pos) => Some(*id),
+                    _ => None,
+                });
+                let id = match id {
+                    Some(id) => id,
+                    None => return false,
+                };
+                data.selected_snippet = Some(SnippetId::Talk(id));
+                self.slip_drag = Some(SlipDrag {
+                    id,
+                    start_time: x_pix(ev.pos.x.max(0.0)),
+                    start_offset: data.scribl.talk.snippet(id).offset(),
+                    prev_state: data.undo_state(),
+                });
+                ctx.set_active(true);
+                ctx.set_handled();
+                true
+            }
+            Event::MouseMove(ev) => {
+                if let Some(drag) = &self.slip_drag {
+                    // Dragging to the right moves the audio later, which means that less of the
+                    // beginning of the recording is cut off.
+                    let shift = x_pix(ev.pos.x.max(0.0)) - drag.start_time;
+                    let offset =
+                        TimeDiff::from_micros(drag.start_offset.as_micros() - shift.as_micros());
+                    // We modify the snippet directly (without adding an undo state), because
+                    // we'll add an undo state when the drag is finished.
+                    data.scribl.talk = data.scribl.talk.with_snippet_offset(drag.id, offset);
+                    ctx.set_handled();
+                    true
+                } else {
+                    false
+                }
+            }
+            Event::MouseUp(_) => {
+                if let Some(drag) = self.slip_drag.take() {
+                    ctx.set_active(false);
+                    if !drag.prev_state.audio_snippets.same(&data.scribl.talk) {
+                        data.push_undo_state(drag.prev_state, "slide audio");
+                    }
+                    ctx.set_handled();
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        }
+    }
 }
 
 impl Widget<EditorState> for TimelineInner {
     fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut EditorState, env: &Env) {
+        if self.gain_event(ctx, event, data) || self.slip_event(ctx, event, data) {
+            return;
+        }
+
         match event {
             Event::WindowConnected => {
                 ctx.request_paint();
             }
+            Event::MouseDown(ev) if self.header_rect(self.header_ys.0).contains(ev.pos) => {
+                data.settings.drawings_collapsed = !data.settings.drawings_collapsed;
+                ctx.set_handled();
+            }
+            Event::MouseDown(ev) if self.header_rect(self.header_ys.1).contains(ev.pos) => {
+                data.settings.audio_collapsed = !data.settings.audio_collapsed;
+                ctx.set_handled();
+            }
             Event::MouseDown(ev) => {
                 let time = Time::from_micros((ev.pos.x / PIXELS_PER_USEC) as i64);
                 ctx.submit_command(cmd::WARP_TIME_TO.with(time));
@@ -710,6 +1599,23 @@ impl Widget<EditorState> for TimelineInner {
                 }
                 self.cursor_drag_scroll_speed = None;
             }
+            Event::Command(c) if c.is(cmd::SPECTROGRAM_READY) => {
+                let ready = c.get_unchecked(cmd::SPECTROGRAM_READY);
+                // If the audio changed while the spectrogram was being computed, there will be
+                // another one coming.
+                if let Some(cached) = self.spectrograms.get_mut(&ready.id) {
+                    if cached.key == ready.key {
+                        cached.spectrogram = Some(ready.spectrogram.clone());
+                        if let Some(child) = self.children.get_mut(&SnippetId::Talk(ready.id)) {
+                            child
+                                .widget_mut()
+                                .set_spectrogram(ready.spectrogram.clone());
+                            ctx.request_paint_rect(child.widget().bbox);
+                        }
+                    }
+                }
+                ctx.set_handled();
+            }
             Event::Command(c) => {
                 let x = pix_x(data.time());
                 let y_int = self.selected(data).map(|s| s.closest_y_interval(x));
@@ -738,6 +1644,12 @@ impl Widget<EditorState> for TimelineInner {
                     if id.is_some() {
                         data.selected_snippet = id;
                     }
+                } else if c.is(cmd::SELECT_SNIPPET_LEFT) {
+                    ctx.set_handled();
+                    data.select_adjacent_snippet(false);
+                } else if c.is(cmd::SELECT_SNIPPET_RIGHT) {
+                    ctx.set_handled();
+                    data.select_adjacent_snippet(true);
                 }
             }
             Event::AnimFrame(ns_elapsed) => {
@@ -767,11 +1679,25 @@ impl Widget<EditorState> for TimelineInner {
         data: &EditorState,
         env: &Env,
     ) {
-        if !data.scribl.draw.same(&old_data.scribl.draw)
+        // If the window moved to a screen with a different scale, the waveforms need to be redone.
+        let scale = device_scale(ctx.window());
+        let scale_changed = scale != self.scale;
+        self.scale = scale;
+
+        if scale_changed
+            || !data.scribl.draw.same(&old_data.scribl.draw)
             || !data.scribl.talk.same(&old_data.scribl.talk)
+            || data.settings.row_layout != old_data.settings.row_layout
+            || data.settings.drawings_collapsed != old_data.settings.drawings_collapsed
+            || data.settings.audio_collapsed != old_data.settings.audio_collapsed
         {
             ctx.request_layout();
-            self.recreate_children(&data.scribl.draw, &data.scribl.talk);
+            self.request_spectrograms(data, ctx.get_external_handle());
+            if let Some(rows) = self.recreate_children(data) {
+                if rows != data.scribl.row_hints {
+                    ctx.submit_command(cmd::SET_ROW_HINTS.with(rows));
+                }
+            }
             ctx.children_changed();
         } else {
             // Don't call update on the children if we just changed them -- we need to let
@@ -781,7 +1707,11 @@ impl Widget<EditorState> for TimelineInner {
             }
         }
 
-        if old_data.mark != data.mark {
+        if old_data.settings.spectrograms != data.settings.spectrograms {
+            self.request_spectrograms(data, ctx.get_external_handle());
+            ctx.request_paint();
+        }
+        if old_data.mark != data.mark || old_data.scribl.project_end != data.scribl.project_end {
             ctx.request_paint();
         }
         if old_data.time() != data.time() {
@@ -800,7 +1730,13 @@ impl Widget<EditorState> for TimelineInner {
     ) {
         match event {
             LifeCycle::WidgetAdded => {
-                self.recreate_children(&data.scribl.draw, &data.scribl.talk);
+                self.scale = device_scale(ctx.window());
+                self.request_spectrograms(data, ctx.get_external_handle());
+                if let Some(rows) = self.recreate_children(data) {
+                    if rows != data.scribl.row_hints {
+                        ctx.submit_command(cmd::SET_ROW_HINTS.with(rows));
+                    }
+                }
                 ctx.children_changed();
             }
             _ => {}
@@ -831,6 +1767,10 @@ impl Widget<EditorState> for TimelineInner {
         size
     }
 
+    fn id(&self) -> Option<WidgetId> {
+        Some(self.id)
+    }
+
     fn paint(&mut self, ctx: &mut PaintCtx, data: &EditorState, env: &Env) {
         // Note that the width here may well be infinite. Intersecting with the
         // paint region will prevent us from trying to fill an infinite rect.
@@ -845,7 +1785,45 @@ impl Widget<EditorState> for TimelineInner {
             }
         }
 
+        let draw_count = data.scribl.draw.snippets().count();
+        let audio_count = data.scribl.talk.snippets().count();
+        self.paint_header(
+            ctx,
+            self.header_ys.0,
+            "Drawings",
+            draw_count,
+            data.settings.drawings_collapsed,
+        );
+        self.paint_header(
+            ctx,
+            self.header_ys.1,
+            "Audio",
+            audio_count,
+            data.settings.audio_collapsed,
+        );
+
         let cursor_x = pix_x(data.time());
+        let thin = snap_width(1.0, self.scale);
+        let thick = snap_width(CURSOR_THICKNESS, self.scale);
+
+        // Darken everything after the end of the animation. If the end was set explicitly, it
+        // gets a solid line; otherwise, it's just wherever the last snippet ends.
+        let end_x = pix_x(data.scribl.end_time());
+        if end_x < rect.x1 {
+            let past_end = Rect::new(end_x.max(rect.x0), rect.y0, rect.x1, rect.y1);
+            ctx.fill(past_end, &PAST_END_FILL_COLOR);
+        }
+        let end_line = Line::new((end_x, 0.0), (end_x, size.height));
+        if data.scribl.project_end.is_some() {
+            ctx.stroke(end_line, &END_LINE_COLOR, thick);
+        } else {
+            ctx.stroke_styled(
+                end_line,
+                &END_LINE_COLOR,
+                thin,
+                &StrokeStyle::new().dash_pattern(&[4.0, 4.0]),
+            );
+        }
 
         // Draw the mark.
         if let Some(mark_time) = data.mark {
@@ -853,18 +1831,82 @@ impl Widget<EditorState> for TimelineInner {
             let rect = Rect::new(cursor_x, 0.0, mark_x, size.height);
             ctx.fill(rect, &SELECTION_FILL_COLOR);
             let mark_line = Line::new((mark_x, 0.0), (mark_x, size.height));
-            ctx.stroke(mark_line, &Color::BLACK, CURSOR_THICKNESS);
+            ctx.stroke(mark_line, &Color::BLACK, thick);
             ctx.stroke_styled(
                 mark_line,
                 &Color::WHITE,
-                1.0,
+                thin,
                 &StrokeStyle::new().dash_pattern(&[2.0, 2.0]),
             );
         }
 
         let cursor_line = Line::new((cursor_x, 0.0), (cursor_x, size.height));
         // Draw a black "background" on the cursor for extra contrast.
-        ctx.stroke(cursor_line, &Color::BLACK, CURSOR_THICKNESS);
-        ctx.stroke(cursor_line, &Color::WHITE, 1.0);
+        ctx.stroke(cursor_line, &Color::BLACK, thick);
+        ctx.stroke(cursor_line, &Color::WHITE, thin);
+    }
+}
+
+/// Draws the whole timeline (snippets, waveforms, and markers) into an image, for documentation
+/// or for reviewing the structure of an animation.
+///
+/// A long animation would make a very wide image, so the timeline gets broken into rows, each
+/// with its own ruler.
+pub fn render_summary(data: &EditorState, env: &Env) -> Result<ImageBuf> {
+    let mut inner = TimelineInner::default();
+    inner.recreate_children(data);
+
+    let total_width = pix_x(data.scribl.end_time()).ceil().max(1.0);
+    let rows = (total_width / SUMMARY_ROW_WIDTH).ceil() as usize;
+    let width = total_width.min(SUMMARY_ROW_WIDTH);
+    let row_height = RULER_HEIGHT + inner.height;
+    let height = rows as f64 * (row_height + SUMMARY_ROW_SPACING) - SUMMARY_ROW_SPACING;
+    let bg = env.get(druid::theme::BACKGROUND_DARK);
+
+    let mut device = Device::new().map_err(|e| anyhow!("failed to get device: {}", e))?;
+    let mut bitmap = device
+        .bitmap_target(width as usize, height.ceil() as usize, 1.0)
+        .map_err(|e| anyhow!("failed to make bitmap: {}", e))?;
+    {
+        let mut ctx = bitmap.render_context();
+        ctx.clear(None, env.get(druid::theme::WINDOW_BACKGROUND_COLOR));
+        for row in 0..rows {
+            let offset = row as f64 * SUMMARY_ROW_WIDTH;
+            let y = row as f64 * (row_height + SUMMARY_ROW_SPACING);
+            let ruler = Rect::new(0.0, y, width, y + RULER_HEIGHT);
+            paint_ruler(&mut ctx, data, ruler, offset, 1.0);
+
+            ctx.with_save(|ctx| {
+                ctx.clip(Rect::new(0.0, y + RULER_HEIGHT, width, y + row_height));
+                ctx.transform(Affine::translate((-offset, y + RULER_HEIGHT)));
+                ctx.fill(Rect::new(offset, 0.0, offset + width, inner.height), &bg);
+                for child in inner.children.values() {
+                    child
+                        .widget()
+                        .paint_contents(ctx, data, inner.height, 1.0, false, None);
+                }
+                Ok(())
+            })
+            .map_err(|e| anyhow!("failed to render: {}", e))?;
+        }
+        ctx.finish()
+            .map_err(|e| anyhow!("failed to finish context: {}", e))?;
     }
+    bitmap
+        .to_image_buf(ImageFormat::RgbaPremul)
+        .map_err(|e| anyhow!("failed to get pixels: {}", e))
+}
+
+/// Draws the whole timeline into a PNG file.
+pub fn save_summary(data: &EditorState, env: &Env, path: &Path) -> Result<()> {
+    let image = render_summary(data, env)?;
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, image.width() as u32, image.height() as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    // The background is opaque, so it doesn't matter that the pixels are premultiplied.
+    encoder
+        .write_header()?
+        .write_image_data(image.raw_pixels())?;
+    Ok(())
 }
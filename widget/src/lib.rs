@@ -1,4 +1,4 @@
-use druid::{Color, Env, Key};
+use druid::{Color, Env, Key, Selector};
 
 pub const BUTTON_ICON_PADDING: Key<f64> = Key::new("ink.scribl.widget.button-icon-padding");
 pub const BUTTON_ICON_DISABLED_COLOR: Key<Color> =
@@ -12,6 +12,18 @@ pub const BUTTON_ICON_HOT_STROKE_THICKNESS: Key<f64> =
     Key::new("ink.scribl.widget.button-icon-hot-stroke-thickness");
 pub const BUTTON_ICON_HOT_STROKE_COLOR: Key<Color> =
     Key::new("ink.scribl.widget.button-icon-hot-stroke-color");
+/// The thickness of the bar along the bottom of toggle buttons that are switched on. The bar is
+/// there so that the color of the icon isn't the only sign that a button is on.
+pub const BUTTON_ICON_SELECTED_MARK_THICKNESS: Key<f64> =
+    Key::new("ink.scribl.widget.button-icon-selected-mark-thickness");
+
+/// The color of the ring around the control that has keyboard focus.
+pub const FOCUS_RING_COLOR: Key<Color> = Key::new("ink.scribl.widget.focus-ring-color");
+pub const FOCUS_RING_THICKNESS: Key<f64> = Key::new("ink.scribl.widget.focus-ring-thickness");
+
+/// When a control gets keyboard focus, it sends this command with a description of itself (like
+/// "Draw: off"), so that the description can be shown or read out.
+pub const ANNOUNCE: Selector<String> = Selector::new("ink.scribl.widget.announce");
 
 pub const DROP_SHADOW_RADIUS: Key<f64> = Key::new("ink.scribl.widget.drop-shadow-radius");
 pub const DROP_SHADOW_COLOR: Key<Color> = Key::new("ink.scribl.widget.drop-shadow-color");
@@ -34,6 +46,10 @@ pub fn configure_env(e: &mut Env) {
     e.set(BUTTON_ICON_BUTTON_COLOR, Color::rgb8(0xA0, 0xA0, 0xA0));
     e.set(BUTTON_ICON_HOT_STROKE_THICKNESS, 2.0);
     e.set(BUTTON_ICON_HOT_STROKE_COLOR, UI_DARK_GREEN);
+    e.set(BUTTON_ICON_SELECTED_MARK_THICKNESS, 3.0);
+
+    e.set(FOCUS_RING_COLOR, UI_LIGHT_BLUE);
+    e.set(FOCUS_RING_THICKNESS, 2.0);
 
     e.set(DROP_SHADOW_RADIUS, 8.0);
     e.set(DROP_SHADOW_COLOR, Color::rgb8(0x00, 0x00, 0x00));
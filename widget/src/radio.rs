@@ -71,10 +71,12 @@ impl<T: Data> RadioGroup<T> {
         Self::new_from_icons(Axis::Vertical, padding, children)
     }
 
-    /// Creates a group of buttons in a column, with custom widgets on the buttons.
-    pub fn column<I: IntoIterator<Item = (Box<dyn Widget<T>>, T)>>(children: I) -> Self {
+    fn new_from_widgets<I: IntoIterator<Item = (Box<dyn Widget<T>>, T)>>(
+        axis: Axis,
+        children: I,
+    ) -> Self {
         Self::new(
-            Axis::Vertical,
+            axis,
             children.into_iter().map(|(child, variant)| {
                 let variant_clone = variant.clone();
                 ShadowlessToggleButton::from_widget(
@@ -88,6 +90,25 @@ impl<T: Data> RadioGroup<T> {
             }),
         )
     }
+
+    /// Creates a group of buttons in a row, with custom widgets on the buttons.
+    pub fn row<I: IntoIterator<Item = (Box<dyn Widget<T>>, T)>>(children: I) -> Self {
+        Self::new_from_widgets(Axis::Horizontal, children)
+    }
+
+    /// Creates a group of buttons in a column, with custom widgets on the buttons.
+    pub fn column<I: IntoIterator<Item = (Box<dyn Widget<T>>, T)>>(children: I) -> Self {
+        Self::new_from_widgets(Axis::Vertical, children)
+    }
+
+    /// Sets the names that get announced when the buttons get keyboard focus, in the same order
+    /// as the buttons.
+    pub fn with_names<I: IntoIterator<Item = String>>(mut self, names: I) -> Self {
+        for (c, name) in self.children.iter_mut().zip(names) {
+            c.button.widget_mut().set_name(name);
+        }
+        self
+    }
 }
 
 impl<T: Data> Widget<T> for RadioGroup<T> {
@@ -1,7 +1,7 @@
 use druid::kurbo::Vec2;
 use druid::widget::prelude::*;
 use druid::widget::LabelText;
-use druid::{theme, Data, Insets, Point, RenderContext, Size, WidgetExt, WidgetPod};
+use druid::{theme, Data, Insets, KbKey, Point, Rect, RenderContext, Size, WidgetExt, WidgetPod};
 
 use crate::{Icon, Shadow, TooltipExt};
 
@@ -17,6 +17,8 @@ pub struct ShadowlessToggleButton<T> {
     toggle_state: Box<dyn Fn(&T) -> bool + 'static>,
     toggle_action: Box<dyn Fn(&mut EventCtx, &mut T, &Env) + 'static>,
     untoggle_action: Box<dyn Fn(&mut EventCtx, &mut T, &Env) + 'static>,
+    // The name that gets announced when this button gets keyboard focus.
+    name: Option<LabelText<T>>,
 }
 
 pub struct ToggleButton<T> {
@@ -34,11 +36,13 @@ impl<T: Data> ShadowlessToggleButton<T> {
         untoggle_action: impl Fn(&mut EventCtx, &mut T, &Env) + 'static,
     ) -> ShadowlessToggleButton<T> {
         let lens = crate::lens::read_map(toggle_state.clone());
+        // The tooltip doubles as the name of the button.
+        let name = tooltip.into();
         let inner = icon
             .to_widget()
             .lens(lens)
             .padding(padding)
-            .tooltip(tooltip);
+            .tooltip(name.clone());
 
         ShadowlessToggleButton {
             inner: WidgetPod::new(Box::new(inner)),
@@ -47,6 +51,7 @@ impl<T: Data> ShadowlessToggleButton<T> {
             toggle_state: Box::new(toggle_state),
             toggle_action: Box::new(toggle_action),
             untoggle_action: Box::new(untoggle_action),
+            name: Some(name),
         }
     }
 
@@ -63,6 +68,33 @@ impl<T: Data> ShadowlessToggleButton<T> {
             toggle_state: Box::new(toggle_state),
             toggle_action: Box::new(toggle_action),
             untoggle_action: Box::new(untoggle_action),
+            name: None,
+        }
+    }
+
+    /// Sets the name that gets announced when this button gets keyboard focus. Buttons made with
+    /// `from_icon` use their tooltip by default.
+    pub fn set_name(&mut self, name: impl Into<LabelText<T>>) {
+        self.name = Some(name.into());
+    }
+
+    fn activate(&mut self, ctx: &mut EventCtx, data: &mut T, env: &Env) {
+        if (self.toggle_state)(data) {
+            (self.untoggle_action)(ctx, data, env)
+        } else {
+            (self.toggle_action)(ctx, data, env)
+        }
+    }
+
+    fn announce(&mut self, ctx: &mut LifeCycleCtx, data: &T, env: &Env) {
+        if let Some(name) = &mut self.name {
+            name.resolve(data, env);
+            let state = if (self.toggle_state)(data) {
+                "on"
+            } else {
+                "off"
+            };
+            ctx.submit_command(crate::ANNOUNCE.with(format!("{}: {}", name.display_text(), state)));
         }
     }
 
@@ -130,18 +162,31 @@ impl<T: Data> Widget<T> for ShadowlessToggleButton<T> {
                 if ctx.is_active() {
                     ctx.set_active(false);
                     ctx.request_paint();
-                    let state = (self.toggle_state)(data);
                     if ctx.is_hot() {
-                        if state {
-                            (self.untoggle_action)(ctx, data, env)
-                        } else {
-                            (self.toggle_action)(ctx, data, env)
-                        }
+                        self.activate(ctx, data, env);
                     }
                     self.down = (self.toggle_state)(data);
                 }
                 ctx.set_handled();
             }
+            // With keyboard focus, Tab moves between buttons and Space or Enter presses them.
+            Event::KeyDown(ev) if ctx.is_focused() => match &ev.key {
+                KbKey::Tab => {
+                    if ev.mods.shift() {
+                        ctx.focus_prev();
+                    } else {
+                        ctx.focus_next();
+                    }
+                    ctx.set_handled();
+                }
+                key if *key == KbKey::Enter || *key == KbKey::Character(" ".to_owned()) => {
+                    self.activate(ctx, data, env);
+                    self.down = (self.toggle_state)(data);
+                    ctx.request_paint();
+                    ctx.set_handled();
+                }
+                _ => {}
+            },
             _ => {}
         }
         self.inner.event(ctx, event, data, env);
@@ -155,6 +200,13 @@ impl<T: Data> Widget<T> for ShadowlessToggleButton<T> {
             }
             LifeCycle::WidgetAdded => {
                 self.down = (self.toggle_state)(data) || (ctx.is_active() && ctx.is_hot());
+                ctx.register_for_focus();
+            }
+            LifeCycle::FocusChanged(focused) => {
+                if *focused {
+                    self.announce(ctx, data, env);
+                }
+                ctx.request_paint();
             }
             _ => {}
         }
@@ -191,6 +243,16 @@ impl<T: Data> Widget<T> for ShadowlessToggleButton<T> {
 
         ctx.fill(button_rect, &button_color);
         self.inner.paint(ctx, data, env);
+        if (self.toggle_state)(data) {
+            let thickness = env.get(crate::BUTTON_ICON_SELECTED_MARK_THICKNESS);
+            let size = ctx.size();
+            let mark = Rect::new(0.0, size.height - thickness, size.width, size.height);
+            let mark_color = env.get(crate::BUTTON_ICON_SELECTED_COLOR);
+            ctx.with_save(|ctx| {
+                ctx.clip(button_rect);
+                ctx.fill(mark, &mark_color);
+            });
+        }
         if ctx.is_hot() {
             let rect = ctx
                 .size()
@@ -199,6 +261,15 @@ impl<T: Data> Widget<T> for ShadowlessToggleButton<T> {
                 .to_rounded_rect(env.get(theme::BUTTON_BORDER_RADIUS));
             ctx.stroke(rect, &stroke_color, stroke_thickness);
         }
+        if ctx.is_focused() {
+            let thickness = env.get(crate::FOCUS_RING_THICKNESS);
+            let rect = ctx
+                .size()
+                .to_rect()
+                .inset(-thickness * 1.5)
+                .to_rounded_rect(env.get(theme::BUTTON_BORDER_RADIUS));
+            ctx.stroke(rect, &env.get(crate::FOCUS_RING_COLOR), thickness);
+        }
 
         if self.is_down() {
             ctx.with_save(|ctx| {
@@ -71,23 +71,30 @@ impl<T: Data, W: Widget<T>> Controller<T, W> for TooltipController<T> {
                     let elapsed = Instant::now().duration_since(last_mouse_move);
                     if elapsed > TOOLTIP_DELAY_CHECK {
                         self.text.resolve(data, env);
-                        let win_id = ctx.new_sub_window(
-                            WindowConfig::default()
-                                .show_titlebar(false)
-                                .window_size_policy(WindowSizePolicy::Content)
-                                .set_level(WindowLevel::Tooltip(ctx.window().clone()))
-                                .set_position(last_mouse_pos + TOOLTIP_OFFSET),
-                            // FIXME: we'd like to use the actual label text instead of
-                            // resolving, but LabelText isn't Clone
-                            Label::new(self.text.display_text())
-                                .border(TOOLTIP_BORDER_COLOR, TOOLTIP_BORDER_WIDTH)
-                                .on_monitor(ctx.window()),
-                            data.clone(),
-                            env.clone(),
-                        );
-                        TooltipState::Showing {
-                            id: win_id,
-                            last_mouse_pos,
+                        if self.text.display_text().is_empty() {
+                            // Dynamic tooltips might only have text for some parts of the
+                            // widget. There's nothing to show right now, so try again when the
+                            // mouse moves.
+                            TooltipState::Off
+                        } else {
+                            let win_id = ctx.new_sub_window(
+                                WindowConfig::default()
+                                    .show_titlebar(false)
+                                    .window_size_policy(WindowSizePolicy::Content)
+                                    .set_level(WindowLevel::Tooltip(ctx.window().clone()))
+                                    .set_position(last_mouse_pos + TOOLTIP_OFFSET),
+                                // FIXME: we'd like to use the actual label text instead of
+                                // resolving, but LabelText isn't Clone
+                                Label::new(self.text.display_text())
+                                    .border(TOOLTIP_BORDER_COLOR, TOOLTIP_BORDER_WIDTH)
+                                    .on_monitor(ctx.window()),
+                                data.clone(),
+                                env.clone(),
+                            );
+                            TooltipState::Showing {
+                                id: win_id,
+                                last_mouse_pos,
+                            }
                         }
                     } else {
                         TooltipState::Waiting {